@@ -4,8 +4,8 @@ use crate::program::{
 };
 use crate::{
     Assign, Element, Exposed, Expression, FunctionArgument, FunctionDeclaration,
-    FunctionDefinition, FunctionSignature, FunctionType, ModuleTraitDefinition, Scope, Statement,
-    TraitDefinition,
+    FunctionDefinition, FunctionSignature, FunctionType, MacroDefinition, ModuleTraitDefinition,
+    Scope, SelectArm, Statement, TraitDefinition,
 };
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
@@ -33,8 +33,9 @@ impl ToTokens for FunctionExpression {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let t = match self {
             FunctionExpression::FunctionCall(name, args) => {
+                let name = name.as_str();
                 quote! {
-                    FunctionExpression::FunctionCall(#name.to_string(), #args)
+                    FunctionExpression::FunctionCall(#name.into(), #args)
                 }
             }
             FunctionExpression::TypeFunctionCall(ty, name, args) => {
@@ -92,9 +93,10 @@ impl ToTokens for Expression {
                     Expression::Map(vec![#(#values)*])
                 }
             }
-            Expression::Identifier(i) => {
+            Expression::Identifier(i, _) => {
+                let i = i.as_str();
                 quote! {
-                    Expression::Identifier(#i.to_string())
+                    Expression::identifier(#i)
                 }
             }
             Expression::BinExp(lhs, op, rhs) => {
@@ -124,9 +126,18 @@ impl ToTokens for Expression {
                     Expression::Cast(#e, #t)
                 }
             }
-            Expression::Symbol(s) => quote! {
-                Expression::Symbol(#s.to_string())
-            },
+            Expression::Annotated(e, t) => {
+                let e = boxed(e);
+                quote! {
+                    Expression::Annotated(#e, #t)
+                }
+            }
+            Expression::Symbol(s) => {
+                let s = s.as_str();
+                quote! {
+                    Expression::Symbol(#s.into())
+                }
+            }
             Expression::If {
                 condition,
                 then,
@@ -249,6 +260,12 @@ impl ToTokens for Expression {
                     Expression::Try(#b)
                 }
             }
+            Expression::Propagate(b) => {
+                let b = boxed(b);
+                quote! {
+                    Expression::Propagate(#b)
+                }
+            }
             Expression::Catch { base, var, catch } => {
                 let b = boxed(base);
                 let v = option(var);
@@ -260,11 +277,76 @@ impl ToTokens for Expression {
                     }
                 }
             }
+            Expression::With {
+                module,
+                function,
+                value,
+                body,
+            } => {
+                let value = boxed(value);
+                quote! {
+                    Expression::With {
+                        module: #module.to_string(),
+                        function: #function.to_string(),
+                        value: #value,
+                        body: #body,
+                    }
+                }
+            }
+            Expression::Select { arms, after } => {
+                let arms = csv_vec(arms);
+                let after = match after {
+                    None => quote! { None },
+                    Some((timeout, body)) => {
+                        let timeout = boxed(timeout);
+                        quote! { Some((#timeout, #body)) }
+                    }
+                };
+                quote! {
+                    Expression::Select {
+                        arms: #arms,
+                        after: #after,
+                    }
+                }
+            }
+            Expression::Comptime(s) => {
+                quote! {
+                    Expression::Comptime(#s)
+                }
+            }
+            Expression::Lazy(s) => {
+                quote! {
+                    Expression::Lazy(#s)
+                }
+            }
+            Expression::Unquote(name) => {
+                quote! {
+                    Expression::Unquote(#name.to_string())
+                }
+            }
         };
         tokens.extend(t)
     }
 }
 
+impl ToTokens for SelectArm {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let SelectArm {
+            source,
+            binding,
+            body,
+        } = self;
+        let binding = option(binding);
+        tokens.extend(quote! {
+            SelectArm {
+                source: #source,
+                binding: #binding.map(|s| s.to_string()),
+                body: #body,
+            }
+        })
+    }
+}
+
 impl ToTokens for RigzArguments {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let t = match self {
@@ -305,15 +387,20 @@ impl ToTokens for Assign {
         let t = match self {
             Assign::This => quote! { Assign::This },
             Assign::Identifier(name, mutable) => {
-                quote! { Assign::Identifier(#name.to_string(), #mutable) }
+                let name = name.as_str();
+                quote! { Assign::Identifier(#name.into(), #mutable) }
             }
             Assign::TypedIdentifier(n, mutable, rt) => {
-                quote! { Assign::TypedIdentifier(#n.to_string(), #mutable, #rt) }
+                let n = n.as_str();
+                quote! { Assign::TypedIdentifier(#n.into(), #mutable, #rt) }
             }
             Assign::Tuple(t) => {
                 let values: Vec<_> = t
                     .iter()
-                    .map(|(id, mutable)| quote! { (#id.to_string(), #mutable), })
+                    .map(|(id, mutable)| {
+                        let id = id.as_str();
+                        quote! { (#id.into(), #mutable), }
+                    })
                     .collect();
                 quote! { Assign::Tuple(vec![#(#values)*]) }
             }
@@ -342,6 +429,8 @@ impl ToTokens for ImportValue {
             ImportValue::TypeValue(s) => quote! {ImportValue::TypeValue(#s.to_string())},
             ImportValue::FilePath(s) => quote! {ImportValue::FilePath(#s.to_string())},
             ImportValue::UrlPath(s) => quote! {ImportValue::UrlPath(#s.to_string())},
+            ImportValue::Lazy(i) => quote! {ImportValue::Lazy(Box::new(#i))},
+            ImportValue::Embedded(i, s) => quote! {ImportValue::Embedded(Box::new(#i), #s)},
         };
         tokens.extend(t)
     }
@@ -359,11 +448,12 @@ impl ToTokens for Exposed {
 
 impl ToTokens for Scope {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let Scope { elements } = self;
+        let Scope { elements, .. } = self;
         let elements = csv_vec(elements);
         tokens.extend(quote! {
             Scope {
-                elements: #elements
+                elements: #elements,
+                lines: vec![],
             }
         })
     }
@@ -372,11 +462,16 @@ impl ToTokens for Scope {
 impl ToTokens for Statement {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let t = match self {
-            Statement::Assignment { lhs, expression } => {
+            Statement::Assignment {
+                lhs,
+                expression,
+                shadow,
+            } => {
                 quote! {
                     Statement::Assignment {
                         lhs: #lhs,
-                        expression: #expression
+                        expression: #expression,
+                        shadow: #shadow
                     }
                 }
             }
@@ -437,11 +532,34 @@ impl ToTokens for Statement {
                     Statement::ObjectDefinition(#o)
                 }
             }
+            Statement::Macro(m) => {
+                quote! {
+                    Statement::Macro(#m)
+                }
+            }
         };
         tokens.extend(t)
     }
 }
 
+impl ToTokens for MacroDefinition {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let MacroDefinition {
+            name,
+            arguments,
+            body,
+        } = self;
+        let arguments = arguments.iter().map(|a| quote! { #a.to_string() });
+        tokens.extend(quote! {
+            MacroDefinition {
+                name: #name.to_string(),
+                arguments: vec![#(#arguments),*],
+                body: #body,
+            }
+        })
+    }
+}
+
 impl ToTokens for FunctionDefinition {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let FunctionDefinition {
@@ -0,0 +1,433 @@
+use crate::token::{TokenKind, TokenValue, BYTE_ORDER_MARK};
+use crate::{
+    parse, Element, ImportPath, ImportResolver, ParserOptions, Statement, ValidationError,
+};
+use logos::Logos;
+use rigz_core::Lifecycle;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+
+/// Embeds the file & url imports of `entry` and removes `@test` functions, the result runs without any files
+/// besides itself. Each import is embedded where it's first imported as `import "<import>" do ... end`, the block
+/// runs in its own scope like the file would and lazy imports stay lazy. Later imports of the same file are kept
+/// as plain imports, the runtime doesn't run them again.
+///
+/// Relative imports are joined with the directory of the file importing them, starting from
+/// `options.current_directory` or the directory of `entry` when it isn't set. Relative imports within url imports
+/// are joined with that starting directory. Imports, including ones within functions, must be on their own line.
+pub fn bundle(
+    entry: &Path,
+    options: &ParserOptions,
+    resolver: &dyn ImportResolver,
+) -> Result<String, ValidationError> {
+    let mut options = options.clone();
+    if options.current_directory.is_none() {
+        options.current_directory = entry.parent().map(|p| p.to_path_buf());
+    }
+    let path = ImportPath::File(entry.to_path_buf());
+    let resolved = resolver.resolve(&path, &options)?;
+    let mut bundler = Bundler {
+        options,
+        resolver,
+        embedding: HashSet::from([resolved.key]),
+        embedded: HashSet::new(),
+    };
+    let mut out = String::new();
+    bundler.source(
+        &resolved.source,
+        &entry.display().to_string(),
+        Path::new(""),
+        &mut out,
+    )?;
+    Ok(out)
+}
+
+struct Bundler<'r> {
+    options: ParserOptions,
+    resolver: &'r dyn ImportResolver,
+    /// keys of the entry & the files being embedded, importing one of them again is a cycle
+    embedding: HashSet<String>,
+    embedded: HashSet<String>,
+}
+
+enum Action {
+    Remove,
+    Import(PathImport),
+}
+
+/// `import [lazy] "<path>"`, `string` is the byte range of the path literal
+struct PathImport {
+    line: usize,
+    string: Range<usize>,
+    path: String,
+    own_line: bool,
+}
+
+impl Bundler<'_> {
+    /// `dir` is the directory of the file relative to the entry's, imports are labeled relative to the entry
+    fn source(
+        &mut self,
+        source: &str,
+        name: &str,
+        dir: &Path,
+        out: &mut String,
+    ) -> Result<(), ValidationError> {
+        let source = source.strip_prefix(BYTE_ORDER_MARK).unwrap_or(source);
+        let program = parse(source, self.options.clone())
+            .map_err(|e| ValidationError::InvalidImport(format!("Failed to parse {name} - {e}")))?;
+        let lines: Vec<&str> = source.split('\n').collect();
+
+        // (first line, last line, action), 1 based
+        let mut actions = vec![];
+        for (index, element) in program.elements.iter().enumerate() {
+            match element {
+                Element::Statement(Statement::FunctionDefinition(f))
+                    if f.lifecycle.as_ref().is_some_and(is_test) => {}
+                _ => continue,
+            }
+            let start = program.lines[index];
+            let shared = |i: usize| program.lines.get(i) == Some(&start);
+            if shared(index + 1) || (index > 0 && shared(index - 1)) {
+                return Err(ValidationError::InvalidImport(format!(
+                    "{name}:{start} - tests must be on their own line to be bundled"
+                )));
+            }
+            let next = program
+                .lines
+                .get(index + 1)
+                .map_or(lines.len(), |next| next - 1);
+            // comments & blank lines before the next element are kept
+            let end = (start..=next)
+                .rev()
+                .find(|l| {
+                    let line = lines[l - 1].trim();
+                    !line.is_empty() && !line.starts_with('#')
+                })
+                .unwrap_or(start);
+            actions.push((start, end, Action::Remove));
+        }
+
+        // imports within removed tests aren't bundled
+        let removed: Vec<_> = actions.iter().map(|(s, e, _)| *s..=*e).collect();
+        for import in path_imports(source) {
+            if removed.iter().any(|r| r.contains(&import.line)) {
+                continue;
+            }
+            if !import.own_line {
+                return Err(ValidationError::InvalidImport(format!(
+                    "{name}:{} - imports must be on their own line to be bundled",
+                    import.line
+                )));
+            }
+            actions.push((import.line, import.line, Action::Import(import)));
+        }
+        actions.sort_by_key(|(start, _, _)| *start);
+
+        let mut line = 1;
+        for (start, end, action) in actions {
+            let mut before = &lines[line - 1..start - 1];
+            match action {
+                Action::Import(import) => {
+                    push_lines(out, before);
+                    let offset = source[..import.string.start]
+                        .rfind('\n')
+                        .map_or(0, |n| n + 1);
+                    self.import(lines[start - 1], import, offset, dir, out)?;
+                }
+                Action::Remove => {
+                    // blank lines separating the removed test
+                    while let [rest @ .., last] = before {
+                        if !last.trim().is_empty() {
+                            break;
+                        }
+                        before = rest;
+                    }
+                    push_lines(out, before);
+                }
+            }
+            line = end + 1;
+        }
+        push_lines(out, &lines[line - 1..]);
+        Ok(())
+    }
+
+    /// `line` starts at `offset` in the source of the importing file
+    fn import(
+        &mut self,
+        line: &str,
+        import: PathImport,
+        offset: usize,
+        dir: &Path,
+        out: &mut String,
+    ) -> Result<(), ValidationError> {
+        let (label, path, inner) = if import.path.starts_with("http") {
+            let url = import.path;
+            (url.clone(), ImportPath::Url(url), PathBuf::new())
+        } else {
+            let label = normalize(&dir.join(&import.path));
+            let path = match &self.options.current_directory {
+                Some(current) => current.join(&label),
+                None => label.clone(),
+            };
+            let inner = label.parent().map(Path::to_path_buf).unwrap_or_default();
+            let label = label.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+            (label, ImportPath::File(path), inner)
+        };
+        let string = import.string.start - offset..import.string.end - offset;
+        let (before, after) = (&line[..string.start], &line[string.end..]);
+
+        let resolved = self.resolver.resolve(&path, &self.options)?;
+        if self.embedding.contains(&resolved.key) {
+            // a cycle, the file is already embedded around this import
+            return Ok(());
+        }
+        if self.embedded.contains(&resolved.key) {
+            out.push_str(&format!("{before}{label:?}{after}\n"));
+            return Ok(());
+        }
+
+        self.embedding.insert(resolved.key.clone());
+        let mut embedded = String::new();
+        self.source(&resolved.source, &label, &inner, &mut embedded)?;
+        self.embedding.remove(&resolved.key);
+        self.embedded.insert(resolved.key);
+
+        let indent = &line[..line.len() - line.trim_start().len()];
+        out.push_str(&format!("{before}{label:?} do{after}\n"));
+        for embedded in embedded.trim_end().split('\n') {
+            if !embedded.trim().is_empty() {
+                out.push_str(indent);
+                out.push_str("  ");
+                out.push_str(embedded);
+            }
+            out.push('\n');
+        }
+        out.push_str(indent);
+        out.push_str("end\n");
+        Ok(())
+    }
+}
+
+fn is_test(lifecycle: &Lifecycle) -> bool {
+    match lifecycle {
+        Lifecycle::Test(_) => true,
+        Lifecycle::Composite(all) => all.iter().any(is_test),
+        _ => false,
+    }
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Removes `.` & `..` without reading the filesystem, leading `..` are kept
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// `import "..."` anywhere in the source, including within functions. Imports that are already embedded are kept.
+fn path_imports(source: &str) -> Vec<PathImport> {
+    let newlines: Vec<usize> = source.match_indices('\n').map(|(i, _)| i).collect();
+    let line = |offset: usize| newlines.partition_point(|n| *n < offset) + 1;
+    let mut imports = vec![];
+    // whether `import` is the first token of its line
+    let mut import: Option<bool> = None;
+    let mut pending: Option<PathImport> = None;
+    let mut previous = TokenKind::Newline;
+    for (token, span) in TokenKind::lexer(source).spanned() {
+        let Ok(token) = token else {
+            import = None;
+            pending = None;
+            continue;
+        };
+        if let Some(mut p) = pending.take() {
+            if token != TokenKind::Do {
+                p.own_line &= matches!(token, TokenKind::Newline | TokenKind::Comment);
+                imports.push(p);
+            }
+        }
+        match token {
+            TokenKind::Import => import = Some(previous == TokenKind::Newline),
+            TokenKind::Identifier("lazy") if import.is_some() => {}
+            TokenKind::Value(TokenValue::String(path)) if import.is_some() => {
+                pending = Some(PathImport {
+                    line: line(span.start),
+                    string: span,
+                    path: path.to_string(),
+                    own_line: import.take().unwrap(),
+                });
+            }
+            _ => import = None,
+        }
+        previous = token;
+    }
+    imports.extend(pending);
+    imports
+}
+
+#[cfg(test)]
+pub mod bundle_tests {
+    use crate::{
+        bundle, ImportPath, ImportResolver, ParserOptions, ResolvedImport, ValidationError,
+    };
+    use std::path::Path;
+    use wasm_bindgen_test::*;
+
+    #[derive(Debug)]
+    struct Files(Vec<(&'static str, &'static str)>);
+
+    impl ImportResolver for Files {
+        fn resolve(
+            &self,
+            path: &ImportPath,
+            _options: &ParserOptions,
+        ) -> Result<ResolvedImport, ValidationError> {
+            let ImportPath::File(path) = path else {
+                return Err(ValidationError::InvalidImport(format!("{path:?}")));
+            };
+            let path = path.strip_prefix("/app").unwrap_or(path);
+            self.0
+                .iter()
+                .find(|(p, _)| Path::new(p) == path)
+                .map(|(p, source)| ResolvedImport {
+                    key: p.to_string(),
+                    source: source.to_string(),
+                })
+                .ok_or_else(|| ValidationError::InvalidImport(format!("{path:?} not found")))
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn embeds_imports_and_strips_tests() {
+        let files = Files(vec![
+            (
+                "main.rg",
+                "import \"math.rg\"\nimport \"math.rg\"\n\n@test\nfn test_main\n  assert_eq 1, 1\nend\n\n# result\nquadruple 2",
+            ),
+            (
+                "math.rg",
+                "import \"util.rg\"\nfn quadruple(a) = double double a\n\n@test\nfn test_math = assert_eq 1, 1",
+            ),
+            ("util.rg", "fn double(a) = a * 2"),
+        ]);
+        let bundled = bundle(Path::new("/app/main.rg"), &ParserOptions::default(), &files);
+        assert_eq!(
+            bundled,
+            Ok(r#"import "math.rg" do
+  import "util.rg" do
+    fn double(a) = a * 2
+  end
+  fn quadruple(a) = double double a
+end
+import "math.rg"
+
+# result
+quadruple 2
+"#
+            .to_string())
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn keeps_lazy_imports_lazy() {
+        let files = Files(vec![
+            ("main.rg", "import lazy \"util.rg\"\ndouble 2"),
+            ("util.rg", "puts \"loaded\"\nfn double(a) = a * 2"),
+        ]);
+        let bundled = bundle(Path::new("/app/main.rg"), &ParserOptions::default(), &files);
+        assert_eq!(
+            bundled,
+            Ok(r#"import lazy "util.rg" do
+  puts "loaded"
+  fn double(a) = a * 2
+end
+double 2
+"#
+            .to_string())
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn embeds_imports_within_functions() {
+        let files = Files(vec![
+            (
+                "main.rg",
+                "fn foo\n  import \"math.rg\" # one\n  one\nend\nfoo",
+            ),
+            ("math.rg", "fn one = 1"),
+        ]);
+        let bundled = bundle(Path::new("/app/main.rg"), &ParserOptions::default(), &files);
+        assert_eq!(
+            bundled,
+            Ok(r#"fn foo
+  import "math.rg" do # one
+    fn one = 1
+  end
+  one
+end
+foo
+"#
+            .to_string())
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn relative_to_the_importing_file() {
+        let files = Files(vec![
+            (
+                "main.rg",
+                "import \"lib/math.rg\"\nimport \"./shared.rg\"\ndouble 2",
+            ),
+            (
+                "lib/math.rg",
+                "import \"util.rg\"\nimport \"../shared.rg\"\nfn double(a) = twice a",
+            ),
+            ("lib/util.rg", "fn twice(a) = a * 2"),
+            ("shared.rg", "fn shared = 1"),
+        ]);
+        let bundled = bundle(Path::new("/app/main.rg"), &ParserOptions::default(), &files);
+        assert_eq!(
+            bundled,
+            Ok(r#"import "lib/math.rg" do
+  import "lib/util.rg" do
+    fn twice(a) = a * 2
+  end
+  import "shared.rg" do
+    fn shared = 1
+  end
+  fn double(a) = twice a
+end
+import "shared.rg"
+double 2
+"#
+            .to_string())
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn imports_sharing_a_line_are_rejected() {
+        let files = Files(vec![
+            ("main.rg", "import \"math.rg\"; 1"),
+            ("math.rg", "fn one = 1"),
+        ]);
+        assert!(bundle(Path::new("/app/main.rg"), &ParserOptions::default(), &files).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{
+    Assign, Element, Expression, FunctionArgument, FunctionDefinition, FunctionExpression,
+    ParsingError, RigzArguments, Scope, Statement,
+};
+use rigz_core::{BinaryOperation, Interned, ObjectValue, PrimitiveValue, RigzType, WithTypeInfo};
+
+/// Arguments of pattern clauses are renamed by position so every clause of a function shares them
+fn clause_argument(index: usize) -> String {
+    format!("?{index}")
+}
+
+fn is_clause(definition: &FunctionDefinition) -> bool {
+    definition
+        .type_definition
+        .arguments
+        .iter()
+        .any(|a| a.name.starts_with('?'))
+}
+
+/// Named arguments are assigned within the clause so the body can use them
+fn bind_arguments(arguments: &mut [FunctionArgument], body: Scope) -> Scope {
+    let mut bindings = Vec::with_capacity(arguments.len());
+    for (index, argument) in arguments.iter_mut().enumerate() {
+        let name = clause_argument(index);
+        if argument.name == name {
+            continue;
+        }
+        let original = std::mem::replace(&mut argument.name, name.clone());
+        bindings.push((original.into(), Expression::identifier(name)));
+    }
+    bind(bindings, body)
+}
+
+fn bind(bindings: Vec<(Interned, Expression)>, body: Scope) -> Scope {
+    let mut elements = Vec::with_capacity(bindings.len() + body.elements.len());
+    elements.extend(bindings.into_iter().map(|(name, expression)| {
+        Element::Statement(Statement::Assignment {
+            lhs: Assign::Identifier(name, false),
+            expression,
+            shadow: true,
+        })
+    }));
+    let first_line = body.lines.first().copied();
+    let lines = match first_line {
+        None => vec![],
+        Some(line) => std::iter::repeat_n(line, elements.len())
+            .chain(body.lines)
+            .collect(),
+    };
+    elements.extend(body.elements);
+    Scope { elements, lines }
+}
+
+/// `lhs && rhs` that only evaluates `rhs` once `lhs` is true, `&&` evaluates both sides so `rhs` couldn't rely
+/// on the kind checked by `lhs`
+fn and_then(lhs: Expression, rhs: Expression) -> Expression {
+    let scope = |value: Expression| Scope {
+        elements: vec![Element::Expression(value)],
+        ..Default::default()
+    };
+    Expression::If {
+        condition: Box::new(lhs),
+        then: scope(rhs),
+        branch: Some(scope(Expression::Value(false.into()))),
+    }
+}
+
+/// `value.is kind`
+fn is_kind(value: Expression, kind: RigzType) -> Expression {
+    FunctionExpression::InstanceFunctionCall(
+        Box::new(value),
+        vec!["is".to_string()],
+        RigzArguments::Positional(vec![Expression::Value(PrimitiveValue::Type(kind))]),
+    )
+    .into()
+}
+
+/// Kind of value a literal pattern matches, `==` converts between kinds so `1 == "1"`
+fn literal_kind(pattern: &Expression) -> Option<RigzType> {
+    match pattern {
+        Expression::Value(v) => Some(v.rigz_type()),
+        // integers too big for an Int
+        Expression::Cast(_, RigzType::Int) => Some(RigzType::Number),
+        _ => None,
+    }
+}
+
+/// Checks `value` against `pattern`, names within list patterns are added to `bindings` instead
+fn pattern_condition(
+    value: Expression,
+    pattern: Expression,
+    bindings: &mut Vec<(Interned, Expression)>,
+) -> Option<Expression> {
+    match pattern {
+        Expression::Identifier(name, _) => {
+            if name.as_str() != "_" {
+                bindings.push((name, value));
+            }
+            None
+        }
+        Expression::List(elements) => {
+            let list = RigzType::List(Box::new(RigzType::Any));
+            let len = FunctionExpression::InstanceFunctionCall(
+                Box::new(Expression::Cast(Box::new(value.clone()), list)),
+                vec!["len".to_string()],
+                RigzArguments::Positional(vec![]),
+            );
+            let len = Expression::BinExp(
+                Box::new(len.into()),
+                BinaryOperation::Eq,
+                Box::new(Expression::Value((elements.len() as i64).into())),
+            );
+            let condition = elements
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, element)| {
+                    let item = Expression::Index(
+                        Box::new(value.clone()),
+                        Box::new(Expression::Value((index as i64).into())),
+                    );
+                    pattern_condition(item, element, bindings)
+                })
+                .fold(len, and_then);
+            let kind = ObjectValue::List(Vec::new()).rigz_type();
+            Some(and_then(is_kind(value, kind), condition))
+        }
+        pattern => {
+            let kind = literal_kind(&pattern);
+            let eq = Expression::BinExp(
+                Box::new(value.clone()),
+                BinaryOperation::Eq,
+                Box::new(pattern),
+            );
+            Some(match kind {
+                None => eq,
+                Some(kind) => and_then(is_kind(value, kind), eq),
+            })
+        }
+    }
+}
+
+/// `fn fib(0) = 0` runs its body when every pattern matches, `merge_clauses` adds the next clause as the else branch.
+/// Literals only match values of the same kind, `fn first([a, _]) = a` matches lists of two values
+pub(crate) fn function_clause(
+    mut definition: FunctionDefinition,
+    patterns: Vec<(usize, Expression)>,
+) -> FunctionDefinition {
+    let mut bindings = Vec::new();
+    let condition = patterns
+        .into_iter()
+        .filter_map(|(index, pattern)| {
+            let argument = Expression::identifier(clause_argument(index));
+            pattern_condition(argument, pattern, &mut bindings)
+        })
+        .reduce(and_then)
+        .expect("function clause without patterns");
+    let line = definition.body.lines.first().copied();
+    let then = bind_arguments(
+        &mut definition.type_definition.arguments,
+        std::mem::take(&mut definition.body),
+    );
+    definition.body = Scope {
+        elements: vec![Element::Expression(Expression::If {
+            condition: Box::new(condition),
+            then: bind(bindings, then),
+            branch: None,
+        })],
+        lines: line.into_iter().collect(),
+    };
+    definition
+}
+
+/// Consecutive definitions of a function with pattern clauses become a single definition,
+/// clauses are checked in the order they're defined
+pub(crate) fn merge_clauses(
+    elements: Vec<Element>,
+    lines: Vec<usize>,
+) -> Result<(Vec<Element>, Vec<usize>), ParsingError> {
+    if !elements
+        .iter()
+        .any(|e| matches!(e, Element::Statement(Statement::FunctionDefinition(f)) if is_clause(f)))
+    {
+        return Ok((elements, lines));
+    }
+
+    let mut merged: Vec<(Element, Option<usize>)> = Vec::with_capacity(elements.len());
+    let mut group: Vec<(FunctionDefinition, Option<usize>)> = Vec::new();
+    let mut lines = lines.into_iter();
+    for element in elements {
+        let line = lines.next();
+        match element {
+            Element::Statement(Statement::FunctionDefinition(f))
+                if group.first().is_none_or(|(g, _)| g.name == f.name) =>
+            {
+                group.push((f, line))
+            }
+            element => {
+                merge_group(std::mem::take(&mut group), &mut merged)?;
+                match element {
+                    Element::Statement(Statement::FunctionDefinition(f)) => group.push((f, line)),
+                    element => merged.push((element, line)),
+                }
+            }
+        }
+    }
+    merge_group(group, &mut merged)?;
+
+    let (elements, lines): (Vec<_>, Vec<_>) = merged.into_iter().unzip();
+    Ok((elements, lines.into_iter().flatten().collect()))
+}
+
+fn merge_group(
+    group: Vec<(FunctionDefinition, Option<usize>)>,
+    merged: &mut Vec<(Element, Option<usize>)>,
+) -> Result<(), ParsingError> {
+    if !group.iter().any(|(f, _)| is_clause(f)) {
+        // overloads by type are resolved when the program is compiled
+        merged.extend(
+            group
+                .into_iter()
+                .map(|(f, line)| (Element::Statement(Statement::FunctionDefinition(f)), line)),
+        );
+        return Ok(());
+    }
+
+    let line = group[0].1;
+    let group: Vec<_> = group.into_iter().map(|(f, _)| f).collect();
+    let name = group[0].name.clone();
+    let arity = group[0].type_definition.arguments.len();
+    for clause in &group {
+        let signature = &clause.type_definition;
+        if signature.arguments.len() != arity {
+            return Err(ParsingError::parse_error(format!(
+                "Every clause of {name} must have {arity} arguments, found {}",
+                signature.arguments.len()
+            )));
+        }
+        if signature.var_args_start.is_some() || signature.arguments.iter().any(|a| a.rest) {
+            return Err(ParsingError::parse_error(format!(
+                "Variable arguments are not supported for clauses of {name}"
+            )));
+        }
+    }
+
+    let mut clauses = group.into_iter().rev();
+    let mut last = clauses.next().unwrap();
+    let mut arguments = last.type_definition.arguments.clone();
+    let mut return_type = last.type_definition.return_type.clone();
+    let mut body = if is_clause(&last) {
+        let mut body = std::mem::take(&mut last.body);
+        let no_match = Scope {
+            elements: vec![Element::Expression(Expression::Error(Box::new(
+                Expression::Value(format!("No clause of {name} matches the arguments").into()),
+            )))],
+            lines: body.lines.clone(),
+        };
+        set_branch(&mut body, no_match);
+        body
+    } else {
+        bind_arguments(&mut arguments, std::mem::take(&mut last.body))
+    };
+
+    // clauses after a function without patterns are never reached
+    for mut clause in clauses {
+        let pattern = is_clause(&clause);
+        let mut clause_arguments = clause.type_definition.arguments;
+        body = if pattern {
+            set_branch(&mut clause.body, body);
+            clause.body
+        } else {
+            bind_arguments(&mut clause_arguments, clause.body)
+        };
+        for (argument, other) in arguments.iter_mut().zip(clause_arguments) {
+            let rigz_type = other.function_type.rigz_type;
+            if rigz_type != RigzType::Any {
+                argument.function_type.rigz_type = rigz_type;
+            }
+        }
+        if clause.type_definition.return_type.rigz_type != RigzType::default() {
+            return_type = clause.type_definition.return_type;
+        }
+        if clause.lifecycle.is_some() {
+            last.lifecycle = clause.lifecycle;
+        }
+    }
+
+    for (index, argument) in arguments.iter_mut().enumerate() {
+        argument.name = clause_argument(index);
+        argument.default = None;
+    }
+    last.type_definition.arguments = arguments;
+    last.type_definition.return_type = return_type;
+    last.body = body;
+    merged.push((
+        Element::Statement(Statement::FunctionDefinition(last)),
+        line,
+    ));
+    Ok(())
+}
+
+fn set_branch(clause: &mut Scope, branch: Scope) {
+    if let Some(Element::Expression(Expression::If { branch: b, .. })) = clause.elements.last_mut()
+    {
+        *b = Some(branch);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::ValidationWarning;
+use rigz_core::Signal;
+use std::collections::HashSet;
+
+/// Literal topics of `send` & `broadcast` calls and `@on` handlers, see `Program::event_topics`.
+/// Merge the topics of every file in a project before `check` so handlers match events sent from other files.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventTopics {
+    /// topic & the name of its handler
+    pub(crate) handlers: Vec<(String, String)>,
+    pub(crate) sent: Vec<String>,
+    /// a topic that isn't a string literal was sent, any handler may fire
+    pub(crate) dynamic: bool,
+}
+
+impl EventTopics {
+    pub fn merge(&mut self, other: EventTopics) {
+        self.handlers.extend(other.handlers);
+        self.sent.extend(other.sent);
+        self.dynamic |= other.dynamic;
+    }
+
+    /// Warns about handlers that can never fire & topics sent without a handler, i.e. a typo'd event name.
+    /// `@on("signal:NAME")` handlers are fired by the OS
+    pub fn check(&self) -> Vec<ValidationWarning> {
+        let handled: HashSet<_> = self.handlers.iter().map(|(topic, _)| topic).collect();
+        let sent: HashSet<_> = self.sent.iter().collect();
+        let mut warnings = vec![];
+        if !self.dynamic {
+            for (topic, handler) in &self.handlers {
+                if !topic.starts_with(Signal::EVENT_PREFIX) && !sent.contains(topic) {
+                    warnings.push(ValidationWarning::UnmatchedEvent(format!(
+                        "@on(\"{topic}\") {handler} never runs, nothing sends \"{topic}\""
+                    )));
+                }
+            }
+        }
+        let mut reported = HashSet::new();
+        for topic in &self.sent {
+            if !handled.contains(topic) && reported.insert(topic) {
+                warnings.push(ValidationWarning::UnmatchedEvent(format!(
+                    "\"{topic}\" is sent but no @on(\"{topic}\") handler receives it"
+                )));
+            }
+        }
+        warnings
+    }
+}
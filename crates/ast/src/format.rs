@@ -1,13 +1,58 @@
-use crate::token::TokenKind;
+use crate::token::{TokenKind, BYTE_ORDER_MARK};
 use logos::Logos;
+use std::str::FromStr;
+
+/// Line endings written by the formatter, `Auto` keeps CRLF when the input uses it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(LineEnding::Auto),
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            s => Err(format!(
+                "Invalid line ending {s}, expected auto, lf, or crlf"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FormatOptions {
+    pub line_ending: LineEnding,
+}
 
 pub fn format(input: String) -> String {
-    let read = input.as_str().trim();
+    format_with_options(input, FormatOptions::default())
+}
+
+/// A leading BOM is kept, CRLF & LF are both accepted regardless of `line_ending`
+pub fn format_with_options(input: String, options: FormatOptions) -> String {
+    let (bom, read) = match input.strip_prefix(BYTE_ORDER_MARK) {
+        None => (false, input.as_str()),
+        Some(read) => (true, read),
+    };
+    let read = read.trim();
 
     if read.is_empty() {
         return input;
     }
 
+    let crlf = match options.line_ending {
+        LineEnding::Auto => read.contains("\r\n"),
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+    };
+
     let mut result = String::with_capacity(read.len());
     let mut tokens = TokenKind::lexer(read);
     let mut indent = 0;
@@ -57,6 +102,9 @@ pub fn format(input: String) -> String {
             TokenKind::Arrow => {
                 result.push_str(" -> ");
             }
+            TokenKind::FatArrow => {
+                result.push_str(" => ");
+            }
             TokenKind::Let => {
                 result.push_str("let ");
             }
@@ -78,6 +126,20 @@ pub fn format(input: String) -> String {
                 result.push(' ');
                 indent += 1;
             }
+            TokenKind::Select => {
+                result.push_str("select");
+                indent += 1;
+            }
+            TokenKind::Comptime => {
+                result.push_str("comptime ");
+            }
+            TokenKind::Macro => {
+                result.push_str("macro ");
+                indent += 1;
+            }
+            TokenKind::Quote => {
+                result.push_str("quote ");
+            }
             TokenKind::Else => {
                 result.push_str("else ");
             }
@@ -113,12 +175,24 @@ pub fn format(input: String) -> String {
         last = token;
     }
 
-    result.trim().to_string()
+    // block comments keep their original line endings
+    let result = result.trim().replace("\r\n", "\n");
+    let result = if crlf {
+        result.replace('\n', "\r\n")
+    } else {
+        result
+    };
+    if bom {
+        format!("{BYTE_ORDER_MARK}{result}")
+    } else {
+        result
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
     use crate::format;
+    use crate::format::{format_with_options, FormatOptions, LineEnding};
     use wasm_bindgen_test::wasm_bindgen_test;
 
     #[wasm_bindgen_test(unsupported = test)]
@@ -136,4 +210,27 @@ pub mod tests {
         let formatted = format(input.to_string());
         assert_eq!(formatted, "fn foo\n  123\nend");
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_format_line_endings() {
+        let input = "\u{feff}# comment\r\nfn foo\r\n\t123\r\nend";
+        assert_eq!(
+            format(input.to_string()),
+            "\u{feff}# comment\r\nfn foo\r\n  123\r\nend"
+        );
+        let options = FormatOptions {
+            line_ending: LineEnding::Lf,
+        };
+        assert_eq!(
+            format_with_options(input.to_string(), options),
+            "\u{feff}# comment\nfn foo\n  123\nend"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_format_raw_identifier() {
+        let input = r#"r#type = r#end + foo"#;
+        let formatted = format(input.to_string());
+        assert_eq!(formatted, "r#type = r#end + foo");
+    }
 }
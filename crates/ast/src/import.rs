@@ -0,0 +1,127 @@
+use crate::{ParserOptions, UrlImportOptions, ValidationError};
+use std::fmt::Debug;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// Location of `import "<path | url>"`, relative file paths are joined with `ParserOptions::current_directory`
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ImportPath {
+    Url(String),
+    File(PathBuf),
+}
+
+/// Source returned by an `ImportResolver`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedImport {
+    /// Imports resolving to the same key are only parsed once, i.e. a canonical path or a database id
+    pub key: String,
+    pub source: String,
+}
+
+/// Loads the source of file & url imports, set `ParserOptions::import_resolver` to back imports with a database,
+/// a bundle, or a virtual filesystem. Without a resolver the runtime reads files & downloads urls.
+pub trait ImportResolver: Debug + Send + Sync {
+    fn resolve(
+        &self,
+        path: &ImportPath,
+        options: &ParserOptions,
+    ) -> Result<ResolvedImport, ValidationError>;
+}
+
+impl UrlImportOptions {
+    /// Project settings for url imports, the first one in the importing file's directory or any parent is used
+    pub const MANIFEST: &'static str = ".rigzmanifest";
+
+    pub const MANIFEST_KEYS: [&'static str; 2] = ["proxy", "no_proxy"];
+
+    /// `key = value` per line, settings that are already set aren't replaced
+    pub fn apply_manifest(&mut self, contents: &str) -> Result<(), ValidationError> {
+        for (index, line) in contents.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ValidationError::InvalidImport(format!(
+                    "Invalid {} on line {}, expected `key = value` - {line}",
+                    Self::MANIFEST,
+                    index + 1
+                )));
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "proxy" => self.proxy = self.proxy.take().or(value),
+                "no_proxy" => self.no_proxy = self.no_proxy.take().or(value),
+                key => {
+                    return Err(ValidationError::InvalidImport(format!(
+                        "Unknown {} key {key}, expected one of {}",
+                        Self::MANIFEST,
+                        Self::MANIFEST_KEYS.join(", ")
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the first manifest found in `directory` or its parents
+    pub fn find_manifest(&mut self, directory: &Path) -> Result<(), ValidationError> {
+        for dir in directory.ancestors() {
+            let path = dir.join(Self::MANIFEST);
+            if !path.is_file() {
+                continue;
+            }
+            return match read_to_string(&path) {
+                Ok(contents) => self.apply_manifest(&contents),
+                Err(e) => Err(ValidationError::InvalidImport(format!(
+                    "Failed to read {} - {e}",
+                    path.display()
+                ))),
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod import_tests {
+    use crate::{UrlImportOptions, ValidationError};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn apply_manifest() {
+        let mut options = UrlImportOptions {
+            no_proxy: Some("localhost".to_string()),
+            ..Default::default()
+        };
+        options
+            .apply_manifest(
+                r#"
+                # corporate proxy
+                proxy = http://proxy.internal:3128
+                no_proxy = .internal
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            options.proxy,
+            Some("http://proxy.internal:3128".to_string())
+        );
+        assert_eq!(options.no_proxy, Some("localhost".to_string()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unknown_manifest_key() {
+        let mut options = UrlImportOptions::default();
+        assert_eq!(
+            options.apply_manifest("timeout = 5"),
+            Err(ValidationError::InvalidImport(
+                "Unknown .rigzmanifest key timeout, expected one of proxy, no_proxy".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{Element, Program, Statement};
+use rigz_core::Lifecycle;
+
+/// `@test` function found in a file, `tags` are any other lifecycles on the function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub line: usize,
+    pub tags: Vec<String>,
+}
+
+fn lifecycle_names(lifecycle: &Lifecycle, names: &mut Vec<String>) {
+    let name = match lifecycle {
+        Lifecycle::On(_) => "on",
+        Lifecycle::After(_) => "after",
+        Lifecycle::Memo(_) => "memo",
+        Lifecycle::Test(_) => "test",
+        Lifecycle::Composite(all) => {
+            for l in all {
+                lifecycle_names(l, names);
+            }
+            return;
+        }
+    };
+    names.push(name.to_string());
+}
+
+impl Program {
+    /// Lists the top level test functions, line numbers are 1 based and point at the function's first lifecycle
+    pub fn test_inventory(&self) -> Vec<TestCase> {
+        let mut tests = vec![];
+        for (index, element) in self.elements.iter().enumerate() {
+            let Element::Statement(Statement::FunctionDefinition(fd)) = element else {
+                continue;
+            };
+            let Some(lifecycle) = &fd.lifecycle else {
+                continue;
+            };
+            let mut tags = vec![];
+            lifecycle_names(lifecycle, &mut tags);
+            let Some(test) = tags.iter().position(|t| t == "test") else {
+                continue;
+            };
+            tags.remove(test);
+            tests.push(TestCase {
+                name: fd.name.clone(),
+                line: self.lines.get(index).copied().unwrap_or_default(),
+                tags,
+            });
+        }
+        tests
+    }
+}
+
+#[cfg(test)]
+pub mod inventory_tests {
+    use crate::{parse, ParserOptions, TestCase};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn finds_tests() {
+        let input = r#"
+fn foo = 42
+
+@test
+fn test_foo
+    assert_eq foo, 42
+end
+
+@on("message")
+fn message = none
+
+@test
+@memo
+fn mut String.test_bar = 1
+"#;
+        assert_eq!(
+            parse(input, ParserOptions::default())
+                .expect("Failed to parse input")
+                .test_inventory(),
+            vec![
+                TestCase {
+                    name: "test_foo".to_string(),
+                    line: 4,
+                    tags: vec![],
+                },
+                TestCase {
+                    name: "test_bar".to_string(),
+                    line: 12,
+                    tags: vec!["memo".to_string()],
+                },
+            ]
+        );
+    }
+}
@@ -1,5 +1,14 @@
+mod bundle;
+mod clauses;
+mod digest;
+mod events;
+mod import;
+mod inventory;
+mod lint;
+mod macros;
 mod modules;
 mod program;
+mod symbols;
 mod token;
 mod validate;
 
@@ -10,26 +19,98 @@ mod ast_derive;
 mod format;
 
 #[cfg(feature = "format")]
-pub use format::format;
-
+pub use format::{format, format_with_options, FormatOptions, LineEnding};
+
+pub use bundle::bundle;
+use clauses::{function_clause, merge_clauses};
+pub use digest::StableHasher;
+pub use events::EventTopics;
+pub use import::{ImportPath, ImportResolver, ResolvedImport};
+pub use inventory::TestCase;
+pub use lint::{LintConfig, LintLevel};
 use logos::Logos;
+use macros::expand_macro;
 pub use modules::{ParsedDependency, ParsedModule, ParsedObject};
 pub use program::*;
+pub use symbols::{Occurrence, SymbolDeclaration, SymbolId, SymbolKind, SymbolTable};
 
 use rigz_core::*;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 pub use token::ParsingError;
-use token::{Symbol, Token, TokenKind, TokenValue};
+use token::{regex_literal, Symbol, Token, TokenKind, TokenValue, BYTE_ORDER_MARK};
 pub use validate::*;
 
-#[derive(Default, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct ParserOptions {
     pub current_directory: Option<PathBuf>,
     pub debug: bool,
     pub disable_file_imports: bool,
     pub disable_url_imports: bool,
+    pub url_imports: UrlImportOptions,
+    pub lint: LintConfig,
+    /// Nested expressions & scopes are parsed recursively, input nested deeper than this fails with
+    /// `ParsingError::TooDeep` instead of overflowing the stack. Lower it when parsing on threads with small stacks.
+    pub max_depth: usize,
+    /// Loads file & url imports, `None` reads files from disk & downloads urls
+    pub import_resolver: Option<Arc<dyn ImportResolver>>,
+    /// Loop & reduce bodies estimated to compile to more instructions than this run in their own scope instead of
+    /// the enclosing one, see `rigz ast --costs` for the instruction counts of functions
+    pub max_inline_cost: usize,
+}
+
+impl ParserOptions {
+    pub const DEFAULT_MAX_DEPTH: usize = 256;
+    pub const DEFAULT_MAX_INLINE_COST: usize = 64;
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            current_directory: None,
+            debug: false,
+            disable_file_imports: false,
+            disable_url_imports: false,
+            url_imports: Default::default(),
+            lint: Default::default(),
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            import_resolver: None,
+            max_inline_cost: Self::DEFAULT_MAX_INLINE_COST,
+        }
+    }
+}
+
+/// Network settings for `import "<url>"`, unset values fall back to the project's `.rigzmanifest` then
+/// environment variables
+#[derive(Debug, Clone)]
+pub struct UrlImportOptions {
+    /// defaults to RIGZ_PROXY, then HTTPS_PROXY for https urls or HTTP_PROXY for http urls, then ALL_PROXY
+    pub proxy: Option<String>,
+    /// comma separated hosts that aren't proxied, including their subdomains (`*` for every host).
+    /// Defaults to NO_PROXY
+    pub no_proxy: Option<String>,
+    /// PEM file of additional root certificates, defaults to RIGZ_CA_BUNDLE or SSL_CERT_FILE
+    pub ca_bundle: Option<PathBuf>,
+    pub retries: u32,
+    pub retry_backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for UrlImportOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+            retries: 3,
+            retry_backoff: Duration::from_millis(250),
+            timeout: Duration::from_secs(30),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -39,6 +120,18 @@ pub struct Parser<'t> {
     tokens: VecDeque<Token<'t>>,
     line: usize, // todo repl should set this
     parser_options: ParserOptions,
+    // bytes removed by trim_start, token spans are relative to the trimmed input
+    offset: usize,
+    // location of the last token consumed, used for errors without a location
+    last: Option<SourceSpan>,
+    // nested calls to parse_element & parse_expression
+    depth: usize,
+    // macros defined so far, calls after the definition are expanded
+    macros: HashMap<String, MacroDefinition>,
+    // arguments of the macro whose quoted expression is being parsed
+    unquote: Option<Vec<String>>,
+    // parsing the value of `with Module.function = value do`, `do` ends the value instead of starting a block argument
+    override_value: bool,
 }
 
 // TODO better error messages
@@ -48,16 +141,25 @@ pub fn parse(input: &str, parser_options: ParserOptions) -> Result<Program, Pars
 
 impl<'t> Parser<'t> {
     pub fn prepare(input: &'t str, parser_options: ParserOptions) -> Result<Self, ParsingError> {
-        let input = input.trim(); // ensure no trailing newlines to avoid issues in parse_element
+        // spans are offsets into the original input, so the BOM is skipped instead of removed
+        let trimmed = input
+            .strip_prefix(BYTE_ORDER_MARK)
+            .unwrap_or(input)
+            .trim_start();
+        // lines removed by trim still count towards line numbers
+        let offset = input.len() - trimmed.len();
+        let leading_lines = input[..offset].matches('\n').count();
+        let input = trimmed.trim_end(); // ensure no trailing newlines to avoid issues in parse_element
         if input.is_empty() {
-            return Err(ParsingError::ParseError(
-                "Invalid Input, no tokens".to_string(),
-            ));
+            return Err(ParsingError::diagnostic(Diagnostic::new(
+                ErrorCode::EmptyInput,
+                "Invalid Input, no tokens",
+            )));
         }
 
         let mut lexer = TokenKind::lexer(input);
         let mut tokens = VecDeque::new();
-        let mut line = 1;
+        let mut line = 1 + leading_lines;
         // todo use relative column numbers
         // let mut offset = 0;
         // let mut start = 0;
@@ -67,26 +169,60 @@ impl<'t> Parser<'t> {
                 None => break,
                 Some(t) => t,
             };
-            let span = lexer.span();
             let kind = match kind {
+                Ok(TokenKind::BinOp(BinaryOperation::Div)) => {
+                    let previous = tokens.back().map(|t: &Token| &t.kind);
+                    match regex_literal(previous, input, lexer.span().start) {
+                        None => TokenKind::BinOp(BinaryOperation::Div),
+                        Some(pattern) => {
+                            lexer.bump(pattern.len() + 1);
+                            let value = TokenValue::Regex(pattern);
+                            // compiled once here, the VM reuses the cached pattern
+                            if let Err(e) = Regex::new(&PrimitiveValue::from(value).to_string()) {
+                                let span = lexer.span();
+                                let diagnostic = Diagnostic::new(ErrorCode::Syntax, e.to_string())
+                                    .with_span(SourceSpan {
+                                        start: span.start + offset,
+                                        end: span.end + offset,
+                                        line,
+                                    });
+                                return Err(ParsingError::diagnostic(diagnostic));
+                            }
+                            TokenKind::Value(value)
+                        }
+                    }
+                }
                 Ok(t) => t,
                 Err(e) => {
-                    return Err(ParsingError::ParseError(format!(
-                        "Invalid input: {e}, {} {:?}:{}",
-                        lexer.slice(),
-                        span,
-                        line
-                    )))
+                    let span = lexer.span();
+                    let diagnostic = Diagnostic::new(
+                        e.code(),
+                        format!("Invalid input: {e}, {} {:?}:{}", lexer.slice(), span, line),
+                    )
+                    .with_span(SourceSpan {
+                        start: span.start + offset,
+                        end: span.end + offset,
+                        line,
+                    });
+                    return Err(ParsingError::diagnostic(diagnostic));
                 }
             };
 
+            let span = lexer.span();
             if kind == TokenKind::Newline {
                 line += 1;
             }
 
+            // block comments & strings can span multiple lines
+            let newlines = match kind {
+                TokenKind::Newline => 0,
+                _ => lexer.slice().matches('\n').count(),
+            };
+
             if kind != TokenKind::Comment {
                 tokens.push_back(Token { kind, span, line })
             }
+            line += newlines;
         }
         let input = if parser_options.debug {
             Some(input.to_string())
@@ -98,20 +234,50 @@ impl<'t> Parser<'t> {
             tokens,
             line,
             parser_options,
+            offset,
+            last: None,
+            depth: 0,
+            macros: HashMap::new(),
+            unquote: None,
+            override_value: false,
         })
     }
 
     pub fn parse(mut self) -> Result<Program, ParsingError> {
         let mut elements = Vec::new();
+        let mut lines = Vec::new();
         while self.has_tokens() {
-            elements.push(self.parse_element()?)
+            lines.push(self.next_line());
+            match self.parse_element() {
+                Ok(e) => elements.push(e),
+                Err(e) => return Err(self.locate(e)),
+            }
         }
+        let (elements, lines) = merge_clauses(elements, lines)?;
         Ok(Program {
             input: self.input,
             elements,
+            lines,
         })
     }
 
+    /// Adds the location of the last token consumed to errors without one
+    fn locate(&self, error: ParsingError) -> ParsingError {
+        match self.last {
+            None => error,
+            Some(span) => error.with_span(span),
+        }
+    }
+
+    /// Line of the next token that isn't a newline
+    fn next_line(&self) -> usize {
+        self.tokens
+            .iter()
+            .find(|t| t.kind != TokenKind::Newline)
+            .map(|t| t.line)
+            .unwrap_or(self.line)
+    }
+
     pub fn parse_module_trait_definition(&mut self) -> Result<ModuleTraitDefinition, ParsingError> {
         let mut next = self.next_required_token("parse_module_trait_definition")?;
         let auto_import = if next.kind == TokenKind::Import {
@@ -122,7 +288,7 @@ impl<'t> Parser<'t> {
         };
 
         if next.kind != TokenKind::Trait {
-            return Err(ParsingError::ParseError(format!(
+            return Err(ParsingError::parse_error(format!(
                 "Invalid trait, expected trait received {:?}",
                 next
             )));
@@ -140,21 +306,35 @@ impl<'t> Parser<'t> {
 impl<'t> From<TokenValue<'t>> for Expression {
     #[inline]
     fn from(value: TokenValue<'t>) -> Self {
-        Expression::Value(value.into())
+        match value {
+            TokenValue::BigInt(digits) => Expression::Cast(
+                Box::new(Expression::Value(digits.replace('_', "").into())),
+                RigzType::Int,
+            ),
+            TokenValue::Regex(_) => FunctionExpression::TypeConstructor(
+                RigzType::Custom(CustomType {
+                    name: "Regex".to_string(),
+                    fields: vec![],
+                }),
+                RigzArguments::Positional(vec![Expression::Value(value.into())]),
+            )
+            .into(),
+            v => Expression::Value(v.into()),
+        }
     }
 }
 
 impl From<&str> for Expression {
     #[inline]
     fn from(value: &str) -> Self {
-        Expression::Identifier(value.to_string())
+        Expression::identifier(value)
     }
 }
 
 impl From<Symbol<'_>> for Expression {
     #[inline]
     fn from(value: Symbol) -> Self {
-        Expression::Symbol(value.0.to_string())
+        Expression::Symbol(value.0.into())
     }
 }
 
@@ -203,7 +383,13 @@ impl<'t> Parser<'t> {
     }
 
     fn next_token(&mut self) -> Option<Token<'t>> {
-        self.tokens.pop_front()
+        let token = self.tokens.pop_front()?;
+        self.last = Some(SourceSpan {
+            start: token.span.start + self.offset,
+            end: token.span.end + self.offset,
+            line: token.line,
+        });
+        Some(token)
     }
 
     fn next_required_token(&mut self, caller: &'static str) -> Result<Token<'t>, ParsingError> {
@@ -216,9 +402,9 @@ impl<'t> Parser<'t> {
     fn consume_token(&mut self, kind: TokenKind<'t>) -> Result<(), ParsingError> {
         match self.next_token() {
             None => Err(Self::eoi_error_string(format!("expected {}", kind))),
-            Some(t) if t.kind != kind => Err(ParsingError::ParseError(format!(
-                "expected {}, received {:?}",
-                kind, t
+            Some(t) if t.kind != kind => Err(ParsingError::diagnostic(Diagnostic::new(
+                ErrorCode::UnexpectedToken,
+                format!("expected {}, received {:?}", kind, t),
             ))),
             Some(_) => Ok(()),
         }
@@ -228,14 +414,24 @@ impl<'t> Parser<'t> {
         match self.next_token() {
             None => Err(Self::eoi_error_string(format!("expected {}", kind))),
             Some(t) if t.kind == TokenKind::Newline => self.consume_token_eat_newlines(kind),
-            Some(t) if t.kind != kind => Err(ParsingError::ParseError(format!(
-                "expected {}, received {:?}",
-                kind, t
+            Some(t) if t.kind != kind => Err(ParsingError::diagnostic(Diagnostic::new(
+                ErrorCode::UnexpectedToken,
+                format!("expected {}, received {:?}", kind, t),
             ))),
             Some(_) => Ok(()),
         }
     }
 
+    /// Every nested expression or scope passes through parse_element or parse_expression
+    #[inline]
+    fn enter(&mut self) -> Result<(), ParsingError> {
+        if self.depth >= self.parser_options.max_depth {
+            return Err(ParsingError::TooDeep(self.parser_options.max_depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
     fn eoi_error(location: &'static str, caller: &'static str) -> ParsingError {
         ParsingError::Eoi(format!("{location} - {caller}"))
     }
@@ -245,6 +441,13 @@ impl<'t> Parser<'t> {
     }
 
     fn parse_element(&mut self) -> Result<Element, ParsingError> {
+        self.enter()?;
+        let result = self.parse_element_unchecked();
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_element_unchecked(&mut self) -> Result<Element, ParsingError> {
         let token = match self.peek_token() {
             None => return Err(Self::eoi_error_string("parse_element".to_string())),
             Some(t) => t,
@@ -293,14 +496,18 @@ impl<'t> Parser<'t> {
             TokenKind::Identifier(id) => {
                 self.consume_token(TokenKind::Identifier(id))?;
                 match self.peek_token() {
-                    None => id.into(),
+                    None => self.identifier(id).into(),
                     Some(t) => match t.kind {
-                        TokenKind::Assign => self.parse_assignment_definition(false, id)?.into(),
-                        TokenKind::Colon => self.parse_assignment_definition(false, id)?.into(),
+                        TokenKind::Assign => {
+                            self.parse_assignment_definition(false, false, id)?.into()
+                        }
+                        TokenKind::Colon => {
+                            self.parse_assignment_definition(false, false, id)?.into()
+                        }
                         TokenKind::Increment => {
                             self.consume_token(TokenKind::Increment)?;
                             Statement::BinaryAssignment {
-                                lhs: Assign::Identifier(id.to_string(), false),
+                                lhs: Assign::Identifier(id.into(), false),
                                 op: BinaryOperation::Add,
                                 expression: Expression::Value(1.into()),
                             }
@@ -309,7 +516,7 @@ impl<'t> Parser<'t> {
                         TokenKind::Decrement => {
                             self.consume_token(TokenKind::Decrement)?;
                             Statement::BinaryAssignment {
-                                lhs: Assign::Identifier(id.to_string(), false),
+                                lhs: Assign::Identifier(id.into(), false),
                                 op: BinaryOperation::Sub,
                                 expression: Expression::Value(1.into()),
                             }
@@ -318,13 +525,13 @@ impl<'t> Parser<'t> {
                         TokenKind::BinAssign(op) => {
                             self.consume_token(TokenKind::BinAssign(op))?;
                             Statement::BinaryAssignment {
-                                lhs: Assign::Identifier(id.to_string(), false),
+                                lhs: Assign::Identifier(id.into(), false),
                                 op,
                                 expression: self.parse_expression()?,
                             }
                             .into()
                         }
-                        _ => self.parse_identifier_element(id)?.into(),
+                        _ => self.parse_identifier_element(id)?,
                     },
                 }
             }
@@ -339,7 +546,7 @@ impl<'t> Parser<'t> {
                     )
                     .into()
                 } else {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Invalid type definition expected TypeValue, received {:?}",
                         next
                     )));
@@ -398,12 +605,17 @@ impl<'t> Parser<'t> {
             TokenKind::Object => {
                 Statement::ObjectDefinition(self.parse_object_definition()?).into()
             }
+            TokenKind::Macro => Statement::Macro(self.parse_macro_definition()?).into(),
             _ => self.parse_expression()?.into(),
         };
         match self.peek_token() {
             None => {}
             Some(t) if t.terminal() => {
                 self.consume_token(t.kind)?;
+                // a list on the next line is a new element, not an index
+                if matches!(self.peek_token(), Some(t) if t.kind == TokenKind::Lbracket) {
+                    return Ok(ele);
+                }
             }
             Some(_) => {}
         }
@@ -418,14 +630,16 @@ impl<'t> Parser<'t> {
             Some(t) => {
                 let (exp, assn) = match element {
                     Element::Statement(s) => match s {
-                        Statement::Assignment { lhs, expression } => {
-                            (expression, Some((lhs, None)))
-                        }
+                        Statement::Assignment {
+                            lhs,
+                            expression,
+                            shadow,
+                        } => (expression, Some((lhs, None, shadow))),
                         Statement::BinaryAssignment {
                             lhs,
                             op,
                             expression,
-                        } => (expression, Some((lhs, Some(op)))),
+                        } => (expression, Some((lhs, Some(op), false))),
                         s => return Ok(Element::Statement(s)),
                     },
                     Element::Expression(e) => (e, None),
@@ -442,10 +656,11 @@ impl<'t> Parser<'t> {
                                 _ => {
                                     let el = match assn {
                                         None => Element::Expression(exp),
-                                        Some((assn, op)) => match op {
+                                        Some((assn, op, shadow)) => match op {
                                             None => Statement::Assignment {
                                                 lhs: assn,
                                                 expression: exp,
+                                                shadow,
                                             }
                                             .into(),
                                             Some(op) => Statement::BinaryAssignment {
@@ -468,12 +683,12 @@ impl<'t> Parser<'t> {
 
                         let fe = match fe {
                             Expression::Function(fe) => fe,
-                            Expression::Identifier(id) => FunctionExpression::FunctionCall(
+                            Expression::Identifier(id, _) => FunctionExpression::FunctionCall(
                                 id,
                                 RigzArguments::Positional(vec![]),
                             ),
                             _ => {
-                                return Err(ParsingError::ParseError(format!(
+                                return Err(ParsingError::parse_error(format!(
                                     "Invalid expression after {t:?}, {fe:?} a Function is required"
                                 )));
                             }
@@ -494,7 +709,7 @@ impl<'t> Parser<'t> {
                                     self.consume_token(TokenKind::Into)?;
                                     let fe = self.parse_expression()?;
                                     let Expression::Function(fe) = fe else {
-                                        return Err(ParsingError::ParseError(format!("Invalid expression after {t:?}, {fe:?} a Function call is required")));
+                                        return Err(ParsingError::parse_error(format!("Invalid expression after {t:?}, {fe:?} a Function call is required")));
                                     };
 
                                     exp = Expression::Into {
@@ -510,10 +725,11 @@ impl<'t> Parser<'t> {
                     _ => {
                         let el = match assn {
                             None => Element::Expression(self.parse_expression_suffix(exp)?),
-                            Some((assn, op)) => match op {
+                            Some((assn, op, shadow)) => match op {
                                 None => Statement::Assignment {
                                     lhs: assn,
                                     expression: exp,
+                                    shadow,
                                 }
                                 .into(),
                                 Some(op) => Statement::BinaryAssignment {
@@ -536,25 +752,166 @@ impl<'t> Parser<'t> {
         match lifecycle {
             // todo support @test.assert_eq, @test.assert_neq, @test.assert
             "test" => Ok(Lifecycle::Test(TestLifecycle)),
-            "memo" => Ok(Lifecycle::Memo(MemoizedLifecycle::default())),
+            "memo" => Ok(Lifecycle::Memo(self.parse_memo_lifecycle()?)),
             "on" => {
                 self.consume_token(TokenKind::Lparen)?;
-                let e = self.parse_paren_expression()?;
-                match e {
-                    Element::Expression(Expression::Value(PrimitiveValue::String(s))) => {
-                        Ok(Lifecycle::On(EventLifecycle { event: s }))
-                    }
-                    _ => Err(ParsingError::ParseError(format!(
-                        "Expressions not supported for `on` lifecycle {e:?}"
-                    ))),
-                }
+                Ok(Lifecycle::On(self.parse_event_lifecycle()?))
             }
-            _ => Err(ParsingError::ParseError(format!(
+            _ => Err(ParsingError::parse_error(format!(
                 "Lifecycle {lifecycle} is not supported"
             ))),
         }
     }
 
+    /// `@memo` or `@memo(persist: "cache.db", limit: 100, ttl: 60)`
+    fn parse_memo_lifecycle(&mut self) -> Result<MemoizedLifecycle, ParsingError> {
+        let mut lifecycle = MemoizedLifecycle::default();
+        if !matches!(self.peek_token(), Some(t) if t.kind == TokenKind::Lparen) {
+            return Ok(lifecycle);
+        }
+        self.consume_token(TokenKind::Lparen)?;
+        loop {
+            let next = self.next_required_token("parse_memo_lifecycle")?;
+            let TokenKind::Identifier(option) = next.kind else {
+                return Err(ParsingError::parse_error(format!(
+                    "Invalid `memo` lifecycle option {next:?}"
+                )));
+            };
+            self.consume_token(TokenKind::Colon)?;
+            match (option, self.parse_expression()?) {
+                ("persist", Expression::Value(PrimitiveValue::String(path)))
+                    if !path.is_empty() =>
+                {
+                    lifecycle.persist = Some(Box::new(PersistentMemo::new(path)))
+                }
+                ("limit", Expression::Value(PrimitiveValue::Number(n))) => {
+                    match n.to_usize().map(NonZeroUsize::new) {
+                        Ok(Some(l)) => lifecycle.limit = Some(l),
+                        _ => {
+                            return Err(ParsingError::parse_error(format!(
+                                "`memo` limit must be greater than 0, received {n}"
+                            )))
+                        }
+                    }
+                }
+                ("ttl", Expression::Value(PrimitiveValue::Number(n))) => {
+                    let seconds = n.to_float();
+                    if seconds <= 0.0 {
+                        return Err(ParsingError::parse_error(format!(
+                            "`memo` ttl must be greater than 0 seconds, received {n}"
+                        )));
+                    }
+                    // ttls under a millisecond expire after one
+                    let ms = (seconds * 1000.0) as usize;
+                    lifecycle.ttl = Some(NonZeroUsize::new(ms).unwrap_or(NonZeroUsize::MIN))
+                }
+                (option, e) => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Invalid `memo` lifecycle option {option}: {e:?}, expected persist: String, limit: Int, or ttl: Number"
+                    )))
+                }
+            }
+            let next = self.next_required_token("parse_memo_lifecycle")?;
+            match next.kind {
+                TokenKind::Rparen => break,
+                TokenKind::Comma => {}
+                _ => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Invalid `memo` lifecycle, expected , or ) received {next:?}"
+                    )))
+                }
+            }
+        }
+        Ok(lifecycle)
+    }
+
+    /// `@on("event")` or `@on("event", capacity: 8, overflow: "drop_oldest", restart: "on_failure", detached: true)`
+    fn parse_event_lifecycle(&mut self) -> Result<EventLifecycle, ParsingError> {
+        let mut max_restarts = false;
+        let mut lifecycle = match self.parse_expression()? {
+            Expression::Value(PrimitiveValue::String(s)) => EventLifecycle::new(s),
+            e => {
+                return Err(ParsingError::parse_error(format!(
+                    "Expressions not supported for `on` lifecycle {e:?}"
+                )))
+            }
+        };
+        lifecycle
+            .signal()
+            .map_err(|e| ParsingError::parse_error(e.to_string()))?;
+        loop {
+            let next = self.next_required_token("parse_event_lifecycle")?;
+            match next.kind {
+                TokenKind::Rparen => break,
+                TokenKind::Comma => {}
+                _ => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Invalid `on` lifecycle, expected , or ) received {next:?}"
+                    )))
+                }
+            }
+            let next = self.next_required_token("parse_event_lifecycle")?;
+            let TokenKind::Identifier(option) = next.kind else {
+                return Err(ParsingError::parse_error(format!(
+                    "Invalid `on` lifecycle option {next:?}"
+                )));
+            };
+            self.consume_token(TokenKind::Colon)?;
+            match (option, self.parse_expression()?) {
+                ("capacity", Expression::Value(PrimitiveValue::Number(n))) => {
+                    match n.to_usize() {
+                        Ok(c) if c > 0 => lifecycle.capacity = c,
+                        _ => {
+                            return Err(ParsingError::parse_error(format!(
+                                "`on` capacity must be greater than 0, received {n}"
+                            )))
+                        }
+                    }
+                }
+                ("overflow", Expression::Value(PrimitiveValue::String(s)))
+                | ("overflow", Expression::Symbol(s)) => {
+                    lifecycle.overflow = s
+                        .parse()
+                        .map_err(|e: VMError| ParsingError::parse_error(e.to_string()))?
+                }
+                ("restart", Expression::Value(PrimitiveValue::String(s)))
+                | ("restart", Expression::Symbol(s)) => {
+                    lifecycle.restart = s
+                        .parse()
+                        .map_err(|e: VMError| ParsingError::parse_error(e.to_string()))?
+                }
+                ("max_restarts", Expression::Value(PrimitiveValue::Number(n))) => {
+                    lifecycle.max_restarts = n.to_usize().map_err(|_| {
+                        ParsingError::parse_error(format!(
+                            "`on` max_restarts must be 0 or more, received {n}"
+                        ))
+                    })?;
+                    max_restarts = true;
+                }
+                ("backoff", Expression::Value(PrimitiveValue::Number(n))) => {
+                    lifecycle.backoff = n.to_usize().map_err(|_| {
+                        ParsingError::parse_error(format!(
+                            "`on` backoff must be 0 or more milliseconds, received {n}"
+                        ))
+                    })?
+                }
+                ("detached", Expression::Value(PrimitiveValue::Bool(b))) => lifecycle.detached = b,
+                (option, e) => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Invalid `on` lifecycle option {option}: {e:?}, expected capacity: Int, overflow: String, restart: String, max_restarts: Int, backoff: Int, or detached: Bool"
+                    )))
+                }
+            }
+        }
+        if max_restarts && lifecycle.restart != Restart::OnFailure {
+            return Err(ParsingError::parse_error(format!(
+                "`on` max_restarts requires restart: \"on_failure\", received restart: \"{}\"",
+                lifecycle.restart
+            )));
+        }
+        Ok(lifecycle)
+    }
+
     fn parse_lifecycle_func(
         &mut self,
         initial_lifecycle: &'t str,
@@ -583,7 +940,15 @@ impl<'t> Parser<'t> {
 
     fn parse_import(&mut self) -> Result<Statement, ParsingError> {
         self.consume_token(TokenKind::Import)?;
-        let next = self.next_required_token("parse_import")?;
+        let mut next = self.next_required_token("parse_import")?;
+        // `lazy` is only a keyword before a path
+        let lazy = next.kind == TokenKind::Identifier("lazy")
+            && self
+                .peek_token()
+                .is_some_and(|t| matches!(t.kind, TokenKind::Value(TokenValue::String(_))));
+        if lazy {
+            next = self.next_required_token("parse_import - lazy")?;
+        }
         let import_value = match next.kind {
             TokenKind::TypeValue(tv) => {
                 ImportValue::TypeValue(tv.to_string())
@@ -591,27 +956,46 @@ impl<'t> Parser<'t> {
             TokenKind::Value(TokenValue::String(s)) => {
                 if s.starts_with("http") {
                     if self.parser_options.disable_url_imports {
-                        return Err(ParsingError::ParseError(format!("URL imports are not allowed - {s}")))
+                        return Err(ParsingError::parse_error(format!("URL imports are not allowed - {s}")))
                     }
                     ImportValue::UrlPath(s.to_string())
                 } else {
                     if self.parser_options.disable_file_imports {
-                        return Err(ParsingError::ParseError(format!("File imports are not allowed - {s}")))
+                        return Err(ParsingError::parse_error(format!("File imports are not allowed - {s}")))
                     }
                     ImportValue::FilePath(s.to_string())
                 }
             }
-            t => return Err(ParsingError::ParseError(format!(
+            t => return Err(ParsingError::parse_error(format!(
                 "Only type values and string literals are supported in import currently, received {t}"
             ))),
         };
+        let import_value = match (&import_value, self.peek_token()) {
+            (ImportValue::FilePath(_) | ImportValue::UrlPath(_), Some(t))
+                if t.kind == TokenKind::Do =>
+            {
+                self.consume_token(TokenKind::Do)?;
+                ImportValue::Embedded(Box::new(import_value), self.parse_scope()?)
+            }
+            _ => import_value,
+        };
+        if lazy {
+            return Ok(Statement::Import(ImportValue::Lazy(Box::new(import_value))));
+        }
         Ok(Statement::Import(import_value))
     }
 
     fn parse_expression(&mut self) -> Result<Expression, ParsingError> {
+        self.enter()?;
+        let result = self.parse_expression_unchecked();
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_expression_unchecked(&mut self) -> Result<Expression, ParsingError> {
         let next = self
             .next_required_token("parse_expression")
-            .map_err(|e| ParsingError::ParseError(format!("Invalid Expression {e}")))?;
+            .map_err(|e| ParsingError::parse_error(format!("Invalid Expression {e}")))?;
         let exp = match next.kind {
             TokenKind::Minus => self.parse_unary_expression(UnaryOperation::Neg)?,
             TokenKind::Not => self.parse_unary_expression(UnaryOperation::Not)?,
@@ -622,7 +1006,7 @@ impl<'t> Parser<'t> {
             TokenKind::Lparen => {
                 let paren = self.parse_paren_expression()?;
                 let Element::Expression(e) = paren else {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Element found instead of expression {paren:?}"
                     )));
                 };
@@ -670,7 +1054,7 @@ impl<'t> Parser<'t> {
                 let type_value = match type_value.parse() {
                     Ok(tv) => tv,
                     Err(e) => {
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "Failed to read type {:?}",
                             e
                         )))
@@ -688,7 +1072,7 @@ impl<'t> Parser<'t> {
                                 let (args, assign) = self.parse_args()?;
                                 if assign {
                                     let t = self.next_required_token("parse_expression: =")?;
-                                    return Err(ParsingError::ParseError(format!(
+                                    return Err(ParsingError::parse_error(format!(
                                         "Unexpected = after {args:?} - {t:?}"
                                     )));
                                 }
@@ -703,14 +1087,14 @@ impl<'t> Parser<'t> {
                                 let (args, assign) = self.parse_args()?;
                                 if assign {
                                     let t = self.next_required_token("parse_expression: =")?;
-                                    return Err(ParsingError::ParseError(format!(
+                                    return Err(ParsingError::parse_error(format!(
                                         "Unexpected = after {args:?} - {t:?}"
                                     )));
                                 }
                                 FunctionExpression::TypeConstructor(type_value, args).into()
                             }
                             _ => {
-                                return Err(ParsingError::ParseError(format!(
+                                return Err(ParsingError::parse_error(format!(
                                     "Invalid Token for Type Function Call {:?}",
                                     func_name
                                 )));
@@ -738,11 +1122,24 @@ impl<'t> Parser<'t> {
             TokenKind::Pipe => self.parse_lambda(false)?,
             TokenKind::BinOp(BinaryOperation::Or) => self.parse_lambda(true)?,
             TokenKind::Try => Expression::Try(Box::new(self.parse_expression()?)),
+            TokenKind::Select => self.parse_select()?,
+            TokenKind::Comptime => {
+                self.consume_token(TokenKind::Do)?;
+                Expression::Comptime(self.parse_scope()?)
+            }
+            TokenKind::Unquote => self.parse_unquote()?,
+            TokenKind::Quote => {
+                return Err(ParsingError::parse_error(
+                    "quote can only be used as the body of a macro".to_string(),
+                ))
+            }
             _ => {
-                return Err(ParsingError::ParseError(format!(
-                    "Invalid Token for Expression {:?}",
-                    next
-                )))
+                let diagnostic = Diagnostic::new(
+                    ErrorCode::InvalidExpression,
+                    format!("Invalid Token for Expression {:?}", next),
+                )
+                .with_help("expressions start with a value, identifier, keyword, or `(`");
+                return Err(ParsingError::diagnostic(diagnostic));
             }
         };
         self.parse_expression_suffix(exp)
@@ -762,6 +1159,132 @@ impl<'t> Parser<'t> {
         })
     }
 
+    /// `after` is only a keyword at the start of an arm when it isn't the source
+    fn parse_select(&mut self) -> Result<Expression, ParsingError> {
+        let mut arms = Vec::new();
+        let mut after = None;
+        loop {
+            let next = self.peek_required_token_eat_newlines("parse_select")?;
+            let is_after = next.kind == TokenKind::Identifier("after")
+                && self
+                    .tokens
+                    .get(1)
+                    .is_some_and(|t| t.kind != TokenKind::FatArrow);
+            match next.kind {
+                TokenKind::End => {
+                    self.consume_token(TokenKind::End)?;
+                    break;
+                }
+                _ if is_after => {
+                    if after.is_some() {
+                        return Err(ParsingError::parse_error(
+                            "select can only have one after arm".to_string(),
+                        ));
+                    }
+                    self.consume_token(next.kind)?;
+                    let timeout = self.parse_expression()?;
+                    self.consume_token(TokenKind::FatArrow)?;
+                    after = Some((Box::new(timeout), self.parse_select_body()?));
+                }
+                _ => {
+                    let source = self.parse_expression()?;
+                    self.consume_token(TokenKind::FatArrow)?;
+                    let binding = match self.peek_token() {
+                        Some(t) if t.kind == TokenKind::Pipe => {
+                            self.consume_token(TokenKind::Pipe)?;
+                            let binding = self.required_identifier()?;
+                            self.consume_token(TokenKind::Pipe)?;
+                            Some(binding)
+                        }
+                        _ => None,
+                    };
+                    arms.push(SelectArm {
+                        source,
+                        binding,
+                        body: self.parse_select_body()?,
+                    });
+                }
+            }
+        }
+        if arms.is_empty() {
+            return Err(ParsingError::parse_error(
+                "select requires at least one arm to receive from".to_string(),
+            ));
+        }
+        Ok(Expression::Select { arms, after })
+    }
+
+    fn parse_select_body(&mut self) -> Result<Scope, ParsingError> {
+        match self.peek_token() {
+            Some(t) if t.kind == TokenKind::Do => {
+                self.consume_token(TokenKind::Do)?;
+                self.parse_scope()
+            }
+            _ => {
+                let lines = vec![self.next_line()];
+                Ok(Scope {
+                    elements: vec![self.parse_element()?],
+                    lines,
+                })
+            }
+        }
+    }
+
+    /// `macro name(a, b) quote <expression> end`, calls to the macro after its definition are replaced by
+    /// the quoted expression with each `unquote(a)` replaced by the argument
+    fn parse_macro_definition(&mut self) -> Result<MacroDefinition, ParsingError> {
+        self.consume_token(TokenKind::Macro)?;
+        let name = self.required_identifier()?;
+        self.consume_token(TokenKind::Lparen)?;
+        let mut arguments: Vec<String> = Vec::new();
+        loop {
+            let next = self.next_required_token("parse_macro_definition")?;
+            match next.kind {
+                TokenKind::Rparen => break,
+                TokenKind::Comma if !arguments.is_empty() => {}
+                TokenKind::Identifier(a) if arguments.iter().any(|arg| arg == a) => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Duplicate argument {a} for macro {name}"
+                    )))
+                }
+                TokenKind::Identifier(a) => arguments.push(a.to_string()),
+                _ => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Invalid argument for macro {name}, expected identifier, received {next:?}"
+                    )))
+                }
+            }
+        }
+        self.consume_token_eat_newlines(TokenKind::Quote)?;
+        let previous = self.unquote.replace(arguments.clone());
+        let body = self.parse_expression();
+        self.unquote = previous;
+        let body = body?;
+        self.consume_token_eat_newlines(TokenKind::End)?;
+        let definition = MacroDefinition {
+            name: name.clone(),
+            arguments,
+            body,
+        };
+        self.macros.insert(name, definition.clone());
+        Ok(definition)
+    }
+
+    fn parse_unquote(&mut self) -> Result<Expression, ParsingError> {
+        self.consume_token(TokenKind::Lparen)?;
+        let argument = self.required_identifier()?;
+        self.consume_token(TokenKind::Rparen)?;
+        match &self.unquote {
+            None => Err(ParsingError::parse_error(format!(
+                "unquote({argument}) can only be used within the quote of a macro"
+            ))),
+            Some(arguments) if !arguments.contains(&argument) => Err(ParsingError::parse_error(
+                format!("unquote({argument}) is not an argument of the macro"),
+            )),
+            Some(_) => Ok(Expression::Unquote(argument)),
+        }
+    }
+
     fn parse_expression_suffix(&mut self, exp: Expression) -> Result<Expression, ParsingError> {
         // todo should other suffix tokens be allowed to have leading newlines?
         match self.peek_token() {
@@ -777,6 +1300,7 @@ impl<'t> Parser<'t> {
                         condition: Box::new(self.parse_expression()?),
                         then: Scope {
                             elements: vec![exp.into()],
+                            ..Default::default()
                         },
                     })
                 }
@@ -786,6 +1310,7 @@ impl<'t> Parser<'t> {
                         condition: Box::new(self.parse_expression()?),
                         then: Scope {
                             elements: vec![exp.into()],
+                            ..Default::default()
                         },
                         branch: None,
                     })
@@ -797,9 +1322,13 @@ impl<'t> Parser<'t> {
                         self.parse_rigz_type(None, false)?,
                     ))
                 }
-                TokenKind::BinOp(_) | TokenKind::Pipe | TokenKind::Minus | TokenKind::Period => {
-                    Ok(self.parse_inline_expression(exp)?)
-                }
+                TokenKind::BinOp(_)
+                | TokenKind::Pipe
+                | TokenKind::Minus
+                | TokenKind::Period
+                | TokenKind::Range
+                | TokenKind::RangeInclusive
+                | TokenKind::Optional => Ok(self.parse_inline_expression(exp)?),
                 TokenKind::Lbracket => {
                     self.consume_token(TokenKind::Lbracket)?;
                     let index = self.parse_expression()?;
@@ -834,7 +1363,7 @@ impl<'t> Parser<'t> {
                                 Some(id.to_string())
                             }
                             _ => {
-                                return Err(ParsingError::ParseError(format!(
+                                return Err(ParsingError::parse_error(format!(
                                     "Expected variable name or |, received {t:?}"
                                 )))
                             }
@@ -856,14 +1385,14 @@ impl<'t> Parser<'t> {
     }
 
     fn parse_assignment(&mut self, mutable: bool) -> Result<Statement, ParsingError> {
-        let next = self
-            .next_required_token("parse_assignment")
-            .map_err(|e| ParsingError::ParseError(format!("Expected token for assignment: {e}")))?;
+        let next = self.next_required_token("parse_assignment").map_err(|e| {
+            ParsingError::parse_error(format!("Expected token for assignment: {e}"))
+        })?;
 
         match next.kind {
-            TokenKind::Identifier(id) => self.parse_assignment_definition(mutable, id),
+            TokenKind::Identifier(id) => self.parse_assignment_definition(mutable, true, id),
             TokenKind::Lparen => self.parse_tuple_assign(mutable),
-            _ => Err(ParsingError::ParseError(format!(
+            _ => Err(ParsingError::parse_error(format!(
                 "Unexpected token for assignment {:?}",
                 next
             ))),
@@ -882,7 +1411,7 @@ impl<'t> Parser<'t> {
                 }
                 TokenKind::Comma => {
                     if needs_id {
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "missing identifier after {}",
                             if is_mut { "mut" } else { "let" }
                         )));
@@ -898,12 +1427,12 @@ impl<'t> Parser<'t> {
                     needs_id = true;
                 }
                 TokenKind::Identifier(id) => {
-                    tuple.push((id.to_string(), is_mut));
+                    tuple.push((id.into(), is_mut));
                     is_mut = mutable;
                     needs_id = false
                 }
                 _ => {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Unexpected token in tuple assign {next:?}"
                     )))
                 }
@@ -913,12 +1442,14 @@ impl<'t> Parser<'t> {
         Ok(Statement::Assignment {
             lhs: Assign::Tuple(tuple),
             expression: self.parse_expression()?,
+            shadow: true,
         })
     }
 
     fn parse_assignment_definition(
         &mut self,
         mutable: bool,
+        shadow: bool,
         id: &'t str,
     ) -> Result<Statement, ParsingError> {
         let token = self.peek_required_token("parse_assignment_definition")?;
@@ -931,12 +1462,13 @@ impl<'t> Parser<'t> {
         };
         self.consume_token(TokenKind::Assign)?;
         let lhs = match rigz_type {
-            None => Assign::Identifier(id.to_string(), mutable),
-            Some(rigz_type) => Assign::TypedIdentifier(id.to_string(), mutable, rigz_type),
+            None => Assign::Identifier(id.into(), mutable),
+            Some(rigz_type) => Assign::TypedIdentifier(id.into(), mutable, rigz_type),
         };
         Ok(Statement::Assignment {
             lhs,
             expression: self.parse_expression()?,
+            shadow,
         })
     }
 
@@ -945,12 +1477,16 @@ impl<'t> Parser<'t> {
         Ok(Statement::Assignment {
             lhs: Assign::This,
             expression: self.parse_expression()?,
+            shadow: false,
         })
     }
 
     fn parse_identifier_element(&mut self, id: &'t str) -> Result<Element, ParsingError> {
+        if id == "with" && self.is_module_override() {
+            return Ok(self.parse_module_override()?.into());
+        }
         let args = match self.peek_token() {
-            None => return Ok(id.into()),
+            None => return Ok(self.identifier(id).into()),
             Some(next) => match next.kind {
                 TokenKind::Value(_)
                 | TokenKind::Identifier(_)
@@ -964,20 +1500,48 @@ impl<'t> Parser<'t> {
                     let (args, assign) = self.parse_args()?;
                     if assign {
                         let t = self.next_required_token("parse_identifier_element - =")?;
-                        return Err(ParsingError::ParseError(format!("Unexpected = after {args:?} - {t:?}")))
+                        return Err(ParsingError::parse_error(format!("Unexpected = after {args:?} - {t:?}")))
                     }
                     args
                 },
-                _ => return self.parse_inline_element(id),
+                _ => return self.parse_inline_element(self.identifier(id)),
             },
         };
-        Ok(FunctionExpression::FunctionCall(id.to_string(), args).into())
+        Ok(self.function_call(id, args)?.into())
+    }
+
+    /// Identifier expression located at the last token consumed
+    fn identifier(&self, id: &str) -> Expression {
+        Expression::Identifier(
+            id.into(),
+            Resolution {
+                span: self.last,
+                symbol: None,
+            },
+        )
+    }
+
+    /// Calls to macros are replaced by the macro's quoted expression
+    fn function_call(&self, id: &str, args: RigzArguments) -> Result<Expression, ParsingError> {
+        match self.macros.get(id) {
+            None => Ok(FunctionExpression::FunctionCall(id.into(), args).into()),
+            Some(definition) => expand_macro(definition, args),
+        }
     }
 
     fn parse_identifier_expression(&mut self, id: &'t str) -> Result<Expression, ParsingError> {
+        if id == "with" && self.is_module_override() {
+            return self.parse_module_override();
+        }
+        // `lazy` is only a keyword before `do`
+        if id == "lazy" && self.peek_token().is_some_and(|t| t.kind == TokenKind::Do) {
+            self.consume_token(TokenKind::Do)?;
+            return Ok(Expression::Lazy(self.parse_scope()?));
+        }
         let args = match self.peek_token() {
-            None => return Ok(id.into()),
+            None => return Ok(self.identifier(id)),
             Some(next) => match next.kind {
+                TokenKind::Do if self.override_value => return Ok(self.identifier(id)),
                 TokenKind::Value(_)
                 | TokenKind::Identifier(_)
                 | TokenKind::Symbol(_)
@@ -990,14 +1554,51 @@ impl<'t> Parser<'t> {
                     let (args, assign) = self.parse_args()?;
                     if assign {
                         let t = self.next_required_token("parse_identifier_expression - =")?;
-                        return Err(ParsingError::ParseError(format!("Unexpected = after {args:?} - {t:?}")))
+                        return Err(ParsingError::parse_error(format!("Unexpected = after {args:?} - {t:?}")))
                     }
                     args
                 },
-                _ => return self.parse_inline_expression(id),
+                _ => return self.parse_inline_expression(self.identifier(id)),
             },
         };
-        Ok(FunctionExpression::FunctionCall(id.to_string(), args).into())
+        self.function_call(id, args)
+    }
+
+    /// `with` is only a keyword when followed by `Module.function =`, otherwise it's a function call
+    fn is_module_override(&self) -> bool {
+        let kinds: Vec<_> = self.tokens.iter().take(4).map(|t| &t.kind).collect();
+        matches!(
+            kinds.as_slice(),
+            [
+                TokenKind::TypeValue(_),
+                TokenKind::Period,
+                TokenKind::Identifier(_),
+                TokenKind::Assign
+            ]
+        )
+    }
+
+    fn parse_module_override(&mut self) -> Result<Expression, ParsingError> {
+        let next = self.next_required_token("parse_module_override")?;
+        let TokenKind::TypeValue(module) = next.kind else {
+            return Err(ParsingError::parse_error(format!(
+                "Expected module for with, received {next:?}"
+            )));
+        };
+        self.consume_token(TokenKind::Period)?;
+        let function = self.required_identifier()?;
+        self.consume_token(TokenKind::Assign)?;
+        let override_value = std::mem::replace(&mut self.override_value, true);
+        let value = self.parse_expression();
+        self.override_value = override_value;
+        let value = value?;
+        self.consume_token(TokenKind::Do)?;
+        Ok(Expression::With {
+            module: module.to_string(),
+            function,
+            value: Box::new(value),
+            body: self.parse_scope()?,
+        })
     }
 
     fn parse_identifier_expression_skip_inline(
@@ -1005,11 +1606,12 @@ impl<'t> Parser<'t> {
         id: &'t str,
     ) -> Result<Expression, ParsingError> {
         let args = match self.peek_token() {
-            None => return Ok(id.into()),
+            None => return Ok(self.identifier(id)),
             Some(next) => match next.kind {
                 TokenKind::Period => {
+                    let base = self.identifier(id);
                     self.consume_token(TokenKind::Period)?;
-                    return self.parse_instance_call(id.into());
+                    return self.parse_instance_call(base);
                 }
                 TokenKind::Value(_)
                 | TokenKind::Identifier(_)
@@ -1022,16 +1624,16 @@ impl<'t> Parser<'t> {
                     if assign {
                         let t = self
                             .next_required_token("parse_identifier_expression_skip_inline - =")?;
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "Unexpected = after {args:?} - {t:?}"
                         )));
                     }
                     args
                 }
-                _ => return Ok(id.into()),
+                _ => return Ok(self.identifier(id)),
             },
         };
-        Ok(FunctionExpression::FunctionCall(id.to_string(), args).into())
+        self.function_call(id, args)
     }
 
     fn parse_paren_expression(&mut self) -> Result<Element, ParsingError> {
@@ -1047,7 +1649,7 @@ impl<'t> Parser<'t> {
                 expr = e;
             }
             _ => {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "Invalid paren expression {t:?}"
                 )))
             }
@@ -1079,7 +1681,7 @@ impl<'t> Parser<'t> {
                 }
                 TokenKind::Comma => {
                     if needs_id {
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "missing identifier after {}",
                             if is_mut { "mut" } else { "let" }
                         )));
@@ -1100,11 +1702,11 @@ impl<'t> Parser<'t> {
                 TokenKind::Identifier(id) => {
                     assign = convert_to_assign(&mut tuple)?;
                     needs_id = false;
-                    assign.push((id.to_string(), is_mut));
+                    assign.push((id.into(), is_mut));
                     is_mut = false;
                 }
                 _ => {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Invalid tuple assign {next:?}"
                     )))
                 }
@@ -1122,10 +1724,11 @@ impl<'t> Parser<'t> {
                 Ok(Element::Statement(Statement::Assignment {
                     lhs: Assign::Tuple(assign),
                     expression: self.parse_expression()?,
+                    shadow: false,
                 }))
             }
             Some(_) if !is_assign => Ok(Expression::Tuple(tuple).into()),
-            _ => Err(ParsingError::ParseError(
+            _ => Err(ParsingError::parse_error(
                 "Missing required = for tuple assign".to_string(),
             )),
         }
@@ -1172,6 +1775,23 @@ impl<'t> Parser<'t> {
                         let op = BinaryOperation::And;
                         res = self.parse_binary_expression(res, op)?
                     }
+                    TokenKind::Range => {
+                        let op = BinaryOperation::Range;
+                        res = match self.peek_token() {
+                            // `s[-3..]` slices to the end
+                            Some(t) if t.kind == TokenKind::Rbracket => {
+                                Expression::binary(res, op, Expression::Value(i64::MAX.into()))
+                            }
+                            _ => self.parse_binary_expression(res, op)?,
+                        }
+                    }
+                    TokenKind::RangeInclusive => {
+                        let op = BinaryOperation::RangeInclusive;
+                        res = self.parse_binary_expression(res, op)?
+                    }
+                    TokenKind::Optional => {
+                        res = Expression::Propagate(Box::new(res));
+                    }
                     TokenKind::Comma
                     | TokenKind::Rparen
                     | TokenKind::Rcurly
@@ -1179,15 +1799,20 @@ impl<'t> Parser<'t> {
                     | TokenKind::Assign // for maps
                     | TokenKind::Colon // named args
                     | TokenKind::End
+                    | TokenKind::FatArrow
                     | TokenKind::Catch => {
                         self.tokens.push_front(next);
                         break;
                     }
+                    TokenKind::Do if self.override_value => {
+                        self.tokens.push_front(next);
+                        break;
+                    }
                     TokenKind::If | TokenKind::Unless => {
                         self.tokens.push_front(next);
                         res = self.parse_expression_suffix(res)?;
                     }
-                    _ => return Err(ParsingError::ParseError(format!("Unexpected {:?} for inline expression", next)))
+                    _ => return Err(ParsingError::parse_error(format!("Unexpected {:?} for inline expression", next)))
                 },
             }
         }
@@ -1199,7 +1824,7 @@ impl<'t> Parser<'t> {
         LHS: Into<Expression>,
     {
         match self.parse_inline_element(lhs)? {
-            Element::Statement(s) => Err(ParsingError::ParseError(format!(
+            Element::Statement(s) => Err(ParsingError::parse_error(format!(
                 "Unexpected statement for inline expression {s:?}"
             ))),
             Element::Expression(e) => Ok(e),
@@ -1234,7 +1859,7 @@ impl<'t> Parser<'t> {
             TokenKind::Lparen => {
                 let e = self.parse_paren_expression()?;
                 let Element::Expression(e) = e else {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Elements not supported in binary expression {e:?}"
                     )));
                 };
@@ -1245,7 +1870,7 @@ impl<'t> Parser<'t> {
             TokenKind::Do => Expression::Scope(self.parse_scope()?),
             TokenKind::This => self.parse_this_expression_skip_inline()?,
             _ => {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "Unexpected {:?} for binary expression: {:?} {}",
                     next, lhs, op
                 )))
@@ -1256,7 +1881,7 @@ impl<'t> Parser<'t> {
 
     fn parse_instance_call(&mut self, lhs: Expression) -> Result<Expression, ParsingError> {
         match self.parse_instance_call_element(lhs)? {
-            Element::Statement(s) => Err(ParsingError::ParseError(format!(
+            Element::Statement(s) => Err(ParsingError::parse_error(format!(
                 "Unexpected statement in place of expression, {s:?}"
             ))),
             Element::Expression(e) => Ok(e),
@@ -1275,7 +1900,7 @@ impl<'t> Parser<'t> {
                 vec![]
             }
             _ => {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "Unexpected {:?} for instance call",
                     next
                 )))
@@ -1327,7 +1952,7 @@ impl<'t> Parser<'t> {
                                 needs_separator = true;
                             }
                             _ => {
-                                return Err(ParsingError::ParseError(format!(
+                                return Err(ParsingError::parse_error(format!(
                                     "Unexpected {:?} for instance call, {:?}.{}",
                                     t,
                                     lhs,
@@ -1349,16 +1974,14 @@ impl<'t> Parser<'t> {
                     Ok(Statement::Assignment {
                         lhs: Assign::InstanceSet(
                             lhs,
-                            calls
-                                .into_iter()
-                                .map(|s| AssignIndex::Identifier(s))
-                                .collect(),
+                            calls.into_iter().map(AssignIndex::Identifier).collect(),
                         ),
                         expression: self.parse_expression()?,
+                        shadow: false,
                     }
                     .into())
                 } else {
-                    Err(ParsingError::ParseError(format!(
+                    Err(ParsingError::parse_error(format!(
                         "Unexpected = after args in instance call - {lhs:?}.{} ({args:?})",
                         calls.join(".")
                     )))
@@ -1414,16 +2037,27 @@ impl<'t> Parser<'t> {
                 None => break,
                 Some(t) if t.terminal() => break,
                 Some(t) => match t.kind {
+                    // after a comma `|` can only start a lambda, i.e. `Server.route "/path", |req| ...`
+                    TokenKind::Pipe | TokenKind::BinOp(BinaryOperation::Or)
+                        if !args.is_empty() && !needs_comma && named.is_none() =>
+                    {
+                        args.push(self.parse_expression()?);
+                        needs_comma = true
+                    }
                     TokenKind::Rparen
                     | TokenKind::Rbracket
                     | TokenKind::Rcurly
                     | TokenKind::End
                     // binary operations are handled within parse_expression
-                    // todo this causes lambdas to require parens or {}
+                    // todo this causes lambdas to require parens or {} as the first argument
                     | TokenKind::BinOp(_)
                     | TokenKind::Pipe
                     | TokenKind::And
                     | TokenKind::Catch
+                    | TokenKind::FatArrow
+                    | TokenKind::Range
+                    | TokenKind::RangeInclusive
+                    | TokenKind::Optional
                     | TokenKind::Minus => break,
                     TokenKind::Identifier(id) => {
                         self.consume_token(TokenKind::Identifier(id))?;
@@ -1433,7 +2067,7 @@ impl<'t> Parser<'t> {
                                 needs_comma = true
                             }
                             None => {
-                                return Err(ParsingError::ParseError(format!("Expected : after {id} {t:?}")))
+                                return Err(ParsingError::parse_error(format!("Expected : after {id} {t:?}")))
                             }
                             Some(s) => {
                                 match s.kind {
@@ -1461,11 +2095,13 @@ impl<'t> Parser<'t> {
                             }
                         };
                     }
-                    TokenKind::Comma => {
+                    // a leading comma ends the call, i.e. `[a.len, b]`
+                    TokenKind::Comma if !args.is_empty() || named.is_some() => {
                         self.consume_token(TokenKind::Comma)?;
                         needs_comma = false;
                         continue;
                     }
+                    TokenKind::Comma => break,
                     TokenKind::If | TokenKind::Unless if !needs_comma => {
                         // todo this needs to be way more efficient
                         let t = self.tokens.clone();
@@ -1480,6 +2116,20 @@ impl<'t> Parser<'t> {
                             }
                         }
                     }
+                    TokenKind::Lparen if args.is_empty() && named.is_none() => {
+                        self.consume_token(TokenKind::Lparen)?;
+                        let paren = self.parse_paren_expression()?;
+                        let Element::Expression(e) = paren else {
+                            return Err(ParsingError::parse_error(format!("Element found instead of expression {paren:?}")));
+                        };
+                        // `foo(1)?` propagates the result of the call, not the argument
+                        if matches!(self.peek_token(), Some(t) if t.kind == TokenKind::Optional) {
+                            args.push(e);
+                            break
+                        }
+                        args.push(self.parse_expression_suffix(e)?);
+                        needs_comma = true
+                    }
                     t if named.is_none() && !needs_comma => {
                         if t == TokenKind::Assign {
                             assign = true;
@@ -1489,7 +2139,7 @@ impl<'t> Parser<'t> {
                         needs_comma = true
                     }
                     _ if named.is_some() && !needs_comma => {
-                        return Err(ParsingError::ParseError(format!("Positional args cannot be used after named args {t:?}")))
+                        return Err(ParsingError::parse_error(format!("Positional args cannot be used after named args {t:?}")))
                     },
                     _ => break
                 },
@@ -1499,11 +2149,10 @@ impl<'t> Parser<'t> {
         let args = match named {
             None => {
                 if args.len() == 1 {
-                    let args = match args.remove(0) {
+                    match args.remove(0) {
                         Expression::Tuple(a) => a.into(),
                         a => vec![a].into(),
-                    };
-                    args
+                    }
                 } else {
                     args.into()
                 }
@@ -1536,9 +2185,10 @@ impl<'t> Parser<'t> {
         }
 
         let mut args = Vec::new();
+        let mut parts = Vec::new();
         loop {
             match self.peek_token() {
-                None => return Err(ParsingError::ParseError("Missing ]".to_string())),
+                None => return Err(ParsingError::parse_error("Missing ]".to_string())),
                 Some(t) if t.kind == TokenKind::Rbracket => {
                     self.consume_token(TokenKind::Rbracket)?;
                     break;
@@ -1546,19 +2196,82 @@ impl<'t> Parser<'t> {
                 Some(t) if t.kind == TokenKind::Comma => {
                     self.consume_token(TokenKind::Comma)?;
                 }
+                Some(t) if t.kind == TokenKind::Range => {
+                    self.consume_token(TokenKind::Range)?;
+                    if !args.is_empty() {
+                        parts.push(Expression::List(std::mem::take(&mut args)));
+                    }
+                    parts.push(Expression::Cast(
+                        Box::new(self.parse_expression()?),
+                        RigzType::List(Box::new(RigzType::Any)),
+                    ));
+                }
                 Some(_) => {
                     args.push(self.parse_expression()?);
                 }
             }
         }
+        if !parts.is_empty() {
+            if !args.is_empty() {
+                parts.push(Expression::List(args));
+            }
+            return Ok(Self::concat_spread(parts));
+        }
+        if args.is_empty() {
+            return self.parse_empty_literal(Expression::List(args));
+        }
         Ok(args.into())
     }
 
+    /// `[1, ..a, 2]` & `{..defaults, a = 1}` are lowered to `[1] + (a as [Any]) + [2]` &
+    /// `(defaults as {Any, Any}) + {a = 1}`, later keys replace earlier ones
+    fn concat_spread(parts: Vec<Expression>) -> Expression {
+        parts
+            .into_iter()
+            .reduce(|lhs, rhs| Expression::binary(lhs, BinaryOperation::Add, rhs))
+            .expect("spread literals have at least one part")
+    }
+
+    /// `[]: [Int]` & `{}: {String, Int}`, without an annotation the elements of empty literals are Any
+    fn parse_empty_literal(&mut self, literal: Expression) -> Result<Expression, ParsingError> {
+        if !self.type_annotation_next() {
+            return Ok(literal);
+        }
+        self.consume_token(TokenKind::Colon)?;
+        let rigz_type = self.parse_rigz_type(None, false)?;
+        match (&literal, &rigz_type) {
+            (Expression::List(_), RigzType::List(_))
+            | (Expression::Map(_), RigzType::Map(_, _)) => {
+                Ok(Expression::Annotated(Box::new(literal), rigz_type))
+            }
+            _ => Err(ParsingError::parse_error(format!(
+                "Invalid type annotation {rigz_type} for empty literal"
+            ))),
+        }
+    }
+
+    /// `:` followed by a list or map type, `for v in []: [v]` uses the colon for the body instead
+    fn type_annotation_next(&self) -> bool {
+        let kind = |i: usize| self.tokens.get(i).map(|t| t.kind);
+        matches!(
+            (kind(0), kind(1), kind(2)),
+            (
+                Some(TokenKind::Colon),
+                Some(TokenKind::Lbracket),
+                Some(TokenKind::TypeValue(_) | TokenKind::Rbracket)
+            ) | (
+                Some(TokenKind::Colon),
+                Some(TokenKind::Lcurly),
+                Some(TokenKind::TypeValue(_) | TokenKind::Rcurly)
+            )
+        )
+    }
+
     fn required_identifier(&mut self) -> Result<String, ParsingError> {
         let t = self.next_required_token("required_identifier")?;
         match t.kind {
             TokenKind::Identifier(id) => Ok(id.to_string()),
-            _ => Err(ParsingError::ParseError(format!(
+            _ => Err(ParsingError::parse_error(format!(
                 "Expected identifier got {t:?}"
             ))),
         }
@@ -1581,7 +2294,7 @@ impl<'t> Parser<'t> {
             }
             TokenKind::Rcurly => None,
             _ => {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "Expected , or }}, received {next:?}"
                 )))
             }
@@ -1619,10 +2332,11 @@ impl<'t> Parser<'t> {
         }
 
         let mut args = Vec::new();
+        let mut parts = Vec::new();
 
         loop {
             match self.peek_token() {
-                None => return Err(ParsingError::ParseError("Missing }".to_string())),
+                None => return Err(ParsingError::parse_error("Missing }".to_string())),
                 Some(t) if t.kind == TokenKind::Rcurly => {
                     self.consume_token(TokenKind::Rcurly)?;
                     break;
@@ -1630,6 +2344,16 @@ impl<'t> Parser<'t> {
                 Some(t) if t.kind == TokenKind::Comma => {
                     self.consume_token(TokenKind::Comma)?;
                 }
+                Some(t) if t.kind == TokenKind::Range => {
+                    self.consume_token(TokenKind::Range)?;
+                    if !args.is_empty() {
+                        parts.push(Expression::Map(std::mem::take(&mut args)));
+                    }
+                    parts.push(Expression::Cast(
+                        Box::new(self.parse_expression()?),
+                        RigzType::Map(Box::new(RigzType::Any), Box::new(RigzType::Any)),
+                    ));
+                }
                 Some(_) => {
                     let key = self.parse_expression()?;
                     let t = self.next_required_token("parse_map: '=', ',', or '}' expected")?;
@@ -1639,7 +2363,7 @@ impl<'t> Parser<'t> {
                             args.push((key, value));
                         }
                         TokenKind::Comma => {
-                            if let Expression::Identifier(id) = &key {
+                            if let Expression::Identifier(id, _) = &key {
                                 args.push((Expression::Value(id.as_str().into()), key));
                             } else {
                                 args.push((key.clone(), key));
@@ -1650,7 +2374,7 @@ impl<'t> Parser<'t> {
                             break;
                         }
                         _ => {
-                            return Err(ParsingError::ParseError(format!(
+                            return Err(ParsingError::parse_error(format!(
                                 "Invalid Map Token {t:?}"
                             )))
                         }
@@ -1658,6 +2382,15 @@ impl<'t> Parser<'t> {
                 }
             }
         }
+        if !parts.is_empty() {
+            if !args.is_empty() {
+                parts.push(Expression::Map(args));
+            }
+            return Ok(Self::concat_spread(parts));
+        }
+        if args.is_empty() {
+            return self.parse_empty_literal(Expression::Map(args));
+        }
         Ok(Expression::Map(args))
     }
 
@@ -1666,7 +2399,7 @@ impl<'t> Parser<'t> {
         lifecycle: Option<Lifecycle>,
     ) -> Result<FunctionDefinition, ParsingError> {
         match self.parse_function_declaration()? {
-            FunctionDeclaration::Declaration { name, .. } => Err(ParsingError::ParseError(
+            FunctionDeclaration::Declaration { name, .. } => Err(ParsingError::parse_error(
                 format!("Missing body for function definition {name}"),
             )),
             FunctionDeclaration::Definition(mut f) => match lifecycle {
@@ -1675,7 +2408,7 @@ impl<'t> Parser<'t> {
                     if matches!(l, Lifecycle::On(_))
                         && f.type_definition.arg_type != ArgType::Positional
                     {
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "Positional arguments are required for @on lifecycle - {f:?}"
                         )));
                     }
@@ -1688,6 +2421,7 @@ impl<'t> Parser<'t> {
 
     fn parse_function_arguments(
         &mut self,
+        patterns: bool,
     ) -> Result<(Vec<FunctionArgument>, Option<usize>, ArgType), ParsingError> {
         let mut args = Vec::new();
         let next = self.peek_required_token_eat_newlines("parse_function_arguments")?;
@@ -1707,7 +2441,13 @@ impl<'t> Parser<'t> {
         self.consume_token(next.kind)?;
 
         let mut var_arg_start = None;
-        self.parse_function_arguments_inner(&mut args, terminal, &mut var_arg_start)?;
+        let patterns = patterns && arg_type == ArgType::Positional;
+        self.parse_function_arguments_inner(&mut args, terminal, &mut var_arg_start, patterns)?;
+        if var_arg_start.is_some() && arg_type != ArgType::Positional {
+            return Err(ParsingError::parse_error(format!(
+                "Variable arguments are only supported for positional arguments, not {arg_type:?} arguments"
+            )));
+        }
         Ok((args, var_arg_start, arg_type))
     }
 
@@ -1716,6 +2456,7 @@ impl<'t> Parser<'t> {
         args: &mut Vec<FunctionArgument>,
         terminal: TokenKind<'t>,
         var_arg_start: &mut Option<usize>,
+        patterns: bool,
     ) -> Result<(), ParsingError> {
         loop {
             match self.peek_token() {
@@ -1728,6 +2469,34 @@ impl<'t> Parser<'t> {
                     self.consume_token(TokenKind::Comma)?;
                     continue;
                 }
+                // `fn fib(0) = 0`, the pattern is held as the default until the clause is created
+                Some(Token {
+                    kind: TokenKind::Value(v),
+                    ..
+                }) if patterns => {
+                    self.consume_token(TokenKind::Value(v))?;
+                    args.push(FunctionArgument {
+                        name: format!("?{}", args.len()),
+                        default: Some(v.into()),
+                        function_type: RigzType::Any.into(),
+                        var_arg: false,
+                        rest: false,
+                    });
+                }
+                // `fn first([a, _]) = a`
+                Some(Token {
+                    kind: TokenKind::Lbracket,
+                    ..
+                }) if patterns => {
+                    let pattern = self.parse_list_pattern()?;
+                    args.push(FunctionArgument {
+                        name: format!("?{}", args.len()),
+                        default: Some(pattern),
+                        function_type: RigzType::Any.into(),
+                        var_arg: false,
+                        rest: false,
+                    });
+                }
                 Some(_) => {
                     let arg = self.parse_function_argument(var_arg_start.is_some())?;
                     if arg.var_arg {
@@ -1740,13 +2509,44 @@ impl<'t> Parser<'t> {
         Ok(())
     }
 
+    /// Elements are names, literals, or nested lists. `_` matches any value without binding it
+    fn parse_list_pattern(&mut self) -> Result<Expression, ParsingError> {
+        self.consume_token(TokenKind::Lbracket)?;
+        let mut elements = Vec::new();
+        loop {
+            let next = self.peek_required_token("parse_list_pattern")?;
+            match next.kind {
+                TokenKind::Rbracket => {
+                    self.consume_token(TokenKind::Rbracket)?;
+                    break;
+                }
+                TokenKind::Comma => self.consume_token(TokenKind::Comma)?,
+                TokenKind::Lbracket => elements.push(self.parse_list_pattern()?),
+                TokenKind::Identifier(name) => {
+                    self.consume_token(TokenKind::Identifier(name))?;
+                    elements.push(Expression::identifier(name));
+                }
+                TokenKind::Value(v) => {
+                    self.consume_token(TokenKind::Value(v))?;
+                    elements.push(v.into());
+                }
+                _ => {
+                    return Err(ParsingError::parse_error(format!(
+                        "Invalid list pattern {next:?}, expected a name, literal, or list"
+                    )))
+                }
+            }
+        }
+        Ok(Expression::List(elements))
+    }
+
     fn parse_lambda_arguments(
         &mut self,
     ) -> Result<(Vec<FunctionArgument>, Option<usize>), ParsingError> {
         let mut args = Vec::new();
 
         let mut var_arg_start = None;
-        self.parse_function_arguments_inner(&mut args, TokenKind::Pipe, &mut var_arg_start)?;
+        self.parse_function_arguments_inner(&mut args, TokenKind::Pipe, &mut var_arg_start, false)?;
         Ok((args, var_arg_start))
     }
 
@@ -1754,7 +2554,7 @@ impl<'t> Parser<'t> {
         let next = self.peek_required_token("check_var_arg")?;
         if next.kind == TokenKind::VariableArgs {
             if existing_var_arg {
-                return Err(ParsingError::ParseError(format!("Multiple var args are not allowed {next:?}, everything after after first declaration is considered a var arg")));
+                return Err(ParsingError::parse_error(format!("Multiple var args are not allowed {next:?}, everything after after first declaration is considered a var arg")));
             }
             self.consume_token(TokenKind::VariableArgs)?;
             Ok(true)
@@ -1779,13 +2579,13 @@ impl<'t> Parser<'t> {
                     self.parse_identifier_argument(var_arg, arg, true)
                 } else {
                     // todo should a named variable always be required?
-                    Err(ParsingError::ParseError(format!(
+                    Err(ParsingError::parse_error(format!(
                         "Invalid Function Argument after .. {:?}",
                         next
                     )))
                 }
             }
-            _ => Err(ParsingError::ParseError(format!(
+            _ => Err(ParsingError::parse_error(format!(
                 "Invalid Function Argument {:?}",
                 next
             ))),
@@ -1810,7 +2610,7 @@ impl<'t> Parser<'t> {
         };
 
         if rigz_type == RigzType::None {
-            return Err(ParsingError::ParseError(format!(
+            return Err(ParsingError::parse_error(format!(
                 "None is not a valid argument type: {next:?}"
             )));
         }
@@ -1827,7 +2627,7 @@ impl<'t> Parser<'t> {
                         rigz_type = v.rigz_type()
                     };
                 }
-                Some(v.into())
+                Some(v)
             }
             _ => None,
         };
@@ -1874,7 +2674,7 @@ impl<'t> Parser<'t> {
             TokenKind::TypeValue(id) => match id.parse::<RigzType>() {
                 Ok(t) => t,
                 Err(e) => {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Invalid type value {:?}",
                         e
                     )))
@@ -1893,7 +2693,7 @@ impl<'t> Parser<'t> {
                         l
                     }
                     _ => {
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "Invalid list type {:?}",
                             t
                         )))
@@ -1927,7 +2727,7 @@ impl<'t> Parser<'t> {
                             value_type = Some(self.parse_rigz_type(None, paren)?);
                         }
                         _ => {
-                            return Err(ParsingError::ParseError(format!(
+                            return Err(ParsingError::parse_error(format!(
                                 "Invalid map type {:?}",
                                 t
                             )))
@@ -1987,7 +2787,12 @@ impl<'t> Parser<'t> {
                 let FunctionType { rigz_type, .. } = self.parse_return_type(false)?;
                 RigzType::Function(vec![], Box::new(rigz_type))
             }
-            _ => return Err(ParsingError::ParseError(format!("Invalid type {:?}", next))),
+            _ => {
+                return Err(ParsingError::parse_error(format!(
+                    "Invalid type {:?}",
+                    next
+                )))
+            }
         };
 
         self.parse_type_suffix(rigz_type, paren)
@@ -2059,7 +2864,7 @@ impl<'t> Parser<'t> {
                 }
                 _ if t.terminal() => self.consume_token(t.kind)?,
                 _ => {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Invalid token for custom type {t:?}"
                     )));
                 }
@@ -2108,7 +2913,7 @@ impl<'t> Parser<'t> {
                                 self.consume_token(separator)?;
                                 needs_sep = false;
                             } else {
-                                return Err(ParsingError::ParseError(format!(
+                                return Err(ParsingError::parse_error(format!(
                                     "Unexpected token in {} type - {t:?}",
                                     if union { "union" } else { "composite" }
                                 )));
@@ -2137,7 +2942,7 @@ impl<'t> Parser<'t> {
         &mut self,
         mut_self: bool,
     ) -> Result<FunctionSignature, ParsingError> {
-        let (arguments, var_args_start, arg_type) = self.parse_function_arguments()?;
+        let (arguments, var_args_start, arg_type) = self.parse_function_arguments(true)?;
         Ok(FunctionSignature {
             arguments,
             var_args_start,
@@ -2149,6 +2954,7 @@ impl<'t> Parser<'t> {
 
     fn parse_scope(&mut self) -> Result<Scope, ParsingError> {
         let mut elements = vec![];
+        let mut lines = vec![];
         loop {
             let next = self.peek_required_token_eat_newlines("parse_scope")?;
             match next.kind {
@@ -2158,24 +2964,31 @@ impl<'t> Parser<'t> {
                 }
                 TokenKind::Assign if elements.is_empty() => {
                     self.consume_token(TokenKind::Assign)?;
-                    elements.push(self.parse_element()?);
+                    lines.push(self.next_line());
+                    // the newline is left for the caller, it ends the arguments of `c = spawn do = 3`
+                    elements.push(self.parse_expression()?.into());
                     break;
                 }
-                _ => elements.push(self.parse_element()?),
+                _ => {
+                    lines.push(self.next_line());
+                    elements.push(self.parse_element()?)
+                }
             }
         }
-        Ok(Scope { elements })
+        let (elements, lines) = merge_clauses(elements, lines)?;
+        Ok(Scope { elements, lines })
     }
 
     fn parse_if_scope(&mut self) -> Result<(Scope, Option<Scope>), ParsingError> {
         let mut elements = vec![];
+        let mut lines = vec![];
         let mut else_encountered = false;
         loop {
             let next = self.peek_required_token("parse_if_scope")?;
             match next.kind {
                 TokenKind::End => {
                     if elements.is_empty() {
-                        return Err(ParsingError::ParseError(format!(
+                        return Err(ParsingError::parse_error(format!(
                             "Missing end for if scope: {next:?}"
                         )));
                     }
@@ -2189,6 +3002,7 @@ impl<'t> Parser<'t> {
                 }
                 TokenKind::Assign if elements.is_empty() => {
                     self.consume_token(TokenKind::Assign)?;
+                    lines.push(self.next_line());
                     elements.push(self.parse_element()?);
                     break;
                 }
@@ -2196,7 +3010,10 @@ impl<'t> Parser<'t> {
                     // parse_element eats NewLines so we have to handle that here for valid end
                     self.consume_token(TokenKind::Newline)?;
                 }
-                _ => elements.push(self.parse_element()?),
+                _ => {
+                    lines.push(self.next_line());
+                    elements.push(self.parse_element()?)
+                }
             }
         }
         let branch = if else_encountered {
@@ -2204,7 +3021,7 @@ impl<'t> Parser<'t> {
         } else {
             None
         };
-        Ok((Scope { elements }, branch))
+        Ok((Scope { elements, lines }, branch))
     }
 
     fn parse_trait_definition(&mut self) -> Result<TraitDefinition, ParsingError> {
@@ -2212,7 +3029,7 @@ impl<'t> Parser<'t> {
         let name = if let TokenKind::TypeValue(name) = next.kind {
             name.to_string()
         } else {
-            return Err(ParsingError::ParseError(format!(
+            return Err(ParsingError::parse_error(format!(
                 "Invalid trait, expected trait name received {:?}",
                 next
             )));
@@ -2237,13 +3054,13 @@ impl<'t> Parser<'t> {
                             all.push(self.parse_function_declaration()?)
                         }
                         _ => {
-                            return Err(ParsingError::ParseError(format!("Invalid Token in trait declarations {:?}, expected Function Definition or Declaration", def)))
+                            return Err(ParsingError::parse_error(format!("Invalid Token in trait declarations {:?}, expected Function Definition or Declaration", def)))
                         }
                     }
                 }
                 // todo support type definitions here too
                 _ => {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Invalid Token in trait declarations {:?}, expected fn or end",
                         next
                     )))
@@ -2263,7 +3080,7 @@ impl<'t> Parser<'t> {
                 if let TokenKind::TypeValue(tv) = next.kind {
                     self.parse_typed_function_declaration(Some(tv), true)
                 } else {
-                    Err(ParsingError::ParseError(format!(
+                    Err(ParsingError::parse_error(format!(
                         "Invalid Token after fn mut {:?}, expected Type",
                         next
                     )))
@@ -2274,7 +3091,7 @@ impl<'t> Parser<'t> {
                 self.parse_typed_function_declaration(Some(tv), false)
             }
             TokenKind::Identifier(_) => self.parse_typed_function_declaration(None, false),
-            _ => Err(ParsingError::ParseError(format!(
+            _ => Err(ParsingError::parse_error(format!(
                 "Invalid Token in function declaration {:?}, expected mut, Type, or function name",
                 next
             ))),
@@ -2293,7 +3110,7 @@ impl<'t> Parser<'t> {
                     self.consume_token(TokenKind::Period)?;
                     is_vm = t.is_vm();
                     if is_vm && !mutable {
-                        return Err(ParsingError::ParseError(
+                        return Err(ParsingError::parse_error(
                             "VM extensions must be mutable".to_string(),
                         ));
                     }
@@ -2303,7 +3120,7 @@ impl<'t> Parser<'t> {
                     })
                 }
                 Err(e) => {
-                    return Err(ParsingError::ParseError(format!(
+                    return Err(ParsingError::parse_error(format!(
                         "Invalid fn type: {} {:?}",
                         rt, e
                     )))
@@ -2318,13 +3135,15 @@ impl<'t> Parser<'t> {
                 // hack to support type as function name
                 "type"
             }
+            // methods are called on a value, so they can share a name with built in functions
             TokenKind::Identifier(name)
-                if matches!(
-                    name,
-                    "send" | "receive" | "log" | "puts" | "spawn" | "broadcast" | "sleep"
-                ) =>
+                if self_type.is_none()
+                    && matches!(
+                        name,
+                        "send" | "receive" | "log" | "puts" | "spawn" | "broadcast" | "sleep"
+                    ) =>
             {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "{name} is a reserved function name and cannot be overwritten"
                 )))
             }
@@ -2332,13 +3151,13 @@ impl<'t> Parser<'t> {
             // todo support nested types, Module.CustomType
             _ => {
                 return match rigz_type {
-                    Some(rt) => Err(ParsingError::ParseError(format!(
+                    Some(rt) => Err(ParsingError::parse_error(format!(
                         "Invalid Token after {} {} {:?}, expected Identifier",
                         if mutable { "fn mut" } else { "fn" },
                         rt,
                         next
                     ))),
-                    None => Err(ParsingError::ParseError(format!(
+                    None => Err(ParsingError::parse_error(format!(
                         "Invalid Token after {} {:?}, expected Identifier",
                         if mutable { "fn mut" } else { "fn" },
                         next
@@ -2348,18 +3167,38 @@ impl<'t> Parser<'t> {
         };
         let mut type_definition = self.parse_function_type_definition(!is_vm && mutable)?;
         type_definition.self_type = self_type;
+        let patterns: Vec<_> = type_definition
+            .arguments
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, a)| match a.name.starts_with('?') {
+                true => a.default.take().map(|p| (index, p)),
+                false => None,
+            })
+            .collect();
         let next = self.peek_required_token_eat_newlines("parse_typed_function_declaration")?;
         let dec = match next.kind {
+            TokenKind::FunctionDef | TokenKind::End if !patterns.is_empty() => {
+                return Err(ParsingError::parse_error(format!(
+                    "Missing body for function clause {name}, patterns require a definition"
+                )))
+            }
             TokenKind::FunctionDef | TokenKind::End => FunctionDeclaration::Declaration {
                 name: name.to_string(),
                 type_definition,
             },
-            _ => FunctionDeclaration::Definition(FunctionDefinition {
-                name: name.to_string(),
-                type_definition,
-                body: self.parse_scope()?,
-                lifecycle: None,
-            }),
+            _ => {
+                let definition = FunctionDefinition {
+                    name: name.to_string(),
+                    type_definition,
+                    body: self.parse_scope()?,
+                    lifecycle: None,
+                };
+                match patterns.is_empty() {
+                    true => FunctionDeclaration::Definition(definition),
+                    false => FunctionDeclaration::Definition(function_clause(definition, patterns)),
+                }
+            }
         };
         Ok(dec)
     }
@@ -2370,7 +3209,7 @@ impl<'t> Parser<'t> {
         let name = if let TokenKind::TypeValue(ty) = n.kind {
             ty.to_string()
         } else {
-            return Err(ParsingError::ParseError(format!(
+            return Err(ParsingError::parse_error(format!(
                 "Missing Type value for Object {n:?}"
             )));
         };
@@ -2398,10 +3237,10 @@ impl<'t> Parser<'t> {
         let t = self.peek_required_token("parse_constructor - end required")?;
         if let TokenKind::TypeValue(tv) = t.kind {
             if tv != "Self" {
-                return Err(ParsingError::ParseError(format!("Received non-self type for constructor, {tv}, use Self() or rely on default constructor")));
+                return Err(ParsingError::parse_error(format!("Received non-self type for constructor, {tv}, use Self() or rely on default constructor")));
             }
             self.consume_token(t.kind)?;
-            let (args, var, ty) = self.parse_function_arguments()?;
+            let (args, var, _ty) = self.parse_function_arguments(false)?;
             // todo support all types for ty
             let next = self.peek_required_token_eat_newlines("parse_constructor - fn or end")?;
             return if let TokenKind::FunctionDef = next.kind {
@@ -2428,7 +3267,7 @@ impl<'t> Parser<'t> {
             let id = if let TokenKind::Identifier(id) = next.kind {
                 id
             } else {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "Expected identifier after `attr`, received {next:?}"
                 )));
             };
@@ -2462,20 +3301,20 @@ impl<'t> Parser<'t> {
     }
 }
 
-fn convert_to_assign(tuple: &mut Vec<Expression>) -> Result<Vec<(String, bool)>, ParsingError> {
+fn convert_to_assign(tuple: &mut Vec<Expression>) -> Result<Vec<(Interned, bool)>, ParsingError> {
     let mut results = Vec::with_capacity(tuple.len());
     for e in tuple.iter() {
         match e {
-            Expression::Identifier(id) => {
-                results.push((id.to_string(), false));
+            Expression::Identifier(id, _) => {
+                results.push((id.clone(), false));
             }
             Expression::Tuple(t) => {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "nested tuples not supported yet - {t:?}"
                 )))
             }
             _ => {
-                return Err(ParsingError::ParseError(format!(
+                return Err(ParsingError::parse_error(format!(
                     "Expression found in tuple assign {e:?}"
                 )))
             }
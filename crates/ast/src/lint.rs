@@ -0,0 +1,228 @@
+use crate::{LintWarning, Program, ValidationError, ValidationWarning};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::read_to_string;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+impl FromStr for LintLevel {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            s => Err(ValidationError::Lint(format!(
+                "Invalid lint level {s}, expected allow, warn, or deny"
+            ))),
+        }
+    }
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintLevel::Allow => write!(f, "allow"),
+            LintLevel::Warn => write!(f, "warn"),
+            LintLevel::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// Level for each lint rule, rules that aren't set use the level of `all` or the rule's default level
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LintConfig {
+    pub rules: HashMap<String, LintLevel>,
+}
+
+impl LintConfig {
+    /// Project config file, found in the current directory or any parent directory
+    pub const FILE_NAME: &'static str = ".rigzlint";
+
+    pub const RULES: [&'static str; 7] = [
+        "all",
+        "unreachable_code",
+        "unused_function",
+        "unused_variable",
+        "unused_import",
+        "shadowed_variable",
+        "unmatched_event",
+    ];
+
+    pub fn set(&mut self, rule: &str, level: LintLevel) -> Result<(), ValidationError> {
+        if !Self::RULES.contains(&rule) {
+            return Err(ValidationError::Lint(format!(
+                "Unknown lint rule {rule}, expected one of {}",
+                Self::RULES.join(", ")
+            )));
+        }
+        self.rules.insert(rule.to_string(), level);
+        Ok(())
+    }
+
+    pub fn level(&self, warning: &ValidationWarning) -> LintLevel {
+        self.rules
+            .get(warning.rule())
+            .or_else(|| self.rules.get("all"))
+            .copied()
+            .unwrap_or_else(|| warning.default_level())
+    }
+
+    /// Rules set in `other` take precedence, setting `all` in `other` replaces every current level
+    pub fn merge(&mut self, other: LintConfig) {
+        if other.rules.contains_key("all") {
+            self.rules.clear();
+        }
+        self.rules.extend(other.rules)
+    }
+
+    /// One `rule = level` per line, `#` starts a comment
+    pub fn parse(contents: &str) -> Result<Self, ValidationError> {
+        let mut config = LintConfig::default();
+        for (index, line) in contents.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((rule, level)) = line.split_once('=') else {
+                return Err(ValidationError::Lint(format!(
+                    "Invalid lint config on line {}, expected `rule = level` - {line}",
+                    index + 1
+                )));
+            };
+            config.set(rule.trim(), level.trim().parse()?)?;
+        }
+        Ok(config)
+    }
+
+    pub fn find(directory: &Path) -> Result<Option<Self>, ValidationError> {
+        for dir in directory.ancestors() {
+            let path = dir.join(Self::FILE_NAME);
+            if !path.is_file() {
+                continue;
+            }
+            return match read_to_string(&path) {
+                Ok(contents) => Self::parse(&contents).map(Some),
+                Err(e) => Err(ValidationError::Lint(format!(
+                    "Failed to read {} - {e}",
+                    path.display()
+                ))),
+            };
+        }
+        Ok(None)
+    }
+
+    /// Removes allowed warnings, returns an error listing every denied warning
+    pub fn check<W: Borrow<ValidationWarning> + Display>(
+        &self,
+        warnings: Vec<W>,
+    ) -> Result<Vec<W>, ValidationError> {
+        let mut denied = vec![];
+        let mut result = vec![];
+        for warning in warnings {
+            match self.level(warning.borrow()) {
+                LintLevel::Allow => {}
+                LintLevel::Warn => result.push(warning),
+                LintLevel::Deny => denied.push(warning.to_string()),
+            }
+        }
+        if denied.is_empty() {
+            Ok(result)
+        } else {
+            Err(ValidationError::Lint(denied.join(", ")))
+        }
+    }
+}
+
+impl Program {
+    /// Validates the program & runs all lints, returns the warnings that should be reported. Identifiers
+    /// are resolved to their symbols by `lint`, see `Program::resolve_symbols`.
+    pub fn validate_with(
+        &mut self,
+        config: &LintConfig,
+    ) -> Result<Vec<LintWarning>, ValidationError> {
+        self.validate()?;
+        config.check(self.lint())
+    }
+}
+
+#[cfg(test)]
+pub mod lint_tests {
+    use crate::{LintConfig, LintLevel, ValidationError, ValidationWarning};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parse_config() {
+        let config = LintConfig::parse(
+            r#"
+            # only fail on dead code
+            all = allow
+            unreachable_code = deny
+            "#,
+        )
+        .unwrap();
+        let unused = ValidationWarning::UnusedVariable("a".to_string());
+        let unreachable = ValidationWarning::UnreachableCode("b".to_string());
+        assert_eq!(config.level(&unused), LintLevel::Allow);
+        assert_eq!(config.level(&unreachable), LintLevel::Deny);
+        let shadowed = ValidationWarning::ShadowedVariable("c".to_string());
+        assert_eq!(LintConfig::default().level(&shadowed), LintLevel::Allow);
+        assert_eq!(config.level(&shadowed), LintLevel::Allow);
+        assert_eq!(config.check(vec![unused.clone()]), Ok(vec![]));
+        assert_eq!(
+            config.check(vec![unused, unreachable]),
+            Err(ValidationError::Lint("Unreachable Code: b".to_string()))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn merge_flags_after_config() {
+        let file = LintConfig::parse(
+            r#"
+            unused_variable = deny
+            unreachable_code = allow
+            "#,
+        )
+        .unwrap();
+        let unused = ValidationWarning::UnusedVariable("a".to_string());
+        let unreachable = ValidationWarning::UnreachableCode("b".to_string());
+        let shadowed = ValidationWarning::ShadowedVariable("c".to_string());
+
+        let mut config = file.clone();
+        let mut flags = LintConfig::default();
+        flags.set("unreachable_code", LintLevel::Deny).unwrap();
+        config.merge(flags);
+        assert_eq!(config.level(&unused), LintLevel::Deny);
+        assert_eq!(config.level(&unreachable), LintLevel::Deny);
+
+        let mut config = file;
+        let mut flags = LintConfig::default();
+        flags.set("all", LintLevel::Allow).unwrap();
+        flags.set("shadowed_variable", LintLevel::Warn).unwrap();
+        config.merge(flags);
+        assert_eq!(config.level(&unused), LintLevel::Allow);
+        assert_eq!(config.level(&unreachable), LintLevel::Allow);
+        assert_eq!(config.level(&shadowed), LintLevel::Warn);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn invalid_config() {
+        assert!(LintConfig::parse("unused = deny").is_err());
+        assert!(LintConfig::parse("unused_import = error").is_err());
+        assert!(LintConfig::parse("unused_import").is_err());
+    }
+}
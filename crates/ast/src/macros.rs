@@ -0,0 +1,206 @@
+use crate::{
+    Assign, AssignIndex, Element, Expression, FunctionExpression, MacroDefinition, ParsingError,
+    RigzArguments, Scope, Statement,
+};
+use std::collections::HashMap;
+
+/// Copy of the macro's quoted expression with every `unquote(argument)` replaced by the expression passed for it
+pub(crate) fn expand_macro(
+    definition: &MacroDefinition,
+    arguments: RigzArguments,
+) -> Result<Expression, ParsingError> {
+    let name = &definition.name;
+    let RigzArguments::Positional(arguments) = arguments else {
+        return Err(ParsingError::parse_error(format!(
+            "Named arguments are not supported for macro {name}"
+        )));
+    };
+    if arguments.len() != definition.arguments.len() {
+        return Err(ParsingError::parse_error(format!(
+            "Macro {name} expects {} arguments, received {}",
+            definition.arguments.len(),
+            arguments.len()
+        )));
+    }
+    let arguments: HashMap<_, _> = definition
+        .arguments
+        .iter()
+        .map(String::as_str)
+        .zip(arguments)
+        .collect();
+    let mut expression = definition.body.clone();
+    Unquote(&arguments).expression(&mut expression);
+    Ok(expression)
+}
+
+struct Unquote<'a>(&'a HashMap<&'a str, Expression>);
+
+impl Unquote<'_> {
+    fn expression(&self, expression: &mut Expression) {
+        match expression {
+            Expression::Unquote(name) => {
+                if let Some(e) = self.0.get(name.as_str()).cloned() {
+                    *expression = e;
+                }
+            }
+            Expression::This
+            | Expression::Value(_)
+            | Expression::Identifier(_, _)
+            | Expression::Symbol(_)
+            | Expression::Return(None) => {}
+            Expression::List(l) | Expression::Tuple(l) => {
+                l.iter_mut().for_each(|e| self.expression(e))
+            }
+            Expression::Map(m) => m.iter_mut().for_each(|(k, v)| {
+                self.expression(k);
+                self.expression(v);
+            }),
+            Expression::BinExp(lhs, _, rhs) | Expression::Index(lhs, rhs) => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expression::UnaryExp(_, e)
+            | Expression::Cast(e, _)
+            | Expression::Annotated(e, _)
+            | Expression::Error(e)
+            | Expression::Return(Some(e))
+            | Expression::DoubleBang(e)
+            | Expression::Try(e)
+            | Expression::Propagate(e) => self.expression(e),
+            Expression::Function(f) => self.function(f),
+            Expression::Scope(s) | Expression::Comptime(s) | Expression::Lazy(s) => self.scope(s),
+            Expression::If {
+                condition,
+                then,
+                branch,
+            } => {
+                self.expression(condition);
+                self.scope(then);
+                if let Some(b) = branch {
+                    self.scope(b);
+                }
+            }
+            Expression::Unless { condition, then } => {
+                self.expression(condition);
+                self.scope(then);
+            }
+            Expression::Lambda {
+                arguments, body, ..
+            } => {
+                arguments
+                    .iter_mut()
+                    .filter_map(|a| a.default.as_mut())
+                    .for_each(|e| self.expression(e));
+                self.expression(body);
+            }
+            Expression::ForList {
+                expression, body, ..
+            } => {
+                self.expression(expression);
+                self.expression(body);
+            }
+            Expression::ForMap {
+                expression,
+                key,
+                value,
+                ..
+            } => {
+                self.expression(expression);
+                self.expression(key);
+                if let Some(v) = value {
+                    self.expression(v);
+                }
+            }
+            Expression::Into { base, next } => {
+                self.expression(base);
+                self.function(next);
+            }
+            Expression::Catch { base, catch, .. } => {
+                self.expression(base);
+                self.scope(catch);
+            }
+            Expression::With { value, body, .. } => {
+                self.expression(value);
+                self.scope(body);
+            }
+            Expression::Select { arms, after } => {
+                for arm in arms {
+                    self.expression(&mut arm.source);
+                    self.scope(&mut arm.body);
+                }
+                if let Some((timeout, body)) = after {
+                    self.expression(timeout);
+                    self.scope(body);
+                }
+            }
+        }
+    }
+
+    fn function(&self, function: &mut FunctionExpression) {
+        match function {
+            FunctionExpression::FunctionCall(_, args)
+            | FunctionExpression::TypeFunctionCall(_, _, args)
+            | FunctionExpression::TypeConstructor(_, args) => self.arguments(args),
+            FunctionExpression::InstanceFunctionCall(base, _, args) => {
+                self.expression(base);
+                self.arguments(args);
+            }
+        }
+    }
+
+    fn arguments(&self, arguments: &mut RigzArguments) {
+        match arguments {
+            RigzArguments::Positional(a) => a.iter_mut().for_each(|e| self.expression(e)),
+            RigzArguments::Mixed(a, n) => {
+                a.iter_mut().for_each(|e| self.expression(e));
+                n.iter_mut().for_each(|(_, e)| self.expression(e));
+            }
+            RigzArguments::Named(n) => n.iter_mut().for_each(|(_, e)| self.expression(e)),
+        }
+    }
+
+    fn scope(&self, scope: &mut Scope) {
+        for element in &mut scope.elements {
+            match element {
+                Element::Expression(e) => self.expression(e),
+                Element::Statement(s) => self.statement(s),
+            }
+        }
+    }
+
+    /// Only expressions are replaced, names of variables & functions can't be unquoted
+    fn statement(&self, statement: &mut Statement) {
+        match statement {
+            Statement::Assignment {
+                lhs, expression, ..
+            }
+            | Statement::BinaryAssignment {
+                lhs, expression, ..
+            } => {
+                if let Assign::InstanceSet(base, indexes) = lhs {
+                    self.expression(base);
+                    indexes.iter_mut().for_each(|i| match i {
+                        AssignIndex::Identifier(_) => {}
+                        AssignIndex::Index(e) => self.expression(e),
+                    });
+                }
+                self.expression(expression);
+            }
+            Statement::FunctionDefinition(f) => {
+                f.type_definition
+                    .arguments
+                    .iter_mut()
+                    .filter_map(|a| a.default.as_mut())
+                    .for_each(|e| self.expression(e));
+                self.scope(&mut f.body);
+            }
+            Statement::Trait(_)
+            | Statement::Import(_)
+            | Statement::Export(_)
+            | Statement::TypeDefinition(_, _)
+            | Statement::TraitImpl { .. }
+            | Statement::ObjectDefinition(_)
+            | Statement::Macro(_) => {}
+        }
+    }
+}
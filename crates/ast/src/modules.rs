@@ -54,3 +54,11 @@ pub trait ParsedObject: Object {
         }
     }
 }
+
+impl ParsedObject for rigz_core::DateTime {}
+
+impl ParsedObject for rigz_core::Duration {}
+
+impl ParsedObject for rigz_core::Uuid {}
+
+impl ParsedObject for rigz_core::Regex {}
@@ -1,9 +1,16 @@
-use rigz_core::{BinaryOperation, Lifecycle, PrimitiveValue, RigzType, UnaryOperation};
+use crate::digest::StableHasher;
+use crate::SymbolId;
+use rigz_core::{
+    BinaryOperation, Interned, Lifecycle, PrimitiveValue, RigzType, SourceSpan, UnaryOperation,
+};
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Program {
     pub input: Option<String>,
     pub elements: Vec<Element>,
+    /// 1 based source line of each element, empty for programs that weren't parsed
+    pub lines: Vec<usize>,
 }
 
 impl Program {
@@ -13,16 +20,32 @@ impl Program {
             ..Default::default()
         }
     }
+
+    /// SHA-256 of the parsed elements as 64 hex characters, formatting, comments, & line numbers
+    /// don't change the digest. Stable across platforms and releases with the same AST,
+    /// use it to check whether a cached result for a program is still valid.
+    pub fn digest(&self) -> String {
+        let mut hasher = StableHasher::new();
+        self.hash(&mut hasher);
+        hasher.finalize_hex()
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Only the elements are hashed, see `Program::digest`
+impl Hash for Program {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.elements.hash(state)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum ArgType {
     Positional,
     List,
     Map,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct FunctionSignature {
     pub arguments: Vec<FunctionArgument>,
     pub return_type: FunctionType,
@@ -31,7 +54,7 @@ pub struct FunctionSignature {
     pub arg_type: ArgType,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct FunctionDefinition {
     pub name: String,
     pub type_definition: FunctionSignature,
@@ -39,7 +62,7 @@ pub struct FunctionDefinition {
     pub lifecycle: Option<Lifecycle>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct FunctionType {
     pub rigz_type: RigzType,
     pub mutable: bool,
@@ -67,7 +90,7 @@ impl FunctionType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct FunctionArgument {
     pub name: String,
     pub default: Option<Expression>,
@@ -76,36 +99,57 @@ pub struct FunctionArgument {
     pub rest: bool,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct Scope {
     pub elements: Vec<Element>,
+    /// 1 based source line of each element, empty for scopes that weren't parsed
+    pub lines: Vec<usize>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Lines are debug info, scopes with the same elements are equal
+impl PartialEq for Scope {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements == other.elements
+    }
+}
+
+impl Hash for Scope {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.elements.hash(state)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Element {
     Statement(Statement),
     Expression(Expression),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum ImportValue {
     TypeValue(String),
     FilePath(String),
     UrlPath(String),
+    /// `import lazy "file"`, top level statements run the first time a function from the file is called
+    Lazy(Box<ImportValue>),
+    /// `import "file" do ... end`, the block is the contents of the file instead of reading it, written by `bundle`
+    Embedded(Box<ImportValue>, Scope),
     // todo support tree shaking?
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Exposed {
     TypeValue(String),
     Identifier(String),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Statement {
     Assignment {
         lhs: Assign,
         expression: Expression,
+        /// declared with `let` or `mut`, may shadow variables from outer scopes
+        shadow: bool,
     },
     BinaryAssignment {
         lhs: Assign,
@@ -123,24 +167,35 @@ pub enum Statement {
         definitions: Vec<FunctionDefinition>,
     },
     ObjectDefinition(ObjectDefinition),
+    /// Calls are expanded by the parser, the definition is kept for tooling & doesn't run
+    Macro(MacroDefinition),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// `macro name(a, b) quote <expression> end`, the quoted expression is an AST value copied into the
+/// program wherever the macro is called
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub struct MacroDefinition {
+    pub name: String,
+    pub arguments: Vec<String>,
+    pub body: Expression,
+}
+
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum AssignIndex {
     Identifier(String),
     Index(Expression),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Assign {
     This,
-    Identifier(String, bool),
-    TypedIdentifier(String, bool, RigzType),
-    Tuple(Vec<(String, bool)>),
+    Identifier(Interned, bool),
+    TypedIdentifier(Interned, bool, RigzType),
+    Tuple(Vec<(Interned, bool)>),
     InstanceSet(Expression, Vec<AssignIndex>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum RigzArguments {
     Positional(Vec<Expression>),
     Mixed(Vec<Expression>, Vec<(String, Expression)>),
@@ -189,9 +244,9 @@ impl From<Vec<Expression>> for RigzArguments {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum FunctionExpression {
-    FunctionCall(String, RigzArguments),
+    FunctionCall(Interned, RigzArguments),
     TypeFunctionCall(RigzType, String, RigzArguments),
     TypeConstructor(RigzType, RigzArguments),
     InstanceFunctionCall(Box<Expression>, Vec<String>, RigzArguments),
@@ -228,19 +283,39 @@ impl From<FunctionExpression> for Box<Expression> {
     }
 }
 
+/// Where an identifier was parsed & the declaration it resolves to, set by `Program::resolve_symbols`.
+/// Ignored when comparing or hashing expressions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Resolution {
+    pub span: Option<SourceSpan>,
+    pub symbol: Option<SymbolId>,
+}
+
+impl PartialEq for Resolution {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Hash for Resolution {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
     This,
     Value(PrimitiveValue),
     List(Vec<Expression>),
     Map(Vec<(Expression, Expression)>),
-    Identifier(String),
+    Identifier(Interned, Resolution),
     BinExp(Box<Expression>, BinaryOperation, Box<Expression>),
     UnaryExp(UnaryOperation, Box<Expression>),
     Function(FunctionExpression),
     Scope(Scope),
     Cast(Box<Expression>, RigzType),
-    Symbol(String),
+    /// `[]: [Int]` or `{}: {String, Int}`, an empty literal with the element types used by the type checker
+    Annotated(Box<Expression>, RigzType),
+    Symbol(Interned),
     If {
         condition: Box<Expression>,
         then: Scope,
@@ -277,11 +352,40 @@ pub enum Expression {
     },
     DoubleBang(Box<Expression>),
     Try(Box<Expression>),
+    /// `expr?`, returns the error from the current function, otherwise evaluates to the value of `expr`
+    Propagate(Box<Expression>),
     Catch {
         base: Box<Expression>,
         var: Option<String>,
         catch: Scope,
     },
+    /// `with Json.parse = |s| ... do ... end`, calls to the module function use `value`
+    /// until `body` exits, including calls made by functions called from `body`
+    With {
+        module: String,
+        function: String,
+        value: Box<Expression>,
+        body: Scope,
+    },
+    /// Waits for the first arm whose source has a message, or runs `after` once its timeout in milliseconds passes
+    Select {
+        arms: Vec<SelectArm>,
+        after: Option<(Box<Expression>, Scope)>,
+    },
+    /// `comptime do ... end`, evaluated while the program is prepared & replaced by its result
+    Comptime(Scope),
+    /// `lazy do ... end`, evaluated the first time the value is used & cached
+    Lazy(Scope),
+    /// `unquote(argument)` within the quoted expression of a macro, replaced by the argument when the macro is called
+    Unquote(String),
+}
+
+/// `source => |binding| body`, the source is a channel or process id
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub struct SelectArm {
+    pub source: Expression,
+    pub binding: Option<String>,
+    pub body: Scope,
 }
 
 impl From<Vec<Expression>> for Expression {
@@ -291,6 +395,123 @@ impl From<Vec<Expression>> for Expression {
     }
 }
 
+/// Same as a derived impl, except numbers also hash their kind. Equal numbers hash the same so `1` & `1.0` would
+/// share a `Program::digest` otherwise.
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+        match self {
+            Expression::This => {}
+            Expression::Value(v) => {
+                if let PrimitiveValue::Number(n) = v {
+                    core::mem::discriminant(n).hash(state);
+                }
+                v.hash(state)
+            }
+            Expression::List(l) | Expression::Tuple(l) => l.hash(state),
+            Expression::Map(m) => m.hash(state),
+            Expression::Identifier(id, resolution) => {
+                id.hash(state);
+                resolution.hash(state);
+            }
+            Expression::BinExp(lhs, op, rhs) => {
+                lhs.hash(state);
+                op.hash(state);
+                rhs.hash(state);
+            }
+            Expression::UnaryExp(op, e) => {
+                op.hash(state);
+                e.hash(state);
+            }
+            Expression::Function(f) => f.hash(state),
+            Expression::Scope(s) | Expression::Comptime(s) | Expression::Lazy(s) => s.hash(state),
+            Expression::Cast(e, rigz_type) | Expression::Annotated(e, rigz_type) => {
+                e.hash(state);
+                rigz_type.hash(state);
+            }
+            Expression::Symbol(s) => s.hash(state),
+            Expression::If {
+                condition,
+                then,
+                branch,
+            } => {
+                condition.hash(state);
+                then.hash(state);
+                branch.hash(state);
+            }
+            Expression::Unless { condition, then } => {
+                condition.hash(state);
+                then.hash(state);
+            }
+            Expression::Error(e)
+            | Expression::DoubleBang(e)
+            | Expression::Try(e)
+            | Expression::Propagate(e) => e.hash(state),
+            Expression::Return(e) => e.hash(state),
+            Expression::Index(base, index) => {
+                base.hash(state);
+                index.hash(state);
+            }
+            Expression::Lambda {
+                arguments,
+                var_args_start,
+                body,
+            } => {
+                arguments.hash(state);
+                var_args_start.hash(state);
+                body.hash(state);
+            }
+            Expression::ForList {
+                var,
+                expression,
+                body,
+            } => {
+                var.hash(state);
+                expression.hash(state);
+                body.hash(state);
+            }
+            Expression::ForMap {
+                k_var,
+                v_var,
+                expression,
+                key,
+                value,
+            } => {
+                k_var.hash(state);
+                v_var.hash(state);
+                expression.hash(state);
+                key.hash(state);
+                value.hash(state);
+            }
+            Expression::Into { base, next } => {
+                base.hash(state);
+                next.hash(state);
+            }
+            Expression::Catch { base, var, catch } => {
+                base.hash(state);
+                var.hash(state);
+                catch.hash(state);
+            }
+            Expression::With {
+                module,
+                function,
+                value,
+                body,
+            } => {
+                module.hash(state);
+                function.hash(state);
+                value.hash(state);
+                body.hash(state);
+            }
+            Expression::Select { arms, after } => {
+                arms.hash(state);
+                after.hash(state);
+            }
+            Expression::Unquote(argument) => argument.hash(state),
+        }
+    }
+}
+
 impl Expression {
     #[inline]
     pub fn binary(lhs: Expression, op: BinaryOperation, rhs: Expression) -> Self {
@@ -301,15 +522,20 @@ impl Expression {
     pub(crate) fn unary(op: UnaryOperation, ex: Expression) -> Self {
         Expression::UnaryExp(op, Box::new(ex))
     }
+
+    #[inline]
+    pub fn identifier(name: impl Into<Interned>) -> Self {
+        Expression::Identifier(name.into(), Resolution::default())
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub struct ModuleTraitDefinition {
     pub auto_import: bool,
     pub definition: TraitDefinition,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum FunctionDeclaration {
     Declaration {
         name: String,
@@ -317,20 +543,20 @@ pub enum FunctionDeclaration {
     },
     Definition(FunctionDefinition),
 }
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub struct TraitDefinition {
     pub name: String,
     pub functions: Vec<FunctionDeclaration>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub struct ObjectAttr {
     pub name: String,
     pub attr_type: FunctionType,
     pub default: Option<Expression>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub struct ObjectDefinition {
     pub rigz_type: RigzType,
     pub fields: Vec<ObjectAttr>,
@@ -338,7 +564,7 @@ pub struct ObjectDefinition {
     pub functions: Vec<FunctionDeclaration>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum Constructor {
     Default,
     Declaration(Vec<FunctionArgument>, Option<usize>),
@@ -0,0 +1,702 @@
+use crate::program::{
+    Assign, AssignIndex, Constructor, Element, Exposed, Expression, FunctionArgument,
+    FunctionDeclaration, FunctionDefinition, FunctionExpression, Program, RigzArguments, Scope,
+    Statement,
+};
+use crate::token::TokenKind;
+use logos::Logos;
+use rigz_core::SourceSpan;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Identifies a declaration, assigned in the order declarations are found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+    Argument,
+    Field,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolDeclaration {
+    pub id: SymbolId,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub mutable: bool,
+    /// 1 based line of the element declaring the symbol, none for programs that weren't parsed
+    pub line: Option<usize>,
+}
+
+/// Use of a name, `symbol` is none when the name isn't declared by the program (modules, built ins, named arguments)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occurrence {
+    pub name: String,
+    pub symbol: Option<SymbolId>,
+    pub declaration: bool,
+    /// assignment to a mutable variable declared in the same scope, the value isn't read
+    pub write: bool,
+    /// location of identifier expressions, declarations & calls are located by `SymbolTable::locate`
+    pub span: Option<SourceSpan>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SymbolTable {
+    pub symbols: Vec<SymbolDeclaration>,
+    /// every identifier in the program, in source order
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl SymbolTable {
+    pub fn symbol(&self, id: SymbolId) -> Option<&SymbolDeclaration> {
+        self.symbols.get(id.0)
+    }
+
+    pub fn references(&self, id: SymbolId) -> impl Iterator<Item = &Occurrence> {
+        self.occurrences
+            .iter()
+            .filter(move |o| o.symbol == Some(id))
+    }
+
+    /// Pairs each identifier token in `input` with its occurrence. Identifier expressions are matched by
+    /// their span, the remaining tokens are matched to occurrences without a span in order. When those
+    /// don't line up for a name (i.e. syntax desugared by the parser) the name is left unresolved.
+    pub fn locate(&self, input: &str) -> Vec<(Range<usize>, Occurrence)> {
+        let mut lexer = TokenKind::lexer(input);
+        let mut tokens = vec![];
+        while let Some(token) = lexer.next() {
+            match token {
+                Ok(TokenKind::Identifier(id)) => tokens.push((lexer.span(), id)),
+                Ok(_) => {}
+                Err(_) => return vec![],
+            }
+        }
+
+        // macro expansions share the span of the quoted identifier, those can't be told apart
+        let mut spanned: HashMap<usize, Option<&Occurrence>> = HashMap::new();
+        let mut by_name: HashMap<&str, Vec<&Occurrence>> = HashMap::new();
+        for o in &self.occurrences {
+            match o.span {
+                Some(span) => {
+                    spanned
+                        .entry(span.start)
+                        .and_modify(|e| {
+                            if e.is_some_and(|e| e.symbol != o.symbol) {
+                                *e = None
+                            }
+                        })
+                        .or_insert(Some(o));
+                }
+                None => by_name.entry(o.name.as_str()).or_default().push(o),
+            }
+        }
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (span, name) in &tokens {
+            if !spanned.contains_key(&span.start) {
+                *counts.entry(*name).or_default() += 1;
+            }
+        }
+
+        let unresolved = |name: &str| Occurrence {
+            name: name.to_string(),
+            symbol: None,
+            declaration: false,
+            write: false,
+            span: None,
+        };
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        tokens
+            .into_iter()
+            .map(|(span, name)| {
+                if let Some(o) = spanned.get(&span.start) {
+                    let occurrence = o.cloned().unwrap_or_else(|| unresolved(name));
+                    return (span, occurrence);
+                }
+                let index = seen.entry(name).or_default();
+                let occurrence = match by_name.get(name) {
+                    Some(o) if o.len() == counts[name] => o[*index].clone(),
+                    _ => unresolved(name),
+                };
+                *index += 1;
+                (span, occurrence)
+            })
+            .collect()
+    }
+}
+
+impl Program {
+    /// Resolves every identifier to its declaration using the runtime's scoping rules and stores the
+    /// symbol on each `Expression::Identifier`. Extension functions, trait functions, and object
+    /// attributes are resolved by name.
+    pub fn resolve_symbols(&mut self) -> SymbolTable {
+        let mut resolver = Resolver {
+            scopes: vec![HashMap::new()],
+            ..Default::default()
+        };
+        resolver.elements(&mut self.elements, &self.lines);
+        resolver.table
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    table: SymbolTable,
+    scopes: Vec<HashMap<String, SymbolId>>,
+    members: HashMap<String, SymbolId>,
+    line: Option<usize>,
+}
+
+impl Resolver {
+    fn new_symbol(&mut self, name: &str, kind: SymbolKind, mutable: bool) -> SymbolId {
+        let id = SymbolId(self.table.symbols.len());
+        self.table.symbols.push(SymbolDeclaration {
+            id,
+            name: name.to_string(),
+            kind,
+            mutable,
+            line: self.line,
+        });
+        id
+    }
+
+    fn occurrence(&mut self, name: &str, symbol: Option<SymbolId>, declaration: bool) {
+        self.table.occurrences.push(Occurrence {
+            name: name.to_string(),
+            symbol,
+            declaration,
+            write: false,
+            span: None,
+        })
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, mutable: bool) -> SymbolId {
+        let id = self.new_symbol(name, kind, mutable);
+        self.occurrence(name, Some(id), true);
+        id
+    }
+
+    fn bind(&mut self, name: &str, id: SymbolId) {
+        self.scopes
+            .last_mut()
+            .expect("resolver always has a scope")
+            .insert(name.to_string(), id);
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.scopes.iter().rev().find_map(|s| s.get(name).copied())
+    }
+
+    fn reference(&mut self, name: &str) {
+        let symbol = self.lookup(name);
+        self.occurrence(name, symbol, false)
+    }
+
+    fn member(&mut self, name: &str, declaration: bool) {
+        let symbol = self.members.get(name).copied();
+        self.occurrence(name, symbol, declaration)
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    /// Returns the new variable to bind once the assigned expression is resolved. Like the runtime, only
+    /// assigning to a mutable variable from the same scope without `let` or `mut` updates it.
+    fn assign_target(&mut self, name: &str, mutable: bool, shadow: bool) -> Option<SymbolId> {
+        let current = self.scopes.last().and_then(|s| s.get(name).copied());
+        match current {
+            Some(id) if !shadow && self.table.symbols[id.0].mutable => {
+                self.table.occurrences.push(Occurrence {
+                    name: name.to_string(),
+                    symbol: Some(id),
+                    declaration: false,
+                    write: true,
+                    span: None,
+                });
+                None
+            }
+            _ => Some(self.declare(name, SymbolKind::Variable, mutable)),
+        }
+    }
+
+    fn hoist_function(&mut self, name: &str, member: bool) {
+        if member {
+            if !self.members.contains_key(name) {
+                let id = self.new_symbol(name, SymbolKind::Function, false);
+                self.members.insert(name.to_string(), id);
+            }
+        } else if !self.scopes.last().is_some_and(|s| s.contains_key(name)) {
+            let id = self.new_symbol(name, SymbolKind::Function, false);
+            self.bind(name, id);
+        }
+    }
+
+    fn hoist(&mut self, statement: &Statement) {
+        match statement {
+            Statement::FunctionDefinition(fd) => {
+                self.hoist_function(&fd.name, fd.type_definition.self_type.is_some())
+            }
+            Statement::Trait(t) => {
+                for f in &t.functions {
+                    let name = match f {
+                        FunctionDeclaration::Declaration { name, .. } => name,
+                        FunctionDeclaration::Definition(fd) => &fd.name,
+                    };
+                    self.hoist_function(name, true);
+                }
+            }
+            Statement::TraitImpl { definitions, .. } => {
+                for fd in definitions {
+                    self.hoist_function(&fd.name, true);
+                }
+            }
+            Statement::ObjectDefinition(o) => {
+                for attr in &o.fields {
+                    if !self.members.contains_key(&attr.name) {
+                        let id = self.new_symbol(&attr.name, SymbolKind::Field, false);
+                        self.members.insert(attr.name.clone(), id);
+                    }
+                }
+                for f in &o.functions {
+                    let name = match f {
+                        FunctionDeclaration::Declaration { name, .. } => name,
+                        FunctionDeclaration::Definition(fd) => &fd.name,
+                    };
+                    self.hoist_function(name, true);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn elements(&mut self, elements: &mut [Element], lines: &[usize]) {
+        let outer = self.line;
+        // functions can be called before they're defined
+        for (index, element) in elements.iter().enumerate() {
+            if let Element::Statement(s) = element {
+                self.line = lines.get(index).copied().or(outer);
+                self.hoist(s);
+            }
+        }
+        for (index, element) in elements.iter_mut().enumerate() {
+            self.line = lines.get(index).copied().or(outer);
+            match element {
+                Element::Statement(s) => self.statement(s),
+                Element::Expression(e) => self.expression(e),
+            }
+        }
+        self.line = outer;
+    }
+
+    fn scope(&mut self, scope: &mut Scope) {
+        self.scoped(|r| r.elements(&mut scope.elements, &scope.lines))
+    }
+
+    fn function_arguments(&mut self, arguments: &mut [FunctionArgument]) {
+        for arg in arguments {
+            let id = self.declare(&arg.name, SymbolKind::Argument, arg.function_type.mutable);
+            self.bind(&arg.name, id);
+            if let Some(d) = &mut arg.default {
+                self.expression(d);
+            }
+        }
+    }
+
+    fn function_definition(&mut self, fd: &mut FunctionDefinition, member: bool) {
+        if member {
+            self.member(&fd.name, true);
+        } else {
+            let symbol = self.scopes.last().and_then(|s| s.get(&fd.name).copied());
+            self.occurrence(&fd.name, symbol, true);
+        }
+        self.scoped(|r| {
+            r.function_arguments(&mut fd.type_definition.arguments);
+            r.elements(&mut fd.body.elements, &fd.body.lines);
+        });
+    }
+
+    fn function_declaration(&mut self, declaration: &mut FunctionDeclaration) {
+        match declaration {
+            FunctionDeclaration::Declaration {
+                name,
+                type_definition,
+            } => {
+                self.member(name, true);
+                self.scoped(|r| r.function_arguments(&mut type_definition.arguments));
+            }
+            FunctionDeclaration::Definition(fd) => self.function_definition(fd, true),
+        }
+    }
+
+    fn instance_set(&mut self, base: &mut Expression, indexes: &mut [AssignIndex]) {
+        self.expression(base);
+        for index in indexes {
+            match index {
+                AssignIndex::Identifier(name) => self.member(name, false),
+                AssignIndex::Index(e) => self.expression(e),
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Assignment {
+                lhs,
+                expression,
+                shadow,
+            } => {
+                let names = match lhs {
+                    Assign::This => vec![],
+                    Assign::Identifier(name, mutable)
+                    | Assign::TypedIdentifier(name, mutable, _) => vec![(name.clone(), *mutable)],
+                    Assign::Tuple(names) => names.clone(),
+                    Assign::InstanceSet(base, indexes) => {
+                        self.instance_set(base, indexes);
+                        vec![]
+                    }
+                };
+                let pending: Vec<_> = names
+                    .into_iter()
+                    .filter_map(|(name, mutable)| {
+                        self.assign_target(&name, mutable, *shadow)
+                            .map(|id| (name, id))
+                    })
+                    .collect();
+                // the assigned expression can't see the new variable
+                self.expression(expression);
+                for (name, id) in pending {
+                    self.bind(&name, id);
+                }
+            }
+            Statement::BinaryAssignment {
+                lhs, expression, ..
+            } => {
+                match lhs {
+                    Assign::This => {}
+                    Assign::Identifier(name, _) | Assign::TypedIdentifier(name, _, _) => {
+                        self.reference(name)
+                    }
+                    Assign::Tuple(names) => names.iter().for_each(|(n, _)| self.reference(n)),
+                    Assign::InstanceSet(base, indexes) => self.instance_set(base, indexes),
+                }
+                self.expression(expression);
+            }
+            Statement::FunctionDefinition(fd) => {
+                let member = fd.type_definition.self_type.is_some();
+                self.function_definition(fd, member)
+            }
+            Statement::Trait(t) => t
+                .functions
+                .iter_mut()
+                .for_each(|f| self.function_declaration(f)),
+            Statement::TraitImpl { definitions, .. } => {
+                for fd in definitions {
+                    self.function_definition(fd, true);
+                }
+            }
+            Statement::ObjectDefinition(o) => {
+                for attr in &mut o.fields {
+                    self.member(&attr.name, true);
+                    if let Some(d) = &mut attr.default {
+                        self.expression(d);
+                    }
+                }
+                match &mut o.constructor {
+                    Constructor::Default => {}
+                    Constructor::Declaration(args, _) => {
+                        self.scoped(|r| r.function_arguments(args))
+                    }
+                    Constructor::Definition(args, _, body) => self.scoped(|r| {
+                        r.function_arguments(args);
+                        r.elements(&mut body.elements, &body.lines);
+                    }),
+                }
+                o.functions
+                    .iter_mut()
+                    .for_each(|f| self.function_declaration(f));
+            }
+            Statement::Export(Exposed::Identifier(name)) => self.reference(name),
+            Statement::Export(Exposed::TypeValue(_))
+            | Statement::Import(_)
+            | Statement::TypeDefinition(_, _)
+            | Statement::Macro(_) => {}
+        }
+    }
+
+    fn arguments(&mut self, args: &mut RigzArguments) {
+        let named = match args {
+            RigzArguments::Positional(a) => {
+                a.iter_mut().for_each(|e| self.expression(e));
+                return;
+            }
+            RigzArguments::Mixed(a, n) => {
+                a.iter_mut().for_each(|e| self.expression(e));
+                n
+            }
+            RigzArguments::Named(n) => n,
+        };
+        for (name, e) in named {
+            self.occurrence(name, None, false);
+            self.expression(e);
+        }
+    }
+
+    fn function_expression(&mut self, function: &mut FunctionExpression) {
+        match function {
+            FunctionExpression::FunctionCall(name, args) => {
+                self.reference(name);
+                self.arguments(args);
+            }
+            FunctionExpression::TypeFunctionCall(_, name, args) => {
+                self.member(name, false);
+                self.arguments(args);
+            }
+            FunctionExpression::TypeConstructor(_, args) => self.arguments(args),
+            FunctionExpression::InstanceFunctionCall(base, calls, args) => {
+                self.expression(base);
+                calls.iter().for_each(|c| self.member(c, false));
+                self.arguments(args);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::This
+            | Expression::Value(_)
+            | Expression::Symbol(_)
+            | Expression::Unquote(_) => {}
+            Expression::Identifier(id, resolution) => {
+                resolution.symbol = self.lookup(id);
+                self.table.occurrences.push(Occurrence {
+                    name: id.to_string(),
+                    symbol: resolution.symbol,
+                    declaration: false,
+                    write: false,
+                    span: resolution.span,
+                });
+            }
+            Expression::List(l) | Expression::Tuple(l) => {
+                l.iter_mut().for_each(|e| self.expression(e));
+            }
+            Expression::Map(m) => {
+                for (k, v) in m {
+                    self.expression(k);
+                    // {a} is parsed as {a = a}
+                    if k == v {
+                        *v = k.clone();
+                    } else {
+                        self.expression(v);
+                    }
+                }
+            }
+            Expression::BinExp(lhs, _, rhs) | Expression::Index(lhs, rhs) => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expression::Cast(e, _)
+            | Expression::Annotated(e, _)
+            | Expression::UnaryExp(_, e)
+            | Expression::Error(e)
+            | Expression::DoubleBang(e)
+            | Expression::Try(e)
+            | Expression::Propagate(e) => self.expression(e),
+            Expression::Return(e) => {
+                if let Some(e) = e {
+                    self.expression(e)
+                }
+            }
+            Expression::Function(f) => self.function_expression(f),
+            Expression::Scope(s) => self.scope(s),
+            Expression::If {
+                condition,
+                then,
+                branch,
+            } => {
+                self.expression(condition);
+                self.scope(then);
+                if let Some(b) = branch {
+                    self.scope(b);
+                }
+            }
+            Expression::Unless { condition, then } => {
+                self.expression(condition);
+                self.scope(then);
+            }
+            Expression::Lambda {
+                arguments, body, ..
+            } => self.scoped(|r| {
+                r.function_arguments(arguments);
+                r.expression(body);
+            }),
+            Expression::ForList {
+                var,
+                expression,
+                body,
+            } => {
+                let id = self.declare(var, SymbolKind::Variable, false);
+                self.expression(expression);
+                self.scoped(|r| {
+                    r.bind(var, id);
+                    r.expression(body);
+                });
+            }
+            Expression::ForMap {
+                k_var,
+                v_var,
+                expression,
+                key,
+                value,
+            } => {
+                let k = self.declare(k_var, SymbolKind::Variable, false);
+                let v = self.declare(v_var, SymbolKind::Variable, false);
+                self.expression(expression);
+                self.scoped(|r| {
+                    r.bind(k_var, k);
+                    r.bind(v_var, v);
+                    r.expression(key);
+                    if let Some(value) = value {
+                        r.expression(value);
+                    }
+                });
+            }
+            Expression::Into { base, next } => {
+                self.expression(base);
+                self.function_expression(next);
+            }
+            Expression::Catch { base, var, catch } => {
+                self.expression(base);
+                self.scoped(|r| {
+                    if let Some(var) = var {
+                        let id = r.declare(var, SymbolKind::Variable, false);
+                        r.bind(var, id);
+                    }
+                    r.elements(&mut catch.elements, &catch.lines);
+                });
+            }
+            Expression::With { value, body, .. } => {
+                self.expression(value);
+                self.scope(body);
+            }
+            Expression::Select { arms, after } => {
+                for arm in arms {
+                    self.expression(&mut arm.source);
+                    self.scoped(|r| {
+                        if let Some(binding) = &arm.binding {
+                            let id = r.declare(binding, SymbolKind::Variable, false);
+                            r.bind(binding, id);
+                        }
+                        r.elements(&mut arm.body.elements, &arm.body.lines);
+                    });
+                }
+                if let Some((timeout, body)) = after {
+                    self.expression(timeout);
+                    self.scope(body);
+                }
+            }
+            Expression::Comptime(body) | Expression::Lazy(body) => self.scope(body),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod symbols_tests {
+    use crate::{parse, Element, Expression, ParserOptions, SymbolId};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn shadowed_names_are_distinct_symbols() {
+        let input = r#"mut a = 1
+a = 2
+b = a
+fn foo(a) = a + b
+b = 3
+foo b"#;
+        let mut program = parse(input, ParserOptions::default()).unwrap();
+        let symbols = program.resolve_symbols();
+        let located: Vec<_> = symbols
+            .locate(input)
+            .into_iter()
+            .map(|(span, o)| (&input[span], o.symbol))
+            .collect();
+        let outer_a = located[0].1;
+        let outer_b = located[2].1;
+        let foo = located[4].1;
+        assert!(outer_a.is_some() && outer_b.is_some() && foo.is_some());
+        assert_eq!(
+            located,
+            vec![
+                ("a", outer_a),
+                ("a", outer_a),
+                ("b", outer_b),
+                ("a", outer_a),
+                ("foo", foo),
+                ("a", Some(SymbolId(3))),
+                ("a", Some(SymbolId(3))),
+                ("b", outer_b),
+                ("b", Some(SymbolId(4))),
+                ("foo", foo),
+                ("b", Some(SymbolId(4))),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn assignments_in_nested_scopes_are_new_variables() {
+        let input = r#"mut a = 1
+if true
+    a = 2
+    a
+end
+let b = 3
+mut a = b
+a"#;
+        let mut program = parse(input, ParserOptions::default()).unwrap();
+        let symbols = program.resolve_symbols();
+        let located: Vec<_> = symbols
+            .locate(input)
+            .into_iter()
+            .map(|(span, o)| (&input[span], o.symbol))
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                ("a", Some(SymbolId(0))),
+                ("a", Some(SymbolId(1))),
+                ("a", Some(SymbolId(1))),
+                ("b", Some(SymbolId(2))),
+                ("a", Some(SymbolId(3))),
+                ("b", Some(SymbolId(2))),
+                ("a", Some(SymbolId(3))),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn identifiers_store_their_symbol() {
+        let input = r#"a = 1
+fn foo(a) = a
+a"#;
+        let mut program = parse(input, ParserOptions::default()).unwrap();
+        program.resolve_symbols();
+        let Some(Element::Expression(Expression::Identifier(name, resolution))) =
+            program.elements.last()
+        else {
+            panic!(
+                "expected identifier, received {:?}",
+                program.elements.last()
+            )
+        };
+        assert_eq!(name.as_str(), "a");
+        // foo is hoisted before a is declared
+        assert_eq!(resolution.symbol, Some(SymbolId(1)));
+        let span = resolution.span.unwrap();
+        assert_eq!(&input[span.start..span.end], "a");
+        assert_eq!(span.line, 3);
+    }
+}
@@ -1,5 +1,7 @@
 use logos::{Logos, Span};
-use rigz_core::{BinaryOperation, Number, PrimitiveValue};
+use rigz_core::{
+    BinaryOperation, Decimal, Diagnostic, ErrorCode, Interned, Number, PrimitiveValue, SourceSpan,
+};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::str::ParseBoolError;
@@ -10,8 +12,10 @@ pub enum ParsingError {
     #[default]
     NonAsciiError,
     BoolParseError,
-    ParseError(String),
+    ParseError(Box<Diagnostic>),
     Eoi(String),
+    /// nesting limit from `ParserOptions::max_depth`
+    TooDeep(usize),
 }
 
 impl Error for ParsingError {}
@@ -22,8 +26,50 @@ impl Display for ParsingError {
             ParsingError::NumberParseError => write!(f, "Invalid Number"),
             ParsingError::NonAsciiError => write!(f, "Invalid Character"),
             ParsingError::BoolParseError => write!(f, "Invalid Bool"),
-            ParsingError::ParseError(s) => write!(f, "{}", s),
+            ParsingError::ParseError(d) => write!(f, "{d}"),
             ParsingError::Eoi(s) => write!(f, "Unexpected end of input: {}", s),
+            ParsingError::TooDeep(max) => {
+                write!(f, "Input is nested too deeply, exceeded {max} levels")
+            }
+        }
+    }
+}
+
+impl ParsingError {
+    /// Syntax error without a location, `Parser::parse` adds the span of the last token
+    pub fn parse_error<M: Into<String>>(message: M) -> Self {
+        Self::diagnostic(Diagnostic::new(ErrorCode::Syntax, message))
+    }
+
+    pub fn diagnostic(diagnostic: Diagnostic) -> Self {
+        ParsingError::ParseError(Box::new(diagnostic))
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ParsingError::NumberParseError => ErrorCode::InvalidNumber,
+            ParsingError::NonAsciiError => ErrorCode::InvalidCharacter,
+            ParsingError::BoolParseError => ErrorCode::InvalidBool,
+            ParsingError::ParseError(d) => d.code,
+            ParsingError::Eoi(_) => ErrorCode::UnexpectedEnd,
+            ParsingError::TooDeep(_) => ErrorCode::TooDeep,
+        }
+    }
+
+    /// Code, message, & location for the error, the message matches Display
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ParsingError::ParseError(d) => d.as_ref().clone(),
+            e => Diagnostic::new(e.code(), e.to_string()),
+        }
+    }
+
+    /// Errors that already have a location keep it, `TooDeep` is about the whole input so it never gets one
+    pub fn with_span(self, span: SourceSpan) -> Self {
+        match self {
+            ParsingError::ParseError(d) if d.span.is_some() => ParsingError::ParseError(d),
+            ParsingError::TooDeep(max) => ParsingError::TooDeep(max),
+            e => Self::diagnostic(e.to_diagnostic().with_span(span)),
         }
     }
 }
@@ -61,7 +107,11 @@ pub(crate) enum TokenValue<'lex> {
     None,
     Bool(bool),
     Number(Number),
+    /// Int literals past the range of an i64, parsed as `<digits> as Int`
+    BigInt(&'lex str),
     String(&'lex str),
+    /// `/pattern/`, see `regex_literal`, escaped slashes (`\/`) are kept
+    Regex(&'lex str),
 }
 
 impl Display for TokenValue<'_> {
@@ -69,8 +119,11 @@ impl Display for TokenValue<'_> {
         match self {
             TokenValue::None => write!(f, "none"),
             TokenValue::Bool(v) => write!(f, "{}", v),
+            TokenValue::Number(Number::Decimal(v)) => write!(f, "{}d", v),
             TokenValue::Number(v) => write!(f, "{}", v),
+            TokenValue::BigInt(v) => write!(f, "{}", v),
             TokenValue::String(v) => write!(f, "{}", v),
+            TokenValue::Regex(v) => write!(f, "/{}/", v),
         }
     }
 }
@@ -81,21 +134,73 @@ impl From<TokenValue<'_>> for PrimitiveValue {
             TokenValue::None => PrimitiveValue::None,
             TokenValue::Bool(b) => PrimitiveValue::Bool(b),
             TokenValue::Number(n) => PrimitiveValue::Number(n),
-            TokenValue::String(s) => PrimitiveValue::String(s.to_string()),
+            TokenValue::BigInt(s) | TokenValue::String(s) => {
+                PrimitiveValue::String(Interned::new(s))
+            }
+            TokenValue::Regex(s) => PrimitiveValue::String(Interned::new(&s.replace("\\/", "/"))),
         }
     }
 }
 
+/// `1.50d`, None when it doesn't fit in a Decimal
+fn decimal(literal: &str) -> Option<TokenValue<'_>> {
+    let digits = literal.trim_end_matches('d').replace('_', "");
+    digits
+        .parse::<Decimal>()
+        .ok()
+        .map(|d| TokenValue::Number(d.into()))
+}
+
+/// Pattern of a `/pattern/` literal starting at `input[start]`, None when the `/` is division. Literals are only
+/// lexed after `=`, `(`, or `,`, or at the start of an expression (see `TokenKind::precedes_regex`), so
+/// `a /b + c / 2` stays division. Literals must end on the same line
+pub(crate) fn regex_literal<'lex>(
+    previous: Option<&TokenKind>,
+    input: &'lex str,
+    start: usize,
+) -> Option<&'lex str> {
+    if !previous.is_none_or(TokenKind::precedes_regex) {
+        return None;
+    }
+    let rest = &input[start + 1..];
+    let mut escaped = false;
+    for (index, c) in rest.char_indices() {
+        match c {
+            '\n' | '\r' => return None,
+            '\\' => {
+                escaped = !escaped;
+                continue;
+            }
+            '/' if !escaped => return (index > 0).then(|| &rest[..index]),
+            _ => {}
+        }
+        escaped = false;
+    }
+    None
+}
+
+/// UTF-8 files saved on Windows often start with a BOM, it is ignored by the parser & formatter
+pub(crate) const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Words lexed as tokens, identifiers matching one of these must be written as `r#<word>`
+pub(crate) const KEYWORDS: [&str; 34] = [
+    "none", "false", "true", "let", "mut", "as", "fn", "do", "end", "if", "unless", "else", "type",
+    "trait", "impl", "self", "return", "import", "export", "var", "mod", "raise", "for", "in",
+    "object", "attr", "new", "try", "catch", "select", "comptime", "macro", "quote", "unquote",
+];
+
 #[derive(Logos, Copy, Debug, PartialEq, Clone)]
 #[logos(skip r"[ \t\f]+", error = ParsingError)]
 pub(crate) enum TokenKind<'lex> {
     #[token("\n")]
+    #[token("\r\n")]
     Newline,
     #[token("none", |_| TokenValue::None)]
     #[token("false", |_| TokenValue::Bool(false))]
     #[token("true", |_| TokenValue::Bool(true))]
     #[regex("-?[0-9][0-9_]*\\.[0-9][0-9_]*", |lex| TokenValue::Number(lex.slice().parse().unwrap()))]
-    #[regex("-?[0-9][0-9_]*", |lex| TokenValue::Number(lex.slice().parse().unwrap()))]
+    #[regex("-?[0-9][0-9_]*(\\.[0-9][0-9_]*)?d", |lex| decimal(lex.slice()))]
+    #[regex("-?[0-9][0-9_]*", |lex| lex.slice().parse().map_or(TokenValue::BigInt(lex.slice()), TokenValue::Number))]
     // todo special logic to support string escape expressions, probably as dedicated tokens
     #[regex("('[^'\n\r]*')|(\"[^\"\n\r]*\")|(`[^`\n\r]*`)", |lex| { let s = lex.slice(); TokenValue::String(&s[1..s.len()-1]) })]
     Value(TokenValue<'lex>),
@@ -107,6 +212,8 @@ pub(crate) enum TokenKind<'lex> {
     Colon,
     #[token("->")]
     Arrow,
+    #[token("=>")]
+    FatArrow,
     #[token("let")]
     Let,
     #[token("mut")]
@@ -161,6 +268,7 @@ pub(crate) enum TokenKind<'lex> {
     FunctionDef,
     #[regex("\\$[a-z_]?[A-Za-z0-9_]*", |lex| lex.slice())]
     #[regex("[a-z_][A-Za-z0-9_]*", |lex| lex.slice())]
+    #[regex("r#[a-z_][A-Za-z0-9_]*", |lex| &lex.slice()[2..])]
     Identifier(&'lex str),
     #[regex(":[A-Za-z0-9_]+", |lex| { let s = lex.slice(); Symbol(&s[1..]) })]
     Symbol(Symbol<'lex>),
@@ -200,7 +308,7 @@ pub(crate) enum TokenKind<'lex> {
     Decrement,
     #[token("self")]
     This,
-    #[regex("#[^\n]*")]
+    #[regex("#[^\r\n]*")]
     #[regex("/\\*(?:[^*]|\\*[^/])*\\*/")]
     Comment, //todo support doc-tests, nested comments
     // Reserved for future versions
@@ -243,6 +351,33 @@ pub(crate) enum TokenKind<'lex> {
     Try,
     #[token("catch")]
     Catch,
+    #[token("select")]
+    Select,
+    #[token("comptime")]
+    Comptime,
+    #[token("macro")]
+    Macro,
+    #[token("quote")]
+    Quote,
+    #[token("unquote")]
+    Unquote,
+}
+
+impl TokenKind<'_> {
+    /// Tokens a `/pattern/` literal can follow, a `/` after anything else is division. A value has to follow
+    /// binary operators & `[`, so `a == /b/` isn't ambiguous
+    pub(crate) fn precedes_regex(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Newline
+                | TokenKind::Semi
+                | TokenKind::Assign
+                | TokenKind::Lparen
+                | TokenKind::Lbracket
+                | TokenKind::Comma
+                | TokenKind::BinOp(_)
+        )
+    }
 }
 
 impl Display for TokenKind<'_> {
@@ -254,6 +389,7 @@ impl Display for TokenKind<'_> {
             TokenKind::Semi => write!(f, ";"),
             TokenKind::Colon => write!(f, ":"),
             TokenKind::Arrow => write!(f, "->"),
+            TokenKind::FatArrow => write!(f, "=>"),
             TokenKind::Into => write!(f, "|>"),
             TokenKind::Let => write!(f, "let"),
             TokenKind::Mut => write!(f, "mut"),
@@ -268,6 +404,7 @@ impl Display for TokenKind<'_> {
             TokenKind::Period => write!(f, "."),
             TokenKind::Comma => write!(f, ","),
             TokenKind::FunctionDef => write!(f, "fn"),
+            TokenKind::Identifier(id) if KEYWORDS.contains(id) => write!(f, "r#{}", id),
             TokenKind::Identifier(id) => write!(f, "{}", id),
             TokenKind::Symbol(s) => write!(f, "{}", s),
             TokenKind::Lifecycle(s) => write!(f, "@{}", s),
@@ -295,6 +432,11 @@ impl Display for TokenKind<'_> {
             TokenKind::In => write!(f, "in"),
             TokenKind::Try => write!(f, "try"),
             TokenKind::Catch => write!(f, "catch"),
+            TokenKind::Select => write!(f, "select"),
+            TokenKind::Comptime => write!(f, "comptime"),
+            TokenKind::Macro => write!(f, "macro"),
+            TokenKind::Quote => write!(f, "quote"),
+            TokenKind::Unquote => write!(f, "unquote"),
             TokenKind::Range => write!(f, ".."),
             TokenKind::RangeInclusive => write!(f, "..="),
             TokenKind::Optional => write!(f, "?"),
@@ -359,4 +501,46 @@ pub mod tests {
             ]
         )
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn tokenize_raw_identifier() {
+        let lexer = TokenKind::lexer("r#type.r#end r # comment");
+        let actual: Vec<TokenKind> = lexer.map(|t| t.unwrap()).collect();
+        assert_eq!(
+            actual,
+            vec![
+                TokenKind::Identifier("type"),
+                TokenKind::Period,
+                TokenKind::Identifier("end"),
+                TokenKind::Identifier("r"),
+                TokenKind::Comment,
+            ]
+        );
+        assert_eq!(TokenKind::Identifier("type").to_string(), "r#type");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn regex_or_division() {
+        let ident = TokenKind::Identifier("a");
+        assert_eq!(regex_literal(None, r"/\d+/", 0), Some(r"\d+"));
+        assert_eq!(
+            regex_literal(Some(&TokenKind::Assign), r"= /a\/b/", 2),
+            Some(r"a\/b")
+        );
+        assert_eq!(
+            regex_literal(Some(&TokenKind::Lparen), "(/b/", 1),
+            Some("b")
+        );
+        assert_eq!(
+            regex_literal(Some(&TokenKind::Newline), "\n/b/", 1),
+            Some("b")
+        );
+        let eq = TokenKind::BinOp(BinaryOperation::Eq);
+        assert_eq!(regex_literal(Some(&eq), "== /b/", 3), Some("b"));
+        assert_eq!(regex_literal(Some(&ident), "a /b/", 2), None);
+        assert_eq!(regex_literal(Some(&ident), "a / b / c", 2), None);
+        assert_eq!(regex_literal(Some(&ident), "a/b/c", 1), None);
+        assert_eq!(regex_literal(Some(&TokenKind::Rparen), ") /b/", 2), None);
+        assert_eq!(regex_literal(Some(&TokenKind::Comma), ", /b\n/", 2), None);
+    }
 }
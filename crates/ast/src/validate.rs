@@ -1,4 +1,12 @@
-use crate::program::{Element, Program};
+use crate::program::{
+    Assign, AssignIndex, Element, Expression, FunctionArgument, FunctionDeclaration,
+    FunctionDefinition, FunctionExpression, ImportValue, ModuleTraitDefinition, Program,
+    RigzArguments, Scope, Statement,
+};
+use crate::{EventTopics, LintLevel, SymbolKind, SymbolTable};
+use rigz_core::{Lifecycle, PrimitiveValue};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
@@ -13,6 +21,9 @@ pub enum ValidationError {
     NotImplemented(String),
     InvalidType(String),
     DownloadFailed(String),
+    Lint(String),
+    InvalidAssignment(String),
+    Comptime(String),
 }
 
 impl Error for ValidationError {}
@@ -29,12 +40,113 @@ impl Display for ValidationError {
             ValidationError::NotImplemented(e) => write!(f, "Not Implemented: {e}"),
             ValidationError::InvalidType(e) => write!(f, "Invalid Type: {e}"),
             ValidationError::DownloadFailed(e) => write!(f, "Download Failed: {e}"),
+            ValidationError::Lint(e) => write!(f, "Lint: {e}"),
+            ValidationError::InvalidAssignment(e) => write!(f, "Invalid Assignment: {e}"),
+            ValidationError::Comptime(e) => write!(f, "Comptime: {e}"),
         }
     }
 }
 
+/// Non-fatal issues found during validation, these don't prevent a program from running
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationWarning {
+    UnreachableCode(String),
+    UnusedFunction(String),
+    UnusedVariable(String),
+    UnusedImport(String),
+    ShadowedVariable(String),
+    UnmatchedEvent(String),
+}
+
+impl Display for ValidationWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationWarning::UnreachableCode(e) => write!(f, "Unreachable Code: {e}"),
+            ValidationWarning::UnusedFunction(e) => write!(f, "Unused Function: {e}"),
+            ValidationWarning::UnusedVariable(e) => write!(f, "Unused Variable: {e}"),
+            ValidationWarning::UnusedImport(e) => write!(f, "Unused Import: {e}"),
+            ValidationWarning::ShadowedVariable(e) => write!(f, "Shadowed Variable: {e}"),
+            ValidationWarning::UnmatchedEvent(e) => write!(f, "Unmatched Event: {e}"),
+        }
+    }
+}
+
+impl ValidationWarning {
+    /// Name used to configure this warning in `LintConfig`
+    pub fn rule(&self) -> &'static str {
+        match self {
+            ValidationWarning::UnreachableCode(_) => "unreachable_code",
+            ValidationWarning::UnusedFunction(_) => "unused_function",
+            ValidationWarning::UnusedVariable(_) => "unused_variable",
+            ValidationWarning::UnusedImport(_) => "unused_import",
+            ValidationWarning::ShadowedVariable(_) => "shadowed_variable",
+            ValidationWarning::UnmatchedEvent(_) => "unmatched_event",
+        }
+    }
+
+    /// Level used when `LintConfig` doesn't set this rule or `all`
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            ValidationWarning::ShadowedVariable(_) | ValidationWarning::UnmatchedEvent(_) => {
+                LintLevel::Allow
+            }
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+/// A warning & the 1 based line of the element it was found in
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintWarning {
+    pub warning: ValidationWarning,
+    pub line: Option<usize>,
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            None => write!(f, "{}", self.warning),
+            Some(line) => write!(f, "{} on line {line}", self.warning),
+        }
+    }
+}
+
+impl Borrow<ValidationWarning> for LintWarning {
+    fn borrow(&self) -> &ValidationWarning {
+        &self.warning
+    }
+}
+
 impl Program {
+    /// Reports unreachable code, unused functions, variables, and imports, and `let` shadowing a
+    /// variable in the same scope. Names starting with `_` are never reported as unused. Identifiers
+    /// are resolved first, see `Program::resolve_symbols`, so each variable & function is checked
+    /// separately from others with the same name.
+    pub fn lint(&mut self) -> Vec<LintWarning> {
+        self.lint_with_modules(&[])
+    }
+
+    /// Same as `lint`, `import Module` is only used when the program calls one of the functions in its
+    /// definition. Without a definition, any call the program doesn't define may come from the module.
+    pub fn lint_with_modules(&mut self, modules: &[&ModuleTraitDefinition]) -> Vec<LintWarning> {
+        let symbols = self.resolve_symbols();
+        Lint::run(&self.elements, &self.lines).finish(&symbols, modules)
+    }
+
+    /// Topics of the events this program sends & handles, use `EventTopics::check` once the topics of every file
+    /// in the project are merged
+    pub fn event_topics(&self) -> EventTopics {
+        Lint::run(&self.elements, &self.lines).topics
+    }
+
     pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(e) = Lint::run(&self.elements, &self.lines)
+            .errors
+            .into_iter()
+            .next()
+        {
+            return Err(e);
+        }
         match self.elements.last() {
             None => Err(ValidationError::MissingExpression(
                 "Invalid Program, no elements".to_string(),
@@ -48,3 +160,575 @@ impl Program {
         }
     }
 }
+
+// handled by the parser, not defined in any module
+const BUILT_IN_FUNCTIONS: [&str; 7] = [
+    "send",
+    "receive",
+    "log",
+    "puts",
+    "spawn",
+    "broadcast",
+    "sleep",
+];
+
+/// The file or url of an import, embedded imports are named by the file they were bundled from
+fn import_path(import: ImportValue) -> Option<String> {
+    match import {
+        ImportValue::FilePath(path) | ImportValue::UrlPath(path) => Some(path),
+        ImportValue::Embedded(import, _) => import_path(*import),
+        ImportValue::TypeValue(_) | ImportValue::Lazy(_) => None,
+    }
+}
+
+fn module_functions(module: &ModuleTraitDefinition) -> impl Iterator<Item = &str> {
+    module.definition.functions.iter().map(|f| match f {
+        FunctionDeclaration::Declaration { name, .. } => name.as_str(),
+        FunctionDeclaration::Definition(fd) => fd.name.as_str(),
+    })
+}
+
+/// Functions, variables, and types defined by the top level of an embedded import
+fn embedded_definitions(scope: &Scope) -> HashSet<String> {
+    let mut defined = HashSet::new();
+    for element in &scope.elements {
+        let Element::Statement(s) = element else {
+            continue;
+        };
+        match s {
+            Statement::FunctionDefinition(fd) => {
+                defined.insert(fd.name.clone());
+            }
+            Statement::Assignment { lhs, .. } => match lhs {
+                Assign::Identifier(name, _) | Assign::TypedIdentifier(name, _, _) => {
+                    defined.insert(name.to_string());
+                }
+                Assign::Tuple(names) => defined.extend(names.iter().map(|(n, _)| n.to_string())),
+                Assign::This | Assign::InstanceSet(_, _) => {}
+            },
+            Statement::ObjectDefinition(o) => {
+                defined.insert(o.rigz_type.to_string());
+            }
+            Statement::Trait(t) => {
+                defined.insert(t.name.clone());
+            }
+            _ => {}
+        }
+    }
+    defined
+}
+
+/// `return` & `raise`, or a block or `if`/`else` that always reaches one
+fn exits(expression: &Expression) -> bool {
+    let scope_exits = |scope: &Scope| {
+        scope
+            .elements
+            .iter()
+            .any(|e| matches!(e, Element::Expression(e) if exits(e)))
+    };
+    match expression {
+        Expression::Return(_) | Expression::Error(_) => true,
+        Expression::Scope(scope) => scope_exits(scope),
+        Expression::If {
+            then,
+            branch: Some(branch),
+            ..
+        } => scope_exits(then) && scope_exits(branch),
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+struct Lint {
+    warnings: Vec<LintWarning>,
+    /// functions that aren't called by the VM, i.e. `@test` or `@on`
+    defined: HashSet<String>,
+    imports: Vec<(ImportValue, Option<usize>)>,
+    /// every name a call or identifier could resolve to within the program
+    locals: HashSet<String>,
+    referenced: HashSet<String>,
+    types: HashSet<String>,
+    /// variables declared in each nested scope and whether they're mutable
+    scopes: Vec<HashMap<String, bool>>,
+    /// innermost `if`, `else`, `unless`, or `for` body being linted
+    conditional: Option<&'static str>,
+    errors: Vec<ValidationError>,
+    topics: EventTopics,
+    /// line of the element being linted
+    line: Option<usize>,
+}
+
+impl Lint {
+    fn run(elements: &[Element], lines: &[usize]) -> Self {
+        let mut lint = Lint {
+            scopes: vec![HashMap::new()],
+            ..Default::default()
+        };
+        lint.elements(elements, lines, "main");
+        lint
+    }
+
+    fn warn(&mut self, warning: ValidationWarning) {
+        self.warnings.push(LintWarning {
+            warning,
+            line: self.line,
+        })
+    }
+
+    /// Each scope runs in its own call frame, so an assignment without `let` or `mut` creates a
+    /// new variable instead of updating a mutable variable from an outer scope
+    fn declare(&mut self, name: &str, mutable: bool, shadow: bool) {
+        let Some((current, outer)) = self.scopes.split_last_mut() else {
+            return;
+        };
+        if current.contains_key(name) {
+            if shadow && !mutable {
+                self.warnings.push(LintWarning {
+                    warning: ValidationWarning::ShadowedVariable(format!(
+                        "let {name} shadows an existing variable in the same scope"
+                    )),
+                    line: self.line,
+                });
+            }
+        } else if !shadow && outer.iter().any(|s| s.get(name) == Some(&true)) {
+            self.errors.push(ValidationError::InvalidAssignment(format!(
+                "{name} = ... creates a new variable that shadows mut {name} from an outer scope, use `let {name}` to shadow it intentionally"
+            )));
+        }
+        current.insert(name.to_string(), mutable);
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    /// Functions are registered globally when the program is prepared, not when the body runs
+    fn conditional(&mut self, body: &'static str, f: impl FnOnce(&mut Self)) {
+        let outer = self.conditional.replace(body);
+        f(self);
+        self.conditional = outer;
+    }
+
+    fn finish(
+        mut self,
+        symbols: &SymbolTable,
+        modules: &[&ModuleTraitDefinition],
+    ) -> Vec<LintWarning> {
+        let read: HashSet<_> = symbols
+            .occurrences
+            .iter()
+            .filter(|o| !o.declaration && !o.write)
+            .filter_map(|o| o.symbol)
+            .collect();
+        for symbol in &symbols.symbols {
+            if symbol.name.starts_with('_') || read.contains(&symbol.id) {
+                continue;
+            }
+            let warning = match symbol.kind {
+                SymbolKind::Function if self.defined.contains(&symbol.name) => {
+                    ValidationWarning::UnusedFunction(format!("{} is never called", symbol.name))
+                }
+                SymbolKind::Variable => ValidationWarning::UnusedVariable(format!(
+                    "{} is assigned but never read",
+                    symbol.name
+                )),
+                _ => continue,
+            };
+            self.warnings.push(LintWarning {
+                warning,
+                line: symbol.line,
+            });
+        }
+
+        // calls that aren't defined by the program come from an import or an auto imported module
+        let unresolved: Vec<_> = self
+            .referenced
+            .iter()
+            .filter(|name| {
+                !self.locals.contains(*name) && !BUILT_IN_FUNCTIONS.contains(&name.as_str())
+            })
+            .collect();
+        let module = |name: &str| modules.iter().find(|m| m.definition.name == name);
+        let embedded: Vec<_> = self
+            .imports
+            .iter()
+            .filter_map(|(import, _)| match import {
+                ImportValue::Embedded(_, scope) => Some(embedded_definitions(scope)),
+                _ => None,
+            })
+            .collect();
+        let provided = |name: &str| {
+            modules
+                .iter()
+                .filter(|m| {
+                    m.auto_import
+                        || self.imports.iter().any(|(import, _)| {
+                            matches!(import, ImportValue::TypeValue(t) if *t == m.definition.name)
+                        })
+                })
+                .any(|m| module_functions(m).any(|f| f == name))
+                || embedded.iter().any(|defined| defined.contains(name))
+        };
+        // file & url imports aren't read when linting, so they may define any call no other import does
+        let unknown = unresolved.iter().any(|name| !provided(name));
+        for (import, line) in std::mem::take(&mut self.imports) {
+            let (import, lazy) = match import {
+                ImportValue::Lazy(import) => (*import, "lazy "),
+                import => (import, ""),
+            };
+            let used = match &import {
+                ImportValue::TypeValue(name) => {
+                    self.types.contains(name)
+                        || match module(name) {
+                            Some(m) => module_functions(m)
+                                .any(|f| unresolved.iter().any(|name| name.as_str() == f)),
+                            None => unknown,
+                        }
+                }
+                ImportValue::Embedded(_, scope) => {
+                    let defined = embedded_definitions(scope);
+                    unresolved.iter().any(|name| defined.contains(*name))
+                        || self.types.iter().any(|t| defined.contains(t))
+                }
+                _ => unknown,
+            };
+            if used {
+                continue;
+            }
+            let name = match import {
+                ImportValue::TypeValue(name) => name,
+                import => match import_path(import) {
+                    Some(name) => format!("{lazy}\"{name}\""),
+                    None => continue,
+                },
+            };
+            self.warnings.push(LintWarning {
+                warning: ValidationWarning::UnusedImport(format!("{name} is never used")),
+                line,
+            });
+        }
+        self.warnings
+    }
+
+    fn assign(&mut self, lhs: &Assign, read: bool, shadow: bool) {
+        let names = match lhs {
+            Assign::This => return,
+            Assign::InstanceSet(base, indexes) => {
+                self.expression(base);
+                for index in indexes {
+                    if let AssignIndex::Index(e) = index {
+                        self.expression(e);
+                    }
+                }
+                return;
+            }
+            Assign::Identifier(name, mutable) | Assign::TypedIdentifier(name, mutable, _) => {
+                vec![(name, *mutable)]
+            }
+            Assign::Tuple(names) => names.iter().map(|(name, m)| (name, *m)).collect(),
+        };
+        for (name, mutable) in names {
+            self.locals.insert(name.to_string());
+            if read {
+                self.referenced.insert(name.to_string());
+            } else {
+                self.declare(name, mutable, shadow);
+            }
+        }
+    }
+
+    fn function_arguments(&mut self, arguments: &[FunctionArgument]) {
+        for arg in arguments {
+            self.locals.insert(arg.name.clone());
+            self.declare(&arg.name, arg.function_type.mutable, true);
+            if let Some(d) = &arg.default {
+                self.expression(d);
+            }
+        }
+    }
+
+    fn elements(&mut self, elements: &[Element], lines: &[usize], scope: &str) {
+        let outer = self.line;
+        let len = elements.len();
+        let mut reported = false;
+        for (index, element) in elements.iter().enumerate() {
+            self.line = lines.get(index).copied().or(outer);
+            match element {
+                Element::Statement(s) => self.statement(s),
+                Element::Expression(e) => self.expression(e),
+            }
+            let exits = matches!(element, Element::Expression(e) if exits(e));
+            if exits && !reported && index + 1 < len {
+                reported = true;
+                let remaining = len - index - 1;
+                self.line = lines.get(index + 1).copied().or(outer);
+                self.warn(ValidationWarning::UnreachableCode(format!(
+                    "{remaining} element(s) after exit in {scope} will never run"
+                )));
+            }
+        }
+        self.line = outer;
+    }
+
+    fn scope(&mut self, scope: &Scope, name: &str) {
+        self.scoped(|l| l.elements(&scope.elements, &scope.lines, name))
+    }
+
+    fn function_definition(&mut self, fd: &FunctionDefinition, track: bool) {
+        // lifecycle functions (@on, @test, etc.) are called by the VM
+        let called_by_vm = match &fd.lifecycle {
+            None | Some(Lifecycle::Memo(_)) => false,
+            Some(Lifecycle::Composite(all)) => all.iter().any(|l| !matches!(l, Lifecycle::Memo(_))),
+            Some(_) => true,
+        };
+        if track && !called_by_vm {
+            self.defined.insert(fd.name.clone());
+        }
+        let handles = |l: &Lifecycle| match l {
+            Lifecycle::On(e) => Some(e.event.clone()),
+            _ => None,
+        };
+        let topics: Vec<_> = match &fd.lifecycle {
+            Some(Lifecycle::Composite(all)) => all.iter().filter_map(handles).collect(),
+            Some(l) => handles(l).into_iter().collect(),
+            None => vec![],
+        };
+        for topic in topics {
+            self.topics.handlers.push((topic, fd.name.clone()));
+        }
+        if let Some(body) = self.conditional {
+            self.errors.push(ValidationError::InvalidFunction(format!(
+                "fn {} can't be defined inside {body}, functions are defined before the program runs, move it to the top level or use a lambda",
+                fd.name
+            )));
+        }
+        self.locals.insert(fd.name.clone());
+        self.scoped(|l| {
+            l.function_arguments(&fd.type_definition.arguments);
+            l.elements(&fd.body.elements, &fd.body.lines, fd.name.as_str());
+        });
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assignment {
+                lhs,
+                expression,
+                shadow,
+            } => {
+                self.expression(expression);
+                self.assign(lhs, false, *shadow);
+            }
+            Statement::BinaryAssignment {
+                lhs, expression, ..
+            } => {
+                self.expression(expression);
+                self.assign(lhs, true, false);
+            }
+            Statement::FunctionDefinition(fd) => self.function_definition(fd, true),
+            Statement::Trait(t) => {
+                for f in &t.functions {
+                    if let FunctionDeclaration::Definition(fd) = f {
+                        self.function_definition(fd, false);
+                    }
+                }
+            }
+            Statement::TraitImpl { definitions, .. } => {
+                for fd in definitions {
+                    self.function_definition(fd, false);
+                }
+            }
+            Statement::ObjectDefinition(o) => {
+                for f in &o.functions {
+                    if let FunctionDeclaration::Definition(fd) = f {
+                        self.function_definition(fd, false);
+                    }
+                }
+            }
+            Statement::Import(i) => self.imports.push((i.clone(), self.line)),
+            Statement::Export(_) | Statement::TypeDefinition(_, _) | Statement::Macro(_) => {}
+        }
+    }
+
+    fn arguments(&mut self, args: &RigzArguments) {
+        match args {
+            RigzArguments::Positional(a) => a.iter().for_each(|e| self.expression(e)),
+            RigzArguments::Mixed(a, n) => {
+                a.iter().for_each(|e| self.expression(e));
+                n.iter().for_each(|(_, e)| self.expression(e));
+            }
+            RigzArguments::Named(n) => n.iter().for_each(|(_, e)| self.expression(e)),
+        }
+    }
+
+    fn sent(&mut self, args: &RigzArguments) {
+        let topic = match args {
+            RigzArguments::Positional(a) | RigzArguments::Mixed(a, _) => a.first(),
+            RigzArguments::Named(_) => None,
+        };
+        match topic {
+            Some(Expression::Value(PrimitiveValue::String(topic))) => {
+                self.topics.sent.push(topic.to_string())
+            }
+            _ => self.topics.dynamic = true,
+        }
+    }
+
+    fn function_expression(&mut self, function: &FunctionExpression) {
+        match function {
+            FunctionExpression::FunctionCall(name, args) => {
+                if name == "send" || name == "broadcast" {
+                    self.sent(args);
+                }
+                self.referenced.insert(name.to_string());
+                self.arguments(args);
+            }
+            FunctionExpression::TypeFunctionCall(rigz_type, name, args) => {
+                self.types.insert(rigz_type.to_string());
+                self.referenced.insert(name.clone());
+                self.locals.insert(name.clone());
+                self.arguments(args);
+            }
+            FunctionExpression::TypeConstructor(rigz_type, args) => {
+                self.types.insert(rigz_type.to_string());
+                self.arguments(args);
+            }
+            FunctionExpression::InstanceFunctionCall(base, calls, args) => {
+                self.expression(base);
+                self.referenced.extend(calls.iter().cloned());
+                // instance calls are resolved from the type of base, not the program
+                self.locals.extend(calls.iter().cloned());
+                self.arguments(args);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Value(PrimitiveValue::Type(rigz_type)) => {
+                self.types.insert(rigz_type.to_string());
+            }
+            Expression::This
+            | Expression::Value(_)
+            | Expression::Symbol(_)
+            | Expression::Unquote(_) => {}
+            Expression::Identifier(id, _) => {
+                self.referenced.insert(id.to_string());
+            }
+            Expression::List(l) | Expression::Tuple(l) => {
+                l.iter().for_each(|e| self.expression(e));
+            }
+            Expression::Map(m) => {
+                for (k, v) in m {
+                    self.expression(k);
+                    self.expression(v);
+                }
+            }
+            Expression::BinExp(lhs, _, rhs) | Expression::Index(lhs, rhs) => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expression::Cast(e, rigz_type) | Expression::Annotated(e, rigz_type) => {
+                self.types.insert(rigz_type.to_string());
+                self.expression(e);
+            }
+            Expression::UnaryExp(_, e)
+            | Expression::Error(e)
+            | Expression::DoubleBang(e)
+            | Expression::Try(e)
+            | Expression::Propagate(e) => self.expression(e),
+            Expression::Return(e) => {
+                if let Some(e) = e {
+                    self.expression(e)
+                }
+            }
+            Expression::Function(f) => self.function_expression(f),
+            Expression::Scope(s) => self.scope(s, "do"),
+            Expression::If {
+                condition,
+                then,
+                branch,
+            } => {
+                self.expression(condition);
+                self.conditional("if", |l| l.scope(then, "if"));
+                if let Some(b) = branch {
+                    self.conditional("else", |l| l.scope(b, "else"));
+                }
+            }
+            Expression::Unless { condition, then } => {
+                self.expression(condition);
+                self.conditional("unless", |l| l.scope(then, "unless"));
+            }
+            Expression::Lambda {
+                arguments, body, ..
+            } => self.scoped(|l| {
+                l.function_arguments(arguments);
+                l.expression(body);
+            }),
+            Expression::ForList {
+                var,
+                expression,
+                body,
+            } => {
+                self.locals.insert(var.clone());
+                self.expression(expression);
+                self.conditional("for", |l| l.expression(body));
+            }
+            Expression::ForMap {
+                k_var,
+                v_var,
+                expression,
+                key,
+                value,
+            } => {
+                self.locals.insert(k_var.clone());
+                self.locals.insert(v_var.clone());
+                self.expression(expression);
+                self.conditional("for", |l| {
+                    l.expression(key);
+                    if let Some(v) = value {
+                        l.expression(v);
+                    }
+                });
+            }
+            Expression::Into { base, next } => {
+                self.expression(base);
+                self.function_expression(next);
+            }
+            Expression::Catch { base, var, catch } => {
+                self.expression(base);
+                self.scoped(|l| {
+                    if let Some(var) = var {
+                        l.locals.insert(var.clone());
+                        l.declare(var, false, true);
+                    }
+                    l.elements(&catch.elements, &catch.lines, "catch");
+                });
+            }
+            Expression::With { value, body, .. } => {
+                self.expression(value);
+                self.scope(body, "with");
+            }
+            Expression::Select { arms, after } => {
+                for arm in arms {
+                    self.expression(&arm.source);
+                    self.scoped(|l| {
+                        if let Some(binding) = &arm.binding {
+                            l.locals.insert(binding.clone());
+                            l.declare(binding, false, true);
+                        }
+                        l.elements(&arm.body.elements, &arm.body.lines, "select");
+                    });
+                }
+                if let Some((timeout, body)) = after {
+                    self.expression(timeout);
+                    self.scope(body, "select");
+                }
+            }
+            Expression::Comptime(body) => self.scope(body, "comptime"),
+            Expression::Lazy(body) => self.scope(body, "lazy"),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use rigz_ast::*;
-use rigz_core::{BinaryOperation, CustomType, PrimitiveValue, RigzType};
+use rigz_core::{
+    BinaryOperation, CustomType, Decimal, ErrorCode, PrimitiveValue, RigzType, SourceSpan,
+};
 use wasm_bindgen_test::*;
 
 macro_rules! test_parse {
@@ -72,7 +74,61 @@ pub mod invalid {
         if_reserved "if = 1",
         else_reserved "else = 1",
         fn_reserved "fn = 1",
+        var_args_map_function "fn add{a, var b} = b",
+        unquote_outside_macro "unquote(a)",
+        unquote_unknown_argument "macro twice(a)\n  quote unquote(b) * 2\nend",
+        macro_wrong_arguments "macro twice(a)\n  quote unquote(a) * 2\nend\ntwice 1, 2",
+        list_annotated_as_map "a = []: {String, Int}",
+        on_unknown_signal "@on(\"signal:USR3\")\nfn stop = none",
     );
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn error_codes() {
+        let error = parse("\n  a = 1\n  b = )", ParserOptions::default()).unwrap_err();
+        let diagnostic = error.to_diagnostic();
+        assert_eq!(diagnostic.code, ErrorCode::InvalidExpression);
+        assert_eq!(
+            diagnostic.span,
+            Some(SourceSpan {
+                start: 15,
+                end: 16,
+                line: 3
+            })
+        );
+        assert!(diagnostic.help.is_some());
+
+        let error = parse("1 +", ParserOptions::default()).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::UnexpectedEnd);
+        assert_eq!(error.to_diagnostic().span.map(|s| s.start), Some(2));
+
+        let error = parse("  ", ParserOptions::default()).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::EmptyInput);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn too_deep() {
+        let options = ParserOptions {
+            max_depth: 32,
+            ..Default::default()
+        };
+        let parens = format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+        let error = parse(&parens, options.clone()).unwrap_err();
+        assert_eq!(error, ParsingError::TooDeep(32));
+        assert_eq!(error.code(), ErrorCode::TooDeep);
+
+        let lists = format!("{}1{}", "[".repeat(10_000), "]".repeat(10_000));
+        assert_eq!(
+            parse(&lists, options.clone()).unwrap_err(),
+            ParsingError::TooDeep(32)
+        );
+
+        // operators are parsed in a loop, long chains aren't nested
+        let chain = format!("1{}", " + 1".repeat(100));
+        assert!(parse(&chain, options.clone()).is_ok());
+
+        let nested = format!("{}1{}", "(".repeat(8), ")".repeat(8));
+        assert!(parse(&nested, options).is_ok());
+    }
 }
 
 pub mod valid {
@@ -84,6 +140,7 @@ pub mod valid {
         valid_function "fn hello = none",
         valid_function_default_type "fn hello -> Any!? = none",
         valid_function_dollar_sign "fn $ = none",
+        on_signal "@on(\"signal:TERM\")\nfn stop = none",
         outer_paren_func "(foo 1, 2, 3)",
         fn_call_with_parens "foo(1, 2, 3)",
         named_args_in_func "foo a: 1, b: 2, c: 3",
@@ -168,12 +225,13 @@ test_parse_equivalent! {
                     },
                     body: Scope {
                      elements: vec![
-                        Element::Expression(Expression::Value(PrimitiveValue::String("hi there".to_string())))
+                        Element::Expression(Expression::Value(PrimitiveValue::String("hi there".into())))
                     ],
+                        ..Default::default()
                     },
                 lifecycle: None
                 })),
-                Element::Expression(Expression::Identifier("hello".to_string()))
+                Element::Expression(Expression::identifier("hello"))
             ];
     define_function r#"
             fn hello
@@ -198,12 +256,13 @@ test_parse_equivalent! {
                     },
                     body: Scope {
                     elements: vec![
-                        Element::Expression(Expression::Value(PrimitiveValue::String("hi there".to_string())))
+                        Element::Expression(Expression::Value(PrimitiveValue::String("hi there".into())))
                     ],
+                            ..Default::default()
                         },
                 lifecycle: None
                 })),
-                Element::Expression(Expression::Identifier("hello".to_string()))
+                Element::Expression(Expression::identifier("hello"))
             ];
     define_function_args r#"
             fn add(a, b, c)
@@ -254,16 +313,17 @@ test_parse_equivalent! {
                             "c".into()
                         ).into(),
                     ],
+                    ..Default::default()
                 },
                 lifecycle: None
             })),
-            Element::Expression(FunctionExpression::FunctionCall("add".to_string(), vec![Expression::Value(PrimitiveValue::Number(1.into())), Expression::Value(PrimitiveValue::Number(2.into())), Expression::Value(PrimitiveValue::Number(3.into()))].into()).into())
+            Element::Expression(FunctionExpression::FunctionCall("add".into(), vec![Expression::Value(PrimitiveValue::Number(1.into())), Expression::Value(PrimitiveValue::Number(2.into())), Expression::Value(PrimitiveValue::Number(3.into()))].into()).into())
         ];
 }
 
 test_parse! {
     symbols "foo :hello" = vec![
-        Element::Expression(FunctionExpression::FunctionCall("foo".to_string(), vec![Expression::Symbol("hello".to_string())].into()).into())
+        Element::Expression(FunctionExpression::FunctionCall("foo".into(), vec![Expression::Symbol("hello".into())].into()).into())
     ],
     traits r#"trait Hello
             fn foo
@@ -316,8 +376,9 @@ test_parse! {
                         },
                         body: Scope {
                             elements: vec![
-                                Element::Expression(FunctionExpression::FunctionCall("puts".to_string(), vec!["message".into()].into()).into())
-                            ]
+                                Element::Expression(FunctionExpression::FunctionCall("puts".into(), vec!["message".into()].into()).into())
+                            ],
+                            ..Default::default()
                         },
                         lifecycle: None
                  }),
@@ -362,20 +423,21 @@ test_parse! {
                 Expression::List(
                     vec![
                         Expression::Value(PrimitiveValue::Number(1.into())),
-                        Expression::Value(PrimitiveValue::String("2".to_string())),
-                        Expression::Map(vec![(Expression::Identifier("a".to_string()), Expression::Value(PrimitiveValue::Number(3.into())))]),
+                        Expression::Value(PrimitiveValue::String("2".into())),
+                        Expression::Map(vec![(Expression::identifier("a"), Expression::Value(PrimitiveValue::Number(3.into())))]),
                     ]
                 )
             )
         ],
     assign "a = 7 - 0" = vec![
             Element::Statement(Statement::Assignment {
-                lhs: Assign::Identifier("a".to_string(), false),
+                lhs: Assign::Identifier("a".into(), false),
                 expression: Expression::BinExp(
                     Box::new(Expression::Value(PrimitiveValue::Number(7.into()))),
                     BinaryOperation::Sub,
                     Box::new(Expression::Value(PrimitiveValue::Number(0.into())))
                 ),
+                shadow: false,
             })
         ],
     multi_complex_parens "1 + (2 * (2 - 4)) / 4" = vec![
@@ -404,8 +466,9 @@ test_parse! {
         ],
     union_type "a: String || Number || Bool = false" = vec![
             Statement::Assignment {
-                lhs: Assign::TypedIdentifier("a".to_string(), false, RigzType::Union(vec![RigzType::String, RigzType::Number, RigzType::Bool])),
+                lhs: Assign::TypedIdentifier("a".into(), false, RigzType::Union(vec![RigzType::String, RigzType::Number, RigzType::Bool])),
                 expression: Expression::Value(false.into()),
+                shadow: false,
             }.into()
         ],
     composite_type r#"
@@ -430,7 +493,7 @@ test_parse! {
                 ],
             })).into(),
             Statement::Assignment {
-                lhs: Assign::TypedIdentifier("a".to_string(), false, RigzType::Composite(vec![RigzType::Custom(CustomType {
+                lhs: Assign::TypedIdentifier("a".into(), false, RigzType::Composite(vec![RigzType::Custom(CustomType {
                     name: "Foo".to_string(),
                     fields: vec![],
                 }), RigzType::Custom(CustomType {
@@ -438,9 +501,10 @@ test_parse! {
                     fields: vec![],
                 })])),
                 expression: Expression::Map(vec![
-                    (Expression::Identifier("foo".to_string()), Expression::Value(1.into())),
-                    (Expression::Identifier("bar".to_string()), Expression::Value(7.into())),
-                ])
+                    (Expression::identifier("foo"), Expression::Value(1.into())),
+                    (Expression::identifier("bar"), Expression::Value(7.into())),
+                ]),
+                shadow: false,
             }.into()
         ],
     union_composite_type_parens r#"
@@ -471,11 +535,21 @@ test_parse! {
                 })])
             ])).into(),
             Statement::Assignment {
-                lhs: Assign::TypedIdentifier("s".to_string(), true, RigzType::Custom(CustomType {
+                lhs: Assign::TypedIdentifier("s".into(), true, RigzType::Custom(CustomType {
                     name: "Result".to_string(),
                     fields: vec![],
                 })),
-                expression: Expression::Value("".into())
+                expression: Expression::Value("".into()),
+                shadow: true,
+            }.into()
+        ],
+    raw_identifier_keyword "let r#type = { r#end = 1 }" = vec![
+            Statement::Assignment {
+                lhs: Assign::Identifier("type".into(), false),
+                expression: Expression::Map(vec![
+                    (Expression::identifier("end"), Expression::Value(1.into())),
+                ]),
+                shadow: true,
             }.into()
         ],
     define_function_named_args r#"
@@ -519,17 +593,18 @@ test_parse! {
                     elements: vec![
                     Element::Expression(Expression::binary(
                             Expression::binary(
-                                Expression::Identifier("a".to_string()),
+                                Expression::identifier("a"),
                                 BinaryOperation::Add,
-                                Expression::Identifier("b".to_string())
+                                Expression::identifier("b")
                             ),
                             BinaryOperation::Add,
-                            Expression::Identifier("c".to_string()))
+                            Expression::identifier("c"))
                         )
                     ],
+                    ..Default::default()
                 }
             })),
-            Element::Expression(FunctionExpression::FunctionCall("add".to_string(), RigzArguments::Named(vec![("a".to_string(), Expression::Value(1.into())), ("b".to_string(), Expression::Value(2.into())), ("c".to_string(), Expression::Value(3.into()))])).into())
+            Element::Expression(FunctionExpression::FunctionCall("add".into(), RigzArguments::Named(vec![("a".to_string(), Expression::Value(1.into())), ("b".to_string(), Expression::Value(2.into())), ("c".to_string(), Expression::Value(3.into()))])).into())
         ],
     define_function_named_args_var r#"
         fn add{a, b, c}
@@ -573,21 +648,23 @@ test_parse! {
                     elements: vec![
                     Element::Expression(Expression::binary(
                             Expression::binary(
-                                Expression::Identifier("a".to_string()),
+                                Expression::identifier("a"),
                                 BinaryOperation::Add,
-                                Expression::Identifier("b".to_string())
+                                Expression::identifier("b")
                             ),
                             BinaryOperation::Add,
-                            Expression::Identifier("c".to_string()))
+                            Expression::identifier("c"))
                         )
                     ],
+                    ..Default::default()
                 }
             })),
             Element::Statement(Statement::Assignment {
-                lhs: Assign::Identifier("v".to_string(), false),
-                expression: Expression::Map(vec![(Expression::Identifier("a".to_string()), Expression::Value(PrimitiveValue::Number(1.into()))), (Expression::Identifier("b".to_string()), Expression::Value(PrimitiveValue::Number(2.into()))), (Expression::Identifier("c".to_string()), Expression::Value(PrimitiveValue::Number(3.into())))]),
+                lhs: Assign::Identifier("v".into(), false),
+                expression: Expression::Map(vec![(Expression::identifier("a"), Expression::Value(PrimitiveValue::Number(1.into()))), (Expression::identifier("b"), Expression::Value(PrimitiveValue::Number(2.into()))), (Expression::identifier("c"), Expression::Value(PrimitiveValue::Number(3.into())))]),
+                shadow: false,
             }),
-            Element::Expression(FunctionExpression::FunctionCall("add".to_string(), vec![Expression::Identifier("v".to_string())].into()).into())
+            Element::Expression(FunctionExpression::FunctionCall("add".into(), vec![Expression::identifier("v")].into()).into())
         ],
     lambda_instance_call r#"[1, 2, 3, 'a', 'b'].filter { |v| v.is_num }.map(|v| v * v)"# = vec![
             Element::Expression(
@@ -616,7 +693,7 @@ test_parse! {
                             ],
                             var_args_start: None,
                             body: FunctionExpression::InstanceFunctionCall(
-                                    Expression::Identifier("v".to_string()).into(),
+                                    Expression::identifier("v").into(),
                                     vec!["is_num".to_string()],
                                     RigzArguments::Positional(vec![])
                                 ).into()
@@ -635,12 +712,199 @@ test_parse! {
                         rest: false
                     }],
                     var_args_start: None,
-                    body: Expression::BinExp(Expression::Identifier("v".to_string()).into(), BinaryOperation::Mul, Expression::Identifier("v".to_string()).into()).into()
+                    body: Expression::BinExp(Expression::identifier("v").into(), BinaryOperation::Mul, Expression::identifier("v").into()).into()
                 }]
                     )
                 ).into()
             )
         ],
+    lazy_import r#"import lazy "lib.rg""# = vec![
+        Element::Statement(Statement::Import(ImportValue::Lazy(Box::new(ImportValue::FilePath("lib.rg".to_string())))))
+    ],
+    embedded_import "import lazy \"lib.rg\" do\n  fn one = 1\nend" = vec![
+        Element::Statement(Statement::Import(ImportValue::Lazy(Box::new(ImportValue::Embedded(
+            Box::new(ImportValue::FilePath("lib.rg".to_string())),
+            Scope {
+                elements: vec![Element::Statement(Statement::FunctionDefinition(FunctionDefinition {
+                    name: "one".to_string(),
+                    lifecycle: None,
+                    type_definition: FunctionSignature {
+                        arguments: vec![],
+                        return_type: FunctionType::new(RigzType::default()),
+                        self_type: None,
+                        arg_type: ArgType::Positional,
+                        var_args_start: None,
+                    },
+                    body: Scope {
+                        elements: vec![Element::Expression(Expression::Value(1.into()))],
+                        lines: vec![],
+                    },
+                }))],
+                lines: vec![],
+            },
+        )))))
+    ],
+    large_int_literal "99_999_999_999_999_999_999" = vec![
+        Element::Expression(Expression::Cast(Box::new(Expression::Value("99999999999999999999".into())), RigzType::Int))
+    ],
+    decimal_literal "1_000.50d" = vec![
+        Element::Expression(Expression::Value(Decimal::new(100050, 2).into()))
+    ],
+    list_spread "[1, ..a]" = vec![
+        Element::Expression(Expression::binary(
+            Expression::List(vec![Expression::Value(1.into())]),
+            BinaryOperation::Add,
+            Expression::Cast(Box::new(Expression::identifier("a")), RigzType::List(Box::new(RigzType::Any))),
+        ))
+    ],
+    map_spread "{..defaults, a = 1}" = vec![
+        Element::Expression(Expression::binary(
+            Expression::Cast(
+                Box::new(Expression::identifier("defaults")),
+                RigzType::Map(Box::new(RigzType::Any), Box::new(RigzType::Any)),
+            ),
+            BinaryOperation::Add,
+            Expression::Map(vec![(Expression::identifier("a"), Expression::Value(1.into()))]),
+        ))
+    ],
+    module_override r#"with JSON.parse = |s| s do
+        JSON.parse '1'
+    end"# = vec![
+        Element::Expression(Expression::With {
+            module: "JSON".to_string(),
+            function: "parse".to_string(),
+            value: Expression::Lambda {
+                arguments: vec![FunctionArgument {
+                    name: "s".to_string(),
+                    default: None,
+                    function_type: FunctionType {
+                        rigz_type: RigzType::Any,
+                        mutable: false
+                    },
+                    var_arg: false,
+                    rest: false
+                }],
+                var_args_start: None,
+                body: Expression::identifier("s").into()
+            }.into(),
+            body: Scope {
+                elements: vec![Element::Expression(FunctionExpression::TypeFunctionCall(
+                    RigzType::Custom(CustomType {
+                        name: "JSON".to_string(),
+                        fields: vec![],
+                    }),
+                    "parse".to_string(),
+                    vec![Expression::Value("1".into())].into()
+                ).into())],
+                ..Default::default()
+            }
+        })
+    ],
+    with_function_call "with a, 1" = vec![
+        Element::Expression(FunctionExpression::FunctionCall(
+            "with".into(),
+            vec![Expression::identifier("a"), Expression::Value(PrimitiveValue::Number(1.into()))].into()
+        ).into())
+    ],
+    propagate_function_call "foo(1)?" = vec![
+        Element::Expression(Expression::Propagate(Box::new(FunctionExpression::FunctionCall(
+            "foo".into(),
+            vec![Expression::Value(PrimitiveValue::Number(1.into()))].into()
+        ).into())))
+    ],
+    propagate_identifier "v = a?" = vec![
+        Statement::Assignment {
+            lhs: Assign::Identifier("v".into(), false),
+            expression: Expression::Propagate(Box::new(Expression::identifier("a"))),
+            shadow: false,
+        }.into()
+    ],
+    select_arms r#"select
+        a => |v| v
+        after 5 => none
+    end"# = vec![
+        Element::Expression(Expression::Select {
+            arms: vec![SelectArm {
+                source: Expression::identifier("a"),
+                binding: Some("v".to_string()),
+                body: Scope {
+                    elements: vec![Element::Expression(Expression::identifier("v"))],
+                    ..Default::default()
+                },
+            }],
+            after: Some((
+                Box::new(Expression::Value(PrimitiveValue::Number(5.into()))),
+                Scope {
+                    elements: vec![Element::Expression(Expression::Value(PrimitiveValue::None))],
+                    ..Default::default()
+                },
+            )),
+        })
+    ],
+    comptime_block r#"comptime do
+        1 + 2
+    end"# = vec![
+        Element::Expression(Expression::Comptime(Scope {
+            elements: vec![Element::Expression(Expression::BinExp(
+                Box::new(Expression::Value(PrimitiveValue::Number(1.into()))),
+                BinaryOperation::Add,
+                Box::new(Expression::Value(PrimitiveValue::Number(2.into()))),
+            ))],
+            ..Default::default()
+        }))
+    ],
+    lazy_block r#"a = lazy do
+        1 + 2
+    end"# = vec![
+        Element::Statement(Statement::Assignment {
+            lhs: Assign::Identifier("a".into(), false),
+            expression: Expression::Lazy(Scope {
+                elements: vec![Element::Expression(Expression::BinExp(
+                    Box::new(Expression::Value(PrimitiveValue::Number(1.into()))),
+                    BinaryOperation::Add,
+                    Box::new(Expression::Value(PrimitiveValue::Number(2.into()))),
+                ))],
+                ..Default::default()
+            }),
+            shadow: false,
+        })
+    ],
+    macro_expansion r#"macro twice(v)
+        quote unquote(v) * 2
+    end
+    twice 3"# = vec![
+        Element::Statement(Statement::Macro(MacroDefinition {
+            name: "twice".to_string(),
+            arguments: vec!["v".to_string()],
+            body: Expression::BinExp(
+                Box::new(Expression::Unquote("v".to_string())),
+                BinaryOperation::Mul,
+                Box::new(Expression::Value(PrimitiveValue::Number(2.into()))),
+            ),
+        })),
+        Element::Expression(Expression::BinExp(
+            Box::new(Expression::Value(PrimitiveValue::Number(3.into()))),
+            BinaryOperation::Mul,
+            Box::new(Expression::Value(PrimitiveValue::Number(2.into()))),
+        ))
+    ],
+    annotated_empty_map "m = {}: {String, Int}" = vec![
+        Element::Statement(Statement::Assignment {
+            lhs: Assign::Identifier("m".into(), false),
+            expression: Expression::Annotated(
+                Box::new(Expression::Map(vec![])),
+                RigzType::Map(Box::new(RigzType::String), Box::new(RigzType::Int)),
+            ),
+            shadow: false,
+        })
+    ],
+    for_list_over_empty_list "[for v in []: [v]]" = vec![
+        Element::Expression(Expression::ForList {
+            var: "v".to_string(),
+            expression: Box::new(Expression::List(vec![])),
+            body: Box::new(Expression::List(vec![Expression::identifier("v")])),
+        })
+    ],
 }
 
 // mod debug {
@@ -648,3 +912,471 @@ test_parse! {
 //
 //     test_parse! {}
 // }
+
+pub mod lines {
+    use super::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn element_lines() {
+        let input = r#"
+        a = 1
+
+        if a == 1
+            b = 2
+            # comment
+            b
+        end
+        "#;
+        let program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(program.lines, vec![2, 4]);
+        let Element::Expression(Expression::If { then, .. }) = &program.elements[1] else {
+            panic!("expected if, received {:?}", program.elements[1])
+        };
+        assert_eq!(then.lines, vec![5, 7]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn crlf_bom_and_tabs() {
+        let lf = parse(
+            "a = 1\n\nif a == 1\n\tb = 2\n\tb\nend",
+            ParserOptions::default(),
+        )
+        .expect("Failed to parse input");
+        let crlf = parse(
+            "\u{feff}\r\na = 1\r\n\r\nif a == 1\r\n\tb = 2\r\n\tb\r\nend\r\n",
+            ParserOptions::default(),
+        )
+        .expect("Failed to parse input");
+        assert_eq!(crlf.elements, lf.elements);
+        assert_eq!(crlf.lines, vec![2, 4]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn crlf_error_span() {
+        let input = "\u{feff}a = 1\r\n\tb = )\r\n";
+        let diagnostic = parse(input, ParserOptions::default())
+            .expect_err("Parse should fail")
+            .to_diagnostic();
+        assert!(
+            diagnostic
+                .render("main.rg", Some(input))
+                .contains(" --> main.rg:2:6"),
+            "{}",
+            diagnostic.render("main.rg", Some(input))
+        );
+    }
+}
+
+pub mod digest {
+    use super::*;
+
+    fn digest(input: &str) -> String {
+        parse(input, ParserOptions::default())
+            .expect("Failed to parse input")
+            .digest()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn formatting_and_comments_are_ignored() {
+        let a = digest("fn add(a, b) = a + b\nadd 1, 2");
+        let b = digest(
+            r#"
+        # adds two numbers
+        fn add(a, b)
+            a + b
+        end
+
+        add 1, 2 # three
+        "#,
+        );
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn semantic_changes() {
+        let base = digest("a = 1\na + 2");
+        assert_ne!(base, digest("a = 1\na - 2"));
+        assert_ne!(base, digest("a = 1.0\na + 2"));
+        assert_ne!(base, digest("b = 1\nb + 2"));
+        assert_ne!(base, digest("a = '1'\na + 2"));
+        assert_ne!(digest("@memo\nfn f = 1"), digest("fn f = 1"));
+    }
+}
+
+pub mod lint {
+    use super::*;
+
+    fn lint(input: &str) -> Vec<ValidationWarning> {
+        parse(input, ParserOptions::default())
+            .expect("Failed to parse input")
+            .lint()
+            .into_iter()
+            .map(|w| w.warning)
+            .collect()
+    }
+
+    fn module(definition: &str) -> ModuleTraitDefinition {
+        Parser::prepare(definition, ParserOptions::default())
+            .expect("Failed to prepare module")
+            .parse_module_trait_definition()
+            .expect("Failed to parse module")
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unreachable_after_return() {
+        let input = r#"
+        fn foo
+            return 1
+            2
+        end
+        foo
+        "#;
+        assert_eq!(
+            lint(input),
+            vec![ValidationWarning::UnreachableCode(
+                "1 element(s) after exit in foo will never run".to_string()
+            )]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unreachable_after_exits() {
+        let input = r#"
+        fn foo
+            raise "failed"
+            2
+        end
+        fn bar(a)
+            if a
+                return 1
+            else
+                raise "not a"
+            end
+            2
+            3
+        end
+        fn baz(a)
+            if a
+                return 1
+            end
+            2
+        end
+        foo + bar(true) + baz(false)
+        "#;
+        assert_eq!(
+            lint(input),
+            vec![
+                ValidationWarning::UnreachableCode(
+                    "1 element(s) after exit in foo will never run".to_string()
+                ),
+                ValidationWarning::UnreachableCode(
+                    "2 element(s) after exit in bar will never run".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unused_function() {
+        let input = r#"
+        fn foo = 1
+        fn bar = 2
+        bar
+        "#;
+        assert_eq!(
+            lint(input),
+            vec![ValidationWarning::UnusedFunction(
+                "foo is never called".to_string()
+            )]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn lifecycle_functions_are_used() {
+        let input = r#"
+        @test
+        fn test_foo = 1
+        1
+        "#;
+        assert_eq!(lint(input), vec![]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unused_variable() {
+        let input = r#"
+        a = 1
+        _b = 2
+        mut c = 3
+        c += 1
+        d = 4
+        d
+        "#;
+        assert_eq!(
+            lint(input),
+            vec![ValidationWarning::UnusedVariable(
+                "a is assigned but never read".to_string()
+            )]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unused_import() {
+        let input = r#"
+        import Random
+        1
+        "#;
+        assert_eq!(
+            lint(input),
+            vec![ValidationWarning::UnusedImport(
+                "Random is never used".to_string()
+            )]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn used_imports() {
+        let input = r#"
+        import Random
+        Random.create 42
+        "#;
+        assert_eq!(lint(input), vec![]);
+        // next_bool isn't defined in the program so it may come from Random
+        let input = r#"
+        import Random
+        next_bool
+        "#;
+        assert_eq!(lint(input), vec![]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unused_imports_with_unresolved_names() {
+        let random = module("trait Random\n  fn next_bool -> Bool\nend");
+        let json = module("trait JSON\n  fn parse(input: String) -> Any\nend");
+        let input = r#"
+        import Random
+        import JSON
+        next_bool
+        "#;
+        let mut program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(
+            program.lint_with_modules(&[&random, &json]),
+            vec![LintWarning {
+                warning: ValidationWarning::UnusedImport("JSON is never used".to_string()),
+                line: Some(3),
+            }]
+        );
+
+        // missing isn't defined by either module, so it can only come from the file
+        let input = r#"
+        import Random
+        import "./helpers.rg"
+        missing
+        "#;
+        let mut program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(
+            program
+                .lint_with_modules(&[&random, &json])
+                .into_iter()
+                .map(|w| w.warning)
+                .collect::<Vec<_>>(),
+            vec![ValidationWarning::UnusedImport(
+                "Random is never used".to_string()
+            )]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unused_variables_per_binding() {
+        let input = r#"
+        fn foo
+            a = 1
+            2
+        end
+        a = 3
+        mut b = 1
+        if true
+            let b = 2
+            b
+        end
+        mut c = 1
+        c = 2
+        foo + a
+        "#;
+        let mut program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(
+            program.lint(),
+            vec![
+                LintWarning {
+                    warning: ValidationWarning::UnusedVariable(
+                        "a is assigned but never read".to_string()
+                    ),
+                    line: Some(3),
+                },
+                LintWarning {
+                    warning: ValidationWarning::UnusedVariable(
+                        "b is assigned but never read".to_string()
+                    ),
+                    line: Some(7),
+                },
+                LintWarning {
+                    warning: ValidationWarning::UnusedVariable(
+                        "c is assigned but never read".to_string()
+                    ),
+                    line: Some(12),
+                },
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn warnings_include_lines() {
+        let input = r#"fn foo
+    return 1
+    2
+end
+fn bar = 1
+let x = 1
+let x = 2
+x + foo"#;
+        let mut program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(
+            program
+                .lint()
+                .into_iter()
+                .map(|w| (w.warning.rule(), w.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("unreachable_code", Some(3)),
+                ("shadowed_variable", Some(7)),
+                ("unused_function", Some(5)),
+                ("unused_variable", Some(6)),
+            ]
+        );
+    }
+    #[wasm_bindgen_test(unsupported = test)]
+    fn let_shadows_same_scope() {
+        let input = r#"
+        a = 1
+        let a = a + 1
+        mut b = 1
+        mut b = 2
+        a + b
+        "#;
+        assert_eq!(
+            lint(input),
+            vec![
+                ValidationWarning::ShadowedVariable(
+                    "let a shadows an existing variable in the same scope".to_string()
+                ),
+                ValidationWarning::UnusedVariable("b is assigned but never read".to_string()),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn assignment_shadows_outer_mut() {
+        let input = r#"
+        mut count = 0
+        fn increment
+            count = count + 1
+        end
+        increment
+        "#;
+        let program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert!(matches!(
+            program.validate(),
+            Err(ValidationError::InvalidAssignment(_))
+        ));
+
+        let input = r#"
+        mut count = 0
+        fn increment
+            let count = 1
+            count += 1
+        end
+        if true
+            count += 1
+        end
+        increment
+        "#;
+        let program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(program.validate(), Ok(()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn function_defined_in_conditional() {
+        for input in [
+            "if true\n  fn foo = 1\nend\nfoo",
+            "if false\n  1\nelse\n  fn foo = 1\nend\nfoo",
+            "unless false\n  fn foo = 1\nend\nfoo",
+            "[for i in [1, 2]: do\n  fn foo = i\n  foo\nend]\nfoo",
+        ] {
+            let program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+            assert!(
+                matches!(program.validate(), Err(ValidationError::InvalidFunction(ref e)) if e.contains("fn foo")),
+                "{input} - {:?}",
+                program.validate()
+            );
+        }
+
+        let input = r#"
+        fn outer
+            a = 1
+            fn inner = a
+            inner
+        end
+        if true
+            f = || 1
+            f
+        end
+        outer
+        "#;
+        let program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(program.validate(), Ok(()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unmatched_events() {
+        let input = r#"
+        @on("tick")
+        fn tick = 1
+
+        @on("signal:TERM")
+        fn stop = 2
+
+        send "tick"
+        send "tock", 1
+        send "tock", 2
+        "#;
+        let program = parse(input, ParserOptions::default()).expect("Failed to parse input");
+        assert_eq!(
+            program.event_topics().check(),
+            vec![ValidationWarning::UnmatchedEvent(
+                "\"tock\" is sent but no @on(\"tock\") handler receives it".to_string()
+            )]
+        );
+        // allowed unless enabled
+        assert_eq!(lint(input), vec![]);
+
+        let handlers = parse("@on(\"tik\")\nfn tick = 1\n1", ParserOptions::default())
+            .expect("Failed to parse input");
+        let mut topics = handlers.event_topics();
+        assert_eq!(
+            topics.check(),
+            vec![ValidationWarning::UnmatchedEvent(
+                "@on(\"tik\") tick never runs, nothing sends \"tik\"".to_string()
+            )]
+        );
+        // handlers match events sent from other files in the project
+        let sender = parse("send 'tik'", ParserOptions::default()).expect("Failed to parse input");
+        topics.merge(sender.event_topics());
+        assert_eq!(topics.check(), vec![]);
+
+        let dynamic = parse("topic = 'tik'\nsend topic", ParserOptions::default())
+            .expect("Failed to parse input");
+        let mut topics = handlers.event_topics();
+        topics.merge(dynamic.event_topics());
+        assert_eq!(topics.check(), vec![]);
+    }
+}
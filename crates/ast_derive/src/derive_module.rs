@@ -1,4 +1,6 @@
-use crate::{create_matched_call, method_name, rigz_type_to_return_type, FirstArg};
+use crate::{
+    create_matched_call, create_resolved_calls, method_name, rigz_type_to_return_type, FirstArg,
+};
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{quote, ToTokens};
 use rigz_ast::{
@@ -11,6 +13,11 @@ use syn::parse::{Parse, ParseStream};
 use syn::{bracketed, parse_str, LitStr, Token, Type};
 
 pub(crate) struct DeriveModule {
+    /// `nondeterministic,` before the other arguments, the module can't be used with `VMOptions::deterministic`
+    nondeterministic: bool,
+    /// `instructions,` before the other arguments, functions without `self` are called through
+    /// `Instruction::Custom`, see `Module::instructions`
+    instructions: bool,
     ident: Option<Ident>,
     dependencies: Vec<Ident>,
     literal: LitStr,
@@ -18,6 +25,19 @@ pub(crate) struct DeriveModule {
 
 impl Parse for DeriveModule {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut nondeterministic = false;
+        let mut instructions = false;
+        while input.peek(syn::Ident) && input.peek2(Token![,]) {
+            let flag = match input.fork().parse::<Ident>()?.to_string().as_str() {
+                "nondeterministic" => &mut nondeterministic,
+                "instructions" => &mut instructions,
+                _ => break,
+            };
+            *flag = true;
+            input.parse::<Ident>()?;
+            input.parse::<Token![,]>()?;
+        }
+
         let (ident, dependencies) = if input.peek(LitStr) {
             (None, vec![])
         } else {
@@ -45,6 +65,8 @@ impl Parse for DeriveModule {
         };
 
         Ok(DeriveModule {
+            nondeterministic,
+            instructions,
             ident,
             dependencies,
             literal: input.parse()?,
@@ -185,7 +207,7 @@ impl ToTokens for DeriveModule {
         let mut module_methods = Vec::new();
 
         // todo support polymorphic functions
-        let calls: Vec<_> = all_fcs
+        let named_calls: Vec<_> = all_fcs
             .iter()
             .map(|(name, f)| {
                 (
@@ -196,8 +218,12 @@ impl ToTokens for DeriveModule {
                 )
             })
             .filter(|(_, f)| !f.is_empty())
-            .map(|(name, fs)| create_matched_call(name, fs, FirstArg::None))
+            .map(|(name, fs)| {
+                let method = method_name(name, fs[0]);
+                (*name, method, create_matched_call(name, fs, FirstArg::None))
+            })
             .collect();
+        let calls: Vec<_> = named_calls.iter().map(|(_, _, call)| call).collect();
 
         if !calls.is_empty() {
             module_methods.push(quote! {
@@ -212,7 +238,7 @@ impl ToTokens for DeriveModule {
             });
         }
 
-        let ext_calls: Vec<_> = all_fcs
+        let extensions: Vec<_> = all_fcs
             .iter()
             .map(|(name, f)| {
                 (
@@ -227,14 +253,27 @@ impl ToTokens for DeriveModule {
                 )
             })
             .filter(|(_, f)| !f.is_empty())
-            .map(|(name, fs)| create_matched_call(name, fs, FirstArg::This))
             .collect();
+        let ext_calls: Vec<_> = extensions
+            .iter()
+            .map(|(name, fs)| create_matched_call(name, fs.clone(), FirstArg::This))
+            .collect();
+        let mut overloads = 0;
+        let (resolve_arms, resolved_calls): (Vec<_>, Vec<_>) = extensions
+            .iter()
+            .map(|(name, fs)| {
+                let first = overloads;
+                overloads += fs.len();
+                create_resolved_calls(name, fs.clone(), first)
+            })
+            .unzip();
+        let resolved_calls = resolved_calls.into_iter().flatten();
 
         if !ext_calls.is_empty() {
             module_methods.push(quote! {
                 fn call_extension(
                     &self,
-                    this: Rc<RefCell<ObjectValue>>,
+                    this: Shared<ObjectValue>,
                     function: String,
                     args: RigzArgs,
                 ) -> Result<ObjectValue, VMError> {
@@ -245,6 +284,29 @@ impl ToTokens for DeriveModule {
                         )))
                     }
                 }
+
+
+                #[allow(unused_variables)]
+                fn resolve_extension(&self, this: &ObjectValue, function: &str) -> Option<usize> {
+                    match function {
+                        #(#resolve_arms)*
+                        _ => None,
+                    }
+                }
+
+                fn call_resolved_extension(
+                    &self,
+                    overload: usize,
+                    this: Shared<ObjectValue>,
+                    args: RigzArgs,
+                ) -> Result<ObjectValue, VMError> {
+                    match overload {
+                        #(#resolved_calls)*
+                        _ => Err(VMError::InvalidModuleFunction(format!(
+                            "Overload {overload} does not exist"
+                        ))),
+                    }
+                }
             });
         }
 
@@ -293,7 +355,7 @@ impl ToTokens for DeriveModule {
             module_methods.push(quote! {
                 fn call_mutable_extension(
                     &self,
-                    this: Rc<RefCell<ObjectValue>>,
+                    this: Shared<ObjectValue>,
                     function: String,
                     args: RigzArgs,
                 ) -> Result<Option<ObjectValue>, VMError> {
@@ -314,11 +376,70 @@ impl ToTokens for DeriveModule {
             }
         };
 
-        tokens.extend(self.final_definition(module, module_methods, module_def, has_vm))
+        let instructions = if self.instructions && !has_vm {
+            self.instructions(&module.definition.name, &named_calls)
+        } else {
+            None
+        };
+        if let Some((_, method)) = &instructions {
+            module_methods.push(method.clone());
+        }
+
+        tokens.extend(self.final_definition(module, module_methods, module_def, has_vm));
+        if let Some((handlers, _)) = instructions {
+            tokens.extend(handlers);
+        }
     }
 }
 
 impl DeriveModule {
+    fn module_name(&self, name: &str) -> Ident {
+        match &self.ident {
+            Some(id) => id.clone(),
+            None => Ident::new(format!("{name}Module").as_str(), Span::call_site()),
+        }
+    }
+
+    /// A method per function running only its match arm, and `Module::instructions` calling them on a new module.
+    /// Only modules created by the macro are supported, they're unit structs
+    fn instructions(
+        &self,
+        name: &str,
+        calls: &[(&str, Ident, Tokens)],
+    ) -> Option<(Tokens, Tokens)> {
+        if self.ident.is_some() {
+            return None;
+        }
+        let module_name = self.module_name(name);
+        let mut methods = Vec::with_capacity(calls.len());
+        let mut handlers = Vec::with_capacity(calls.len());
+        for (name, method, call) in calls {
+            let method = Ident::new(format!("{method}_instruction").as_str(), Span::call_site());
+            methods.push(quote! {
+                fn #method(&self, args: RigzArgs) -> Result<ObjectValue, VMError> {
+                    match #name {
+                        #call
+                        _ => unreachable!(),
+                    }
+                }
+            });
+            handlers.push(quote! {
+                (#name, (|args| #module_name.#method(args)) as rigz_core::InstructionHandler)
+            });
+        }
+        let impl_methods = quote! {
+            impl #module_name {
+                #(#methods)*
+            }
+        };
+        let module_method = quote! {
+            fn instructions(&self) -> Vec<(&'static str, rigz_core::InstructionHandler)> {
+                vec![#(#handlers, )*]
+            }
+        };
+        Some((impl_methods, module_method))
+    }
+
     fn final_definition(
         &self,
         module: ModuleTraitDefinition,
@@ -327,10 +448,7 @@ impl DeriveModule {
         has_vm: bool,
     ) -> TokenStream {
         let name = &module.definition.name;
-        let module_name = match &self.ident {
-            Some(id) => id.clone(),
-            None => Ident::new(format!("{name}Module").as_str(), Span::call_site()),
-        };
+        let module_name = self.module_name(name);
 
         let input = self.literal.value();
         let input = input.as_str();
@@ -355,6 +473,17 @@ impl DeriveModule {
             }
         };
 
+        let deterministic = if self.nondeterministic {
+            quote! {
+                #[inline]
+                fn deterministic(&self) -> bool {
+                    false
+                }
+            }
+        } else {
+            quote! {}
+        };
+
         let parsed_deps = if self.dependencies.is_empty() {
             quote! {}
         } else {
@@ -387,6 +516,8 @@ impl DeriveModule {
             impl Module for #lifetime_module {
                 #deps
 
+                #deterministic
+
                 #(#module_methods)*
             }
 
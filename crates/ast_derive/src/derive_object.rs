@@ -1,19 +1,15 @@
-use crate::{
-    convert_response, convert_type_for_arg, rigz_type_to_arg, rigz_type_to_return_type,
-    setup_call_args,
-};
+use crate::{convert_response, convert_type_for_arg, rigz_type_to_return_type, setup_call_args};
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{quote, ToTokens};
-use rigz_ast::{FunctionDeclaration, FunctionType, ObjectDefinition, Parser, ParserOptions};
+use rigz_ast::{FunctionDeclaration, ObjectDefinition, Parser, ParserOptions};
 use rigz_core::derive::Tokens;
 use rigz_core::RigzType;
-use std::slice::Iter;
 use syn::parse::{Parse, ParseStream};
-use syn::{token, ItemStruct, LitStr, Token, Type, Visibility};
+use syn::{token, ItemStruct, LitBool, LitStr, Token, Type, Visibility};
 
 enum ObjectArg {
     Ident(Ident),
-    Struct(ItemStruct),
+    Struct(Box<ItemStruct>),
 }
 
 pub(crate) struct DeriveObject {
@@ -33,11 +29,28 @@ impl Parse for DeriveObject {
             ObjectArg::Ident(input.parse()?)
         };
         input.parse::<Token![,]>()?;
+        let literal = input.parse()?;
+        // `display = false` skips the generated Display impl so the object can implement its own
+        let mut display = true;
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            if !input.is_empty() {
+                let option: Ident = input.parse()?;
+                if option != "display" {
+                    return Err(syn::Error::new(
+                        option.span(),
+                        "expected `display = <bool>`",
+                    ));
+                }
+                input.parse::<Token![=]>()?;
+                display = input.parse::<LitBool>()?.value;
+            }
+        }
         Ok(DeriveObject {
             parent,
             definition,
-            literal: input.parse()?,
-            display: true,
+            literal,
+            display,
         })
     }
 }
@@ -50,20 +63,18 @@ impl ToTokens for DeriveObject {
 
 fn type_to_rigz_type(rust_type: &Type) -> RigzType {
     match rust_type {
-        Type::Path(t) => {
-            if t.qself.is_none() {
-                if let Some(last) = t.path.segments.last() {
-                    match last.ident.to_string().as_str() {
-                        "i8" | "i16" | "i32" | "i64" => return RigzType::Int,
-                        "f32" | "f64" => return RigzType::Float,
-                        "bool" => return RigzType::Bool,
-                        "String" => return RigzType::String,
-                        _ => {}
-                    }
+        Type::Path(t) if t.qself.is_none() => {
+            if let Some(last) = t.path.segments.last() {
+                match last.ident.to_string().as_str() {
+                    "i8" | "i16" | "i32" | "i64" => return RigzType::Int,
+                    "f32" | "f64" => return RigzType::Float,
+                    "bool" => return RigzType::Bool,
+                    "String" => return RigzType::String,
+                    _ => {}
                 }
             }
         }
-        t => {}
+        _ => {}
     }
     RigzType::Any
 }
@@ -220,6 +231,7 @@ struct CustomTrait {
 }
 
 fn custom_trait(name: &Ident, object_definition: &ObjectDefinition) -> CustomTrait {
+    let trait_name = Ident::new(format!("{}Object", name).as_str(), Span::call_site());
     let funcs: Vec<_> = object_definition
         .functions
         .iter()
@@ -251,18 +263,6 @@ fn custom_trait(name: &Ident, object_definition: &ObjectDefinition) -> CustomTra
                         panic!(
                             "Non Self extensions are not supported for Objects yet {:?}",
                             s.rigz_type
-                        );
-                        let arg = match rigz_type_to_return_type(&s.rigz_type) {
-                            None => quote! { ObjectValue },
-                            Some(t) => quote! { #t },
-                        };
-                        (
-                            quote! { value: #arg },
-                            Ident::new(
-                                format!("{mut_str}{}", s.rigz_type.to_string().to_lowercase())
-                                    .as_str(),
-                                Span::call_site(),
-                            ),
                         )
                     };
                     let base = if s.mutable {
@@ -336,19 +336,29 @@ fn custom_trait(name: &Ident, object_definition: &ObjectDefinition) -> CustomTra
                     });
                 }
                 Some(ft) if ft.mutable => {
-                    let method_call =
-                        convert_response(quote! { self.#fn_name(#(#call_args)*) }, sig);
+                    let method_call = convert_response(
+                        quote! { #trait_name::#fn_name(self, #(#call_args)*) },
+                        sig,
+                    );
+                    // functions with a value return it from `convert_response`
+                    let none = match &sig.return_type.rigz_type {
+                        RigzType::None | RigzType::This => Some(quote! { Ok(None) }),
+                        _ => None,
+                    };
                     mut_funcs.push(quote! {
                         #name => {
                             #base_args
                             #(#setup_args)*
                             #method_call
+                            #none
                         }
                     });
                 }
                 Some(_) => {
-                    let method_call =
-                        convert_response(quote! { self.#fn_name(#(#call_args)*) }, sig);
+                    let method_call = convert_response(
+                        quote! { #trait_name::#fn_name(self, #(#call_args)*) },
+                        sig,
+                    );
                     ext_funcs.push(quote! {
                         #name => {
                             #base_args
@@ -359,10 +369,8 @@ fn custom_trait(name: &Ident, object_definition: &ObjectDefinition) -> CustomTra
                 }
             }
 
-            let ret = match rigz_type_to_return_type(&sig.return_type.rigz_type) {
-                None => None,
-                Some(s) => Some(quote! { -> #s }),
-            };
+            let ret =
+                rigz_type_to_return_type(&sig.return_type.rigz_type).map(|s| quote! { -> #s });
             if sig.self_type.is_none() {
                 quote! {
                     fn #fn_name(#(#args, )*) #ret where Self: Sized;
@@ -405,12 +413,11 @@ fn custom_trait(name: &Ident, object_definition: &ObjectDefinition) -> CustomTra
                 match function.as_str() {
                     #(#mut_funcs)*
                     _ => {
-                        return Err(VMError::UnsupportedOperation(format!(
+                        Err(VMError::UnsupportedOperation(format!(
                             "{self:?} does not implement `call_mutable_extension` - {function}"
                         )))
                     }
                 }
-                Ok(None)
             }
         })
     };
@@ -432,7 +439,6 @@ fn custom_trait(name: &Ident, object_definition: &ObjectDefinition) -> CustomTra
         })
     };
 
-    let trait_name = Ident::new(format!("{}Object", name).as_str(), Span::call_site());
     let trait_def = quote! {
         trait #trait_name {
             #(#trait_methods)*
@@ -6,12 +6,11 @@ extern crate proc_macro;
 use crate::derive_module::DeriveModule;
 use crate::derive_object::DeriveObject;
 use proc_macro::TokenStream;
-use proc_macro2::{Ident, Span};
-use quote::{quote, ToTokens};
+use proc_macro2::{Ident, Span, TokenTree};
+use quote::quote;
 use rigz_ast::FunctionSignature;
 use rigz_core::derive::{rigz_type_to_rust_str, Tokens};
 use rigz_core::RigzType;
-use syn::parse::Parse;
 use syn::{parse_macro_input, parse_str, Type};
 
 /// Generate Module & ParsedModule implementations
@@ -65,149 +64,22 @@ fn create_matched_call(name: &str, fs: Vec<&&FunctionSignature>, first_arg: Firs
     }
 
     let is_mut = FirstArg::MutThis == first_arg;
-    let first_arg: Option<Tokens> = first_arg.into();
+    let first_arg: Option<Tokens> = match first_arg {
+        // frozen values are matched by the value they wrap
+        FirstArg::This => Some(quote! { this.borrow().unfrozen().clone() }),
+        f => f.into(),
+    };
     let first_arg = first_arg.expect("Multi match not supported for non-extension functions");
 
     let mut has_any = false;
     let match_arms: Vec<_> = fs
         .iter()
-        .map(|fs| match &fs.self_type {
-            None => panic!("Matched call only supported for extension functions currently"),
-            Some(ft) => {
-                let v = if is_mut {
-                    match convert_type_for_borrowed_arg(quote! { this }, &ft.rigz_type, true) {
-                        None => Some(quote! { this.borrow_mut().deref_mut() }),
-                        Some((s, _)) => Some(s),
-                    }
-                } else {
-                    Some(quote! { v })
-                };
-                let base_call = base_call(name, fs, v, true);
-                match &ft.rigz_type {
-                    RigzType::Any => {
-                        has_any = true;
-                        quote! {
-                            v => {
-                                #base_call
-                            }
-                        }
-                    }
-                    RigzType::Bool => {
-                        if is_mut {
-                            quote! {
-                                RigzType::Bool => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::Primitive(PrimitiveValue::Bool(v)) => {
-                                    let v = v.to_bool();
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::Int => {
-                        if is_mut {
-                            quote! {
-                                RigzType::Int => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::Primitive(PrimitiveValue::Number(n)) => {
-                                    let v = n.to_int();
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::Float => {
-                        if is_mut {
-                            quote! {
-                                RigzType::Float => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::Primitive(PrimitiveValue::Number(n)) => {
-                                    let v = n.to_float();
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::Number => {
-                        if is_mut {
-                            quote! {
-                                RigzType::Number => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::Primitive(PrimitiveValue::Number(v)) => {
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::String => {
-                        if is_mut {
-                            quote! {
-                                RigzType::String => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::Primitive(PrimitiveValue::String(v)) => {
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::List(_) => {
-                        if is_mut {
-                            quote! {
-                                RigzType::List(_) => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::List(v) => {
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::Map(_, _) => {
-                        if is_mut {
-                            quote! {
-                                RigzType::Map(_, _) => {
-                                    #base_call
-                                }
-                            }
-                        } else {
-                            quote! {
-                                ObjectValue::Map(v) => {
-                                    #base_call
-                                }
-                            }
-                        }
-                    }
-                    RigzType::Error => {
-                        quote! {
-                            ObjectValue::Primitive(PrimitiveValue::Error(v)) => {
-                                #base_call
-                            }
-                        }
-                    }
-                    r => todo!("Type not supported yet - {r}"),
+        .map(|fs| {
+            let (pattern, call, any) = overload_arm(name, fs, is_mut);
+            has_any |= any;
+            quote! {
+                #pattern => {
+                    #call
                 }
             }
         })
@@ -242,10 +114,134 @@ fn create_matched_call(name: &str, fs: Vec<&&FunctionSignature>, first_arg: Firs
     }
 }
 
+/// `resolve_extension` arm returning the overload `create_matched_call` runs for `this`, and the
+/// `call_resolved_extension` arms running each overload, numbered from `first`
+fn create_resolved_calls(
+    name: &str,
+    fs: Vec<&&FunctionSignature>,
+    first: usize,
+) -> (Tokens, Vec<Tokens>) {
+    if fs.len() == 1 {
+        let call = create_method_call(name, fs[0], FirstArg::This);
+        let resolve = quote! { #name => Some(#first), };
+        let calls = vec![quote! {
+            #first => match #name {
+                #call
+                _ => unreachable!(),
+            },
+        }];
+        return (resolve, calls);
+    }
+
+    let mut has_any = false;
+    let mut patterns = Vec::with_capacity(fs.len());
+    let mut calls = Vec::with_capacity(fs.len());
+    for (index, fs) in fs.iter().enumerate() {
+        let index = first + index;
+        let (pattern, call, any) = overload_arm(name, fs, false);
+        has_any |= any;
+        let fallback = if any {
+            None
+        } else {
+            Some(quote! {
+                v => Err(VMError::RuntimeError(format!("Cannot call {} on {v}", #name))),
+            })
+        };
+        patterns.push(quote! { #pattern => Some(#index), });
+        calls.push(quote! {
+            #index => match this.borrow().unfrozen().clone() {
+                #pattern => {
+                    #call
+                }
+                #fallback
+            },
+        });
+    }
+    let fallback = if has_any {
+        None
+    } else {
+        Some(quote! { _ => None, })
+    };
+    let resolve = quote! {
+        #name => match this.unfrozen() {
+            #(#patterns)*
+            #fallback
+        },
+    };
+    (resolve, calls)
+}
+
+/// Pattern matching the receiver of an extension overload & the call it runs, `v` is the receiver.
+/// Mutable extensions are matched by the receiver's type, others by its value
+fn overload_arm(name: &str, fs: &FunctionSignature, is_mut: bool) -> (Tokens, Tokens, bool) {
+    let Some(ft) = &fs.self_type else {
+        panic!("Matched call only supported for extension functions currently")
+    };
+    let v = if is_mut {
+        match convert_type_for_borrowed_arg(quote! { this }, &ft.rigz_type, true) {
+            None => Some(quote! { this.borrow_mut().deref_mut() }),
+            Some((s, _)) => Some(s),
+        }
+    } else {
+        Some(quote! { v })
+    };
+    let base_call = base_call(name, fs, v, true);
+    let (pattern, call) = match &ft.rigz_type {
+        RigzType::Any => return (quote! { v }, base_call, true),
+        RigzType::Bool if is_mut => (quote! { RigzType::Bool }, base_call),
+        RigzType::Bool => (
+            quote! { ObjectValue::Primitive(PrimitiveValue::Bool(v)) },
+            quote! {
+                let v = v.to_bool();
+                #base_call
+            },
+        ),
+        RigzType::Int if is_mut => (quote! { RigzType::Int }, base_call),
+        RigzType::Int => (
+            quote! { ObjectValue::Primitive(PrimitiveValue::Number(n)) },
+            quote! {
+                let v = n.to_int();
+                #base_call
+            },
+        ),
+        RigzType::Float if is_mut => (quote! { RigzType::Float }, base_call),
+        RigzType::Float => (
+            quote! { ObjectValue::Primitive(PrimitiveValue::Number(n)) },
+            quote! {
+                let v = n.to_float();
+                #base_call
+            },
+        ),
+        RigzType::Number if is_mut => (quote! { RigzType::Number }, base_call),
+        RigzType::Number => (
+            quote! { ObjectValue::Primitive(PrimitiveValue::Number(v)) },
+            base_call,
+        ),
+        RigzType::String if is_mut => (quote! { RigzType::String }, base_call),
+        RigzType::String => (
+            quote! { ObjectValue::Primitive(PrimitiveValue::String(v)) },
+            quote! {
+                let v = v.into_string();
+                #base_call
+            },
+        ),
+        RigzType::List(_) if is_mut => (quote! { RigzType::List(_) }, base_call),
+        RigzType::List(_) => (quote! { ObjectValue::List(v) }, base_call),
+        RigzType::Map(_, _) if is_mut => (quote! { RigzType::Map(_, _) }, base_call),
+        RigzType::Map(_, _) => (quote! { ObjectValue::Map(v) }, base_call),
+        RigzType::Error => (
+            quote! { ObjectValue::Primitive(PrimitiveValue::Error(v)) },
+            base_call,
+        ),
+        r => todo!("Type not supported yet - {r}"),
+    };
+    (pattern, call, false)
+}
+
 fn convert_response(base_call: Tokens, function_signature: &FunctionSignature) -> Tokens {
-    let (mut_result, is_vm) = match &function_signature.self_type {
-        None => (false, false),
-        Some(t) => (t.mutable, t.rigz_type.is_vm()),
+    let mut_result = match &function_signature.self_type {
+        None => false,
+        Some(t) => t.mutable,
     };
 
     if mut_result {
@@ -666,8 +662,9 @@ fn convert_type_for_borrowed_arg(
                 ),
                 Some((t, e)) => {
                     if e {
+                        let t = try_result(t);
                         (
-                            quote! { #name.borrow_mut().deref_mut().maybe_map_mut(|#name| Ok(#t))? },
+                            quote! { #name.borrow_mut().deref_mut().maybe_map_mut(|#name| #t)? },
                             true,
                         )
                     } else {
@@ -704,8 +701,9 @@ fn convert_type_for_borrowed_arg(
                 None => (quote! { #name.borrow().deref().map(|t| t.clone()) }, false),
                 Some((t, e)) => {
                     if e {
+                        let t = try_result(t);
                         (
-                            quote! { #name.borrow().deref().maybe_map(|#name| Ok(#t))? },
+                            quote! { #name.borrow().deref().maybe_map(|#name| #t)? },
                             true,
                         )
                     } else {
@@ -727,6 +725,18 @@ fn convert_type_for_borrowed_arg(
     Some(t)
 }
 
+/// Fallible conversions end with `?`, closures return their `Result` as is instead of `Ok(value?)`
+fn try_result(t: Tokens) -> Tokens {
+    let mut tokens: Vec<_> = t.clone().into_iter().collect();
+    match tokens.last() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '?' => {
+            tokens.pop();
+            tokens.into_iter().collect()
+        }
+        _ => quote! { Ok(#t) },
+    }
+}
+
 fn convert_type_for_arg(
     name: Tokens,
     rigz_type: &RigzType,
@@ -772,7 +782,8 @@ fn convert_type_for_arg(
                 None => (quote! { #name.borrow().deref().map(|t| t.clone()) }, false),
                 Some((t, e)) => {
                     if e {
-                        (quote! { #name.maybe_map_mut(|#name| Ok(#t))? }, true)
+                        let t = try_result(t);
+                        (quote! { #name.maybe_map_mut(|#name| #t)? }, true)
                     } else {
                         (quote! { #name.map_mut(|#name| #t) }, false)
                     }
@@ -804,7 +815,8 @@ fn convert_type_for_arg(
                 None => return None,
                 Some((t, e)) => {
                     if e {
-                        (quote! { #name.maybe_map(|#name| Ok(#t))? }, true)
+                        let t = try_result(t);
+                        (quote! { #name.maybe_map(|#name| #t)? }, true)
                     } else {
                         (quote! { #name.map(|#name| #t) }, false)
                     }
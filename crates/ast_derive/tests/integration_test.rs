@@ -1,8 +1,6 @@
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 // todo figure this out later
 // struct VM {}
@@ -294,12 +292,29 @@ fn blah() {
     let json = JSONModule;
     assert_eq!(
         "5",
-        json.call(
-            "parse".to_string(),
-            vec![Rc::new(RefCell::new(5.into()))].into()
-        )
-        .expect("json parse failed")
-        .to_string()
-        .as_str()
+        json.call("parse".to_string(), vec![Shared::new(5.into())].into())
+            .expect("json parse failed")
+            .to_string()
+            .as_str()
     )
 }
+
+#[wasm_bindgen_test(unsupported = test)]
+fn resolve_extension_overloads() {
+    let list = ObjectValue::List(vec![1.into()]);
+    let map = ObjectValue::Map(IndexMap::new());
+    let first_list = StdModule.resolve_extension(&list, "first");
+    let first_map = StdModule.resolve_extension(&map, "first");
+    assert!(first_list.is_some() && first_map.is_some());
+    assert_ne!(first_list, first_map);
+    assert_eq!(StdModule.resolve_extension(&1.into(), "first"), None);
+    assert_eq!(StdModule.resolve_extension(&list, "missing"), None);
+
+    let to_json = JSONModule
+        .resolve_extension(&5.into(), "to_json")
+        .expect("to_json was not resolved");
+    assert_eq!(
+        JSONModule.call_resolved_extension(to_json, Shared::new(5.into()), vec![].into()),
+        Ok("5".into())
+    );
+}
@@ -1,25 +1,24 @@
-use crate::{AsPrimitive, ObjectValue, VMError};
-use std::cell::RefCell;
-use std::fmt::{Debug, Formatter};
-use std::rc::Rc;
+use crate::prelude::*;
+use crate::{AsPrimitive, ObjectValue, Shared, VMError};
+use core::fmt::{Debug, Formatter};
 
 #[derive(Clone)]
-pub struct RigzArgs(pub Vec<Rc<RefCell<ObjectValue>>>);
+pub struct RigzArgs(pub Vec<Shared<ObjectValue>>);
 
 impl Debug for RigzArgs {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self.0)
     }
 }
 
-impl From<Vec<Rc<RefCell<ObjectValue>>>> for RigzArgs {
+impl From<Vec<Shared<ObjectValue>>> for RigzArgs {
     #[inline]
-    fn from(value: Vec<Rc<RefCell<ObjectValue>>>) -> Self {
+    fn from(value: Vec<Shared<ObjectValue>>) -> Self {
         RigzArgs(value)
     }
 }
 
-impl From<RigzArgs> for Vec<Rc<RefCell<ObjectValue>>> {
+impl From<RigzArgs> for Vec<Shared<ObjectValue>> {
     #[inline]
     fn from(value: RigzArgs) -> Self {
         value.0
@@ -34,12 +33,10 @@ impl From<RigzArgs> for Vec<ObjectValue> {
 }
 
 pub type VarArgs<const START: usize, const COUNT: usize> =
-    ([Rc<RefCell<ObjectValue>>; START], [Vec<ObjectValue>; COUNT]);
+    ([Shared<ObjectValue>; START], [Vec<ObjectValue>; COUNT]);
 
-pub type VarArgsRc<const START: usize, const COUNT: usize> = (
-    [Rc<RefCell<ObjectValue>>; START],
-    [Rc<RefCell<ObjectValue>>; COUNT],
-);
+pub type VarArgsRc<const START: usize, const COUNT: usize> =
+    ([Shared<ObjectValue>; START], [Shared<ObjectValue>; COUNT]);
 
 impl RigzArgs {
     #[inline]
@@ -53,7 +50,7 @@ impl RigzArgs {
     }
 
     #[inline]
-    pub fn first(self) -> Result<Rc<RefCell<ObjectValue>>, VMError> {
+    pub fn first(self) -> Result<Shared<ObjectValue>, VMError> {
         if self.is_empty() {
             return Err(VMError::RuntimeError(
                 "Invalid args, expected 1 argument".to_string(),
@@ -64,7 +61,7 @@ impl RigzArgs {
     }
 
     #[inline]
-    pub fn take<const N: usize>(self) -> Result<[Rc<RefCell<ObjectValue>>; N], VMError> {
+    pub fn take<const N: usize>(self) -> Result<[Shared<ObjectValue>; N], VMError> {
         if self.len() < N {
             return Err(VMError::RuntimeError(format!(
                 "Invalid args, expected {N} argument{}",
@@ -72,7 +69,7 @@ impl RigzArgs {
             )));
         }
 
-        let mut results = [(); N].map(|_| Rc::new(ObjectValue::default().into()));
+        let mut results = [(); N].map(|_| Shared::new(ObjectValue::default()));
         for (i, v) in self.0.into_iter().take(N).rev().enumerate() {
             results[i] = v;
         }
@@ -90,7 +87,7 @@ impl RigzArgs {
             )));
         }
 
-        let mut results = [(); START].map(|_| Rc::new(ObjectValue::default().into()));
+        let mut results = [(); START].map(|_| Shared::new(ObjectValue::default()));
         let mut var = [(); COUNT].map(|_| Vec::new());
         for (i, v) in self.0.into_iter().rev().enumerate() {
             if i < START {
@@ -110,7 +107,6 @@ impl RigzArgs {
             };
         }
         let min = var[0].len();
-        dbg!(&var, &results);
         if var.iter().any(|v| v.len() != min) {
             Err(VMError::RuntimeError(format!(
                 "Invalid var args, expected all args to contain {min}"
@@ -131,9 +127,9 @@ impl RigzArgs {
             )));
         }
 
-        let mut results = [(); START].map(|_| Rc::new(ObjectValue::default().into()));
+        let mut results = [(); START].map(|_| Shared::new(ObjectValue::default()));
         let mut var_len = [0; COUNT];
-        let mut var = [(); COUNT].map(|_| Rc::new(RefCell::new(ObjectValue::default())));
+        let mut var = [(); COUNT].map(|_| Shared::new(ObjectValue::default()));
         for (i, v) in self.0.into_iter().rev().enumerate() {
             if i < START {
                 results[i] = v;
@@ -166,39 +162,33 @@ impl RigzArgs {
 
 #[cfg(test)]
 pub mod rigz_args {
-    use crate::{ObjectValue, PrimitiveValue, RigzArgs};
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    use crate::{ObjectValue, PrimitiveValue, RigzArgs, Shared};
     use wasm_bindgen_test::*;
 
     #[wasm_bindgen_test(unsupported = test)]
     fn take() {
-        let args: RigzArgs = RigzArgs(vec![
-            Rc::new(RefCell::new(1.into())),
-            Rc::new(RefCell::new(2.into())),
-        ]);
+        let args: RigzArgs = RigzArgs(vec![Shared::new(1.into()), Shared::new(2.into())]);
         let [first] = args.take().expect("Failed to take first");
-        assert_eq!(first, Rc::new(RefCell::new(1.into())));
+        assert_eq!(first, Shared::new(1.into()));
     }
 
     #[wasm_bindgen_test(unsupported = test)]
     fn var_args_one() {
         let a: ObjectValue = 2.into();
         let b: ObjectValue = 3.into();
-        let args: RigzArgs = RigzArgs(vec![
-            Rc::new(RefCell::new(vec![a, b].into())),
-            Rc::new(RefCell::new(1.into())),
-        ]);
+        let args: RigzArgs = RigzArgs(vec![Shared::new(vec![a, b].into()), Shared::new(1.into())]);
         let ([first], [var]) = args.var_args().expect("Failed to get var_args");
-        assert_eq!(first, Rc::new(RefCell::new(1.into())));
+        assert_eq!(first, Shared::new(1.into()));
         assert_eq!(var, vec![2.into(), 3.into()]);
     }
 
     #[wasm_bindgen_test(unsupported = test)]
     fn var_args_skip_first() {
-        let args: RigzArgs = RigzArgs(vec![Rc::new(RefCell::new(
-            ObjectValue::List(vec![1.into(), 2.into(), 3.into()]).into(),
-        ))]);
+        let args: RigzArgs = RigzArgs(vec![Shared::new(ObjectValue::List(vec![
+            1.into(),
+            2.into(),
+            3.into(),
+        ]))]);
         let ([], [var]) = args.var_args().expect("Failed to get var_args");
         assert_eq!(var, vec![1.into(), 2.into(), 3.into()]);
     }
@@ -206,9 +196,9 @@ pub mod rigz_args {
     #[wasm_bindgen_test(unsupported = test)]
     fn var_args_two() {
         let args: RigzArgs = RigzArgs(vec![
-            Rc::new(RefCell::new(vec![PrimitiveValue::Number(3.into())].into())),
-            Rc::new(RefCell::new(ObjectValue::List(vec![2.into()]).into())),
-            Rc::new(RefCell::new(1.into())),
+            Shared::new(vec![PrimitiveValue::Number(3.into())].into()),
+            Shared::new(ObjectValue::List(vec![2.into()])),
+            Shared::new(1.into()),
         ]);
         let ([first], [var1, var2]) = args.var_args().expect("Failed to get var_args");
         let v: ObjectValue = 1.into();
@@ -220,11 +210,9 @@ pub mod rigz_args {
     #[wasm_bindgen_test(unsupported = test)]
     fn var_args_error() {
         let args: RigzArgs = RigzArgs(vec![
-            Rc::new(RefCell::new(1.into())),
-            Rc::new(RefCell::new(ObjectValue::List(vec![2.into()]).into())),
-            Rc::new(RefCell::new(
-                ObjectValue::List(vec![3.into(), 3.into()]).into(),
-            )),
+            Shared::new(1.into()),
+            Shared::new(ObjectValue::List(vec![2.into()])),
+            Shared::new(ObjectValue::List(vec![3.into(), 3.into()])),
         ]);
         assert!(
             args.var_args::<1, 2>().is_err(),
@@ -0,0 +1,264 @@
+use crate::{
+    AsPrimitive, BinaryOperation, CreateObject, Definition, Number, Object, ObjectValue,
+    PrimitiveValue, RigzArgs, RigzType, VMError, WithTypeInfo,
+};
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::str::FromStr;
+use num_traits::{ToPrimitive, Zero};
+
+/// Ints past the range of an i64, created when checked Int arithmetic overflows or from large literals.
+/// Results that fit in an i64 are converted back to `Number::Int`, see [`BigInt::normalize`]
+#[derive(
+    Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct BigInt(pub num_bigint::BigInt);
+
+impl BigInt {
+    /// Ints that fit in an i64 become `Number::Int`, everything else stays a BigInt
+    pub fn normalize(value: num_bigint::BigInt) -> ObjectValue {
+        match value.to_i64() {
+            Some(i) => i.into(),
+            None => BigInt(value).into(),
+        }
+    }
+
+    /// Digits with an optional sign & `_` separators, i.e. a literal that doesn't fit in an i64
+    pub fn parse(value: &str) -> Result<ObjectValue, VMError> {
+        match num_bigint::BigInt::from_str(value.replace('_', "").as_str()) {
+            Ok(v) => Ok(BigInt::normalize(v)),
+            Err(e) => Err(VMError::ConversionError(format!(
+                "Cannot convert {value} to Int - {e}"
+            ))),
+        }
+    }
+
+    pub fn cmp_number(&self, number: &Number) -> Option<Ordering> {
+        match number {
+            Number::Int(i) => Some(self.0.cmp(&(*i).into())),
+            Number::Float(_) | Number::Decimal(_) => {
+                self.0.to_f64()?.partial_cmp(&number.to_float())
+            }
+        }
+    }
+
+    fn operation(
+        &self,
+        operation: BinaryOperation,
+        rhs: &num_bigint::BigInt,
+        reversed: bool,
+    ) -> Option<ObjectValue> {
+        let (a, b) = if reversed {
+            (rhs, &self.0)
+        } else {
+            (&self.0, rhs)
+        };
+        let result = match operation {
+            BinaryOperation::Add => a + b,
+            BinaryOperation::Sub => a - b,
+            BinaryOperation::Mul => a * b,
+            BinaryOperation::Div | BinaryOperation::Rem if b.is_zero() => {
+                return Some(VMError::RuntimeError(format!("Cannot divide {a} by 0")).into())
+            }
+            BinaryOperation::Div => a / b,
+            BinaryOperation::Rem => a % b,
+            _ => return None,
+        };
+        Some(BigInt::normalize(result))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        BigInt(value.into())
+    }
+}
+
+impl Debug for BigInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "BigInt({})", self.0)
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl WithTypeInfo for BigInt {
+    fn rigz_type(&self) -> RigzType {
+        RigzType::Int
+    }
+}
+
+impl AsPrimitive<ObjectValue> for BigInt {
+    fn binary_operation(
+        &self,
+        operation: BinaryOperation,
+        other: &ObjectValue,
+        reversed: bool,
+    ) -> Option<ObjectValue> {
+        match other {
+            ObjectValue::Primitive(PrimitiveValue::Number(Number::Int(i))) => {
+                self.operation(operation, &(*i).into(), reversed)
+            }
+            // floats & decimals lose precision either way, the result is a float like Int & Float arithmetic
+            ObjectValue::Primitive(PrimitiveValue::Number(n)) => {
+                let a = Number::Float(self.0.to_f64()?);
+                let b = Number::Float(n.to_float());
+                let (a, b) = if reversed { (b, a) } else { (a, b) };
+                let result = match operation {
+                    BinaryOperation::Add => &a + &b,
+                    BinaryOperation::Sub => &a - &b,
+                    BinaryOperation::Mul => &a * &b,
+                    BinaryOperation::Div => &a / &b,
+                    BinaryOperation::Rem => &a % &b,
+                    _ => return None,
+                };
+                Some(result.into())
+            }
+            ObjectValue::Object(o) => {
+                let other = o.downcast_ref::<BigInt>()?;
+                self.operation(operation, &other.0, reversed)
+            }
+            _ => None,
+        }
+    }
+
+    fn to_number(&self) -> Result<Number, VMError> {
+        match self.0.to_f64() {
+            Some(f) => Ok(f.into()),
+            None => Err(VMError::ConversionError(format!(
+                "Cannot convert {self} to Number"
+            ))),
+        }
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.is_zero()
+    }
+
+    fn to_usize(&self) -> Result<usize, VMError> {
+        self.0
+            .to_usize()
+            .ok_or_else(|| VMError::ConversionError(format!("Cannot convert {self} to usize")))
+    }
+
+    fn to_int(&self) -> Result<i64, VMError> {
+        self.0
+            .to_i64()
+            .ok_or_else(|| VMError::ConversionError(format!("{self} does not fit in an i64")))
+    }
+}
+
+impl CreateObject for BigInt {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let [value] = args.take()?;
+        let value = value.borrow();
+        match &*value {
+            ObjectValue::Primitive(PrimitiveValue::Number(Number::Int(i))) => Ok((*i).into()),
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => {
+                match num_bigint::BigInt::from_str(s.replace('_', "").as_str()) {
+                    Ok(v) => Ok(BigInt(v)),
+                    Err(e) => Err(VMError::ConversionError(format!(
+                        "Cannot create BigInt from {s} - {e}"
+                    ))),
+                }
+            }
+            ObjectValue::Object(o) => match o.downcast_ref::<BigInt>() {
+                Some(b) => Ok(b.clone()),
+                None => Err(VMError::ConversionError(format!(
+                    "Cannot create BigInt from {o}"
+                ))),
+            },
+            v => Err(VMError::ConversionError(format!(
+                "Cannot create BigInt from {v}"
+            ))),
+        }
+    }
+}
+
+impl Definition for BigInt {
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "BigInt"
+    }
+
+    fn trait_definition() -> &'static str
+    where
+        Self: Sized,
+    {
+        "object BigInt\nend"
+    }
+}
+
+#[typetag::serde]
+impl Object for BigInt {}
+
+impl From<BigInt> for ObjectValue {
+    fn from(value: BigInt) -> Self {
+        ObjectValue::Object(Box::new(value))
+    }
+}
+
+#[cfg(test)]
+pub mod bigint_tests {
+    use crate::{AsPrimitive, BigInt, BinaryOperation, ObjectValue};
+    use wasm_bindgen_test::*;
+
+    fn big(value: &str) -> ObjectValue {
+        BigInt::parse(value).unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parses_large_literals() {
+        assert_eq!(big("42"), ObjectValue::from(42));
+        assert_eq!(
+            big("-9_223_372_036_854_775_808"),
+            ObjectValue::from(i64::MIN)
+        );
+        assert_eq!(
+            big("9223372036854775808").to_string(),
+            "9223372036854775808"
+        );
+        assert!(BigInt::parse("12a").is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn arithmetic() {
+        let max = BigInt::from(i64::MAX);
+        let one = ObjectValue::from(1);
+        let sum = max
+            .binary_operation(BinaryOperation::Add, &one, false)
+            .unwrap();
+        assert_eq!(sum, big("9223372036854775808"));
+        assert_eq!(&sum - &one, ObjectValue::from(i64::MAX));
+        assert_eq!(&one - &sum, big("-9223372036854775807"));
+        assert_eq!(&sum * &sum, big("85070591730234615865843651857942052864"));
+        assert_eq!(&sum / &ObjectValue::from(2), ObjectValue::from(1i64 << 62));
+        assert_eq!(
+            &sum + &ObjectValue::from(0.5),
+            ObjectValue::from(9.223372036854776e18)
+        );
+        assert!(matches!(
+            &sum % &ObjectValue::from(0),
+            ObjectValue::Primitive(crate::PrimitiveValue::Error(_))
+        ));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn compares_to_numbers() {
+        let large = big("9223372036854775808");
+        assert!(large > ObjectValue::from(i64::MAX));
+        assert!(ObjectValue::from(1.5) < large);
+        assert!(big("-9223372036854775809") < ObjectValue::from(i64::MIN));
+        assert!(large < big("9223372036854775809"));
+        assert_eq!(-&large, big("-9223372036854775808"));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::{with_context, RuntimeContext};
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Where captured output came from, `pid` is None for the main VM
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OutputSource {
+    pub pid: Option<usize>,
+    pub scope: String,
+}
+
+impl OutputSource {
+    pub fn main(scope: impl Into<String>) -> Self {
+        Self {
+            pid: None,
+            scope: scope.into(),
+        }
+    }
+
+    pub fn process(pid: usize, scope: impl Into<String>) -> Self {
+        Self {
+            pid: Some(pid),
+            scope: scope.into(),
+        }
+    }
+}
+
+impl Display for OutputSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.pid {
+            None => write!(f, "main:{}", self.scope),
+            Some(pid) => write!(f, "process {pid}:{}", self.scope),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedOutput {
+    pub source: OutputSource,
+    pub stream: OutputStream,
+    pub content: String,
+}
+
+impl Display for CapturedOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let prefix = match self.stream {
+            OutputStream::Stdout => format!("[{}]", self.source),
+            OutputStream::Stderr => format!("[{} stderr]", self.source),
+        };
+        let len = self.content.lines().count();
+        for (index, line) in self.content.lines().enumerate() {
+            write!(f, "{prefix} {line}")?;
+            if index + 1 != len {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static SOURCE: RefCell<OutputSource> = RefCell::new(OutputSource::default());
+}
+
+/// Sets the source used to tag output written from the current thread, returns the previous source
+#[cfg(feature = "std")]
+pub fn set_output_source(source: OutputSource) -> OutputSource {
+    SOURCE.with(|s| s.replace(source))
+}
+
+/// Starts capturing output of the current thread's context, see `RuntimeContext::start_capture`
+#[cfg(feature = "std")]
+pub fn start_capture() {
+    with_context(|c| c.start_capture())
+}
+
+#[cfg(feature = "std")]
+pub fn stop_capture() -> Vec<CapturedOutput> {
+    with_context(|c| c.stop_capture())
+}
+
+#[cfg(feature = "std")]
+pub fn drain_capture() -> Vec<CapturedOutput> {
+    with_context(|c| c.drain_capture())
+}
+
+/// Returns the content back if capture is not enabled so the caller can write it
+#[cfg(feature = "std")]
+pub fn capture_output(stream: OutputStream, content: String) -> Result<(), String> {
+    with_context(|c| capture_in(c, stream, content))
+}
+
+#[cfg(not(feature = "std"))]
+pub type OutputHandler = fn(OutputStream, &str);
+
+#[cfg(not(feature = "std"))]
+static OUTPUT_HANDLER: spin::Mutex<Option<OutputHandler>> = spin::Mutex::new(None);
+
+/// There's no stdout without std, output is written to `handler` instead
+#[cfg(not(feature = "std"))]
+pub fn set_output_handler(handler: OutputHandler) {
+    *OUTPUT_HANDLER.lock() = Some(handler);
+}
+
+/// Returns the content back if no output handler is set, see `set_output_handler`
+#[cfg(not(feature = "std"))]
+pub fn capture_output(stream: OutputStream, content: String) -> Result<(), String> {
+    let handler = *OUTPUT_HANDLER.lock();
+    match handler {
+        Some(handler) => {
+            handler(stream, &content);
+            Ok(())
+        }
+        None => Err(content),
+    }
+}
+
+#[cfg(feature = "std")]
+fn capture_in(
+    context: &RuntimeContext,
+    stream: OutputStream,
+    content: String,
+) -> Result<(), String> {
+    let mut capture = context.capture();
+    let Some(captured) = capture.as_mut() else {
+        return Err(content);
+    };
+    let source = SOURCE.with(|s| s.borrow().clone());
+    // continue partial lines from the same source instead of creating a new entry
+    let last = captured
+        .iter_mut()
+        .rev()
+        .find(|c| c.source == source && c.stream == stream);
+    match last {
+        Some(last) if !last.content.ends_with('\n') => last.content.push_str(content.as_str()),
+        _ => captured.push(CapturedOutput {
+            source,
+            stream,
+            content,
+        }),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod capture_tests {
+    use crate::{
+        capture_output, set_output_source, start_capture, stop_capture, OutputSource, OutputStream,
+    };
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn capture_tags_source() {
+        start_capture();
+        let previous = set_output_source(OutputSource::process(1, "message"));
+        capture_output(OutputStream::Stdout, "a".to_string()).unwrap();
+        set_output_source(OutputSource::main("test"));
+        capture_output(OutputStream::Stdout, "b\n".to_string()).unwrap();
+        set_output_source(OutputSource::process(1, "message"));
+        capture_output(OutputStream::Stdout, "c\n".to_string()).unwrap();
+        set_output_source(previous);
+        let captured = stop_capture();
+        assert_eq!(captured.len(), 2);
+        assert_eq!(captured[0].content, "ac\n");
+        assert_eq!(captured[0].to_string(), "[process 1:message] ac");
+        assert_eq!(captured[1].to_string(), "[main:test] b");
+        assert!(capture_output(OutputStream::Stdout, "d".to_string()).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::deterministic::Deterministic;
+use crate::{CapturedOutput, FloatFormat, InternTable, Registry};
+use core::any::Any;
+use core::cell::RefCell;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
+
+/// State shared by a VM and its processes, each VM creates its own so runtimes in the same host process
+/// don't see each other's captured output, float format, interned strings, seeded randomness, or tracked state.
+/// Clones share the state.
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeContext {
+    capture: Arc<Mutex<Option<Vec<CapturedOutput>>>>,
+    float_format: Arc<RwLock<FloatFormat>>,
+    pub(crate) deterministic: Deterministic,
+    pub(crate) interned: InternTable,
+    // states created by `Tracked::new`, see `collect`
+    pub(crate) tracked: Arc<Registry>,
+    // identity of the context for `with_local`
+    id: Arc<()>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<RuntimeContext> = RefCell::new(RuntimeContext::default());
+    static LOCALS: RefCell<Vec<Local>> = const { RefCell::new(Vec::new()) };
+}
+
+// value of `with_local` & its context, dropped along with the thread or once the context is dropped
+type Local = (Weak<()>, Box<dyn Any>);
+
+/// Restores the previous context of the thread when dropped
+#[must_use]
+pub struct ContextGuard {
+    previous: Option<RuntimeContext>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CONTEXT.with(|c| c.replace(previous));
+        }
+    }
+}
+
+/// Output, float formatting, interning, deterministic mode, & tracked state on the current thread use `context`
+/// until the guard is dropped, threads that never enter a context have their own
+pub fn enter_context(context: RuntimeContext) -> ContextGuard {
+    let previous = CONTEXT.with(|c| c.replace(context));
+    ContextGuard {
+        previous: Some(previous),
+    }
+}
+
+/// Context of the current thread, used to run processes & workers with the same context as the VM that started them
+pub fn current_context() -> RuntimeContext {
+    CONTEXT.with(|c| c.borrow().clone())
+}
+
+pub(crate) fn with_context<T>(f: impl FnOnce(&RuntimeContext) -> T) -> T {
+    CONTEXT.with(|c| f(&c.borrow()))
+}
+
+/// State of the current context that can't leave the thread, i.e. values referencing the stack or VMs.
+/// Each context has its own `T` on each thread, created with `T::default()` on first use.
+pub fn with_local<T: Default + 'static, R>(f: impl FnOnce(&mut T) -> R) -> R {
+    let context = with_context(|c| Arc::downgrade(&c.id));
+    // taken out while `f` runs, so `f` & values dropped here can use other locals
+    let (local, dropped) = LOCALS.with(|l| {
+        let mut locals = l.borrow_mut();
+        let (live, dropped) = core::mem::take(&mut *locals)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(c, _)| c.strong_count() > 0);
+        *locals = live;
+        let local = locals
+            .iter()
+            .position(|(c, v)| c.ptr_eq(&context) && v.is::<T>())
+            .map(|i| locals.swap_remove(i).1);
+        (local, dropped)
+    });
+    drop(dropped);
+    let mut local = local.unwrap_or_else(|| Box::new(T::default()));
+    let result = f(local.downcast_mut().expect("local has the requested type"));
+    LOCALS.with(|l| l.borrow_mut().push((context, local)));
+    result
+}
+
+impl RuntimeContext {
+    /// Shares everything but the seeded generator & virtual clock, each process of a deterministic runtime gets
+    /// its own so the values it sees don't depend on how processes are scheduled
+    pub fn for_process(&self) -> Self {
+        RuntimeContext {
+            deterministic: Deterministic::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Strings interned by `Interned::new` while this context is entered
+    pub fn intern_table(&self) -> &InternTable {
+        &self.interned
+    }
+
+    /// Mutexes, channels, & tasks created in this context that haven't been freed, see `collect`
+    pub fn shared_states(&self) -> usize {
+        self.tracked.live()
+    }
+
+    pub(crate) fn capture(&self) -> MutexGuard<'_, Option<Vec<CapturedOutput>>> {
+        self.capture.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// All output written through `out!`, `outln!`, `err!`, & `errln!` in this context is stored until
+    /// `stop_capture` is called
+    pub fn start_capture(&self) {
+        *self.capture() = Some(Vec::new());
+    }
+
+    pub fn stop_capture(&self) -> Vec<CapturedOutput> {
+        self.capture().take().unwrap_or_default()
+    }
+
+    /// Returns the output captured so far without stopping the capture, used to forward output while a program runs
+    pub fn drain_capture(&self) -> Vec<CapturedOutput> {
+        self.capture()
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn float_format(&self) -> FloatFormat {
+        *self.float_format.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the previous format
+    pub fn set_float_format(&self, format: FloatFormat) -> FloatFormat {
+        let mut current = self.float_format.write().unwrap_or_else(|e| e.into_inner());
+        core::mem::replace(&mut *current, format)
+    }
+}
+
+#[cfg(test)]
+pub mod context_tests {
+    use crate::{
+        capture_output, current_context, enter_context, float_format, set_float_format,
+        start_capture, stop_capture, with_local, FloatFormat, Interned, OutputStream,
+        RuntimeContext,
+    };
+    use std::thread;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn contexts_are_isolated() {
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    let context = RuntimeContext::default();
+                    let _context = enter_context(context.clone());
+                    start_capture();
+                    set_float_format(FloatFormat::with_precision(i + 1));
+                    for _ in 0..10 {
+                        capture_output(OutputStream::Stdout, format!("{i}\n")).unwrap();
+                        thread::yield_now();
+                    }
+                    assert_eq!(float_format(), FloatFormat::with_precision(i + 1));
+                    let captured = context.stop_capture();
+                    assert_eq!(captured.len(), 10);
+                    assert!(captured.iter().all(|c| c.content == format!("{i}\n")));
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn guard_restores_previous() {
+        let context = RuntimeContext::default();
+        {
+            let _context = enter_context(context.clone());
+            start_capture();
+            capture_output(OutputStream::Stdout, "a\n".to_string()).unwrap();
+        }
+        assert!(capture_output(OutputStream::Stdout, "b\n".to_string()).is_err());
+        assert!(stop_capture().is_empty());
+        assert_eq!(context.stop_capture().len(), 1);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn intern_tables_are_isolated() {
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    let context = RuntimeContext::default();
+                    let _context = enter_context(context.clone());
+                    let value = Interned::new(&format!("context_tests::{i}"));
+                    (context, value)
+                })
+            })
+            .collect();
+        let contexts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        for (i, (context, _)) in contexts.iter().enumerate() {
+            for j in 0..4 {
+                let value = format!("context_tests::{j}");
+                assert_eq!(context.intern_table().contains(&value), i == j);
+            }
+        }
+        assert!(!current_context()
+            .intern_table()
+            .contains("context_tests::0"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn locals_are_per_context() {
+        with_local(|v: &mut Vec<usize>| v.push(1));
+        {
+            let _context = enter_context(RuntimeContext::default());
+            assert!(with_local(|v: &mut Vec<usize>| v.is_empty()));
+        }
+        assert_eq!(with_local(|v: &mut Vec<usize>| v.clone()), vec![1]);
+    }
+}
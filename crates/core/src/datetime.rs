@@ -0,0 +1,576 @@
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::virtual_now;
+use crate::{
+    humanize_duration, parse_duration, AsPrimitive, BinaryOperation, CreateObject, CustomType,
+    Definition, Number, Object, ObjectValue, PrimitiveValue, RigzArgs, RigzType, VMError,
+    WithTypeInfo,
+};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, SecondsFormat, Timelike, Utc};
+use chrono_tz::Tz;
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter, Write};
+use core::hash::{Hash, Hasher};
+
+const NANOS_PER_MILLI: i64 = 1_000_000;
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// Span of time in nanoseconds, negative when subtracting a later time. Numbers are milliseconds,
+/// i.e. `sleep 5.seconds`
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Duration {
+    pub nanos: i64,
+}
+
+/// Point in time as nanoseconds since the unix epoch. `zone` only changes how it's read & displayed, i.e. `hour` &
+/// `format`, times are compared as instants regardless of their zone. UTC without a zone
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct DateTime {
+    pub nanos: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<Tz>,
+}
+
+impl Duration {
+    pub fn from_nanos(nanos: i64) -> Self {
+        Duration { nanos }
+    }
+
+    /// Fractions are rounded to the nearest nanosecond, i.e. `1.5.seconds`
+    pub fn scaled(value: &Number, nanos: i64) -> Self {
+        let nanos = match value {
+            Number::Int(i) => i.saturating_mul(nanos),
+            n => (n.to_float() * nanos as f64).round() as i64,
+        };
+        Duration { nanos }
+    }
+
+    /// Accepts the format of `humanize`, i.e. `2h 3m` or `-1.5s`
+    pub fn parse(value: &str) -> Result<Self, VMError> {
+        let (value, negative) = match value.trim().strip_prefix('-') {
+            Some(v) => (v, true),
+            None => (value.trim(), false),
+        };
+        let nanos = parse_duration(value)?.as_nanos();
+        let nanos = i64::try_from(nanos).map_err(|_| {
+            VMError::ConversionError(format!("Cannot convert {value:?} to Duration - too large"))
+        })?;
+        Ok(Duration {
+            nanos: if negative { -nanos } else { nanos },
+        })
+    }
+
+    /// Two largest units, i.e. `2h 3m`, negative durations start with `-`
+    pub fn humanize(&self) -> String {
+        let humanized =
+            humanize_duration(core::time::Duration::from_nanos(self.nanos.unsigned_abs()));
+        if self.nanos < 0 {
+            format!("-{humanized}")
+        } else {
+            humanized
+        }
+    }
+
+    fn downcast(value: &ObjectValue) -> Option<&Duration> {
+        match value {
+            ObjectValue::Object(o) => o.downcast_ref::<Duration>(),
+            _ => None,
+        }
+    }
+}
+
+impl DateTime {
+    /// Current time, or the virtual clock while `VMOptions::deterministic` is set
+    #[cfg(feature = "std")]
+    pub fn now() -> Self {
+        let nanos =
+            virtual_now().unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or(i64::MAX));
+        DateTime { nanos, zone: None }
+    }
+
+    pub fn from_millis(millis: i64) -> Self {
+        DateTime {
+            nanos: millis.saturating_mul(NANOS_PER_MILLI),
+            zone: None,
+        }
+    }
+
+    /// RFC 3339 (`2024-05-01T12:30:00Z`) without a pattern, otherwise a strftime pattern. Patterns without an offset
+    /// are read as UTC and patterns without a time as midnight
+    pub fn parse(value: &str, pattern: Option<&str>) -> Result<Self, VMError> {
+        let invalid = |e: chrono::ParseError| {
+            VMError::ConversionError(format!("Cannot convert {value:?} to DateTime - {e}"))
+        };
+        let utc = match pattern {
+            None => chrono::DateTime::parse_from_rfc3339(value)
+                .map_err(invalid)?
+                .to_utc(),
+            Some(pattern) => match chrono::DateTime::parse_from_str(value, pattern) {
+                Ok(d) => d.to_utc(),
+                Err(_) => match NaiveDateTime::parse_from_str(value, pattern) {
+                    Ok(d) => d.and_utc(),
+                    Err(_) => NaiveDate::parse_from_str(value, pattern)
+                        .map_err(invalid)?
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap_or_default()
+                        .and_utc(),
+                },
+            },
+        };
+        utc.timestamp_nanos_opt()
+            .map(|nanos| DateTime { nanos, zone: None })
+            .ok_or_else(|| {
+                VMError::ConversionError(format!(
+                    "Cannot convert {value:?} to DateTime - out of range"
+                ))
+            })
+    }
+
+    fn utc(&self) -> chrono::DateTime<Utc> {
+        chrono::DateTime::from_timestamp_nanos(self.nanos)
+    }
+
+    fn local(&self) -> chrono::DateTime<Tz> {
+        self.utc().with_timezone(&self.zone.unwrap_or(Tz::UTC))
+    }
+
+    /// Same instant in an IANA time zone, i.e. `America/New_York`
+    pub fn in_zone(&self, zone: &str) -> Result<Self, VMError> {
+        let zone = zone
+            .parse()
+            .map_err(|e| VMError::ConversionError(format!("Invalid time zone {zone:?} - {e}")))?;
+        Ok(DateTime {
+            nanos: self.nanos,
+            zone: Some(zone),
+        })
+    }
+
+    pub fn zone_name(&self) -> &'static str {
+        self.zone.unwrap_or(Tz::UTC).name()
+    }
+
+    /// strftime pattern, i.e. `%Y-%m-%d %H:%M`
+    pub fn format(&self, pattern: &str) -> Result<String, VMError> {
+        let items: Vec<_> = StrftimeItems::new(pattern).collect();
+        if items.iter().any(|i| matches!(i, Item::Error)) {
+            return Err(VMError::RuntimeError(format!(
+                "Invalid DateTime format {pattern:?}"
+            )));
+        }
+        let mut result = String::new();
+        write!(
+            result,
+            "{}",
+            self.local().format_with_items(items.into_iter())
+        )
+        .map_err(|_| VMError::RuntimeError(format!("Cannot format {self} with {pattern:?}")))?;
+        Ok(result)
+    }
+
+    /// Time since this instant, negative for instants in the future
+    #[cfg(feature = "std")]
+    pub fn elapsed(&self) -> Result<Duration, VMError> {
+        DateTime::now()
+            .nanos
+            .checked_sub(self.nanos)
+            .map(Duration::from_nanos)
+            .ok_or_else(|| VMError::RuntimeError(format!("Time overflow: elapsed since {self}")))
+    }
+
+    fn downcast(value: &ObjectValue) -> Option<&DateTime> {
+        match value {
+            ObjectValue::Object(o) => o.downcast_ref::<DateTime>(),
+            _ => None,
+        }
+    }
+
+    fn overflow(&self, operation: BinaryOperation, other: &ObjectValue) -> ObjectValue {
+        VMError::RuntimeError(format!("Time overflow: {self} {operation} {other}")).into()
+    }
+}
+
+impl From<Duration> for ObjectValue {
+    fn from(value: Duration) -> Self {
+        ObjectValue::Object(Box::new(value))
+    }
+}
+
+impl From<DateTime> for ObjectValue {
+    fn from(value: DateTime) -> Self {
+        ObjectValue::Object(Box::new(value))
+    }
+}
+
+impl Display for Duration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.humanize())
+    }
+}
+
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.local().to_rfc3339_opts(SecondsFormat::AutoSi, true)
+        )
+    }
+}
+
+impl PartialEq for DateTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.nanos == other.nanos
+    }
+}
+
+impl Eq for DateTime {}
+
+impl PartialOrd for DateTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DateTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.nanos.cmp(&other.nanos)
+    }
+}
+
+impl Hash for DateTime {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nanos.hash(state)
+    }
+}
+
+impl WithTypeInfo for Duration {
+    fn rigz_type(&self) -> RigzType {
+        RigzType::Custom(CustomType {
+            name: Self::name().to_string(),
+            fields: vec![],
+        })
+    }
+}
+
+impl WithTypeInfo for DateTime {
+    fn rigz_type(&self) -> RigzType {
+        RigzType::Custom(CustomType {
+            name: Self::name().to_string(),
+            fields: vec![],
+        })
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Duration {
+    fn to_number(&self) -> Result<Number, VMError> {
+        Ok((self.nanos / NANOS_PER_MILLI).into())
+    }
+
+    fn binary_operation(
+        &self,
+        operation: BinaryOperation,
+        other: &ObjectValue,
+        reversed: bool,
+    ) -> Option<ObjectValue> {
+        let nanos = match (operation, other) {
+            // DateTime handles `Duration + DateTime`
+            (BinaryOperation::Add, other) => {
+                self.nanos.checked_add(Duration::downcast(other)?.nanos)?
+            }
+            (BinaryOperation::Sub, other) => {
+                let other = Duration::downcast(other)?.nanos;
+                if reversed {
+                    other.checked_sub(self.nanos)?
+                } else {
+                    self.nanos.checked_sub(other)?
+                }
+            }
+            (BinaryOperation::Mul, ObjectValue::Primitive(PrimitiveValue::Number(n))) => {
+                Duration::scaled(n, self.nanos).nanos
+            }
+            (BinaryOperation::Div, ObjectValue::Primitive(PrimitiveValue::Number(n)))
+                if !reversed =>
+            {
+                (self.nanos as f64 / n.to_float()) as i64
+            }
+            (BinaryOperation::Div, other) => {
+                let other = Duration::downcast(other)?.nanos as f64;
+                let ratio = if reversed {
+                    other / self.nanos as f64
+                } else {
+                    self.nanos as f64 / other
+                };
+                return Some(ratio.into());
+            }
+            _ => return None,
+        };
+        Some(Duration { nanos }.into())
+    }
+}
+
+impl AsPrimitive<ObjectValue> for DateTime {
+    fn to_number(&self) -> Result<Number, VMError> {
+        Ok((self.nanos / NANOS_PER_MILLI).into())
+    }
+
+    fn binary_operation(
+        &self,
+        operation: BinaryOperation,
+        other: &ObjectValue,
+        reversed: bool,
+    ) -> Option<ObjectValue> {
+        let nanos = match (operation, reversed) {
+            (BinaryOperation::Sub, false) => {
+                if let Some(d) = DateTime::downcast(other) {
+                    return Some(match self.nanos.checked_sub(d.nanos) {
+                        Some(nanos) => Duration::from_nanos(nanos).into(),
+                        None => self.overflow(operation, other),
+                    });
+                }
+                self.nanos.checked_sub(Duration::downcast(other)?.nanos)
+            }
+            (BinaryOperation::Add, _) => self.nanos.checked_add(Duration::downcast(other)?.nanos),
+            _ => return None,
+        };
+        Some(match nanos {
+            Some(nanos) => DateTime { nanos, ..*self }.into(),
+            None => self.overflow(operation, other),
+        })
+    }
+}
+
+impl CreateObject for Duration {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?;
+        let value = value.borrow();
+        match &*value {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => Duration::parse(s),
+            v => Ok(Duration::from_nanos(v.to_int()?)),
+        }
+    }
+}
+
+impl CreateObject for DateTime {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?;
+        let value = value.borrow();
+        match &*value {
+            #[cfg(feature = "std")]
+            ObjectValue::Primitive(PrimitiveValue::None) => Ok(DateTime::now()),
+            #[cfg(not(feature = "std"))]
+            ObjectValue::Primitive(PrimitiveValue::None) => Err(VMError::UnsupportedOperation(
+                "DateTime.now is not available without std".to_string(),
+            )),
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => DateTime::parse(s, None),
+            v => Ok(DateTime::from_millis(v.to_int()?)),
+        }
+    }
+}
+
+impl Definition for Duration {
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Duration"
+    }
+
+    fn trait_definition() -> &'static str
+    where
+        Self: Sized,
+    {
+        r#"object Duration
+            Self(value: Any = 0)
+            fn Self.as_nanos -> Int
+            fn Self.as_millis -> Float
+            fn Self.as_secs -> Float
+            fn Self.humanize -> String
+        end"#
+    }
+}
+
+impl Definition for DateTime {
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "DateTime"
+    }
+
+    fn trait_definition() -> &'static str
+    where
+        Self: Sized,
+    {
+        r#"object DateTime
+            Self(value: Any? = none)
+            fn Self.format(pattern: String) -> String!
+            fn Self.in_zone(zone: String) -> DateTime!
+            fn Self.zone -> String
+            fn Self.timestamp -> Int
+            fn Self.as_millis -> Int
+            fn Self.year -> Int
+            fn Self.month -> Int
+            fn Self.day -> Int
+            fn Self.hour -> Int
+            fn Self.minute -> Int
+            fn Self.second -> Int
+            fn Self.elapsed -> Duration!
+        end"#
+    }
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl Object for Duration {
+    fn call_extension(&self, function: String, args: RigzArgs) -> Result<ObjectValue, VMError> {
+        let v: ObjectValue = match function.as_str() {
+            "as_nanos" => self.nanos.into(),
+            "as_millis" => (self.nanos as f64 / NANOS_PER_MILLI as f64).into(),
+            "as_secs" => (self.nanos as f64 / NANOS_PER_SECOND as f64).into(),
+            "humanize" | "to_s" => self.humanize().into(),
+            _ => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "Duration does not implement `{function}` - {args:?}"
+                )))
+            }
+        };
+        Ok(v)
+    }
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl Object for DateTime {
+    fn call_extension(&self, function: String, args: RigzArgs) -> Result<ObjectValue, VMError> {
+        let local = self.local();
+        let v: ObjectValue = match function.as_str() {
+            "format" => {
+                let pattern = args.first()?.borrow().to_string();
+                self.format(&pattern)?.into()
+            }
+            "in_zone" => {
+                let zone = args.first()?.borrow().to_string();
+                self.in_zone(&zone)?.into()
+            }
+            "zone" => self.zone_name().into(),
+            "timestamp" => local.timestamp().into(),
+            "as_millis" => local.timestamp_millis().into(),
+            "year" => (local.year() as i64).into(),
+            "month" => (local.month() as i64).into(),
+            "day" => (local.day() as i64).into(),
+            "hour" => (local.hour() as i64).into(),
+            "minute" => (local.minute() as i64).into(),
+            "second" => (local.second() as i64).into(),
+            "to_s" => self.to_string().into(),
+            #[cfg(feature = "std")]
+            "elapsed" => self.elapsed()?.into(),
+            _ => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "DateTime does not implement `{function}` - {args:?}"
+                )))
+            }
+        };
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+pub mod datetime_tests {
+    use crate::{AsPrimitive, BinaryOperation, DateTime, Duration, ObjectValue};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn duration_arithmetic() {
+        let minute: ObjectValue = Duration::from_nanos(60_000_000_000).into();
+        let second: ObjectValue = Duration::parse("1s").unwrap().into();
+        assert_eq!(
+            &minute + &second,
+            ObjectValue::from(Duration::parse("1m 1s").unwrap())
+        );
+        assert_eq!(&second - &minute, Duration::parse("-59s").unwrap().into());
+        assert_eq!(&minute / &second, ObjectValue::from(60.0));
+        assert_eq!(
+            &second * &ObjectValue::from(1.5),
+            Duration::parse("1.5s").unwrap().into()
+        );
+        assert!(minute > second);
+        assert_eq!(Duration::parse("-1m 30s").unwrap().to_string(), "-1m 30s");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn datetime_arithmetic() {
+        let start = DateTime::parse("2024-02-28T23:00:00Z", None).unwrap();
+        let later = start
+            .binary_operation(
+                BinaryOperation::Add,
+                &Duration::parse("2h").unwrap().into(),
+                false,
+            )
+            .unwrap();
+        assert_eq!(later.to_string(), "2024-02-29T01:00:00Z");
+        assert_eq!(
+            &later - &ObjectValue::from(start),
+            Duration::parse("2h").unwrap().into()
+        );
+        assert!(later > ObjectValue::from(start));
+        assert_eq!(
+            &Duration::parse("1d").unwrap().into() + &ObjectValue::from(start),
+            DateTime::parse("2024-02-29T23:00:00Z", None)
+                .unwrap()
+                .into()
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn datetime_parse_and_format() {
+        let d = DateTime::parse("2024-05-01 12:30", Some("%Y-%m-%d %H:%M")).unwrap();
+        assert_eq!(d.to_string(), "2024-05-01T12:30:00Z");
+        assert_eq!(d.format("%d/%m/%Y").unwrap(), "01/05/2024");
+        let d = DateTime::parse("2024-05-01T14:30:00.5+02:00", None).unwrap();
+        assert_eq!(d.to_string(), "2024-05-01T12:30:00.500Z");
+        assert_eq!(
+            DateTime::parse("05/01/2024", Some("%m/%d/%Y")).unwrap(),
+            DateTime::parse("2024-05-01T00:00:00Z", None).unwrap()
+        );
+        assert!(DateTime::parse("yesterday", None).is_err());
+        assert!(d.format("%Q").is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn datetime_zones() {
+        let d = DateTime::parse("2024-01-15T12:00:00Z", None).unwrap();
+        let tokyo = d.in_zone("Asia/Tokyo").unwrap();
+        assert_eq!(tokyo.to_string(), "2024-01-15T21:00:00+09:00");
+        assert_eq!(tokyo.format("%H %Z").unwrap(), "21 JST");
+        assert_eq!(tokyo.zone_name(), "Asia/Tokyo");
+        assert_eq!(d.zone_name(), "UTC");
+        assert_eq!(tokyo, d);
+        let json = serde_json::to_string(&tokyo).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DateTime>(&json).unwrap().zone,
+            tokyo.zone
+        );
+        assert_eq!(
+            serde_json::to_string(&d).unwrap(),
+            format!("{{\"nanos\":{}}}", d.nanos)
+        );
+        assert!(d.in_zone("Nowhere").is_err());
+    }
+}
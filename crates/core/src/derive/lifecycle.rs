@@ -1,9 +1,11 @@
-use crate::derive::csv_vec;
+use crate::derive::{boxed, csv_vec, option};
 use crate::{
-    EventLifecycle, Lifecycle, MemoizedLifecycle, Stage, StatefulLifecycle, TestLifecycle,
+    EventLifecycle, Lifecycle, MemoResult, MemoizedLifecycle, PersistentMemo, Stage,
+    StatefulLifecycle, TestLifecycle,
 };
+use core::num::NonZeroUsize;
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 
 impl ToTokens for Lifecycle {
     fn to_tokens(&self, tokens: &mut TokenStream) {
@@ -33,10 +35,26 @@ impl ToTokens for Lifecycle {
 
 impl ToTokens for EventLifecycle {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let EventLifecycle { event } = self;
+        let EventLifecycle {
+            event,
+            capacity,
+            overflow,
+            restart,
+            max_restarts,
+            backoff,
+            detached,
+        } = self;
+        let overflow = format_ident!("{}", format!("{overflow:?}"));
+        let restart = format_ident!("{}", format!("{restart:?}"));
         tokens.extend(quote! {
             EventLifecycle {
-                event: #event.into()
+                event: #event.into(),
+                capacity: #capacity,
+                overflow: Backpressure::#overflow,
+                restart: Restart::#restart,
+                max_restarts: #max_restarts,
+                backoff: #backoff,
+                detached: #detached,
             }
         })
     }
@@ -44,7 +62,22 @@ impl ToTokens for EventLifecycle {
 
 impl ToTokens for MemoizedLifecycle {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let MemoizedLifecycle { results } = self;
+        let MemoizedLifecycle {
+            results,
+            persist,
+            limit,
+            ttl,
+        } = self;
+        let persist = option(&persist.as_ref().map(boxed));
+        let non_zero = |n: &Option<NonZeroUsize>| match n {
+            None => quote! { None },
+            Some(n) => {
+                let n = n.get();
+                quote! { core::num::NonZeroUsize::new(#n) }
+            }
+        };
+        let limit = non_zero(limit);
+        let ttl = non_zero(ttl);
         let results: Vec<_> = results
             .iter()
             .map(|(k, v)| {
@@ -56,12 +89,39 @@ impl ToTokens for MemoizedLifecycle {
             .collect();
         tokens.extend(quote! {
             MemoizedLifecycle {
-                results: HashMap::from(#(#results)*)
+                results: IndexMap::from([#(#results)*]),
+                persist: #persist,
+                limit: #limit,
+                ttl: #ttl,
             }
         });
     }
 }
 
+impl ToTokens for MemoResult {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let MemoResult { value, created } = self;
+        tokens.extend(quote! {
+            MemoResult {
+                value: #value,
+                created: #created,
+            }
+        })
+    }
+}
+
+impl ToTokens for PersistentMemo {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let PersistentMemo { path, key } = self;
+        tokens.extend(quote! {
+            PersistentMemo {
+                path: #path.into(),
+                key: #key.into(),
+            }
+        })
+    }
+}
+
 impl ToTokens for Stage {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let t = match self {
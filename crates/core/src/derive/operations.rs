@@ -41,6 +41,8 @@ impl ToTokens for BinaryOperation {
             BinaryOperation::Lt => quote! { BinaryOperation::Lt },
             BinaryOperation::Lte => quote! { BinaryOperation::Lte },
             BinaryOperation::Elvis => quote! { BinaryOperation::Elvis },
+            BinaryOperation::Range => quote! { BinaryOperation::Range },
+            BinaryOperation::RangeInclusive => quote! { BinaryOperation::RangeInclusive },
         };
         tokens.extend(t);
     }
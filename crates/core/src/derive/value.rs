@@ -1,7 +1,7 @@
-use crate::derive::csv_vec;
+use crate::derive::{csv_vec, option};
 use crate::{Number, ObjectValue, PrimitiveValue, VMError, ValueRange};
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 
 impl ToTokens for ObjectValue {
     fn to_tokens(&self, tokens: &mut TokenStream) {
@@ -41,6 +41,10 @@ impl ToTokens for Number {
         let t = match self {
             Number::Int(n) => quote! { Number::Int(#n) },
             Number::Float(n) => quote! { Number::Float(#n) },
+            Number::Decimal(d) => {
+                let bytes = d.serialize();
+                quote! { Number::Decimal(Decimal::deserialize([#(#bytes),*])) }
+            }
         };
         tokens.extend(t)
     }
@@ -65,6 +69,7 @@ impl ToTokens for PrimitiveValue {
                 }
             }
             PrimitiveValue::String(s) => {
+                let s = s.as_str();
                 quote! {
                     PrimitiveValue::String(#s.into())
                 }
@@ -104,6 +109,11 @@ impl ToTokens for ValueRange {
                 let c = quote! { #s..#e };
                 quote! { ValueRange::Char(#c) }
             }
+            ValueRange::Date(d) => {
+                let s = d.start.days();
+                let e = d.end.days();
+                quote! { ValueRange::Date(Date::from_days(#s)..Date::from_days(#e)) }
+            }
         };
         tokens.extend(r)
     }
@@ -130,6 +140,23 @@ impl ToTokens for VMError {
             }
             VMError::LifecycleError(s) => quote! { VMError::LifecycleError(#s.into()) },
             VMError::TimeoutError(s) => quote! { VMError::TimeoutError(#s.into()) },
+            VMError::BudgetExceeded(s) => quote! { VMError::BudgetExceeded(#s.into()) },
+            // frames only exist at runtime
+            VMError::Traceback(e, _) => return e.to_tokens(tokens),
+            // spans point into the source the error came from
+            VMError::Diagnostic(d) => {
+                let code = format_ident!("{}", format!("{:?}", d.code));
+                let message = &d.message;
+                let help = option(&d.help);
+                quote! {
+                    VMError::diagnostic(Diagnostic {
+                        code: ErrorCode::#code,
+                        message: #message.to_string(),
+                        span: None,
+                        help: #help.map(|s: &str| s.to_string()),
+                    })
+                }
+            }
         };
         tokens.extend(t)
     }
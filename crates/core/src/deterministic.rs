@@ -0,0 +1,183 @@
+use crate::context::with_context;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Unix time in nanoseconds the virtual clock starts at, 2000-01-01T00:00:00Z
+pub const VIRTUAL_EPOCH_NANOS: i64 = 946_684_800_000_000_000;
+
+#[derive(Clone, Copy, Debug)]
+struct State {
+    rng: u64,
+    clock: i64,
+}
+
+/// Seeded generator & virtual clock of a `RuntimeContext`, none outside of deterministic mode
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Deterministic(Arc<Mutex<Option<State>>>);
+
+impl Deterministic {
+    fn state(&self) -> MutexGuard<'_, Option<State>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Option<State>) -> T) -> T {
+    with_context(|c| f(&mut c.deterministic.state()))
+}
+
+/// Randomness & the clock are virtual in the current context until the guard is dropped, see `VMOptions::deterministic`
+#[must_use]
+pub struct DeterministicGuard {
+    entered: Option<Deterministic>,
+}
+
+impl Drop for DeterministicGuard {
+    fn drop(&mut self) {
+        if let Some(deterministic) = self.entered.take() {
+            *deterministic.state() = None;
+        }
+    }
+}
+
+/// Starts deterministic mode in the current thread's context, when it's already active the existing generator &
+/// clock keep going so nested runs don't repeat values
+pub fn enter_deterministic(seed: u64) -> DeterministicGuard {
+    let deterministic = with_context(|c| c.deterministic.clone());
+    let entered = {
+        let mut state = deterministic.state();
+        match *state {
+            Some(_) => false,
+            None => {
+                *state = Some(State {
+                    rng: seed,
+                    clock: VIRTUAL_EPOCH_NANOS,
+                });
+                true
+            }
+        }
+    };
+    DeterministicGuard {
+        entered: entered.then_some(deterministic),
+    }
+}
+
+pub fn is_deterministic() -> bool {
+    with_state(|s| s.is_some())
+}
+
+/// Next value of the seeded generator (SplitMix64), `None` outside of deterministic mode
+pub fn deterministic_u64() -> Option<u64> {
+    with_state(|s| {
+        let state = s.as_mut()?;
+        state.rng = state.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Some(z ^ (z >> 31))
+    })
+}
+
+/// Nanoseconds since the unix epoch on the virtual clock, `None` outside of deterministic mode.
+/// The clock only moves when the program sleeps.
+pub fn virtual_now() -> Option<i64> {
+    with_state(|s| s.map(|state| state.clock))
+}
+
+/// Moves the virtual clock forward instead of sleeping, returns false outside of deterministic mode
+pub fn advance_virtual_clock(nanos: i64) -> bool {
+    with_state(|s| match s {
+        None => false,
+        Some(state) => {
+            state.clock = state.clock.saturating_add(nanos);
+            true
+        }
+    })
+}
+
+#[cfg(test)]
+pub mod deterministic_tests {
+    use crate::{
+        advance_virtual_clock, deterministic_u64, enter_context, enter_deterministic,
+        is_deterministic, virtual_now, RuntimeContext, VIRTUAL_EPOCH_NANOS,
+    };
+    use std::thread;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn same_seed_same_values() {
+        assert_eq!(deterministic_u64(), None);
+        let first: Vec<_> = {
+            let _guard = enter_deterministic(7);
+            (0..3).map(|_| deterministic_u64()).collect()
+        };
+        assert!(!is_deterministic());
+        let second: Vec<_> = {
+            let _guard = enter_deterministic(7);
+            (0..3).map(|_| deterministic_u64()).collect()
+        };
+        assert_eq!(first, second);
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn virtual_clock() {
+        assert_eq!(virtual_now(), None);
+        assert!(!advance_virtual_clock(5));
+        let _guard = enter_deterministic(0);
+        assert_eq!(virtual_now(), Some(VIRTUAL_EPOCH_NANOS));
+        {
+            // nested runs keep the outer clock
+            let _nested = enter_deterministic(1);
+            assert!(advance_virtual_clock(5));
+        }
+        assert_eq!(virtual_now(), Some(VIRTUAL_EPOCH_NANOS + 5));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn state_is_per_context() {
+        let context = RuntimeContext::default();
+        let _context = enter_context(context.clone());
+        let _guard = enter_deterministic(3);
+        let expected = deterministic_u64();
+        {
+            let _other = enter_context(RuntimeContext::default());
+            assert!(!is_deterministic());
+            let _guard = enter_deterministic(3);
+            assert_eq!(deterministic_u64(), expected);
+        }
+        // processes of the runtime on other threads share the generator
+        let next = thread::spawn(move || {
+            let _context = enter_context(context);
+            deterministic_u64()
+        })
+        .join()
+        .unwrap();
+        assert!(next.is_some());
+        assert_ne!(next, expected);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn concurrent_contexts() {
+        let expected: Vec<_> = {
+            let _context = enter_context(RuntimeContext::default());
+            let _guard = enter_deterministic(11);
+            (0..100).map(|_| deterministic_u64()).collect()
+        };
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    let _context = enter_context(RuntimeContext::default());
+                    let _guard = enter_deterministic(11);
+                    (0..100)
+                        .map(|_| {
+                            thread::yield_now();
+                            deterministic_u64()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+}
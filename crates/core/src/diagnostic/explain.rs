@@ -0,0 +1,495 @@
+use crate::ErrorCode;
+
+impl ErrorCode {
+    /// Extended explanation used by `rigz explain`, includes common causes and an erroneous & working example
+    pub fn explanation(self) -> &'static str {
+        match self {
+            ErrorCode::Runtime => {
+                r#"An error was raised while the program was running.
+
+This is the code for errors created by the program itself (`raise`, failed
+assertions) and for failures inside module functions that don't have a more
+specific code, i.e. integer overflow or a stack overflow from unbounded recursion.
+
+Common causes:
+- `raise` or a failed `assert`/`assert_eq`
+- integer arithmetic that overflows an Int
+- a recursive function without a base case
+
+Erroneous example:
+```
+fn foo = raise "Failure"
+foo
+```
+
+Working example, handle the error with `catch`:
+```
+fn foo = raise "Failure"
+foo catch
+    22
+end
+```"#
+            }
+            ErrorCode::EmptyStack => {
+                r#"An instruction needed a value but the VM stack was empty.
+
+Compiled programs always leave the values an instruction needs on the stack,
+this error means the bytecode is invalid. It usually comes from bytecode built
+by hand, a custom module returning the wrong number of values, or bytecode
+compiled by a different version of rigz.
+
+Common causes:
+- a custom module function that doesn't return a value
+- loading `.rzbc` files written by another version
+
+Erroneous example, running bytecode compiled by another version:
+```
+rigz run old.rzbc
+```
+
+Working example, recompile the program first:
+```
+rigz compile main.rg
+rigz run main.rzbc
+```"#
+            }
+            ErrorCode::Conversion => {
+                r#"A value could not be converted to the requested type.
+
+Conversions such as `to_i`, `to_f`, casts with `as`, and arguments passed to
+typed parameters fail when the value doesn't represent the target type.
+
+Common causes:
+- converting a String that isn't a number, i.e. `'a'.to_i`
+- passing a value to a typed argument that can't be converted
+
+Erroneous example:
+```
+'a'.to_i
+```
+
+Working example, check the value first:
+```
+a = 'a'
+a.to_i if a.is_num
+```"#
+            }
+            ErrorCode::ScopeDoesNotExist => {
+                r#"A scope or instruction referenced by the bytecode does not exist.
+
+Each function body, lambda, and block is compiled into its own scope, jumps
+reference scopes by index. Like E0002 this points to invalid bytecode rather
+than a mistake in the rigz source.
+
+Common causes:
+- bytecode compiled by a different version of rigz
+- a debugger or embedder jumping to a scope that was removed
+
+Erroneous example, running stale bytecode:
+```
+rigz run old.rzbc
+```
+
+Working example, recompile the program first:
+```
+rigz compile main.rg
+rigz run main.rzbc
+```"#
+            }
+            ErrorCode::UnsupportedOperation => {
+                r#"The operation is not supported for these values.
+
+Binary & unary operators and extension functions are defined per type, using
+one with a type that doesn't implement it is an error. Module extension
+functions are only available once the module is imported.
+
+Common causes:
+- calling an extension function from a module that wasn't imported
+- an operator between types that don't support it
+
+Erroneous example, `to_json` is defined by the JSON module:
+```
+1.to_json
+```
+
+Working example:
+```
+import JSON
+1.to_json
+```"#
+            }
+            ErrorCode::VariableDoesNotExist => {
+                r#"The variable does not exist, or it exists but is immutable.
+
+Variables must be assigned before they are read, and only variables declared
+with `mut` can be reassigned or modified in place.
+
+Common causes:
+- a typo in the variable name
+- reading a variable that was assigned in a different function
+- modifying a variable declared without `mut`
+
+Erroneous example:
+```
+a = 4
+a += 2
+a
+```
+
+Working example:
+```
+mut a = 4
+a += 2
+a
+```"#
+            }
+            ErrorCode::InvalidModule => {
+                r#"The module does not exist.
+
+Modules must be registered with the runtime before they can be called, the
+default modules are registered by `Runtime::new`. Embedders that create a
+runtime without the default modules need to register each module they use.
+
+Common causes:
+- a typo in the module name
+- a runtime created without the module being registered
+
+Erroneous example:
+```
+import Jsn
+Jsn.parse '5'
+```
+
+Working example:
+```
+import JSON
+JSON.parse '5'
+```"#
+            }
+            ErrorCode::InvalidModuleFunction => {
+                r#"The function does not exist in the module.
+
+Every module declares its functions in a trait, calling a function that isn't
+part of that trait, or calling it with the wrong receiver, fails.
+
+Common causes:
+- a typo in the function name
+- calling an extension function on a type the module doesn't extend
+
+Erroneous example:
+```
+import JSON
+JSON.parsed '5'
+```
+
+Working example:
+```
+import JSON
+JSON.parse '5'
+```"#
+            }
+            ErrorCode::Lifecycle => {
+                r#"A lifecycle function failed.
+
+Functions with lifecycles (`@on`, `@after`, `@memo`, `@test`) are called by
+the VM instead of directly, errors raised by them or by the lifecycle itself
+(i.e. a full `@on` mailbox) are reported with this code.
+
+Common causes:
+- an `@on` handler raising an error
+- an `@memo(persist: path)` cache that can't be read or written
+
+Erroneous example:
+```
+@on("message")
+fn foo(a) = raise "Failure"
+send 'message', 1
+```
+
+Working example:
+```
+@on("message")
+fn foo(a) = a * 2
+send 'message', 1
+```"#
+            }
+            ErrorCode::Timeout => {
+                r#"The operation did not complete before its timeout.
+
+`receive` waits for the result of an `@on` handler, when a timeout is given and
+the handler doesn't finish in time the call fails.
+
+Common causes:
+- a slow handler with a short timeout
+- a handler that never finishes
+
+Erroneous example:
+```
+@on("message")
+fn foo(a)
+    sleep 1
+    a * 2
+end
+
+pids = send 'message', 21
+receive pids.0, 0
+```
+
+Working example, wait without a timeout:
+```
+@on("message")
+fn foo(a)
+    sleep 1
+    a * 2
+end
+
+pids = send 'message', 21
+receive pids.0
+```"#
+            }
+            ErrorCode::BudgetExceeded => {
+                r#"The program ran more instructions than allowed.
+
+`VMOptions::max_instructions` (or `rigz run --max-instructions`) limits the
+number of instructions a program can run, once the budget is used up every
+following instruction fails with this error.
+
+Common causes:
+- an infinite loop or unbounded recursion
+- a budget that is too small for the program
+
+Erroneous example, with `--max-instructions 100`:
+```
+fn fib(n: Int) -> Int
+    if n <= 1
+        n
+    else
+        (fib n - 1) + (fib n - 2)
+    end
+end
+fib 15
+```
+
+Working example, raise the budget or run without one:
+```
+rigz run --max-instructions 1000000 main.rg
+```"#
+            }
+            ErrorCode::Syntax => {
+                r#"The input is not valid rigz syntax.
+
+This is the general code for parse errors that don't have a more specific code,
+the message describes what the parser expected.
+
+Common causes:
+- a missing `end` or `do`
+- a definition where an expression is expected
+
+Erroneous example, a program must end with an expression:
+```
+a = 3 * 2
+```
+
+Working example:
+```
+a = 3 * 2
+a
+```"#
+            }
+            ErrorCode::UnexpectedEnd => {
+                r#"The input ended before the expression was complete.
+
+The parser reached the end of the file while it was still expecting tokens,
+usually because a block or an expression was left open.
+
+Common causes:
+- a missing `end` for `fn`, `if`, `do`, or `object`
+- a trailing operator, i.e. `1 +`
+- an unclosed `(`, `[`, or `{`
+
+Erroneous example:
+```
+fn foo
+    1
+```
+
+Working example:
+```
+fn foo
+    1
+end
+foo
+```"#
+            }
+            ErrorCode::UnexpectedToken => {
+                r#"The token does not match what the parser expected.
+
+Some positions require a specific token, i.e. the name after `fn` or the `=`
+in an assignment. The message includes the expected and the received token.
+
+Common causes:
+- a missing `=` in a type definition or default argument
+- a keyword used as a name
+
+Erroneous example:
+```
+type Id Int
+1
+```
+
+Working example:
+```
+type Id = Int
+1
+```"#
+            }
+            ErrorCode::InvalidExpression => {
+                r#"The token cannot start an expression.
+
+Expressions start with a value, identifier, or keyword. Closing delimiters and
+operators without a left hand side are reported with this code.
+
+Common causes:
+- an extra `)`, `]`, or `end`
+- a binary operator at the start of a line
+
+Erroneous example:
+```
+a = )
+```
+
+Working example:
+```
+a = 1
+a
+```"#
+            }
+            ErrorCode::InvalidNumber => {
+                r#"The number could not be parsed.
+
+Integers must fit in a 64 bit signed Int and floats must be valid decimal
+numbers.
+
+Common causes:
+- an integer literal larger than 9223372036854775807
+- more than one decimal point
+
+Erroneous example:
+```
+9223372036854775808
+```
+
+Working example, use a Float for large values:
+```
+9223372036854775808.0
+```"#
+            }
+            ErrorCode::InvalidCharacter => {
+                r#"The input contains a character that isn't part of the language.
+
+Outside of strings and comments only ASCII identifiers, numbers, and operators
+are valid.
+
+Common causes:
+- smart quotes pasted from a document, i.e. `“hello”`
+- non ASCII characters in identifiers
+
+Erroneous example:
+```
+“hello”
+```
+
+Working example:
+```
+"hello"
+```"#
+            }
+            ErrorCode::InvalidBool => {
+                r#"The bool could not be parsed.
+
+Bools are `true` or `false`, any other spelling is an error.
+
+Common causes:
+- a capitalized bool, i.e. `True`
+
+Erroneous example:
+```
+a = True
+a
+```
+
+Working example:
+```
+a = true
+a
+```"#
+            }
+            ErrorCode::EmptyInput => {
+                r#"The input has no tokens.
+
+A program must contain at least one expression, files that are empty or only
+contain comments can't be run.
+
+Common causes:
+- running an empty file
+- a file that only has comments
+
+Erroneous example:
+```
+# todo
+```
+
+Working example:
+```
+# todo
+none
+```"#
+            }
+            ErrorCode::TooDeep => {
+                r#"The input is nested deeper than the parser allows.
+
+Expressions, scopes, and chained operators are parsed recursively, so each
+level of nesting uses stack space. Input nested deeper than
+`ParserOptions::max_depth` (256 by default) is rejected instead of
+overflowing the stack.
+
+Common causes:
+- generated code with thousands of nested parentheses or lists
+- a very long chain of binary operators
+
+Erroneous example, 300 nested parentheses:
+```
+((((((((((...1...))))))))))
+```
+
+Working example, split the expression using variables:
+```
+a = (((1)))
+b = (((a)))
+b
+```"#
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod explain_tests {
+    use crate::ErrorCode;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn every_code_has_examples() {
+        for code in ErrorCode::ALL {
+            let explanation = code.explanation();
+            assert!(
+                explanation.contains("Erroneous example"),
+                "{code} is missing an erroneous example"
+            );
+            assert!(
+                explanation.contains("Working example"),
+                "{code} is missing a working example"
+            );
+        }
+    }
+}
@@ -0,0 +1,300 @@
+mod explain;
+
+use crate::prelude::*;
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Stable identifier for each kind of error, displayed as `E0001`.
+/// Runtime errors use E00xx, parsing errors use E01xx.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ErrorCode {
+    Runtime = 1,
+    EmptyStack = 2,
+    Conversion = 3,
+    ScopeDoesNotExist = 4,
+    UnsupportedOperation = 5,
+    VariableDoesNotExist = 6,
+    InvalidModule = 7,
+    InvalidModuleFunction = 8,
+    Lifecycle = 9,
+    Timeout = 10,
+    BudgetExceeded = 11,
+    Syntax = 100,
+    UnexpectedEnd = 101,
+    UnexpectedToken = 102,
+    InvalidExpression = 103,
+    InvalidNumber = 104,
+    InvalidCharacter = 105,
+    InvalidBool = 106,
+    EmptyInput = 107,
+    TooDeep = 108,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 20] = [
+        ErrorCode::Runtime,
+        ErrorCode::EmptyStack,
+        ErrorCode::Conversion,
+        ErrorCode::ScopeDoesNotExist,
+        ErrorCode::UnsupportedOperation,
+        ErrorCode::VariableDoesNotExist,
+        ErrorCode::InvalidModule,
+        ErrorCode::InvalidModuleFunction,
+        ErrorCode::Lifecycle,
+        ErrorCode::Timeout,
+        ErrorCode::BudgetExceeded,
+        ErrorCode::Syntax,
+        ErrorCode::UnexpectedEnd,
+        ErrorCode::UnexpectedToken,
+        ErrorCode::InvalidExpression,
+        ErrorCode::InvalidNumber,
+        ErrorCode::InvalidCharacter,
+        ErrorCode::InvalidBool,
+        ErrorCode::EmptyInput,
+        ErrorCode::TooDeep,
+    ];
+
+    #[inline]
+    pub fn number(self) -> u16 {
+        self as u16
+    }
+
+    pub fn from_number(number: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.number() == number)
+    }
+
+    /// One line summary of the error kind
+    pub fn summary(self) -> &'static str {
+        match self {
+            ErrorCode::Runtime => "error raised while running the program",
+            ErrorCode::EmptyStack => "value missing from the VM stack",
+            ErrorCode::Conversion => "value could not be converted to the requested type",
+            ErrorCode::ScopeDoesNotExist => "scope or instruction does not exist",
+            ErrorCode::UnsupportedOperation => "operation is not supported for these values",
+            ErrorCode::VariableDoesNotExist => "variable does not exist or is immutable",
+            ErrorCode::InvalidModule => "module does not exist",
+            ErrorCode::InvalidModuleFunction => "function does not exist in module",
+            ErrorCode::Lifecycle => "lifecycle function failed",
+            ErrorCode::Timeout => "operation timed out",
+            ErrorCode::BudgetExceeded => "program ran more instructions than allowed",
+            ErrorCode::Syntax => "invalid syntax",
+            ErrorCode::UnexpectedEnd => "input ended before the expression was complete",
+            ErrorCode::UnexpectedToken => "token does not match what was expected",
+            ErrorCode::InvalidExpression => "token cannot start an expression",
+            ErrorCode::InvalidNumber => "invalid number",
+            ErrorCode::InvalidCharacter => "invalid character",
+            ErrorCode::InvalidBool => "invalid bool",
+            ErrorCode::EmptyInput => "input has no tokens",
+            ErrorCode::TooDeep => "input is nested deeper than the parser allows",
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "E{:04}", self.number())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = String;
+
+    /// Accepts `E0001`, `e0001`, or `1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s.strip_prefix(['E', 'e']).unwrap_or(s);
+        number
+            .parse()
+            .ok()
+            .and_then(Self::from_number)
+            .ok_or_else(|| format!("Unknown error code {s}"))
+    }
+}
+
+/// Byte offsets into the source, `line` is 1 based
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+/// Error with a code, primary message, and optional location & help, rendered like rustc diagnostics
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Option<SourceSpan>,
+    pub help: Option<String>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Diagnostic {
+    pub fn new<M: Into<String>>(code: ErrorCode, message: M) -> Self {
+        Diagnostic {
+            code,
+            message: message.into(),
+            span: None,
+            help: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: SourceSpan) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_help<H: Into<String>>(mut self, help: H) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// ```text
+    /// error[E0102]: expected end, received Eoi
+    ///  --> main.rg:3:5
+    ///   |
+    /// 3 |     foo = )
+    ///   |           ^
+    ///   = help: ...
+    /// ```
+    /// The source line is only included when `source` is the input the span points into.
+    pub fn render(&self, file: &str, source: Option<&str>) -> String {
+        let mut result = format!("error[{}]: {}", self.code, self.message);
+        let gutter = " ".repeat(self.span.map(|s| s.line.to_string().len()).unwrap_or(1));
+        if let Some(span) = self.span {
+            let snippet = source.and_then(|s| snippet(s, span));
+            match snippet {
+                None => result.push_str(&format!("\n{gutter}--> {file}:{}", span.line)),
+                Some(snippet) => {
+                    result.push_str(&format!(
+                        "\n{gutter}--> {file}:{}:{}",
+                        span.line, snippet.column
+                    ));
+                    result.push_str(&format!("\n{gutter} |"));
+                    result.push_str(&format!("\n{} | {}", span.line, snippet.line));
+                    result.push_str(&format!(
+                        "\n{gutter} | {}{}",
+                        " ".repeat(snippet.padding),
+                        "^".repeat(snippet.width)
+                    ));
+                }
+            }
+        }
+        if let Some(help) = &self.help {
+            result.push_str(&format!("\n{gutter} = help: {help}"));
+        }
+        result
+    }
+}
+
+/// Tabs are displayed as this many spaces so the underline lines up with the source
+const TAB_WIDTH: usize = 4;
+
+struct Snippet {
+    /// source line with tabs expanded
+    line: String,
+    /// 1 based, in characters, a tab counts as one column
+    column: usize,
+    /// display width before the span
+    padding: usize,
+    /// display width of the span, at least 1
+    width: usize,
+}
+
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+/// Source line containing the span, lines may end with CRLF & the first line may start with a BOM
+fn snippet(source: &str, span: SourceSpan) -> Option<Snippet> {
+    let start = source.get(..span.start)?;
+    let line_start = match start.rfind('\n') {
+        Some(i) => i + 1,
+        None if source.starts_with('\u{feff}') => '\u{feff}'.len_utf8(),
+        None => 0,
+    };
+    let line_end = source[line_start..]
+        .find('\n')
+        .map(|i| line_start + i)
+        .unwrap_or(source.len());
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let before = source.get(line_start..span.start)?;
+    let underlined = source
+        .get(span.start..span.end.min(line_start + line.len()))
+        .unwrap_or_default();
+    Some(Snippet {
+        line: line.replace('\t', &" ".repeat(TAB_WIDTH)),
+        column: before.chars().count() + 1,
+        padding: display_width(before),
+        width: display_width(underlined).max(1),
+    })
+}
+
+#[cfg(test)]
+pub mod diagnostic_tests {
+    use crate::{Diagnostic, ErrorCode, SourceSpan};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn error_codes() {
+        assert_eq!(ErrorCode::UnexpectedToken.to_string(), "E0102");
+        assert_eq!("E0102".parse(), Ok(ErrorCode::UnexpectedToken));
+        assert_eq!("1".parse(), Ok(ErrorCode::Runtime));
+        assert!("E9999".parse::<ErrorCode>().is_err());
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_number(code.number()), Some(code));
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn render() {
+        let source = "a = 1\nb = )\n";
+        let diagnostic =
+            Diagnostic::new(ErrorCode::InvalidExpression, "Invalid Token for Expression")
+                .with_span(SourceSpan {
+                    start: 10,
+                    end: 11,
+                    line: 2,
+                })
+                .with_help("expressions start with a value, identifier, or keyword");
+        assert_eq!(
+            diagnostic.render("main.rg", Some(source)),
+            "error[E0103]: Invalid Token for Expression
+ --> main.rg:2:5
+  |
+2 | b = )
+  |     ^
+  = help: expressions start with a value, identifier, or keyword"
+        );
+        let tabbed = "\u{feff}a = 1\r\n\tb = )\r\n";
+        assert_eq!(
+            diagnostic
+                .clone()
+                .with_span(SourceSpan {
+                    start: 15,
+                    end: 16,
+                    line: 2,
+                })
+                .render("main.rg", Some(tabbed)),
+            "error[E0103]: Invalid Token for Expression
+ --> main.rg:2:6
+  |
+2 |     b = )
+  |         ^
+  = help: expressions start with a value, identifier, or keyword"
+        );
+        assert_eq!(
+            diagnostic.render("main.rg", None),
+            "error[E0103]: Invalid Token for Expression
+ --> main.rg:2
+  = help: expressions start with a value, identifier, or keyword"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::prelude::*;
+use crate::{
+    AsPrimitive, BinaryOperation, CreateObject, Definition, IndexMap, Number, Object, ObjectValue,
+    RigzArgs, RigzType, VMError, WithTypeInfo,
+};
+use alloc::sync::Arc;
+use core::fmt::{Display, Formatter};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Deep-immutable value, see `ObjectValue::freeze`. Copies share the wrapped value, so a frozen constant can be sent to
+/// other processes without copying it. Reads behave like the wrapped value, nested collections are read frozen
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Frozen(Arc<ObjectValue>);
+
+impl Frozen {
+    pub fn new(value: ObjectValue) -> Self {
+        match value.as_frozen() {
+            Some(f) => f.clone(),
+            None => Frozen(Arc::new(value)),
+        }
+    }
+
+    #[inline]
+    pub fn value(&self) -> &ObjectValue {
+        &self.0
+    }
+
+    pub(crate) fn immutable(&self) -> VMError {
+        VMError::UnsupportedOperation(format!("Cannot mutate frozen value {}", self.0))
+    }
+}
+
+impl From<Frozen> for ObjectValue {
+    fn from(value: Frozen) -> Self {
+        ObjectValue::Object(Box::new(value))
+    }
+}
+
+impl Display for Frozen {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Frozen {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Frozen {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ObjectValue::deserialize(deserializer).map(Frozen::new)
+    }
+}
+
+impl WithTypeInfo for Frozen {
+    fn rigz_type(&self) -> RigzType {
+        self.0.rigz_type()
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Frozen {
+    fn reverse(&self) -> Result<ObjectValue, VMError> {
+        self.0.reverse()
+    }
+
+    fn contained_values(&self) -> Vec<ObjectValue> {
+        vec![self.value().clone()]
+    }
+
+    /// Arithmetic uses the wrapped value, the result isn't frozen
+    fn binary_operation(
+        &self,
+        operation: BinaryOperation,
+        other: &ObjectValue,
+        reversed: bool,
+    ) -> Option<ObjectValue> {
+        let (lhs, rhs) = if reversed {
+            (other, self.value())
+        } else {
+            (self.value(), other)
+        };
+        let v = match operation {
+            BinaryOperation::Add => lhs + rhs,
+            BinaryOperation::Sub => lhs - rhs,
+            BinaryOperation::Mul => lhs * rhs,
+            BinaryOperation::Div => lhs / rhs,
+            BinaryOperation::Rem => lhs % rhs,
+            BinaryOperation::Shr => lhs >> rhs,
+            BinaryOperation::Shl => lhs << rhs,
+            BinaryOperation::BitOr => lhs | rhs,
+            BinaryOperation::BitAnd => lhs & rhs,
+            BinaryOperation::BitXor => lhs ^ rhs,
+            _ => return None,
+        };
+        Some(v)
+    }
+
+    fn as_list(&mut self) -> Result<&mut Vec<ObjectValue>, VMError> {
+        Err(self.immutable())
+    }
+
+    fn to_list(&self) -> Result<Vec<ObjectValue>, VMError> {
+        self.0.to_list()
+    }
+
+    fn to_map(&self) -> Result<IndexMap<ObjectValue, ObjectValue>, VMError> {
+        self.0.to_map()
+    }
+
+    fn as_map(&mut self) -> Result<&mut IndexMap<ObjectValue, ObjectValue>, VMError> {
+        Err(self.immutable())
+    }
+
+    fn to_number(&self) -> Result<Number, VMError> {
+        self.0.to_number()
+    }
+
+    fn to_bool(&self) -> bool {
+        self.0.to_bool()
+    }
+
+    fn get(&self, attr: &ObjectValue) -> Result<ObjectValue, VMError> {
+        Ok(self.0.get(attr)?.unwrap_or_default().freeze())
+    }
+
+    fn set(&mut self, _attr: &ObjectValue, _value: ObjectValue) -> Result<(), VMError> {
+        Err(self.immutable())
+    }
+}
+
+impl CreateObject for Frozen {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?;
+        let value = value.borrow().clone();
+        Ok(Frozen::new(value))
+    }
+}
+
+impl Definition for Frozen {
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Frozen"
+    }
+
+    fn trait_definition() -> &'static str
+    where
+        Self: Sized,
+    {
+        r#"object Frozen
+            Self(value: Any)
+        end"#
+    }
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl Object for Frozen {
+    fn call_extension(&self, function: String, args: RigzArgs) -> Result<ObjectValue, VMError> {
+        match self.value() {
+            ObjectValue::Object(o) => o.call_extension(function, args),
+            v => Err(VMError::UnsupportedOperation(format!(
+                "{v} does not implement `{function}` - {args:?}"
+            ))),
+        }
+    }
+
+    fn call_mutable_extension(
+        &mut self,
+        _function: String,
+        _args: RigzArgs,
+    ) -> Result<Option<ObjectValue>, VMError> {
+        Err(self.immutable())
+    }
+}
+
+#[cfg(test)]
+pub mod frozen_tests {
+    use crate::{AsPrimitive, Frozen, IndexMap, ObjectValue, Shared};
+    use indexmap::IndexSet;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn reads_like_value() {
+        let list = ObjectValue::List(vec![1.into(), ObjectValue::List(vec![2.into()])]);
+        let frozen = list.clone().freeze();
+        assert!(frozen.is_frozen());
+        assert_eq!(frozen, list);
+        assert_eq!(list, frozen);
+        assert_eq!(frozen.to_string(), list.to_string());
+        assert_eq!(frozen.to_list(), Ok(list.to_list().unwrap()));
+        let nested = frozen.get(&1.into()).unwrap().unwrap();
+        assert!(nested.is_frozen());
+        assert_eq!(nested, ObjectValue::List(vec![2.into()]));
+        let set: IndexSet<_> = [list, frozen.clone(), frozen.freeze()]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn primitives_unchanged() {
+        assert!(!ObjectValue::from(1).freeze().is_frozen());
+        assert!(!ObjectValue::from("a").freeze().is_frozen());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn mutation_fails() {
+        let mut map = IndexMap::new();
+        map.insert("a".into(), 1.into());
+        let mut frozen = ObjectValue::Map(map.clone()).freeze();
+        assert!(frozen.as_map().is_err());
+        assert!(frozen.as_list().is_err());
+        assert!(frozen
+            .instance_set(Shared::new("b".into()), &2.into())
+            .is_err());
+        assert_eq!(frozen, ObjectValue::Map(map));
+        assert!(!Frozen::new(frozen.clone()).value().is_frozen());
+    }
+}
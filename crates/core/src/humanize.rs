@@ -0,0 +1,156 @@
+use crate::prelude::*;
+use crate::VMError;
+use core::time::Duration;
+
+const DURATION_UNITS: [(&str, u128); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("µs", 1_000),
+    ("ns", 1),
+];
+
+const BINARY_SIZE_UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// Two largest non-zero units, i.e. `2h 3m`, `1s 250ms`, or `15ns`, parsed by `parse_duration`
+pub fn humanize_duration(duration: Duration) -> String {
+    let mut nanos = duration.as_nanos();
+    if nanos == 0 {
+        return "0s".to_string();
+    }
+    let mut parts = Vec::with_capacity(2);
+    for (unit, size) in DURATION_UNITS {
+        if nanos >= size {
+            parts.push(format!("{}{unit}", nanos / size));
+            nanos %= size;
+            if parts.len() == 2 {
+                break;
+            }
+        } else if !parts.is_empty() {
+            // `1h 30s` would hide that minutes were zero, smaller units are dropped instead
+            break;
+        }
+    }
+    parts.join(" ")
+}
+
+/// Whitespace separated `<number><unit>` pairs, i.e. `2h 3m` or `1.5s`. Units are `d`, `h`, `m`, `s`, `ms`,
+/// `us` (or `µs`), and `ns`
+pub fn parse_duration(value: &str) -> Result<Duration, VMError> {
+    let invalid = |reason: &str| {
+        VMError::ConversionError(format!("Cannot convert {value:?} to Duration - {reason}"))
+    };
+    let mut nanos = 0.0;
+    let mut parts = 0;
+    for part in value.split_whitespace() {
+        let split = part
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(|| invalid("missing unit"))?;
+        let (number, unit) = part.split_at(split);
+        let number: f64 = number
+            .parse()
+            .map_err(|_| invalid(&format!("invalid number {number:?}")))?;
+        let size = match unit {
+            "us" => 1_000,
+            unit => DURATION_UNITS
+                .iter()
+                .find(|(u, _)| *u == unit)
+                .map(|(_, size)| *size)
+                .ok_or_else(|| invalid(&format!("unknown unit {unit:?}")))?,
+        };
+        nanos += number * size as f64;
+        parts += 1;
+    }
+    if parts == 0 {
+        return Err(invalid("empty"));
+    }
+    Ok(Duration::from_nanos(nanos.round() as u64))
+}
+
+/// Binary units with one decimal place, i.e. `512 B` or `1.4 MiB`, parsed by `parse_size`
+pub fn humanize_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < BINARY_SIZE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{bytes} B");
+    }
+    let size = format!("{size:.1}");
+    let size = size.strip_suffix(".0").unwrap_or(&size);
+    format!("{size} {}", BINARY_SIZE_UNITS[unit])
+}
+
+/// A number followed by a unit, i.e. `1.4 MiB` or `10KB`. Binary units (`KiB`, `MiB`, ...) are multiples of 1024,
+/// decimal units (`KB`, `MB`, ...) are multiples of 1000, units are case insensitive and `B` is the default
+pub fn parse_size(value: &str) -> Result<u64, VMError> {
+    let invalid = |reason: &str| {
+        VMError::ConversionError(format!("Cannot convert {value:?} to Size - {reason}"))
+    };
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| invalid(&format!("invalid number {number:?}")))?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let multiplier = match unit.as_str() {
+        "" | "b" => 1.0,
+        unit => {
+            let (prefix, base) = match unit.strip_suffix("ib") {
+                Some(prefix) => (prefix, 1024f64),
+                None => (unit.strip_suffix('b').unwrap_or(unit), 1000f64),
+            };
+            let power = ["k", "m", "g", "t", "p"]
+                .iter()
+                .position(|p| *p == prefix)
+                .ok_or_else(|| invalid(&format!("unknown unit {unit:?}")))?;
+            base.powi(power as i32 + 1)
+        }
+    };
+    Ok((number * multiplier).round() as u64)
+}
+
+#[cfg(test)]
+pub mod humanize_tests {
+    use crate::{humanize_duration, humanize_size, parse_duration, parse_size};
+    use core::time::Duration;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn durations() {
+        assert_eq!(humanize_duration(Duration::ZERO), "0s");
+        assert_eq!(humanize_duration(Duration::from_nanos(15)), "15ns");
+        assert_eq!(humanize_duration(Duration::from_millis(1250)), "1s 250ms");
+        assert_eq!(humanize_duration(Duration::from_secs(7380)), "2h 3m");
+        assert_eq!(humanize_duration(Duration::from_secs(3630)), "1h");
+        for value in ["2h 3m", "1s 250ms", "15ns", "0s"] {
+            let duration = parse_duration(value).unwrap();
+            assert_eq!(humanize_duration(duration), value);
+        }
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("10us"), Ok(Duration::from_micros(10)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5w").is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn sizes() {
+        assert_eq!(humanize_size(512), "512 B");
+        assert_eq!(humanize_size(1024), "1 KiB");
+        assert_eq!(humanize_size(1_468_006), "1.4 MiB");
+        assert_eq!(parse_size("1.4 MiB"), Ok(1_468_006));
+        assert_eq!(parse_size("10KB"), Ok(10_000));
+        assert_eq!(parse_size("2gib"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert!(parse_size("MiB").is_err());
+        assert!(parse_size("1 XB").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+use crate::prelude::*;
+use alloc::sync::Arc;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+use core::mem::ManuallyDrop;
+
+#[cfg(feature = "std")]
+type Set = std::collections::HashSet<Key>;
+#[cfg(feature = "std")]
+type Lock<T> = std::sync::Mutex<T>;
+#[cfg(feature = "std")]
+type Guard<'a, T> = std::sync::MutexGuard<'a, T>;
+
+// without std the table is a tree behind a spin lock
+#[cfg(not(feature = "std"))]
+type Set = alloc::collections::BTreeSet<Key>;
+#[cfg(not(feature = "std"))]
+type Lock<T> = spin::Mutex<T>;
+#[cfg(not(feature = "std"))]
+type Guard<'a, T> = spin::MutexGuard<'a, T>;
+
+/// Entry of the table, looked up by `&str`
+struct Key(Arc<String>);
+
+impl Borrow<str> for Key {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for Key {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_str().hash(state)
+    }
+}
+
+impl PartialOrd for Key {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_str().cmp(other.0.as_str())
+    }
+}
+
+/// Strings shared by `Interned::new`, clones share the table
+#[derive(Clone, Default)]
+pub struct InternTable(Arc<Lock<Set>>);
+
+impl Debug for InternTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "InternTable({})", self.len())
+    }
+}
+
+impl InternTable {
+    #[cfg(feature = "std")]
+    fn lock(&self) -> Guard<'_, Set> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn lock(&self) -> Guard<'_, Set> {
+        self.0.lock()
+    }
+
+    pub fn intern(&self, value: &str) -> Interned {
+        let mut set = self.lock();
+        let value = match set.get(value) {
+            Some(Key(s)) => s.clone(),
+            None => {
+                let s = Arc::new(value.to_string());
+                set.insert(Key(s.clone()));
+                s
+            }
+        };
+        Interned {
+            value: ManuallyDrop::new(value),
+            table: Some(self.clone()),
+        }
+    }
+
+    pub fn contains(&self, value: &str) -> bool {
+        self.lock().contains(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+}
+
+// each runtime has its own table, see `RuntimeContext`
+#[cfg(feature = "std")]
+fn table() -> InternTable {
+    crate::context::with_context(|c| c.interned.clone())
+}
+
+#[cfg(not(feature = "std"))]
+fn table() -> InternTable {
+    static TABLE: spin::Mutex<Option<InternTable>> = spin::Mutex::new(None);
+    TABLE.lock().get_or_insert_with(Default::default).clone()
+}
+
+/// Immutable string shared by clones, used for identifiers in the parser & instructions, call frame variables,
+/// and string values. `Interned::new` stores the string in the intern table so equal identifiers, constants, & map
+/// keys share one allocation and compare by pointer, strings built at runtime (`Interned::owned`) skip the table.
+/// A string is removed from the table once its last `Interned` is dropped.
+#[derive(Clone)]
+pub struct Interned {
+    // only dropped in `Drop`, so the reference count changes while the table is locked
+    value: ManuallyDrop<Arc<String>>,
+    // the table holding `value`, `None` for strings built at runtime
+    table: Option<InternTable>,
+}
+
+impl Drop for Interned {
+    fn drop(&mut self) {
+        // SAFETY: `value` isn't used after this
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        if let Some(table) = &self.table {
+            // the table holds the other reference, `intern` clones & this drops under the same lock
+            let mut set = table.lock();
+            if Arc::strong_count(&value) == 2 {
+                set.remove(value.as_str());
+            }
+            drop(value);
+        }
+    }
+}
+
+impl Interned {
+    /// Shares the string with equal interned strings
+    pub fn new(value: &str) -> Self {
+        table().intern(value)
+    }
+
+    /// String built at runtime, it isn't added to the table
+    pub fn owned(value: String) -> Self {
+        Interned {
+            value: ManuallyDrop::new(Arc::new(value)),
+            table: None,
+        }
+    }
+
+    /// Adds the string to the table, i.e. when it's used as a constant or map key
+    pub fn intern(&self) -> Self {
+        match self.table {
+            Some(_) => self.clone(),
+            None => Interned::new(self.as_str()),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.value.as_str()
+    }
+
+    /// Mutable access, the string is copied first when it's interned or shared
+    pub fn make_mut(&mut self) -> &mut String {
+        if self.table.is_some() {
+            *self = Interned::owned(self.as_str().to_string());
+        }
+        Arc::make_mut(&mut self.value)
+    }
+
+    /// Takes the string, only copied when it's shared
+    pub fn into_string(mut self) -> String {
+        core::mem::take(self.make_mut())
+    }
+}
+
+impl Default for Interned {
+    #[inline]
+    fn default() -> Self {
+        Interned::new("")
+    }
+}
+
+impl Deref for Interned {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Interned {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Lets `IndexMap<Interned, _>` be read with a `&str`
+impl Borrow<str> for Interned {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<String> for Interned {
+    #[inline]
+    fn borrow(&self) -> &String {
+        &self.value
+    }
+}
+
+impl PartialEq for Interned {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.value, &other.value) || self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Interned {}
+
+impl PartialEq<str> for Interned {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Interned {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Interned {
+    #[inline]
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Interned> for String {
+    #[inline]
+    fn eq(&self, other: &Interned) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<Interned> for &str {
+    #[inline]
+    fn eq(&self, other: &Interned) -> bool {
+        *self == other.as_str()
+    }
+}
+
+/// Must match `str` for `Borrow<str>` lookups
+impl Hash for Interned {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialOrd for Interned {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interned {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        if Arc::ptr_eq(&self.value, &other.value) {
+            return Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Debug for Interned {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Display for Interned {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl From<&str> for Interned {
+    #[inline]
+    fn from(value: &str) -> Self {
+        Interned::new(value)
+    }
+}
+
+impl From<&String> for Interned {
+    #[inline]
+    fn from(value: &String) -> Self {
+        Interned::new(value)
+    }
+}
+
+impl From<String> for Interned {
+    #[inline]
+    fn from(value: String) -> Self {
+        Interned::new(&value)
+    }
+}
+
+impl From<Interned> for String {
+    #[inline]
+    fn from(value: Interned) -> Self {
+        value.into_string()
+    }
+}
+
+impl serde::Serialize for Interned {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Interned {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Interned::from)
+    }
+}
+
+#[cfg(test)]
+pub mod interned_tests {
+    use crate::interned::table;
+    use crate::Interned;
+    use indexmap::IndexMap;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn equal_strings_share_storage() {
+        let a = Interned::from("name");
+        let b = Interned::from("name".to_string());
+        assert_eq!(a, b);
+        assert!(core::ptr::eq(a.as_str(), b.as_str()));
+        assert_ne!(a, Interned::from("other"));
+        assert_eq!(a, "name");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn freed_with_last_reference() {
+        let contains = |s: &str| table().contains(s);
+        let a = Interned::from("interned_tests::freed");
+        let b = a.clone();
+        drop(a);
+        assert!(contains("interned_tests::freed"));
+        drop(b);
+        assert!(!contains("interned_tests::freed"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn concurrent_drops_free() {
+        let value = Interned::from("interned_tests::concurrent");
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let value = value.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        drop(value.clone());
+                    }
+                    drop(value)
+                })
+            })
+            .collect();
+        drop(value);
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert!(!table().contains("interned_tests::concurrent"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn owned_strings_skip_table() {
+        let mut owned = Interned::owned("interned_tests::owned".to_string());
+        assert!(!table().contains("interned_tests::owned"));
+        let interned = owned.intern();
+        assert!(table().contains("interned_tests::owned"));
+        assert_eq!(owned, interned);
+
+        owned.make_mut().push('!');
+        assert_eq!(owned, "interned_tests::owned!");
+        assert_eq!(interned, "interned_tests::owned");
+        let mut copy = interned.clone();
+        copy.make_mut().push('?');
+        assert_eq!(interned, "interned_tests::owned");
+        drop(interned);
+        assert!(!table().contains("interned_tests::owned"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn map_lookup_by_str() {
+        let mut map = IndexMap::new();
+        map.insert(Interned::from("a"), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get("b"), None);
+    }
+}
@@ -1,30 +1,82 @@
+// without the `std` feature only core & alloc are available, see Cargo.toml
+#![cfg_attr(not(feature = "std"), no_std)]
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+
 #[cfg(feature = "derive")]
 pub mod derive;
 
 mod args;
+#[cfg(feature = "bigint")]
+mod bigint;
+mod capture;
+#[cfg(feature = "std")]
+mod context;
+mod datetime;
+#[cfg(feature = "std")]
+mod deterministic;
+mod diagnostic;
+mod frozen;
+mod humanize;
+mod interned;
 mod lifecycle;
 mod macros;
 mod number;
 mod object;
 mod operations;
+mod prelude;
+mod pretty;
 mod primitive;
 mod reference;
+#[cfg(feature = "std")]
+mod regex;
 mod rigz_object;
+mod text;
 mod traits;
 mod types;
+#[cfg(feature = "std")]
+mod uuid;
 mod vm_values;
 
+#[cfg(feature = "std")]
 pub type IndexMap<K, V> = indexmap::map::IndexMap<K, V>;
+#[cfg(feature = "std")]
+pub type IndexSet<T> = indexmap::set::IndexSet<T>;
+// there's no random state without std, maps use a fixed seed
+#[cfg(not(feature = "std"))]
+pub type IndexMap<K, V> = indexmap::map::IndexMap<K, V, foldhash::fast::FixedState>;
+#[cfg(not(feature = "std"))]
+pub type IndexSet<T> = indexmap::set::IndexSet<T, foldhash::fast::FixedState>;
 pub type IndexMapEntry<'a, K, V> = indexmap::map::Entry<'a, K, V>;
 
 pub use args::RigzArgs;
+#[cfg(feature = "bigint")]
+pub use bigint::BigInt;
+pub use capture::*;
+#[cfg(feature = "std")]
+pub use context::*;
+pub use datetime::{DateTime, Duration};
+#[cfg(feature = "std")]
+pub use deterministic::*;
+pub use diagnostic::*;
+pub use frozen::Frozen;
+pub use humanize::*;
+pub use interned::{InternTable, Interned};
 pub use lifecycle::*;
 pub use number::*;
 pub use object::*;
 pub use operations::*;
+pub use pretty::{pretty, PrettyOptions};
 pub use primitive::*;
 pub use reference::*;
-pub use rigz_object::RigzObject;
+#[cfg(feature = "std")]
+pub use regex::Regex;
+#[cfg(feature = "std")]
+pub use rigz_object::{enter_object_hooks, ObjectHooksGuard};
+pub use rigz_object::{ObjectHooks, RigzObject};
+pub use text::*;
 pub use traits::*;
 pub use types::*;
+#[cfg(feature = "std")]
+pub use uuid::Uuid;
 pub use vm_values::*;
@@ -1,13 +1,16 @@
 #[cfg(feature = "snapshot")]
 mod snapshot;
 
-use crate::{ObjectValue, VMError};
-use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
-use std::ops::AddAssign;
-use std::time::Duration;
+use crate::prelude::*;
+use crate::{humanize_duration, CapturedOutput, IndexMap, ObjectValue, VMError};
+use core::fmt::{Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::num::NonZeroUsize;
+use core::ops::AddAssign;
+use core::str::FromStr;
+use core::time::Duration;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Lifecycle {
     On(EventLifecycle),
     After(StatefulLifecycle),
@@ -16,12 +19,207 @@ pub enum Lifecycle {
     Composite(Vec<Lifecycle>),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Each `@on` handler receives events through its own queue, i.e. `@on("message", capacity: 8, overflow: "drop_oldest")`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct EventLifecycle {
     pub event: String,
+    /// events waiting for the handler, not including the event being handled
+    pub capacity: usize,
+    pub overflow: Backpressure,
+    pub restart: Restart,
+    /// restarts allowed per event with `Restart::OnFailure`
+    pub max_restarts: usize,
+    /// milliseconds before the first restart, doubled after each restart of the same event
+    pub backoff: usize,
+    /// every event is handled by its own worker instead of waiting in the queue, results are dropped
+    pub detached: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl EventLifecycle {
+    pub const DEFAULT_CAPACITY: usize = 64;
+    pub const DEFAULT_MAX_RESTARTS: usize = 3;
+    pub const DEFAULT_BACKOFF: usize = 100;
+    /// backoff stops doubling once it reaches a minute
+    pub const MAX_BACKOFF: usize = 60_000;
+
+    pub fn new<S: Into<String>>(event: S) -> Self {
+        EventLifecycle {
+            event: event.into(),
+            capacity: Self::DEFAULT_CAPACITY,
+            overflow: Backpressure::default(),
+            restart: Restart::default(),
+            max_restarts: Self::DEFAULT_MAX_RESTARTS,
+            backoff: Self::DEFAULT_BACKOFF,
+            detached: false,
+        }
+    }
+
+    /// `@on("signal:INT")` handlers receive OS signals instead of messages from `send`
+    pub fn signal(&self) -> Result<Option<Signal>, VMError> {
+        self.event
+            .strip_prefix(Signal::EVENT_PREFIX)
+            .map(str::parse)
+            .transpose()
+    }
+}
+
+/// OS signals that can be handled with `@on("signal:NAME")`. Once a signal is received the VM stops
+/// before its next instruction, after its handlers finish the host exits with `exit_code`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Signal {
+    Hup,
+    Int,
+    Quit,
+    Term,
+}
+
+impl Signal {
+    pub const ALL: [Signal; 4] = [Signal::Hup, Signal::Int, Signal::Quit, Signal::Term];
+    pub const EVENT_PREFIX: &'static str = "signal:";
+
+    /// POSIX signal number
+    pub fn number(&self) -> i32 {
+        match self {
+            Signal::Hup => 1,
+            Signal::Int => 2,
+            Signal::Quit => 3,
+            Signal::Term => 15,
+        }
+    }
+
+    pub fn from_number(number: i32) -> Option<Signal> {
+        Self::ALL.into_iter().find(|s| s.number() == number)
+    }
+
+    /// Exit code used by shells for a process stopped by the signal
+    pub fn exit_code(&self) -> i32 {
+        128 + self.number()
+    }
+
+    pub fn event(&self) -> String {
+        format!("{}{self}", Self::EVENT_PREFIX)
+    }
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Signal::Hup => write!(f, "HUP"),
+            Signal::Int => write!(f, "INT"),
+            Signal::Quit => write!(f, "QUIT"),
+            Signal::Term => write!(f, "TERM"),
+        }
+    }
+}
+
+impl FromStr for Signal {
+    type Err = VMError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("SIG").unwrap_or(s);
+        Self::ALL
+            .into_iter()
+            .find(|signal| signal.to_string() == name)
+            .ok_or_else(|| {
+                VMError::RuntimeError(format!(
+                    "Invalid signal {s}, expected HUP, INT, QUIT, or TERM"
+                ))
+            })
+    }
+}
+
+/// How an `@on` handler is supervised, an event fails when the handler returns an error or panics.
+/// Restarted events are handled by a new VM, i.e. `@on("job", restart: "on_failure", max_restarts: 5, backoff: 50)`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Restart {
+    /// the error is the result of the event
+    #[default]
+    Never,
+    /// restart until the event succeeds or `max_restarts` is reached, then the last error is the result
+    OnFailure,
+    /// restart until the event succeeds, `max_restarts` is ignored
+    Always,
+}
+
+impl Restart {
+    pub const ALL: [Restart; 3] = [Restart::Never, Restart::OnFailure, Restart::Always];
+}
+
+impl Display for Restart {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Restart::Never => write!(f, "never"),
+            Restart::OnFailure => write!(f, "on_failure"),
+            Restart::Always => write!(f, "always"),
+        }
+    }
+}
+
+impl FromStr for Restart {
+    type Err = VMError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|r| r.to_string() == s)
+            .ok_or_else(|| {
+                VMError::RuntimeError(format!(
+                    "Invalid restart {s}, expected never, on_failure, or always"
+                ))
+            })
+    }
+}
+
+/// What `send` does when a handler's queue is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Backpressure {
+    /// wait for the handler to take the next event
+    #[default]
+    Block,
+    /// discard the event being sent
+    DropNewest,
+    /// discard the oldest queued event to make room
+    DropOldest,
+    /// return an error for that handler
+    Error,
+}
+
+impl Backpressure {
+    pub const ALL: [Backpressure; 4] = [
+        Backpressure::Block,
+        Backpressure::DropNewest,
+        Backpressure::DropOldest,
+        Backpressure::Error,
+    ];
+}
+
+impl Display for Backpressure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Backpressure::Block => write!(f, "block"),
+            Backpressure::DropNewest => write!(f, "drop_newest"),
+            Backpressure::DropOldest => write!(f, "drop_oldest"),
+            Backpressure::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for Backpressure {
+    type Err = VMError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|b| b.to_string() == s)
+            .ok_or_else(|| {
+                VMError::RuntimeError(format!(
+                    "Invalid overflow {s}, expected block, drop_newest, drop_oldest, or error"
+                ))
+            })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Stage {
     Parse,
     Run,
@@ -29,17 +227,100 @@ pub enum Stage {
     Custom(String),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct StatefulLifecycle {
     pub stage: Stage,
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct MemoizedLifecycle {
-    pub results: HashMap<Vec<ObjectValue>, ObjectValue>,
+    /// least recently used first
+    pub results: IndexMap<Vec<ObjectValue>, MemoResult>,
+    /// `@memo(persist: "cache.db")`, results are also stored on disk and reused by later runs
+    pub persist: Option<Box<PersistentMemo>>,
+    /// `@memo(limit: 100)`, the least recently used results are removed once there are more results
+    pub limit: Option<NonZeroUsize>,
+    /// `@memo(ttl: 60)` in seconds, stored as milliseconds. Expired results are removed and the function runs again
+    pub ttl: Option<NonZeroUsize>,
+}
+
+/// Results are filled in at runtime, only the options are hashed
+impl Hash for MemoizedLifecycle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.persist.hash(state);
+        self.limit.hash(state);
+        self.ttl.hash(state);
+    }
+}
+
+impl MemoizedLifecycle {
+    fn expired(&self, result: &MemoResult, now: usize) -> bool {
+        matches!(self.ttl, Some(ttl) if now.saturating_sub(result.created) >= ttl.get())
+    }
+
+    /// Result for `args` unless it expired, it becomes the most recently used result.
+    /// `now` is milliseconds since the unix epoch
+    pub fn get(&mut self, args: &[ObjectValue], now: usize) -> Option<ObjectValue> {
+        let index = self.results.get_index_of(args)?;
+        if self.expired(&self.results[index], now) {
+            self.results.shift_remove_index(index);
+            return None;
+        }
+        let last = self.results.len() - 1;
+        self.results.move_index(index, last);
+        Some(self.results[last].value.clone())
+    }
+
+    /// Stores `value` as the most recently used result, then removes expired results & the least recently used
+    /// results over `limit`
+    pub fn insert(&mut self, args: Vec<ObjectValue>, value: ObjectValue, now: usize) {
+        self.results.shift_remove(&args);
+        self.results.insert(
+            args,
+            MemoResult {
+                value,
+                created: now,
+            },
+        );
+        if self.ttl.is_some() {
+            let results = core::mem::take(&mut self.results);
+            self.results = results
+                .into_iter()
+                .filter(|(_, r)| !self.expired(r, now))
+                .collect();
+        }
+        if let Some(limit) = self.limit {
+            let over = self.results.len().saturating_sub(limit.get());
+            self.results.drain(..over);
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoResult {
+    pub value: ObjectValue,
+    /// milliseconds since the unix epoch, persisted results keep the time of the run that stored them
+    pub created: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PersistentMemo {
+    pub path: String,
+    /// program digest & function name, set when the function is compiled.
+    /// Entries with a different key are ignored, changing the program invalidates its results
+    pub key: String,
+}
+
+impl PersistentMemo {
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        PersistentMemo {
+            path: path.into(),
+            key: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TestLifecycle;
 
 #[derive(Clone, Debug, Eq, Default)]
@@ -47,6 +328,8 @@ pub struct TestResults {
     pub passed: usize,
     pub failed: usize,
     pub failure_messages: Vec<(String, VMError)>,
+    /// output captured while running failed tests, tagged with the process that wrote it
+    pub failure_output: Vec<(String, Vec<CapturedOutput>)>,
     pub duration: Duration,
 }
 
@@ -55,6 +338,7 @@ impl AddAssign for TestResults {
         self.passed += rhs.passed;
         self.failed += rhs.failed;
         self.failure_messages.extend(rhs.failure_messages);
+        self.failure_output.extend(rhs.failure_output);
         self.duration += rhs.duration;
     }
 }
@@ -67,7 +351,7 @@ impl PartialEq for TestResults {
 }
 
 impl Display for TestResults {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let success = self.failed == 0 && self.failure_messages.is_empty();
 
         let preamble = if success {
@@ -77,7 +361,17 @@ impl Display for TestResults {
                 "test result: \x1b[32mok\x1b[0m".to_string()
             }
         } else {
-            let mut result = "\nfailures:\n".to_string();
+            let mut result = String::new();
+            for (name, output) in &self.failure_output {
+                if output.is_empty() {
+                    continue;
+                }
+                result.push_str(format!("\n---- {name} output ----\n").as_str());
+                for captured in output {
+                    result.push_str(format!("{captured}\n").as_str())
+                }
+            }
+            result.push_str("\nfailures:\n");
             for (name, reason) in &self.failure_messages {
                 result.push_str(format!("\t{name}: {reason}\n").as_str())
             }
@@ -92,8 +386,51 @@ impl Display for TestResults {
 
         write!(
             f,
-            "{preamble}. passed: {}, failed: {}, finished in {:?}",
-            self.passed, self.failed, self.duration
+            "{preamble}. passed: {}, failed: {}, finished in {}",
+            self.passed,
+            self.failed,
+            humanize_duration(self.duration)
         )
     }
 }
+
+#[cfg(test)]
+pub mod lifecycle_tests {
+    use crate::{MemoizedLifecycle, ObjectValue};
+    use core::num::NonZeroUsize;
+    use wasm_bindgen_test::*;
+
+    fn args(v: i64) -> Vec<ObjectValue> {
+        vec![v.into()]
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn memo_limit_removes_least_recently_used() {
+        let mut memo = MemoizedLifecycle {
+            limit: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        memo.insert(args(1), 1.into(), 0);
+        memo.insert(args(2), 2.into(), 0);
+        assert_eq!(memo.get(&args(1), 0), Some(1.into()));
+        memo.insert(args(3), 3.into(), 0);
+        assert_eq!(memo.get(&args(2), 0), None);
+        assert_eq!(memo.get(&args(1), 0), Some(1.into()));
+        assert_eq!(memo.get(&args(3), 0), Some(3.into()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn memo_ttl_expires_results() {
+        let mut memo = MemoizedLifecycle {
+            ttl: NonZeroUsize::new(100),
+            ..Default::default()
+        };
+        memo.insert(args(1), 1.into(), 0);
+        memo.insert(args(2), 2.into(), 50);
+        assert_eq!(memo.get(&args(1), 99), Some(1.into()));
+        assert_eq!(memo.get(&args(1), 100), None);
+        assert_eq!(memo.results.len(), 1);
+        memo.insert(args(3), 3.into(), 150);
+        assert_eq!(memo.results.len(), 1);
+    }
+}
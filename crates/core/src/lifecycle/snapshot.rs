@@ -1,9 +1,10 @@
+use crate::prelude::*;
 use crate::{
-    EventLifecycle, Lifecycle, MemoizedLifecycle, Snapshot, Stage, StatefulLifecycle,
-    TestLifecycle, VMError,
+    Backpressure, EventLifecycle, Lifecycle, MemoResult, MemoizedLifecycle, PersistentMemo,
+    Restart, Snapshot, Stage, StatefulLifecycle, TestLifecycle, VMError,
 };
-use std::fmt::Display;
-use std::vec::IntoIter;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
 
 impl Snapshot for Lifecycle {
     fn as_bytes(&self) -> Vec<u8> {
@@ -60,12 +61,60 @@ impl Snapshot for Lifecycle {
 
 impl Snapshot for EventLifecycle {
     fn as_bytes(&self) -> Vec<u8> {
-        Snapshot::as_bytes(&self.event)
+        let mut res = Snapshot::as_bytes(&self.event);
+        res.extend(self.capacity.as_bytes());
+        res.push(self.overflow as u8);
+        res.push(self.restart as u8);
+        res.extend(self.max_restarts.as_bytes());
+        res.extend(self.backoff.as_bytes());
+        res.extend(self.detached.as_bytes());
+        res
     }
 
     fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        let event = Snapshot::from_bytes(bytes, location)?;
+        let capacity = Snapshot::from_bytes(bytes, location)?;
+        let overflow = match bytes.next() {
+            Some(b) => match Backpressure::ALL.get(b as usize) {
+                Some(o) => *o,
+                None => {
+                    return Err(VMError::RuntimeError(format!(
+                        "Illegal Backpressure byte {b} - {location}"
+                    )))
+                }
+            },
+            None => {
+                return Err(VMError::RuntimeError(format!(
+                    "Missing Backpressure byte {location}"
+                )))
+            }
+        };
+        let restart = match bytes.next() {
+            Some(b) => match Restart::ALL.get(b as usize) {
+                Some(r) => *r,
+                None => {
+                    return Err(VMError::RuntimeError(format!(
+                        "Illegal Restart byte {b} - {location}"
+                    )))
+                }
+            },
+            None => {
+                return Err(VMError::RuntimeError(format!(
+                    "Missing Restart byte {location}"
+                )))
+            }
+        };
+        let max_restarts = Snapshot::from_bytes(bytes, location)?;
+        let backoff = Snapshot::from_bytes(bytes, location)?;
+        let detached = Snapshot::from_bytes(bytes, location)?;
         Ok(EventLifecycle {
-            event: Snapshot::from_bytes(bytes, location)?,
+            event,
+            capacity,
+            overflow,
+            restart,
+            max_restarts,
+            backoff,
+            detached,
         })
     }
 }
@@ -123,12 +172,49 @@ impl Snapshot for StatefulLifecycle {
 
 impl Snapshot for MemoizedLifecycle {
     fn as_bytes(&self) -> Vec<u8> {
-        Snapshot::as_bytes(&self.results)
+        let mut res = Snapshot::as_bytes(&self.results);
+        res.extend(Snapshot::as_bytes(&self.persist));
+        res.extend(Snapshot::as_bytes(&self.limit));
+        res.extend(Snapshot::as_bytes(&self.ttl));
+        res
     }
 
     fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
         Ok(MemoizedLifecycle {
             results: Snapshot::from_bytes(bytes, location)?,
+            persist: Snapshot::from_bytes(bytes, location)?,
+            limit: Snapshot::from_bytes(bytes, location)?,
+            ttl: Snapshot::from_bytes(bytes, location)?,
+        })
+    }
+}
+
+impl Snapshot for MemoResult {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut res = Snapshot::as_bytes(&self.value);
+        res.extend(Snapshot::as_bytes(&self.created));
+        res
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        Ok(MemoResult {
+            value: Snapshot::from_bytes(bytes, location)?,
+            created: Snapshot::from_bytes(bytes, location)?,
+        })
+    }
+}
+
+impl Snapshot for PersistentMemo {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut res = Snapshot::as_bytes(&self.path);
+        res.extend(Snapshot::as_bytes(&self.key));
+        res
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        Ok(PersistentMemo {
+            path: Snapshot::from_bytes(bytes, location)?,
+            key: Snapshot::from_bytes(bytes, location)?,
         })
     }
 }
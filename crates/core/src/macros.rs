@@ -9,7 +9,8 @@ macro_rules! define_value_tests {
             fn $test_name() {
                 let lhs: PrimitiveValue = $lhs.into();
                 let expected: PrimitiveValue = $expected.into();
-                assert_eq!(expected, &lhs $op &$rhs.into());
+                let result = &lhs $op &$rhs.into();
+                assert!(expected.loose_eq(&result), "{expected:?} != {result:?}");
             }
         )*
     };
@@ -1,14 +1,24 @@
-use crate::number::Number;
-use std::ops::Add;
+use crate::number::{decimal, Number};
+use crate::{BinaryOperation, Decimal, VMError};
+use core::ops::Add;
 
 impl Add for &Number {
-    type Output = Number;
+    type Output = Result<Number, VMError>;
 
     #[inline]
     fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (Number::Int(i), rhs) => Number::Int(i + rhs.to_int()),
-            (Number::Float(f), rhs) => Number::Float(f + rhs.to_float()),
+            (Number::Int(i), Number::Decimal(d)) => {
+                decimal::operation((*i).into(), *d, Decimal::checked_add, BinaryOperation::Add)
+            }
+            (Number::Decimal(d), rhs) => decimal::operation(
+                *d,
+                rhs.to_decimal(),
+                Decimal::checked_add,
+                BinaryOperation::Add,
+            ),
+            (Number::Int(_), rhs) => self.checked_int(BinaryOperation::Add, *rhs),
+            (Number::Float(f), rhs) => Ok(Number::Float(f + rhs.to_float())),
         }
     }
 }
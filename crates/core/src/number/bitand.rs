@@ -1,5 +1,5 @@
 use crate::number::Number;
-use std::ops::BitAnd;
+use core::ops::BitAnd;
 
 impl BitAnd for &Number {
     type Output = Number;
@@ -7,6 +7,7 @@ impl BitAnd for &Number {
     #[inline]
     fn bitand(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
+            (Number::Decimal(_), rhs) => &Number::Int(self.to_int()) & rhs,
             (Number::Int(i), rhs) => Number::Int(i & rhs.to_int()),
             (Number::Float(f), rhs) => {
                 Number::Float(f64::from_bits(f.to_bits() & rhs.to_float().to_bits()))
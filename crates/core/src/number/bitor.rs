@@ -1,5 +1,5 @@
 use crate::number::Number;
-use std::ops::BitOr;
+use core::ops::BitOr;
 
 impl BitOr for &Number {
     type Output = Number;
@@ -7,6 +7,7 @@ impl BitOr for &Number {
     #[inline]
     fn bitor(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
+            (Number::Decimal(_), rhs) => &Number::Int(self.to_int()) | rhs,
             (Number::Int(i), rhs) => Number::Int(i | rhs.to_int()),
             (Number::Float(f), rhs) => {
                 Number::Float(f64::from_bits(f.to_bits() | rhs.to_float().to_bits()))
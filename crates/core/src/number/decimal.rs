@@ -0,0 +1,86 @@
+use crate::number::Number;
+use crate::{BinaryOperation, VMError};
+use rust_decimal::{Decimal, MathematicalOps};
+
+/// `+ - * / %` where either side is a Decimal, results that don't fit in a Decimal (or division by 0) are an error
+#[inline]
+pub(crate) fn operation(
+    lhs: Decimal,
+    rhs: Decimal,
+    checked: fn(Decimal, Decimal) -> Option<Decimal>,
+    operation: BinaryOperation,
+) -> Result<Number, VMError> {
+    match checked(lhs, rhs) {
+        Some(d) => Ok(Number::Decimal(d)),
+        None if rhs.is_zero()
+            && matches!(operation, BinaryOperation::Div | BinaryOperation::Rem) =>
+        {
+            Err(VMError::RuntimeError(format!("Cannot divide {lhs} by 0")))
+        }
+        None => Err(VMError::RuntimeError(format!(
+            "Decimal overflow: {lhs} {operation} {rhs}"
+        ))),
+    }
+}
+
+/// Int exponents keep the result a Decimal, results that don't fit in a Decimal are an error
+pub(crate) fn pow(base: Decimal, e: Number) -> Result<Number, VMError> {
+    let result = match e {
+        Number::Int(e) => base.checked_powi(e),
+        _ => base.checked_powd(e.to_decimal()),
+    };
+    result
+        .map(Number::Decimal)
+        .ok_or_else(|| VMError::RuntimeError(format!("Decimal overflow: {base} ^ {e}")))
+}
+
+#[cfg(test)]
+pub mod decimal_tests {
+    use crate::{Decimal, Number};
+    use core::str::FromStr;
+    use wasm_bindgen_test::*;
+
+    fn d(value: &str) -> Number {
+        Decimal::from_str(value).unwrap().into()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn exact_arithmetic() {
+        assert_eq!((&d("0.1") + &d("0.2")).unwrap().to_string(), "0.3");
+        assert_eq!((&d("1.50") * &Number::Int(3)).unwrap().to_string(), "4.50");
+        assert_eq!((&Number::Int(10) - &d("0.01")).unwrap().to_string(), "9.99");
+        assert_eq!((&d("10") / &Number::Int(4)).unwrap().to_string(), "2.50");
+        assert_eq!((&d("10.5") % &Number::Int(4)).unwrap().to_string(), "2.5");
+        assert_eq!((-&d("1.25")).unwrap().to_string(), "-1.25");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn floats_stay_floats() {
+        assert_eq!(&Number::Float(0.5) + &d("0.25"), Ok(Number::Float(0.75)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn overflow_is_an_error() {
+        assert!((&Number::Decimal(Decimal::MAX) + &Number::one()).is_err());
+        assert!((&Number::Decimal(Decimal::MAX) * &d("2")).is_err());
+        assert!((&d("1") / &Number::Int(0)).is_err());
+        assert!((&d("1") % &Number::Int(0)).is_err());
+        assert!(Number::Decimal(Decimal::MAX).pow(Number::Int(2)).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn compares_with_other_numbers() {
+        assert_eq!(d("2.00"), Number::Int(2));
+        assert_eq!(d("0.5"), Number::Float(0.5));
+        assert!(d("2.01") > Number::Int(2));
+        assert!(Number::Float(1.5) < d("1.51"));
+        assert_eq!(d("2.9").to_int(), 2);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn pow() {
+        assert_eq!(d("1.1").pow(Number::Int(2)), Ok(d("1.21")));
+        assert_eq!(d("4").sqrt(), Ok(d("2")));
+        assert!(d("-4").sqrt().is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::with_context;
+
+/// How floats are displayed by `to_s`, `puts`, & the REPL
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FloatFormat {
+    /// Significant digits, None uses the shortest representation that parses back to the same value
+    pub precision: Option<usize>,
+    /// Values with a decimal exponent >= this are displayed in scientific notation, i.e. 1e21
+    pub scientific_above: i32,
+    /// Values with a decimal exponent < this are displayed in scientific notation, i.e. 1e-7
+    pub scientific_below: i32,
+}
+
+impl FloatFormat {
+    pub const DEFAULT: FloatFormat = FloatFormat {
+        precision: None,
+        scientific_above: 21,
+        scientific_below: -6,
+    };
+
+    pub fn with_precision(precision: usize) -> Self {
+        FloatFormat {
+            precision: Some(precision),
+            ..Self::DEFAULT
+        }
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() || value == 0.0 {
+            return value.to_string();
+        }
+        let value = match self.precision {
+            // round trip through scientific notation to round to significant digits
+            Some(p) => format!("{value:.*e}", p.max(1) - 1)
+                .parse()
+                .unwrap_or(value),
+            None => value,
+        };
+        let exponent = value.abs().log10().floor() as i32;
+        if exponent >= self.scientific_above || exponent < self.scientific_below {
+            format!("{value:e}")
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+impl Default for FloatFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Format used when displaying every float in the current thread's context, shared by the VM & its processes
+#[cfg(feature = "std")]
+pub fn float_format() -> FloatFormat {
+    with_context(|c| c.float_format())
+}
+
+/// Runtime contexts need std, floats are always displayed with the default format
+#[cfg(not(feature = "std"))]
+pub fn float_format() -> FloatFormat {
+    FloatFormat::DEFAULT
+}
+
+/// Returns the previous format
+#[cfg(feature = "std")]
+pub fn set_float_format(format: FloatFormat) -> FloatFormat {
+    with_context(|c| c.set_float_format(format))
+}
+
+#[cfg(test)]
+pub mod format_tests {
+    use crate::FloatFormat;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn default_format() {
+        let format = FloatFormat::default();
+        assert_eq!(format.format(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format.format(1.0), "1");
+        assert_eq!(format.format(1.5e21), "1.5e21");
+        assert_eq!(format.format(-2.5e-7), "-2.5e-7");
+        assert_eq!(format.format(0.000001), "0.000001");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn precision() {
+        let format = FloatFormat::with_precision(15);
+        assert_eq!(format.format(0.1 + 0.2), "0.3");
+        assert_eq!(FloatFormat::with_precision(3).format(1.23456), "1.23");
+        assert_eq!(FloatFormat::with_precision(2).format(123456.0), "120000");
+    }
+}
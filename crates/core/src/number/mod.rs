@@ -2,33 +2,47 @@ mod add;
 mod bitand;
 mod bitor;
 mod bitxor;
+mod decimal;
 mod div;
+mod format;
 mod mul;
 mod neg;
 mod not;
+mod overflow;
 mod rem;
 mod rev;
 mod shl;
 mod shr;
 mod sub;
 
+use crate::prelude::*;
 use crate::{impl_from, impl_from_cast, VMError};
-use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::fmt::{Display, Formatter};
-use std::hash::{Hash, Hasher};
-use std::str::FromStr;
-
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+#[cfg(feature = "std")]
+pub use format::set_float_format;
+pub use format::{float_format, FloatFormat};
+pub use overflow::OverflowPolicy;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::MathematicalOps;
+pub use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+
+#[derive(Copy, Clone, Debug, Serialize)]
 #[serde(untagged)]
 pub enum Number {
     Int(i64),
     Float(f64),
+    /// `1.50d`, exact base 10 arithmetic for money & other values floats round incorrectly
+    Decimal(#[serde(with = "rust_decimal::serde::str")] Decimal),
 }
 
 impl_from! {
     i64, Number, Number::Int;
     f64, Number, Number::Float;
+    Decimal, Number, Number::Decimal;
 }
 
 impl_from_cast! {
@@ -40,23 +54,41 @@ impl_from_cast! {
 // From usize not supported, since that would be a RegisterValue
 
 impl Hash for Number {
+    /// Equal numbers hash the same regardless of type, whole numbers are hashed as `i64` & others as
+    /// their exact `Decimal` value
     fn hash<H: Hasher>(&self, state: &mut H) {
-        match self {
-            Number::Int(v) => v.hash(state),
-            Number::Float(v) => v.to_bits().hash(state),
+        let decimal = match *self {
+            Number::Int(i) => return i.hash(state),
+            Number::Float(f) if f.is_nan() => return f64::NAN.to_bits().hash(state),
+            Number::Float(f) => match Decimal::from_f64_retain(f) {
+                Some(d) => d,
+                // infinite or too large for a decimal
+                None => return f.to_bits().hash(state),
+            },
+            Number::Decimal(d) => d,
+        };
+        match decimal
+            .fract()
+            .is_zero()
+            .then(|| decimal.to_i64())
+            .flatten()
+        {
+            Some(i) => i.hash(state),
+            None => decimal.hash(state),
         }
     }
 }
 
 impl Display for Number {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Number::Int(i) => {
                 write!(f, "{}", i)
             }
             Number::Float(v) => {
-                write!(f, "{}", v)
+                write!(f, "{}", float_format().format(*v))
             }
+            Number::Decimal(d) => write!(f, "{}", d),
         }
     }
 }
@@ -66,12 +98,7 @@ impl Eq for Number {}
 impl PartialEq for Number {
     #[inline]
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (&Number::Int(a), &Number::Int(b)) => a == b,
-            (&Number::Float(a), &Number::Float(b)) => a == b,
-            (&Number::Int(a), &Number::Float(b)) => a as f64 == b,
-            (&Number::Float(a), &Number::Int(b)) => a == b as f64,
-        }
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -81,17 +108,52 @@ impl PartialOrd for Number {
     }
 }
 
+/// Numbers are compared by their exact value, `-0.0` is equal to `0` and NaN is equal to itself & greater than
+/// every other number so numbers can be sorted & used as set or map keys
 impl Ord for Number {
     fn cmp(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            (Number::Int(a), Number::Int(b)) => a.cmp(b),
-            (Number::Float(a), Number::Float(b)) => a.total_cmp(b),
-            (Number::Int(a), Number::Float(b)) => (*a as f64).total_cmp(b),
-            (Number::Float(a), Number::Int(b)) => a.total_cmp(&(*b as f64)),
+        match (*self, *other) {
+            (Number::Int(a), Number::Int(b)) => a.cmp(&b),
+            (Number::Float(a), Number::Float(b)) => cmp_floats(a, b),
+            (Number::Int(a), Number::Float(b)) => cmp_int_float(a, b),
+            (Number::Float(a), Number::Int(b)) => cmp_int_float(b, a).reverse(),
+            (Number::Decimal(a), Number::Decimal(b)) => a.cmp(&b),
+            (Number::Decimal(a), Number::Int(b)) => a.cmp(&Decimal::from(b)),
+            (Number::Int(a), Number::Decimal(b)) => Decimal::from(a).cmp(&b),
+            (Number::Decimal(a), Number::Float(b)) => cmp_decimal_float(a, b),
+            (Number::Float(a), Number::Decimal(b)) => cmp_decimal_float(b, a).reverse(),
         }
     }
 }
 
+#[inline]
+fn cmp_floats(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b)
+        .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+/// `a as f64` rounds large integers, compares the whole & fractional parts of `b` instead
+fn cmp_int_float(a: i64, b: f64) -> Ordering {
+    if b.is_nan() || b >= i64::MAX as f64 {
+        Ordering::Less
+    } else if b < i64::MIN as f64 {
+        Ordering::Greater
+    } else {
+        a.cmp(&(b as i64)).then_with(|| cmp_floats(0.0, b.fract()))
+    }
+}
+
+/// Compares against the exact value of `b`, digits past a decimal's precision are compared as floats
+fn cmp_decimal_float(a: Decimal, b: f64) -> Ordering {
+    match Decimal::from_f64_retain(b) {
+        Some(d) => a
+            .cmp(&d)
+            .then_with(|| cmp_floats(d.to_f64().unwrap_or(b), b)),
+        None if b.is_nan() || b > 0.0 => Ordering::Less,
+        None => Ordering::Greater,
+    }
+}
+
 impl FromStr for Number {
     type Err = String;
 
@@ -126,6 +188,7 @@ impl Number {
         match self {
             Number::Int(i) => i == 1,
             Number::Float(f) => f == 1.0,
+            Number::Decimal(d) => d == Decimal::ONE,
         }
     }
 
@@ -134,6 +197,7 @@ impl Number {
         match self {
             Number::Int(i) => i == 0,
             Number::Float(f) => f == 0.0,
+            Number::Decimal(d) => d.is_zero(),
         }
     }
 
@@ -146,8 +210,11 @@ impl Number {
                 ))),
                 Number::Int(i) => Ok(i.ilog(e).into()),
                 Number::Float(f) => Ok(f.log(e as f64).into()),
+                Number::Decimal(_) => Ok(self.to_float().log(e as f64).into()),
             },
-            Number::Float(n) => Ok(self.to_float().log(n).into()),
+            Number::Float(_) | Number::Decimal(_) => {
+                Ok(self.to_float().log(other.to_float()).into())
+            }
         }
     }
 
@@ -158,7 +225,7 @@ impl Number {
                 "Cannot take log2 of {i}, convert to float"
             ))),
             Number::Int(i) => Ok(i.ilog2().into()),
-            Number::Float(f) => Ok(f.log2().into()),
+            Number::Float(_) | Number::Decimal(_) => Ok(self.to_float().log2().into()),
         }
     }
 
@@ -169,7 +236,7 @@ impl Number {
                 "Cannot take log10 of {i}, convert to float"
             ))),
             Number::Int(i) => Ok(i.ilog10().into()),
-            Number::Float(f) => Ok(f.log10().into()),
+            Number::Float(_) | Number::Decimal(_) => Ok(self.to_float().log10().into()),
         }
     }
 
@@ -181,6 +248,13 @@ impl Number {
             (Number::Int(a), Number::Float(b)) | (Number::Float(b), Number::Int(a)) => {
                 (a as f64).max(b).into()
             }
+            (Number::Decimal(_), _) | (_, Number::Decimal(_)) => {
+                if self >= other {
+                    self
+                } else {
+                    other
+                }
+            }
         }
     }
 
@@ -192,6 +266,13 @@ impl Number {
             (Number::Int(a), Number::Float(b)) | (Number::Float(b), Number::Int(a)) => {
                 (a as f64).min(b).into()
             }
+            (Number::Decimal(_), _) | (_, Number::Decimal(_)) => {
+                if self <= other {
+                    self
+                } else {
+                    other
+                }
+            }
         }
     }
 
@@ -207,13 +288,28 @@ impl Number {
                 i.isqrt().into()
             }
             Number::Float(f) => f.sqrt().into(),
+            Number::Decimal(d) => match d.sqrt() {
+                None => {
+                    return Err(VMError::UnsupportedOperation(format!(
+                        "cannot take sqrt of negative number {d}"
+                    )))
+                }
+                Some(d) => d.into(),
+            },
         };
         Ok(v)
     }
 
+    /// Int overflow is an error, see [`Number::pow_with`]
     #[inline]
     pub fn pow(self, e: Self) -> Result<Self, VMError> {
+        self.pow_with(e, OverflowPolicy::Checked)
+    }
+
+    /// Int base & exponent follow `policy`, negative exponents return a Float
+    pub fn pow_with(self, e: Self, policy: OverflowPolicy) -> Result<Self, VMError> {
         let v = match self {
+            Number::Decimal(d) => return decimal::pow(d, e),
             Number::Int(i) => match e {
                 Number::Int(e) => {
                     if e.is_negative() {
@@ -231,10 +327,11 @@ impl Number {
                                 u32::MAX
                             )));
                         }
-                        i.pow(e as u32).into()
+                        return overflow::int_pow(i, e as u32, policy);
                     }
                 }
                 Number::Float(e) => (i as f64).powf(e).into(),
+                Number::Decimal(_) => return decimal::pow(i.into(), e),
             },
             Number::Float(f) => match e {
                 Number::Int(e) => {
@@ -252,7 +349,7 @@ impl Number {
                     }
                     f.powi(e as i32).into()
                 }
-                Number::Float(e) => f.powf(e).into(),
+                Number::Float(_) | Number::Decimal(_) => f.powf(e.to_float()).into(),
             },
         };
         Ok(v)
@@ -263,6 +360,7 @@ impl Number {
         match self {
             Number::Int(i) => i as f64,
             Number::Float(f) => f,
+            Number::Decimal(d) => d.to_f64().unwrap_or(f64::NAN),
         }
     }
 
@@ -271,6 +369,21 @@ impl Number {
         match self {
             Number::Int(i) => i,
             Number::Float(f) => f as i64,
+            Number::Decimal(d) => d.trunc().to_i64().unwrap_or(if d.is_sign_negative() {
+                i64::MIN
+            } else {
+                i64::MAX
+            }),
+        }
+    }
+
+    /// Floats that aren't finite become 0
+    #[inline]
+    pub fn to_decimal(self) -> Decimal {
+        match self {
+            Number::Int(i) => i.into(),
+            Number::Float(f) => Decimal::try_from(f).unwrap_or_default(),
+            Number::Decimal(d) => d,
         }
     }
 
@@ -279,6 +392,7 @@ impl Number {
         match self {
             Number::Int(i) => i.to_be_bytes(),
             Number::Float(f) => f.to_be_bytes(),
+            Number::Decimal(_) => self.to_float().to_be_bytes(),
         }
     }
 
@@ -297,6 +411,7 @@ impl Number {
         let u = match self {
             Number::Int(i) => i as usize,
             Number::Float(f) => f as usize,
+            Number::Decimal(_) => self.to_int() as usize,
         };
         Ok(u)
     }
@@ -306,6 +421,7 @@ impl Number {
         match self {
             Number::Int(i) => i.is_negative(),
             Number::Float(f) => f.is_sign_negative(),
+            Number::Decimal(d) => d.is_sign_negative(),
         }
     }
 }
@@ -313,6 +429,9 @@ impl Number {
 #[cfg(test)]
 pub mod number_tests {
     use crate::Number;
+    use core::cmp::Ordering;
+    use core::hash::BuildHasher;
+    use rust_decimal::Decimal;
     use wasm_bindgen_test::*;
 
     #[wasm_bindgen_test(unsupported = test)]
@@ -325,4 +444,49 @@ pub mod number_tests {
         assert_eq!(Number::Float(1.0).to_string(), "1".to_string());
         assert_eq!(Number::Float(1.2).to_string(), "1.2".to_string());
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn equal_numbers_hash_the_same() {
+        let state = std::collections::hash_map::RandomState::new();
+        let hash = |n: Number| state.hash_one(n);
+        let equal = [
+            (Number::Int(1), Number::Float(1.0)),
+            (Number::Int(1), Number::Decimal(Decimal::new(100, 2))),
+            (Number::Float(1.5), Number::Decimal(Decimal::new(15, 1))),
+            (Number::Int(0), Number::Float(-0.0)),
+            (Number::Float(0.0), Number::Float(-0.0)),
+            (Number::Float(f64::NAN), Number::Float(-f64::NAN)),
+        ];
+        for (a, b) in equal {
+            assert_eq!(a, b);
+            assert_eq!(a.cmp(&b), Ordering::Equal, "{a} {b}");
+            assert_eq!(hash(a), hash(b), "{a} {b}");
+        }
+        assert_ne!(Number::Float(0.1), Number::Decimal(Decimal::new(1, 1)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn compares_exact_values() {
+        // 2^53 + 1 isn't a float, `as f64` rounds it to 2^53
+        let large = 9_007_199_254_740_993;
+        assert!(Number::Int(large) > Number::Float(large as f64));
+        assert!(Number::Int(i64::MAX) < Number::Float(i64::MAX as f64));
+        assert!(Number::Int(1) < Number::Float(1.5));
+        assert!(Number::Int(-1) > Number::Float(-1.5));
+        assert!(Number::Float(f64::NAN) > Number::Float(f64::INFINITY));
+        assert!(Number::Decimal(Decimal::MAX) < Number::Float(f64::INFINITY));
+        assert!(Number::Decimal(Decimal::new(1, 1)) < Number::Float(0.1));
+        let mut numbers = [
+            Number::Float(f64::NAN),
+            Number::Float(2.5),
+            Number::Int(2),
+            Number::Decimal(Decimal::new(-5, 1)),
+            Number::Float(f64::NEG_INFINITY),
+        ];
+        numbers.sort();
+        assert_eq!(
+            numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+            ["-inf", "-0.5", "2", "2.5", "NaN"]
+        );
+    }
 }
@@ -1,14 +1,16 @@
 use crate::number::Number;
-use std::ops::Neg;
+use crate::{OverflowPolicy, VMError};
+use core::ops::Neg;
 
 impl Neg for &Number {
-    type Output = Number;
+    type Output = Result<Number, VMError>;
 
     #[inline]
     fn neg(self) -> Self::Output {
         match self {
-            Number::Int(i) => Number::Int(-i),
-            Number::Float(f) => Number::Float(-f),
+            Number::Int(_) => self.negate(OverflowPolicy::Checked),
+            Number::Float(f) => Ok(Number::Float(-f)),
+            Number::Decimal(d) => Ok(Number::Decimal(-d)),
         }
     }
 }
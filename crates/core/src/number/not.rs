@@ -1,5 +1,5 @@
 use crate::number::Number;
-use std::ops::Not;
+use core::ops::Not;
 
 impl Not for &Number {
     type Output = Number;
@@ -9,6 +9,7 @@ impl Not for &Number {
         match self {
             Number::Int(v) => Number::Int(!v),
             Number::Float(v) => Number::Float(f64::from_bits(!v.to_bits())),
+            Number::Decimal(_) => Number::Int(!self.to_int()),
         }
     }
 }
@@ -0,0 +1,235 @@
+use crate::{BinaryOperation, Number, VMError};
+
+/// How `Int` arithmetic handles results that don't fit in an i64, floats are unaffected
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Overflow returns an error
+    #[default]
+    Checked,
+    /// Wraps around at the boundary, i.e. `i64::MAX + 1 == i64::MIN`
+    Wrapping,
+    /// Clamps to i64::MIN or i64::MAX
+    Saturating,
+}
+
+impl Number {
+    /// Int arithmetic following `policy`, None if the operation isn't affected by overflow
+    /// (floats, division by zero, comparisons, etc.) and should use the default behavior
+    pub fn int_operation(
+        self,
+        operation: BinaryOperation,
+        rhs: Number,
+        policy: OverflowPolicy,
+    ) -> Option<Result<Number, VMError>> {
+        // Decimal rhs converts the lhs to a Decimal instead
+        let (Number::Int(a), Number::Int(_) | Number::Float(_)) = (self, rhs) else {
+            return None;
+        };
+        // matches the default operators, Int lhs converts the rhs to an Int
+        let b = rhs.to_int();
+        let (value, overflowed) = match operation {
+            BinaryOperation::Add => a.overflowing_add(b),
+            BinaryOperation::Sub => a.overflowing_sub(b),
+            BinaryOperation::Mul => a.overflowing_mul(b),
+            BinaryOperation::Div if b != 0 => a.overflowing_div(b),
+            BinaryOperation::Rem if b != 0 => a.overflowing_rem(b),
+            BinaryOperation::Shl | BinaryOperation::Shr => {
+                let Ok(shift) = u32::try_from(b) else {
+                    return Some(Err(VMError::RuntimeError(format!(
+                        "Invalid shift: {a} {operation} {b}, shift is negative"
+                    ))));
+                };
+                if operation == BinaryOperation::Shl {
+                    overflowing_shl(a, shift)
+                } else {
+                    // only the sign is left once every bit is shifted out
+                    (a >> shift.min(i64::BITS - 1), false)
+                }
+            }
+            _ => return None,
+        };
+        if !overflowed {
+            return Some(Ok(value.into()));
+        }
+        let result = match policy {
+            OverflowPolicy::Checked => {
+                return Some(Err(VMError::RuntimeError(format!(
+                    "Integer overflow: {a} {operation} {b}"
+                ))))
+            }
+            OverflowPolicy::Wrapping => value,
+            OverflowPolicy::Saturating => match operation {
+                BinaryOperation::Add => a.saturating_add(b),
+                BinaryOperation::Sub => a.saturating_sub(b),
+                BinaryOperation::Mul => a.saturating_mul(b),
+                BinaryOperation::Div => a.saturating_div(b),
+                // i64::MIN % -1
+                BinaryOperation::Rem => 0,
+                // Shl, shifting left multiplies by 2^b
+                _ => saturate(a.is_negative()),
+            },
+        };
+        Some(Ok(result.into()))
+    }
+
+    /// Int lhs of the `&Number` operators, overflow & division by 0 are errors
+    pub(crate) fn checked_int(
+        self,
+        operation: BinaryOperation,
+        rhs: Number,
+    ) -> Result<Number, VMError> {
+        match self.int_operation(operation, rhs, OverflowPolicy::Checked) {
+            Some(result) => result,
+            None => Err(VMError::RuntimeError(format!("Cannot divide {self} by 0"))),
+        }
+    }
+
+    /// `-self` following `policy`, only `-i64::MIN` overflows
+    pub fn negate(self, policy: OverflowPolicy) -> Result<Number, VMError> {
+        let Number::Int(a) = self else {
+            return -&self;
+        };
+        match (a.checked_neg(), policy) {
+            (Some(v), _) => Ok(v.into()),
+            (None, OverflowPolicy::Checked) => {
+                Err(VMError::RuntimeError(format!("Integer overflow: -({a})")))
+            }
+            (None, OverflowPolicy::Wrapping) => Ok(a.wrapping_neg().into()),
+            (None, OverflowPolicy::Saturating) => Ok(i64::MAX.into()),
+        }
+    }
+}
+
+/// `a ^ e` following `policy`, see [`Number::pow_with`]
+pub(crate) fn int_pow(a: i64, e: u32, policy: OverflowPolicy) -> Result<Number, VMError> {
+    let (value, overflowed) = a.overflowing_pow(e);
+    if !overflowed {
+        return Ok(value.into());
+    }
+    match policy {
+        OverflowPolicy::Checked => Err(VMError::RuntimeError(format!(
+            "Integer overflow: {a} ^ {e}"
+        ))),
+        OverflowPolicy::Wrapping => Ok(value.into()),
+        OverflowPolicy::Saturating => Ok(saturate(a.is_negative() && e % 2 == 1).into()),
+    }
+}
+
+#[inline]
+fn saturate(negative: bool) -> i64 {
+    if negative {
+        i64::MIN
+    } else {
+        i64::MAX
+    }
+}
+
+/// Shifting out bits, including every bit of a non-zero value, overflows
+fn overflowing_shl(a: i64, shift: u32) -> (i64, bool) {
+    if shift >= i64::BITS {
+        return (0, a != 0);
+    }
+    let value = a << shift;
+    (value, value >> shift != a)
+}
+
+#[cfg(test)]
+pub mod overflow_tests {
+    use crate::{BinaryOperation, Number, OverflowPolicy};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn int_overflow() {
+        let max = Number::Int(i64::MAX);
+        let add = |policy| max.int_operation(BinaryOperation::Add, Number::one(), policy);
+        assert!(matches!(add(OverflowPolicy::Checked), Some(Err(_))));
+        assert_eq!(
+            add(OverflowPolicy::Wrapping),
+            Some(Ok(Number::Int(i64::MIN)))
+        );
+        assert_eq!(add(OverflowPolicy::Saturating), Some(Ok(max)));
+        assert_eq!(
+            Number::Int(i64::MIN).int_operation(
+                BinaryOperation::Mul,
+                Number::Int(2),
+                OverflowPolicy::Saturating
+            ),
+            Some(Ok(Number::Int(i64::MIN)))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn operators_are_checked() {
+        let max = Number::Int(i64::MAX);
+        let min = Number::Int(i64::MIN);
+        assert!((&max + &Number::one()).is_err());
+        assert!((&min - &Number::one()).is_err());
+        assert!((&max * &Number::Int(2)).is_err());
+        assert!((&min / &Number::Int(-1)).is_err());
+        assert!((&min % &Number::Int(-1)).is_err());
+        assert!((&Number::one() / &Number::zero()).is_err());
+        assert!((&Number::one() % &Number::zero()).is_err());
+        assert!((-&min).is_err());
+        assert!((&max << &Number::one()).is_err());
+        assert!((&Number::one() << &Number::Int(64)).is_err());
+        assert!((&Number::one() >> &Number::Int(-1)).is_err());
+        assert!(Number::Int(2).pow(Number::Int(64)).is_err());
+        assert_eq!(&Number::one() << &Number::Int(62), Ok(Number::Int(1 << 62)));
+        assert_eq!(&min >> &Number::Int(100), Ok(Number::Int(-1)));
+        assert_eq!(-&max, Ok(Number::Int(-i64::MAX)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unary_and_pow_overflow() {
+        let min = Number::Int(i64::MIN);
+        assert_eq!(
+            min.negate(OverflowPolicy::Wrapping),
+            Ok(Number::Int(i64::MIN))
+        );
+        assert_eq!(
+            min.negate(OverflowPolicy::Saturating),
+            Ok(Number::Int(i64::MAX))
+        );
+        let pow = |base: i64, policy| Number::Int(base).pow_with(Number::Int(63), policy);
+        assert_eq!(pow(2, OverflowPolicy::Wrapping), Ok(Number::Int(i64::MIN)));
+        assert_eq!(
+            pow(3, OverflowPolicy::Saturating),
+            Ok(Number::Int(i64::MAX))
+        );
+        assert_eq!(pow(-3, OverflowPolicy::Saturating), Ok(min));
+        let shl =
+            |policy| Number::Int(3).int_operation(BinaryOperation::Shl, Number::Int(63), policy);
+        assert_eq!(shl(OverflowPolicy::Wrapping), Some(Ok(min)));
+        assert_eq!(
+            shl(OverflowPolicy::Saturating),
+            Some(Ok(Number::Int(i64::MAX)))
+        );
+        assert_eq!(
+            min.int_operation(
+                BinaryOperation::Rem,
+                Number::Int(-1),
+                OverflowPolicy::Wrapping
+            ),
+            Some(Ok(Number::zero()))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unaffected_operations() {
+        let one = Number::one();
+        let policy = OverflowPolicy::Checked;
+        assert_eq!(
+            one.int_operation(BinaryOperation::Add, one, policy),
+            Some(Ok(Number::Int(2)))
+        );
+        assert_eq!(
+            Number::Float(1.0).int_operation(BinaryOperation::Add, one, policy),
+            None
+        );
+        assert_eq!(
+            one.int_operation(BinaryOperation::Div, Number::zero(), policy),
+            None
+        );
+        assert_eq!(one.int_operation(BinaryOperation::Gt, one, policy), None);
+    }
+}
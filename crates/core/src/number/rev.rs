@@ -9,6 +9,7 @@ impl Reverse for &Number {
         match *self {
             Number::Int(i) => Number::Int(i.reverse_bits()),
             Number::Float(f) => Number::Float(f64::from_bits(f.to_bits().reverse_bits())),
+            Number::Decimal(_) => Number::Int(self.to_int().reverse_bits()),
         }
     }
 }
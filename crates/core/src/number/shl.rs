@@ -1,19 +1,25 @@
 use crate::number::Number;
-use std::ops::Shl;
+use crate::{BinaryOperation, VMError};
+use core::ops::Shl;
 
 impl Shl for &Number {
-    type Output = Number;
+    type Output = Result<Number, VMError>;
 
     #[inline]
     fn shl(self, rhs: Self) -> Self::Output {
-        let shift = match rhs {
-            Number::Int(i) => i,
-            Number::Float(f) => &(*f as i64),
-        };
-
         match self {
-            Number::Int(i) => Number::Int(i << shift),
-            Number::Float(f) => Number::Float(f64::from_bits(f.to_bits() << shift)),
+            Number::Int(_) => self.checked_int(BinaryOperation::Shl, *rhs),
+            Number::Float(f) => match u32::try_from(rhs.to_int()) {
+                Ok(shift) if shift < u64::BITS => {
+                    Ok(Number::Float(f64::from_bits(f.to_bits() << shift)))
+                }
+                _ => Err(VMError::RuntimeError(format!(
+                    "Invalid shift: {f} << {rhs}"
+                ))),
+            },
+            Number::Decimal(_) => {
+                Number::Int(self.to_int()).checked_int(BinaryOperation::Shl, *rhs)
+            }
         }
     }
 }
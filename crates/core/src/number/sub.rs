@@ -1,14 +1,24 @@
-use crate::number::Number;
-use std::ops::Sub;
+use crate::number::{decimal, Number};
+use crate::{BinaryOperation, Decimal, VMError};
+use core::ops::Sub;
 
 impl Sub for &Number {
-    type Output = Number;
+    type Output = Result<Number, VMError>;
 
     #[inline]
     fn sub(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (Number::Int(i), rhs) => Number::Int(i - rhs.to_int()),
-            (Number::Float(f), rhs) => Number::Float(f - rhs.to_float()),
+            (Number::Int(i), Number::Decimal(d)) => {
+                decimal::operation((*i).into(), *d, Decimal::checked_sub, BinaryOperation::Sub)
+            }
+            (Number::Decimal(d), rhs) => decimal::operation(
+                *d,
+                rhs.to_decimal(),
+                Decimal::checked_sub,
+                BinaryOperation::Sub,
+            ),
+            (Number::Int(_), rhs) => self.checked_int(BinaryOperation::Sub, *rhs),
+            (Number::Float(f), rhs) => Ok(Number::Float(f - rhs.to_float())),
         }
     }
 }
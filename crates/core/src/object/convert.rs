@@ -0,0 +1,57 @@
+use crate::{ObjectValue, VMError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Converts any serializable host value into a rigz value, structs become maps keyed by field name,
+/// i.e. `vm.add_bindings` or module return values without a hand-written `From`
+pub fn to_value<T: Serialize>(value: &T) -> Result<ObjectValue, VMError> {
+    let json = serde_json::to_value(value)
+        .map_err(|e| VMError::ConversionError(format!("Cannot convert to rigz value - {e}")))?;
+    ObjectValue::deserialize(json)
+        .map_err(|e| VMError::ConversionError(format!("Cannot convert to rigz value - {e}")))
+}
+
+/// Converts a rigz value into any deserializable host value, maps are read as structs or maps
+pub fn from_value<T: DeserializeOwned>(value: &ObjectValue) -> Result<T, VMError> {
+    let json = serde_json::to_value(value)
+        .map_err(|e| VMError::ConversionError(format!("Cannot convert {value} - {e}")))?;
+    T::deserialize(json)
+        .map_err(|e| VMError::ConversionError(format!("Cannot convert {value} - {e}")))
+}
+
+#[cfg(test)]
+pub mod convert_tests {
+    use crate::{from_value, to_value, IndexMap, ObjectValue};
+    use wasm_bindgen_test::*;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct User {
+        name: String,
+        age: i64,
+        tags: Vec<String>,
+        manager: Option<Box<User>>,
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn host_struct_round_trip() {
+        let user = User {
+            name: "ada".to_string(),
+            age: 36,
+            tags: vec!["admin".to_string()],
+            manager: None,
+        };
+        let value = to_value(&user).unwrap();
+        let mut expected = IndexMap::new();
+        expected.insert("name".into(), "ada".into());
+        expected.insert("age".into(), 36.into());
+        expected.insert("tags".into(), ObjectValue::List(vec!["admin".into()]));
+        expected.insert("manager".into(), ObjectValue::default());
+        assert_eq!(value, ObjectValue::Map(expected));
+        assert_eq!(from_value::<User>(&value).unwrap(), user);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn mismatched_shape() {
+        assert!(from_value::<User>(&ObjectValue::from(1)).is_err());
+    }
+}
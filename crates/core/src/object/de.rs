@@ -0,0 +1,223 @@
+use crate::prelude::*;
+use crate::{Decimal, IndexMap, Number, ObjectValue, PrimitiveValue};
+use core::fmt::Formatter;
+use core::str::FromStr;
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+// Values are serialized untagged, so reading them back is done by the shape of the input rather than trying each
+// variant in order; otherwise `"1"` would be read as a Decimal & `[1, 2]` as a Range. Ranges, types, errors & objects
+// are written as plain strings, lists, & maps, and are read back as those
+
+struct ObjectValueVisitor;
+
+impl<'de> Visitor<'de> for ObjectValueVisitor {
+    type Value = ObjectValue;
+
+    fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        formatter.write_str("a rigz value")
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(NumberVisitor.visit_u64::<E>(v)?.into())
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_string<E: Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(ObjectValue::List(
+            v.iter().map(|b| ObjectValue::from(*b as i64)).collect(),
+        ))
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+        Ok(ObjectValue::default())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        ObjectValue::deserialize(deserializer)
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+        Ok(ObjectValue::default())
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        ObjectValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(v) = seq.next_element()? {
+            values.push(v);
+        }
+        Ok(ObjectValue::List(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut values = IndexMap::with_capacity_and_hasher(
+            map.size_hint().unwrap_or_default(),
+            Default::default(),
+        );
+        while let Some((k, v)) = map.next_entry()? {
+            values.insert(k, v);
+        }
+        Ok(ObjectValue::Map(values))
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ObjectValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for PrimitiveValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match ObjectValue::deserialize(deserializer)? {
+            ObjectValue::Primitive(p) => Ok(p),
+            v => Err(D::Error::custom(format!("expected a primitive, found {v}"))),
+        }
+    }
+}
+
+struct NumberVisitor;
+
+impl<'de> Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        formatter.write_str("a number")
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    /// Larger than an Int becomes a Float, same as an overflowing literal
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        match i64::try_from(v) {
+            Ok(i) => Ok(i.into()),
+            Err(_) => Ok((v as f64).into()),
+        }
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    /// Decimals are serialized as strings so they keep their precision
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        match Decimal::from_str_exact(v) {
+            Ok(d) if v.contains('.') => Ok(d.into()),
+            _ => Number::from_str(v).map_err(E::custom),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+#[cfg(test)]
+pub mod de_tests {
+    use crate::{Decimal, IndexMap, Number, ObjectValue, PrimitiveValue};
+    use core::str::FromStr;
+    use wasm_bindgen_test::*;
+
+    fn parse(input: &str) -> ObjectValue {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn reads_by_shape() {
+        assert_eq!(parse(r#""1""#), ObjectValue::from("1"));
+        assert_eq!(parse("1"), ObjectValue::from(1));
+        assert_eq!(parse("1.5"), ObjectValue::from(1.5));
+        assert_eq!(parse("null"), ObjectValue::default());
+        assert_eq!(parse("[1, 2]"), ObjectValue::List(vec![1.into(), 2.into()]));
+        assert_eq!(
+            parse("18446744073709551615"),
+            ObjectValue::from(u64::MAX as f64)
+        );
+        let mut inner = IndexMap::new();
+        inner.insert("b".into(), ObjectValue::List(vec![true.into()]));
+        let mut outer = IndexMap::new();
+        outer.insert("a".into(), ObjectValue::Map(inner));
+        assert_eq!(parse(r#"{"a": {"b": [true]}}"#), ObjectValue::Map(outer));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn round_trip() {
+        let mut map = IndexMap::new();
+        map.insert("name".into(), "rigz".into());
+        map.insert(
+            "values".into(),
+            ObjectValue::List(vec![1.into(), 2.5.into(), ObjectValue::default()]),
+        );
+        let value = ObjectValue::Map(map);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"name":"rigz","values":[1,2.5,null]}"#);
+        assert_eq!(parse(&json), value);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn primitives_and_numbers() {
+        assert_eq!(
+            serde_json::from_str::<PrimitiveValue>("true").unwrap(),
+            PrimitiveValue::Bool(true)
+        );
+        assert!(serde_json::from_str::<PrimitiveValue>("[1]").is_err());
+        assert_eq!(
+            serde_json::from_str::<Number>(r#""1_000""#).unwrap(),
+            Number::Int(1000)
+        );
+        assert!(serde_json::from_str::<Number>("true").is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decimals_keep_precision() {
+        let d = Number::Decimal(Decimal::from_str("0.10000000000000000000000001").unwrap());
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(json, r#""0.10000000000000000000000001""#);
+        assert_eq!(serde_json::from_str::<Number>(&json).unwrap(), d);
+        assert!(matches!(
+            serde_json::from_str::<Number>(&json).unwrap(),
+            Number::Decimal(_)
+        ));
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn object_snapshot() {
+        use crate::{Snapshot, Uuid};
+        let value: ObjectValue = Uuid::from_random_bytes([3; 16]).into();
+        let bytes = value.as_bytes();
+        let restored: ObjectValue =
+            Snapshot::from_bytes(&mut bytes.into_iter(), &"object").unwrap();
+        assert_eq!(restored, value);
+    }
+}
@@ -1,11 +1,10 @@
-use crate::{IndexMap, Object, ObjectValue, PrimitiveValue, VMError};
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::prelude::*;
+use crate::{IndexMap, Object, ObjectValue, PrimitiveValue, Shared, VMError};
 
-impl From<ObjectValue> for Rc<RefCell<ObjectValue>> {
+impl From<ObjectValue> for Shared<ObjectValue> {
     #[inline]
     fn from(value: ObjectValue) -> Self {
-        Rc::new(RefCell::new(value))
+        Shared::new(value)
     }
 }
 
@@ -1,25 +1,30 @@
+#[cfg(feature = "serde")]
+mod convert;
+mod de;
 pub mod from;
 mod ops;
 
 #[cfg(feature = "snapshot")]
 mod snapshot;
 
+use crate::prelude::*;
 use crate::{
-    AsPrimitive, IndexMap, Number, Object, PrimitiveValue, RigzType, VMError, WithTypeInfo,
+    grapheme_at, grapheme_slice, graphemes, AsPrimitive, Frozen, IndexMap, Number, Object,
+    PrimitiveValue, RigzType, Shared, VMError, ValueRange, WithTypeInfo,
 };
-use itertools::Itertools;
-use std::cell::RefCell;
-use std::cmp::Ordering;
-use std::fmt::{Display, Formatter};
-use std::hash::{Hash, Hasher};
-use std::ops::Deref;
-use std::rc::Rc;
-
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+#[cfg(feature = "serde")]
+pub use convert::{from_value, to_value};
+
+#[derive(Clone, Debug, serde::Serialize)]
 #[serde(untagged)]
 pub enum ObjectValue {
     Primitive(PrimitiveValue),
-    // todo Lists, Maps, & Tuples should use Rc<RefCell<ObjectValue>> to make this language fully pass by reference
+    // todo Lists, Maps, & Tuples should use Shared<ObjectValue> to make this language fully pass by reference
     List(Vec<ObjectValue>),
     Map(IndexMap<ObjectValue, ObjectValue>),
     Tuple(Vec<ObjectValue>),
@@ -55,57 +60,164 @@ impl Hash for ObjectValue {
     }
 }
 
+/// Values of different kinds are never equal (`[1] != (1,)`) & maps are equal if their entries are in the same
+/// order, consistent with `Ord` & `Hash`. The `==` operator uses `loose_eq` instead
 impl PartialEq for ObjectValue {
     fn eq(&self, other: &Self) -> bool {
+        if self.is_frozen() || other.is_frozen() {
+            return self.unfrozen() == other.unfrozen();
+        }
+
         match (self, other) {
             (ObjectValue::Primitive(left), ObjectValue::Primitive(right)) => left == right,
+            (ObjectValue::List(left), ObjectValue::List(right))
+            | (ObjectValue::Tuple(left), ObjectValue::Tuple(right)) => left == right,
+            // in order, like `Hash` & `Ord`
+            (ObjectValue::Map(left), ObjectValue::Map(right)) => left.iter().eq(right.iter()),
+            (ObjectValue::Object(left), ObjectValue::Object(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl ObjectValue {
+    /// String keys are interned so maps with the same keys share them
+    pub fn map_key(self) -> Self {
+        match self {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => s.intern().into(),
+            v => v,
+        }
+    }
+
+    /// Equality of the `==` operator, `none` & `false` equal empty collections, lists equal tuples, maps are
+    /// compared in any order, & primitives are converted, see `PrimitiveValue::loose_eq`
+    pub fn loose_eq(&self, other: &Self) -> bool {
+        if self.is_frozen() || other.is_frozen() {
+            return self.unfrozen().loose_eq(other.unfrozen());
+        }
+
+        match (self, other) {
+            (ObjectValue::Primitive(left), ObjectValue::Primitive(right)) => left.loose_eq(right),
             (
                 ObjectValue::Primitive(PrimitiveValue::None)
                 | ObjectValue::Primitive(PrimitiveValue::Bool(false)),
                 ObjectValue::List(l) | ObjectValue::Tuple(l),
+            )
+            | (
+                ObjectValue::List(l) | ObjectValue::Tuple(l),
+                ObjectValue::Primitive(PrimitiveValue::None)
+                | ObjectValue::Primitive(PrimitiveValue::Bool(false)),
             ) => l.is_empty(),
             (
                 ObjectValue::Primitive(PrimitiveValue::None)
                 | ObjectValue::Primitive(PrimitiveValue::Bool(false)),
-                ObjectValue::Map(l),
-            ) => l.is_empty(),
-            (ObjectValue::List(l) | ObjectValue::Tuple(l), ObjectValue::Map(m)) => {
+                ObjectValue::Map(m),
+            )
+            | (
+                ObjectValue::Map(m),
+                ObjectValue::Primitive(PrimitiveValue::None)
+                | ObjectValue::Primitive(PrimitiveValue::Bool(false)),
+            ) => m.is_empty(),
+            (ObjectValue::List(l) | ObjectValue::Tuple(l), ObjectValue::Map(m))
+            | (ObjectValue::Map(m), ObjectValue::List(l) | ObjectValue::Tuple(l)) => {
                 l.is_empty() && m.is_empty()
             }
             (
                 ObjectValue::List(left) | ObjectValue::Tuple(left),
                 ObjectValue::List(right) | ObjectValue::Tuple(right),
-            ) => left == right,
-            (ObjectValue::Map(left), ObjectValue::Map(right)) => left == right,
-            (ObjectValue::Object(left), ObjectValue::Object(right)) => left == right,
-            _ => false,
+            ) => left.len() == right.len() && left.iter().zip(right).all(|(l, r)| l.loose_eq(r)),
+            (ObjectValue::Map(left), ObjectValue::Map(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .all(|(k, v)| right.get(k).is_some_and(|r| v.loose_eq(r)))
+            }
+            _ => self == other,
         }
     }
 }
 
 impl PartialOrd for ObjectValue {
+    #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (ObjectValue::Primitive(left), ObjectValue::Primitive(right)) => Some(left.cmp(right)),
-            (ObjectValue::List(lhs), ObjectValue::List(rhs)) => lhs.partial_cmp(rhs),
-            (ObjectValue::Map(lhs), ObjectValue::Map(rhs)) => lhs.into_iter().partial_cmp(rhs),
-            (ObjectValue::Tuple(lhs), ObjectValue::Tuple(rhs)) => lhs.partial_cmp(rhs),
-            (ObjectValue::Object(lhs), ObjectValue::Object(rhs)) => lhs.dyn_partial_cmp(rhs),
-            _ => None,
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "bigint")]
+const NUMBER: PrimitiveValue = PrimitiveValue::Number(Number::Int(0));
+
+impl ObjectValue {
+    /// Position of this kind of value in the total order: primitives (ordered by `PrimitiveValue`), lists, tuples,
+    /// maps, then objects. Values that rank as a primitive return the primitive they're ordered as
+    fn rank(&self) -> (u8, Option<&PrimitiveValue>) {
+        match self {
+            ObjectValue::Primitive(p) => (0, Some(p)),
+            #[cfg(feature = "bigint")]
+            ObjectValue::Object(o) if o.is::<crate::BigInt>() => (0, Some(&NUMBER)),
+            ObjectValue::List(_) => (1, None),
+            ObjectValue::Tuple(_) => (2, None),
+            ObjectValue::Map(_) => (3, None),
+            ObjectValue::Object(_) => (4, None),
+        }
+    }
+
+    fn cmp_rank(&self, other: &Self) -> Ordering {
+        match (self.rank(), other.rank()) {
+            ((_, Some(lhs)), (_, Some(rhs))) => lhs.cmp(rhs),
+            ((lhs, _), (rhs, _)) if lhs != rhs => lhs.cmp(&rhs),
+            _ => self
+                .rigz_type()
+                .to_string()
+                .cmp(&other.rigz_type().to_string())
+                .then_with(|| self.to_string().cmp(&other.to_string())),
         }
     }
 }
 
+/// Strict total order used by sorting, sets, & map keys, independent of the loose `==` (`0 == "0"` but they never
+/// compare equal here). Values of different kinds are ordered by their rank then value, objects of different types
+/// are ordered by type name then display
 impl Ord for ObjectValue {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Less)
+        if self.is_frozen() || other.is_frozen() {
+            return self.unfrozen().cmp(other.unfrozen());
+        }
+
+        match (self, other) {
+            (ObjectValue::Primitive(left), ObjectValue::Primitive(right)) => left.cmp(right),
+            (ObjectValue::List(lhs), ObjectValue::List(rhs))
+            | (ObjectValue::Tuple(lhs), ObjectValue::Tuple(rhs)) => lhs.cmp(rhs),
+            (ObjectValue::Map(lhs), ObjectValue::Map(rhs)) => lhs.iter().cmp(rhs.iter()),
+            // compares the objects, not their boxes
+            (ObjectValue::Object(lhs), ObjectValue::Object(rhs)) => lhs
+                .as_ref()
+                .partial_cmp(rhs.as_ref())
+                .unwrap_or_else(|| self.cmp_rank(other)),
+            #[cfg(feature = "bigint")]
+            (ObjectValue::Object(lhs), ObjectValue::Primitive(PrimitiveValue::Number(rhs))) => {
+                // never equal since they're different kinds, BigInts follow numbers of the same value
+                match lhs.downcast_ref::<crate::BigInt>() {
+                    Some(b) => b
+                        .cmp_number(rhs)
+                        .unwrap_or_else(|| NUMBER.cmp(&PrimitiveValue::Number(*rhs)))
+                        .then(Ordering::Greater),
+                    None => self.cmp_rank(other),
+                }
+            }
+            #[cfg(feature = "bigint")]
+            (ObjectValue::Primitive(PrimitiveValue::Number(_)), ObjectValue::Object(_)) => {
+                other.cmp(self).reverse()
+            }
+            _ => self.cmp_rank(other),
+        }
     }
 }
 
 impl Eq for ObjectValue {}
 
 impl Display for ObjectValue {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             ObjectValue::Primitive(p) => write!(f, "{}", p),
             ObjectValue::Object(o) => write!(f, "{}", o),
@@ -149,6 +261,74 @@ impl Display for ObjectValue {
 }
 
 impl ObjectValue {
+    /// Values of `for v in value`, strings are iterated by grapheme
+    pub fn iter_values(&self) -> Result<Vec<ObjectValue>, VMError> {
+        match self {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => {
+                Ok(graphemes(s).map(ObjectValue::from).collect())
+            }
+            v => v.to_list(),
+        }
+    }
+
+    /// Deep-immutable copy of lists, maps, tuples, & objects, primitives are returned as is
+    pub fn freeze(self) -> ObjectValue {
+        match self {
+            ObjectValue::Primitive(_) => self,
+            v if v.is_frozen() => v,
+            v => Frozen::new(v).into(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_frozen(&self) -> Option<&Frozen> {
+        match self {
+            ObjectValue::Object(o) => o.downcast_ref::<Frozen>(),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.as_frozen().is_some()
+    }
+
+    /// The value a frozen value wraps, otherwise self
+    #[inline]
+    pub fn unfrozen(&self) -> &ObjectValue {
+        self.as_frozen().map_or(self, Frozen::value)
+    }
+
+    /// True if this value holds the shared state `id` (see `AsPrimitive::shared_id`) directly or through lists,
+    /// maps, & the values of other objects. Storing such a value in that state would create a reference cycle
+    /// that is never freed, mutexes & channels reject it instead.
+    pub fn references(&self, id: usize) -> bool {
+        fn visit(value: &ObjectValue, id: usize, seen: &mut Vec<usize>) -> bool {
+            match value {
+                ObjectValue::Primitive(_) => false,
+                ObjectValue::List(l) | ObjectValue::Tuple(l) => {
+                    l.iter().any(|v| visit(v, id, seen))
+                }
+                ObjectValue::Map(m) => m
+                    .iter()
+                    .any(|(k, v)| visit(k, id, seen) || visit(v, id, seen)),
+                ObjectValue::Object(o) => {
+                    if let Some(shared) = o.shared_id() {
+                        if shared == id {
+                            return true;
+                        }
+                        if seen.contains(&shared) {
+                            return false;
+                        }
+                        seen.push(shared);
+                    }
+                    o.contained_values().iter().any(|v| visit(v, id, seen))
+                }
+            }
+        }
+        visit(self, id, &mut Vec::new())
+    }
+
     #[inline]
     pub fn is_error(&self) -> bool {
         matches!(self, ObjectValue::Primitive(PrimitiveValue::Error(_)))
@@ -203,19 +383,21 @@ impl ObjectValue {
     }
 
     pub fn get(&self, attr: &ObjectValue) -> Result<Option<ObjectValue>, VMError> {
-        // todo support negative numbers as index, -1 is last element
+        // todo support negative numbers as index for lists, -1 is last element
         let v = match (self, attr) {
-            // todo support ranges as attr
+            // strings are indexed by grapheme, `s[-1]` is the last one
             (
                 ObjectValue::Primitive(PrimitiveValue::String(source)),
                 ObjectValue::Primitive(PrimitiveValue::Number(n)),
-            ) => {
-                let index = n.to_usize()?;
-                match source.chars().nth(index) {
-                    None => return Ok(None),
-                    Some(c) => c.to_string().into(),
-                }
-            }
+            ) => match grapheme_at(source, n.to_int()) {
+                None => return Ok(None),
+                Some(c) => c.to_string().into(),
+            },
+            // todo support ranges as attr for lists
+            (
+                ObjectValue::Primitive(PrimitiveValue::String(source)),
+                ObjectValue::Primitive(PrimitiveValue::Range(ValueRange::Int(range))),
+            ) => grapheme_slice(source, range).into(),
             (ObjectValue::List(source), ObjectValue::Primitive(PrimitiveValue::Number(n)))
             | (ObjectValue::Tuple(source), ObjectValue::Primitive(PrimitiveValue::Number(n))) => {
                 match n.to_usize() {
@@ -256,7 +438,7 @@ impl ObjectValue {
 
     pub fn instance_set(
         &mut self,
-        attr: Rc<RefCell<ObjectValue>>,
+        attr: Shared<ObjectValue>,
         value: &ObjectValue,
     ) -> Result<(), VMError> {
         // todo support negative numbers as index, -1 is last element
@@ -267,7 +449,7 @@ impl ObjectValue {
                 ObjectValue::Primitive(PrimitiveValue::Number(n)),
             ) => match n.to_usize() {
                 Ok(index) => {
-                    s.insert_str(index, value.to_string().as_str());
+                    s.make_mut().insert_str(index, value.to_string().as_str());
                     None
                 }
                 Err(e) => Some(e),
@@ -283,7 +465,7 @@ impl ObjectValue {
                 }
             }
             (ObjectValue::Map(source), index) => {
-                source.insert(index.clone(), value.clone());
+                source.insert(index.clone().map_key(), value.clone());
                 None
             }
             (
@@ -292,7 +474,7 @@ impl ObjectValue {
             ) => {
                 let value = if value.to_bool() { 1 } else { 0 };
                 *source = match source {
-                    Number::Int(_) => {
+                    Number::Int(_) | Number::Decimal(_) => {
                         i64::from_le_bytes((source.to_bits() & (value << n.to_int())).to_le_bytes())
                             .into()
                     }
@@ -305,12 +487,7 @@ impl ObjectValue {
             (source, attr) => {
                 if let ObjectValue::Object(o) = source {
                     let value = value.clone();
-                    let v = o.set(attr, value);
-                    if let Err(e) = v {
-                        Some(e)
-                    } else {
-                        None
-                    }
+                    o.set(attr, value).err()
                 } else {
                     Some(VMError::UnsupportedOperation(format!(
                         "Cannot read {} for {}",
@@ -339,6 +516,16 @@ impl ObjectValue {
                 Ok(n) => n.into(),
             },
             (s, RigzType::Any) => s.clone(),
+            // Ints past the range of an i64, i.e. `99999999999999999999 as Int`
+            #[cfg(feature = "bigint")]
+            (ObjectValue::Object(o), RigzType::Int) if o.is::<crate::BigInt>() => self.clone(),
+            #[cfg(feature = "bigint")]
+            (ObjectValue::Primitive(PrimitiveValue::String(s)), RigzType::Int) => {
+                match crate::BigInt::parse(s) {
+                    Ok(v) => v,
+                    Err(e) => e.into(),
+                }
+            }
             (v, RigzType::Int) => match v.to_int() {
                 Err(_) => VMError::ConversionError(format!("Cannot convert {} to Int", v)).into(),
                 Ok(n) => n.into(),
@@ -411,6 +598,9 @@ impl WithTypeInfo for ObjectValue {
 
 impl AsPrimitive<ObjectValue> for ObjectValue {
     fn as_list(&mut self) -> Result<&mut Vec<ObjectValue>, VMError> {
+        if let Some(f) = self.as_frozen() {
+            return Err(f.immutable());
+        }
         *self = ObjectValue::List(AsPrimitive::to_list(self)?);
         let ObjectValue::List(m) = self else {
             unreachable!()
@@ -420,15 +610,30 @@ impl AsPrimitive<ObjectValue> for ObjectValue {
 
     fn to_list(&self) -> Result<Vec<ObjectValue>, VMError> {
         match self {
+            ObjectValue::Primitive(PrimitiveValue::Range(r)) => {
+                Ok(r.to_list().into_iter().map(|v| v.into()).collect())
+            }
             ObjectValue::Tuple(v) | ObjectValue::List(v) => Ok(v.clone()),
             ObjectValue::Map(m) => Ok(m.values().cloned().collect()),
+            ObjectValue::Object(o) => o.to_list(),
             _ => Err(VMError::UnsupportedOperation(format!(
                 "Cannot convert {self} to List"
             ))),
         }
     }
 
-    fn to_map(&self) -> Result<indexmap::IndexMap<ObjectValue, ObjectValue>, VMError> {
+    fn as_map(&mut self) -> Result<&mut IndexMap<ObjectValue, ObjectValue>, VMError> {
+        if let Some(f) = self.as_frozen() {
+            return Err(f.immutable());
+        }
+        *self = ObjectValue::Map(AsPrimitive::to_map(self)?);
+        let ObjectValue::Map(m) = self else {
+            unreachable!()
+        };
+        Ok(m)
+    }
+
+    fn to_map(&self) -> Result<IndexMap<ObjectValue, ObjectValue>, VMError> {
         match self {
             ObjectValue::Primitive(m) => Ok(m
                 .to_map()?
@@ -469,3 +674,144 @@ impl AsPrimitive<ObjectValue> for ObjectValue {
         }
     }
 }
+
+#[cfg(test)]
+pub mod object_tests {
+    use crate::{IndexMap, ObjectValue, PrimitiveValue, VMError};
+    use core::cmp::Ordering;
+    use itertools::Itertools;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn total_order_by_rank() {
+        let mut map = IndexMap::new();
+        map.insert("a".into(), 1.into());
+        let mut values = vec![
+            ObjectValue::Map(map.clone()),
+            ObjectValue::List(vec![1.into()]),
+            "a".into(),
+            2.5.into(),
+            ObjectValue::default(),
+            true.into(),
+            1.into(),
+            VMError::RuntimeError("e".into()).into(),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                VMError::RuntimeError("e".into()).into(),
+                ObjectValue::default(),
+                true.into(),
+                1.into(),
+                2.5.into(),
+                "a".into(),
+                ObjectValue::List(vec![1.into()]),
+                ObjectValue::Map(map),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn consistent_across_kinds() {
+        let values: Vec<ObjectValue> = vec![
+            1.into(),
+            "1".into(),
+            ObjectValue::List(vec![]),
+            ObjectValue::List(vec![1.into()]),
+            ObjectValue::Tuple(vec![1.into(), 2.into()]),
+            ObjectValue::Map(IndexMap::new()),
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{a} <=> {b}");
+                assert_eq!(a.partial_cmp(b), Some(a.cmp(b)));
+            }
+        }
+        assert_eq!(
+            ObjectValue::List(vec![]).cmp(&ObjectValue::default()),
+            Ordering::Greater
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn map_keys_are_interned() {
+        let key = ObjectValue::from("object_tests::key".to_string()).map_key();
+        let other = ObjectValue::from("object_tests::key").map_key();
+        match (key, other) {
+            (
+                ObjectValue::Primitive(PrimitiveValue::String(a)),
+                ObjectValue::Primitive(PrimitiveValue::String(b)),
+            ) => assert!(core::ptr::eq(a.as_str(), b.as_str())),
+            v => panic!("expected strings, received {v:?}"),
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn eq_agrees_with_cmp() {
+        let mut ab: IndexMap<ObjectValue, ObjectValue> = IndexMap::new();
+        ab.insert("a".into(), 1.into());
+        ab.insert("b".into(), 2.into());
+        let ba: IndexMap<_, _> = ab
+            .iter()
+            .rev()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let values: Vec<ObjectValue> = vec![
+            ObjectValue::default(),
+            false.into(),
+            0.into(),
+            0.0.into(),
+            "".into(),
+            "0".into(),
+            ObjectValue::List(vec![]),
+            ObjectValue::List(vec![1.into()]),
+            ObjectValue::Tuple(vec![]),
+            ObjectValue::Tuple(vec![1.into()]),
+            ObjectValue::Map(IndexMap::new()),
+            ObjectValue::Map(ab),
+            ObjectValue::Map(ba),
+            ObjectValue::List(vec![1.into()]).freeze(),
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(a == b, a.cmp(b) == Ordering::Equal, "{a:?} <=> {b:?}");
+            }
+        }
+        // `==` is still loose
+        assert!(ObjectValue::default().loose_eq(&ObjectValue::List(vec![])));
+        assert!(ObjectValue::List(vec![1.into()]).loose_eq(&ObjectValue::Tuple(vec!["1".into()])));
+        assert!(ObjectValue::Map(IndexMap::new()).loose_eq(&false.into()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn sort_independent_of_input_order() {
+        let values: Vec<ObjectValue> = vec![
+            0.into(),
+            "0".into(),
+            "".into(),
+            ObjectValue::default(),
+            false.into(),
+            ObjectValue::List(vec![]),
+            ObjectValue::Map(IndexMap::new()),
+        ];
+        let expected = vec![
+            ObjectValue::default(),
+            false.into(),
+            0.into(),
+            "".into(),
+            "0".into(),
+            ObjectValue::List(vec![]),
+            ObjectValue::Map(IndexMap::new()),
+        ];
+        for permutation in values.iter().cloned().permutations(values.len()) {
+            let mut sorted = permutation.clone();
+            sorted.sort();
+            assert_eq!(
+                format!("{sorted:?}"),
+                format!("{expected:?}"),
+                "sorting {permutation:?}"
+            );
+        }
+    }
+}
@@ -1,10 +1,16 @@
+use crate::object::ops::object_operation;
+use crate::prelude::*;
+use crate::BinaryOperation;
 use crate::{ObjectValue, VMError};
-use std::ops::Add;
+use core::ops::Add;
 
 impl Add for &ObjectValue {
     type Output = ObjectValue;
 
     fn add(self, rhs: Self) -> Self::Output {
+        if let Some(v) = object_operation(BinaryOperation::Add, self, rhs) {
+            return v;
+        }
         match (self, rhs) {
             (ObjectValue::Primitive(a), ObjectValue::Primitive(b)) => (a + b).into(),
             (ObjectValue::Tuple(a), ObjectValue::Tuple(b)) => {
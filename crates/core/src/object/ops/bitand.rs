@@ -1,5 +1,5 @@
 use crate::{ObjectValue, VMError};
-use std::ops::BitAnd;
+use core::ops::BitAnd;
 
 impl BitAnd for &ObjectValue {
     type Output = ObjectValue;
@@ -1,5 +1,5 @@
 use crate::{ObjectValue, VMError};
-use std::ops::BitOr;
+use core::ops::BitOr;
 
 impl BitOr for &ObjectValue {
     type Output = ObjectValue;
@@ -1,5 +1,5 @@
 use crate::{ObjectValue, VMError};
-use std::ops::BitXor;
+use core::ops::BitXor;
 
 impl BitXor for &ObjectValue {
     type Output = ObjectValue;
@@ -1,12 +1,18 @@
+use crate::object::ops::object_operation;
+use crate::prelude::*;
+use crate::BinaryOperation;
 use crate::ObjectValue;
 use crate::ObjectValue::Primitive;
 use crate::{PrimitiveValue, VMError};
-use std::ops::Div;
+use core::ops::Div;
 
 impl Div for &ObjectValue {
     type Output = ObjectValue;
 
     fn div(self, rhs: Self) -> Self::Output {
+        if let Some(v) = object_operation(BinaryOperation::Div, self, rhs) {
+            return v;
+        }
         match (self, rhs) {
             (Primitive(PrimitiveValue::String(a)), Primitive(PrimitiveValue::String(b))) => {
                 let result = a.split(b.as_str());
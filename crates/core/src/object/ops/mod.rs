@@ -11,3 +11,23 @@ mod reverse;
 mod shl;
 mod shr;
 mod sub;
+
+use crate::{BinaryOperation, ObjectValue};
+
+/// Objects get the first chance to handle arithmetic, i.e. `Time::Instant - Duration`
+pub(crate) fn object_operation(
+    operation: BinaryOperation,
+    lhs: &ObjectValue,
+    rhs: &ObjectValue,
+) -> Option<ObjectValue> {
+    match (lhs, rhs) {
+        (ObjectValue::Object(a), b) => {
+            a.binary_operation(operation, b, false).or_else(|| match b {
+                ObjectValue::Object(b) => b.binary_operation(operation, lhs, true),
+                _ => None,
+            })
+        }
+        (a, ObjectValue::Object(b)) => b.binary_operation(operation, a, true),
+        _ => None,
+    }
+}
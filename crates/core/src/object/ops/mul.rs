@@ -1,12 +1,17 @@
+use crate::object::ops::object_operation;
+use crate::BinaryOperation;
 use crate::ObjectValue;
 use crate::ObjectValue::Primitive;
 use crate::{PrimitiveValue, VMError};
-use std::ops::Mul;
+use core::ops::Mul;
 
 impl Mul for &ObjectValue {
     type Output = ObjectValue;
 
     fn mul(self, rhs: Self) -> Self::Output {
+        if let Some(v) = object_operation(BinaryOperation::Mul, self, rhs) {
+            return v;
+        }
         match (self, rhs) {
             (Primitive(PrimitiveValue::String(a)), Primitive(PrimitiveValue::String(b))) => {
                 &ObjectValue::List(vec![a.clone().into()]) * &b.clone().into()
@@ -1,5 +1,5 @@
 use crate::ObjectValue;
-use std::ops::Neg;
+use core::ops::Neg;
 
 impl Neg for &ObjectValue {
     type Output = ObjectValue;
@@ -7,6 +7,11 @@ impl Neg for &ObjectValue {
     fn neg(self) -> Self::Output {
         match self {
             ObjectValue::Primitive(p) => p.neg().into(),
+            #[cfg(feature = "bigint")]
+            ObjectValue::Object(o) if o.is::<crate::BigInt>() => {
+                let big = o.downcast_ref::<crate::BigInt>().expect("checked by is");
+                crate::BigInt::normalize(-&big.0)
+            }
             o => o.clone(),
         }
     }
@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, ObjectValue};
-use std::ops::Not;
+use core::ops::Not;
 
 impl Not for &ObjectValue {
     type Output = ObjectValue;
@@ -1,11 +1,16 @@
+use crate::object::ops::object_operation;
+use crate::BinaryOperation;
 use crate::ObjectValue;
+use core::ops::Rem;
 use log::warn;
-use std::ops::Rem;
 
 impl Rem for &ObjectValue {
     type Output = ObjectValue;
 
     fn rem(self, rhs: Self) -> Self::Output {
+        if let Some(v) = object_operation(BinaryOperation::Rem, self, rhs) {
+            return v;
+        }
         match (self, rhs) {
             (ObjectValue::Primitive(a), ObjectValue::Primitive(b)) => a.rem(b).into(),
             (ObjectValue::Tuple(a), ObjectValue::Tuple(b)) => {
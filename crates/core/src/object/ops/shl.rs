@@ -1,5 +1,5 @@
 use crate::{ObjectValue, VMError};
-use std::ops::Shl;
+use core::ops::Shl;
 
 impl Shl for &ObjectValue {
     type Output = ObjectValue;
@@ -1,5 +1,5 @@
 use crate::{ObjectValue, VMError};
-use std::ops::Shr;
+use core::ops::Shr;
 
 impl Shr for &ObjectValue {
     type Output = ObjectValue;
@@ -1,9 +1,14 @@
+use crate::object::ops::object_operation;
+use crate::BinaryOperation;
 use crate::{ObjectValue, VMError};
-use std::ops::Sub;
+use core::ops::Sub;
 
 impl Sub for &ObjectValue {
     type Output = ObjectValue;
     fn sub(self, other: Self) -> Self::Output {
+        if let Some(v) = object_operation(BinaryOperation::Sub, self, other) {
+            return v;
+        }
         match (self, other) {
             (ObjectValue::Primitive(a), ObjectValue::Primitive(b)) => (a - b).into(),
             (ObjectValue::List(a), ObjectValue::List(b)) => {
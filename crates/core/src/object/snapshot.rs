@@ -1,6 +1,7 @@
+use crate::prelude::*;
 use crate::{ObjectValue, Snapshot, VMError};
-use std::fmt::Display;
-use std::vec::IntoIter;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
 
 impl Snapshot for ObjectValue {
     fn as_bytes(&self) -> Vec<u8> {
@@ -1,6 +1,6 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum BinaryOperation {
     Add,
     Sub,
@@ -22,10 +22,12 @@ pub enum BinaryOperation {
     Lt,
     Lte,
     Elvis,
+    Range,
+    RangeInclusive,
 }
 
 impl Display for BinaryOperation {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             BinaryOperation::Add => write!(f, "+"),
             BinaryOperation::Sub => write!(f, "-"),
@@ -47,6 +49,8 @@ impl Display for BinaryOperation {
             BinaryOperation::Lt => write!(f, "<"),
             BinaryOperation::Lte => write!(f, "<="),
             BinaryOperation::Elvis => write!(f, "?:"),
+            BinaryOperation::Range => write!(f, ".."),
+            BinaryOperation::RangeInclusive => write!(f, "..="),
         }
     }
 }
@@ -1,6 +1,7 @@
+use crate::prelude::*;
 use crate::{BinaryOperation, Snapshot, UnaryOperation, VMError};
-use std::fmt::Display;
-use std::vec::IntoIter;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
 
 impl Snapshot for BinaryOperation {
     fn as_bytes(&self) -> Vec<u8> {
@@ -38,6 +39,8 @@ impl Snapshot for BinaryOperation {
             17 => BinaryOperation::Lt,
             18 => BinaryOperation::Lte,
             19 => BinaryOperation::Elvis,
+            20 => BinaryOperation::Range,
+            21 => BinaryOperation::RangeInclusive,
             b => {
                 return Err(VMError::RuntimeError(format!(
                     "Illegal UnaryOperation byte {b} - {location}"
@@ -1,6 +1,6 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum UnaryOperation {
     Neg,
     Not,
@@ -12,7 +12,7 @@ pub enum UnaryOperation {
 }
 
 impl Display for UnaryOperation {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             UnaryOperation::Neg => write!(f, "-"),
             UnaryOperation::Not => write!(f, "!"),
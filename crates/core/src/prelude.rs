@@ -0,0 +1,67 @@
+// Items of the std prelude that come from alloc, glob imported by modules that use them so they build without std
+
+pub(crate) use alloc::boxed::Box;
+pub(crate) use alloc::string::{String, ToString};
+pub(crate) use alloc::vec::Vec;
+
+/// Float methods std provides, without it they're from libm
+#[cfg(not(feature = "std"))]
+pub(crate) trait FloatMath {
+    fn floor(self) -> Self;
+    fn fract(self) -> Self;
+    fn log(self, base: Self) -> Self;
+    fn log2(self) -> Self;
+    fn log10(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn round(self) -> Self;
+    fn sqrt(self) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+impl FloatMath for f64 {
+    #[inline]
+    fn floor(self) -> Self {
+        libm::floor(self)
+    }
+
+    #[inline]
+    fn fract(self) -> Self {
+        self - libm::trunc(self)
+    }
+
+    #[inline]
+    fn log(self, base: Self) -> Self {
+        libm::log(self) / libm::log(base)
+    }
+
+    #[inline]
+    fn log2(self) -> Self {
+        libm::log2(self)
+    }
+
+    #[inline]
+    fn log10(self) -> Self {
+        libm::log10(self)
+    }
+
+    #[inline]
+    fn powf(self, n: Self) -> Self {
+        libm::pow(self, n)
+    }
+
+    #[inline]
+    fn powi(self, n: i32) -> Self {
+        libm::pow(self, n as f64)
+    }
+
+    #[inline]
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+
+    #[inline]
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+}
@@ -0,0 +1,232 @@
+use crate::prelude::*;
+use crate::{ObjectValue, PrimitiveValue};
+
+/// How `pretty` renders values
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrettyOptions {
+    /// Lists, tuples, & maps longer than this are split across lines, one item per line
+    pub width: usize,
+    /// Spaces per nesting level when a value is split across lines
+    pub indent: usize,
+    /// Strings are quoted so `'1'` and `1` can be told apart, top level strings are quoted too
+    pub quote_strings: bool,
+    /// Collections with more items are truncated with `...`
+    pub max_items: Option<usize>,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            width: 80,
+            indent: 2,
+            quote_strings: true,
+            max_items: None,
+        }
+    }
+}
+
+impl PrettyOptions {
+    /// Always a single line, used where values are shown inline (hovers, debugger variables)
+    pub fn compact() -> Self {
+        PrettyOptions {
+            width: usize::MAX,
+            ..Default::default()
+        }
+    }
+}
+
+/// Renders values the same way everywhere they're shown to users (REPL, assertion failures, debugger),
+/// collections use rigz syntax and are split across lines once they don't fit in `options.width`
+pub fn pretty(value: &ObjectValue, options: &PrettyOptions) -> String {
+    let mut out = String::new();
+    Printer { options }.write(value, 0, 0, &mut out);
+    out
+}
+
+struct Printer<'o> {
+    options: &'o PrettyOptions,
+}
+
+impl Printer<'_> {
+    /// `column` is where the value starts on the current line
+    fn write(&self, value: &ObjectValue, level: usize, column: usize, out: &mut String) {
+        let inline = self.inline(value);
+        let Some((open, close, items)) = self.items(value) else {
+            out.push_str(&inline);
+            return;
+        };
+        if items.is_empty() || column.saturating_add(inline.len()) <= self.options.width {
+            out.push_str(&inline);
+            return;
+        }
+
+        let indent = " ".repeat((level + 1) * self.options.indent);
+        out.push(open);
+        let len = items.len();
+        for (index, item) in items.into_iter().enumerate() {
+            out.push('\n');
+            out.push_str(&indent);
+            match item {
+                Item::Value(key, value) => {
+                    let start = out.len();
+                    if let Some(key) = key {
+                        out.push_str(&key);
+                        out.push_str(" = ");
+                    }
+                    let column = indent.len() + out.len() - start;
+                    self.write(value, level + 1, column, out);
+                }
+                Item::Truncated(remaining) => out.push_str(&format!("...{remaining} more")),
+            }
+            if index + 1 != len {
+                out.push(',');
+            }
+        }
+        out.push('\n');
+        out.push_str(&" ".repeat(level * self.options.indent));
+        out.push(close);
+    }
+
+    fn inline(&self, value: &ObjectValue) -> String {
+        match value {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) if self.options.quote_strings => {
+                quote(s)
+            }
+            ObjectValue::Primitive(p) => p.to_string(),
+            ObjectValue::Object(o) => o.to_string(),
+            _ => {
+                let Some((open, close, items)) = self.items(value) else {
+                    unreachable!("collections always have items")
+                };
+                let items: Vec<_> = items
+                    .into_iter()
+                    .map(|item| match item {
+                        Item::Value(None, v) => self.inline(v),
+                        Item::Value(Some(key), v) => format!("{key} = {}", self.inline(v)),
+                        Item::Truncated(remaining) => format!("...{remaining} more"),
+                    })
+                    .collect();
+                format!("{open}{}{close}", items.join(", "))
+            }
+        }
+    }
+
+    fn items<'v>(&self, value: &'v ObjectValue) -> Option<(char, char, Vec<Item<'v>>)> {
+        let (open, close, items): (_, _, Vec<_>) = match value {
+            ObjectValue::List(values) => ('[', ']', values.iter().map(|v| (None, v)).collect()),
+            ObjectValue::Tuple(values) => ('(', ')', values.iter().map(|v| (None, v)).collect()),
+            ObjectValue::Map(values) => (
+                '{',
+                '}',
+                values
+                    .iter()
+                    .map(|(k, v)| (Some(self.inline(k)), v))
+                    .collect(),
+            ),
+            _ => return None,
+        };
+        let len = items.len();
+        let shown = self.options.max_items.unwrap_or(len).min(len);
+        let mut items: Vec<_> = items
+            .into_iter()
+            .take(shown)
+            .map(|(k, v)| Item::Value(k, v))
+            .collect();
+        if shown < len {
+            items.push(Item::Truncated(len - shown));
+        }
+        Some((open, close, items))
+    }
+}
+
+enum Item<'v> {
+    Value(Option<String>, &'v ObjectValue),
+    Truncated(usize),
+}
+
+/// String literals can't contain escapes, so the first quote that doesn't appear in the string is used.
+/// Control characters are escaped to keep the value on one line.
+fn quote(s: &str) -> String {
+    let quote = ['\'', '"', '`']
+        .into_iter()
+        .find(|q| !s.contains(*q))
+        .unwrap_or('\'');
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push(quote);
+    for c in s.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push(quote);
+    quoted
+}
+
+#[cfg(test)]
+pub mod pretty_tests {
+    use crate::{pretty, ObjectValue, PrettyOptions};
+    use indexmap::IndexMap;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn primitives() {
+        let options = PrettyOptions::default();
+        assert_eq!(pretty(&1.into(), &options), "1");
+        assert_eq!(pretty(&ObjectValue::default(), &options), "none");
+        assert_eq!(pretty(&"a".into(), &options), "'a'");
+        assert_eq!(pretty(&"it's".into(), &options), "\"it's\"");
+        assert_eq!(pretty(&"a\nb".into(), &options), "'a\\nb'");
+        let unquoted = PrettyOptions {
+            quote_strings: false,
+            ..Default::default()
+        };
+        assert_eq!(pretty(&"a".into(), &unquoted), "a");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn collections_inline() {
+        let mut map = IndexMap::new();
+        map.insert("a".into(), ObjectValue::Tuple(vec![1.into(), 2.into()]));
+        let value = ObjectValue::List(vec![1.into(), "b".into(), ObjectValue::Map(map)]);
+        assert_eq!(
+            pretty(&value, &PrettyOptions::default()),
+            "[1, 'b', {'a' = (1, 2)}]"
+        );
+        assert_eq!(
+            pretty(&ObjectValue::List(vec![]), &PrettyOptions::default()),
+            "[]"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn collections_split_when_too_wide() {
+        let mut map = IndexMap::new();
+        map.insert(
+            "key".into(),
+            ObjectValue::List(vec![1.into(), 2.into(), 3.into()]),
+        );
+        map.insert("other".into(), 4.into());
+        let value = ObjectValue::List(vec![ObjectValue::Map(map), 5.into()]);
+        let options = PrettyOptions {
+            width: 24,
+            ..Default::default()
+        };
+        assert_eq!(
+            pretty(&value, &options),
+            "[\n  {\n    'key' = [1, 2, 3],\n    'other' = 4\n  },\n  5\n]"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn max_items() {
+        let value = ObjectValue::List((0..5).map(|i: i64| i.into()).collect());
+        let options = PrettyOptions {
+            max_items: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(pretty(&value, &options), "[0, 1, ...3 more]");
+    }
+}
@@ -1,9 +1,8 @@
-use crate::PrimitiveValue;
+use crate::prelude::*;
+use crate::{Diagnostic, ErrorCode, PrimitiveValue, Shared};
+use core::error::Error;
+use core::fmt::{Display, Formatter};
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
-use std::error::Error;
-use std::fmt::{Display, Formatter};
-use std::rc::Rc;
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum VMError {
@@ -17,14 +16,38 @@ pub enum VMError {
     InvalidModule(String),
     InvalidModuleFunction(String),
     LifecycleError(String),
+    /// `VMOptions::max_instructions` was reached, every instruction after this fails until the budget is refilled
+    BudgetExceeded(String),
+    /// Error with the call frames that were active when it was created, innermost frame first
+    Traceback(Box<VMError>, Vec<TraceFrame>),
+    /// Error with an explicit code and optional span & help text
+    Diagnostic(Box<Diagnostic>),
+}
+
+/// Call frame captured by `VMOptions::enable_traceback`
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TraceFrame {
+    pub name: String,
+    pub scope_id: usize,
+    /// Only available when the program was parsed with line tables
+    pub line: Option<usize>,
+}
+
+impl Display for TraceFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.line {
+            None => write!(f, "{} (scope {})", self.name, self.scope_id),
+            Some(line) => write!(f, "{} (scope {}), line {line}", self.name, self.scope_id),
+        }
+    }
 }
 
 impl Error for VMError {}
 
-impl From<VMError> for Rc<RefCell<PrimitiveValue>> {
+impl From<VMError> for Shared<PrimitiveValue> {
     #[inline]
     fn from(value: VMError) -> Self {
-        Rc::new(RefCell::new(value.into()))
+        Shared::new(value.into())
     }
 }
 
@@ -36,7 +59,7 @@ impl From<&VMError> for PrimitiveValue {
 }
 
 impl Display for VMError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             VMError::RuntimeError(m) => write!(f, "{m}"),
             VMError::EmptyStack(m) => write!(f, "Empty Register: {m}"),
@@ -48,6 +71,9 @@ impl Display for VMError {
             VMError::InvalidModuleFunction(m) => write!(f, "Invalid Module Function: {m}"),
             VMError::LifecycleError(m) => write!(f, "Lifecycle Error: {m}"),
             VMError::TimeoutError(m) => write!(f, "Timeout Error: {m}"),
+            VMError::BudgetExceeded(m) => write!(f, "Budget Exceeded: {m}"),
+            VMError::Traceback(e, _) => write!(f, "{e}"),
+            VMError::Diagnostic(d) => write!(f, "{d}"),
         }
     }
 }
@@ -61,7 +87,125 @@ impl VMError {
         VMError::InvalidModuleFunction(format!("Function {func} does not exist"))
     }
 
+    pub fn diagnostic(diagnostic: Diagnostic) -> Self {
+        VMError::Diagnostic(Box::new(diagnostic))
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            VMError::TimeoutError(_) => ErrorCode::Timeout,
+            VMError::RuntimeError(_) => ErrorCode::Runtime,
+            VMError::EmptyStack(_) => ErrorCode::EmptyStack,
+            VMError::ConversionError(_) => ErrorCode::Conversion,
+            VMError::ScopeDoesNotExist(_) => ErrorCode::ScopeDoesNotExist,
+            VMError::UnsupportedOperation(_) => ErrorCode::UnsupportedOperation,
+            VMError::VariableDoesNotExist(_) => ErrorCode::VariableDoesNotExist,
+            VMError::InvalidModule(_) => ErrorCode::InvalidModule,
+            VMError::InvalidModuleFunction(_) => ErrorCode::InvalidModuleFunction,
+            VMError::LifecycleError(_) => ErrorCode::Lifecycle,
+            VMError::BudgetExceeded(_) => ErrorCode::BudgetExceeded,
+            VMError::Traceback(e, _) => e.code(),
+            VMError::Diagnostic(d) => d.code,
+        }
+    }
+
+    /// Code, message, & help for the error, the message matches Display
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self.untraced() {
+            VMError::Diagnostic(d) => d.as_ref().clone(),
+            e => Diagnostic::new(e.code(), e.to_string()),
+        }
+    }
+
     pub fn todo<T: Display>(message: T) -> Self {
         VMError::UnsupportedOperation(format!("Not implemented - {message}"))
     }
+
+    /// Call frames captured when the error was created, empty unless tracebacks are enabled
+    pub fn traceback(&self) -> &[TraceFrame] {
+        match self {
+            VMError::Traceback(_, frames) => frames,
+            _ => &[],
+        }
+    }
+
+    /// The error without its traceback
+    pub fn untraced(&self) -> &VMError {
+        match self {
+            VMError::Traceback(e, _) => e.untraced(),
+            e => e,
+        }
+    }
+
+    /// Errors that already have a traceback keep the original frames
+    pub fn with_traceback(self, frames: Vec<TraceFrame>) -> Self {
+        match self {
+            VMError::Traceback(..) => self,
+            e if frames.is_empty() => e,
+            e => VMError::Traceback(Box::new(e), frames),
+        }
+    }
+
+    /// Error message followed by one line per frame, innermost frame first
+    pub fn pretty_traceback(&self) -> String {
+        let mut result = self.to_string();
+        for frame in self.traceback() {
+            result.push_str("\n    at ");
+            result.push_str(&frame.to_string());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+pub mod error_tests {
+    use crate::{Diagnostic, ErrorCode, TraceFrame, VMError};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn traceback() {
+        let frames = vec![
+            TraceFrame {
+                name: "foo".to_string(),
+                scope_id: 1,
+                line: Some(3),
+            },
+            TraceFrame {
+                name: "main".to_string(),
+                scope_id: 0,
+                line: None,
+            },
+        ];
+        let e = VMError::RuntimeError("failed".to_string()).with_traceback(frames.clone());
+        assert_eq!(e.traceback(), frames.as_slice());
+        assert_eq!(e.untraced(), &VMError::RuntimeError("failed".to_string()));
+        assert_eq!(e.to_string(), "failed");
+        assert_eq!(
+            e.pretty_traceback(),
+            "failed\n    at foo (scope 1), line 3\n    at main (scope 0)"
+        );
+        assert_eq!(e.clone().with_traceback(vec![]), e);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn codes() {
+        let e = VMError::VariableDoesNotExist("a".to_string());
+        assert_eq!(e.code(), ErrorCode::VariableDoesNotExist);
+        assert_eq!(
+            e.to_diagnostic(),
+            Diagnostic::new(
+                ErrorCode::VariableDoesNotExist,
+                "Variable Does Not Exist: a"
+            )
+        );
+        let d = Diagnostic::new(ErrorCode::Conversion, "bad value").with_help("use to_i");
+        let e = VMError::diagnostic(d.clone()).with_traceback(vec![TraceFrame {
+            name: "main".to_string(),
+            scope_id: 0,
+            line: None,
+        }]);
+        assert_eq!(e.code(), ErrorCode::Conversion);
+        assert_eq!(e.to_string(), "bad value");
+        assert_eq!(e.to_diagnostic(), d);
+    }
 }
@@ -5,52 +5,57 @@ mod ops;
 mod snapshot;
 mod value_range;
 
-pub use error::VMError;
-pub use value_range::ValueRange;
-
-use std::cell::RefCell;
-
-use crate::{impl_from, AsPrimitive, Number, RigzType, WithTypeInfo};
-use indexmap::IndexMap;
-use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::fmt::{Debug, Display, Formatter};
-use std::hash::{Hash, Hasher};
-use std::rc::Rc;
-
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub use error::{TraceFrame, VMError};
+pub use value_range::{Date, ValueRange};
+
+use crate::prelude::*;
+use crate::IndexMap;
+use crate::{impl_from, AsPrimitive, Interned, Number, RigzType, Shared, WithTypeInfo};
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use serde::Serialize;
+
+#[derive(Clone, Debug, Default, Serialize)]
 #[serde(untagged)]
 pub enum PrimitiveValue {
     #[default]
     None,
     Bool(bool),
     Number(Number),
-    String(String),
+    String(Interned),
     Range(ValueRange),
     Error(VMError),
     // todo create dedicated object value to avoid map usage everywhere, might need to be a trait. Create to_o method for value
     Type(RigzType),
 }
 
-impl From<PrimitiveValue> for Rc<RefCell<PrimitiveValue>> {
+impl From<PrimitiveValue> for Shared<PrimitiveValue> {
     #[inline]
     fn from(value: PrimitiveValue) -> Self {
-        Rc::new(RefCell::new(value))
+        Shared::new(value)
     }
 }
 
 impl_from! {
     bool, PrimitiveValue, PrimitiveValue::Bool;
     VMError, PrimitiveValue, PrimitiveValue::Error;
-    String, PrimitiveValue, PrimitiveValue::String;
+    Interned, PrimitiveValue, PrimitiveValue::String;
     ValueRange, PrimitiveValue, PrimitiveValue::Range;
     RigzType, PrimitiveValue, PrimitiveValue::Type;
 }
 
+impl From<String> for PrimitiveValue {
+    #[inline]
+    fn from(value: String) -> Self {
+        PrimitiveValue::String(Interned::owned(value))
+    }
+}
+
 impl From<&'_ str> for PrimitiveValue {
     #[inline]
     fn from(value: &'_ str) -> Self {
-        PrimitiveValue::String(value.to_string())
+        PrimitiveValue::String(Interned::owned(value.to_string()))
     }
 }
 
@@ -68,6 +73,17 @@ impl From<()> for PrimitiveValue {
     }
 }
 
+impl PrimitiveValue {
+    /// Number arithmetic, errors like Int overflow become `PrimitiveValue::Error`
+    #[inline]
+    pub(crate) fn number(result: Result<Number, VMError>) -> Self {
+        match result {
+            Ok(n) => PrimitiveValue::Number(n),
+            Err(e) => PrimitiveValue::Error(e),
+        }
+    }
+}
+
 impl WithTypeInfo for PrimitiveValue {
     fn rigz_type(&self) -> RigzType {
         match self {
@@ -163,10 +179,10 @@ impl AsPrimitive<PrimitiveValue> for PrimitiveValue {
 
     fn as_string(&mut self) -> Result<&mut String, VMError> {
         if let PrimitiveValue::String(m) = self {
-            return Ok(m);
+            return Ok(m.make_mut());
         }
 
-        *self = PrimitiveValue::String(self.to_string());
+        *self = self.to_string().into();
         self.as_string()
     }
 
@@ -188,7 +204,7 @@ impl AsPrimitive<PrimitiveValue> for PrimitiveValue {
     fn as_float(&mut self) -> Result<&mut f64, VMError> {
         if let PrimitiveValue::Number(m) = self {
             return match m {
-                Number::Int(_) => {
+                Number::Int(_) | Number::Decimal(_) => {
                     *m = Number::Float(m.to_float());
                     let Number::Float(f) = m else { unreachable!() };
                     Ok(f)
@@ -205,7 +221,7 @@ impl AsPrimitive<PrimitiveValue> for PrimitiveValue {
         if let PrimitiveValue::Number(m) = self {
             return match m {
                 Number::Int(i) => Ok(i),
-                Number::Float(_) => {
+                Number::Float(_) | Number::Decimal(_) => {
                     *m = Number::Int(m.to_int());
                     let Number::Int(i) = m else { unreachable!() };
                     Ok(i)
@@ -226,18 +242,18 @@ impl PartialOrd for PrimitiveValue {
     }
 }
 
+/// Strict total order, independent of the loose `==`: values are ordered by kind (error, type, none, bool, number,
+/// range, string) then by value
 impl Ord for PrimitiveValue {
     fn cmp(&self, other: &Self) -> Ordering {
-        if self.eq(other) {
-            return Ordering::Equal;
-        }
-
         match (self, other) {
+            (PrimitiveValue::Error(a), PrimitiveValue::Error(b)) => a.cmp(b),
             (PrimitiveValue::Error(_), _) => Ordering::Less,
             (_, PrimitiveValue::Error(_)) => Ordering::Greater,
             (PrimitiveValue::Type(a), PrimitiveValue::Type(b)) => a.cmp(b),
             (PrimitiveValue::Type(_), _) => Ordering::Less,
             (_, PrimitiveValue::Type(_)) => Ordering::Greater,
+            (PrimitiveValue::None, PrimitiveValue::None) => Ordering::Equal,
             (PrimitiveValue::None, _) => Ordering::Less,
             (_, PrimitiveValue::None) => Ordering::Greater,
             (PrimitiveValue::Bool(a), PrimitiveValue::Bool(b)) => a.cmp(b),
@@ -281,7 +297,7 @@ impl PrimitiveValue {
 }
 
 impl Display for PrimitiveValue {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             PrimitiveValue::None => write!(f, "none"),
             // todo dedicated to_string instead of debug
@@ -309,8 +325,25 @@ impl Hash for PrimitiveValue {
     }
 }
 
+/// Values of different kinds are never equal, consistent with `Ord`. The `==` operator uses `loose_eq` instead
 impl PartialEq for PrimitiveValue {
     fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PrimitiveValue::None, PrimitiveValue::None) => true,
+            (PrimitiveValue::Error(a), PrimitiveValue::Error(b)) => *a == *b,
+            (PrimitiveValue::Type(a), PrimitiveValue::Type(b)) => *a == *b,
+            (PrimitiveValue::Bool(a), PrimitiveValue::Bool(b)) => a == b,
+            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => a == b,
+            (PrimitiveValue::Range(a), PrimitiveValue::Range(b)) => a == b,
+            (PrimitiveValue::String(a), PrimitiveValue::String(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl PrimitiveValue {
+    /// Equality of the `==` operator, values of different kinds are converted, i.e. `none == 0` & `1 == "1"`
+    pub fn loose_eq(&self, other: &Self) -> bool {
         match (self, other) {
             (PrimitiveValue::None, PrimitiveValue::None) => true,
             (PrimitiveValue::Error(a), PrimitiveValue::Error(b)) => *a == *b,
@@ -357,21 +390,18 @@ pub mod value_tests {
 
     #[wasm_bindgen_test(unsupported = test)]
     fn value_eq() {
-        assert_eq!(PrimitiveValue::None, PrimitiveValue::None);
-        assert_eq!(PrimitiveValue::None, PrimitiveValue::Bool(false));
-        assert_eq!(PrimitiveValue::None, PrimitiveValue::Number(Number::Int(0)));
-        assert_eq!(
-            PrimitiveValue::None,
-            PrimitiveValue::Number(Number::Float(0.0))
-        );
-        assert_eq!(PrimitiveValue::None, PrimitiveValue::String(String::new()));
-        assert_eq!(
-            PrimitiveValue::Bool(false),
-            PrimitiveValue::String(String::new())
-        );
-        assert_eq!(
-            PrimitiveValue::Number(Number::Int(0)),
-            PrimitiveValue::String(String::new())
-        );
+        let none = PrimitiveValue::None;
+        let zero = PrimitiveValue::Number(Number::Int(0));
+        let empty = PrimitiveValue::String(String::new().into());
+        assert_eq!(none, PrimitiveValue::None);
+        assert!(none.loose_eq(&PrimitiveValue::Bool(false)));
+        assert!(none.loose_eq(&zero));
+        assert!(none.loose_eq(&PrimitiveValue::Number(Number::Float(0.0))));
+        assert!(none.loose_eq(&empty));
+        assert!(PrimitiveValue::Bool(false).loose_eq(&empty));
+        assert!(zero.loose_eq(&empty));
+        // different kinds are never equal outside of `==`
+        assert_ne!(none, PrimitiveValue::Bool(false));
+        assert_ne!(zero, empty);
     }
 }
@@ -1,5 +1,6 @@
+use crate::prelude::*;
 use crate::{AsPrimitive, PrimitiveValue, VMError};
-use std::ops::Add;
+use core::ops::Add;
 
 impl Add for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -13,22 +14,22 @@ impl Add for &PrimitiveValue {
             ),
             (PrimitiveValue::None, v) | (v, PrimitiveValue::None) => v.clone(),
             (PrimitiveValue::Bool(a), PrimitiveValue::Bool(b)) => PrimitiveValue::Bool(a | b),
-            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::Number(a + b),
+            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::number(a + b),
             (PrimitiveValue::Number(a), PrimitiveValue::String(b)) => match b.parse() {
                 Err(_) => {
                     let mut res = a.to_string();
                     res.push_str(b.as_str());
-                    PrimitiveValue::String(res)
+                    PrimitiveValue::from(res)
                 }
-                Ok(r) => PrimitiveValue::Number(a + &r),
+                Ok(r) => PrimitiveValue::number(a + &r),
             },
             (PrimitiveValue::String(a), PrimitiveValue::Number(b)) => match a.parse() {
                 Err(_) => {
                     let mut res = a.to_string();
                     res.push_str(b.to_string().as_str());
-                    PrimitiveValue::String(res)
+                    PrimitiveValue::from(res)
                 }
-                Ok(r) => PrimitiveValue::Number(b + &r),
+                Ok(r) => PrimitiveValue::number(b + &r),
             },
             (PrimitiveValue::Number(a), PrimitiveValue::Range(r))
             | (PrimitiveValue::Range(r), PrimitiveValue::Number(a)) => match r + a {
@@ -46,9 +47,9 @@ impl Add for &PrimitiveValue {
                 VMError::UnsupportedOperation(format!("Cannot perform {a} + {b}")).into()
             }
             (PrimitiveValue::String(a), PrimitiveValue::String(b)) => {
-                let mut result = a.clone();
+                let mut result = a.to_string();
                 result.push_str(b.as_str());
-                PrimitiveValue::String(result)
+                PrimitiveValue::from(result)
             }
             // todo should "a" + true = "atrue" or true
             (PrimitiveValue::Bool(a), b) | (b, PrimitiveValue::Bool(a)) => {
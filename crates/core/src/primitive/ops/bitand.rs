@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, PrimitiveValue, VMError};
-use std::ops::BitAnd;
+use core::ops::BitAnd;
 
 impl BitAnd for &PrimitiveValue {
     type Output = PrimitiveValue;
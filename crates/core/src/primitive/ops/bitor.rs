@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, PrimitiveValue, VMError};
-use std::ops::BitOr;
+use core::ops::BitOr;
 
 impl BitOr for &PrimitiveValue {
     type Output = PrimitiveValue;
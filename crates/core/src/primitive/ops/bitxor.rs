@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, PrimitiveValue, VMError};
-use std::ops::BitXor;
+use core::ops::BitXor;
 
 impl BitXor for &PrimitiveValue {
     type Output = PrimitiveValue;
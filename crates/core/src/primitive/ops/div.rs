@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, PrimitiveValue, VMError};
-use std::ops::Div;
+use core::ops::Div;
 
 impl Div for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -29,11 +29,11 @@ impl Div for &PrimitiveValue {
                     )));
                 }
 
-                PrimitiveValue::Number(a / b)
+                PrimitiveValue::number(a / b)
             }
             (PrimitiveValue::Number(a), PrimitiveValue::String(b)) => match b.parse() {
                 Err(_) => VMError::UnsupportedOperation(format!("{} / {}", a, b)).to_value(),
-                Ok(r) => PrimitiveValue::Number(a / &r),
+                Ok(r) => PrimitiveValue::number(a / &r),
             },
             (PrimitiveValue::Number(a), PrimitiveValue::Range(r))
             | (PrimitiveValue::Range(r), PrimitiveValue::Number(a)) => match r / a {
@@ -1,5 +1,7 @@
+#[cfg(not(feature = "std"))]
+use crate::prelude::FloatMath;
 use crate::{AsPrimitive, Number, PrimitiveValue, VMError};
-use std::ops::Mul;
+use core::ops::Mul;
 
 impl Mul for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -18,7 +20,7 @@ impl Mul for &PrimitiveValue {
             (PrimitiveValue::Bool(a), PrimitiveValue::Bool(b)) => PrimitiveValue::Bool(a | b),
             (PrimitiveValue::Bool(a), b) => PrimitiveValue::Bool(a | b.to_bool()),
             (b, PrimitiveValue::Bool(a)) => PrimitiveValue::Bool(a | b.to_bool()),
-            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::Number(a * b),
+            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::number(a * b),
             (PrimitiveValue::Number(a), PrimitiveValue::String(b))
             | (PrimitiveValue::String(b), PrimitiveValue::Number(a)) => match b.parse() {
                 Err(_) => {
@@ -32,15 +34,16 @@ impl Mul for &PrimitiveValue {
 
                     let s = match a {
                         Number::Int(_) => b.repeat(a.to_usize().unwrap()),
-                        Number::Float(f) => {
+                        Number::Float(_) | Number::Decimal(_) => {
+                            let f = a.to_float();
                             let mut result = b.repeat(a.to_usize().unwrap());
                             result.push_str(&b[..(f.fract() * b.len() as f64) as usize]);
                             result
                         }
                     };
-                    PrimitiveValue::String(s)
+                    PrimitiveValue::from(s)
                 }
-                Ok(r) => PrimitiveValue::Number(a * &r),
+                Ok(r) => PrimitiveValue::number(a * &r),
             },
             (PrimitiveValue::Number(a), PrimitiveValue::Range(r))
             | (PrimitiveValue::Range(r), PrimitiveValue::Number(a)) => match r * a {
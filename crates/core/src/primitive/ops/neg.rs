@@ -1,5 +1,5 @@
 use crate::PrimitiveValue;
-use std::ops::Neg;
+use core::ops::Neg;
 
 impl Neg for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -9,7 +9,7 @@ impl Neg for &PrimitiveValue {
         match self {
             PrimitiveValue::None => PrimitiveValue::None,
             PrimitiveValue::Bool(b) => PrimitiveValue::Bool(!b),
-            PrimitiveValue::Number(n) => PrimitiveValue::Number(-n),
+            PrimitiveValue::Number(n) => PrimitiveValue::number(-n),
             PrimitiveValue::Range(n) => PrimitiveValue::Range(-n),
             v => v.clone(),
         }
@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, PrimitiveValue};
-use std::ops::Not;
+use core::ops::Not;
 
 impl Not for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -1,6 +1,6 @@
 use crate::{AsPrimitive, PrimitiveValue, VMError};
+use core::ops::Rem;
 use log::warn;
-use std::ops::Rem;
 
 impl Rem for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -19,10 +19,10 @@ impl Rem for &PrimitiveValue {
             (PrimitiveValue::Bool(a), PrimitiveValue::Bool(b)) => PrimitiveValue::Bool(a | b),
             (PrimitiveValue::Bool(a), b) => PrimitiveValue::Bool(a | b.to_bool()),
             (b, PrimitiveValue::Bool(a)) => PrimitiveValue::Bool(a | b.to_bool()),
-            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::Number(a % b),
+            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::number(a % b),
             (PrimitiveValue::Number(a), PrimitiveValue::String(b)) => match b.parse() {
                 Err(_) => VMError::UnsupportedOperation(format!("{} % {}", a, b)).into(),
-                Ok(r) => PrimitiveValue::Number(a % &r),
+                Ok(r) => PrimitiveValue::number(a % &r),
             },
             (a, b) => {
                 warn!("{a} % {b} not implemented, defaulting to a - b");
@@ -1,3 +1,4 @@
+use crate::prelude::*;
 use crate::{PrimitiveValue, Reverse};
 
 impl Reverse for PrimitiveValue {
@@ -8,8 +9,8 @@ impl Reverse for PrimitiveValue {
         match self {
             PrimitiveValue::Number(n) => PrimitiveValue::Number(n.reverse()),
             PrimitiveValue::String(s) => {
-                let s = s.chars().rev().collect();
-                PrimitiveValue::String(s)
+                let s: String = s.chars().rev().collect();
+                PrimitiveValue::from(s)
             }
             v => v.clone(),
         }
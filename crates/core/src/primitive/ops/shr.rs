@@ -1,5 +1,6 @@
+use crate::prelude::*;
 use crate::{Number, PrimitiveValue, VMError};
-use std::ops::Shr;
+use core::ops::Shr;
 
 impl Shr for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -24,17 +25,17 @@ impl Shr for &PrimitiveValue {
             }
             (&PrimitiveValue::Bool(lhs), PrimitiveValue::Number(rhs)) => {
                 if lhs {
-                    PrimitiveValue::Number(&Number::Int(1) >> rhs)
+                    PrimitiveValue::number(&Number::Int(1) >> rhs)
                 } else {
                     0.into()
                 }
             }
             (PrimitiveValue::Number(lhs), PrimitiveValue::Number(rhs)) => {
-                PrimitiveValue::Number(lhs >> rhs)
+                PrimitiveValue::number(lhs >> rhs)
             }
             (PrimitiveValue::Number(a), PrimitiveValue::String(b)) => match b.parse() {
                 Err(_) => VMError::UnsupportedOperation(format!("{} >> {}", a, b)).to_value(),
-                Ok(r) => PrimitiveValue::Number(a >> &r),
+                Ok(r) => PrimitiveValue::number(a >> &r),
             },
             (PrimitiveValue::String(lhs), PrimitiveValue::Number(rhs)) => {
                 let lhs = lhs.as_str();
@@ -43,12 +44,12 @@ impl Shr for &PrimitiveValue {
                 } else {
                     lhs[..=rhs.to_usize().unwrap()].to_string()
                 };
-                PrimitiveValue::String(s)
+                PrimitiveValue::from(s)
             }
             (PrimitiveValue::String(lhs), PrimitiveValue::String(rhs)) => {
-                let mut res = rhs.clone();
+                let mut res = rhs.to_string();
                 res.push_str(lhs.as_str());
-                PrimitiveValue::String(res)
+                PrimitiveValue::from(res)
             }
             (lhs, rhs) => {
                 VMError::UnsupportedOperation(format!("Not supported: {lhs} >> {rhs}")).into()
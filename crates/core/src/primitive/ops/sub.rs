@@ -1,5 +1,5 @@
 use crate::{AsPrimitive, PrimitiveValue, VMError};
-use std::ops::Sub;
+use core::ops::Sub;
 
 impl Sub for &PrimitiveValue {
     type Output = PrimitiveValue;
@@ -18,10 +18,10 @@ impl Sub for &PrimitiveValue {
             (PrimitiveValue::Bool(a), PrimitiveValue::Bool(b)) => PrimitiveValue::Bool(a | b),
             (PrimitiveValue::Bool(a), b) => PrimitiveValue::Bool(a | b.to_bool()),
             (b, PrimitiveValue::Bool(a)) => PrimitiveValue::Bool(a | b.to_bool()),
-            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::Number(a - b),
+            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => PrimitiveValue::number(a - b),
             (PrimitiveValue::Number(a), PrimitiveValue::String(b)) => match b.parse() {
                 Err(_) => VMError::UnsupportedOperation(format!("{} - {}", a, b)).to_value(),
-                Ok(r) => PrimitiveValue::Number(a / &r),
+                Ok(r) => PrimitiveValue::number(a / &r),
             },
             (PrimitiveValue::Number(a), PrimitiveValue::Range(r))
             | (PrimitiveValue::Range(r), PrimitiveValue::Number(a)) => match r - a {
@@ -40,7 +40,7 @@ impl Sub for &PrimitiveValue {
             },
             (PrimitiveValue::String(a), PrimitiveValue::String(b)) => {
                 let result = a.replace(b.as_str(), "");
-                PrimitiveValue::String(result)
+                PrimitiveValue::from(result)
             }
             (lhs, rhs) => {
                 VMError::UnsupportedOperation(format!("Not supported: {lhs} - {rhs}")).into()
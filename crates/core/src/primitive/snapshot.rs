@@ -1,7 +1,8 @@
-use crate::{Number, PrimitiveValue, Snapshot, VMError};
+use crate::prelude::*;
+use crate::{Decimal, Number, PrimitiveValue, Snapshot, VMError};
+use alloc::vec::IntoIter;
+use core::fmt::Display;
 use itertools::Itertools;
-use std::fmt::Display;
-use std::vec::IntoIter;
 
 impl Snapshot for PrimitiveValue {
     fn as_bytes(&self) -> Vec<u8> {
@@ -12,17 +13,22 @@ impl Snapshot for PrimitiveValue {
                 res.extend(b.as_bytes());
                 res
             }
+            PrimitiveValue::Number(Number::Decimal(d)) => {
+                let mut res = vec![8];
+                res.extend(d.serialize());
+                res
+            }
             PrimitiveValue::Number(n) => {
                 let mut res = match n {
                     Number::Int(_) => vec![2],
-                    Number::Float(_) => vec![3],
+                    _ => vec![3],
                 };
                 res.extend(n.to_bytes());
                 res
             }
             PrimitiveValue::String(s) => {
                 let mut res = vec![4];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             PrimitiveValue::Range(r) => {
@@ -81,6 +87,17 @@ impl Snapshot for PrimitiveValue {
             5 => PrimitiveValue::Range(Snapshot::from_bytes(bytes, location)?),
             6 => PrimitiveValue::Error(Snapshot::from_bytes(bytes, location)?),
             7 => PrimitiveValue::Type(Snapshot::from_bytes(bytes, location)?),
+            8 => {
+                let b = match bytes.next_array() {
+                    None => {
+                        return Err(VMError::RuntimeError(format!(
+                            "Missing Number::Decimal bytes {location}"
+                        )))
+                    }
+                    Some(s) => s,
+                };
+                PrimitiveValue::Number(Number::Decimal(Decimal::deserialize(b)))
+            }
             b => {
                 return Err(VMError::RuntimeError(format!(
                     "Illegal Value byte {b} - {location}"
@@ -0,0 +1,127 @@
+use crate::prelude::*;
+use core::fmt::{Display, Formatter};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Calendar day in the proleptic Gregorian calendar, written as an ISO 8601 date (`YYYY-MM-DD`)
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    // since 1970-01-01
+    days: i64,
+}
+
+impl Date {
+    #[inline]
+    pub fn from_days(days: i64) -> Self {
+        Date { days }
+    }
+
+    /// Day of a unix timestamp in milliseconds, UTC
+    #[inline]
+    pub fn from_millis(millis: i64) -> Self {
+        Date::from_days(millis.div_euclid(MILLIS_PER_DAY))
+    }
+
+    /// Days since 1970-01-01
+    #[inline]
+    pub fn days(&self) -> i64 {
+        self.days
+    }
+
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 {
+            return None;
+        }
+        // Howard Hinnant's days_from_civil
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let date = Date::from_days(era * 146_097 + doe - 719_468);
+        // days past the end of the month roll into the next one
+        (date.ymd() == (year, month, day)).then_some(date)
+    }
+
+    pub fn ymd(&self) -> (i64, u32, u32) {
+        // Howard Hinnant's civil_from_days
+        let z = self.days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        (year, month, day)
+    }
+
+    /// `YYYY-MM-DD`, returns None for anything else
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut parts = input.splitn(3, '-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if !digits(year) || !digits(month) || !digits(day) {
+            return None;
+        }
+        Date::from_ymd(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Date::parse(&s).ok_or_else(|| {
+            serde::de::Error::custom(format!("Invalid date {s}, expected YYYY-MM-DD"))
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod date_tests {
+    use crate::Date;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn round_trip() {
+        for days in [-719_468, -1, 0, 19_782, 2_932_896] {
+            let date = Date::from_days(days);
+            assert_eq!(Date::parse(&date.to_string()), Some(date), "{date}");
+        }
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::parse("2024-02-29").map(|d| d.days()), Some(19_782));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn invalid_dates() {
+        for input in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-1-01",
+            "24-01-01",
+            "a",
+        ] {
+            assert_eq!(Date::parse(input), None, "{input}");
+        }
+    }
+}
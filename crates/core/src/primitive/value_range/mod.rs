@@ -1,17 +1,22 @@
+mod date;
 mod ops;
 
-use crate::{impl_from, PrimitiveValue};
-use indexmap::IndexMap;
+use crate::prelude::*;
+use crate::IndexMap;
+use crate::{impl_from, PrimitiveValue, VMError};
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter};
+use core::ops::{Neg, Range};
+pub use date::Date;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::fmt::{Display, Formatter};
-use std::ops::{Neg, Range};
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ValueRange {
     Int(Range<i64>),
     Char(Range<char>),
+    /// Iterates each day, values are ISO 8601 dates
+    Date(Range<Date>),
 }
 
 fn range_compare<Idx: PartialOrd>(a: &Range<Idx>, b: &Range<Idx>) -> Ordering {
@@ -24,6 +29,14 @@ fn range_compare<Idx: PartialOrd>(a: &Range<Idx>, b: &Range<Idx>) -> Ordering {
     }
 }
 
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
 impl PartialOrd for ValueRange {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -39,8 +52,11 @@ impl Ord for ValueRange {
         match (self, other) {
             (ValueRange::Int(a), ValueRange::Int(b)) => range_compare(a, b),
             (ValueRange::Char(a), ValueRange::Char(b)) => range_compare(a, b),
-            (ValueRange::Int(_), ValueRange::Char(_)) => Ordering::Less,
-            (ValueRange::Char(_), ValueRange::Int(_)) => Ordering::Greater,
+            (ValueRange::Date(a), ValueRange::Date(b)) => range_compare(a, b),
+            (ValueRange::Int(_), _) => Ordering::Less,
+            (_, ValueRange::Int(_)) => Ordering::Greater,
+            (ValueRange::Char(_), _) => Ordering::Less,
+            (_, ValueRange::Char(_)) => Ordering::Greater,
         }
     }
 }
@@ -55,17 +71,69 @@ impl Neg for &ValueRange {
                 end: i.end,
             }),
             ValueRange::Char(a) => ValueRange::Char(a.clone()),
+            ValueRange::Date(a) => ValueRange::Date(a.clone()),
         }
     }
 }
 
 impl ValueRange {
+    /// `start..end` or `start..=end`, numbers create an Int range, single characters a Char range,
+    /// and `YYYY-MM-DD` strings a Date range
+    pub fn new(
+        start: &PrimitiveValue,
+        end: &PrimitiveValue,
+        inclusive: bool,
+    ) -> Result<ValueRange, VMError> {
+        let invalid = || {
+            VMError::UnsupportedOperation(format!(
+                "Cannot create range from {start} to {end}, expected numbers, characters, or dates (YYYY-MM-DD)"
+            ))
+        };
+        match (start, end) {
+            (PrimitiveValue::Number(a), PrimitiveValue::Number(b)) => {
+                let end = b
+                    .to_int()
+                    .checked_add(inclusive as i64)
+                    .ok_or_else(invalid)?;
+                Ok(ValueRange::Int(a.to_int()..end))
+            }
+            (PrimitiveValue::String(a), PrimitiveValue::String(b)) => {
+                match (single_char(a), single_char(b)) {
+                    (Some(a), Some(b)) => {
+                        let end = if inclusive {
+                            // skips the surrogate range
+                            (b as u32 + 1..=char::MAX as u32)
+                                .find_map(char::from_u32)
+                                .ok_or_else(invalid)?
+                        } else {
+                            b
+                        };
+                        Ok(ValueRange::Char(a..end))
+                    }
+                    _ => match (Date::parse(a), Date::parse(b)) {
+                        (Some(a), Some(b)) => {
+                            let end = Date::from_days(b.days() + inclusive as i64);
+                            Ok(ValueRange::Date(a..end))
+                        }
+                        _ => Err(invalid()),
+                    },
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         match self {
             ValueRange::Int(r) => r.is_empty(),
             ValueRange::Char(r) => r.is_empty(),
+            ValueRange::Date(r) => r.is_empty(),
         }
     }
+
+    fn days(range: &Range<Date>) -> impl Iterator<Item = Date> {
+        (range.start.days()..range.end.days()).map(Date::from_days)
+    }
     pub(crate) fn to_map(&self) -> IndexMap<PrimitiveValue, PrimitiveValue> {
         match self {
             ValueRange::Int(r) => r.clone().map(|v| (v.into(), v.into())).collect(),
@@ -73,6 +141,9 @@ impl ValueRange {
                 .clone()
                 .map(|v| (v.to_string().into(), v.to_string().into()))
                 .collect(),
+            ValueRange::Date(r) => Self::days(r)
+                .map(|v| (v.to_string().into(), v.to_string().into()))
+                .collect(),
         }
     }
 
@@ -80,15 +151,17 @@ impl ValueRange {
         match self {
             ValueRange::Int(r) => r.clone().map(|v| v.into()).collect(),
             ValueRange::Char(r) => r.clone().map(|v| v.to_string().into()).collect(),
+            ValueRange::Date(r) => Self::days(r).map(|v| v.to_string().into()).collect(),
         }
     }
 }
 
 impl Display for ValueRange {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             ValueRange::Int(r) => write!(f, "{}..{}", r.start, r.end),
             ValueRange::Char(r) => write!(f, "{}..{}", r.start, r.end),
+            ValueRange::Date(r) => write!(f, "{}..{}", r.start, r.end),
         }
     }
 }
@@ -96,4 +169,5 @@ impl Display for ValueRange {
 impl_from! {
     Range<i64>, ValueRange, ValueRange::Int;
     Range<char>, ValueRange, ValueRange::Char;
+    Range<Date>, ValueRange, ValueRange::Date;
 }
@@ -1,5 +1,5 @@
-use crate::{Number, ValueRange};
-use std::ops::{Add, Range};
+use crate::{Date, Number, ValueRange};
+use core::ops::{Add, Range};
 
 impl Add for &ValueRange {
     type Output = Option<ValueRange>;
@@ -21,6 +21,7 @@ impl Add for &ValueRange {
                 let end = char::from_u32(a.end as u32 + b.end as u32)?;
                 Some(ValueRange::Char(Range { start, end }))
             }
+            (ValueRange::Date(_), _) | (_, ValueRange::Date(_)) => None,
         }
     }
 }
@@ -40,6 +41,10 @@ impl Add<&Number> for &ValueRange {
                 let end = char::from_u32(r.end as u32 + rhs as u32)?;
                 Some(ValueRange::Char(Range { start, end }))
             }
+            ValueRange::Date(r) => Some(ValueRange::Date(Range {
+                start: Date::from_days(r.start.days() + rhs),
+                end: Date::from_days(r.end.days() + rhs),
+            })),
         }
     }
 }
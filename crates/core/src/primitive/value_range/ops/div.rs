@@ -1,5 +1,5 @@
 use crate::{Number, ValueRange};
-use std::ops::{Div, Range};
+use core::ops::{Div, Range};
 
 impl Div for &ValueRange {
     type Output = Option<ValueRange>;
@@ -21,6 +21,7 @@ impl Div for &ValueRange {
                 let end = char::from_u32(a.end as u32 / b.end as u32)?;
                 Some(ValueRange::Char(Range { start, end }))
             }
+            (ValueRange::Date(_), _) | (_, ValueRange::Date(_)) => None,
         }
     }
 }
@@ -40,6 +41,7 @@ impl Div<&Number> for &ValueRange {
                 let end = char::from_u32(r.end as u32 / rhs as u32)?;
                 Some(ValueRange::Char(Range { start, end }))
             }
+            ValueRange::Date(_) => None,
         }
     }
 }
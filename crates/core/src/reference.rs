@@ -1,16 +1,22 @@
-use std::fmt::Debug;
-use std::ops::{Deref, DerefMut};
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+
+// Lists, maps, & tuples own their values, assigning a collection into itself (`m.insert 'self', m`)
+// stores a copy. Objects that share state between copies (mutexes, channels, & tasks hold values
+// behind an `Arc`) can form a cycle that reference counting never frees, that state is `Tracked`
+// and freed by `collect`. Storing a value that references the state it's stored in is rejected
+// up front, see `ObjectValue::references`.
 
 #[cfg(feature = "threaded")]
-pub type Reference<T> = std::sync::Arc<T>;
+pub type Reference<T> = alloc::sync::Arc<T>;
 
 #[cfg(not(feature = "threaded"))]
-pub type Reference<T> = std::rc::Rc<T>;
+pub type Reference<T> = alloc::rc::Rc<T>;
 
 #[derive(Debug)]
 pub struct MutableReference<T: Debug>(
-    #[cfg(feature = "threaded")] std::sync::Arc<std::sync::RwLock<T>>,
-    #[cfg(not(feature = "threaded"))] std::rc::Rc<std::cell::RefCell<T>>,
+    #[cfg(feature = "threaded")] alloc::sync::Arc<std::sync::RwLock<T>>,
+    #[cfg(not(feature = "threaded"))] alloc::rc::Rc<core::cell::RefCell<T>>,
 );
 
 impl<T: Debug> Clone for MutableReference<T> {
@@ -23,11 +29,11 @@ impl<T: Debug> From<T> for MutableReference<T> {
     fn from(t: T) -> Self {
         #[cfg(feature = "threaded")]
         {
-            MutableReference(std::sync::Arc::new(std::sync::RwLock::new(t)))
+            MutableReference(alloc::sync::Arc::new(std::sync::RwLock::new(t)))
         }
         #[cfg(not(feature = "threaded"))]
         {
-            MutableReference(std::rc::Rc::new(std::cell::RefCell::new(t)))
+            MutableReference(alloc::rc::Rc::new(core::cell::RefCell::new(t)))
         }
     }
 }
@@ -86,3 +92,464 @@ impl<T: Debug> MutableReference<T> {
         f(self.0.borrow_mut().deref_mut(), s)
     }
 }
+
+/// Stack values, variables, & arguments. `Rc<RefCell<T>>` by default, with the `send` feature
+/// `Arc<RwLock<T>>` so a VM can be moved to another thread, i.e. to run rigz off the host's main thread.
+/// Borrowing mutably while a value is borrowed panics with `RefCell` and can deadlock with `RwLock`.
+pub struct Shared<T>(
+    #[cfg(feature = "send")] alloc::sync::Arc<std::sync::RwLock<T>>,
+    #[cfg(not(feature = "send"))] alloc::rc::Rc<core::cell::RefCell<T>>,
+);
+
+#[cfg(feature = "send")]
+pub type SharedRef<'a, T> = std::sync::RwLockReadGuard<'a, T>;
+#[cfg(feature = "send")]
+pub type SharedMut<'a, T> = std::sync::RwLockWriteGuard<'a, T>;
+
+#[cfg(not(feature = "send"))]
+pub type SharedRef<'a, T> = core::cell::Ref<'a, T>;
+#[cfg(not(feature = "send"))]
+pub type SharedMut<'a, T> = core::cell::RefMut<'a, T>;
+
+impl<T> Shared<T> {
+    #[cfg(feature = "send")]
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Shared(alloc::sync::Arc::new(std::sync::RwLock::new(value)))
+    }
+
+    #[cfg(not(feature = "send"))]
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Shared(alloc::rc::Rc::new(core::cell::RefCell::new(value)))
+    }
+
+    #[cfg(feature = "send")]
+    #[inline]
+    pub fn borrow(&self) -> SharedRef<'_, T> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(not(feature = "send"))]
+    #[inline]
+    pub fn borrow(&self) -> SharedRef<'_, T> {
+        self.0.borrow()
+    }
+
+    #[cfg(feature = "send")]
+    #[inline]
+    pub fn borrow_mut(&self) -> SharedMut<'_, T> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(not(feature = "send"))]
+    #[inline]
+    pub fn borrow_mut(&self) -> SharedMut<'_, T> {
+        self.0.borrow_mut()
+    }
+
+    /// None if the value is already borrowed
+    #[cfg(feature = "send")]
+    #[inline]
+    pub fn try_borrow_mut(&self) -> Option<SharedMut<'_, T>> {
+        self.0.try_write().ok()
+    }
+
+    /// None if the value is already borrowed
+    #[cfg(not(feature = "send"))]
+    #[inline]
+    pub fn try_borrow_mut(&self) -> Option<SharedMut<'_, T>> {
+        self.0.try_borrow_mut().ok()
+    }
+
+    /// Returns the previous value
+    #[inline]
+    pub fn replace(&self, value: T) -> T {
+        core::mem::replace(&mut *self.borrow_mut(), value)
+    }
+
+    #[inline]
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// True if both point to the same value
+    #[cfg(feature = "send")]
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        alloc::sync::Arc::ptr_eq(&this.0, &other.0)
+    }
+
+    /// True if both point to the same value
+    #[cfg(not(feature = "send"))]
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        alloc::rc::Rc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T> Shared<T> {
+    /// Reference that doesn't keep the value alive
+    #[cfg(feature = "send")]
+    #[inline]
+    pub fn downgrade(this: &Self) -> WeakShared<T> {
+        WeakShared(alloc::sync::Arc::downgrade(&this.0))
+    }
+
+    /// Reference that doesn't keep the value alive
+    #[cfg(not(feature = "send"))]
+    #[inline]
+    pub fn downgrade(this: &Self) -> WeakShared<T> {
+        WeakShared(alloc::rc::Rc::downgrade(&this.0))
+    }
+}
+
+/// Weak version of `Shared`, see `Shared::downgrade`
+pub struct WeakShared<T>(
+    #[cfg(feature = "send")] alloc::sync::Weak<std::sync::RwLock<T>>,
+    #[cfg(not(feature = "send"))] alloc::rc::Weak<core::cell::RefCell<T>>,
+);
+
+impl<T> WeakShared<T> {
+    /// None once every `Shared` pointing to the value was dropped
+    #[inline]
+    pub fn upgrade(&self) -> Option<Shared<T>> {
+        self.0.upgrade().map(Shared)
+    }
+
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+}
+
+impl<T> Clone for WeakShared<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        WeakShared(self.0.clone())
+    }
+}
+
+impl<T> Debug for WeakShared<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "(Weak)")
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T: Debug> Debug for Shared<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&*self.borrow(), f)
+    }
+}
+
+impl<T: Default> Default for Shared<T> {
+    fn default() -> Self {
+        Shared::new(T::default())
+    }
+}
+
+/// Compares the values, like `Rc<RefCell<T>>`
+impl<T: PartialEq> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(self, other) || *self.borrow() == *other.borrow()
+    }
+}
+
+impl<T: Eq> Eq for Shared<T> {}
+
+#[cfg(feature = "std")]
+pub(crate) use tracked::Registry;
+#[cfg(feature = "std")]
+pub use tracked::{collect, Collectable, Held, Tracked};
+
+#[cfg(feature = "std")]
+mod tracked {
+    use crate::context::with_context;
+    use crate::ObjectValue;
+    use alloc::sync::{Arc, Weak};
+    use core::cell::Cell;
+    use core::fmt::Debug;
+    use core::mem::ManuallyDrop;
+    use core::ops::Deref;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, MutexGuard, TryLockError};
+
+    /// Values held by shared state, locked by `collect` so they can't change while it decides what to free
+    pub trait Held: Send {
+        /// Values currently held by the state
+        fn values(&self) -> Vec<ObjectValue>;
+
+        /// Drops the held values, called by `collect` for state that is only reachable through a cycle
+        fn clear(&mut self);
+    }
+
+    /// State shared by every copy of an object that holds values, i.e. the value of a mutex or the queue of a channel
+    pub trait Collectable: Send + Sync {
+        /// Lock guarding the held values
+        fn held(&self) -> &Mutex<dyn Held>;
+
+        fn held_values(&self) -> Vec<ObjectValue> {
+            self.held()
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .values()
+        }
+    }
+
+    /// States created by one runtime, part of its `RuntimeContext`
+    #[derive(Default)]
+    pub(crate) struct Registry {
+        states: Mutex<Vec<Weak<dyn Collectable>>>,
+        // copies & drops of `Tracked` references, a collection started while one is running or that sees one
+        // start is abandoned since the reference counts it read may be inconsistent
+        started: AtomicUsize,
+        finished: AtomicUsize,
+    }
+
+    impl Debug for Registry {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("Registry")
+                .field("states", &self.states().len())
+                .finish()
+        }
+    }
+
+    std::thread_local! {
+        // set while `collect` reads held values, the copies it makes are dropped before it checks for changes
+        static READING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    // attempts made by `collect` before giving up while other threads keep copying references
+    const ATTEMPTS: usize = 8;
+
+    impl Registry {
+        fn states(&self) -> MutexGuard<'_, Vec<Weak<dyn Collectable>>> {
+            self.states.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        fn track(&self, state: Weak<dyn Collectable>) {
+            let mut states = self.states();
+            // forget freed state before growing
+            if states.len() == states.capacity() {
+                states.retain(|s| s.strong_count() > 0);
+            }
+            states.push(state);
+        }
+
+        #[inline]
+        fn change<T>(&self, f: impl FnOnce() -> T) -> T {
+            if READING.get() {
+                return f();
+            }
+            self.started.fetch_add(1, Ordering::SeqCst);
+            let result = f();
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            result
+        }
+
+        /// States that haven't been freed yet
+        pub(crate) fn live(&self) -> usize {
+            self.states()
+                .iter()
+                .filter(|s| s.strong_count() > 0)
+                .count()
+        }
+
+        pub(crate) fn collect(&self) -> usize {
+            for _ in 0..ATTEMPTS {
+                if let Some(freed) = self.try_collect() {
+                    return freed;
+                }
+                std::thread::yield_now();
+            }
+            0
+        }
+
+        // None if a reference was copied or dropped, or a state was locked, while counting
+        fn try_collect(&self) -> Option<usize> {
+            // new states wait for the collection
+            let mut tracked = self.states();
+            tracked.retain(|s| s.strong_count() > 0);
+            let finished = self.finished.load(Ordering::SeqCst);
+            let started = self.started.load(Ordering::SeqCst);
+            if started != finished {
+                return None;
+            }
+
+            let states: Vec<Arc<dyn Collectable>> =
+                tracked.iter().filter_map(Weak::upgrade).collect();
+            // values can't move in or out of a state while it's locked
+            let mut held = Vec::with_capacity(states.len());
+            for state in &states {
+                match state.held().try_lock() {
+                    Ok(guard) => held.push(guard),
+                    Err(TryLockError::Poisoned(e)) => held.push(e.into_inner()),
+                    Err(TryLockError::WouldBlock) => return None,
+                }
+            }
+
+            let id = |s: &Arc<dyn Collectable>| Arc::as_ptr(s) as *const () as usize;
+            // `states` holds one of the references
+            let counts: HashMap<usize, usize> = states
+                .iter()
+                .map(|s| (id(s), Arc::strong_count(s) - 1))
+                .collect();
+            READING.set(true);
+            let held_ids: HashMap<usize, Vec<usize>> = states
+                .iter()
+                .zip(&held)
+                .map(|(s, values)| {
+                    let mut ids = Vec::new();
+                    values.values().iter().for_each(|v| held_ids(v, &mut ids));
+                    (id(s), ids)
+                })
+                .collect();
+            READING.set(false);
+            if self.started.load(Ordering::SeqCst) != started {
+                return None;
+            }
+
+            let mut external = counts;
+            for id in held_ids.values().flatten() {
+                if let Some(count) = external.get_mut(id) {
+                    *count = count.saturating_sub(1);
+                }
+            }
+            let mut reachable = HashSet::new();
+            let mut pending: Vec<usize> = external
+                .into_iter()
+                .filter_map(|(id, count)| (count > 0).then_some(id))
+                .collect();
+            while let Some(next) = pending.pop() {
+                if reachable.insert(next) {
+                    if let Some(ids) = held_ids.get(&next) {
+                        pending.extend(ids);
+                    }
+                }
+            }
+
+            // garbage can't be copied, every reference to it is held by a locked state
+            let mut freed = 0;
+            for (state, values) in states.iter().zip(&mut held) {
+                if !reachable.contains(&id(state)) {
+                    values.clear();
+                    freed += 1;
+                }
+            }
+            drop(held);
+            Some(freed)
+        }
+    }
+
+    /// `Arc` of shared state that `collect` knows about, clones point to the same state.
+    /// The state is tracked by the runtime that created it, copies sent to other runtimes are still
+    /// collected by that runtime.
+    pub struct Tracked<T: Collectable + 'static> {
+        // only copied & dropped through `Registry::change`
+        state: ManuallyDrop<Arc<T>>,
+        registry: Arc<Registry>,
+    }
+
+    impl<T: Collectable + 'static> Tracked<T> {
+        pub fn new(state: T) -> Self {
+            let state = Arc::new(state);
+            let weak: Weak<dyn Collectable> = Arc::downgrade(&state) as Weak<T>;
+            let registry = with_context(|c| c.tracked.clone());
+            registry.track(weak);
+            Tracked {
+                state: ManuallyDrop::new(state),
+                registry,
+            }
+        }
+
+        /// Identity of the state, see `AsPrimitive::shared_id`
+        #[inline]
+        pub fn id(this: &Self) -> usize {
+            Arc::as_ptr(&this.state) as usize
+        }
+
+        /// Reference that doesn't keep the state alive
+        #[inline]
+        pub fn downgrade(this: &Self) -> Weak<T> {
+            Arc::downgrade(&this.state)
+        }
+    }
+
+    impl<T: Collectable + 'static> Clone for Tracked<T> {
+        #[inline]
+        fn clone(&self) -> Self {
+            Tracked {
+                state: self.registry.change(|| self.state.clone()),
+                registry: self.registry.clone(),
+            }
+        }
+    }
+
+    impl<T: Collectable + 'static> Drop for Tracked<T> {
+        fn drop(&mut self) {
+            // SAFETY: `state` isn't used after this
+            let state = unsafe { ManuallyDrop::take(&mut self.state) };
+            self.registry.change(|| drop(state))
+        }
+    }
+
+    impl<T: Collectable + 'static> Deref for Tracked<T> {
+        type Target = T;
+
+        #[inline]
+        fn deref(&self) -> &Self::Target {
+            &self.state
+        }
+    }
+
+    /// Used for state skipped by serde, deserialized objects are tracked too
+    impl<T: Collectable + Default + 'static> Default for Tracked<T> {
+        fn default() -> Self {
+            Tracked::new(T::default())
+        }
+    }
+
+    impl<T: Collectable + Debug + 'static> Debug for Tracked<T> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            Debug::fmt(&**self.state, f)
+        }
+    }
+
+    // shared state directly held by the value, the state of nested objects is held by their own state
+    fn held_ids(value: &ObjectValue, ids: &mut Vec<usize>) {
+        match value {
+            ObjectValue::Primitive(_) => {}
+            ObjectValue::List(l) | ObjectValue::Tuple(l) => l.iter().for_each(|v| held_ids(v, ids)),
+            ObjectValue::Map(m) => m.iter().for_each(|(k, v)| {
+                held_ids(k, ids);
+                held_ids(v, ids);
+            }),
+            ObjectValue::Object(o) => match o.shared_id() {
+                Some(id) => ids.push(id),
+                None => o.contained_values().iter().for_each(|v| held_ids(v, ids)),
+            },
+        }
+    }
+
+    /// Frees `Tracked` state of the current runtime that is only reachable through reference cycles, i.e. a
+    /// mutex holding a channel that holds the mutex, returns the number of states that were cleared.
+    ///
+    /// Each state's reference count is compared to the references held by the other tracked states, state with
+    /// more references than that is held by a variable, the stack, a lazy value, or another process and is kept
+    /// along with everything it holds. The counts are read while every state is locked and no reference is being
+    /// copied or dropped, state in use by another process is kept until the next collection.
+    pub fn collect() -> usize {
+        with_context(|c| c.tracked.clone()).collect()
+    }
+}
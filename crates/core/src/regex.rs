@@ -0,0 +1,197 @@
+use crate::{
+    AsPrimitive, CreateObject, CustomType, Definition, Object, ObjectValue, PrimitiveValue,
+    RigzArgs, RigzType, VMError, WithTypeInfo,
+};
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter};
+use core::hash::{Hash, Hasher};
+use indexmap::IndexMap;
+
+/// Patterns compiled per thread, the oldest is dropped once full
+const CACHE_SIZE: usize = 256;
+
+std::thread_local! {
+    static CACHE: RefCell<IndexMap<String, regex::Regex>> = RefCell::new(IndexMap::new());
+}
+
+/// Compiled pattern, `Regex.new '\d+'` or `/\d+/`. Compiling is cached by pattern so literals in loops &
+/// module functions called with the same pattern don't recompile it. Compared & hashed by its pattern
+#[derive(Clone, Debug)]
+pub struct Regex(pub regex::Regex);
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, VMError> {
+        if let Some(r) = CACHE.with(|c| c.borrow().get(pattern).cloned()) {
+            return Ok(Regex(r));
+        }
+        let compiled = regex::Regex::new(pattern).map_err(|e| {
+            VMError::ConversionError(format!("Cannot convert {pattern:?} to Regex - {e}"))
+        })?;
+        CACHE.with(|c| {
+            let mut c = c.borrow_mut();
+            if c.len() >= CACHE_SIZE {
+                c.shift_remove_index(0);
+            }
+            c.insert(pattern.to_string(), compiled.clone());
+        });
+        Ok(Regex(compiled))
+    }
+
+    /// Regex objects as is & strings compiled as patterns, for module functions with `Regex` arguments
+    pub fn from_value(value: &ObjectValue) -> Result<Self, VMError> {
+        match value {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => Regex::new(s),
+            ObjectValue::Object(o) => match o.downcast_ref::<Regex>() {
+                Some(r) => Ok(r.clone()),
+                None => Err(VMError::ConversionError(format!(
+                    "Cannot convert {o} to Regex"
+                ))),
+            },
+            v => Err(VMError::ConversionError(format!(
+                "Cannot convert {v} to Regex"
+            ))),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Regex {}
+
+impl PartialOrd for Regex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Regex {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Regex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl serde::Serialize for Regex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Regex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<Regex> for ObjectValue {
+    fn from(value: Regex) -> Self {
+        ObjectValue::Object(Box::new(value))
+    }
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "/{}/", self.as_str().replace('/', "\\/"))
+    }
+}
+
+impl WithTypeInfo for Regex {
+    fn rigz_type(&self) -> RigzType {
+        RigzType::Custom(CustomType {
+            name: Self::name().to_string(),
+            fields: vec![],
+        })
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Regex {}
+
+impl CreateObject for Regex {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?;
+        let value = value.borrow();
+        Regex::from_value(&value)
+    }
+}
+
+impl Definition for Regex {
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Regex"
+    }
+
+    fn trait_definition() -> &'static str
+    where
+        Self: Sized,
+    {
+        r#"object Regex
+            Self(pattern: Any)
+            fn Self.source -> String
+            fn Self.is_match(input: String) -> Bool
+        end"#
+    }
+}
+
+#[typetag::serde]
+impl Object for Regex {
+    fn call_extension(&self, function: String, args: RigzArgs) -> Result<ObjectValue, VMError> {
+        let v: ObjectValue = match function.as_str() {
+            "source" => self.as_str().into(),
+            "is_match" => {
+                let input = args.first()?.borrow().to_string();
+                self.0.is_match(&input).into()
+            }
+            _ => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "Regex does not implement `{function}` - {args:?}"
+                )))
+            }
+        };
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+pub mod regex_tests {
+    use crate::{ObjectValue, Regex};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn compiles_once() {
+        let a = Regex::new(r"\d+").unwrap();
+        let b = Regex::from_value(&ObjectValue::from(r"\d+")).unwrap();
+        assert_eq!(a, b);
+        assert!(b.0.is_match("a1"));
+        assert_eq!(
+            Regex::from_value(&a.clone().into()).unwrap().to_string(),
+            r"/\d+/"
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn invalid_pattern() {
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::from_value(&ObjectValue::from(1)).is_err());
+    }
+}
@@ -1,13 +1,65 @@
+use crate::prelude::*;
 use crate::{
-    AsPrimitive, CreateObject, Definition, Object, ObjectValue, PrimitiveValue, RigzArgs, RigzType,
-    VMError, WithTypeInfo,
+    AsPrimitive, CreateObject, Definition, IndexMap, Object, ObjectValue, PrimitiveValue, RigzArgs,
+    RigzType, VMError, WithTypeInfo,
 };
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
 use log::warn;
-use std::fmt::{Debug, Display, Formatter};
-use std::ops::Deref;
-use std::sync::Arc;
 
-#[derive(Clone, Hash, PartialOrd, PartialEq, serde::Serialize, serde::Deserialize)]
+/// `fn Self.hash -> Int` & `fn Self.eq(other) -> Bool` of objects defined in rigz, provided by the VM that runs them.
+/// `None` falls back to hashing & comparing the object's fields
+pub trait ObjectHooks {
+    fn hash(&self, object: &RigzObject) -> Option<i64>;
+
+    fn eq(&self, object: &RigzObject, other: &RigzObject) -> Option<bool>;
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static HOOKS: RefCell<Option<Rc<dyn ObjectHooks>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous hooks of the thread when dropped
+#[cfg(feature = "std")]
+#[must_use]
+pub struct ObjectHooksGuard {
+    previous: Option<Rc<dyn ObjectHooks>>,
+}
+
+#[cfg(feature = "std")]
+impl Drop for ObjectHooksGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        HOOKS.with(|h| h.replace(previous));
+    }
+}
+
+/// Objects hashed or compared on the current thread use `hooks` until the guard is dropped,
+/// processes enter the hooks of their VM on the thread that runs them
+#[cfg(feature = "std")]
+pub fn enter_object_hooks(hooks: Rc<dyn ObjectHooks>) -> ObjectHooksGuard {
+    let previous = HOOKS.with(|h| h.replace(Some(hooks)));
+    ObjectHooksGuard { previous }
+}
+
+#[cfg(feature = "std")]
+fn object_hooks() -> Option<Rc<dyn ObjectHooks>> {
+    HOOKS.with(|h| h.borrow().clone())
+}
+
+// hooks are per thread, without std objects always use their fields
+#[cfg(not(feature = "std"))]
+fn object_hooks() -> Option<Rc<dyn ObjectHooks>> {
+    None
+}
+
+#[derive(Clone, PartialOrd, serde::Serialize, serde::Deserialize)]
 pub struct RigzObject {
     #[serde(skip)]
     pub rigz_type: Arc<RigzType>,
@@ -33,14 +85,37 @@ impl RigzObject {
     }
 }
 
+impl PartialEq for RigzObject {
+    fn eq(&self, other: &Self) -> bool {
+        if self.rigz_type != other.rigz_type {
+            return false;
+        }
+        match object_hooks().and_then(|h| h.eq(self, other)) {
+            Some(eq) => eq,
+            None => self.values == other.values,
+        }
+    }
+}
+
+/// Hooks must hash objects they consider equal the same, objects with `eq` but no `hash` only hash their type
+impl Hash for RigzObject {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rigz_type.hash(state);
+        match object_hooks().and_then(|h| h.hash(self)) {
+            Some(hash) => hash.hash(state),
+            None => self.values.hash(state),
+        }
+    }
+}
+
 impl Debug for RigzObject {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}{{{:?}}}", self.rigz_type, self.values)
     }
 }
 
 impl Display for RigzObject {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{self:?}")
     }
 }
@@ -52,6 +127,25 @@ impl WithTypeInfo for RigzObject {
 }
 
 impl AsPrimitive<ObjectValue> for RigzObject {
+    fn contained_values(&self) -> Vec<ObjectValue> {
+        self.values.clone()
+    }
+
+    /// Fields by name, i.e. `JSON.generate`
+    fn to_map(&self) -> Result<IndexMap<ObjectValue, ObjectValue>, VMError> {
+        match self.rigz_type.as_ref() {
+            RigzType::Custom(c) => Ok(c
+                .fields
+                .iter()
+                .zip(&self.values)
+                .map(|((f, _), v)| (f.clone().into(), v.clone()))
+                .collect()),
+            _ => Err(VMError::UnsupportedOperation(format!(
+                "Cannot convert {self:?} to Map"
+            ))),
+        }
+    }
+
     fn get(&self, attr: &ObjectValue) -> Result<ObjectValue, VMError> {
         if let ObjectValue::Primitive(s) = attr {
             match s {
@@ -174,7 +268,7 @@ impl Definition for RigzObject {
     }
 }
 
-#[typetag::serde]
+#[cfg_attr(feature = "std", typetag::serde)]
 impl Object for RigzObject {}
 
 impl From<RigzObject> for ObjectValue {
@@ -182,3 +276,61 @@ impl From<RigzObject> for ObjectValue {
         ObjectValue::Object(Box::new(value))
     }
 }
+
+#[cfg(test)]
+pub mod rigz_object_tests {
+    use crate::{
+        enter_object_hooks, AsPrimitive, CustomType, ObjectHooks, ObjectValue, RigzObject, RigzType,
+    };
+    use alloc::rc::Rc;
+    use alloc::sync::Arc;
+    use std::collections::HashSet;
+    use wasm_bindgen_test::*;
+
+    /// compares points by `x` only
+    struct XHooks;
+
+    impl ObjectHooks for XHooks {
+        fn hash(&self, object: &RigzObject) -> Option<i64> {
+            object.values[0].to_int().ok()
+        }
+
+        fn eq(&self, object: &RigzObject, other: &RigzObject) -> Option<bool> {
+            Some(object.values[0] == other.values[0])
+        }
+    }
+
+    fn point(x: i64, y: i64) -> RigzObject {
+        let rigz_type = Arc::new(RigzType::Custom(CustomType {
+            name: "Point".to_string(),
+            fields: vec![
+                ("x".to_string(), RigzType::Int),
+                ("y".to_string(), RigzType::Int),
+            ],
+        }));
+        RigzObject {
+            rigz_type,
+            values: vec![x.into(), y.into()],
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn default_compares_values() {
+        assert_eq!(point(1, 2), point(1, 2));
+        assert_ne!(point(1, 2), point(1, 3));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn hooks_used_until_dropped() {
+        {
+            let _hooks = enter_object_hooks(Rc::new(XHooks));
+            assert_eq!(point(1, 2), point(1, 3));
+            let set: HashSet<ObjectValue> = [point(1, 2), point(1, 3), point(2, 2)]
+                .into_iter()
+                .map(ObjectValue::from)
+                .collect();
+            assert_eq!(set.len(), 2);
+        }
+        assert_ne!(point(1, 2), point(1, 3));
+    }
+}
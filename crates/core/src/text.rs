@@ -0,0 +1,106 @@
+use crate::prelude::*;
+use crate::VMError;
+use core::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Negative indexes count back from `len`, the result is clamped to `0..=len`
+fn resolve_index(index: i64, len: usize) -> usize {
+    if index < 0 {
+        len.saturating_sub(index.unsigned_abs() as usize)
+    } else {
+        (index as usize).min(len)
+    }
+}
+
+/// User perceived characters of `value`, the unit of `s[0]`, `for c in s`, & `String.len`
+pub fn graphemes(value: &str) -> impl DoubleEndedIterator<Item = &str> {
+    value.graphemes(true)
+}
+
+/// Grapheme at `index`, `-1` is the last grapheme
+pub fn grapheme_at(value: &str, index: i64) -> Option<&str> {
+    let mut graphemes = value.graphemes(true);
+    if index < 0 {
+        graphemes.nth_back(index.unsigned_abs() as usize - 1)
+    } else {
+        graphemes.nth(index as usize)
+    }
+}
+
+/// Graphemes in `range`, i.e. `s[0..5]` or `s[-3..]`. Negative bounds count back from the end and bounds past
+/// either end are clamped, so the result may be empty but never fails
+pub fn grapheme_slice(value: &str, range: &Range<i64>) -> String {
+    let graphemes: Vec<_> = value.graphemes(true).collect();
+    let len = graphemes.len();
+    let start = resolve_index(range.start, len);
+    let end = resolve_index(range.end, len);
+    if start >= end {
+        return String::new();
+    }
+    graphemes[start..end].concat()
+}
+
+/// Every `step` grapheme starting with the first, negative steps start from the last grapheme
+pub fn grapheme_step(value: &str, step: i64) -> Result<String, VMError> {
+    let size = match step.unsigned_abs() {
+        0 => {
+            return Err(VMError::RuntimeError(
+                "String.step must not be 0".to_string(),
+            ))
+        }
+        s => s as usize,
+    };
+    let graphemes = value.graphemes(true);
+    let res = if step < 0 {
+        graphemes.rev().step_by(size).collect()
+    } else {
+        graphemes.step_by(size).collect()
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+pub mod text_tests {
+    use crate::{grapheme_at, grapheme_slice, grapheme_step, graphemes};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn slices() {
+        assert_eq!(grapheme_slice("hello world", &(0..5)), "hello");
+        assert_eq!(grapheme_slice("hello world", &(-5..i64::MAX)), "world");
+        assert_eq!(grapheme_slice("hello", &(1..-1)), "ell");
+        assert_eq!(grapheme_slice("hello", &(3..1)), "");
+        assert_eq!(grapheme_slice("hello", &(-10..10)), "hello");
+        // e + combining acute accent is one grapheme
+        assert_eq!(grapheme_slice("cafe\u{301}s", &(3..4)), "e\u{301}");
+        assert_eq!(grapheme_slice("🇯🇵🇺🇸", &(-1..i64::MAX)), "🇺🇸");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn grapheme_iteration() {
+        let value = "cafe\u{301} 🇯🇵";
+        assert_eq!(
+            graphemes(value).collect::<Vec<_>>(),
+            vec!["c", "a", "f", "e\u{301}", " ", "🇯🇵"]
+        );
+        assert_eq!(value.chars().count(), 8);
+        assert_eq!(value.len(), 15);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn indexes() {
+        assert_eq!(grapheme_at("hello", 0), Some("h"));
+        assert_eq!(grapheme_at("hello", -1), Some("o"));
+        assert_eq!(grapheme_at("hello", 5), None);
+        assert_eq!(grapheme_at("hello", -6), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn steps() {
+        assert_eq!(grapheme_step("abcdef", 2), Ok("ace".to_string()));
+        assert_eq!(grapheme_step("abcdef", -2), Ok("fdb".to_string()));
+        assert_eq!(grapheme_step("abc", 1), Ok("abc".to_string()));
+        assert!(grapheme_step("abc", 0).is_err());
+    }
+}
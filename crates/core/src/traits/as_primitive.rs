@@ -1,6 +1,7 @@
-use crate::{Number, RigzType, VMError};
-use indexmap::IndexMap;
-use std::fmt::{Debug, Display};
+use crate::prelude::*;
+use crate::IndexMap;
+use crate::{BinaryOperation, Number, RigzType, VMError};
+use core::fmt::{Debug, Display};
 
 pub trait WithTypeInfo {
     fn rigz_type(&self) -> RigzType;
@@ -15,6 +16,45 @@ pub trait AsPrimitive<T: Clone + AsPrimitive<T> + Default + Sized>:
         )))
     }
 
+    /// Arithmetic with another value, `reversed` is set when self is the right hand side.
+    /// None falls back to the default behavior of the operation
+    fn binary_operation(&self, operation: BinaryOperation, other: &T, reversed: bool) -> Option<T> {
+        let _ = (operation, other, reversed);
+        None
+    }
+
+    /// Next value waiting to be received without blocking, used by `select`.
+    /// `Ok(None)` when nothing has arrived yet
+    fn poll_receive(&self) -> Result<Option<T>, VMError> {
+        Err(VMError::UnsupportedOperation(format!(
+            "Cannot receive from {self}"
+        )))
+    }
+
+    /// Identity of the state shared by every copy of this object (the value of a mutex, the queue of a channel),
+    /// None for objects that own their values
+    fn shared_id(&self) -> Option<usize> {
+        None
+    }
+
+    /// Values held by this object, used by `ObjectValue::references` to reject reference cycles
+    fn contained_values(&self) -> Vec<T> {
+        Vec::new()
+    }
+
+    /// Objects read one value at a time by `for v in self` with `next_value`, i.e. rows of a file,
+    /// instead of converting the whole object with `to_list`
+    fn is_stream(&self) -> bool {
+        false
+    }
+
+    /// Next value of a stream, `Ok(None)` once every value was read
+    fn next_value(&self) -> Result<Option<T>, VMError> {
+        Err(VMError::UnsupportedOperation(format!(
+            "Cannot iterate over {self}"
+        )))
+    }
+
     fn as_list(&mut self) -> Result<&mut Vec<T>, VMError> {
         Err(VMError::UnsupportedOperation(format!(
             "Cannot convert {self:?} to mut List"
@@ -1,6 +1,6 @@
-use std::any::Any;
-use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use core::any::Any;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
 
 // DynCompare & DynHash are from https://quinedot.github.io/rust-learning/dyn-trait-examples.html
 pub trait AsDynCompare: Any {
@@ -3,21 +3,21 @@ mod dyn_traits;
 #[cfg(feature = "snapshot")]
 mod snapshot;
 
-use crate::{ObjectValue, RigzArgs, VMError};
+use crate::prelude::*;
+use crate::{ObjectValue, RigzArgs, Shared, VMError};
+#[cfg(feature = "snapshot")]
+use alloc::vec::IntoIter;
 pub use as_primitive::{AsPrimitive, WithTypeInfo};
+use core::cmp::Ordering;
+#[cfg(feature = "snapshot")]
+use core::fmt::Display;
+use core::fmt::{Debug, Formatter};
+use core::hash::{Hash, Hasher};
 use dyn_clone::DynClone;
 pub use dyn_traits::*;
-use log::{error, warn};
 use mopa::mopafy;
 #[cfg(feature = "snapshot")]
 pub use snapshot::Snapshot;
-use std::any::Any;
-use std::cell::RefCell;
-use std::cmp::Ordering;
-use std::fmt::{Debug, Display, Formatter};
-use std::hash::{Hash, Hasher};
-use std::rc::Rc;
-use std::vec::IntoIter;
 
 pub trait Definition {
     fn name() -> &'static str
@@ -35,7 +35,7 @@ pub struct Dependency {
 }
 
 impl Debug for Dependency {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Dependency")
     }
 }
@@ -49,10 +49,20 @@ impl Dependency {
     }
 }
 
+/// Runs a module function as its own VM instruction, see `Module::instructions`
+pub type InstructionHandler = fn(RigzArgs) -> Result<ObjectValue, VMError>;
+
 // todo convert function: String to function: usize?
 
 #[allow(unused_variables)]
 pub trait Module: Debug + Definition {
+    /// Modules that read outside state (network, files, environment variables) return false,
+    /// they can't be called while `VMOptions::deterministic` is set
+    #[inline]
+    fn deterministic(&self) -> bool {
+        true
+    }
+
     fn deps() -> Vec<Dependency>
     where
         Self: Sized,
@@ -66,9 +76,15 @@ pub trait Module: Debug + Definition {
         )))
     }
 
+    /// Functions called through `Instruction::Custom` instead of `call`, skipping the module lookup & the match
+    /// on the function name. Handlers can't use the module, only its arguments; extensions aren't supported
+    fn instructions(&self) -> Vec<(&'static str, InstructionHandler)> {
+        vec![]
+    }
+
     fn call_extension(
         &self,
-        this: Rc<RefCell<ObjectValue>>,
+        this: Shared<ObjectValue>,
         function: String,
         args: RigzArgs,
     ) -> Result<ObjectValue, VMError> {
@@ -77,9 +93,27 @@ pub trait Module: Debug + Definition {
         )))
     }
 
+    /// Overload of the extension `function` that `call_extension` runs for `this`, the VM caches it per call site &
+    /// receiver type then uses `call_resolved_extension`, skipping the match on the function name & receiver.
+    /// `None` always uses `call_extension`
+    fn resolve_extension(&self, this: &ObjectValue, function: &str) -> Option<usize> {
+        None
+    }
+
+    fn call_resolved_extension(
+        &self,
+        overload: usize,
+        this: Shared<ObjectValue>,
+        args: RigzArgs,
+    ) -> Result<ObjectValue, VMError> {
+        Err(VMError::UnsupportedOperation(format!(
+            "{self:?} does not implement `call_resolved_extension` - {overload}"
+        )))
+    }
+
     fn call_mutable_extension(
         &self,
-        this: Rc<RefCell<ObjectValue>>,
+        this: Shared<ObjectValue>,
         function: String,
         args: RigzArgs,
     ) -> Result<Option<ObjectValue>, VMError> {
@@ -98,7 +132,7 @@ pub trait CreateObject {
 }
 
 #[allow(unused_variables)]
-#[typetag::serde]
+#[cfg_attr(feature = "std", typetag::serde)]
 pub trait Object:
     mopa::Any
     + DynCompare
@@ -137,9 +171,25 @@ pub trait Object:
     }
 }
 
-mopafy!(Object);
+// the downcasts mopa generates transmute the data pointer of `dyn Object`
+#[allow(clippy::transmute_ptr_to_ref)]
+mod downcast {
+    use super::*;
+
+    mopafy!(Object, core = core, alloc = alloc);
+}
 dyn_clone::clone_trait_object!(Object);
 
+// objects are serialized with typetag, which needs std
+#[cfg(not(feature = "std"))]
+impl serde::Serialize for dyn Object {
+    fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom(format!(
+            "{self:?} can't be serialized without std"
+        )))
+    }
+}
+
 impl Hash for dyn Object {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.dyn_hash(state)
@@ -171,12 +221,12 @@ impl PartialOrd<&Self> for Box<dyn Object> {
 }
 
 // todo first pass will use serde to read/write from bytes
-#[cfg(feature = "snapshot")]
+#[cfg(all(feature = "snapshot", feature = "std"))]
 impl Snapshot for Box<dyn Object + '_> {
     fn as_bytes(&self) -> Vec<u8> {
         match serde_json::to_string(self) {
             Ok(v) => {
-                let mut bytes = vec![0];
+                let mut bytes = vec![1];
                 bytes.extend(Snapshot::as_bytes(&v));
                 bytes
             }
@@ -210,6 +260,28 @@ impl Snapshot for Box<dyn Object + '_> {
     }
 }
 
+/// Objects are serialized with typetag, without std snapshots containing objects fail to load
+#[cfg(all(feature = "snapshot", not(feature = "std")))]
+impl Snapshot for Box<dyn Object + '_> {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0];
+        bytes.extend(
+            VMError::UnsupportedOperation(format!("Cannot serialize {self:?} without std"))
+                .as_bytes(),
+        );
+        bytes
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        match bytes.next() {
+            Some(0) => Err(Snapshot::from_bytes(bytes, location)?),
+            o => Err(VMError::UnsupportedOperation(format!(
+                "Cannot deserialize object without std, byte {o:?}"
+            ))),
+        }
+    }
+}
+
 pub trait Reverse {
     type Output;
 
@@ -1,15 +1,18 @@
-use crate::{VMError, ValueRange};
-use indexmap::IndexMap;
+use crate::prelude::*;
+use crate::IndexMap;
+use crate::{Date, Diagnostic, ErrorCode, Interned, SourceSpan, TraceFrame, VMError, ValueRange};
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::vec::IntoIter;
+use core::cell::RefCell;
+use core::fmt::Display;
+use core::hash::Hash;
+use core::num::NonZeroUsize;
+use core::ops::Range;
 use itertools::Itertools;
 use log::Level;
-use std::cell::RefCell;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
-use std::fmt::Display;
-use std::hash::Hash;
-use std::ops::Range;
-use std::rc::Rc;
-use std::sync::Arc;
-use std::vec::IntoIter;
 // todo make snapshot a feature
 
 pub trait Snapshot: Sized {
@@ -68,6 +71,18 @@ impl Snapshot for usize {
     }
 }
 
+impl Snapshot for NonZeroUsize {
+    fn as_bytes(&self) -> Vec<u8> {
+        Snapshot::as_bytes(&self.get())
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        let value: usize = Snapshot::from_bytes(bytes, location)?;
+        NonZeroUsize::new(value)
+            .ok_or_else(|| VMError::RuntimeError(format!("Invalid {location}, expected non-zero")))
+    }
+}
+
 impl<T: Snapshot> Snapshot for Vec<T> {
     fn as_bytes(&self) -> Vec<u8> {
         let mut res = Snapshot::as_bytes(&self.len());
@@ -99,7 +114,7 @@ impl<K: Snapshot + Hash + Eq, V: Snapshot> Snapshot for IndexMap<K, V> {
 
     fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
         let len = Snapshot::from_bytes(bytes, &format!("{location} len"))?;
-        let mut results = IndexMap::with_capacity(len);
+        let mut results = IndexMap::with_capacity_and_hasher(len, Default::default());
         for _ in 0..len {
             let k = K::from_bytes(bytes, location)?;
             let v = V::from_bytes(bytes, location)?;
@@ -109,6 +124,7 @@ impl<K: Snapshot + Hash + Eq, V: Snapshot> Snapshot for IndexMap<K, V> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<K: Snapshot + Hash + Eq, V: Snapshot> Snapshot for HashMap<K, V> {
     fn as_bytes(&self) -> Vec<u8> {
         let mut res = Snapshot::as_bytes(&self.len());
@@ -244,6 +260,16 @@ impl Snapshot for i64 {
     }
 }
 
+impl Snapshot for Date {
+    fn as_bytes(&self) -> Vec<u8> {
+        self.days().as_bytes()
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        Ok(Date::from_days(Snapshot::from_bytes(bytes, location)?))
+    }
+}
+
 impl Snapshot for char {
     fn as_bytes(&self) -> Vec<u8> {
         (*self as u32).as_bytes()
@@ -344,6 +370,18 @@ impl Snapshot for String {
     }
 }
 
+impl Snapshot for Interned {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut l = self.len().as_bytes();
+        l.extend(self.bytes());
+        l
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        Ok(String::from_bytes(bytes, location)?.into())
+    }
+}
+
 impl Snapshot for ValueRange {
     fn as_bytes(&self) -> Vec<u8> {
         match self {
@@ -357,6 +395,11 @@ impl Snapshot for ValueRange {
                 res.extend(r.as_bytes());
                 res
             }
+            ValueRange::Date(r) => {
+                let mut res = vec![2];
+                res.extend(r.as_bytes());
+                res
+            }
         }
     }
 
@@ -373,6 +416,7 @@ impl Snapshot for ValueRange {
         let v = match next {
             0 => ValueRange::Int(Snapshot::from_bytes(bytes, location)?),
             1 => ValueRange::Char(Snapshot::from_bytes(bytes, location)?),
+            2 => ValueRange::Date(Snapshot::from_bytes(bytes, location)?),
             b => {
                 return Err(VMError::RuntimeError(format!(
                     "Illegal ValueRange byte {b} - {location}"
@@ -436,6 +480,22 @@ impl Snapshot for VMError {
                 res.extend(Snapshot::as_bytes(m));
                 res
             }
+            VMError::BudgetExceeded(m) => {
+                let mut res = vec![12];
+                res.extend(Snapshot::as_bytes(m));
+                res
+            }
+            VMError::Traceback(e, frames) => {
+                let mut res = vec![10];
+                res.extend(e.as_bytes());
+                res.extend(frames.as_bytes());
+                res
+            }
+            VMError::Diagnostic(d) => {
+                let mut res = vec![11];
+                res.extend(d.as_bytes());
+                res
+            }
         }
     }
 
@@ -444,6 +504,14 @@ impl Snapshot for VMError {
             Some(s) => s,
             None => return Err(VMError::RuntimeError(format!("Missing VMError {location}"))),
         };
+        if next == 10 {
+            let e = Snapshot::from_bytes(bytes, location)?;
+            let frames = Snapshot::from_bytes(bytes, location)?;
+            return Ok(VMError::Traceback(e, frames));
+        }
+        if next == 11 {
+            return Ok(VMError::Diagnostic(Snapshot::from_bytes(bytes, location)?));
+        }
         let message = String::from_bytes(bytes, &format!("VMError - {location}"))?;
         let e = match next {
             0 => VMError::TimeoutError(message),
@@ -456,6 +524,7 @@ impl Snapshot for VMError {
             7 => VMError::InvalidModule(message),
             8 => VMError::InvalidModuleFunction(message),
             9 => VMError::LifecycleError(message),
+            12 => VMError::BudgetExceeded(message),
             b => {
                 return Err(VMError::RuntimeError(format!(
                     "Illegal VMError byte {b} {location}"
@@ -465,3 +534,65 @@ impl Snapshot for VMError {
         Ok(e)
     }
 }
+
+impl Snapshot for Diagnostic {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut res = (self.code.number() as usize).as_bytes();
+        res.extend(Snapshot::as_bytes(&self.message));
+        res.extend(self.span.as_bytes());
+        res.extend(self.help.as_bytes());
+        res
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        let code: usize = Snapshot::from_bytes(bytes, location)?;
+        let Some(code) = u16::try_from(code).ok().and_then(ErrorCode::from_number) else {
+            return Err(VMError::RuntimeError(format!(
+                "Illegal error code {code} {location}"
+            )));
+        };
+        Ok(Diagnostic {
+            code,
+            message: Snapshot::from_bytes(bytes, location)?,
+            span: Snapshot::from_bytes(bytes, location)?,
+            help: Snapshot::from_bytes(bytes, location)?,
+        })
+    }
+}
+
+impl Snapshot for SourceSpan {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut res = self.start.as_bytes();
+        res.extend(self.end.as_bytes());
+        res.extend(self.line.as_bytes());
+        res
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        Ok(SourceSpan {
+            start: Snapshot::from_bytes(bytes, location)?,
+            end: Snapshot::from_bytes(bytes, location)?,
+            line: Snapshot::from_bytes(bytes, location)?,
+        })
+    }
+}
+
+impl Snapshot for TraceFrame {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut res = Snapshot::as_bytes(&self.name);
+        res.extend(self.scope_id.as_bytes());
+        res.extend(self.line.as_bytes());
+        res
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        let name = String::from_bytes(bytes, location)?;
+        let scope_id = Snapshot::from_bytes(bytes, location)?;
+        let line = Snapshot::from_bytes(bytes, location)?;
+        Ok(TraceFrame {
+            name,
+            scope_id,
+            line,
+        })
+    }
+}
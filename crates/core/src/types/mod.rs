@@ -1,11 +1,14 @@
 #[cfg(feature = "snapshot")]
 mod snapshot;
 
+use crate::prelude::*;
 use crate::VMError;
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
-use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize)]
 pub enum RigzType {
@@ -62,6 +65,20 @@ impl RigzType {
         }
         false
     }
+
+    /// Whether a value of this type may be an error, i.e. `Any`, `Error`, or `Int!`
+    pub fn can_return_error(&self) -> bool {
+        match self {
+            RigzType::Any | RigzType::Error => true,
+            RigzType::Wrapper {
+                base_type,
+                can_return_error,
+                ..
+            } => *can_return_error || base_type.can_return_error(),
+            RigzType::Union(v) | RigzType::Composite(v) => v.iter().any(|t| t.can_return_error()),
+            _ => false,
+        }
+    }
 }
 
 impl FromStr for RigzType {
@@ -115,7 +132,7 @@ impl FromStr for RigzType {
 }
 
 impl Display for RigzType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             RigzType::None => write!(f, "None"),
             RigzType::Any => write!(f, "Any"),
@@ -157,14 +174,33 @@ impl Display for RigzType {
     }
 }
 
-#[derive(Clone, Debug, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, Serialize, Deserialize)]
 pub struct CustomType {
     pub name: String,
     pub fields: Vec<(String, RigzType)>,
 }
 
+// custom types are identified by name, hashing & ordering have to agree with `==`
 impl PartialEq for CustomType {
     fn eq(&self, other: &Self) -> bool {
         self.name.eq(&other.name)
     }
 }
+
+impl Hash for CustomType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
+
+impl PartialOrd for CustomType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CustomType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    AsPrimitive, CreateObject, CustomType, Definition, Object, ObjectValue, PrimitiveValue,
+    RigzArgs, RigzType, VMError, WithTypeInfo,
+};
+use core::fmt::{Display, Formatter};
+
+/// Validated UUID, compared & hashed by its bytes so it works as a map key or set member.
+/// Displayed hyphenated & lowercase regardless of how it was parsed
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Uuid(pub ::uuid::Uuid);
+
+impl Uuid {
+    /// Hyphenated, simple, braced, or urn formats, i.e. `urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8`
+    pub fn parse(value: &str) -> Result<Self, VMError> {
+        ::uuid::Uuid::try_parse(value).map(Uuid).map_err(|e| {
+            VMError::ConversionError(format!("Cannot convert {value:?} to Uuid - {e}"))
+        })
+    }
+
+    /// Version 4 UUID, the version & variant bits of `bytes` are overwritten
+    pub fn from_random_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(::uuid::Builder::from_random_bytes(bytes).into_uuid())
+    }
+}
+
+impl From<Uuid> for ObjectValue {
+    fn from(value: Uuid) -> Self {
+        ObjectValue::Object(Box::new(value))
+    }
+}
+
+impl Display for Uuid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0.hyphenated())
+    }
+}
+
+impl WithTypeInfo for Uuid {
+    fn rigz_type(&self) -> RigzType {
+        RigzType::Custom(CustomType {
+            name: Self::name().to_string(),
+            fields: vec![],
+        })
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Uuid {
+    fn to_bool(&self) -> bool {
+        !self.0.is_nil()
+    }
+}
+
+impl CreateObject for Uuid {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?;
+        let value = value.borrow();
+        match &*value {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => Uuid::parse(s),
+            ObjectValue::Object(o) => match o.downcast_ref::<Uuid>() {
+                Some(u) => Ok(*u),
+                None => Err(VMError::ConversionError(format!(
+                    "Cannot create Uuid from {o}"
+                ))),
+            },
+            v => Err(VMError::ConversionError(format!(
+                "Cannot create Uuid from {v}"
+            ))),
+        }
+    }
+}
+
+impl Definition for Uuid {
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Uuid"
+    }
+
+    fn trait_definition() -> &'static str
+    where
+        Self: Sized,
+    {
+        r#"object Uuid
+            Self(value: Any)
+            fn Self.version -> Int
+            fn Self.is_nil -> Bool
+            fn Self.bytes -> [Int]
+        end"#
+    }
+}
+
+#[typetag::serde]
+impl Object for Uuid {
+    fn call_extension(&self, function: String, args: RigzArgs) -> Result<ObjectValue, VMError> {
+        let v: ObjectValue = match function.as_str() {
+            "version" => (self.0.get_version_num() as i64).into(),
+            "is_nil" => self.0.is_nil().into(),
+            "bytes" => self
+                .0
+                .as_bytes()
+                .iter()
+                .map(|b| ObjectValue::from(*b as i64))
+                .collect::<Vec<_>>()
+                .into(),
+            "to_s" => self.to_string().into(),
+            _ => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "Uuid does not implement `{function}` - {args:?}"
+                )))
+            }
+        };
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+pub mod uuid_tests {
+    use crate::{ObjectValue, Uuid};
+    use indexmap::IndexSet;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parse_formats() {
+        let id = Uuid::parse("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+        assert_eq!(Uuid::parse("67E5504410B1426F9247BB680E5FE0C8"), Ok(id));
+        assert_eq!(
+            Uuid::parse("urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8"),
+            Ok(id)
+        );
+        assert_eq!(id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert!(Uuid::parse("67e55044-10b1").is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn equal_ids_are_one_key() {
+        let a: ObjectValue = Uuid::parse("67e55044-10b1-426f-9247-bb680e5fe0c8")
+            .unwrap()
+            .into();
+        let b: ObjectValue = Uuid::parse("{67E55044-10B1-426F-9247-BB680E5FE0C8}")
+            .unwrap()
+            .into();
+        let c: ObjectValue = Uuid::from_random_bytes([7; 16]).into();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        let set: IndexSet<_> = [a, b, c].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+}
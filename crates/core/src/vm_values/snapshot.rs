@@ -1,6 +1,7 @@
-use crate::{ObjectValue, Snapshot, StackValue, VMError};
-use std::fmt::Display;
-use std::vec::IntoIter;
+use crate::prelude::*;
+use crate::{ObjectValue, Snapshot, StackValue, Thunk, VMError};
+use alloc::vec::IntoIter;
+use core::fmt::Display;
 
 impl Snapshot for StackValue {
     fn as_bytes(&self) -> Vec<u8> {
@@ -18,6 +19,11 @@ impl Snapshot for StackValue {
                 results.push(2);
                 results.extend(c.as_bytes());
             }
+            StackValue::Lazy(t) => {
+                results.push(3);
+                results.extend(t.scope.as_bytes());
+                results.extend(t.value().as_bytes());
+            }
         }
         results
     }
@@ -34,6 +40,14 @@ impl Snapshot for StackValue {
                 StackValue::Value(v.into())
             }
             2 => StackValue::Constant(Snapshot::from_bytes(bytes, location)?),
+            3 => {
+                let scope = Snapshot::from_bytes(bytes, location)?;
+                let value: Option<ObjectValue> = Snapshot::from_bytes(bytes, location)?;
+                match value {
+                    None => StackValue::Lazy(Thunk::new(scope)),
+                    Some(v) => StackValue::Lazy(Thunk::resolved(scope, v)),
+                }
+            }
             _ => {
                 return Err(VMError::RuntimeError(format!(
                     "{location} Invalid StackValue type {tv}"
@@ -1,14 +1,67 @@
-use crate::ObjectValue;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::{ObjectValue, Shared};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum StackValue {
     ScopeId(usize),
-    Value(Rc<RefCell<ObjectValue>>),
+    Value(Shared<ObjectValue>),
     Constant(usize),
+    Lazy(Thunk),
 }
 
+/// `lazy do ... end`, the scope runs the first time the value is resolved. Copies share the result,
+/// errors aren't kept so the scope runs again the next time the value is resolved
+#[derive(Clone, Debug)]
+pub struct Thunk {
+    pub scope: usize,
+    value: Shared<Option<ObjectValue>>,
+}
+
+impl Thunk {
+    #[inline]
+    pub fn new(scope: usize) -> Self {
+        Thunk {
+            scope,
+            value: Shared::new(None),
+        }
+    }
+
+    /// Thunk that was already resolved to `value`
+    #[inline]
+    pub fn resolved(scope: usize, value: ObjectValue) -> Self {
+        Thunk {
+            scope,
+            value: Shared::new(Some(value)),
+        }
+    }
+
+    /// None until the thunk is resolved
+    #[inline]
+    pub fn value(&self) -> Option<ObjectValue> {
+        self.value.borrow().clone()
+    }
+
+    pub fn resolve<T: ResolveValue + ?Sized>(&self, vm: &mut T) -> Shared<ObjectValue> {
+        if let Some(v) = self.value() {
+            return v.into();
+        }
+        let v = vm.handle_scope(self.scope);
+        let value = v.borrow().clone();
+        if !value.is_error() {
+            *self.value.borrow_mut() = Some(value);
+        }
+        v
+    }
+}
+
+/// Copies of the same thunk are equal, `lazy do 1 end` twice are not
+impl PartialEq for Thunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.scope == other.scope && Shared::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl Eq for Thunk {}
+
 impl<T: Into<ObjectValue>> From<T> for StackValue {
     fn from(value: T) -> Self {
         let v: ObjectValue = value.into();
@@ -16,9 +69,9 @@ impl<T: Into<ObjectValue>> From<T> for StackValue {
     }
 }
 
-impl From<Rc<RefCell<ObjectValue>>> for StackValue {
+impl From<Shared<ObjectValue>> for StackValue {
     #[inline]
-    fn from(value: Rc<RefCell<ObjectValue>>) -> Self {
+    fn from(value: Shared<ObjectValue>) -> Self {
         StackValue::Value(value)
     }
 }
@@ -26,17 +79,18 @@ impl From<Rc<RefCell<ObjectValue>>> for StackValue {
 pub trait ResolveValue {
     fn location(&self) -> &'static str;
 
-    fn handle_scope(&mut self, scope: usize) -> Rc<RefCell<ObjectValue>>;
+    fn handle_scope(&mut self, scope: usize) -> Shared<ObjectValue>;
 
-    fn get_constant(&self, constant_id: usize) -> Rc<RefCell<ObjectValue>>;
+    fn get_constant(&self, constant_id: usize) -> Shared<ObjectValue>;
 }
 
 impl StackValue {
-    pub fn resolve<T: ResolveValue + ?Sized>(&self, vm: &mut T) -> Rc<RefCell<ObjectValue>> {
+    pub fn resolve<T: ResolveValue + ?Sized>(&self, vm: &mut T) -> Shared<ObjectValue> {
         match self {
             &StackValue::ScopeId(scope) => vm.handle_scope(scope),
             StackValue::Value(v) => v.clone(),
             &StackValue::Constant(c) => vm.get_constant(c),
+            StackValue::Lazy(t) => t.resolve(vm),
         }
     }
 }
@@ -0,0 +1,343 @@
+mod protocol;
+mod session;
+
+use crate::protocol::{read_message, Output};
+use crate::session::{Command, Resume, Session, THREAD_ID};
+use serde_json::{json, Value};
+use std::io::{stdin, stdout};
+use std::path::{Path, PathBuf};
+
+/// Debug Adapter Protocol server over stdio, launches a rigz program & debugs it using the VM's breakpoint hook
+struct Adapter {
+    output: Output,
+    session: Option<Session>,
+    // lines requested before launch, set once the program is parsed
+    pending: Vec<(PathBuf, Vec<usize>)>,
+    configured: bool,
+}
+
+impl Adapter {
+    fn new(output: Output) -> Self {
+        Adapter {
+            output,
+            session: None,
+            pending: vec![],
+            configured: false,
+        }
+    }
+
+    /// Returns false once the client disconnects
+    fn handle(&mut self, request: &Value) -> bool {
+        let command = request["command"].as_str().unwrap_or_default();
+        match command {
+            "initialize" => self.output.respond(
+                request,
+                json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsTerminateRequest": true,
+                }),
+            ),
+            "launch" => self.launch(request),
+            "setBreakpoints" => self.set_breakpoints(request),
+            "configurationDone" => {
+                self.configured = true;
+                if let Some(session) = &self.session {
+                    session.start();
+                }
+                self.output.respond(request, json!({}))
+            }
+            "threads" => self.output.respond(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            ),
+            "stackTrace" => self.paused(request, Command::StackTrace),
+            "scopes" => self.paused(request, Command::Scopes),
+            "variables" => self.paused(request, Command::Variables),
+            "continue" => self.paused(request, |r| Command::Resume(r, Resume::Continue)),
+            "next" => self.paused(request, |r| Command::Resume(r, Resume::Next)),
+            "stepIn" => self.paused(request, |r| Command::Resume(r, Resume::StepIn)),
+            "stepOut" => self.paused(request, |r| Command::Resume(r, Resume::StepOut)),
+            "pause" => {
+                if let Some(session) = &self.session {
+                    session.handle.pause();
+                }
+                self.output.respond(request, json!({}))
+            }
+            "terminate" => {
+                if let Some(session) = &self.session {
+                    session.terminate();
+                }
+                self.output.respond(request, json!({}))
+            }
+            "disconnect" => {
+                if let Some(session) = &self.session {
+                    session.terminate();
+                }
+                self.output.respond(request, json!({}));
+                return false;
+            }
+            command => self
+                .output
+                .fail(request, format!("Unsupported request {command}")),
+        }
+        true
+    }
+
+    fn launch(&mut self, request: &Value) {
+        let arguments = &request["arguments"];
+        let Some(program) = arguments["program"].as_str() else {
+            self.output
+                .fail(request, "launch requires `program`, the path to a .rg file");
+            return;
+        };
+        let stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+        let session = match Session::launch(program.into(), stop_on_entry, self.output.clone()) {
+            Ok(session) => session,
+            Err(e) => {
+                self.output.fail(request, e);
+                return;
+            }
+        };
+        for (path, lines) in std::mem::take(&mut self.pending) {
+            if same_file(&path, &session.program) {
+                for line in lines {
+                    session.set_breakpoint(line);
+                }
+            }
+        }
+        if self.configured {
+            session.start();
+        }
+        self.session = Some(session);
+        self.output.respond(request, json!({}));
+        // breakpoints can be verified once the program is parsed
+        self.output.event("initialized", json!({}));
+    }
+
+    fn set_breakpoints(&mut self, request: &Value) {
+        let arguments = &request["arguments"];
+        let path = PathBuf::from(arguments["source"]["path"].as_str().unwrap_or_default());
+        let lines: Vec<usize> = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|b| b["line"].as_u64())
+            .map(|l| l as usize)
+            .collect();
+
+        let breakpoints: Vec<_> = match &self.session {
+            None => {
+                self.pending.retain(|(p, _)| !same_file(p, &path));
+                self.pending.push((path, lines.clone()));
+                lines
+                    .iter()
+                    .map(|line| json!({ "verified": false, "line": line }))
+                    .collect()
+            }
+            Some(session) if !same_file(&path, &session.program) => lines
+                .iter()
+                .map(|line| {
+                    json!({
+                        "verified": false,
+                        "line": line,
+                        "message": "Breakpoints are only supported in the launched program",
+                    })
+                })
+                .collect(),
+            Some(session) => {
+                session.handle.clear_breakpoints();
+                lines
+                    .iter()
+                    .map(|&line| match session.set_breakpoint(line) {
+                        Some(actual) => json!({ "verified": true, "line": actual }),
+                        None => json!({
+                            "verified": false,
+                            "line": line,
+                            "message": "No code on or after this line",
+                        }),
+                    })
+                    .collect()
+            }
+        };
+        self.output
+            .respond(request, json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Forwards requests that need VM state to the VM thread, they fail while the program is running
+    fn paused(&self, request: &Value, command: impl FnOnce(Value) -> Command) {
+        match &self.session {
+            Some(session) if session.is_paused() => session.send(command(request.clone())),
+            _ => self.output.fail(request, "The program is not paused"),
+        }
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn main() {
+    let mut adapter = Adapter::new(Output::new(stdout()));
+    let mut input = stdin().lock();
+    loop {
+        let request = match read_message(&mut input) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Failed to read request: {e}");
+                break;
+            }
+        };
+        if request["type"] == "request" && !adapter.handle(&request) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod adapter_tests {
+    use crate::protocol::protocol_tests::Buffer;
+    use crate::protocol::Output;
+    use crate::session::session_tests::program;
+    use crate::Adapter;
+    use serde_json::{json, Value};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use wasm_bindgen_test::*;
+
+    struct Client {
+        adapter: Adapter,
+        buffer: Buffer,
+        seq: i64,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let buffer = Buffer::default();
+            Client {
+                adapter: Adapter::new(Output::new(buffer.clone())),
+                buffer,
+                seq: 0,
+            }
+        }
+
+        /// Sends a request & waits for its response, some responses are sent by the VM thread
+        fn request(&mut self, command: &str, arguments: Value) -> Value {
+            self.seq += 1;
+            let seq = self.seq;
+            self.adapter.handle(&json!({
+                "seq": seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            }));
+            self.wait_for(|m| m["type"] == "response" && m["request_seq"] == seq)
+        }
+
+        fn event(&self, event: &str) -> Value {
+            self.wait_for(|m| m["type"] == "event" && m["event"] == event)
+        }
+
+        fn wait_for(&self, matches: impl Fn(&Value) -> bool) -> Value {
+            let start = Instant::now();
+            loop {
+                if let Some(message) = self.buffer.messages().into_iter().find(&matches) {
+                    return message;
+                }
+                assert!(
+                    start.elapsed() < Duration::from_secs(10),
+                    "timed out waiting for message, received {:?}",
+                    self.buffer.messages()
+                );
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unsupported_and_not_paused() {
+        let mut client = Client::new();
+        let response = client.request("evaluate", json!({}));
+        assert_eq!(response["success"], false);
+        assert_eq!(response["message"], "Unsupported request evaluate");
+        let response = client.request("stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(response["message"], "The program is not paused");
+        let response = client.request("launch", json!({}));
+        assert_eq!(response["success"], false);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn launch_breakpoint_variables() {
+        let path = program("round_trip", "a = 1\nb = [1, 2]\nc = a + 2\nc\n");
+        let mut client = Client::new();
+        let response = client.request("initialize", json!({ "adapterID": "rigz" }));
+        assert_eq!(response["body"]["supportsConfigurationDoneRequest"], true);
+
+        // set before launch, verified once the program is parsed
+        let response = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": [{ "line": 3 }] }),
+        );
+        assert_eq!(
+            response["body"]["breakpoints"],
+            json!([{ "verified": false, "line": 3 }])
+        );
+        let response = client.request("launch", json!({ "program": path }));
+        assert_eq!(response["success"], true);
+        client.event("initialized");
+        let response = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": [{ "line": 3 }] }),
+        );
+        assert_eq!(
+            response["body"]["breakpoints"],
+            json!([{ "verified": true, "line": 3 }])
+        );
+        client.request("configurationDone", json!({}));
+
+        let stopped = client.event("stopped");
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        let response = client.request("stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(response["body"]["stackFrames"][0]["name"], "main");
+        assert_eq!(response["body"]["stackFrames"][0]["line"], 3);
+        let response = client.request("scopes", json!({ "frameId": 0 }));
+        let locals = response["body"]["scopes"][0]["variablesReference"].clone();
+        let response = client.request("variables", json!({ "variablesReference": locals }));
+        let variables = response["body"]["variables"].as_array().unwrap().clone();
+        let variable = |name: &str| {
+            variables
+                .iter()
+                .find(|v| v["name"] == name)
+                .unwrap_or_else(|| panic!("missing {name} in {variables:?}"))
+                .clone()
+        };
+        assert_eq!(variable("a")["value"], "1");
+        assert_eq!(variable("a")["variablesReference"], 0);
+        let list = variable("b");
+        assert_eq!(list["value"], "[1, 2]");
+        let response = client.request(
+            "variables",
+            json!({ "variablesReference": list["variablesReference"] }),
+        );
+        assert_eq!(
+            response["body"]["variables"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| (v["name"].clone(), v["value"].clone()))
+                .collect::<Vec<_>>(),
+            vec![(json!("0"), json!("1")), (json!("1"), json!("2"))]
+        );
+
+        let response = client.request("continue", json!({ "threadId": 1 }));
+        assert_eq!(response["success"], true);
+        let exited = client.event("exited");
+        assert_eq!(exited["body"]["exitCode"], 0);
+        client.event("terminated");
+        client.request("disconnect", json!({}));
+        std::fs::remove_file(path).unwrap();
+    }
+}
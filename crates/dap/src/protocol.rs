@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Reads the next `Content-Length` framed message, None once the input is closed
+pub fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Writes responses & events, shared by the request loop and the VM thread
+#[derive(Clone)]
+pub struct Output {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    seq: Arc<AtomicI64>,
+}
+
+impl Output {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Output {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            seq: Arc::new(AtomicI64::new(1)),
+        }
+    }
+
+    fn send(&self, mut message: Value) {
+        message["seq"] = self.seq.fetch_add(1, Ordering::Relaxed).into();
+        let body = message.to_string();
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // the client is gone if this fails, the request loop stops once stdin closes
+        let _ = write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len());
+        let _ = writer.flush();
+    }
+
+    pub fn respond(&self, request: &Value, body: Value) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    pub fn fail(&self, request: &Value, message: impl Into<String>) {
+        let message = message.into();
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+            "body": { "error": { "id": 1, "format": message } },
+        }))
+    }
+
+    pub fn event(&self, event: &str, body: Value) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    pub fn output(&self, category: &str, output: impl Into<String>) {
+        self.event(
+            "output",
+            json!({ "category": category, "output": output.into() }),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod protocol_tests {
+    use crate::protocol::{read_message, Output};
+    use serde_json::{json, Value};
+    use std::io::{Cursor, Write};
+    use std::sync::{Arc, Mutex};
+    use wasm_bindgen_test::*;
+
+    /// Collects everything written by an `Output`
+    #[derive(Clone, Default)]
+    pub struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        pub fn messages(&self) -> Vec<Value> {
+            let mut input = Cursor::new(self.0.lock().unwrap().clone());
+            let mut messages = vec![];
+            while let Some(message) = read_message(&mut input).unwrap() {
+                messages.push(message);
+            }
+            messages
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn reads_framed_messages() {
+        let first = r#"{"seq":1,"type":"request","command":"initialize"}"#;
+        let second = r#"{"seq":2,"type":"request","command":"threads"}"#;
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{first}content-type: application/json\r\ncontent-length: {}\r\n\r\n{second}",
+            first.len(),
+            second.len()
+        );
+        let mut input = Cursor::new(input);
+        assert_eq!(
+            read_message(&mut input).unwrap(),
+            Some(json!({"seq": 1, "type": "request", "command": "initialize"}))
+        );
+        assert_eq!(
+            read_message(&mut input).unwrap(),
+            Some(json!({"seq": 2, "type": "request", "command": "threads"}))
+        );
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn invalid_frames() {
+        let mut missing_length = Cursor::new("Content-Type: application/json\r\n\r\n{}");
+        assert!(read_message(&mut missing_length).is_err());
+        let mut invalid_body = Cursor::new("Content-Length: 3\r\n\r\n{x}");
+        assert!(read_message(&mut invalid_body).is_err());
+        let mut truncated = Cursor::new("Content-Length: 10\r\n\r\n{}");
+        assert!(read_message(&mut truncated).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn writes_framed_messages() {
+        let buffer = Buffer::default();
+        let output = Output::new(buffer.clone());
+        let request = json!({"seq": 7, "type": "request", "command": "launch"});
+        output.respond(&request, json!({}));
+        output.fail(&request, "missing program");
+        output.event("initialized", json!({}));
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let body = written
+            .strip_prefix("Content-Length: ")
+            .and_then(|rest| rest.split_once("\r\n\r\n"));
+        let (length, rest) = body.expect("missing Content-Length header");
+        let length: usize = length.parse().unwrap();
+        assert!(rest[..length].ends_with('}'));
+
+        let messages = buffer.messages();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|m| (m["seq"].clone(), m["type"].clone(), m["success"].clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (json!(1), json!("response"), json!(true)),
+                (json!(2), json!("response"), json!(false)),
+                (json!(3), json!("event"), Value::Null),
+            ]
+        );
+        assert_eq!(messages[0]["request_seq"], 7);
+        assert_eq!(messages[1]["message"], "missing program");
+        assert_eq!(messages[2]["event"], "initialized");
+    }
+}
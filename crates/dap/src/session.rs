@@ -0,0 +1,483 @@
+use crate::protocol::Output;
+use rigz_ast::ParserOptions;
+use rigz_core::{
+    drain_capture, pretty, CapturedOutput, ObjectValue, OutputStream, PrettyOptions, WithTypeInfo,
+};
+use rigz_runtime::runtime::RuntimeOptions;
+use rigz_runtime::{Runtime, RuntimeError};
+use rigz_vm::{DebugAction, DebugHandle, DebugState, PauseReason, Scope};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The VM runs on a single thread, processes started by the program aren't shown
+pub const THREAD_ID: i64 = 1;
+
+const LOCALS: i64 = 1;
+const STACK: i64 = 2;
+
+/// Requests that need the paused VM, answered by the VM thread
+pub enum Command {
+    Start,
+    StackTrace(Value),
+    Scopes(Value),
+    Variables(Value),
+    Resume(Value, Resume),
+    Disconnect,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    Next,
+    StepIn,
+    StepOut,
+}
+
+/// A program launched on its own thread, the thread waits for `start` before running
+pub struct Session {
+    pub program: PathBuf,
+    pub handle: DebugHandle,
+    commands: Sender<Command>,
+    paused: Arc<AtomicBool>,
+    terminated: Arc<AtomicBool>,
+    // source line -> the first instruction of that line in each scope
+    lines: BTreeMap<usize, Vec<(usize, usize)>>,
+}
+
+impl Session {
+    /// Parses the program, parse & validation errors are returned before anything runs
+    pub fn launch(program: PathBuf, stop_on_entry: bool, output: Output) -> Result<Self, String> {
+        let (commands, receiver) = channel();
+        let (ready, ready_receiver) = channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let terminated = Arc::new(AtomicBool::new(false));
+        let hook = Hook {
+            commands: receiver,
+            output,
+            program: program.clone(),
+            paused: paused.clone(),
+            terminated: terminated.clone(),
+            step: None,
+            entry: stop_on_entry,
+        };
+        let main = program.clone();
+        thread::spawn(move || run(main, hook, ready));
+
+        let (handle, lines) = ready_receiver
+            .recv()
+            .map_err(|_| "Debugger thread exited before the program was parsed".to_string())??;
+        Ok(Session {
+            program,
+            handle,
+            commands,
+            paused,
+            terminated,
+            lines,
+        })
+    }
+
+    pub fn start(&self) {
+        let _ = self.commands.send(Command::Start);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Only sent while the VM is paused, otherwise it would be answered at the next pause
+    pub fn send(&self, command: Command) {
+        if let Command::Resume(..) = command {
+            self.paused.store(false, Ordering::Release);
+        }
+        let _ = self.commands.send(command);
+    }
+
+    /// Stops the program at its next instruction
+    pub fn terminate(&self) {
+        self.terminated.store(true, Ordering::Release);
+        self.handle.pause();
+        let _ = self.commands.send(Command::Disconnect);
+    }
+
+    /// Lines without instructions move to the next line that has some, like most debuggers.
+    /// Returns the line the breakpoint was placed on.
+    pub fn set_breakpoint(&self, line: usize) -> Option<usize> {
+        let (&actual, locations) = self.lines.range(line..).next()?;
+        for &(scope, pc) in locations {
+            self.handle.set_breakpoint(scope, pc);
+        }
+        Some(actual)
+    }
+}
+
+fn line_table(scopes: &[Scope]) -> BTreeMap<usize, Vec<(usize, usize)>> {
+    let mut lines: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    for (scope_id, scope) in scopes.iter().enumerate() {
+        for &(pc, line) in &scope.lines {
+            let locations = lines.entry(line).or_default();
+            if !locations.iter().any(|(s, _)| *s == scope_id) {
+                locations.push((scope_id, pc));
+            }
+        }
+    }
+    lines
+}
+
+fn create_runtime(program: &Path) -> Result<Runtime<'static>, String> {
+    let input = read_to_string(program)
+        .map_err(|e| format!("Failed to read {}: {e}", program.display()))?;
+    let parser_options = ParserOptions {
+        current_directory: program.parent().map(Path::to_path_buf),
+        ..Default::default()
+    };
+    Runtime::create_with_options(input, RuntimeOptions::default(), parser_options)
+        .map_err(|e| e.to_string())
+}
+
+type Ready = Result<(DebugHandle, BTreeMap<usize, Vec<(usize, usize)>>), String>;
+
+fn run(program: PathBuf, mut hook: Hook, ready: Sender<Ready>) {
+    let mut runtime = match create_runtime(&program) {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    let vm = runtime.vm_mut();
+    vm.options.enable_traceback = true;
+    let handle = vm.debug_handle();
+    let _ = ready.send(Ok((handle.clone(), line_table(&vm.scopes))));
+
+    // wait for configurationDone so breakpoints are set before the first instruction
+    loop {
+        match hook.commands.recv() {
+            Ok(Command::Start) => break,
+            Ok(Command::Disconnect) | Err(_) => return,
+            Ok(_) => {}
+        }
+    }
+    if hook.entry {
+        handle.pause();
+    }
+    let output = hook.output.clone();
+    let terminated = hook.terminated.clone();
+    let context = vm.context.clone();
+    vm.on_break(move |state| hook.on_break(state));
+
+    // the program's output would corrupt the protocol on stdout, it's forwarded as output events instead
+    context.start_capture();
+    let running = Arc::new(AtomicBool::new(true));
+    let forward = {
+        let running = running.clone();
+        let output = output.clone();
+        let context = context.clone();
+        thread::spawn(move || {
+            while running.load(Ordering::Acquire) {
+                forward_output(&output, context.drain_capture());
+                thread::sleep(Duration::from_millis(50));
+            }
+        })
+    };
+    let result = runtime.run();
+    running.store(false, Ordering::Release);
+    let _ = forward.join();
+    forward_output(&output, context.stop_capture());
+
+    let exit_code = match result {
+        Ok(_) => 0,
+        Err(_) if terminated.load(Ordering::Acquire) => 0,
+        Err(e) => {
+            output.output("stderr", format!("{}\n", error_message(&e)));
+            1
+        }
+    };
+    output.event("exited", json!({ "exitCode": exit_code }));
+    output.event("terminated", json!({}));
+}
+
+fn error_message(error: &RuntimeError) -> String {
+    match error {
+        RuntimeError::Run(e) => match e.traceback().iter().find_map(|f| f.line) {
+            None => e.to_string(),
+            Some(line) => format!("line {line} - {e}"),
+        },
+        e => e.to_string(),
+    }
+}
+
+fn forward_output(output: &Output, captured: Vec<CapturedOutput>) {
+    for captured in captured {
+        let category = match captured.stream {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
+        };
+        // output from processes keeps its `[process 1:scope]` prefix so it can be told apart
+        let content = match captured.source.pid {
+            None => captured.content,
+            Some(_) => format!("{captured}\n"),
+        };
+        output.output(category, content);
+    }
+}
+
+struct Step {
+    resume: Resume,
+    depth: usize,
+    line: Option<usize>,
+}
+
+impl Step {
+    fn done(&self, state: &DebugState) -> bool {
+        let depth = call_depth(state);
+        match self.resume {
+            Resume::Continue => true,
+            Resume::StepIn => depth != self.depth || state.line != self.line,
+            Resume::Next => depth < self.depth || (depth == self.depth && state.line != self.line),
+            Resume::StepOut => depth < self.depth,
+        }
+    }
+}
+
+// stepping over a line doesn't enter functions but it does enter blocks
+fn call_depth(state: &DebugState) -> usize {
+    state
+        .frames
+        .iter()
+        .filter(|f| !Scope::BLOCK_NAMES.contains(&f.name.as_str()))
+        .count()
+}
+
+/// Runs on the VM thread whenever the VM pauses
+struct Hook {
+    commands: Receiver<Command>,
+    output: Output,
+    program: PathBuf,
+    paused: Arc<AtomicBool>,
+    terminated: Arc<AtomicBool>,
+    step: Option<Step>,
+    // set by stopOnEntry, the first pause is reported as an entry instead of a pause
+    entry: bool,
+}
+
+impl Hook {
+    fn on_break(&mut self, state: &DebugState) -> DebugAction {
+        if self.terminated.load(Ordering::Acquire) {
+            return DebugAction::Stop;
+        }
+        let reason = match state.reason {
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Paused if self.entry => "entry",
+            PauseReason::Paused => "pause",
+            // instructions are stepped one at a time until the line or frame changes
+            PauseReason::Step => match &self.step {
+                Some(step) if !step.done(state) => return DebugAction::Step,
+                _ => "step",
+            },
+        };
+        self.entry = false;
+        self.step = None;
+
+        // runs within `VM::run` so the VM's context is current
+        forward_output(&self.output, drain_capture());
+        self.paused.store(true, Ordering::Release);
+        self.output.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        );
+
+        let mut variables = Variables::default();
+        let action = loop {
+            let Ok(command) = self.commands.recv() else {
+                break DebugAction::Stop;
+            };
+            match command {
+                Command::Start => {}
+                Command::StackTrace(request) => {
+                    self.output.respond(&request, self.stack_trace(state))
+                }
+                Command::Scopes(request) => {
+                    let frame = request["arguments"]["frameId"].as_i64().unwrap_or_default();
+                    self.output.respond(&request, scopes(frame))
+                }
+                Command::Variables(request) => {
+                    let reference = request["arguments"]["variablesReference"]
+                        .as_i64()
+                        .unwrap_or_default();
+                    let body = json!({ "variables": variables.get(reference, state) });
+                    self.output.respond(&request, body)
+                }
+                Command::Resume(request, resume) => {
+                    self.output
+                        .respond(&request, json!({ "allThreadsContinued": true }));
+                    if resume == Resume::Continue {
+                        break DebugAction::Continue;
+                    }
+                    self.step = Some(Step {
+                        resume,
+                        depth: call_depth(state),
+                        line: state.line,
+                    });
+                    break DebugAction::Step;
+                }
+                Command::Disconnect => break DebugAction::Stop,
+            }
+        };
+        self.paused.store(false, Ordering::Release);
+        action
+    }
+
+    fn stack_trace(&self, state: &DebugState) -> Value {
+        let source = json!({
+            "name": self.program.file_name().map(|f| f.to_string_lossy()),
+            "path": self.program,
+        });
+        let frames: Vec<_> = state
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(id, frame)| {
+                json!({
+                    "id": id,
+                    "name": frame.name,
+                    "source": source,
+                    "line": frame.line.unwrap_or_default(),
+                    "column": 1,
+                })
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+}
+
+/// Locals are only available for the innermost frame
+fn scopes(frame: i64) -> Value {
+    if frame != 0 {
+        return json!({ "scopes": [] });
+    }
+    json!({
+        "scopes": [
+            { "name": "Locals", "presentationHint": "locals", "variablesReference": LOCALS, "expensive": false },
+            { "name": "Stack", "variablesReference": STACK, "expensive": false },
+        ]
+    })
+}
+
+/// Lists, maps, & tuples are expanded on demand, references are only valid while the VM is paused
+#[derive(Default)]
+struct Variables {
+    children: Vec<Vec<(String, ObjectValue)>>,
+}
+
+impl Variables {
+    const FIRST_CHILD: i64 = STACK + 1;
+
+    fn get(&mut self, reference: i64, state: &DebugState) -> Vec<Value> {
+        match reference {
+            LOCALS => state
+                .locals
+                .iter()
+                .map(|l| self.variable(&l.name, l.value.as_ref()))
+                .collect(),
+            // top of the stack first
+            STACK => state
+                .stack
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(index, value)| self.variable(&index.to_string(), value.as_ref()))
+                .collect(),
+            reference => {
+                let index = (reference - Self::FIRST_CHILD) as usize;
+                let Some(children) = self.children.get(index).cloned() else {
+                    return vec![];
+                };
+                children
+                    .iter()
+                    .map(|(name, value)| self.variable(name, Some(value)))
+                    .collect()
+            }
+        }
+    }
+
+    fn variable(&mut self, name: &str, value: Option<&ObjectValue>) -> Value {
+        let Some(value) = value else {
+            return json!({ "name": name, "value": "<not evaluated>", "variablesReference": 0 });
+        };
+        let children: Vec<_> = match value {
+            ObjectValue::List(values) | ObjectValue::Tuple(values) => values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (index.to_string(), value.clone()))
+                .collect(),
+            ObjectValue::Map(values) => values
+                .iter()
+                .map(|(key, value)| (pretty(key, &PrettyOptions::compact()), value.clone()))
+                .collect(),
+            _ => vec![],
+        };
+        let reference = if children.is_empty() {
+            0
+        } else {
+            self.children.push(children);
+            Self::FIRST_CHILD + self.children.len() as i64 - 1
+        };
+        json!({
+            "name": name,
+            "value": pretty(value, &PrettyOptions::compact()),
+            "type": value.rigz_type().to_string(),
+            "variablesReference": reference,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod session_tests {
+    use crate::protocol::Output;
+    use crate::session::Session;
+    use std::io::sink;
+    use std::path::PathBuf;
+    use wasm_bindgen_test::*;
+
+    // test binaries of other checkouts or targets can run at the same time
+    pub fn program(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rigz_dap_{}_{name}.rg", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn breakpoints_move_to_next_line() {
+        let path = program("next_line", "a = 1\n\n# comment\nb = a + 1\nb\n");
+        let session = Session::launch(path.clone(), false, Output::new(sink())).unwrap();
+        assert_eq!(session.set_breakpoint(1), Some(1));
+        assert_eq!(session.set_breakpoint(2), Some(4));
+        assert_eq!(session.set_breakpoint(100), None);
+        assert!(!session.handle.breakpoints().is_empty());
+        assert!(!session.is_paused());
+        session.terminate();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn launch_errors() {
+        let missing = std::env::temp_dir().join("rigz_dap_missing.rg");
+        assert!(Session::launch(missing, false, Output::new(sink())).is_err());
+
+        let path = program("invalid", "fn foo = 1\n");
+        assert!(Session::launch(path.clone(), false, Output::new(sink())).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
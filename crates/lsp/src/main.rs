@@ -1,6 +1,8 @@
 use dashmap::DashMap;
-use rigz_ast::format;
+use rigz_ast::{format, parse, LintConfig, LintLevel, ModuleTraitDefinition, ParserOptions};
+use rigz_runtime::Runtime;
 use ropey::Rope;
+use std::collections::HashMap;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
@@ -9,6 +11,8 @@ use tower_lsp::{Client, LanguageServer, LspService, Server};
 struct Backend {
     client: Client,
     files: DashMap<Url, Rope>,
+    /// used to find unused module imports
+    modules: Vec<ModuleTraitDefinition>,
 }
 
 #[tower_lsp::async_trait]
@@ -27,6 +31,8 @@ impl LanguageServer for Backend {
                     },
                 )),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
                 ..Default::default()
             },
             server_info: None,
@@ -44,11 +50,18 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        self.lint(params.text_document.uri.clone(), &params.text_document.text)
+            .await;
         self.files
             .insert(params.text_document.uri, params.text_document.text.into());
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        self.lint(
+            params.text_document.uri.clone(),
+            &params.content_changes[0].text,
+        )
+        .await;
         self.files.insert(
             params.text_document.uri,
             Rope::from_str(&params.content_changes[0].text),
@@ -64,8 +77,8 @@ impl LanguageServer for Backend {
         let s = rope.to_string();
         let l = s.len();
         let f = format(s);
-        let start = offset_to_position(0, &rope);
-        let end = offset_to_position(l, &rope);
+        let start = offset_to_position(0, rope);
+        let end = offset_to_position(l, rope);
         *rope = Rope::from_str(&f);
         let update = match start.map(|s| end.map(|e| Range::new(s, e)).map(|r| TextEdit::new(r, f)))
         {
@@ -74,6 +87,33 @@ impl LanguageServer for Backend {
         };
         Ok(Some(update))
     }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri;
+        Ok(self
+            .symbol_ranges(&uri, position.position, params.context.include_declaration)
+            .map(|ranges| {
+                ranges
+                    .into_iter()
+                    .map(|r| Location::new(uri.clone(), r))
+                    .collect()
+            }))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri;
+        Ok(self
+            .symbol_ranges(&uri, position.position, true)
+            .map(|ranges| {
+                let edits = ranges
+                    .into_iter()
+                    .map(|r| TextEdit::new(r, params.new_name.clone()))
+                    .collect();
+                WorkspaceEdit::new(HashMap::from([(uri, edits)]))
+            }))
+    }
 }
 
 fn offset_to_position(offset: usize, rope: &Rope) -> Option<Position> {
@@ -88,8 +128,103 @@ impl Backend {
         Self {
             client,
             files: Default::default(),
+            modules: Runtime::new()
+                .module_definitions()
+                .into_iter()
+                .cloned()
+                .collect(),
         }
     }
+
+    /// Every use of the symbol at `position`, matched by symbol id so shadowed names are left alone
+    fn symbol_ranges(
+        &self,
+        uri: &Url,
+        position: Position,
+        include_declaration: bool,
+    ) -> Option<Vec<Range>> {
+        let rope = self.files.get(uri)?;
+        let text = rope.to_string();
+        let mut program = parse(&text, ParserOptions::default()).ok()?;
+        let located = program.resolve_symbols().locate(&text);
+        let line = rope.try_line_to_char(position.line as usize).ok()?;
+        let offset = rope
+            .try_char_to_byte(line + position.character as usize)
+            .ok()?;
+        let id = located
+            .iter()
+            .find(|(span, _)| span.start <= offset && offset <= span.end)?
+            .1
+            .symbol?;
+        let range = |start: usize, end: usize| {
+            Some(Range::new(
+                offset_to_position(rope.try_byte_to_char(start).ok()?, &rope)?,
+                offset_to_position(rope.try_byte_to_char(end).ok()?, &rope)?,
+            ))
+        };
+        Some(
+            located
+                .iter()
+                .filter(|(_, o)| o.symbol == Some(id) && (include_declaration || !o.declaration))
+                .filter_map(|(span, _)| range(span.start, span.end))
+                .collect(),
+        )
+    }
+
+    /// Warnings cover the line of the element they were found in, parse errors are reported at the start of the file
+    async fn lint(&self, uri: Url, text: &str) {
+        let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+        let rope = Rope::from_str(text);
+        let line_range = |line: usize| {
+            let line = line.checked_sub(1)?;
+            let text = rope.get_line(line)?.to_string();
+            let end = text.trim_end_matches(['\r', '\n']).chars().count();
+            Some(Range::new(
+                Position::new(line as u32, 0),
+                Position::new(line as u32, end as u32),
+            ))
+        };
+        let modules: Vec<_> = self.modules.iter().collect();
+        let config = uri
+            .to_file_path()
+            .ok()
+            .and_then(|p| LintConfig::find(p.parent()?).ok().flatten())
+            .unwrap_or_default();
+        let diagnostics = match parse(text, ParserOptions::default()) {
+            Ok(mut program) => program
+                .lint_with_modules(&modules)
+                .into_iter()
+                .filter_map(|w| {
+                    let severity = match config.level(&w.warning) {
+                        LintLevel::Allow => return None,
+                        LintLevel::Warn => DiagnosticSeverity::WARNING,
+                        LintLevel::Deny => DiagnosticSeverity::ERROR,
+                    };
+                    Some(Diagnostic::new(
+                        w.line.and_then(line_range).unwrap_or(start),
+                        Some(severity),
+                        None,
+                        Some("rigz".to_string()),
+                        w.warning.to_string(),
+                        None,
+                        None,
+                    ))
+                })
+                .collect(),
+            Err(e) => vec![Diagnostic::new(
+                start,
+                Some(DiagnosticSeverity::ERROR),
+                None,
+                Some("rigz".to_string()),
+                e.to_string(),
+                None,
+                None,
+            )],
+        };
+        self.client
+            .publish_diagnostics(uri, diagnostics, None)
+            .await;
+    }
 }
 
 #[tokio::main]
@@ -97,6 +232,6 @@ async fn main() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| Backend::new(client));
+    let (service, socket) = LspService::new(Backend::new);
     Server::new(stdin, stdout, socket).serve(service).await;
 }
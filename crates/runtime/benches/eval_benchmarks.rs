@@ -33,5 +33,35 @@ fn expressions(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, expressions);
+// loops use collection & conversion functions from the default modules
+fn loops(c: &mut Criterion) {
+    c.bench_function("eval: list comprehension", |b| {
+        b.iter(|| {
+            let mut runtime = Runtime::new();
+            let _ = runtime
+                .eval("[for v in 0..1000: v * v if v % 2 == 0]".to_string())
+                .expect("Run Failed");
+        })
+    });
+
+    c.bench_function("eval: map comprehension", |b| {
+        b.iter(|| {
+            let mut runtime = Runtime::new();
+            let _ = runtime
+                .eval("{for k, v in (0..1000).to_map: v, k * 2}".to_string())
+                .expect("Run Failed");
+        })
+    });
+
+    c.bench_function("eval: reduce", |b| {
+        b.iter(|| {
+            let mut runtime = Runtime::new();
+            let _ = runtime
+                .eval("(0..1000).to_list.reduce(0, |acc, v| acc + v)".to_string())
+                .expect("Run Failed");
+        })
+    });
+}
+
+criterion_group!(benches, expressions, loops);
 criterion_main!(benches);
@@ -1,6 +1,9 @@
 mod modules;
 mod prepare;
 pub mod runtime;
+mod timings;
 
 pub use modules::*;
-pub use runtime::{eval, Runtime, RuntimeError};
+pub use prepare::DefaultImportResolver;
+pub use runtime::{eval, eval_within, Runtime, RuntimeError};
+pub use timings::{Phase, Timing, Timings};
@@ -3,8 +3,6 @@ use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
 use rigz_vm::{out, outln};
-use std::cell::RefCell;
-use std::rc::Rc;
 
 derive_module! {
     r#"
@@ -21,11 +19,22 @@ derive_module! {
         fn Any.to_i -> Int!
         fn Any.to_f -> Float!
         fn Any.to_n -> Number!
+        fn Any.to_d -> Number!
         fn Any.to_s -> String
         fn Any.to_list -> List!
         fn Any.to_map -> Map!
         fn Any.type -> String
         fn Any.get(index) -> Any!?
+        fn Any.cmp(other: Any) -> Int
+        fn Any.freeze -> Any
+        fn Any.is_frozen -> Bool
+
+        fn Any.tap(func: |Any| -> Any) -> Any
+            _ = func self
+            self
+        end
+
+        fn Any.then(func: |Any| -> Any) -> Any = func self
 
         fn format(template: String, var args) -> String
         fn print(var args) -> None
@@ -125,6 +134,19 @@ impl RigzAny for AnyModule {
         this.to_number()
     }
 
+    fn any_to_d(&self, this: ObjectValue) -> Result<Number, VMError> {
+        // parsed directly so trailing zeros are kept, "1.50".to_d is 1.50 instead of 1.5
+        if let ObjectValue::Primitive(PrimitiveValue::String(s)) = &this {
+            return match s.trim().replace('_', "").parse::<Decimal>() {
+                Ok(d) => Ok(d.into()),
+                Err(e) => Err(VMError::ConversionError(format!(
+                    "Cannot convert {s} to Decimal: {e}"
+                ))),
+            };
+        }
+        Ok(this.to_number()?.to_decimal().into())
+    }
+
     fn any_to_s(&self, this: ObjectValue) -> String {
         this.to_string()
     }
@@ -149,6 +171,19 @@ impl RigzAny for AnyModule {
         this.get(&index)
     }
 
+    /// -1, 0, or 1 using the same order as `List.sort`, values of different types are ordered by type
+    fn any_cmp(&self, this: ObjectValue, other: ObjectValue) -> i64 {
+        this.cmp(&other) as i64
+    }
+
+    fn any_freeze(&self, this: ObjectValue) -> ObjectValue {
+        this.freeze()
+    }
+
+    fn any_is_frozen(&self, this: ObjectValue) -> bool {
+        this.is_frozen()
+    }
+
     fn format(&self, template: String, args: Vec<ObjectValue>) -> String {
         let mut res = template;
         for arg in args {
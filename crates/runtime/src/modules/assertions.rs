@@ -32,11 +32,15 @@ impl RigzAssertions for AssertionsModule {
         rhs: ObjectValue,
         message: String,
     ) -> Result<(), VMError> {
-        if lhs == rhs {
+        if lhs.loose_eq(&rhs) {
             return Ok(());
         }
 
-        let base = format!("\tLeft: {lhs}\n\t\tRight: {rhs}");
+        let base = format!(
+            "\tLeft: {}\n\t\tRight: {}",
+            pretty(&lhs, &PrettyOptions::compact()),
+            pretty(&rhs, &PrettyOptions::compact())
+        );
         let message = if message.is_empty() {
             format!("Assertion Failed\n\t{base}")
         } else {
@@ -52,11 +56,15 @@ impl RigzAssertions for AssertionsModule {
         rhs: ObjectValue,
         message: String,
     ) -> Result<(), VMError> {
-        if lhs != rhs {
+        if !lhs.loose_eq(&rhs) {
             return Ok(());
         }
 
-        let base = format!("\tLeft: {lhs}\n\t\tRight: {rhs}");
+        let base = format!(
+            "\tLeft: {}\n\t\tRight: {}",
+            pretty(&lhs, &PrettyOptions::compact()),
+            pretty(&rhs, &PrettyOptions::compact())
+        );
         let message = if message.is_empty() {
             format!("Assertion Failed\n\t{base}")
         } else {
@@ -0,0 +1,293 @@
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use rigz_vm::{coroutine_wait_start, retry_call, Instant};
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+derive_object! {
+    "Channel",
+    struct Channel {
+        pub id: i64,
+        // none for unbounded channels
+        capacity: Option<usize>,
+        // clones share the queue, deserialized channels start with an empty queue
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        queue: Tracked<Queue>,
+    },
+    r#"object Channel
+        Self(capacity: Number? = none)
+        fn Self.capacity -> Int?
+        fn Self.len -> Int
+        fn Self.is_closed -> Bool
+        fn Self.send(value) -> None!
+        fn Self.receive(timeout: Number? = none) -> Any!
+        fn Self.try_receive -> Any?
+        fn Self.close -> None
+    end
+    "#
+}
+
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+#[derive(Debug, Default)]
+struct QueueState {
+    values: VecDeque<ObjectValue>,
+    closed: bool,
+}
+
+/// Values sent to a channel, shared by every copy of the channel including the ones sent to other processes
+#[derive(Debug, Default)]
+struct Queue {
+    state: Mutex<QueueState>,
+    // notified when a value is sent or received, or the channel is closed
+    changed: Condvar,
+}
+
+impl Held for QueueState {
+    fn values(&self) -> Vec<ObjectValue> {
+        self.values.iter().cloned().collect()
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+impl Collectable for Queue {
+    fn held(&self) -> &Mutex<dyn Held> {
+        &self.state
+    }
+}
+
+impl Queue {
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(
+        &self,
+        state: MutexGuard<'a, QueueState>,
+        deadline: Option<Instant>,
+    ) -> MutexGuard<'a, QueueState> {
+        match deadline {
+            None => self.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                self.changed
+                    .wait_timeout(state, remaining)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+        }
+    }
+}
+
+impl Channel {
+    fn new(capacity: Option<usize>) -> Self {
+        Channel {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            capacity,
+            queue: Default::default(),
+        }
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Channel {
+    fn shared_id(&self) -> Option<usize> {
+        Some(Tracked::id(&self.queue))
+    }
+
+    fn contained_values(&self) -> Vec<ObjectValue> {
+        self.queue.held_values()
+    }
+
+    fn poll_receive(&self) -> Result<Option<ObjectValue>, VMError> {
+        let mut state = self.queue.state();
+        match state.values.pop_front() {
+            Some(v) => {
+                self.queue.changed.notify_all();
+                Ok(Some(v))
+            }
+            None if state.closed => Err(VMError::RuntimeError(
+                "`receive` failed, channel is closed".to_string(),
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ChannelObject for Channel {
+    fn capacity(&self) -> Option<i64> {
+        self.capacity.map(|c| c as i64)
+    }
+
+    fn len(&self) -> i64 {
+        self.queue.state().values.len() as i64
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queue.state().closed
+    }
+
+    /// Waits while a bounded channel is full, values that contain this channel are rejected.
+    /// Processes running as coroutines yield to the other coroutines instead of blocking the thread
+    fn send(&self, value: ObjectValue) -> Result<(), VMError> {
+        if self.shared_id().is_some_and(|id| value.references(id)) {
+            return Err(VMError::RuntimeError(
+                "Cannot send a value that contains the channel to itself".to_string(),
+            ));
+        }
+        let mut state = self.queue.state();
+        loop {
+            if state.closed {
+                return Err(VMError::RuntimeError(format!(
+                    "Cannot send {value}, channel is closed"
+                )));
+            }
+            match self.capacity {
+                Some(c) if state.values.len() >= c => {
+                    if coroutine_wait_start().is_some() {
+                        retry_call();
+                        return Ok(());
+                    }
+                    state = self.queue.wait(state, None)
+                }
+                _ => break,
+            }
+        }
+        state.values.push_back(value);
+        self.queue.changed.notify_all();
+        Ok(())
+    }
+
+    /// Oldest value sent to the channel, waits for a value unless the channel is closed.
+    /// Processes running as coroutines yield to the other coroutines instead of blocking the thread
+    fn receive(&self, timeout: Option<Number>) -> Result<ObjectValue, VMError> {
+        let timeout = timeout.map(|t| t.to_usize()).transpose()?;
+        let coroutine = coroutine_wait_start();
+        let start = coroutine.unwrap_or_else(Instant::now);
+        let deadline = timeout.map(|t| start + Duration::from_millis(t as u64));
+        let mut state = self.queue.state();
+        loop {
+            if let Some(v) = state.values.pop_front() {
+                self.queue.changed.notify_all();
+                return Ok(v);
+            }
+            if state.closed {
+                return Err(VMError::RuntimeError(
+                    "`receive` failed, channel is closed".to_string(),
+                ));
+            }
+            if let (Some(time), Some(deadline)) = (timeout, deadline) {
+                if Instant::now() >= deadline {
+                    return Err(VMError::RuntimeError(format!(
+                        "`receive` timed out after {time}ms"
+                    )));
+                }
+            }
+            if coroutine.is_some() {
+                retry_call();
+                return Ok(ObjectValue::default());
+            }
+            state = self.queue.wait(state, deadline);
+        }
+    }
+
+    fn try_receive(&self) -> Option<ObjectValue> {
+        let v = self.queue.state().values.pop_front();
+        if v.is_some() {
+            self.queue.changed.notify_all();
+        }
+        v
+    }
+
+    /// Values already sent can still be received
+    fn close(&self) {
+        self.queue.state().closed = true;
+        self.queue.changed.notify_all();
+    }
+}
+
+impl CreateObject for Channel {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let v = args.first()?;
+        let capacity = match v.borrow().deref() {
+            ObjectValue::Primitive(PrimitiveValue::None) => None,
+            v => match v.to_usize()? {
+                0 => {
+                    return Err(VMError::UnsupportedOperation(format!(
+                        "Cannot create {} with a capacity of 0",
+                        Self::name()
+                    )))
+                }
+                c => Some(c),
+            },
+        };
+        Ok(Channel::new(capacity))
+    }
+}
+
+derive_module! {
+    [Channel],
+    r#"trait Channel
+        fn create(capacity: Number? = none) -> Channel::Channel!
+            Channel::Channel.new capacity
+        end
+    end"#
+}
+
+impl RigzChannel for ChannelModule {}
+
+#[cfg(test)]
+pub mod channel_tests {
+    use crate::modules::channel::{Channel, ChannelObject};
+    use rigz_core::{IndexMap, Number, ObjectValue};
+    use std::thread;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn clones_share_values() {
+        let sender = Channel::new(None);
+        let receiver = sender.clone();
+        let t = thread::spawn(move || {
+            for i in 0..3 {
+                sender.send(i.into()).unwrap();
+            }
+            sender.close();
+        });
+        let received: Vec<_> = (0..3).map(|_| receiver.receive(None)).collect();
+        t.join().unwrap();
+        assert_eq!(received, vec![Ok(0.into()), Ok(1.into()), Ok(2.into())]);
+        assert!(receiver.receive(None).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn bounded_send_waits() {
+        let sender = Channel::new(Some(1));
+        let receiver = sender.clone();
+        sender.send(1.into()).unwrap();
+        let t = thread::spawn(move || sender.send(2.into()));
+        assert_eq!(receiver.receive(None), Ok(1.into()));
+        t.join().unwrap().unwrap();
+        assert_eq!(receiver.try_receive(), Some(2.into()));
+        assert_eq!(receiver.try_receive(), None::<ObjectValue>);
+        assert!(receiver.receive(Some(Number::Int(1))).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn send_rejects_cycles() {
+        let channel = Channel::new(None);
+        let mut map = IndexMap::new();
+        map.insert("self".into(), ObjectValue::new(channel.clone()));
+        assert!(channel.send(ObjectValue::Map(map)).is_err());
+        assert_eq!(channel.len(), 0);
+    }
+}
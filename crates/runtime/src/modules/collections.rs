@@ -2,8 +2,6 @@ use itertools::Itertools;
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 derive_module! {
     r#"import trait Collections
@@ -15,6 +13,19 @@ derive_module! {
             [for v in self: func v]
         end
 
+        fn List.par_map(func: |Any| -> Any) -> List
+            [for v in self: func v]
+        end
+
+        fn List.par_filter(func: |Any| -> Bool) -> List
+            [for v in self: v if func v]
+        end
+
+        fn List.par_each(func: |Any| -> Any) -> None
+            [for v in self: func v]
+            none
+        end
+
         fn Map.filter(func: |Any, Any| -> Bool) -> Map
             {for k, v in self: k, v if func k, v}
         end
@@ -32,6 +43,9 @@ derive_module! {
         fn List.split_first -> (Any?, List)
         fn List.split_last -> (Any?, List)
         fn List.zip(other: List) -> Map
+        fn List.windows(size: Number) -> List!
+        fn List.pairs -> List
+        fn List.flatten -> List
 
         fn Map.split_first -> ((Any, Any)?, Map)
         fn Map.split_last -> ((Any, Any)?, Map)
@@ -43,7 +57,6 @@ derive_module! {
             else
                 (first, rest) = self.split_first
                 next = func init, first
-                puts first, init, next, self
                 rest.reduce next, func
             end
         end
@@ -67,7 +80,38 @@ derive_module! {
             self.reduce(0, |res, _, next| res + next)
         end
 
+        fn List.flat_map(func: |Any| -> Any) -> List
+            [for v in self: func v].flatten
+        end
+
+        fn List.take_while(func: |Any| -> Bool) -> List
+            if !self
+                []
+            else
+                (first, rest) = self.split_first
+                if func first
+                    [first] + rest.take_while(func)
+                else
+                    []
+                end
+            end
+        end
+
+        fn List.drop_while(func: |Any| -> Bool) -> List
+            if !self
+                []
+            else
+                (first, rest) = self.split_first
+                if func first
+                    rest.drop_while func
+                else
+                    self
+                end
+            end
+        end
+
         fn List.empty = self.to_bool
+        fn List.len -> Int
         fn List.first -> Any?
         fn List.last -> Any?
         fn mut List.push(var value)
@@ -132,6 +176,36 @@ impl RigzCollections for CollectionsModule {
         this.into_iter().zip(other).collect()
     }
 
+    fn list_windows(
+        &self,
+        this: Vec<ObjectValue>,
+        size: Number,
+    ) -> Result<Vec<ObjectValue>, VMError> {
+        let size = size.to_usize()?;
+        if size == 0 {
+            return Err(VMError::RuntimeError(
+                "List.windows size must be greater than 0".to_string(),
+            ));
+        }
+        Ok(this.windows(size).map(|w| w.to_vec().into()).collect())
+    }
+
+    fn list_pairs(&self, this: Vec<ObjectValue>) -> Vec<ObjectValue> {
+        this.into_iter()
+            .tuple_windows()
+            .map(|(a, b)| ObjectValue::Tuple(vec![a, b]))
+            .collect()
+    }
+
+    fn list_flatten(&self, this: Vec<ObjectValue>) -> Vec<ObjectValue> {
+        this.into_iter()
+            .flat_map(|v| match v {
+                ObjectValue::List(l) => l,
+                v => vec![v],
+            })
+            .collect()
+    }
+
     fn map_split_first(
         &self,
         this: IndexMap<ObjectValue, ObjectValue>,
@@ -180,6 +254,10 @@ impl RigzCollections for CollectionsModule {
         ObjectValue::Tuple(this)
     }
 
+    fn list_len(&self, this: Vec<ObjectValue>) -> i64 {
+        this.len() as i64
+    }
+
     fn list_first(&self, this: Vec<ObjectValue>) -> Option<ObjectValue> {
         this.first().cloned()
     }
@@ -239,7 +317,7 @@ impl RigzCollections for CollectionsModule {
         key: ObjectValue,
         value: ObjectValue,
     ) {
-        this.insert(key, value);
+        this.insert(key.map_key(), value);
     }
 
     fn map_with(
@@ -249,7 +327,7 @@ impl RigzCollections for CollectionsModule {
         value: Vec<ObjectValue>,
     ) -> IndexMap<ObjectValue, ObjectValue> {
         let mut this = this;
-        for (k, v) in key.into_iter().zip(value.into_iter()) {
+        for (k, v) in key.into_iter().zip(value) {
             this.insert(k, v);
         }
         this
@@ -0,0 +1,25 @@
+use crate::modules::secret::Secret;
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+
+derive_module! {
+    r#"
+trait Crypto
+    fn constant_time_eq(a, b) -> Bool
+end
+"#
+}
+
+impl RigzCrypto for CryptoModule {
+    /// Secrets are compared using their revealed value, only the length of the inputs can leak through timing
+    fn constant_time_eq(&self, a: ObjectValue, b: ObjectValue) -> bool {
+        let a = Secret::exposed(&a);
+        let b = Secret::exposed(&b);
+        let mut diff = u8::from(a.len() != b.len());
+        for (x, y) in a.bytes().zip(b.bytes()) {
+            diff |= x ^ y;
+        }
+        diff == 0
+    }
+}
@@ -0,0 +1,223 @@
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+derive_object! {
+    "CSV",
+    struct Rows {
+        pub path: String,
+        headers: bool,
+        delimiter: u8,
+        // clones share the position in the file, deserialized rows start over from the first row
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        reader: Arc<Mutex<Option<RowReader>>>,
+    },
+    r#"object Rows
+        Self(path: String, headers = true, delimiter = ",")
+        fn Self.next -> Any!?
+    end
+    "#
+}
+
+derive_module! {
+    nondeterministic,
+    [Rows],
+    r#"trait CSV
+        fn parse(input: String, headers = true, delimiter = ",") -> List!
+        fn generate(rows: List, headers = true, delimiter = ",") -> String!
+
+        fn rows(path: String, headers = true, delimiter = ",") -> CSV::Rows!
+            CSV::Rows.new path, headers, delimiter
+        end
+
+        fn each_row(path: String, func: |Any| -> Any, headers = true, delimiter = ",") -> None
+            rows = CSV.rows path, headers, delimiter
+            [for row in rows: none if func row]
+            none
+        end
+    end"#
+}
+
+/// Rows are maps keyed by the header row when `headers` is set, otherwise lists. Every value is a String
+struct RowReader {
+    reader: csv::Reader<Box<dyn Read + Send>>,
+    headers: Option<Vec<String>>,
+}
+
+impl RowReader {
+    fn new(input: Box<dyn Read + Send>, headers: bool, delimiter: u8) -> Result<Self, VMError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(headers)
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let headers = if headers {
+            let h = reader.headers().map_err(csv_error)?;
+            Some(h.iter().map(String::from).collect())
+        } else {
+            None
+        };
+        Ok(RowReader { reader, headers })
+    }
+
+    fn next(&mut self) -> Result<Option<ObjectValue>, VMError> {
+        let mut record = csv::StringRecord::new();
+        if !self.reader.read_record(&mut record).map_err(csv_error)? {
+            return Ok(None);
+        }
+        let row = match &self.headers {
+            None => ObjectValue::List(record.iter().map(ObjectValue::from).collect()),
+            Some(headers) => ObjectValue::Map(
+                headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(h, v)| (h.as_str().into(), v.into()))
+                    .collect(),
+            ),
+        };
+        Ok(Some(row))
+    }
+}
+
+fn csv_error(e: csv::Error) -> VMError {
+    VMError::RuntimeError(format!("Failed to read csv - {e}"))
+}
+
+/// Strings aren't escaped in rigz, `"\t"` is read as a tab
+fn delimiter(value: &str) -> Result<u8, VMError> {
+    match value.as_bytes() {
+        [b'\\', b't'] => Ok(b'\t'),
+        [b] => Ok(*b),
+        _ => Err(VMError::RuntimeError(format!(
+            "CSV delimiter must be a single character, received {value:?}"
+        ))),
+    }
+}
+
+impl Rows {
+    fn open(&self) -> Result<RowReader, VMError> {
+        let file = File::open(&self.path)
+            .map_err(|e| VMError::RuntimeError(format!("Failed to open {} - {e}", self.path)))?;
+        RowReader::new(Box::new(file), self.headers, self.delimiter)
+    }
+
+    fn reader(&self) -> MutexGuard<'_, Option<RowReader>> {
+        self.reader.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Rows {
+    fn is_stream(&self) -> bool {
+        true
+    }
+
+    fn next_value(&self) -> Result<Option<ObjectValue>, VMError> {
+        self.next()
+    }
+
+    /// Rows that haven't been read yet
+    fn to_list(&self) -> Result<Vec<ObjectValue>, VMError> {
+        let mut rows = vec![];
+        while let Some(row) = self.next()? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+impl RowsObject for Rows {
+    /// Only the current row is read from the file, none once every row was read
+    fn next(&self) -> Result<Option<ObjectValue>, VMError> {
+        let mut reader = self.reader();
+        if reader.is_none() {
+            *reader = Some(self.open()?);
+        }
+        reader.as_mut().expect("reader was opened").next()
+    }
+}
+
+impl CreateObject for Rows {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let [path, headers, d] = args.take()?;
+        let rows = Rows {
+            path: path.borrow().to_string(),
+            headers: headers.borrow().to_bool(),
+            delimiter: delimiter(&d.borrow().to_string())?,
+            reader: Default::default(),
+        };
+        // missing files fail here rather than on the first row
+        *rows.reader() = Some(rows.open()?);
+        Ok(rows)
+    }
+}
+
+fn cell(value: &ObjectValue) -> String {
+    match value {
+        ObjectValue::Primitive(PrimitiveValue::None) => String::new(),
+        v => v.to_string(),
+    }
+}
+
+impl RigzCSV for CSVModule {
+    fn parse(&self, input: String, headers: bool, d: String) -> Result<Vec<ObjectValue>, VMError> {
+        let input = std::io::Cursor::new(input.into_bytes());
+        let mut reader = RowReader::new(Box::new(input), headers, delimiter(&d)?)?;
+        let mut rows = vec![];
+        while let Some(row) = reader.next()? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Rows are maps or lists, the header row is the keys of the first map
+    fn generate(
+        &self,
+        rows: Vec<ObjectValue>,
+        headers: bool,
+        d: String,
+    ) -> Result<String, VMError> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter(&d)?)
+            .flexible(true)
+            .from_writer(vec![]);
+        let mut keys: Option<Vec<ObjectValue>> = None;
+        for row in &rows {
+            let record: Vec<String> = match row.unfrozen() {
+                ObjectValue::List(l) | ObjectValue::Tuple(l) => l.iter().map(cell).collect(),
+                row => {
+                    let row = row.to_map()?;
+                    if keys.is_none() {
+                        let k: Vec<_> = row.keys().cloned().collect();
+                        if headers {
+                            writer
+                                .write_record(k.iter().map(cell))
+                                .map_err(write_error)?;
+                        }
+                        keys = Some(k);
+                    }
+                    keys.iter()
+                        .flatten()
+                        .map(|k| row.get(k).map(cell).unwrap_or_default())
+                        .collect()
+                }
+            };
+            writer.write_record(&record).map_err(write_error)?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| VMError::RuntimeError(format!("Failed to write csv - {e}")))?;
+        String::from_utf8(bytes)
+            .map_err(|e| VMError::RuntimeError(format!("Failed to write csv - {e}")))
+    }
+}
+
+fn write_error(e: csv::Error) -> VMError {
+    VMError::RuntimeError(format!("Failed to write csv - {e}"))
+}
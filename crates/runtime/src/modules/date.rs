@@ -1,23 +1,31 @@
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
+use std::ops::Deref;
 
 derive_module! {
+    [DateTime],
     r#"
 trait Date
-    fn now -> Number
-    fn utc -> Number
+    fn now -> DateTime
+    fn utc -> DateTime
+    fn parse(value: String, pattern: String? = none) -> DateTime!
 end
 "#
 }
 
-// todo use object instead of Number
 impl RigzDate for DateModule {
-    fn now(&self) -> Number {
-        chrono::Local::now().timestamp_millis().into()
+    // DateTime is always UTC, `now` is kept for scripts written before it existed
+    fn now(&self) -> ObjectValue {
+        DateTime::now().into()
     }
 
-    fn utc(&self) -> Number {
-        chrono::Utc::now().timestamp_millis().into()
+    fn utc(&self) -> ObjectValue {
+        DateTime::now().into()
+    }
+
+    /// RFC 3339 without a pattern, otherwise a strftime pattern, i.e. `Date.parse "05/01/2024", "%m/%d/%Y"`
+    fn parse(&self, value: String, pattern: Option<String>) -> Result<ObjectValue, VMError> {
+        Ok(DateTime::parse(&value, pattern.as_deref())?.into())
     }
 }
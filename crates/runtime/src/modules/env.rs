@@ -0,0 +1,210 @@
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
+
+derive_module! {
+    nondeterministic,
+    r#"trait Env
+        fn get(name: String) -> String?
+        fn fetch(name: String, default: String) -> String
+        fn set(name: String, value: String) -> None
+        fn vars -> Map
+        fn load(path = ".env", overwrite = false) -> Int!
+    end"#
+}
+
+/// Parses the contents of a `.env` file, later entries replace earlier ones.
+///
+/// Each line is `KEY=value` with an optional `export ` prefix, `#` starts a comment.
+/// Single quoted values are used as is, double quoted values support `\n`, `\t`, `\"`, & `\\`
+/// and can span multiple lines.
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, VMError> {
+    let mut vars: Vec<(String, String)> = Vec::new();
+    let mut lines = contents.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(VMError::RuntimeError(format!(
+                "Invalid env file entry on line {}, expected KEY=value - {line}",
+                index + 1
+            )));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(VMError::RuntimeError(format!(
+                "Invalid env file key on line {} - {key}",
+                index + 1
+            )));
+        }
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut raw = value[1..].to_string();
+                // quoted values continue until the closing quote
+                while !closes(&raw, quote) {
+                    match lines.next() {
+                        None => {
+                            return Err(VMError::RuntimeError(format!(
+                                "Unterminated quote for {key} on line {}",
+                                index + 1
+                            )))
+                        }
+                        Some((_, next)) => {
+                            raw.push('\n');
+                            raw.push_str(next);
+                        }
+                    }
+                }
+                let end = raw.rfind(quote).unwrap_or(raw.len());
+                let raw = &raw[..end];
+                if quote == '"' {
+                    unescape(raw)
+                } else {
+                    raw.to_string()
+                }
+            }
+            _ => match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.to_string(),
+            },
+        };
+        match vars.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => vars.push((key.to_string(), value)),
+        }
+    }
+    Ok(vars)
+}
+
+fn closes(raw: &str, quote: char) -> bool {
+    let mut escaped = false;
+    for c in raw.chars() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return true,
+            _ => escaped = false,
+        }
+    }
+    false
+}
+
+fn unescape(raw: &str) -> String {
+    let mut result = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Loads each file into the process environment, later files replace values from earlier files.
+/// Variables that were already set are kept unless `overwrite` is true. Returns the number of variables set.
+pub fn load_env_files<P: AsRef<Path>>(paths: &[P], overwrite: bool) -> Result<usize, VMError> {
+    let mut vars: Vec<(String, String)> = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        let contents = read_to_string(path).map_err(|e| {
+            VMError::RuntimeError(format!("Failed to read env file {} - {e}", path.display()))
+        })?;
+        for (key, value) in parse_env_file(&contents)? {
+            match vars.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => *v = value,
+                None => vars.push((key, value)),
+            }
+        }
+    }
+
+    let mut set = 0;
+    for (key, value) in vars {
+        if !overwrite && env::var_os(&key).is_some() {
+            continue;
+        }
+        env::set_var(key, value);
+        set += 1;
+    }
+    Ok(set)
+}
+
+impl RigzEnv for EnvModule {
+    fn get(&self, name: String) -> Option<String> {
+        env::var(name).ok()
+    }
+
+    fn fetch(&self, name: String, default: String) -> String {
+        env::var(name).unwrap_or(default)
+    }
+
+    fn set(&self, name: String, value: String) {
+        env::set_var(name, value)
+    }
+
+    fn vars(&self) -> IndexMap<ObjectValue, ObjectValue> {
+        let mut vars: Vec<_> = env::vars().collect();
+        vars.sort();
+        vars.into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect()
+    }
+
+    fn load(&self, path: String, overwrite: bool) -> Result<i64, VMError> {
+        load_env_files(&[path], overwrite).map(|n| n as i64)
+    }
+}
+
+#[cfg(test)]
+pub mod env_tests {
+    use crate::modules::env::parse_env_file;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn parse() {
+        let vars = parse_env_file(
+            r#"
+            # database
+            export DB_HOST=localhost # inline comment
+            DB_PASS='p#ss word'
+            GREETING="hello\nworld"
+            MULTI="a
+            b"
+            DB_HOST=127.0.0.1
+            EMPTY=
+            "#,
+        )
+        .unwrap();
+        let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(
+            vars,
+            vec![
+                ("DB_HOST", "127.0.0.1"),
+                ("DB_PASS", "p#ss word"),
+                ("GREETING", "hello\nworld"),
+                ("MULTI", "a\n            b"),
+                ("EMPTY", ""),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn invalid() {
+        assert!(parse_env_file("NAME").is_err());
+        assert!(parse_env_file("A B=1").is_err());
+        assert!(parse_env_file("A=\"unterminated").is_err());
+    }
+}
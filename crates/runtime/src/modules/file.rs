@@ -1,44 +1,420 @@
 use rigz_ast::*;
-use rigz_ast_derive::derive_module;
+use rigz_ast_derive::{derive_module, derive_object};
 use rigz_core::*;
 
+derive_object! {
+    "File",
+    struct Mmap {
+        pub path: String,
+        // clones share the mapping, deserialized objects are empty until `File.mmap` is called again
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        source: Arc<Mapping>,
+        // byte offset of the next line read by `next`, shared by clones like the mapping
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        cursor: Arc<Mutex<usize>>,
+    },
+    r#"object Mmap
+        Self(path: String)
+        fn Self.len -> Int
+        fn Self.is_empty -> Bool
+        fn Self.slice(start: Int, stop: Int? = none) -> String
+        fn Self.bytes(start: Int, stop: Int? = none) -> List
+        fn Self.find(value: String, start: Int = 0) -> Int?
+        fn Self.lines(skip: Int = 0, take: Int? = none) -> List
+        fn Self.next -> String?
+    end
+    "#
+}
+
 derive_module! {
+    nondeterministic,
+    [Mmap],
     r#"trait File
         fn read(path: String, encoding = "utf-8") -> String!
         fn write(path: String, contents: String, encoding = "utf-8") -> None!
+        fn read_async(path: String, encoding = "utf-8") -> Task::Task
+        fn write_async(path: String, contents: String, encoding = "utf-8") -> Task::Task
+
+        # maps the file into memory instead of reading it, pages are loaded as they're used.
+        # `File.write` fails for the file until every copy of the map is dropped
+        fn mmap(path: String) -> File::Mmap!
+            File::Mmap.new path
+        end
     end"#
 }
 
-use std::fs::{read_to_string, File};
-use std::io::Write;
+use crate::modules::task::Task;
+use std::fs::{read_to_string, File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Write};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
 
-impl RigzFile for FileModule {
-    fn read(&self, path: String, encoding: String) -> Result<String, VMError> {
-        let path = path.to_string();
-        let encoding = encoding.to_string();
-        if encoding.as_str().to_lowercase() != "utf-8" {
-            return Err(VMError::RuntimeError(format!(
-                "Non utf-8 files are not supported yet, received {encoding}"
-            )));
+/// Read-only mapping of a file, unmapped once every copy of the object is dropped.
+///
+/// The file holds a shared lock while it's mapped, `File.write` & other programs that lock the file can't change
+/// it. Values are copied out of the mapping so none of them outlive it.
+#[derive(Debug, Default)]
+struct Mapping {
+    // `None` for deserialized objects, the file is kept open to hold the lock
+    map: Option<(File, memmap2::Mmap)>,
+    // offset of every line, built by the first `lines` call
+    lines: OnceLock<Lines>,
+}
+
+#[derive(Debug)]
+struct Lines {
+    starts: Vec<usize>,
+    // length of the contents without the final `\n`
+    end: usize,
+}
+
+/// Platforms without file locks (i.e. wasm) don't protect mapped files
+fn lock(path: &str, result: Result<(), TryLockError>) -> Result<(), VMError> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(TryLockError::Error(e)) if e.kind() == ErrorKind::Unsupported => Ok(()),
+        Err(TryLockError::WouldBlock) => Err(VMError::RuntimeError(format!(
+            "{path} is locked, it's memory mapped or being written"
+        ))),
+        Err(TryLockError::Error(e)) => Err(VMError::RuntimeError(format!(
+            "Failed to lock {path} - {e}"
+        ))),
+    }
+}
+
+impl Mapping {
+    fn open(path: &str) -> Result<Self, VMError> {
+        let map_error = |e| VMError::RuntimeError(format!("Failed to map {path} - {e}"));
+        let file = File::open(path).map_err(map_error)?;
+        lock(path, file.try_lock_shared())?;
+        // SAFETY: the mapping is read-only & only read through `bytes`, the shared lock keeps `File.write` from
+        // truncating or changing the file until it's unmapped. Like any mapping, programs that ignore the lock
+        // can still change the file underneath it
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(map_error)?;
+        Ok(Mapping {
+            map: Some((file, map)),
+            lines: OnceLock::new(),
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match &self.map {
+            None => &[],
+            Some((_, map)) => map,
+        }
+    }
+
+    /// Length of the contents without the final `\n`, lines are split on `\n`
+    fn content_len(&self) -> usize {
+        let bytes = self.bytes();
+        bytes.strip_suffix(b"\n").unwrap_or(bytes).len()
+    }
+
+    fn lines(&self) -> &Lines {
+        self.lines.get_or_init(|| {
+            let end = self.content_len();
+            let mut starts = if end == 0 { Vec::new() } else { vec![0] };
+            starts.extend(
+                self.bytes()[..end]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, b)| **b == b'\n')
+                    .map(|(i, _)| i + 1),
+            );
+            Lines { starts, end }
+        })
+    }
+
+    fn line(&self, index: usize) -> Option<&[u8]> {
+        let lines = self.lines();
+        let start = *lines.starts.get(index)?;
+        let end = match lines.starts.get(index + 1) {
+            Some(next) => next - 1,
+            None => lines.end,
+        };
+        Some(&self.bytes()[start..end])
+    }
+}
+
+/// A trailing `\r` is removed, invalid utf-8 is replaced with `�`
+fn line_value(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).to_string()
+}
+
+impl Mmap {
+    pub fn open(path: String) -> Result<Self, VMError> {
+        let source = Arc::new(Mapping::open(&path)?);
+        Ok(Mmap {
+            path,
+            source,
+            cursor: Default::default(),
+        })
+    }
+
+    /// Byte offsets, negative offsets count back from the end & both are clamped to the file
+    fn range(&self, start: i64, stop: Option<i64>) -> &[u8] {
+        let bytes = self.source.bytes();
+        let len = bytes.len() as i64;
+        let clamp = |i: i64| if i < 0 { len + i } else { i }.clamp(0, len) as usize;
+        let (start, stop) = (clamp(start), clamp(stop.unwrap_or(len)));
+        if start >= stop {
+            return &[];
+        }
+        &bytes[start..stop]
+    }
+
+    /// Next line after the last one read by `for`, copies of the object share the position
+    fn next_line(&self) -> Option<String> {
+        let mut cursor = self.cursor.lock().unwrap_or_else(|e| e.into_inner());
+        let end = self.source.content_len();
+        if end == 0 || *cursor > end {
+            return None;
+        }
+        let rest = &self.source.bytes()[*cursor..end];
+        let line = match rest.iter().position(|b| *b == b'\n') {
+            Some(i) => &rest[..i],
+            None => rest,
+        };
+        *cursor += line.len() + 1;
+        Some(line_value(line))
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Mmap {
+    fn is_stream(&self) -> bool {
+        true
+    }
+
+    fn next_value(&self) -> Result<Option<ObjectValue>, VMError> {
+        Ok(self.next_line().map(ObjectValue::from))
+    }
+
+    /// Lines that haven't been read yet
+    fn to_list(&self) -> Result<Vec<ObjectValue>, VMError> {
+        let mut lines = vec![];
+        while let Some(line) = self.next_line() {
+            lines.push(line.into());
+        }
+        Ok(lines)
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.source.bytes().is_empty()
+    }
+}
+
+impl MmapObject for Mmap {
+    fn len(&self) -> i64 {
+        self.source.bytes().len() as i64
+    }
+
+    fn is_empty(&self) -> bool {
+        self.source.bytes().is_empty()
+    }
+
+    /// Invalid utf-8 is replaced with `�`
+    fn slice(&self, start: i64, stop: Option<i64>) -> String {
+        String::from_utf8_lossy(self.range(start, stop)).to_string()
+    }
+
+    fn bytes(&self, start: i64, stop: Option<i64>) -> Vec<ObjectValue> {
+        self.range(start, stop)
+            .iter()
+            .map(|b| (*b as i64).into())
+            .collect()
+    }
+
+    /// Byte offset of the first match at or after `start`
+    fn find(&self, value: String, start: i64) -> Option<i64> {
+        let bytes = self.source.bytes();
+        let start = start.clamp(0, bytes.len() as i64) as usize;
+        let needle = value.as_bytes();
+        if needle.is_empty() {
+            return Some(start as i64);
         }
+        bytes[start..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|i| (start + i) as i64)
+    }
+
+    /// Lines are split on `\n`, the offset of every line is found once so paging through a file stays linear.
+    /// Only the returned lines are copied into the VM
+    fn lines(&self, skip: i64, take: Option<i64>) -> Vec<ObjectValue> {
+        let skip = skip.max(0) as usize;
+        let end = match take {
+            None => usize::MAX,
+            Some(take) => skip.saturating_add(take.max(0) as usize),
+        };
+        (skip..end)
+            .map_while(|index| self.source.line(index))
+            .map(|line| line_value(line).into())
+            .collect()
+    }
 
+    fn next(&self) -> Option<String> {
+        self.next_line()
+    }
+}
+
+impl CreateObject for Mmap {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let path = args.first()?.borrow().to_string();
+        Mmap::open(path)
+    }
+}
+
+fn check_encoding(encoding: &str) -> Result<(), VMError> {
+    if encoding.to_lowercase() != "utf-8" {
+        return Err(VMError::RuntimeError(format!(
+            "Non utf-8 files are not supported yet, received {encoding}"
+        )));
+    }
+    Ok(())
+}
+
+/// Mapped files hold a shared lock, the file is locked before it's truncated so their mapping can't fault
+fn write_file(path: &str, contents: &str) -> Result<(), VMError> {
+    let write_error = |e| VMError::RuntimeError(format!("Failed to write {path} - {e}"));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|e| VMError::RuntimeError(format!("Failed to open {path} - {e}")))?;
+    lock(path, file.try_lock())?;
+    file.set_len(0).map_err(write_error)?;
+    file.write_all(contents.as_bytes()).map_err(write_error)
+}
+
+impl RigzFile for FileModule {
+    fn read(&self, path: String, encoding: String) -> Result<String, VMError> {
+        check_encoding(&encoding)?;
         read_to_string(&path)
             .map_err(|e| VMError::RuntimeError(format!("Failed to read {path} - {e}")))
     }
 
     fn write(&self, path: String, contents: String, encoding: String) -> Result<(), VMError> {
-        let path = path.to_string();
-        let contents = contents.to_string();
-        let encoding = encoding.to_string();
-        if encoding.as_str().to_lowercase() != "utf-8" {
-            return Err(VMError::RuntimeError(format!(
-                "Non utf-8 files are not supported yet, received {encoding}"
-            )));
-        }
-        let mut file = File::open(&path)
-            .map_err(|e| VMError::RuntimeError(format!("Failed to open {path} - {e}")))?;
-        file.write_all(contents.as_bytes())
-            .map_err(|e| VMError::RuntimeError(format!("Failed to write {path} - {e}")))?;
-        Ok(())
+        check_encoding(&encoding)?;
+        write_file(&path, &contents)
+    }
+
+    #[cfg(feature = "threaded")]
+    fn read_async(&self, path: String, encoding: String) -> ObjectValue {
+        Task::spawn(async move {
+            check_encoding(&encoding)?;
+            tokio::fs::read_to_string(&path)
+                .await
+                .map(|s| s.into())
+                .map_err(|e| VMError::RuntimeError(format!("Failed to read {path} - {e}")))
+        })
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    fn read_async(&self, path: String, encoding: String) -> ObjectValue {
+        Task::finished(self.read(path, encoding).map(|s| s.into()))
+    }
+
+    #[cfg(feature = "threaded")]
+    fn write_async(&self, path: String, contents: String, encoding: String) -> ObjectValue {
+        Task::spawn(async move {
+            check_encoding(&encoding)?;
+            tokio::task::spawn_blocking(move || write_file(&path, &contents))
+                .await
+                .map_err(|e| VMError::RuntimeError(format!("File write failed - {e}")))?
+                .map(|_| ObjectValue::default())
+        })
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    fn write_async(&self, path: String, contents: String, encoding: String) -> ObjectValue {
+        Task::finished(
+            self.write(path, contents, encoding)
+                .map(|_| ObjectValue::default()),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod file_tests {
+    use crate::modules::file::{write_file, Mmap, MmapObject};
+    use rigz_core::{AsPrimitive, ObjectValue};
+    use std::path::PathBuf;
+    use wasm_bindgen_test::*;
+
+    // test binaries of other checkouts or targets can run at the same time
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rigz_mmap_{}_{name}.txt", std::process::id()))
+    }
+
+    fn mapped(name: &str, contents: &str) -> Mmap {
+        let path = path(name);
+        std::fs::write(&path, contents).unwrap();
+        let map = Mmap::open(path.to_string_lossy().to_string()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        map
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn slices_bytes() {
+        let map = mapped("slices", "hello world");
+        assert_eq!(map.len(), 11);
+        assert_eq!(map.slice(0, Some(5)), "hello");
+        assert_eq!(map.slice(-5, None), "world");
+        assert_eq!(map.slice(8, Some(2)), "");
+        assert_eq!(map.bytes(0, Some(1)), vec![ObjectValue::from(104)]);
+        assert_eq!(map.find("o".into(), 0), Some(4));
+        assert_eq!(map.find("o".into(), 5), Some(7));
+        assert_eq!(map.find("x".into(), 0), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn lines() {
+        let map = mapped("lines", "a\r\nb\n\nc\n");
+        let expected: Vec<ObjectValue> = vec!["a".into(), "b".into(), "".into(), "c".into()];
+        assert_eq!(map.to_list(), Ok(expected));
+        assert_eq!(map.lines(1, Some(1)), vec![ObjectValue::from("b")]);
+        assert_eq!(map.lines(3, None), vec![ObjectValue::from("c")]);
+        assert_eq!(map.lines(4, Some(2)), vec![]);
+        let empty = mapped("empty", "");
+        assert!(empty.is_empty());
+        assert!(empty.to_list().unwrap().is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn stream_lines() {
+        let map = mapped("stream", "a\nb\r\n\nc");
+        let copy = map.clone();
+        assert!(map.is_stream());
+        assert_eq!(map.next_line(), Some("a".to_string()));
+        assert_eq!(copy.next_value(), Ok(Some("b".into())));
+        let rest: Vec<ObjectValue> = vec!["".into(), "c".into()];
+        assert_eq!(map.to_list(), Ok(rest));
+        assert_eq!(map.next_line(), None);
+        // `lines` doesn't move the cursor
+        assert_eq!(copy.lines(0, Some(1)), vec![ObjectValue::from("a")]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn write_waits_for_unmap() {
+        let path = path("locked");
+        std::fs::write(&path, "hello").unwrap();
+        let file = path.to_string_lossy().to_string();
+        let map = Mmap::open(file.clone()).unwrap();
+        let copy = map.clone();
+        assert!(write_file(&file, "hi").is_err());
+        assert_eq!(copy.slice(0, None), "hello");
+        drop(map);
+        assert!(write_file(&file, "hi").is_err());
+        drop(copy);
+        assert_eq!(write_file(&file, "hi"), Ok(()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi");
+        std::fs::remove_file(&path).unwrap();
     }
 }
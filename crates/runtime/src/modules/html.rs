@@ -2,8 +2,6 @@ use rigz_ast::*;
 use rigz_ast_derive::{derive_module, derive_object};
 use rigz_core::*;
 use scraper::Selector;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 derive_object! {
     "Html",
@@ -1,11 +1,10 @@
+use crate::modules::task::Task;
 use log::warn;
 use rigz_ast::*;
 use rigz_ast_derive::{derive_module, derive_object};
 use rigz_core::*;
-use std::any::Any;
-use std::io::Error;
 use std::ops::Deref;
-use std::sync::{Arc, LockResult, RwLock};
+use std::sync::{Arc, RwLock};
 
 derive_object! {
     "Http",
@@ -17,7 +16,7 @@ derive_object! {
         pub headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     },
     r#"object Request
-        Self(path: String, method: String? = none, body: Any? = none, headers: Map? = none)
+        Self(path: String = "", method: String? = none, body: Any? = none, headers: Map? = none)
 
         fn Self.header(key) -> Any?
 
@@ -29,9 +28,7 @@ derive_object! {
 
 impl Request {
     fn from_map(map: IndexMap<ObjectValue, ObjectValue>) -> Result<Self, VMError> {
-        let path = match map.get(&ObjectValue::Primitive(PrimitiveValue::String(
-            "path".to_string(),
-        ))) {
+        let path = match map.get(&ObjectValue::from("path")) {
             None => {
                 return Err(VMError::RuntimeError(format!(
                     "Missing `path`, cannot create request from {map:?}"
@@ -40,20 +37,10 @@ impl Request {
             Some(p) => p.to_string(),
         };
 
-        let method = map
-            .get(&ObjectValue::Primitive(PrimitiveValue::String(
-                "method".to_string(),
-            )))
-            .map(|o| o.to_string());
-        let body = map
-            .get(&ObjectValue::Primitive(PrimitiveValue::String(
-                "body".to_string(),
-            )))
-            .cloned();
-
-        let headers = match map.get(&ObjectValue::Primitive(PrimitiveValue::String(
-            "headers".to_string(),
-        ))) {
+        let method = map.get(&ObjectValue::from("method")).map(|o| o.to_string());
+        let body = map.get(&ObjectValue::from("body")).cloned();
+
+        let headers = match map.get(&ObjectValue::from("headers")) {
             None => None,
             Some(p) => match p {
                 ObjectValue::Map(m) => Some(m.clone()),
@@ -137,7 +124,7 @@ derive_object! {
     struct Response {
         #[serde(skip)]
         #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
-        resp: Arc<RwLock<Option<ureq::Response>>>,
+        resp: Arc<RwLock<Option<ResponseBody>>>,
     },
     r#"object Response
         fn mut Self.text -> String!
@@ -148,10 +135,25 @@ derive_object! {
     end"#
 }
 
+/// Blocking requests read the body when it is used, async requests read it before the task finishes
+#[derive(Debug)]
+enum ResponseBody {
+    Reader(Box<ureq::Response>),
+    Text(String),
+}
+
 impl From<ureq::Response> for Response {
     fn from(value: ureq::Response) -> Self {
         Self {
-            resp: Arc::new(Some(value).into()),
+            resp: Arc::new(Some(ResponseBody::Reader(Box::new(value))).into()),
+        }
+    }
+}
+
+impl From<String> for Response {
+    fn from(value: String) -> Self {
+        Self {
+            resp: Arc::new(Some(ResponseBody::Text(value)).into()),
         }
     }
 }
@@ -184,7 +186,8 @@ impl ResponseObject for Response {
             None => Err(VMError::RuntimeError(
                 "Response has been consumed".to_string(),
             )),
-            Some(r) => match r.into_string() {
+            Some(ResponseBody::Text(s)) => Ok(s),
+            Some(ResponseBody::Reader(r)) => match r.into_string() {
                 Ok(s) => Ok(s),
                 Err(e) => Err(VMError::RuntimeError(format!(
                     "Failed to convert response to string - {e}"
@@ -194,21 +197,8 @@ impl ResponseObject for Response {
     }
 }
 
-#[derive(Debug)]
-pub struct HttpModule {
-    client: ureq::Agent,
-}
-
-impl Default for HttpModule {
-    fn default() -> Self {
-        Self {
-            client: ureq::Agent::new(),
-        }
-    }
-}
-
 derive_module! {
-    HttpModule,
+    nondeterministic,
     [Request, Response],
     r#"trait Http
         fn request -> Http::Request = Http::Request.new
@@ -220,11 +210,13 @@ derive_module! {
         fn patch(path: String, body: Any? = none, headers: Map? = none) -> Http::Response!
         fn post(path: String, body: Any? = none, headers: Map? = none) -> Http::Response!
         fn put(path: String, body: Any? = none, headers: Map? = none) -> Http::Response!
+        fn fetch_async(request: Http::Request) -> Task::Task
+        fn get_async(path: String, headers: Map? = none) -> Task::Task
     end"#
 }
 
 fn set_headers(
-    mut request: ureq::Request,
+    request: ureq::Request,
     headers: Option<IndexMap<ObjectValue, ObjectValue>>,
 ) -> ureq::Request {
     let mut request = request;
@@ -247,7 +239,7 @@ fn to_object(res: Result<ureq::Response, ureq::Error>) -> Result<ObjectValue, VM
     }
 }
 
-fn handle_body(mut req: ureq::Request, body: Option<ObjectValue>) -> Result<ObjectValue, VMError> {
+fn handle_body(req: ureq::Request, body: Option<ObjectValue>) -> Result<ObjectValue, VMError> {
     let resp = match body {
         None => req.call(),
         Some(ObjectValue::Primitive(PrimitiveValue::String(body))) => req.send_string(&body),
@@ -261,20 +253,79 @@ fn handle_body(mut req: ureq::Request, body: Option<ObjectValue>) -> Result<Obje
     to_object(resp)
 }
 
+fn to_request(request: ObjectValue) -> Result<Request, VMError> {
+    match request {
+        ObjectValue::Map(m) => Request::from_map(m),
+        ObjectValue::Object(o) => match o.downcast_ref::<Request>() {
+            Some(r) => Ok(r.clone()),
+            None => Err(VMError::UnsupportedOperation(format!(
+                "Cannot convert {o} to Http::Request"
+            ))),
+        },
+        o => Err(VMError::todo(format!("`fetch` cannot be called with {o}"))),
+    }
+}
+
+/// Connections are pooled for every runtime, the module itself has no state so parsers can share it
+fn client() -> &'static ureq::Agent {
+    static CLIENT: std::sync::OnceLock<ureq::Agent> = std::sync::OnceLock::new();
+    CLIENT.get_or_init(ureq::Agent::new)
+}
+
+#[cfg(feature = "threaded")]
+fn async_client() -> Result<&'static reqwest::Client, VMError> {
+    static CLIENT: std::sync::OnceLock<Result<reqwest::Client, String>> =
+        std::sync::OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::Client::builder()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| VMError::RuntimeError(format!("Failed to create HTTP client {e}")))
+}
+
+/// Sends the request on the I/O runtime without blocking a thread, the body is read before the task finishes
+#[cfg(feature = "threaded")]
+fn send_async(
+    method: reqwest::Method,
+    path: String,
+    headers: Option<IndexMap<ObjectValue, ObjectValue>>,
+    body: Option<ObjectValue>,
+) -> ObjectValue {
+    let headers: Vec<_> = headers
+        .into_iter()
+        .flatten()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let body = body.map(|b| match b {
+        ObjectValue::Primitive(PrimitiveValue::String(body)) => body.into_string(),
+        // todo use json
+        o => o.to_string(),
+    });
+    Task::spawn(async move {
+        let failed = |e: reqwest::Error| VMError::RuntimeError(format!("Request Failed: {e}"));
+        let mut req = async_client()?.request(method, &path);
+        for (k, v) in headers {
+            req = req.header(k, v);
+        }
+        if let Some(body) = body {
+            req = req.body(body);
+        }
+        let res = req
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(failed)?;
+        let text = res.text().await.map_err(failed)?;
+        Ok(ObjectValue::new(Response::from(text)))
+    })
+}
+
 impl RigzHttp for HttpModule {
     fn fetch(&self, request: ObjectValue) -> Result<ObjectValue, VMError> {
-        let r: Request = match request {
-            ObjectValue::Map(m) => Request::from_map(m)?,
-            ObjectValue::Object(o) => match o.downcast_ref::<Request>() {
-                Some(r) => r.clone(),
-                None => {
-                    return Err(VMError::UnsupportedOperation(format!(
-                        "Cannot convert {o} to Http::Request"
-                    )))
-                }
-            },
-            o => return Err(VMError::todo(format!("`fetch` cannot be called with {o}"))),
-        };
+        let r = to_request(request)?;
         match r.method {
             None => {
                 if r.body.is_some() {
@@ -304,12 +355,65 @@ impl RigzHttp for HttpModule {
         }
     }
 
+    #[cfg(feature = "threaded")]
+    fn fetch_async(&self, request: ObjectValue) -> ObjectValue {
+        let r = match to_request(request) {
+            Ok(r) => r,
+            Err(e) => return Task::finished(Err(e)),
+        };
+        let method = r.method.as_deref().unwrap_or("get").to_lowercase();
+        let method = match method.as_str() {
+            "get" => reqwest::Method::GET,
+            "delete" => reqwest::Method::DELETE,
+            "post" => reqwest::Method::POST,
+            "put" => reqwest::Method::PUT,
+            method => {
+                return Task::finished(Err(VMError::RuntimeError(format!(
+                    "Invalid HTTP method {method}"
+                ))))
+            }
+        };
+        let body = match method {
+            reqwest::Method::GET | reqwest::Method::DELETE => {
+                if r.body.is_some() {
+                    warn!("Ignoring body for {method} request - {:?}", r.body)
+                }
+                None
+            }
+            _ => r.body,
+        };
+        send_async(method, r.path, r.headers, body)
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    fn fetch_async(&self, request: ObjectValue) -> ObjectValue {
+        Task::finished(self.fetch(request))
+    }
+
+    #[cfg(feature = "threaded")]
+    fn get_async(
+        &self,
+        path: String,
+        headers: Option<IndexMap<ObjectValue, ObjectValue>>,
+    ) -> ObjectValue {
+        send_async(reqwest::Method::GET, path, headers, None)
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    fn get_async(
+        &self,
+        path: String,
+        headers: Option<IndexMap<ObjectValue, ObjectValue>>,
+    ) -> ObjectValue {
+        Task::finished(self.get(path, headers))
+    }
+
     fn head(
         &self,
         path: String,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.head(&path);
+        let mut req = client().head(&path);
         req = set_headers(req, headers);
 
         let res = req.call();
@@ -321,7 +425,7 @@ impl RigzHttp for HttpModule {
         path: String,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.get(&path);
+        let mut req = client().get(&path);
         req = set_headers(req, headers);
 
         let res = req.call();
@@ -333,7 +437,7 @@ impl RigzHttp for HttpModule {
         path: String,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.delete(&path);
+        let mut req = client().delete(&path);
         req = set_headers(req, headers);
 
         let res = req.call();
@@ -345,7 +449,7 @@ impl RigzHttp for HttpModule {
         path: String,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.request("OPTIONS", &path);
+        let mut req = client().request("OPTIONS", &path);
         req = set_headers(req, headers);
 
         let res = req.call();
@@ -358,7 +462,7 @@ impl RigzHttp for HttpModule {
         body: Option<ObjectValue>,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.patch(&path);
+        let mut req = client().patch(&path);
         req = set_headers(req, headers);
         handle_body(req, body)
     }
@@ -369,7 +473,7 @@ impl RigzHttp for HttpModule {
         body: Option<ObjectValue>,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.post(&path);
+        let mut req = client().post(&path);
         req = set_headers(req, headers);
         handle_body(req, body)
     }
@@ -380,7 +484,7 @@ impl RigzHttp for HttpModule {
         body: Option<ObjectValue>,
         headers: Option<IndexMap<ObjectValue, ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
-        let mut req = self.client.put(&path);
+        let mut req = client().put(&path);
         req = set_headers(req, headers);
         handle_body(req, body)
     }
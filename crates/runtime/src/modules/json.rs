@@ -1,23 +1,59 @@
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use serde::ser::{Error, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
 
 derive_module! {
     r#"trait JSON
         fn Any.to_json -> String!
         fn parse(input: String) -> Any!
+        fn generate(value, pretty = false) -> String!
     end"#
 }
 
+/// Value written by `generate` of JSON, YAML, & TOML. Objects defined in rigz are written as a map of their fields,
+/// so they can be parsed back as a map. Map keys that aren't strings are written as they're displayed
+pub(crate) struct Document<'a>(pub(crate) &'a ObjectValue);
+
+impl Serialize for Document<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.unfrozen() {
+            ObjectValue::List(l) | ObjectValue::Tuple(l) => {
+                let mut seq = serializer.serialize_seq(Some(l.len()))?;
+                for v in l {
+                    seq.serialize_element(&Document(v))?;
+                }
+                seq.end()
+            }
+            ObjectValue::Map(m) => serialize_map(m, serializer),
+            ObjectValue::Object(o) if o.is::<RigzObject>() => {
+                serialize_map(&o.to_map().map_err(S::Error::custom)?, serializer)
+            }
+            v => v.serialize(serializer),
+        }
+    }
+}
+
+fn serialize_map<S: Serializer>(
+    map: &IndexMap<ObjectValue, ObjectValue>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut object = serializer.serialize_map(Some(map.len()))?;
+    for (k, v) in map {
+        match k {
+            ObjectValue::Primitive(PrimitiveValue::String(s)) => object.serialize_key(s)?,
+            k => object.serialize_key(&k.to_string())?,
+        }
+        object.serialize_value(&Document(v))?;
+    }
+    object.end()
+}
+
 impl RigzJSON for JSONModule {
     #[inline]
     fn any_to_json(&self, value: ObjectValue) -> Result<String, VMError> {
-        match serde_json::to_string(&value) {
-            Ok(s) => Ok(s),
-            Err(e) => Err(VMError::RuntimeError(format!("Failed to write json - {e}"))),
-        }
+        self.generate(value, false)
     }
 
     #[inline]
@@ -32,4 +68,13 @@ impl RigzJSON for JSONModule {
             Err(e) => Err(VMError::RuntimeError(format!("Failed to parse json - {e}"))),
         }
     }
+
+    fn generate(&self, value: ObjectValue, pretty: bool) -> Result<String, VMError> {
+        let json = if pretty {
+            serde_json::to_string_pretty(&Document(&value))
+        } else {
+            serde_json::to_string(&Document(&value))
+        };
+        json.map_err(|e| VMError::RuntimeError(format!("Failed to write json - {e}")))
+    }
 }
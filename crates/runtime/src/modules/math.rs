@@ -1,8 +1,6 @@
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 derive_module! {
     r#"import trait Math
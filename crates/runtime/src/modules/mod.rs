@@ -1,7 +1,11 @@
 mod any;
 mod assertions;
+mod channel;
 mod collections;
+mod crypto;
+mod csv;
 mod date;
+mod env;
 mod file;
 mod html;
 mod http;
@@ -9,10 +13,23 @@ mod json;
 mod log;
 mod math;
 mod number;
+#[cfg(feature = "threaded")]
+mod process;
 mod random;
+mod regex;
+mod secret;
+mod server;
+mod set;
+mod size;
 mod string;
+mod sync;
+mod task;
+mod time;
+mod toml;
 mod uuid;
 // mod vm;
+mod weak_ref;
+mod yaml;
 
 use crate::prepare::ProgramParser;
 
@@ -20,19 +37,37 @@ use crate::modules::html::HtmlModule;
 use crate::modules::http::HttpModule;
 pub use any::AnyModule;
 pub use assertions::AssertionsModule;
+pub use channel::ChannelModule;
 pub use collections::CollectionsModule;
+pub use crypto::CryptoModule;
+pub use csv::CSVModule;
 pub use date::DateModule;
+pub use env::{load_env_files, parse_env_file, EnvModule};
 pub use file::FileModule;
 pub use json::JSONModule;
 pub use log::LogModule;
 pub use math::MathModule;
 pub use number::NumberModule;
+#[cfg(feature = "threaded")]
+pub use process::ProcessModule;
 pub use random::RandomModule;
+pub use regex::RegexModule;
 use rigz_ast::ValidationError;
 use rigz_vm::RigzBuilder;
+pub use secret::SecretModule;
+pub use server::ServerModule;
+pub(crate) use server::ROUTE_EVENT_PREFIX;
+pub use set::SetModule;
+pub use size::SizeModule;
 pub use string::StringModule;
+pub use sync::SyncModule;
+pub use task::TaskModule;
+pub use time::TimeModule;
+pub use toml::TOMLModule;
 pub use uuid::UUIDModule;
 // pub use vm::VMModule;
+pub use weak_ref::WeakRefModule;
+pub use yaml::YAMLModule;
 
 impl<T: RigzBuilder> ProgramParser<'_, T> {
     pub fn add_default_modules(&mut self) -> Result<(), ValidationError> {
@@ -44,13 +79,28 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         self.register_module(CollectionsModule)?;
         self.register_module(LogModule)?;
         self.register_module(JSONModule)?;
+        self.register_module(YAMLModule)?;
+        self.register_module(TOMLModule)?;
+        self.register_module(CSVModule)?;
+        self.register_module(TaskModule)?; // file & http modules depend on task
         self.register_module(FileModule)?;
         self.register_module(DateModule)?;
+        self.register_module(EnvModule)?;
+        self.register_module(TimeModule)?;
+        self.register_module(SizeModule)?;
         self.register_module(UUIDModule)?;
         self.register_module(RandomModule)?;
+        self.register_module(RegexModule)?;
+        self.register_module(SecretModule)?;
+        self.register_module(ChannelModule)?;
+        self.register_module(SyncModule)?;
+        self.register_module(WeakRefModule)?;
+        self.register_module(SetModule)?;
+        self.register_module(CryptoModule)?;
         self.register_module(MathModule)?;
         self.register_module(HtmlModule)?; // http module depends on html
-        self.register_module(HttpModule::default())?;
+        self.register_module(HttpModule)?;
+        self.register_module(ServerModule)?;
         Ok(())
     }
 }
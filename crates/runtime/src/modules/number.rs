@@ -1,10 +1,10 @@
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::ops::Deref;
 
 derive_module! {
+    [Duration],
     r#"
 import trait Number
     fn Number.ceil -> Number
@@ -17,6 +17,16 @@ import trait Number
     fn Number.to_bits -> List
     fn int_from_bits(raw: List) -> Int
     fn float_from_bits(raw: List) -> Float
+
+    fn Number.format(digits: Int? = none, scientific_above: Int? = none, scientific_below: Int? = none) -> String
+    fn set_float_format(digits: Int? = none, scientific_above: Int? = none, scientific_below: Int? = none) -> None
+
+    fn Number.nanos -> Duration
+    fn Number.millis -> Duration
+    fn Number.seconds -> Duration
+    fn Number.minutes -> Duration
+    fn Number.hours -> Duration
+    fn Number.days -> Duration
 end
 "#
 }
@@ -26,6 +36,7 @@ impl RigzNumber for NumberModule {
         match this {
             Number::Int(_) => this,
             Number::Float(f) => (f.ceil() as i64).into(),
+            Number::Decimal(d) => Number::Decimal(d.ceil()).to_int().into(),
         }
     }
 
@@ -33,6 +44,12 @@ impl RigzNumber for NumberModule {
         match this {
             Number::Int(_) => this,
             Number::Float(f) => (f.round() as i64).into(),
+            // matches floats, 2.5 rounds to 3 instead of the nearest even number
+            Number::Decimal(d) => {
+                Number::Decimal(d.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero))
+                    .to_int()
+                    .into()
+            }
         }
     }
 
@@ -40,6 +57,7 @@ impl RigzNumber for NumberModule {
         match this {
             Number::Int(_) => this,
             Number::Float(f) => (f.trunc() as i64).into(),
+            Number::Decimal(d) => Number::Decimal(d.trunc()).to_int().into(),
         }
     }
 
@@ -72,6 +90,54 @@ impl RigzNumber for NumberModule {
             })
     }
 
+    fn number_format(
+        &self,
+        this: Number,
+        digits: Option<i64>,
+        scientific_above: Option<i64>,
+        scientific_below: Option<i64>,
+    ) -> String {
+        match this {
+            Number::Int(i) => i.to_string(),
+            Number::Float(f) => {
+                let mut format = float_format();
+                if digits.is_some() {
+                    format.precision = digits.map(|d| d.max(1) as usize);
+                }
+                if let Some(above) = scientific_above {
+                    format.scientific_above = above as i32;
+                }
+                if let Some(below) = scientific_below {
+                    format.scientific_below = below as i32;
+                }
+                format.format(f)
+            }
+            Number::Decimal(d) => match digits {
+                None => d.to_string(),
+                Some(digits) => {
+                    let digits = digits.max(0) as u32;
+                    let d =
+                        d.round_dp_with_strategy(digits, RoundingStrategy::MidpointAwayFromZero);
+                    format!("{:.*}", digits as usize, d)
+                }
+            },
+        }
+    }
+
+    fn set_float_format(
+        &self,
+        digits: Option<i64>,
+        scientific_above: Option<i64>,
+        scientific_below: Option<i64>,
+    ) {
+        let default = FloatFormat::default();
+        rigz_core::set_float_format(FloatFormat {
+            precision: digits.map(|d| d.max(1) as usize),
+            scientific_above: scientific_above.map_or(default.scientific_above, |a| a as i32),
+            scientific_below: scientific_below.map_or(default.scientific_below, |b| b as i32),
+        });
+    }
+
     fn float_from_bits(&self, raw: Vec<ObjectValue>) -> f64 {
         let raw = raw
             .into_iter()
@@ -82,4 +148,28 @@ impl RigzNumber for NumberModule {
             });
         f64::from_bits(raw)
     }
+
+    fn number_nanos(&self, this: Number) -> ObjectValue {
+        Duration::scaled(&this, 1).into()
+    }
+
+    fn number_millis(&self, this: Number) -> ObjectValue {
+        Duration::scaled(&this, 1_000_000).into()
+    }
+
+    fn number_seconds(&self, this: Number) -> ObjectValue {
+        Duration::scaled(&this, 1_000_000_000).into()
+    }
+
+    fn number_minutes(&self, this: Number) -> ObjectValue {
+        Duration::scaled(&this, 60_000_000_000).into()
+    }
+
+    fn number_hours(&self, this: Number) -> ObjectValue {
+        Duration::scaled(&this, 3_600_000_000_000).into()
+    }
+
+    fn number_days(&self, this: Number) -> ObjectValue {
+        Duration::scaled(&this, 86_400_000_000_000).into()
+    }
 }
@@ -0,0 +1,51 @@
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+use rigz_vm::ProcessHandle;
+
+/// Lists & cancels the processes of the VM the handle came from. Not a default module since scripts could
+/// cancel any process, hosts opt in with `Runtime::allow_process_control`
+#[derive(Clone, Debug)]
+pub struct ProcessModule {
+    handle: ProcessHandle,
+}
+
+impl ProcessModule {
+    pub fn new(handle: ProcessHandle) -> Self {
+        Self { handle }
+    }
+}
+
+derive_module! {
+    ProcessModule,
+    r#"trait Process
+        fn list -> [Map]
+        fn kill(pid: Int) -> None!
+    end"#
+}
+
+impl RigzProcess for ProcessModule {
+    fn list(&self) -> Vec<IndexMap<ObjectValue, ObjectValue>> {
+        self.handle
+            .list()
+            .into_iter()
+            .map(|p| {
+                IndexMap::from([
+                    ("pid".into(), ObjectValue::from(p.pid as i64)),
+                    ("scope".into(), p.scope.into()),
+                    ("state".into(), p.state.to_string().into()),
+                    (
+                        "mailbox_depth".into(),
+                        ObjectValue::from(p.mailbox_depth as i64),
+                    ),
+                ])
+            })
+            .collect()
+    }
+
+    fn kill(&self, pid: i64) -> Result<(), VMError> {
+        let pid = usize::try_from(pid)
+            .map_err(|_| VMError::RuntimeError(format!("Process {pid} does not exist")))?;
+        self.handle.cancel(pid)
+    }
+}
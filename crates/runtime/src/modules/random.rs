@@ -3,7 +3,6 @@ use rand_chacha::ChaCha8Rng;
 use rigz_ast::*;
 use rigz_ast_derive::{derive_module, derive_object};
 use rigz_core::*;
-use std::fmt::{Display, Formatter};
 use std::ops::Deref;
 
 derive_object! {
@@ -81,7 +80,10 @@ impl From<ChaCha8Rng> for InnerRng {
 impl Default for InnerRng {
     #[inline]
     fn default() -> Self {
-        ChaCha8Rng::from_entropy().into()
+        match deterministic_u64() {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed).into(),
+            None => ChaCha8Rng::from_entropy().into(),
+        }
     }
 }
 
@@ -95,7 +97,7 @@ impl CreateObject for Random {
         let v = value.first()?;
         let seed = match v.borrow().deref() {
             ObjectValue::Primitive(p) => match p {
-                PrimitiveValue::None => rand::random(),
+                PrimitiveValue::None => deterministic_u64().map_or_else(rand::random, |s| s as i64),
                 PrimitiveValue::Number(n) => n.to_int(),
                 PrimitiveValue::String(s) => match s.parse() {
                     Ok(i) => i,
@@ -150,17 +152,24 @@ derive_module! {
     end"#
 }
 
+/// Seeded from `VMOptions::seed` when the VM is deterministic
+fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    match deterministic_u64() {
+        Some(seed) => f(&mut ChaCha8Rng::seed_from_u64(seed)),
+        None => f(&mut rand::thread_rng()),
+    }
+}
+
 impl RigzRandom for RandomModule {
     fn next_int(&self) -> i64 {
-        rand::random()
+        with_rng(|rng| rng.gen())
     }
 
     fn next_float(&self) -> f64 {
-        rand::random()
+        with_rng(|rng| rng.gen())
     }
 
     fn next_bool(&self, percent: f64) -> bool {
-        let mut rng = rand::thread_rng();
-        rng.gen_bool(percent)
+        with_rng(|rng| rng.gen_bool(percent))
     }
 }
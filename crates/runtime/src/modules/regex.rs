@@ -0,0 +1,99 @@
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+
+derive_module! {
+    r#"trait Regex
+        fn escape(input: String) -> String
+        fn matches(pattern, input: String) -> Bool!
+        fn find(pattern, input: String) -> String!?
+        fn find_all(pattern, input: String) -> [String]!
+        fn captures(pattern, input: String) -> [String]?!
+        fn split(pattern, input: String) -> [String]!
+        fn replace(pattern, input: String, replacement: String) -> String!
+        fn replace_all(pattern, input: String, replacement: String) -> String!
+    end"#
+}
+
+/// Patterns are Regex values or strings, see `Regex::from_value`
+pub(crate) fn matches(pattern: &ObjectValue, input: &str) -> Result<bool, VMError> {
+    Ok(Regex::from_value(pattern)?.0.is_match(input))
+}
+
+/// The whole match followed by each group, groups that didn't participate are empty. None without a match
+pub(crate) fn captures(pattern: &ObjectValue, input: &str) -> Result<Option<Vec<String>>, VMError> {
+    let regex = Regex::from_value(pattern)?;
+    let captures = regex.0.captures(input).map(|c| {
+        c.iter()
+            .map(|m| m.map(|m| m.as_str().to_string()).unwrap_or_default())
+            .collect()
+    });
+    Ok(captures)
+}
+
+/// `$1` & `${name}` in the replacement are replaced by the group, `$$` is a literal `$`
+pub(crate) fn replace_all(
+    pattern: &ObjectValue,
+    input: &str,
+    replacement: &str,
+) -> Result<String, VMError> {
+    let regex = Regex::from_value(pattern)?;
+    Ok(regex.0.replace_all(input, replacement).into_owned())
+}
+
+impl RigzRegex for RegexModule {
+    fn escape(&self, input: String) -> String {
+        regex::escape(&input)
+    }
+
+    fn matches(&self, pattern: ObjectValue, input: String) -> Result<bool, VMError> {
+        matches(&pattern, &input)
+    }
+
+    fn find(&self, pattern: ObjectValue, input: String) -> Result<Option<String>, VMError> {
+        let regex = Regex::from_value(&pattern)?;
+        Ok(regex.0.find(&input).map(|m| m.as_str().to_string()))
+    }
+
+    fn find_all(&self, pattern: ObjectValue, input: String) -> Result<Vec<String>, VMError> {
+        let regex = Regex::from_value(&pattern)?;
+        Ok(regex
+            .0
+            .find_iter(&input)
+            .map(|m| m.as_str().to_string())
+            .collect())
+    }
+
+    fn captures(
+        &self,
+        pattern: ObjectValue,
+        input: String,
+    ) -> Result<Option<Vec<String>>, VMError> {
+        captures(&pattern, &input)
+    }
+
+    fn split(&self, pattern: ObjectValue, input: String) -> Result<Vec<String>, VMError> {
+        let regex = Regex::from_value(&pattern)?;
+        Ok(regex.0.split(&input).map(|s| s.to_string()).collect())
+    }
+
+    /// Only the first match, see `replace_all`
+    fn replace(
+        &self,
+        pattern: ObjectValue,
+        input: String,
+        replacement: String,
+    ) -> Result<String, VMError> {
+        let regex = Regex::from_value(&pattern)?;
+        Ok(regex.0.replace(&input, replacement.as_str()).into_owned())
+    }
+
+    fn replace_all(
+        &self,
+        pattern: ObjectValue,
+        input: String,
+        replacement: String,
+    ) -> Result<String, VMError> {
+        replace_all(&pattern, &input, &replacement)
+    }
+}
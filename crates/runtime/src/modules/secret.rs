@@ -0,0 +1,66 @@
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use std::fmt::Formatter;
+
+derive_object! {
+    "Secret",
+    struct Secret {
+        // never serialized so secrets don't end up in snapshots or json
+        #[serde(skip)]
+        #[derivative(Debug(format_with = "redact"))]
+        value: ObjectValue,
+    },
+    r#"object Secret
+        Self(value: Any)
+        fn Self.reveal -> Any
+    end
+    "#
+}
+
+/// to_s, inspect, and error messages all use Debug for objects
+fn redact(_: &ObjectValue, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "[REDACTED]")
+}
+
+impl Secret {
+    /// Value used for comparisons, strings are compared by their contents
+    pub(crate) fn exposed(value: &ObjectValue) -> String {
+        match value {
+            ObjectValue::Object(o) => match o.downcast_ref::<Secret>() {
+                Some(s) => s.value.to_string(),
+                None => value.to_string(),
+            },
+            v => v.to_string(),
+        }
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Secret {}
+
+impl SecretObject for Secret {
+    fn reveal(&self) -> ObjectValue {
+        self.value.clone()
+    }
+}
+
+impl CreateObject for Secret {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?.borrow().clone();
+        Ok(Secret { value })
+    }
+}
+
+derive_module! {
+    [Secret],
+    r#"trait Secret
+        fn wrap(value: Any) -> Secret::Secret
+            Secret::Secret.new value
+        end
+    end"#
+}
+
+impl RigzSecret for SecretModule {}
@@ -0,0 +1,429 @@
+use crate::modules::json::Document;
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// `Server.route "/path", |req| ...` handlers are `@on` processes for this event, see `ProgramParser::server_route`
+pub(crate) const ROUTE_EVENT_PREFIX: &str = "http:";
+
+derive_object! {
+    "Server",
+    struct Request {
+        pub method: String,
+        pub path: String,
+        pub body: String,
+        #[derivative(Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        pub query: IndexMap<ObjectValue, ObjectValue>,
+        #[derivative(Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        pub headers: IndexMap<ObjectValue, ObjectValue>,
+        // shared by copies of the request, empty for requests created by scripts
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        responder: Arc<Mutex<Option<Responder>>>,
+    },
+    r#"object Request
+        Self(path: String, method = "GET", body = "")
+
+        fn Self.header(key: String) -> String?
+        fn Self.event -> String
+        fn Self.respond(result) -> None!
+        fn Self.detach -> Server::Request
+    end"#
+}
+
+derive_object! {
+    "Server",
+    struct Listener {
+        pub host: String,
+        pub port: i64,
+        routes: Vec<String>,
+        limit: Option<usize>,
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        state: Arc<ListenerState>,
+    },
+    r#"object Listener
+        Self(port: Number, host: String = "127.0.0.1", routes: List = [], requests: Number? = none)
+
+        fn Self.address -> String
+        fn Self.drain -> None
+    end"#
+}
+
+// routes are collected by the parser of each program, see `ProgramParser::server_listen`
+derive_module! {
+    nondeterministic,
+    [Request, Listener],
+    r#"trait Server
+        fn route(path: String, func: |Server::Request| -> Any) -> None = none
+
+        fn dispatch(req: Server::Request) -> None
+            send req.event, req.detach
+            none
+        end
+
+        # each request is handled by its own process, handlers answer with `req.respond` so the loop doesn't wait
+        fn listen(port: Number, host: String = "127.0.0.1", requests: Number? = none, routes: List = []) -> None!
+            server = try Server::Listener.new port, host, routes, requests
+            [for req in server: Server.dispatch req]
+            server.drain
+            none
+        end
+    end"#
+}
+
+#[derive(Default)]
+struct ListenerState {
+    server: Option<tiny_http::Server>,
+    received: AtomicUsize,
+    // requests sent to a handler that haven't been answered
+    open: Mutex<usize>,
+    answered: Condvar,
+}
+
+impl ListenerState {
+    fn open(&self) -> MutexGuard<'_, usize> {
+        self.open.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn answered(&self) {
+        *self.open() -= 1;
+        self.answered.notify_all();
+    }
+
+    fn wait(&self) {
+        let mut open = self.open();
+        while *open != 0 {
+            open = self.answered.wait(open).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Answers the request once, requests dropped without an answer are a 500
+struct Responder {
+    request: Option<tiny_http::Request>,
+    state: Arc<ListenerState>,
+}
+
+impl Responder {
+    fn new(request: tiny_http::Request, state: Arc<ListenerState>) -> Self {
+        *state.open() += 1;
+        Responder {
+            request: Some(request),
+            state,
+        }
+    }
+
+    fn respond(mut self, response: Response) -> Result<(), VMError> {
+        let result = match self.request.take() {
+            Some(request) => request
+                .respond(response)
+                .map_err(|e| VMError::RuntimeError(format!("Failed to respond - {e}"))),
+            None => Ok(()),
+        };
+        self.state.answered();
+        result
+    }
+}
+
+impl Drop for Responder {
+    fn drop(&mut self) {
+        if let Some(request) = self.request.take() {
+            let response =
+                tiny_http::Response::from_string("Handler did not respond").with_status_code(500);
+            if let Err(e) = request.respond(response) {
+                log::warn!("Failed to respond - {e}")
+            }
+            self.state.answered();
+        }
+    }
+}
+
+type Response = tiny_http::Response<std::io::Cursor<Vec<u8>>>;
+
+/// Bodies that aren't utf-8 are an error, the listener answers with a 400
+fn read_body(request: &mut tiny_http::Request) -> Result<String, String> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| format!("Failed to read request body - {e}"))?;
+    String::from_utf8(body).map_err(|_| "Request body is not valid utf-8".to_string())
+}
+
+/// Path & query of the url
+fn split_url(url: &str) -> (&str, &str) {
+    url.split_once('?').unwrap_or((url, ""))
+}
+
+impl Request {
+    fn new(request: tiny_http::Request, body: String, state: Arc<ListenerState>) -> Self {
+        let (path, query) = split_url(request.url());
+        let path = path.to_string();
+        let query = url::form_urlencoded::parse(query.as_bytes())
+            .map(|(k, v)| (k.as_ref().into(), v.as_ref().into()))
+            .collect();
+        let headers = request
+            .headers()
+            .iter()
+            .map(|h| (h.field.as_str().as_str().into(), h.value.as_str().into()))
+            .collect();
+        Request {
+            method: request.method().to_string(),
+            path,
+            body,
+            query,
+            headers,
+            responder: Arc::new(Mutex::new(Some(Responder::new(request, state)))),
+        }
+    }
+
+    fn responder(&self) -> MutexGuard<'_, Option<Responder>> {
+        self.responder.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Request {
+    fn to_map(&self) -> Result<IndexMap<ObjectValue, ObjectValue>, VMError> {
+        let mut map = IndexMap::new();
+        map.insert("method".into(), self.method.as_str().into());
+        map.insert("path".into(), self.path.as_str().into());
+        map.insert("body".into(), self.body.as_str().into());
+        map.insert("query".into(), ObjectValue::Map(self.query.clone()));
+        map.insert("headers".into(), ObjectValue::Map(self.headers.clone()));
+        Ok(map)
+    }
+
+    fn get(&self, attr: &ObjectValue) -> Result<ObjectValue, VMError> {
+        match self.to_map()?.swap_remove(attr) {
+            None => Err(VMError::UnsupportedOperation(format!(
+                "Cannot get {attr} from {self}"
+            ))),
+            Some(v) => Ok(v),
+        }
+    }
+}
+
+impl RequestObject for Request {
+    /// Header names are matched ignoring case
+    fn header(&self, key: String) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.to_string().eq_ignore_ascii_case(&key))
+            .map(|(_, v)| v.to_string())
+    }
+
+    fn event(&self) -> String {
+        format!("{ROUTE_EVENT_PREFIX}{}", self.path)
+    }
+
+    /// `result` is the handler's result, invalid responses are answered with a 500 & returned as errors
+    fn respond(&self, result: ObjectValue) -> Result<(), VMError> {
+        let Some(responder) = self.responder().take() else {
+            return Err(VMError::RuntimeError(format!(
+                "Cannot respond to {}, it was already answered, detached, or wasn't received by Server.listen",
+                self.path
+            )));
+        };
+        match response(result) {
+            Ok(r) => responder.respond(r),
+            Err(e) => {
+                responder.respond(
+                    tiny_http::Response::from_string(e.to_string()).with_status_code(500),
+                )?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Copy of the request that is answered once it's dropped, this request can't respond anymore.
+    /// Handlers receive the copy so a failed handler doesn't wait for the listener to drop its request
+    fn detach(&self) -> ObjectValue {
+        let responder = self.responder().take();
+        ObjectValue::new(Request {
+            responder: Arc::new(Mutex::new(responder)),
+            ..self.clone()
+        })
+    }
+}
+
+impl CreateObject for Request {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let [path, method, body] = args.take()?;
+        let path = path.borrow().to_string();
+        let method = method.borrow().to_string();
+        let body = body.borrow().to_string();
+        Ok(Request {
+            method,
+            path,
+            body,
+            ..Default::default()
+        })
+    }
+}
+
+impl Listener {
+    /// Requests without a route are answered with a 404 & invalid requests with a 400 here, they aren't sent to a
+    /// handler. The stream ends once `limit` requests were received
+    fn next(&self) -> Result<Option<ObjectValue>, VMError> {
+        let Some(server) = &self.state.server else {
+            return Err(VMError::RuntimeError(format!(
+                "Server for {}:{} is not running",
+                self.host, self.port
+            )));
+        };
+        loop {
+            if self
+                .limit
+                .is_some_and(|l| self.state.received.load(Ordering::Relaxed) >= l)
+            {
+                return Ok(None);
+            }
+            let mut request = server
+                .recv()
+                .map_err(|e| VMError::RuntimeError(format!("Failed to receive request - {e}")))?;
+            self.state.received.fetch_add(1, Ordering::Relaxed);
+            let (path, _) = split_url(request.url());
+            let (request, response) = if !self.routes.iter().any(|r| r == path) {
+                let response = format!("No route for {path}");
+                (
+                    request,
+                    tiny_http::Response::from_string(response).with_status_code(404),
+                )
+            } else {
+                match read_body(&mut request) {
+                    Ok(body) => {
+                        let r = Request::new(request, body, self.state.clone());
+                        return Ok(Some(ObjectValue::new(r)));
+                    }
+                    Err(e) => (
+                        request,
+                        tiny_http::Response::from_string(e).with_status_code(400),
+                    ),
+                }
+            };
+            if let Err(e) = request.respond(response) {
+                log::warn!("Failed to respond - {e}")
+            }
+        }
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Listener {
+    fn is_stream(&self) -> bool {
+        true
+    }
+
+    fn next_value(&self) -> Result<Option<ObjectValue>, VMError> {
+        self.next()
+    }
+}
+
+fn header(key: &str, value: &str) -> Result<tiny_http::Header, VMError> {
+    tiny_http::Header::from_bytes(key.as_bytes(), value.as_bytes())
+        .map_err(|_| VMError::RuntimeError(format!("Invalid response header {key}: {value}")))
+}
+
+fn json(value: &ObjectValue) -> Result<String, VMError> {
+    serde_json::to_string(&Document(value))
+        .map_err(|e| VMError::RuntimeError(format!("Failed to write json - {e}")))
+}
+
+/// Handlers return the body, none for a 204, or a map with `status`, `body`, & `headers`.
+/// Lists & other maps are written as json, errors are a 500
+fn response(result: ObjectValue) -> Result<Response, VMError> {
+    let text = |body: String| tiny_http::Response::from_string(body);
+    let res = match result.unfrozen() {
+        ObjectValue::Primitive(PrimitiveValue::None) => text(String::new()).with_status_code(204),
+        ObjectValue::Primitive(PrimitiveValue::Error(e)) => {
+            text(e.to_string()).with_status_code(500)
+        }
+        ObjectValue::Primitive(PrimitiveValue::String(s)) => text(s.to_string()),
+        ObjectValue::Map(m) if m.contains_key(&ObjectValue::from("status")) => {
+            let status = m[&ObjectValue::from("status")].to_number()?.to_int();
+            let status = match u16::try_from(status) {
+                Ok(s) if (100..=999).contains(&s) => s,
+                _ => {
+                    return Err(VMError::RuntimeError(format!(
+                        "Invalid response status {status}, expected 100 to 999"
+                    )))
+                }
+            };
+            let mut res = match m.get(&ObjectValue::from("body")) {
+                None => text(String::new()),
+                Some(ObjectValue::Primitive(PrimitiveValue::String(s))) => text(s.to_string()),
+                Some(v) => text(json(v)?).with_header(header("Content-Type", "application/json")?),
+            };
+            if let Some(headers) = m.get(&ObjectValue::from("headers")) {
+                for (k, v) in headers.to_map()? {
+                    res.add_header(header(&k.to_string(), &v.to_string())?);
+                }
+            }
+            res.with_status_code(status)
+        }
+        v @ (ObjectValue::List(_) | ObjectValue::Map(_) | ObjectValue::Tuple(_)) => {
+            text(json(v)?).with_header(header("Content-Type", "application/json")?)
+        }
+        v => text(v.to_string()),
+    };
+    Ok(res)
+}
+
+impl ListenerObject for Listener {
+    /// Address the server is bound to, i.e. the port picked for port 0
+    fn address(&self) -> String {
+        match self.state.server.as_ref().map(|s| s.server_addr()) {
+            Some(tiny_http::ListenAddr::IP(addr)) => addr.to_string(),
+            _ => format!("{}:{}", self.host, self.port),
+        }
+    }
+
+    /// Waits for the handlers of every request that was received, unanswered requests are a 500 once dropped
+    fn drain(&self) {
+        self.state.wait()
+    }
+}
+
+impl CreateObject for Listener {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let [port, host, routes, limit] = args.take()?;
+        let port = port.borrow().to_number()?.to_int();
+        let host = host.borrow().to_string();
+        let routes = routes
+            .borrow()
+            .to_list()?
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        let limit = match &*limit.borrow() {
+            ObjectValue::Primitive(PrimitiveValue::None) => None,
+            l => Some(l.to_usize()?),
+        };
+        let Ok(p) = u16::try_from(port) else {
+            return Err(VMError::RuntimeError(format!("Invalid port {port}")));
+        };
+        let server = tiny_http::Server::http((host.as_str(), p)).map_err(|e| {
+            VMError::RuntimeError(format!("Failed to listen on {host}:{port} - {e}"))
+        })?;
+        Ok(Listener {
+            host,
+            port,
+            routes,
+            limit,
+            state: Arc::new(ListenerState {
+                server: Some(server),
+                ..Default::default()
+            }),
+        })
+    }
+}
+
+impl RigzServer for ServerModule {}
@@ -0,0 +1,172 @@
+use itertools::Itertools;
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+
+derive_object! {
+    "Set",
+    struct Set {
+        values: Values,
+    },
+    r#"object Set
+        Self(values: List = [])
+        fn Self.len -> Int
+        fn Self.is_empty -> Bool
+        fn Self.contains(value) -> Bool
+        fn mut Self.insert(value) -> Bool
+        fn mut Self.remove(value) -> Bool
+        fn Self.sorted -> List
+    end
+    "#,
+    display = false
+}
+
+/// Unique values in the order they were first inserted, iteration, `to_list`, & serialization always use this order.
+/// Sets with the same values are equal (and hash the same) regardless of their order.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Values(IndexSet<ObjectValue>);
+
+impl PartialEq for Values {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl PartialOrd for Values {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.0.iter().sorted().cmp(other.0.iter().sorted()))
+    }
+}
+
+impl Hash for Values {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.len().hash(state);
+        self.0.iter().sorted().for_each(|v| v.hash(state));
+    }
+}
+
+/// Values are shown in insertion order, i.e. `Set[1, 2]`
+impl Display for Set {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Set[{}]", self.values.0.iter().join(", "))
+    }
+}
+
+impl From<Vec<ObjectValue>> for Set {
+    fn from(values: Vec<ObjectValue>) -> Self {
+        Set {
+            values: Values(values.into_iter().collect()),
+        }
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Set {
+    fn contained_values(&self) -> Vec<ObjectValue> {
+        self.values.0.iter().cloned().collect()
+    }
+
+    /// Used by `Any.to_list`, values are in insertion order
+    fn to_list(&self) -> Result<Vec<ObjectValue>, VMError> {
+        Ok(self.values.0.iter().cloned().collect())
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.values.0.is_empty()
+    }
+}
+
+impl SetObject for Set {
+    fn len(&self) -> i64 {
+        self.values.0.len() as i64
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.0.is_empty()
+    }
+
+    fn contains(&self, value: ObjectValue) -> bool {
+        self.values.0.contains(&value)
+    }
+
+    /// Existing values keep their position, returns false if the value was already in the set
+    fn mut_insert(&mut self, value: ObjectValue) -> bool {
+        self.values.0.insert(value)
+    }
+
+    /// Values after the removed value move up so the insertion order is kept
+    fn mut_remove(&mut self, value: ObjectValue) -> bool {
+        self.values.0.shift_remove(&value)
+    }
+
+    fn sorted(&self) -> Vec<ObjectValue> {
+        self.values.0.iter().sorted().cloned().collect()
+    }
+}
+
+impl CreateObject for Set {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let values = args.first()?.borrow().to_list()?;
+        Ok(values.into())
+    }
+}
+
+derive_module! {
+    [Set],
+    r#"trait Set
+        fn from_list(values: List = []) -> Set::Set!
+            Set::Set.new values
+        end
+    end"#
+}
+
+impl RigzSet for SetModule {}
+
+#[cfg(test)]
+pub mod set_tests {
+    use crate::modules::set::{Set, SetObject};
+    use rigz_core::{AsPrimitive, ObjectValue};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use wasm_bindgen_test::*;
+
+    fn values(values: &[i64]) -> Vec<ObjectValue> {
+        values.iter().map(|&v| v.into()).collect()
+    }
+
+    fn hash(set: &Set) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        set.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn insertion_order() {
+        let mut set = Set::from(values(&[3, 1, 2, 1]));
+        assert!(!set.mut_insert(3.into()));
+        assert!(set.mut_insert(0.into()));
+        assert!(set.mut_remove(1.into()));
+        assert_eq!(set.to_list(), Ok(values(&[3, 2, 0])));
+        assert_eq!(set.sorted(), values(&[0, 2, 3]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn equal_regardless_of_order() {
+        let a = Set::from(values(&[1, 2, 3]));
+        let b = Set::from(values(&[3, 2, 1]));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(a, Set::from(values(&[1, 2])));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn display() {
+        assert_eq!(Set::from(values(&[2, 1, 2])).to_string(), "Set[2, 1]");
+        assert_eq!(Set::default().to_string(), "Set[]");
+    }
+}
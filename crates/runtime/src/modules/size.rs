@@ -0,0 +1,31 @@
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+
+derive_module! {
+    instructions,
+    r#"trait Size
+        fn humanize(bytes: Int) -> String
+        fn parse(value: String) -> Int!
+    end"#
+}
+
+impl RigzSize for SizeModule {
+    /// Binary units with one decimal place, i.e. `1.4 MiB`
+    fn humanize(&self, bytes: i64) -> String {
+        let humanized = humanize_size(bytes.unsigned_abs());
+        if bytes < 0 {
+            format!("-{humanized}")
+        } else {
+            humanized
+        }
+    }
+
+    /// Accepts the format of `Size.humanize` and decimal units, i.e. `1.4 MiB` or `10KB`
+    fn parse(&self, value: String) -> Result<i64, VMError> {
+        let bytes = parse_size(&value)?;
+        i64::try_from(bytes).map_err(|_| {
+            VMError::ConversionError(format!("Cannot convert {value:?} to Size - too large"))
+        })
+    }
+}
@@ -1,10 +1,11 @@
+use crate::modules::regex;
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 derive_module! {
+    // `/pattern/` literals construct a Regex
+    [Regex],
     r#"import trait String
     fn mut String.push(value)
     fn String.concat(value: String) -> String
@@ -12,6 +13,16 @@ derive_module! {
     fn String.trim -> String
     fn String.split(pattern: String) -> [String]
     fn String.replace(pattern: String, value: String) -> String
+    fn String.matches(pattern) -> Bool!
+    fn String.replace_all(pattern, replacement: String) -> String!
+    fn String.captures(pattern) -> [String]?!
+    fn String.step(step: Int) -> String!
+    fn String.len -> Int
+    fn String.graphemes -> [String]
+    fn String.chars -> [String]
+    fn String.char_len -> Int
+    fn String.bytes -> [Int]
+    fn String.byte_len -> Int
 end"#
 }
 
@@ -45,4 +56,59 @@ impl RigzString for StringModule {
     fn string_replace(&self, this: String, pattern: String, value: String) -> String {
         this.replace(pattern.as_str(), value.as_str())
     }
+
+    /// `pattern` is a Regex or a string compiled as one, i.e. `'a1'.matches /\d/`
+    fn string_matches(&self, this: String, pattern: ObjectValue) -> Result<bool, VMError> {
+        regex::matches(&pattern, &this)
+    }
+
+    fn string_replace_all(
+        &self,
+        this: String,
+        pattern: ObjectValue,
+        replacement: String,
+    ) -> Result<String, VMError> {
+        regex::replace_all(&pattern, &this, &replacement)
+    }
+
+    fn string_captures(
+        &self,
+        this: String,
+        pattern: ObjectValue,
+    ) -> Result<Option<Vec<String>>, VMError> {
+        regex::captures(&pattern, &this)
+    }
+
+    /// Every `step` grapheme, i.e. `'abcdef'.step 2` is `ace` and `'abcdef'.step -2` is `fdb`
+    fn string_step(&self, this: String, step: i64) -> Result<String, VMError> {
+        grapheme_step(&this, step)
+    }
+
+    /// Number of graphemes, the same unit as `s[0]` & `for c in s`
+    fn string_len(&self, this: String) -> i64 {
+        graphemes(&this).count() as i64
+    }
+
+    /// User perceived characters, `'cafe\u{301}'.graphemes` is `['c', 'a', 'f', 'e\u{301}']`
+    fn string_graphemes(&self, this: String) -> Vec<String> {
+        graphemes(&this).map(|g| g.to_string()).collect()
+    }
+
+    /// Unicode scalar values, `'cafe\u{301}'.chars` is `['c', 'a', 'f', 'e', '\u{301}']`
+    fn string_chars(&self, this: String) -> Vec<String> {
+        this.chars().map(|c| c.to_string()).collect()
+    }
+
+    fn string_char_len(&self, this: String) -> i64 {
+        this.chars().count() as i64
+    }
+
+    /// UTF-8 bytes
+    fn string_bytes(&self, this: String) -> Vec<i64> {
+        this.bytes().map(|b| b as i64).collect()
+    }
+
+    fn string_byte_len(&self, this: String) -> i64 {
+        this.len() as i64
+    }
 }
@@ -0,0 +1,392 @@
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use rigz_vm::{coroutine_wait_start, retry_call};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Condvar, Mutex as StdMutex, MutexGuard};
+
+derive_object! {
+    "Sync",
+    struct Mutex {
+        pub id: i64,
+        // clones share the value, deserialized mutexes start unlocked with none
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        guarded: Tracked<Guarded>,
+    },
+    r#"object Mutex
+        Self(value = none)
+        fn Self.get -> Any
+        fn Self.is_locked -> Bool
+        fn Self.acquire -> Any
+        fn Self.release(value) -> Any!
+
+        # errors raised by func are returned after the mutex is released, the value is kept
+        fn Self.lock(func: |Any| -> Any) -> Any
+            value = self.acquire
+            result = func value
+            self.release result
+        end
+    end
+    "#
+}
+
+derive_object! {
+    "Sync",
+    struct Atomic {
+        pub id: i64,
+        // clones share the counter, deserialized atomics start at 0
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        counter: Arc<AtomicI64>,
+    },
+    r#"object Atomic
+        Self(value: Int = 0)
+        fn Self.get -> Int
+        fn Self.set(value: Int) -> None
+        fn Self.add(value: Int) -> Int
+        fn Self.increment -> Int
+        fn Self.decrement -> Int
+        fn Self.compare_and_swap(current: Int, replacement: Int) -> Bool
+    end
+    "#
+}
+
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+#[derive(Debug, Default)]
+struct GuardedState {
+    value: ObjectValue,
+    locked: bool,
+}
+
+/// Value of a mutex, shared by every copy of the mutex including the ones sent to other processes
+#[derive(Debug, Default)]
+struct Guarded {
+    state: StdMutex<GuardedState>,
+    // notified when the mutex is released
+    released: Condvar,
+}
+
+impl Guarded {
+    fn state(&self) -> MutexGuard<'_, GuardedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Held for GuardedState {
+    fn values(&self) -> Vec<ObjectValue> {
+        vec![self.value.clone()]
+    }
+
+    fn clear(&mut self) {
+        self.value = ObjectValue::default();
+    }
+}
+
+impl Collectable for Guarded {
+    fn held(&self) -> &StdMutex<dyn Held> {
+        &self.state
+    }
+}
+
+impl Mutex {
+    fn new(value: ObjectValue) -> Self {
+        Mutex {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            guarded: Tracked::new(Guarded {
+                state: StdMutex::new(GuardedState {
+                    value,
+                    locked: false,
+                }),
+                released: Condvar::new(),
+            }),
+        }
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Mutex {
+    fn shared_id(&self) -> Option<usize> {
+        Some(Tracked::id(&self.guarded))
+    }
+
+    fn contained_values(&self) -> Vec<ObjectValue> {
+        self.guarded.held_values()
+    }
+}
+
+impl MutexObject for Mutex {
+    /// Current value, doesn't wait for the mutex to be released
+    fn get(&self) -> ObjectValue {
+        self.guarded.state().value.clone()
+    }
+
+    fn is_locked(&self) -> bool {
+        self.guarded.state().locked
+    }
+
+    /// Waits until no other process holds the mutex, the mutex stays locked until `release` is called.
+    /// Processes running as coroutines yield to the other coroutines instead of blocking the thread.
+    /// Calling `acquire` again before `release` waits forever, prefer `lock`.
+    fn acquire(&self) -> ObjectValue {
+        let mut state = self.guarded.state();
+        while state.locked {
+            if coroutine_wait_start().is_some() {
+                retry_call();
+                return ObjectValue::default();
+            }
+            state = self
+                .guarded
+                .released
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        state.locked = true;
+        state.value.clone()
+    }
+
+    /// Stores the value and unlocks the mutex, errors are returned without replacing the current value.
+    /// Values that contain this mutex are rejected, the mutex would keep itself alive.
+    fn release(&self, value: ObjectValue) -> Result<ObjectValue, VMError> {
+        if self.shared_id().is_some_and(|id| value.references(id)) {
+            return Err(VMError::RuntimeError(
+                "Cannot release mutex with a value that contains the mutex".to_string(),
+            ));
+        }
+        let mut state = self.guarded.state();
+        if !state.locked {
+            return Err(VMError::RuntimeError(
+                "Cannot release mutex, it is not locked".to_string(),
+            ));
+        }
+        if !value.is_error() {
+            state.value = value.clone();
+        }
+        state.locked = false;
+        self.guarded.released.notify_one();
+        Ok(value)
+    }
+}
+
+impl CreateObject for Mutex {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?.borrow().clone();
+        Ok(Mutex::new(value))
+    }
+}
+
+impl Atomic {
+    fn new(value: i64) -> Self {
+        Atomic {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            counter: Arc::new(AtomicI64::new(value)),
+        }
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Atomic {}
+
+impl AtomicObject for Atomic {
+    fn get(&self) -> i64 {
+        self.counter.load(Ordering::SeqCst)
+    }
+
+    fn set(&self, value: i64) {
+        self.counter.store(value, Ordering::SeqCst)
+    }
+
+    /// Returns the updated value, wraps on overflow
+    fn add(&self, value: i64) -> i64 {
+        self.counter
+            .fetch_add(value, Ordering::SeqCst)
+            .wrapping_add(value)
+    }
+
+    fn increment(&self) -> i64 {
+        self.add(1)
+    }
+
+    fn decrement(&self) -> i64 {
+        self.add(-1)
+    }
+
+    /// Sets the value to `new` if it is still `current`, returns false when another process changed it first
+    fn compare_and_swap(&self, current: i64, replacement: i64) -> bool {
+        self.counter
+            .compare_exchange(current, replacement, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+impl CreateObject for Atomic {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?.borrow().to_int()?;
+        Ok(Atomic::new(value))
+    }
+}
+
+derive_module! {
+    [Mutex, Atomic],
+    r#"trait Sync
+        fn mutex(value = none) -> Sync::Mutex!
+            Sync::Mutex.new value
+        end
+
+        fn atomic(value: Int = 0) -> Sync::Atomic!
+            Sync::Atomic.new value
+        end
+
+        # frees mutexes, channels, & tasks that only hold each other, returns how many were freed
+        fn gc -> Int
+    end"#
+}
+
+impl RigzSync for SyncModule {
+    /// Weak references that can't be upgraded anymore are dropped as well
+    fn gc(&self) -> i64 {
+        crate::modules::weak_ref::purge();
+        collect() as i64
+    }
+}
+
+#[cfg(test)]
+pub mod sync_tests {
+    use crate::modules::sync::{Atomic, AtomicObject, Mutex, MutexObject};
+    use rigz_core::{
+        collect, enter_context, ObjectValue, PrimitiveValue, RuntimeContext, Tracked, VMError,
+    };
+    use std::thread;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn clones_share_lock() {
+        let mutex = Mutex::new(0.into());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mutex = mutex.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        let value = rigz_core::AsPrimitive::to_int(&mutex.acquire()).unwrap();
+                        mutex.release((value + 1).into()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(mutex.get(), 400.into());
+        assert!(!mutex.is_locked());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn release_keeps_value_on_error() {
+        let mutex = Mutex::new(1.into());
+        assert!(mutex.release(2.into()).is_err());
+        mutex.acquire();
+        let error: ObjectValue =
+            PrimitiveValue::Error(VMError::RuntimeError("failed".to_string())).into();
+        assert_eq!(mutex.release(error.clone()), Ok(error));
+        assert_eq!(mutex.get(), 1.into());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn release_rejects_cycles() {
+        let mutex = Mutex::new(ObjectValue::default());
+        let outer = Mutex::new(ObjectValue::new(mutex.clone()));
+        mutex.acquire();
+        assert!(mutex
+            .release(ObjectValue::List(vec![ObjectValue::new(mutex.clone())]))
+            .is_err());
+        assert!(mutex.release(ObjectValue::new(outer)).is_err());
+        assert!(mutex.is_locked());
+        assert_eq!(mutex.release(1.into()), Ok(1.into()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn collect_frees_cycles() {
+        let mutex = Mutex::new(ObjectValue::default());
+        let other = Mutex::new(ObjectValue::new(mutex.clone()));
+        // release rejects this, a task finishing later can build the same cycle
+        mutex.guarded.state().value = ObjectValue::List(vec![ObjectValue::new(other)]);
+        let weak = Tracked::downgrade(&mutex.guarded);
+
+        let kept = Mutex::new(ObjectValue::default());
+        kept.guarded.state().value = ObjectValue::new(kept.clone());
+        collect();
+        assert_eq!(weak.strong_count(), 2);
+        drop(mutex);
+        collect();
+        assert_eq!(weak.strong_count(), 0);
+        assert_eq!(kept.get(), ObjectValue::new(kept.clone()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn collect_only_frees_own_runtime() {
+        let context = RuntimeContext::default();
+        let weak = {
+            let _context = enter_context(context.clone());
+            let mutex = Mutex::new(ObjectValue::default());
+            mutex.guarded.state().value = ObjectValue::new(mutex.clone());
+            Tracked::downgrade(&mutex.guarded)
+        };
+        assert_eq!(collect(), 0);
+        assert_eq!(weak.strong_count(), 1);
+        let _context = enter_context(context);
+        assert_eq!(collect(), 1);
+        assert_eq!(weak.strong_count(), 0);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn collect_keeps_state_copied_by_other_threads() {
+        let context = RuntimeContext::default();
+        let _context = enter_context(context.clone());
+        let inner = Mutex::new(1.into());
+        let outer = Mutex::new(ObjectValue::new(inner));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let outer = outer.clone();
+                let context = context.clone();
+                thread::spawn(move || {
+                    let _context = enter_context(context);
+                    for _ in 0..1000 {
+                        let copy = outer.get();
+                        let cycle = Mutex::new(ObjectValue::default());
+                        cycle.guarded.state().value = ObjectValue::new(cycle.clone());
+                        drop(copy);
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..100 {
+            collect();
+        }
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        collect();
+        assert_eq!(collect(), 0);
+        let ObjectValue::Object(inner) = outer.get() else {
+            panic!("mutex was cleared")
+        };
+        assert_eq!(inner.contained_values(), vec![1.into()]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn atomic_counter() {
+        let counter = Atomic::new(0);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || (0..100).for_each(|_| _ = counter.increment()))
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(counter.get(), 400);
+        assert!(!counter.compare_and_swap(0, 1));
+        assert!(counter.compare_and_swap(400, 1));
+        assert_eq!(counter.decrement(), 0);
+    }
+}
@@ -0,0 +1,259 @@
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use rigz_vm::{coroutine_wait_start, retry_call, Instant};
+#[cfg(feature = "threaded")]
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+derive_object! {
+    "Task",
+    struct Task {
+        pub id: i64,
+        // clones share the result, deserialized tasks are finished with none
+        #[serde(skip)]
+        #[derivative(Debug="ignore", Hash="ignore", PartialEq="ignore", PartialOrd="ignore")]
+        outcome: Tracked<Outcome>,
+    },
+    r#"object Task
+        Self(value = none)
+        fn Self.is_done -> Bool
+        fn Self.wait(timeout: Number? = none) -> Any!
+    end
+    "#
+}
+
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+/// Result of the work started by `Task::spawn`, none until the work finishes
+#[derive(Debug)]
+struct Outcome {
+    result: Mutex<TaskResult>,
+    finished: Condvar,
+}
+
+#[derive(Debug)]
+struct TaskResult(Option<Result<ObjectValue, VMError>>);
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Outcome::new(Some(Ok(ObjectValue::default())))
+    }
+}
+
+impl Outcome {
+    fn new(result: Option<Result<ObjectValue, VMError>>) -> Self {
+        Outcome {
+            result: Mutex::new(TaskResult(result)),
+            finished: Condvar::new(),
+        }
+    }
+
+    fn result(&self) -> MutexGuard<'_, TaskResult> {
+        self.result.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(feature = "threaded")]
+    fn finish(&self, result: Result<ObjectValue, VMError>) {
+        self.result().0 = Some(result);
+        self.finished.notify_all();
+    }
+}
+
+impl Held for TaskResult {
+    fn values(&self) -> Vec<ObjectValue> {
+        match &self.0 {
+            Some(Ok(v)) => vec![v.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Finished tasks keep a result of none
+    fn clear(&mut self) {
+        if let Some(Ok(v)) = &mut self.0 {
+            *v = ObjectValue::default();
+        }
+    }
+}
+
+impl Collectable for Outcome {
+    fn held(&self) -> &Mutex<dyn Held> {
+        &self.result
+    }
+}
+
+#[cfg(feature = "threaded")]
+fn io_runtime() -> Result<&'static tokio::runtime::Runtime, VMError> {
+    static RUNTIME: std::sync::OnceLock<Result<tokio::runtime::Runtime, String>> =
+        std::sync::OnceLock::new();
+    RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .thread_name("rigz-io")
+                .enable_all()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| VMError::RuntimeError(format!("Failed to create I/O runtime {e}")))
+}
+
+impl Task {
+    fn new(result: Option<Result<ObjectValue, VMError>>) -> Self {
+        Task {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            outcome: Tracked::new(Outcome::new(result)),
+        }
+    }
+
+    /// Runs async I/O on the shared I/O runtime so the process can keep running until it waits for the task
+    #[cfg(feature = "threaded")]
+    pub(crate) fn spawn(
+        work: impl Future<Output = Result<ObjectValue, VMError>> + Send + 'static,
+    ) -> ObjectValue {
+        let task = Task::new(None);
+        match io_runtime() {
+            Ok(runtime) => {
+                let outcome = task.outcome.clone();
+                runtime.spawn(async move { outcome.finish(work.await) });
+            }
+            Err(e) => task.outcome.finish(Err(e)),
+        }
+        ObjectValue::new(task)
+    }
+
+    /// Work that finished before the task was created, i.e. in the browser where threads aren't available
+    pub(crate) fn finished(result: Result<ObjectValue, VMError>) -> ObjectValue {
+        ObjectValue::new(Task::new(Some(result)))
+    }
+}
+
+impl AsPrimitive<ObjectValue> for Task {
+    fn shared_id(&self) -> Option<usize> {
+        Some(Tracked::id(&self.outcome))
+    }
+
+    fn contained_values(&self) -> Vec<ObjectValue> {
+        self.outcome.held_values()
+    }
+
+    fn poll_receive(&self) -> Result<Option<ObjectValue>, VMError> {
+        self.outcome.result().0.clone().transpose()
+    }
+}
+
+impl TaskObject for Task {
+    fn is_done(&self) -> bool {
+        self.outcome.result().0.is_some()
+    }
+
+    /// Waits for the work to finish, every copy of the task returns the same result.
+    /// Processes running as coroutines yield to the other coroutines instead of blocking the thread
+    fn wait(&self, timeout: Option<Number>) -> Result<ObjectValue, VMError> {
+        let timeout = timeout.map(|t| t.to_usize()).transpose()?;
+        let coroutine = coroutine_wait_start();
+        let start = coroutine.unwrap_or_else(Instant::now);
+        let deadline = timeout.map(|t| start + Duration::from_millis(t as u64));
+        let mut result = self.outcome.result();
+        loop {
+            if let Some(r) = &result.0 {
+                return r.clone();
+            }
+            if let (Some(time), Some(deadline)) = (timeout, deadline) {
+                if Instant::now() >= deadline {
+                    return Err(VMError::RuntimeError(format!(
+                        "`wait` timed out after {time}ms"
+                    )));
+                }
+            }
+            if coroutine.is_some() {
+                retry_call();
+                return Ok(ObjectValue::default());
+            }
+            result = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    self.outcome
+                        .finished
+                        .wait_timeout(result, remaining)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .outcome
+                    .finished
+                    .wait(result)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+impl CreateObject for Task {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?.borrow().clone();
+        Ok(Task::new(Some(Ok(value))))
+    }
+}
+
+derive_module! {
+    [Task],
+    r#"trait Task
+        fn done(value = none) -> Task::Task!
+            Task::Task.new value
+        end
+
+        fn wait_all(tasks: List, timeout: Number? = none) -> List!
+            [for t in tasks: t.wait timeout]
+        end
+    end"#
+}
+
+impl RigzTask for TaskModule {}
+
+#[cfg(test)]
+pub mod task_tests {
+    use crate::modules::task::{Task, TaskObject};
+    use rigz_core::{AsPrimitive, Number, VMError};
+    use wasm_bindgen_test::*;
+    #[cfg(feature = "threaded")]
+    use {rigz_core::ObjectValue, std::time::Duration};
+
+    #[cfg(feature = "threaded")]
+    fn task(value: ObjectValue) -> Task {
+        match value {
+            ObjectValue::Object(o) => o.downcast_ref::<Task>().unwrap().clone(),
+            v => panic!("expected task, received {v}"),
+        }
+    }
+
+    #[cfg(feature = "threaded")]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn spawn_finishes() {
+        let t = task(Task::spawn(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(42.into())
+        }));
+        assert_eq!(t.wait(None), Ok(42.into()));
+        assert!(t.is_done());
+        assert_eq!(t.poll_receive(), Ok(Some(42.into())));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn wait_timeout() {
+        let t = Task::new(None);
+        assert_eq!(t.poll_receive(), Ok(None));
+        assert_eq!(
+            t.wait(Some(Number::Int(0))),
+            Err(VMError::RuntimeError(
+                "`wait` timed out after 0ms".to_string()
+            ))
+        );
+    }
+}
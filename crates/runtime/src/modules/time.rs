@@ -0,0 +1,59 @@
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+
+const NANOS_PER_MILLI: i64 = 1_000_000;
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+derive_module! {
+    // Duration is registered by Number, i.e. `5.minutes`, & DateTime by Date
+    r#"trait Time
+        fn now -> DateTime
+        fn nanos(value: Number) -> Duration
+        fn millis(value: Number) -> Duration
+        fn seconds(value: Number) -> Duration
+        fn minutes(value: Number) -> Duration
+        fn parse(value: String) -> Duration!
+        fn strptime(value: String, pattern: String) -> DateTime!
+        fn strftime(value: DateTime, pattern: String) -> String! = value.format pattern
+
+        fn measure(func: || -> Any) -> Duration
+            start = Time.now
+            func
+            start.elapsed
+        end
+    end"#
+}
+
+impl RigzTime for TimeModule {
+    /// UTC, see `DateTime.in_zone`
+    fn now(&self) -> ObjectValue {
+        DateTime::now().into()
+    }
+
+    fn nanos(&self, value: Number) -> ObjectValue {
+        Duration::scaled(&value, 1).into()
+    }
+
+    fn millis(&self, value: Number) -> ObjectValue {
+        Duration::scaled(&value, NANOS_PER_MILLI).into()
+    }
+
+    fn seconds(&self, value: Number) -> ObjectValue {
+        Duration::scaled(&value, NANOS_PER_SECOND).into()
+    }
+
+    fn minutes(&self, value: Number) -> ObjectValue {
+        Duration::scaled(&value, 60 * NANOS_PER_SECOND).into()
+    }
+
+    /// Accepts the format of `Duration.humanize`, i.e. `2h 3m` or `1.5s`
+    fn parse(&self, value: String) -> Result<ObjectValue, VMError> {
+        Ok(Duration::parse(&value)?.into())
+    }
+
+    /// strftime pattern, i.e. `Time.strptime "05/01/2024 9:30", "%m/%d/%Y %H:%M"`. Times without an offset are UTC
+    fn strptime(&self, value: String, pattern: String) -> Result<ObjectValue, VMError> {
+        Ok(DateTime::parse(&value, Some(&pattern))?.into())
+    }
+}
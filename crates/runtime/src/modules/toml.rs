@@ -0,0 +1,42 @@
+use crate::modules::json::Document;
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+
+derive_module! {
+    r#"trait TOML
+        fn parse(input: String) -> Any!
+        fn generate(value) -> String!
+    end"#
+}
+
+impl RigzTOML for TOMLModule {
+    /// Dates & times are read as strings
+    fn parse(&self, input: String) -> Result<ObjectValue, VMError> {
+        let value: toml::Value = toml::from_str(input.as_str())
+            .map_err(|e| VMError::RuntimeError(format!("Failed to parse toml - {e}")))?;
+        Ok(from_toml(value))
+    }
+
+    /// Documents must be a map, TOML has no none
+    fn generate(&self, value: ObjectValue) -> Result<String, VMError> {
+        toml::to_string(&Document(&value))
+            .map_err(|e| VMError::RuntimeError(format!("Failed to write toml - {e}")))
+    }
+}
+
+fn from_toml(value: toml::Value) -> ObjectValue {
+    match value {
+        toml::Value::String(s) => s.into(),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        toml::Value::Datetime(d) => d.to_string().into(),
+        toml::Value::Array(a) => ObjectValue::List(a.into_iter().map(from_toml).collect()),
+        toml::Value::Table(t) => ObjectValue::Map(
+            t.into_iter()
+                .map(|(k, v)| (k.into(), from_toml(v)))
+                .collect(),
+        ),
+    }
+}
@@ -1,27 +1,36 @@
 use rigz_ast::*;
 use rigz_ast_derive::derive_module;
 use rigz_core::*;
-use uuid::Uuid;
 
 derive_module! {
+    [Uuid],
     r#"
 trait UUID
-    fn v4 -> String
-    fn from(input: String) -> String!
+    fn v4 -> Uuid
+    fn parse(input: String) -> Uuid!
+    fn from(input: String) -> Uuid!
 end
 "#
 }
 
-// todo once object exists use that instead of strings
 impl RigzUUID for UUIDModule {
-    fn v4(&self) -> String {
-        Uuid::new_v4().to_string()
+    fn v4(&self) -> ObjectValue {
+        let id = match deterministic_u64() {
+            Some(high) => {
+                let low = deterministic_u64().unwrap_or_default();
+                Uuid::from_random_bytes(((high as u128) << 64 | low as u128).to_be_bytes())
+            }
+            None => Uuid(uuid::Uuid::new_v4()),
+        };
+        id.into()
     }
 
-    fn from(&self, input: String) -> Result<String, VMError> {
-        match Uuid::try_parse(input.as_str()) {
-            Ok(s) => Ok(s.to_string()),
-            Err(e) => Err(VMError::RuntimeError(format!("Failed to parse uuid: {e}"))),
-        }
+    fn parse(&self, input: String) -> Result<ObjectValue, VMError> {
+        Ok(Uuid::parse(input.as_str())?.into())
+    }
+
+    /// Same as `parse`, kept for scripts written before `Uuid` existed
+    fn from(&self, input: String) -> Result<ObjectValue, VMError> {
+        self.parse(input)
     }
 }
@@ -0,0 +1,91 @@
+use rigz_ast::*;
+use rigz_ast_derive::{derive_module, derive_object};
+use rigz_core::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+derive_object! {
+    "WeakRef",
+    struct WeakRef {
+        pub id: i64,
+    },
+    r#"object WeakRef
+        Self(value)
+        fn Self.get -> Any?
+        fn Self.is_alive -> Bool
+    end
+    "#
+}
+
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+// stack values can't be sent to other threads, weak refs sent to other processes or runtimes, or deserialized
+// are empty
+type References = HashMap<i64, WeakShared<ObjectValue>>;
+
+/// Forgets the references of this runtime & thread whose value was dropped, see `Sync.gc`
+pub(crate) fn purge() {
+    with_local(|r: &mut References| r.retain(|_, v| v.is_alive()))
+}
+
+impl WeakRef {
+    fn new(value: &Shared<ObjectValue>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        purge();
+        with_local(|r: &mut References| r.insert(id, Shared::downgrade(value)));
+        WeakRef { id }
+    }
+
+    fn upgrade(&self) -> Option<Shared<ObjectValue>> {
+        with_local(|r: &mut References| r.get(&self.id).and_then(|v| v.upgrade()))
+    }
+}
+
+impl AsPrimitive<ObjectValue> for WeakRef {}
+
+impl WeakRefObject for WeakRef {
+    /// The value while a variable or another value on the stack still holds it, none once it was dropped
+    fn get(&self) -> Option<ObjectValue> {
+        self.upgrade().map(|v| v.borrow().clone())
+    }
+
+    fn is_alive(&self) -> bool {
+        self.upgrade().is_some()
+    }
+}
+
+impl CreateObject for WeakRef {
+    fn create(args: RigzArgs) -> Result<Self, VMError>
+    where
+        Self: Sized,
+    {
+        let value = args.first()?;
+        Ok(WeakRef::new(&value))
+    }
+}
+
+derive_module! {
+    [WeakRef],
+    r#"trait WeakRef
+    end"#
+}
+
+impl RigzWeakRef for WeakRefModule {}
+
+#[cfg(test)]
+pub mod weak_ref_tests {
+    use crate::modules::weak_ref::{WeakRef, WeakRefObject};
+    use rigz_core::{ObjectValue, Shared};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn empty_once_dropped() {
+        let value = Shared::new(ObjectValue::from(1));
+        let weak = WeakRef::new(&value);
+        let copy = weak.clone();
+        assert_eq!(weak.get(), Some(1.into()));
+        drop(value);
+        assert!(!copy.is_alive());
+        assert_eq!(weak.get(), None);
+    }
+}
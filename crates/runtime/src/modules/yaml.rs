@@ -0,0 +1,23 @@
+use crate::modules::json::Document;
+use rigz_ast::*;
+use rigz_ast_derive::derive_module;
+use rigz_core::*;
+
+derive_module! {
+    r#"trait YAML
+        fn parse(input: String) -> Any!
+        fn generate(value) -> String!
+    end"#
+}
+
+impl RigzYAML for YAMLModule {
+    fn parse(&self, input: String) -> Result<ObjectValue, VMError> {
+        serde_yaml::from_str(input.as_str())
+            .map_err(|e| VMError::RuntimeError(format!("Failed to parse yaml - {e}")))
+    }
+
+    fn generate(&self, value: ObjectValue) -> Result<String, VMError> {
+        serde_yaml::to_string(&Document(&value))
+            .map_err(|e| VMError::RuntimeError(format!("Failed to write yaml - {e}")))
+    }
+}
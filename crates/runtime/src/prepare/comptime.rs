@@ -0,0 +1,75 @@
+use crate::prepare::{Program, ProgramParser};
+use rigz_ast::{Scope, ValidationError};
+use rigz_core::{set_output_source, ObjectValue, OutputSource};
+use rigz_vm::{LoadValue, RigzBuilder, VMBuilder, VMOptions};
+
+/// Instructions a `comptime` block can run before the program fails to prepare
+pub(crate) const COMPTIME_MAX_INSTRUCTIONS: usize = 1_000_000;
+
+/// Built-in functions that write output or start processes
+pub(crate) const COMPTIME_SIDE_EFFECTS: [&str; 5] = ["puts", "log", "spawn", "send", "receive"];
+
+/// Modules that read or write outside the program, or control processes
+pub(crate) const COMPTIME_SIDE_EFFECT_MODULES: [&str; 7] =
+    ["CSV", "Env", "File", "Http", "Log", "Process", "Server"];
+
+/// Objects belong to the dependencies of the VM that created them, so they can't be embedded in another program
+fn embeddable(value: &ObjectValue) -> bool {
+    match value {
+        ObjectValue::Primitive(_) => true,
+        ObjectValue::List(l) | ObjectValue::Tuple(l) => l.iter().all(embeddable),
+        ObjectValue::Map(m) => m.iter().all(|(k, v)| embeddable(k) && embeddable(v)),
+        ObjectValue::Object(_) => false,
+    }
+}
+
+impl<T: RigzBuilder> ProgramParser<'_, T> {
+    /// Runs the block in its own VM while the program is prepared and loads the result as a constant.
+    /// The block can't use variables or functions from the program. Imports, output, processes, & modules with side
+    /// effects (`COMPTIME_SIDE_EFFECT_MODULES`) are rejected, the VM is deterministic and stops after
+    /// `COMPTIME_MAX_INSTRUCTIONS`.
+    pub(crate) fn parse_comptime(&mut self, body: Scope) -> Result<(), ValidationError> {
+        let mut parser: ProgramParser<VMBuilder> =
+            ProgramParser::with_options(self.parser_options.clone());
+        parser.comptime = true;
+        parser.builder.with_options(VMOptions {
+            deterministic: true,
+            max_instructions: Some(COMPTIME_MAX_INSTRUCTIONS),
+            ..Default::default()
+        });
+        parser.parse_program(Program {
+            elements: body.elements,
+            lines: body.lines,
+        })?;
+        let mut vm = parser.create().builder;
+
+        let previous = set_output_source(OutputSource::main("comptime"));
+        let result = vm.eval();
+        set_output_source(previous);
+        let value = result.map_err(|e| ValidationError::Comptime(format!("block failed - {e}")))?;
+        if !embeddable(&value) {
+            return Err(ValidationError::Comptime(format!(
+                "block must evaluate to a value, list, map, or tuple without objects, received {value}"
+            )));
+        }
+
+        let index = self.find_or_create_constant(value);
+        self.builder
+            .add_load_instruction(LoadValue::Constant(index));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod comptime_tests {
+    use crate::prepare::comptime::embeddable;
+    use rigz_core::{IndexMap, ObjectValue};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn values_are_embeddable() {
+        let map = ObjectValue::Map(IndexMap::from([(1.into(), vec![1, 2].into())]));
+        assert!(embeddable(&map));
+        assert!(embeddable(&ObjectValue::Tuple(vec![map, "a".into()])));
+    }
+}
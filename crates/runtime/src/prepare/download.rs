@@ -0,0 +1,254 @@
+use rigz_ast::{UrlImportOptions, ValidationError};
+use rustls::RootCertStore;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::CertificateDer;
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::sleep;
+use ureq::{Agent, AgentBuilder, ErrorKind, Proxy};
+use url::Url;
+
+const HTTPS_PROXY_VARS: [&str; 3] = ["RIGZ_PROXY", "HTTPS_PROXY", "ALL_PROXY"];
+const HTTP_PROXY_VARS: [&str; 3] = ["RIGZ_PROXY", "HTTP_PROXY", "ALL_PROXY"];
+const NO_PROXY_VARS: [&str; 1] = ["NO_PROXY"];
+const CA_BUNDLE_VARS: [&str; 2] = ["RIGZ_CA_BUNDLE", "SSL_CERT_FILE"];
+
+fn first_env(vars: &[&str]) -> Option<String> {
+    vars.iter().find_map(|v| {
+        env::var(v)
+            .or_else(|_| env::var(v.to_lowercase()))
+            .ok()
+            .filter(|s| !s.is_empty())
+    })
+}
+
+/// Each proxy variable only applies to urls with its scheme, none for hosts matching `no_proxy`
+pub(crate) fn proxy(url: &str, options: &UrlImportOptions) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    let no_proxy = options
+        .no_proxy
+        .clone()
+        .or_else(|| first_env(&NO_PROXY_VARS));
+    if no_proxy.is_some_and(|n| bypass_proxy(&n, host)) {
+        return None;
+    }
+    options.proxy.clone().or_else(|| match url.scheme() {
+        "https" => first_env(&HTTPS_PROXY_VARS),
+        "http" => first_env(&HTTP_PROXY_VARS),
+        _ => None,
+    })
+}
+
+/// Entries match the host or its subdomains, ports are ignored and `*` matches every host
+fn bypass_proxy(no_proxy: &str, host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    no_proxy
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let entry = entry.trim_start_matches('.');
+            let entry = match entry.rsplit_once(':') {
+                Some((domain, port))
+                    if !domain.contains(':') && port.chars().all(|c| c.is_ascii_digit()) =>
+                {
+                    domain
+                }
+                _ => entry,
+            };
+            let entry = entry.trim_start_matches('[').trim_end_matches(']');
+            host.eq_ignore_ascii_case(entry)
+                || host.len().checked_sub(entry.len() + 1).is_some_and(|i| {
+                    host.as_bytes()[i] == b'.' && host[i + 1..].eq_ignore_ascii_case(entry)
+                })
+        })
+}
+
+pub(crate) fn ca_bundle(options: &UrlImportOptions) -> Option<PathBuf> {
+    options
+        .ca_bundle
+        .clone()
+        .or_else(|| first_env(&CA_BUNDLE_VARS).map(PathBuf::from))
+}
+
+fn agent(url: &str, options: &UrlImportOptions) -> Result<Agent, ValidationError> {
+    let mut builder = AgentBuilder::new()
+        .timeout(options.timeout)
+        .try_proxy_from_env(false);
+
+    if let Some(proxy) = proxy(url, options) {
+        let p = Proxy::new(proxy.as_str())
+            .map_err(|e| ValidationError::DownloadFailed(format!("Invalid proxy {proxy} - {e}")))?;
+        builder = builder.proxy(p);
+    }
+
+    if let Some(bundle) = ca_bundle(options) {
+        let mut roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let invalid = |e: &dyn std::fmt::Display| {
+            ValidationError::DownloadFailed(format!("Invalid CA bundle {} - {e}", bundle.display()))
+        };
+        for cert in CertificateDer::pem_file_iter(&bundle).map_err(|e| invalid(&e))? {
+            let cert = cert.map_err(|e| invalid(&e))?;
+            roots.add(cert).map_err(|e| invalid(&e))?;
+        }
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        builder = builder.tls_config(Arc::new(config));
+    }
+    Ok(builder.build())
+}
+
+/// rustls errors are wrapped in an io::Error by the time they reach ureq
+fn is_tls_error(error: &ureq::Transport) -> bool {
+    let mut source = error.source();
+    while let Some(e) = source {
+        if e.is::<rustls::Error>() {
+            return true;
+        }
+        if let Some(inner) = e
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+        {
+            if inner.is::<rustls::Error>() {
+                return true;
+            }
+        }
+        source = e.source();
+    }
+    false
+}
+
+enum Failure {
+    Retry(String),
+    Fatal(String),
+}
+
+fn classify(url: &str, error: ureq::Error) -> Failure {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let message = format!("HTTP {code} {} from {url}", response.status_text());
+            if code == 429 || code >= 500 {
+                Failure::Retry(message)
+            } else {
+                Failure::Fatal(message)
+            }
+        }
+        ureq::Error::Transport(t) => {
+            if is_tls_error(&t) {
+                return Failure::Fatal(format!(
+                    "TLS error for {url} - {t} (set RIGZ_CA_BUNDLE to a PEM file if your network uses a custom certificate authority)"
+                ));
+            }
+            match t.kind() {
+                ErrorKind::Dns => Failure::Retry(format!("DNS lookup failed for {url} - {t}")),
+                ErrorKind::InvalidUrl | ErrorKind::UnknownScheme => {
+                    Failure::Fatal(format!("Invalid url {url} - {t}"))
+                }
+                ErrorKind::InvalidProxyUrl => Failure::Fatal(format!("Invalid proxy - {t}")),
+                ErrorKind::ProxyUnauthorized => {
+                    Failure::Fatal(format!("Proxy rejected credentials for {url} - {t}"))
+                }
+                ErrorKind::ProxyConnect => {
+                    Failure::Retry(format!("Failed to connect to proxy for {url} - {t}"))
+                }
+                _ => Failure::Retry(format!("Failed to download {url} - {t}")),
+            }
+        }
+    }
+}
+
+/// Fetches the contents of a url import, retrying transient failures with exponential backoff
+pub(crate) fn download(url: &str, options: &UrlImportOptions) -> Result<String, ValidationError> {
+    let agent = agent(url, options)?;
+    let mut backoff = options.retry_backoff;
+    let mut attempt = 0;
+    loop {
+        let message = match agent.get(url).call() {
+            Ok(r) => {
+                return r.into_string().map_err(|e| {
+                    ValidationError::DownloadFailed(format!("Failed to parse response {url} - {e}"))
+                })
+            }
+            Err(e) => match classify(url, e) {
+                Failure::Fatal(message) => return Err(ValidationError::DownloadFailed(message)),
+                Failure::Retry(message) => message,
+            },
+        };
+        if attempt >= options.retries {
+            return Err(ValidationError::DownloadFailed(format!(
+                "{message} (after {} attempts)",
+                attempt + 1
+            )));
+        }
+        attempt += 1;
+        sleep(backoff);
+        backoff *= 2;
+    }
+}
+
+#[cfg(test)]
+pub mod download_tests {
+    use crate::prepare::download::{download, proxy};
+    use rigz_ast::{UrlImportOptions, ValidationError};
+    use std::time::Duration;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn explicit_proxy_wins() {
+        let options = UrlImportOptions {
+            proxy: Some("http://localhost:3128".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            proxy("https://example.com/lib.rg", &options),
+            Some("http://localhost:3128".to_string())
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn no_proxy_hosts() {
+        let options = UrlImportOptions {
+            proxy: Some("http://localhost:3128".to_string()),
+            no_proxy: Some("localhost, .internal.dev:8443,10.0.0.1".to_string()),
+            ..Default::default()
+        };
+        for url in [
+            "http://localhost:8080/lib.rg",
+            "https://internal.dev/lib.rg",
+            "https://git.internal.dev/lib.rg",
+            "https://10.0.0.1/lib.rg",
+        ] {
+            assert_eq!(proxy(url, &options), None, "{url}");
+        }
+        for url in [
+            "https://example.com/lib.rg",
+            "https://notinternal.dev/lib.rg",
+        ] {
+            assert!(proxy(url, &options).is_some(), "{url}");
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn invalid_url_is_not_retried() {
+        let options = UrlImportOptions {
+            retries: 5,
+            retry_backoff: Duration::from_secs(60),
+            ..Default::default()
+        };
+        match download("not a url", &options) {
+            Err(ValidationError::DownloadFailed(message)) => {
+                assert!(message.starts_with("Invalid url"), "{message}")
+            }
+            r => panic!("unexpected result {r:?}"),
+        }
+    }
+}
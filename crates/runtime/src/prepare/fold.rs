@@ -0,0 +1,95 @@
+use rigz_ast::Expression;
+use rigz_core::{ObjectValue, OverflowPolicy, PrimitiveValue, UnaryOperation};
+use rigz_vm::{eval_binary_operation, eval_unary};
+
+/// Evaluates expressions made entirely of literals, i.e. `2 + 3 * 4` or `"a" + "b"`.
+/// Returns None if the expression depends on runtime values or would produce an error,
+/// errors are left to the VM so they're raised at the same point they would be without folding.
+pub(crate) fn fold_constant(expression: &Expression) -> Option<PrimitiveValue> {
+    let value = match expression {
+        Expression::Value(v) => return Some(v.clone()),
+        Expression::BinExp(lhs, op, rhs) => {
+            let lhs: ObjectValue = fold_constant(lhs)?.into();
+            let rhs: ObjectValue = fold_constant(rhs)?.into();
+            // overflow is an error here so it's left to the VM's policy
+            eval_binary_operation(*op, &lhs, &rhs, OverflowPolicy::Checked)
+        }
+        // print operations need to run at runtime
+        Expression::UnaryExp(
+            op @ (UnaryOperation::Neg | UnaryOperation::Not | UnaryOperation::Reverse),
+            e,
+        ) => {
+            let v: ObjectValue = fold_constant(e)?.into();
+            eval_unary(*op, &v, OverflowPolicy::Checked)
+        }
+        _ => return None,
+    };
+
+    match value {
+        ObjectValue::Primitive(PrimitiveValue::Error(_)) => None,
+        ObjectValue::Primitive(p) => Some(p),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub mod fold_tests {
+    use crate::prepare::fold::fold_constant;
+    use rigz_ast::Expression;
+    use rigz_core::{BinaryOperation, PrimitiveValue, UnaryOperation};
+    use wasm_bindgen_test::*;
+
+    fn bin(lhs: Expression, op: BinaryOperation, rhs: Expression) -> Expression {
+        Expression::BinExp(Box::new(lhs), op, Box::new(rhs))
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn fold_arithmetic() {
+        let exp = bin(
+            Expression::Value(2.into()),
+            BinaryOperation::Add,
+            bin(
+                Expression::Value(3.into()),
+                BinaryOperation::Mul,
+                Expression::Value(4.into()),
+            ),
+        );
+        assert_eq!(fold_constant(&exp), Some(14.into()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn fold_strings() {
+        let exp = bin(
+            Expression::Value("a".into()),
+            BinaryOperation::Add,
+            Expression::Value("b".into()),
+        );
+        assert_eq!(fold_constant(&exp), Some("ab".into()));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn skip_identifiers_and_errors() {
+        let exp = bin(
+            Expression::identifier("a"),
+            BinaryOperation::Add,
+            Expression::Value(1.into()),
+        );
+        assert_eq!(fold_constant(&exp), None);
+        let exp = bin(
+            Expression::Value(1.into()),
+            BinaryOperation::Div,
+            Expression::Value(0.into()),
+        );
+        assert_eq!(fold_constant(&exp), None);
+        let exp = Expression::UnaryExp(
+            UnaryOperation::PrintLn,
+            Box::new(Expression::Value(1.into())),
+        );
+        assert_eq!(fold_constant(&exp), None);
+        let exp = Expression::UnaryExp(
+            UnaryOperation::Not,
+            Box::new(Expression::Value(PrimitiveValue::Bool(true))),
+        );
+        assert_eq!(fold_constant(&exp), Some(false.into()));
+    }
+}
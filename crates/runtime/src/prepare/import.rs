@@ -0,0 +1,55 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::prepare::download::download;
+use rigz_ast::{ImportPath, ImportResolver, ParserOptions, ResolvedImport, ValidationError};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::read_to_string;
+
+/// Used when `ParserOptions::import_resolver` isn't set, files are read from disk and urls are downloaded
+/// using `ParserOptions::url_imports` & the `.rigzmanifest` of the importing file's project
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultImportResolver;
+
+impl ImportResolver for DefaultImportResolver {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn resolve(
+        &self,
+        path: &ImportPath,
+        options: &ParserOptions,
+    ) -> Result<ResolvedImport, ValidationError> {
+        match path {
+            ImportPath::Url(url) => {
+                let mut url_imports = options.url_imports.clone();
+                if let Some(directory) = &options.current_directory {
+                    url_imports.find_manifest(directory)?;
+                }
+                Ok(ResolvedImport {
+                    key: url.clone(),
+                    source: download(url, &url_imports)?,
+                })
+            }
+            ImportPath::File(file) => {
+                let source = read_to_string(file).map_err(|e| {
+                    ValidationError::InvalidImport(format!("Failed to read {file:?} - {e}"))
+                })?;
+                let key = file.canonicalize().unwrap_or_else(|_| file.clone());
+                Ok(ResolvedImport {
+                    key: key.to_string_lossy().to_string(),
+                    source,
+                })
+            }
+        }
+    }
+
+    /// There's no file system or blocking network access in the browser, imports have to be provided by
+    /// `ParserOptions::import_resolver`
+    #[cfg(target_arch = "wasm32")]
+    fn resolve(
+        &self,
+        path: &ImportPath,
+        _options: &ParserOptions,
+    ) -> Result<ResolvedImport, ValidationError> {
+        Err(ValidationError::InvalidImport(format!(
+            "Cannot import {path:?} in wasm, set ParserOptions::import_resolver to provide imports"
+        )))
+    }
+}
@@ -0,0 +1,158 @@
+use rigz_ast::{Element, Expression, FunctionExpression, RigzArguments, Scope, Statement};
+
+/// Whether a loop body can run directly in the enclosing scope instead of a scope call per
+/// iteration. Anything that could leave the current scope early (return, raise, try/catch) or
+/// create a value that outlives the loop variables (lambdas) keeps the scope based loop.
+pub(crate) fn inlinable(expression: &Expression) -> bool {
+    match expression {
+        Expression::This
+        | Expression::Value(_)
+        | Expression::Identifier(_, _)
+        | Expression::Symbol(_)
+        // loads the constant created by prepare
+        | Expression::Comptime(_) => true,
+        Expression::List(l) | Expression::Tuple(l) => l.iter().all(inlinable),
+        Expression::Map(m) => m.iter().all(|(k, v)| inlinable(k) && inlinable(v)),
+        Expression::BinExp(lhs, _, rhs) | Expression::Index(lhs, rhs) => {
+            inlinable(lhs) && inlinable(rhs)
+        }
+        Expression::UnaryExp(_, e) | Expression::Cast(e, _) | Expression::Annotated(e, _) => {
+            inlinable(e)
+        }
+        Expression::Function(f) => function_inlinable(f),
+        Expression::If {
+            condition,
+            then,
+            branch,
+        } => inlinable(condition) && scope_inlinable(then) && branch.iter().all(scope_inlinable),
+        Expression::Unless { condition, then } => inlinable(condition) && scope_inlinable(then),
+        // nested loops pick their own strategy, only the iterable runs in this scope
+        Expression::ForList { expression, .. } | Expression::ForMap { expression, .. } => {
+            inlinable(expression)
+        }
+        Expression::Lambda { .. }
+        | Expression::Scope(_)
+        | Expression::Lazy(_)
+        | Expression::Error(_)
+        | Expression::Return(_)
+        | Expression::Into { .. }
+        | Expression::DoubleBang(_)
+        | Expression::Try(_)
+        | Expression::Propagate(_)
+        | Expression::Catch { .. }
+        | Expression::With { .. }
+        | Expression::Select { .. }
+        | Expression::Unquote(_) => false,
+    }
+}
+
+/// Estimated number of instructions `expression` compiles to, loop bodies above
+/// `ParserOptions::max_inline_cost` keep their own scope
+pub(crate) fn cost(expression: &Expression) -> usize {
+    1 + match expression {
+        Expression::This
+        | Expression::Value(_)
+        | Expression::Identifier(_, _)
+        | Expression::Symbol(_)
+        | Expression::Comptime(_)
+        | Expression::Unquote(_) => 0,
+        Expression::List(l) | Expression::Tuple(l) => l.iter().map(cost).sum(),
+        Expression::Map(m) => m.iter().map(|(k, v)| cost(k) + cost(v)).sum(),
+        Expression::BinExp(lhs, _, rhs) | Expression::Index(lhs, rhs) => cost(lhs) + cost(rhs),
+        Expression::UnaryExp(_, e)
+        | Expression::Cast(e, _)
+        | Expression::Annotated(e, _)
+        | Expression::Error(e)
+        | Expression::DoubleBang(e)
+        | Expression::Try(e)
+        | Expression::Propagate(e)
+        | Expression::Lambda { body: e, .. } => cost(e),
+        Expression::Return(e) => e.as_deref().map_or(0, cost),
+        Expression::Function(f) => function_cost(f),
+        Expression::Into { base, next } => cost(base) + function_cost(next),
+        Expression::If {
+            condition,
+            then,
+            branch,
+        } => cost(condition) + scope_cost(then) + branch.as_ref().map_or(0, scope_cost),
+        Expression::Unless { condition, then } => cost(condition) + scope_cost(then),
+        Expression::Scope(s) | Expression::Lazy(s) => scope_cost(s),
+        Expression::Catch { base, catch, .. } => cost(base) + scope_cost(catch),
+        Expression::With { value, body, .. } => cost(value) + scope_cost(body),
+        Expression::Select { arms, after } => {
+            let arms: usize = arms
+                .iter()
+                .map(|a| cost(&a.source) + scope_cost(&a.body))
+                .sum();
+            arms + after.as_ref().map_or(0, |(t, s)| cost(t) + scope_cost(s))
+        }
+        Expression::ForList {
+            expression, body, ..
+        } => cost(expression) + cost(body),
+        Expression::ForMap {
+            expression,
+            key,
+            value,
+            ..
+        } => cost(expression) + cost(key) + value.as_deref().map_or(0, cost),
+    }
+}
+
+fn function_cost(function: &FunctionExpression) -> usize {
+    let arguments = |args: &RigzArguments| -> usize {
+        match args {
+            RigzArguments::Positional(a) => a.iter().map(cost).sum(),
+            RigzArguments::Mixed(a, n) => {
+                a.iter().map(cost).sum::<usize>() + n.iter().map(|(_, e)| cost(e)).sum::<usize>()
+            }
+            RigzArguments::Named(n) => n.iter().map(|(_, e)| cost(e)).sum(),
+        }
+    };
+    match function {
+        FunctionExpression::FunctionCall(_, args)
+        | FunctionExpression::TypeFunctionCall(_, _, args)
+        | FunctionExpression::TypeConstructor(_, args) => arguments(args),
+        FunctionExpression::InstanceFunctionCall(base, _, args) => cost(base) + arguments(args),
+    }
+}
+
+fn scope_cost(scope: &Scope) -> usize {
+    scope
+        .elements
+        .iter()
+        .map(|e| match e {
+            Element::Expression(e) => cost(e),
+            Element::Statement(Statement::Assignment { expression, .. }) => 1 + cost(expression),
+            Element::Statement(_) => 1,
+        })
+        .sum()
+}
+
+fn function_inlinable(function: &FunctionExpression) -> bool {
+    match function {
+        FunctionExpression::FunctionCall(_, args)
+        | FunctionExpression::TypeFunctionCall(_, _, args)
+        | FunctionExpression::TypeConstructor(_, args) => arguments_inlinable(args),
+        FunctionExpression::InstanceFunctionCall(base, _, args) => {
+            inlinable(base) && arguments_inlinable(args)
+        }
+    }
+}
+
+fn arguments_inlinable(args: &RigzArguments) -> bool {
+    // lambda arguments are only called by the function, they run in their own scope
+    let argument = |e: &Expression| matches!(e, Expression::Lambda { .. }) || inlinable(e);
+    match args {
+        RigzArguments::Positional(a) => a.iter().all(argument),
+        RigzArguments::Mixed(a, n) => a.iter().all(argument) && n.iter().all(|(_, e)| argument(e)),
+        RigzArguments::Named(n) => n.iter().all(|(_, e)| argument(e)),
+    }
+}
+
+fn scope_inlinable(scope: &Scope) -> bool {
+    scope.elements.iter().all(|e| match e {
+        Element::Expression(e) => inlinable(e),
+        Element::Statement(Statement::Assignment { expression, .. }) => inlinable(expression),
+        Element::Statement(_) => false,
+    })
+}
@@ -1,18 +1,33 @@
+mod comptime;
+#[cfg(not(target_arch = "wasm32"))]
+mod download;
+mod fold;
+mod import;
+mod inline;
 mod program;
 
+use crate::modules::ROUTE_EVENT_PREFIX;
+use crate::prepare::comptime::{COMPTIME_SIDE_EFFECTS, COMPTIME_SIDE_EFFECT_MODULES};
+use crate::prepare::fold::fold_constant;
+use crate::prepare::inline::{cost, inlinable};
+use crate::prepare::program::expression::{infer_lambda_arguments, matches_self_type, widens_to};
+use crate::timings::{Phase, Timings};
 use crate::RuntimeError;
+pub use import::DefaultImportResolver;
 use log::{error, warn, Level};
 pub use program::Program;
 use rigz_ast::*;
 use rigz_core::{
-    IndexMap, IndexMapEntry, Lifecycle, Number, ObjectValue, PrimitiveValue, RigzType,
+    AsPrimitive, BinaryOperation, EventLifecycle, IndexMap, IndexMapEntry, Interned, Lifecycle,
+    Number, ObjectValue, PrimitiveValue, RigzType,
+};
+use rigz_vm::{
+    CustomInstruction, Instruction, LoadValue, ObjectHookScopes, Parallel, RigzBuilder, VMBuilder,
+    VM,
 };
-use rigz_vm::{Instruction, LoadValue, RigzBuilder, VMBuilder, VM};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::env;
-use std::fmt::Debug;
-use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -41,14 +56,10 @@ impl FunctionCallSignature {
             RigzArguments::Positional(a) => Ok(a),
             RigzArguments::Mixed(a, n) => {
                 let mut args = a;
-                let (_, rem) = self.arguments.split_at(args.len());
-                args.extend(match_args(rem, n)?);
+                args.extend(self.match_args(args.len(), n)?);
                 Ok(args)
             }
-            RigzArguments::Named(n) => {
-                let (_, rem) = self.arguments.split_at(0);
-                match_args(rem, n)
-            }
+            RigzArguments::Named(n) => self.match_args(0, n),
         }
     }
 
@@ -60,54 +71,66 @@ impl FunctionCallSignature {
                 for e in a {
                     args.push(e)
                 }
-                let (_, rem) = self.arguments.split_at(args.len());
+                let (_, rem) = self
+                    .arguments
+                    .split_at(args.len().min(self.arguments.len()));
                 args.extend(match_args_ref(rem, n));
                 args
             }
-            RigzArguments::Named(n) => {
-                let (_, rem) = self.arguments.split_at(0);
-                match_args_ref(rem, n)
-            }
+            RigzArguments::Named(n) => match_args_ref(&self.arguments, n),
         }
     }
-}
 
-fn match_args(
-    rem: &[FunctionArgument],
-    named: Vec<(String, Expression)>,
-) -> Result<Vec<Expression>, ValidationError> {
-    let mut res = Vec::with_capacity(rem.len());
-    for (name, e) in named {
-        for arg in rem {
-            if arg.name == name {
-                res.push(e.clone());
-            }
-        }
-    }
-    if res.len() != rem.len() {
-        Err(ValidationError::InvalidFunction(format!(
-            "Invalid # of args, expected {} got {}",
-            rem.len(),
-            res.len()
-        )))
-    } else {
-        Ok(res)
+    /// Named arguments are reordered to match the declaration, missing arguments use their default
+    fn match_args(
+        &self,
+        positional: usize,
+        named: Vec<(String, Expression)>,
+    ) -> Result<Vec<Expression>, ValidationError> {
+        let function = &self.name;
+        if positional > self.arguments.len() {
+            return Err(ValidationError::InvalidFunction(format!(
+                "Too many arguments for {function}, expected {} got {positional}",
+                self.arguments.len()
+            )));
+        }
+        let (passed, rem) = self.arguments.split_at(positional);
+        for (index, (name, _)) in named.iter().enumerate() {
+            if passed.iter().any(|a| &a.name == name)
+                || named[..index].iter().any(|(n, _)| n == name)
+            {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "Argument {name} passed more than once to {function}"
+                )));
+            }
+            if !rem.iter().any(|a| &a.name == name) {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "Unknown argument {name} for {function}"
+                )));
+            }
+        }
+        let mut named = named;
+        rem.iter()
+            .map(|arg| match named.iter().position(|(n, _)| n == &arg.name) {
+                Some(index) => Ok(named.swap_remove(index).1),
+                None => arg.default.clone().ok_or_else(|| {
+                    ValidationError::MissingExpression(format!(
+                        "Missing argument {} for {function}",
+                        arg.name
+                    ))
+                }),
+            })
+            .collect()
     }
 }
 
 fn match_args_ref<'a>(
     rem: &[FunctionArgument],
-    named: &'a Vec<(String, Expression)>,
+    named: &'a [(String, Expression)],
 ) -> Vec<&'a Expression> {
-    let mut res = Vec::with_capacity(rem.len());
-    for (name, e) in named {
-        for arg in rem {
-            if &arg.name == name {
-                res.push(e);
-            }
-        }
-    }
-    res
+    rem.iter()
+        .filter_map(|arg| named.iter().find(|(n, _)| n == &arg.name).map(|(_, e)| e))
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -129,7 +152,7 @@ impl CallSignature {
 
 type FunctionCallSignatures = Vec<CallSignature>;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum ModuleDefinition {
     Imported,
     Module(ModuleTraitDefinition),
@@ -139,6 +162,8 @@ pub(crate) enum ModuleDefinition {
 #[allow(dead_code)]
 struct Imports {
     root: usize,
+    key: String,
+    lazy: bool,
 }
 
 #[derive(Debug)]
@@ -155,25 +180,30 @@ struct ObjectDeclaration {
     dep: Option<usize>,
 }
 
-#[derive(Debug, Hash, PartialEq, Eq)]
-enum ImportPath {
-    Url(String),
-    File(PathBuf),
-}
-
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct ProgramParser<'vm, T: RigzBuilder> {
     pub(crate) builder: T,
     pub(crate) modules: IndexMap<&'vm str, ModuleDefinition>,
     // todo nested functions are global, they should be removed if invalid
-    pub(crate) function_scopes: IndexMap<String, FunctionCallSignatures>,
+    pub(crate) function_scopes: IndexMap<Interned, FunctionCallSignatures>,
     pub(crate) constants: IndexMap<ObjectValue, usize>,
-    pub(crate) identifiers: HashMap<String, FunctionType>,
+    pub(crate) identifiers: HashMap<Interned, FunctionType>,
     pub(crate) types: HashMap<String, RigzType>,
     pub(crate) parser_options: ParserOptions,
     // todo imports should be fully resolved path
     imports: HashMap<ImportPath, Imports>,
     objects: HashMap<String, Rc<ObjectDeclaration>>,
+    // digest of the program being parsed, used as part of the key for `@memo(persist: ...)`
+    program_digest: String,
+    // return types of the functions & lambdas being parsed, innermost last, used to validate `expr?`
+    return_types: Vec<RigzType>,
+    // root scope of the lazy import being parsed, its functions run it before their body
+    lazy_import: Option<usize>,
+    // paths of the `Server.route` calls parsed so far, passed to `Server.listen`
+    server_routes: Vec<String>,
+    pub(crate) timings: Timings,
+    // set while parsing a `comptime` block, see `parse_comptime`
+    pub(crate) comptime: bool,
 }
 
 impl<T: RigzBuilder> Default for ProgramParser<'_, T> {
@@ -190,11 +220,34 @@ impl<T: RigzBuilder> Default for ProgramParser<'_, T> {
             parser_options: Default::default(),
             imports: Default::default(),
             objects: Default::default(),
+            program_digest: Default::default(),
+            return_types: Default::default(),
+            lazy_import: None,
+            server_routes: Default::default(),
+            timings: Default::default(),
+            comptime: false,
         }
     }
 }
 
 impl<'vm> ProgramParser<'vm, VMBuilder> {
+    pub(crate) fn new() -> Self {
+        let mut p = ProgramParser::default();
+        p.add_default_modules()
+            .expect("failed to register default modules");
+        p
+    }
+
+    pub(crate) fn with_options(parser_options: ParserOptions) -> Self {
+        let mut p = ProgramParser {
+            parser_options,
+            ..Default::default()
+        };
+        p.add_default_modules()
+            .expect("failed to register default modules");
+        p
+    }
+
     pub(crate) fn create(self) -> ProgramParser<'vm, VM> {
         let ProgramParser {
             builder,
@@ -206,6 +259,12 @@ impl<'vm> ProgramParser<'vm, VMBuilder> {
             parser_options,
             imports,
             objects,
+            program_digest,
+            return_types,
+            lazy_import,
+            server_routes,
+            timings,
+            comptime,
         } = self;
         ProgramParser {
             builder: builder.build(),
@@ -217,6 +276,12 @@ impl<'vm> ProgramParser<'vm, VMBuilder> {
             parser_options,
             imports,
             objects,
+            program_digest,
+            return_types,
+            lazy_import,
+            server_routes,
+            timings,
+            comptime,
         }
     }
 }
@@ -251,23 +316,6 @@ struct BestMatch {
 }
 
 impl<T: RigzBuilder> ProgramParser<'_, T> {
-    pub(crate) fn new() -> Self {
-        let mut p = ProgramParser::default();
-        p.add_default_modules()
-            .expect("failed to register default modules");
-        p
-    }
-
-    pub(crate) fn with_options(parser_options: ParserOptions) -> Self {
-        let mut p = ProgramParser {
-            parser_options,
-            ..Default::default()
-        };
-        p.add_default_modules()
-            .expect("failed to register default modules");
-        p
-    }
-
     pub(crate) fn register_module<M: ParsedModule + 'static>(
         &mut self,
         module: M,
@@ -280,6 +328,13 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             self.parse_object_definition(obj, Some(dep))?;
         }
         self.modules.insert(name, ModuleDefinition::Module(def));
+        let deterministic = module.deterministic();
+        for (func, handler) in module.instructions() {
+            self.builder.register_instruction(CustomInstruction {
+                deterministic,
+                ..CustomInstruction::new(name, func, handler)
+            });
+        }
         self.builder.register_module(module);
         Ok(())
     }
@@ -300,9 +355,11 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         program: Program,
         current: Option<usize>,
     ) -> Result<(), ValidationError> {
-        for element in program.elements {
-            self.parse_element(element)?;
-        }
+        // imported programs are keyed by their own digest
+        let digest = std::mem::replace(&mut self.program_digest, program.digest());
+        let result = self.parse_elements(program.elements, program.lines);
+        self.program_digest = digest;
+        result?;
         match current {
             None => {
                 self.builder.add_halt_instruction();
@@ -334,7 +391,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 (
                     a.name.clone(),
                     self.identifiers
-                        .insert(a.name.clone(), a.function_type.clone()),
+                        .insert(a.name.as_str().into(), a.function_type.clone()),
                 )
             })
             .collect();
@@ -343,6 +400,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             Expression::Scope(s) => s,
             ex => Scope {
                 elements: vec![Element::Expression(ex)],
+                ..Default::default()
             },
         };
         let fd = FunctionDefinition {
@@ -427,6 +485,25 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         self.parse_lambda(&name, arguments, var_args_start, body)?
                     }
                     exp => {
+                        // empty literals take the element types of the declaration, map entries must match it
+                        let exp = match exp {
+                            Expression::List(l)
+                                if l.is_empty() && matches!(rigz_type, RigzType::List(_)) =>
+                            {
+                                Expression::Annotated(
+                                    Box::new(Expression::List(l)),
+                                    rigz_type.clone(),
+                                )
+                            }
+                            Expression::Map(m) if matches!(rigz_type, RigzType::Map(_, _)) => {
+                                self.check_map_literal(&rigz_type, &m)?;
+                                Expression::Annotated(
+                                    Box::new(Expression::Map(m)),
+                                    rigz_type.clone(),
+                                )
+                            }
+                            exp => exp,
+                        };
                         let ext = self.rigz_type(&exp)?;
                         if ext != rigz_type {
                             return Err(ValidationError::InvalidType(format!(
@@ -475,7 +552,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     exp => {
                         let ext = self.rigz_type(&exp)?;
                         self.identifiers.insert(
-                            "self".to_string(),
+                            "self".into(),
                             FunctionType {
                                 rigz_type: ext,
                                 mutable: true,
@@ -515,11 +592,12 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         "Invalid InstanceSet call {base:?}"
                     )));
                 }
+                self.check_map_set(&base, &calls, &expression)?;
                 match base {
                     Expression::This => {
                         self.builder.add_get_self_mut_instruction();
                     }
-                    Expression::Identifier(id) => {
+                    Expression::Identifier(id, _) => {
                         self.builder.add_get_mutable_variable_instruction(id);
                     }
                     e => {
@@ -565,7 +643,9 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
 
     pub(crate) fn parse_statement(&mut self, statement: Statement) -> Result<(), ValidationError> {
         match statement {
-            Statement::Assignment { lhs, expression } => self.parse_assignment(lhs, expression)?,
+            Statement::Assignment {
+                lhs, expression, ..
+            } => self.parse_assignment(lhs, expression)?,
             Statement::BinaryAssignment {
                 lhs: Assign::Identifier(name, _),
                 op,
@@ -640,6 +720,8 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             Statement::ObjectDefinition(definition) => {
                 self.parse_object_definition(definition, None)?
             }
+            // calls were expanded by the parser
+            Statement::Macro(_) => {}
         }
         Ok(())
     }
@@ -663,10 +745,12 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                                     Expression::This,
                                     vec![AssignIndex::Identifier(f.name.clone())],
                                 ),
-                                expression: Expression::Identifier(f.name.clone()),
+                                expression: Expression::identifier(f.name.as_str()),
+                                shadow: false,
                             })
                         })
                         .collect(),
+                    ..Default::default()
                 };
                 let args = definition
                     .fields
@@ -678,7 +762,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         var_arg: false,
                         rest: false,
                     })
-                    .collect();
+                    .collect::<Vec<_>>();
                 let s = self.parse_constructor(body, rt.clone(), &args)?;
                 ObjectConstructor::Scope(args, None, s)
             }
@@ -689,6 +773,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             }
         };
 
+        let mut hooks = ObjectHookScopes::default();
         for func in definition.functions {
             match func {
                 FunctionDeclaration::Declaration {
@@ -731,7 +816,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         var_args_start,
                     };
                     let cs = CallSignature::Function(fcs, CallSite::Object(dep));
-                    match self.function_scopes.entry(name) {
+                    match self.function_scopes.entry(name.into()) {
                         IndexMapEntry::Occupied(mut ex) => ex.get_mut().push(cs),
                         IndexMapEntry::Vacant(v) => {
                             v.insert(vec![cs]);
@@ -744,7 +829,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         Some(f) => {
                             if f.rigz_type == RigzType::This {
                                 let old = self.identifiers.insert(
-                                    "self".to_string(),
+                                    "self".into(),
                                     FunctionType {
                                         rigz_type: rt.as_ref().clone(),
                                         mutable: f.mutable,
@@ -756,14 +841,29 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                             }
                         }
                     };
+                    let name = d.name.clone();
+                    let hook = match (name.as_str(), d.type_definition.arguments.len()) {
+                        ("hash", 0) | ("eq", 1) => this.is_some(),
+                        _ => false,
+                    };
                     self.parse_function_definition(d)?;
+                    if hook {
+                        if let Some(CallSignature::Function(_, CallSite::Scope(scope, _))) =
+                            self.function_scopes.get(&name).and_then(|s| s.last())
+                        {
+                            match name.as_str() {
+                                "hash" => hooks.hash = Some(*scope),
+                                _ => hooks.eq = Some(*scope),
+                            }
+                        }
+                    }
                     if let Some(old) = this {
                         match old {
                             None => {
                                 self.identifiers.remove("self");
                             }
                             Some(old) => {
-                                self.identifiers.insert("self".to_string(), old);
+                                self.identifiers.insert("self".into(), old);
                             }
                         }
                     }
@@ -771,6 +871,10 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             }
         }
 
+        if hooks != ObjectHookScopes::default() {
+            self.builder.set_object_hooks(obj.clone(), hooks);
+        }
+
         let decl = ObjectDeclaration {
             constructor,
             rigz_type: rt,
@@ -788,7 +892,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         &mut self,
         body: Scope,
         rigz_type: Arc<RigzType>,
-        args: &Vec<FunctionArgument>,
+        args: &[FunctionArgument],
     ) -> Result<usize, ValidationError> {
         let current_vars = self.identifiers.clone();
         let current = self.builder.current_scope();
@@ -796,6 +900,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             rigz_type.to_string(),
             args.iter()
                 .map(|a| (a.name.clone(), a.function_type.mutable))
+                .rev()
                 .collect(),
             None,
         );
@@ -803,16 +908,14 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         self.builder
             .add_create_object_instruction(rigz_type.clone());
         self.identifiers.insert(
-            "self".to_string(),
+            "self".into(),
             FunctionType {
                 rigz_type: rigz_type.as_ref().clone(),
                 mutable: true,
             },
         );
         self.builder.add_load_mut_instruction("self".to_string());
-        for e in body.elements {
-            self.parse_element(e)?;
-        }
+        self.parse_elements(body.elements, body.lines)?;
         self.builder.add_get_self_instruction();
         self.builder.exit_scope(current);
         self.identifiers = current_vars;
@@ -868,26 +971,33 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 self.builder.enter_scope(name.to_string(), args, set_self);
                 false
             }
-            Some(l) => {
-                let memoized = match &l {
-                    Lifecycle::Memo(_) => true,
-                    Lifecycle::Composite(all) => {
-                        all.iter().any(|l| matches!(l, Lifecycle::Memo(_)))
-                    }
-                    _ => false,
+            Some(mut l) => {
+                let memo = match &mut l {
+                    Lifecycle::Memo(m) => Some(m),
+                    Lifecycle::Composite(all) => all.iter_mut().find_map(|l| match l {
+                        Lifecycle::Memo(m) => Some(m),
+                        _ => None,
+                    }),
+                    _ => None,
                 };
+                let memoized = memo.is_some();
+                if let Some(persist) = memo.and_then(|m| m.persist.as_mut()) {
+                    persist.key = format!("{}:{name}", self.program_digest);
+                }
                 self.builder
                     .enter_lifecycle_scope(name.to_string(), l, args, set_self);
                 memoized
             }
         };
+        let mut lambda_args = Vec::new();
         for arg in &type_definition.arguments {
             let rt = &arg.function_type.rigz_type;
             match rt {
                 RigzType::Function(args, ret) => {
+                    lambda_args.push(arg.name.clone());
                     let args = args.to_vec();
                     let cs = CallSignature::Lambda(type_definition.clone(), args, *ret.clone());
-                    match self.function_scopes.entry(arg.name.clone()) {
+                    match self.function_scopes.entry(arg.name.as_str().into()) {
                         IndexMapEntry::Occupied(mut entry) => {
                             entry.get_mut().push(cs);
                         }
@@ -898,14 +1008,18 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 }
                 _ => {
                     self.identifiers
-                        .insert(arg.name.clone(), arg.function_type.clone());
+                        .insert(arg.name.as_str().into(), arg.function_type.clone());
                 }
             }
         }
         // todo store arguments variable
         let f_def = self.builder.current_scope();
+        if let Some(root) = self.lazy_import {
+            self.builder.add_call_once_instruction(root);
+        }
         let self_type = type_definition.self_type.clone();
-        match self.function_scopes.entry(name) {
+        let return_type = type_definition.return_type.rigz_type.clone();
+        match self.function_scopes.entry(name.into()) {
             IndexMapEntry::Occupied(mut entry) => {
                 entry.get_mut().push(CallSignature::Function(
                     type_definition,
@@ -920,9 +1034,13 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             }
         }
         if let Some(t) = &self_type {
-            self.identifiers.insert("self".to_string(), t.clone());
+            self.identifiers.insert("self".into(), t.clone());
         };
-        for e in body.elements {
+        self.return_types.push(return_type);
+        for (index, e) in body.elements.into_iter().enumerate() {
+            if let Some(line) = body.lines.get(index) {
+                self.builder.set_line(*line);
+            }
             match e {
                 Element::Expression(Expression::This) => match &self_type {
                     Some(t) if t.mutable => {
@@ -933,8 +1051,24 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 e => self.parse_element(e)?,
             }
         }
+        self.return_types.pop();
         self.builder.exit_scope(current_scope);
         self.identifiers = identifiers;
+        // lambda arguments are only callable within the body, `Mutex.lock(func)` shouldn't shadow a `func` lambda
+        for name in lambda_args {
+            let Some(signatures) = self.function_scopes.get_mut(&name) else {
+                continue;
+            };
+            if let Some(index) = signatures
+                .iter()
+                .rposition(|cs| matches!(cs, CallSignature::Lambda(..)))
+            {
+                signatures.remove(index);
+            }
+            if signatures.is_empty() {
+                self.function_scopes.shift_remove(&name);
+            }
+        }
         Ok(())
     }
 
@@ -950,7 +1084,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     name,
                 } => {
                     let type_definition = self.parse_type_signature(&name, type_definition)?;
-                    match self.function_scopes.entry(name) {
+                    match self.function_scopes.entry(name.into()) {
                         IndexMapEntry::Occupied(mut entry) => {
                             entry.get_mut().push(CallSignature::Function(
                                 type_definition,
@@ -1045,6 +1179,13 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         &mut self,
         expression: Expression,
     ) -> Result<(), ValidationError> {
+        if let Expression::BinExp(..) | Expression::UnaryExp(..) = &expression {
+            if let Some(v) = fold_constant(&expression) {
+                self.parse_folded_value(v);
+                return Ok(());
+            }
+        }
+
         match expression {
             Expression::DoubleBang(e) => {
                 self.parse_expression(*e)?;
@@ -1063,6 +1204,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 self.parse_tuple(v)?;
             }
             Expression::Index(base, index) => {
+                self.check_map_index(&base, &index)?;
                 self.parse_expression(*base)?;
                 self.parse_expression(*index)?;
                 self.builder.add_instance_get_instruction(false);
@@ -1077,7 +1219,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 self.parse_expression(*ex)?;
                 self.builder.add_unary_instruction(op);
             }
-            Expression::Identifier(id) => {
+            Expression::Identifier(id, _) => {
                 if self.function_scopes.contains_key(&id) {
                     self.call_function(None, &id, vec![].into())?;
                 } else {
@@ -1089,6 +1231,26 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 then,
                 branch,
             } => {
+                // only the branch that will run is kept for constant conditions
+                if let Some(c) = fold_constant(&condition) {
+                    let truthy = c.to_bool();
+                    match (truthy, branch) {
+                        (true, _) => {
+                            let if_output = self.parse_scope(then, "if")?;
+                            self.parse_value(true.into());
+                            self.builder.add_if_instruction(if_output);
+                        }
+                        (false, Some(p)) => {
+                            let else_output = self.parse_scope(p, "else")?;
+                            self.parse_value(true.into());
+                            self.builder.add_if_instruction(else_output);
+                        }
+                        (false, None) => {
+                            self.parse_folded_value(PrimitiveValue::None);
+                        }
+                    }
+                    return Ok(());
+                }
                 self.parse_expression(*condition)?;
                 let if_output = self.parse_scope(then, "if")?;
                 match branch {
@@ -1102,6 +1264,16 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 }
             }
             Expression::Unless { condition, then } => {
+                if let Some(c) = fold_constant(&condition) {
+                    if c.to_bool() {
+                        self.parse_folded_value(PrimitiveValue::None);
+                    } else {
+                        let unless = self.parse_scope(then, "unless")?;
+                        self.parse_value(false.into());
+                        self.builder.add_unless_instruction(unless);
+                    }
+                    return Ok(());
+                }
                 self.parse_expression(*condition)?;
                 let unless = self.parse_scope(then, "unless")?;
                 self.builder.add_unless_instruction(unless);
@@ -1120,6 +1292,36 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     "Dangling lambda cannot be used".to_string(),
                 ))
             }
+            Expression::ForList {
+                var,
+                expression: exp,
+                body,
+            } if !self.identifiers.contains_key(&var) && self.inline_body(&body) => {
+                self.parse_expression(*exp)?;
+                self.builder.add_for_list_start_instruction();
+                self.parse_inline_loop(vec![var], None, |p| p.parse_expression(*body))?;
+            }
+            Expression::ForMap {
+                k_var,
+                v_var,
+                expression,
+                key,
+                value,
+            } if k_var != v_var
+                && !self.identifiers.contains_key(&k_var)
+                && !self.identifiers.contains_key(&v_var)
+                && inlinable(&key)
+                && value.iter().all(|v| inlinable(v))
+                && cost(&key) + value.as_deref().map_or(0, cost)
+                    <= self.parser_options.max_inline_cost =>
+            {
+                self.parse_expression(*expression)?;
+                self.builder.add_for_map_start_instruction();
+                self.parse_inline_loop(vec![k_var, v_var], None, |p| match value {
+                    None => p.parse_expression(*key),
+                    Some(value) => p.parse_tuple(vec![*key, *value]),
+                })?;
+            }
             Expression::ForList {
                 var,
                 expression: exp,
@@ -1129,7 +1331,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 // todo extract type from expression
                 let old = self
                     .identifiers
-                    .insert(var.clone(), FunctionType::new(RigzType::Any));
+                    .insert(var.as_str().into(), FunctionType::new(RigzType::Any));
                 let inner_scope = self.builder.enter_scope(
                     "for-list".to_string(),
                     vec![(var.to_string(), false)],
@@ -1164,10 +1366,10 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 let current = self.builder.current_scope();
                 let k_old = self
                     .identifiers
-                    .insert(k_var.clone(), FunctionType::new(RigzType::Any));
+                    .insert(k_var.as_str().into(), FunctionType::new(RigzType::Any));
                 let v_old = self
                     .identifiers
-                    .insert(v_var.clone(), FunctionType::new(RigzType::Any));
+                    .insert(v_var.as_str().into(), FunctionType::new(RigzType::Any));
                 let inner_scope = self.builder.enter_scope(
                     "for-map".to_string(),
                     vec![(k_var.to_string(), false), (v_var.to_string(), false)],
@@ -1205,10 +1407,41 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 let s = self.parse_scope(s, "do")?;
                 self.builder.add_load_instruction(LoadValue::ScopeId(s));
             }
+            Expression::Lazy(s) => {
+                let s = self.parse_scope(s, "lazy")?;
+                self.builder.add_load_instruction(LoadValue::Lazy(s));
+            }
+            Expression::With {
+                module,
+                function,
+                value,
+                body,
+            } => {
+                let scope = self.parse_module_override(&module, &function, *value)?;
+                let current_vars = self.identifiers.clone();
+                let current = self.builder.current_scope();
+                let with = self.builder.enter_scope("with".to_string(), vec![], None);
+                // registered in the with scope's call frame, so it's removed when the scope exits
+                self.builder
+                    .add_override_module_instruction(module, function, scope);
+                self.parse_elements(body.elements, body.lines)?;
+                self.builder.exit_scope(current);
+                self.identifiers = current_vars;
+                self.builder.add_load_instruction(LoadValue::ScopeId(with));
+            }
+            Expression::Select { arms, after } => self.parse_select(arms, after)?,
+            Expression::Comptime(body) => self.parse_comptime(body)?,
+            Expression::Unquote(argument) => {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "unquote({argument}) can only be used within the quote of a macro"
+                )))
+            }
             Expression::Cast(e, t) => {
                 self.parse_expression(*e)?;
                 self.builder.add_cast_instruction(t);
             }
+            // only used by the type checker, the literal is already the annotated type
+            Expression::Annotated(e, _) => self.parse_expression(*e)?,
             Expression::Symbol(s) => {
                 let index = self.find_or_create_constant(s.into());
                 self.builder
@@ -1229,6 +1462,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             Expression::Into { base, next } => {
                 self.parse_function(next.prepend(*base))?;
             }
+            Expression::Propagate(e) => self.parse_propagate(*e)?,
             Expression::Try(b) => {
                 if let Expression::Catch { .. } = b.as_ref() {
                     return Err(ValidationError::InvalidType("Try/Catch cannot be part of the same expression, try will bubble up an error that can be caught".to_string()));
@@ -1247,9 +1481,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     var.map(|s| vec![(s, false)]).unwrap_or(vec![]),
                     None,
                 );
-                for e in catch.elements {
-                    self.parse_element(e)?;
-                }
+                self.parse_elements(catch.elements, catch.lines)?;
                 self.builder.exit_scope(current);
                 self.builder.add_catch_instruction(inner);
             }
@@ -1263,27 +1495,52 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
     ) -> Result<(), ValidationError> {
         match function_expression {
             FunctionExpression::FunctionCall(name, args) => {
-                self.call_function(None, &name, args)?;
+                match self.variable_index(&name, &args) {
+                    Some(index) => self.parse_expression(index)?,
+                    None => self.call_function(None, &name, args)?,
+                }
             }
             // todo make a clear delineation between self.foo & Self.foo
             FunctionExpression::TypeFunctionCall(rigz_type, name, args) => {
-                self.call_function(Some(rigz_type), &name, args)?;
+                let server = matches!(&rigz_type, RigzType::Custom(c) if c.name == "Server");
+                match name.as_str() {
+                    "route" if server => self.server_route(args)?,
+                    "listen" if server => self.server_listen(rigz_type, args)?,
+                    _ => self.call_function(Some(rigz_type), &name, args)?,
+                }
             }
             FunctionExpression::InstanceFunctionCall(exp, calls, args) => {
+                self.check_map_call(&exp, &calls, &args)?;
                 let len = calls.len();
                 assert!(len > 0, "Invalid Instance Function Call no calls");
                 let last = len - 1;
                 let mut calls = calls.into_iter().enumerate();
                 let (_, first) = calls.next().unwrap();
                 self.check_module_exists(&first)?;
-                match self.function_scopes.contains_key(&first) {
+                let field = match self.function_scopes.contains_key(&first) {
+                    true if last == 0 || args.is_empty() => {
+                        let this = self.rigz_type(&exp)?;
+                        self.field_type(&this, &first).is_some()
+                    }
+                    _ => false,
+                };
+                match self.function_scopes.contains_key(&first) && !field {
                     false => {
                         self.parse_expression(*exp)?;
                         self.builder.add_load_instruction(first.into());
                         self.builder.add_instance_get_instruction(false);
                     }
                     true if last == 0 => {
-                        self.call_extension_function(*exp, &first, args)?;
+                        let handled = match first.as_str() {
+                            "reduce" => self.inline_reduce(&exp, &args)?,
+                            "par_map" | "par_filter" | "par_each" => {
+                                self.call_parallel(&exp, &first, &args)?
+                            }
+                            _ => false,
+                        };
+                        if !handled {
+                            self.call_extension_function(*exp, &first, args)?;
+                        }
                         return Ok(());
                     }
                     true => {
@@ -1311,7 +1568,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             }
             FunctionExpression::TypeConstructor(ty, args) => {
                 let ty = ty.to_string();
-                let dec = match self.objects.get(&ty) {
+                let dec = match self.constructed_object(&ty) {
                     None => {
                         return Err(ValidationError::InvalidType(format!(
                             "Missing constructor for {ty}"
@@ -1320,10 +1577,8 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     Some(dec) => dec.clone(),
                 };
                 let (cargs, var, scope) = match &dec.constructor {
-                    ObjectConstructor::Scope(cargs, var, s) => {
-                        (cargs.clone(), var.clone(), Some(*s))
-                    }
-                    ObjectConstructor::Custom(cargs, var) => (cargs.clone(), var.clone(), None),
+                    ObjectConstructor::Scope(cargs, var, s) => (cargs.clone(), *var, Some(*s)),
+                    ObjectConstructor::Custom(cargs, var) => (cargs.clone(), *var, None),
                 };
 
                 let args = self.setup_call_args(
@@ -1339,6 +1594,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         arg_type: ArgType::Positional,
                         var_args_start: var,
                     },
+                    None,
                 )?;
 
                 match scope {
@@ -1393,7 +1649,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                             (is_vm, this.mutable)
                         }
                     };
-                    let len = self.setup_call_args(args, fcs)?;
+                    let len = self.setup_call_args(args, fcs, None)?;
                     self.process_extension_call(name.to_string(), vm_module, mutable, len, call);
                 }
                 CallSignature::Lambda(..) => {
@@ -1410,6 +1666,55 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         Ok(())
     }
 
+    /// Emits `ForNext`, the body, then `ForPush` (or `ReducePush` when there's an accumulator)
+    /// into the current scope, the matching start instruction must already be added.
+    /// Loop variables are only visible to the body and removed once the loop ends.
+    fn parse_inline_loop<F>(
+        &mut self,
+        vars: Vec<String>,
+        acc: Option<(String, FunctionType)>,
+        body: F,
+    ) -> Result<(), ValidationError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), ValidationError>,
+    {
+        for var in &vars {
+            self.identifiers
+                .insert(var.as_str().into(), FunctionType::new(RigzType::Any));
+        }
+        let acc_name = match acc {
+            None => None,
+            Some((name, function_type)) => {
+                self.identifiers.insert(name.as_str().into(), function_type);
+                Some(name)
+            }
+        };
+        let start = self.builder.next_instruction_index();
+        self.builder
+            .add_for_next_instruction(vars.clone(), acc_name.clone(), start);
+        body(self)?;
+        for var in &vars {
+            self.identifiers.remove(var);
+        }
+        match &acc_name {
+            None => self.builder.add_for_push_instruction(start),
+            Some(name) => {
+                self.identifiers.remove(name);
+                self.builder.add_reduce_push_instruction(start)
+            }
+        };
+        let end = self.builder.next_instruction_index();
+        self.builder.set_instruction(
+            start,
+            Instruction::ForNext {
+                vars,
+                acc: acc_name,
+                end,
+            },
+        );
+        Ok(())
+    }
+
     fn parse_list(&mut self, list: Vec<Expression>) -> Result<(), ValidationError> {
         let mut base = Vec::new();
         let mut values_only = true;
@@ -1423,7 +1728,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         values_only = false;
                         let index = Number::Int(index as i64);
                         self.builder
-                            .add_load_instruction(ObjectValue::List(base.into()).into());
+                            .add_load_instruction(ObjectValue::List(base).into());
                         base = Vec::new();
                         self.builder.add_load_instruction(index.into());
                         self.parse_expression(e)?;
@@ -1456,7 +1761,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     e => {
                         values_only = false;
                         self.builder
-                            .add_load_instruction(ObjectValue::Tuple(base.into()).into());
+                            .add_load_instruction(ObjectValue::Tuple(base).into());
                         base = Vec::new();
                         let index = Number::Int(index as i64);
                         self.builder.add_load_instruction(index.into());
@@ -1488,10 +1793,10 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     (Expression::Value(k), Expression::Value(v)) => {
                         base.insert(k.into(), v.into());
                     }
-                    (Expression::Identifier(k), Expression::Value(v)) => {
-                        base.insert(k.to_string().into(), v.into());
+                    (Expression::Identifier(k, _), Expression::Value(v)) => {
+                        base.insert(PrimitiveValue::String(k).into(), v.into());
                     }
-                    (Expression::Identifier(k), e) => {
+                    (Expression::Identifier(k, _), e) => {
                         values_only = false;
                         self.builder
                             .add_load_instruction(ObjectValue::Map(base).into());
@@ -1512,7 +1817,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 }
             } else {
                 match (k, v) {
-                    (Expression::Identifier(k), e) => {
+                    (Expression::Identifier(k, _), e) => {
                         self.builder.add_load_instruction(k.into());
                         self.parse_expression(e)?;
                         self.builder.add_instance_set_instruction();
@@ -1559,6 +1864,12 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             return Ok(Some(arguments));
         };
 
+        if self.comptime && COMPTIME_SIDE_EFFECTS.contains(&name) {
+            return Err(ValidationError::Comptime(format!(
+                "`{name}` isn't allowed in comptime blocks"
+            )));
+        }
+
         match name {
             "puts" => {
                 let len = arguments.len();
@@ -1585,7 +1896,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     };
 
                     let template = match arguments.next().unwrap() {
-                        Expression::Value(PrimitiveValue::String(s)) => s.clone(),
+                        Expression::Value(PrimitiveValue::String(s)) => s.to_string(),
                         _ => "{}".to_string(),
                     };
 
@@ -1660,11 +1971,20 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             "sleep" => {
                 if arguments.len() != 1 {
                     return Err(ValidationError::InvalidFunction(
-                        "`sleep` requires one argument, duration to sleep (ms)".to_string(),
+                        "`sleep` requires one argument, duration to sleep (ms or Duration)"
+                            .to_string(),
                     ));
                 }
                 let mut args = arguments.into_iter();
-                self.parse_expression(args.next().unwrap())?;
+                let duration = args.next().unwrap();
+                if let RigzType::Custom(c) = self.rigz_type(&duration)? {
+                    if c.name == "Time::Instant" || c.name == "DateTime" {
+                        return Err(ValidationError::InvalidType(format!(
+                            "`sleep` expects a Number (ms) or Duration, received {}, use `instant - Time.now`", c.name
+                        )));
+                    }
+                }
+                self.parse_expression(duration)?;
                 self.builder.add_sleep_instruction();
             }
             _ => return Ok(Some(RigzArguments::Positional(arguments))),
@@ -1704,7 +2024,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
 
         match fcs {
             CallSignature::Function(fcs, call) => {
-                let len = self.setup_call_args(arguments, fcs)?;
+                let len = self.setup_call_args(arguments, fcs, None)?;
                 match call {
                     CallSite::Scope(s, memo) => {
                         if memo {
@@ -1714,7 +2034,9 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         }
                     }
                     CallSite::Module(m) => {
-                        if vm_module {
+                        if let Some(opcode) = self.builder.find_instruction(&m, name) {
+                            self.builder.add_custom_instruction(opcode, len);
+                        } else if vm_module {
                             // self.builder.add_call_vm_extension_module_instruction(
                             //     m,
                             //     name.to_string(),
@@ -1749,6 +2071,47 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         Ok(())
     }
 
+    /// `Channel.new` creates the object of the same name from an imported module, `Channel::Channel`,
+    /// other objects are found in the first imported module that defines them, `Mutex.new` for `Sync::Mutex`
+    /// Type of `this.name` when it reads a field of the object. Fields take precedence over extensions of other
+    /// types with the same name, `req.method` on a `Server::Request` isn't `Http::Request.method`
+    pub(crate) fn field_type(&self, this: &RigzType, name: &str) -> Option<RigzType> {
+        let RigzType::Custom(c) = this else {
+            return None;
+        };
+        let field = self
+            .constructed_object(&c.name)?
+            .fields
+            .iter()
+            .find(|f| f.name == name)?;
+        let extension = self.function_scopes.get(name).is_some_and(|f| {
+            f.iter().any(|cs| match cs {
+                CallSignature::Function(f, _) => f
+                    .self_type
+                    .as_ref()
+                    .is_some_and(|t| matches_self_type(&t.rigz_type, this)),
+                CallSignature::Lambda(..) => false,
+            })
+        });
+        (!extension).then(|| field.attr_type.rigz_type.clone())
+    }
+
+    fn constructed_object(&self, ty: &str) -> Option<&Rc<ObjectDeclaration>> {
+        let module_object = format!("{ty}::{ty}");
+        self.objects
+            .get(ty)
+            .or_else(|| match self.modules.get(ty) {
+                Some(ModuleDefinition::Imported) => self.objects.get(&module_object),
+                _ => None,
+            })
+            .or_else(|| {
+                self.modules
+                    .iter()
+                    .filter(|(_, m)| matches!(m, ModuleDefinition::Imported))
+                    .find_map(|(m, _)| self.objects.get(&format!("{m}::{ty}")))
+            })
+    }
+
     fn best_matched_function(
         &self,
         name: &str,
@@ -1798,20 +2161,23 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     CallSignature::Function(fc, call_site) => {
                         // let arguments = fc.convert_ref(arguments); todo check arg type
                         let fc_arg_len = fc.arguments.len();
+                        // `Size.humanize 1` calls the module function, not an extension function of the same name
+                        let module_call = match (&call_site, &rigz_type) {
+                            (CallSite::Module(m), Some(RigzType::Custom(c))) => *m == c.name,
+                            _ => false,
+                        };
                         match (&fc.self_type, &rigz_type) {
-                            (None, None) => {
+                            (None, _) if rigz_type.is_none() || module_call => {
                                 if arg_len <= fc_arg_len {
                                     fcs = Some(CallSignature::Function(fc, call_site));
                                     break;
                                 } else {
                                     match fc.var_args_start {
-                                        None => {}
-                                        Some(i) => {
-                                            if (fc_arg_len - 1) % (arg_len - i) == 0 {
-                                                fcs = Some(CallSignature::Function(fc, call_site));
-                                                break;
-                                            }
+                                        Some(i) if (fc_arg_len - 1) % (arg_len - i) == 0 => {
+                                            fcs = Some(CallSignature::Function(fc, call_site));
+                                            break;
                                         }
+                                        _ => {}
                                     }
                                 }
                             }
@@ -1821,7 +2187,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                                 } else {
                                     s
                                 };
-                                if &ft.rigz_type == s {
+                                if matches_self_type(&ft.rigz_type, s) {
                                     if arg_len <= fc_arg_len {
                                         vm_module = ft.rigz_type.is_vm();
                                         mutable = ft.mutable;
@@ -1829,21 +2195,17 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                                         break;
                                     } else {
                                         match fc.var_args_start {
-                                            None => {}
-                                            Some(i) => {
-                                                if (fc_arg_len - 1) % (arg_len - i) == 0 {
-                                                    mutable = ft.mutable;
-                                                    fcs = Some(CallSignature::Function(
-                                                        fc, call_site,
-                                                    ));
-                                                    break;
-                                                }
+                                            Some(i) if (fc_arg_len - 1) % (arg_len - i) == 0 => {
+                                                mutable = ft.mutable;
+                                                fcs = Some(CallSignature::Function(fc, call_site));
+                                                break;
                                             }
+                                            _ => {}
                                         }
                                     }
                                 }
                             }
-                            (None, Some(_)) | (Some(_), None) => {}
+                            (None, _) | (Some(_), None) => {}
                         }
                     }
                     CallSignature::Lambda(acs, args, ret) => {
@@ -1855,6 +2217,27 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 }
             }
         }
+        if fcs.is_none() {
+            if let Some(this) = &rigz_type {
+                // inferred types can be more specific than declared self types
+                for cs in self.get_function(name)? {
+                    let CallSignature::Function(fc, call_site) = cs else {
+                        continue;
+                    };
+                    let Some(ft) = &fc.self_type else {
+                        continue;
+                    };
+                    if widens_to(this, &ft.rigz_type)
+                        && (arguments.len() <= fc.arguments.len() || fc.var_args_start.is_some())
+                    {
+                        vm_module = ft.rigz_type.is_vm();
+                        mutable = ft.mutable;
+                        fcs = Some(CallSignature::Function(fc, call_site));
+                        break;
+                    }
+                }
+            }
+        }
         // todo support runtime function matching?
         match fcs {
             None => match rigz_type {
@@ -1873,11 +2256,82 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         }
     }
 
+    /// `fn f{a, b}` & `fn f[a, b]` can be called with a map or list literal, its entries are bound to the arguments
+    /// and checked when the call is compiled. Maps & lists only known at runtime can't be checked, so they're rejected.
+    fn destructure_arguments(
+        &mut self,
+        arguments: RigzArguments,
+        fcs: &FunctionCallSignature,
+    ) -> Result<RigzArguments, ValidationError> {
+        let map = match fcs.arg_type {
+            ArgType::Positional => return Ok(arguments),
+            ArgType::Map => true,
+            ArgType::List => false,
+        };
+        let name = &fcs.name;
+        let (kind, example) = if map {
+            ("map", format!("`{name} {{a = 1}}` or `{name} a: 1`"))
+        } else {
+            ("list", format!("`{name} [1, 2]` or `{name} 1, 2`"))
+        };
+        let mut args = match arguments {
+            RigzArguments::Positional(args) if args.len() == 1 => args,
+            arguments => return Ok(arguments),
+        };
+        match args.remove(0) {
+            Expression::Map(entries) if map => {
+                let named = entries
+                    .into_iter()
+                    .map(|(key, value)| match key {
+                        Expression::Identifier(key, _)
+                        | Expression::Value(PrimitiveValue::String(key)) => {
+                            Ok((key.to_string(), value))
+                        }
+                        key => Err(ValidationError::InvalidFunction(format!(
+                            "Invalid key {key:?} for {name}, keys must be argument names"
+                        ))),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(RigzArguments::Named(named))
+            }
+            Expression::List(mut values) if !map => {
+                // `fn f[a, ..rest]` collects the remaining values
+                let rest = fcs.arguments.iter().position(|a| a.rest);
+                if let Some(rest) = rest.filter(|r| *r <= values.len()) {
+                    let remaining = values.split_off(rest);
+                    values.push(Expression::List(remaining));
+                } else if rest.is_none() && values.len() > fcs.arguments.len() {
+                    return Err(ValidationError::InvalidFunction(format!(
+                        "Too many values for {name}, expected at most {} got {}",
+                        fcs.arguments.len(),
+                        values.len()
+                    )));
+                }
+                Ok(RigzArguments::Positional(values))
+            }
+            arg => {
+                let destructured = match self.rigz_type(&arg) {
+                    Ok(RigzType::Map(..)) => map,
+                    Ok(RigzType::List(_)) => !map,
+                    _ => false,
+                };
+                if destructured {
+                    return Err(ValidationError::InvalidFunction(format!(
+                        "{name} takes {kind} arguments, its arguments can't be checked for a {kind} created at runtime, use {example}"
+                    )));
+                }
+                Ok(RigzArguments::Positional(vec![arg]))
+            }
+        }
+    }
+
     fn setup_call_args(
         &mut self,
         arguments: RigzArguments,
         fcs: FunctionCallSignature, // todo don't use FCS here, create a minimal type
+        this: Option<&RigzType>,
     ) -> Result<usize, ValidationError> {
+        let arguments = self.destructure_arguments(arguments, &fcs)?;
         let arguments = fcs.convert(arguments)?;
         let al = arguments.len();
         let arguments = if al < fcs.arguments.len() {
@@ -1937,17 +2391,29 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         }
         let arg_len = arguments.len();
         for (arg, expression) in fcs.arguments.iter().zip(arguments) {
+            let expression = match (expression, &arg.function_type.rigz_type) {
+                // `Time.measure do ... end`, blocks are lambdas for functions without arguments
+                (Expression::Scope(scope), RigzType::Function(args, _)) if args.is_empty() => {
+                    Expression::Lambda {
+                        arguments: vec![],
+                        var_args_start: None,
+                        body: Box::new(Expression::Scope(scope)),
+                    }
+                }
+                (e, _) => e,
+            };
             match expression {
                 Expression::Lambda {
-                    arguments,
+                    mut arguments,
                     var_args_start,
                     body,
                 } => {
+                    infer_lambda_arguments(&mut arguments, &arg.function_type.rigz_type, this);
                     self.parse_anon_lambda(&fcs, &arg.name, arguments, var_args_start, *body)?;
                 }
                 _ => {
                     if let RigzType::Function(args, res) = &arg.function_type.rigz_type {
-                        let Expression::Identifier(id) = expression else {
+                        let Expression::Identifier(id, _) = expression else {
                             return Err(ValidationError::InvalidFunction(format!("Function type argument, expected anonymous lambda or function reference |{args:?}| -> {res}, received {expression:?}")));
                         };
                         match self.function_scopes.get(&id) {
@@ -2001,6 +2467,229 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         Ok(arg_len)
     }
 
+    /// Loop bodies run in the enclosing scope when they're inlinable & estimated to cost at most
+    /// `ParserOptions::max_inline_cost` instructions
+    fn inline_body(&self, body: &Expression) -> bool {
+        inlinable(body) && cost(body) <= self.parser_options.max_inline_cost
+    }
+
+    /// `list.reduce(init, |acc, next| ...)` runs the lambda body as an inline loop instead of
+    /// the recursive List.reduce, returns false if the call should use the function instead
+    fn inline_reduce(
+        &mut self,
+        this_exp: &Expression,
+        args: &RigzArguments,
+    ) -> Result<bool, ValidationError> {
+        let RigzArguments::Positional(a) = args else {
+            return Ok(false);
+        };
+        let [init, Expression::Lambda {
+            arguments,
+            var_args_start: None,
+            body,
+        }] = a.as_slice()
+        else {
+            return Ok(false);
+        };
+        let [acc, next] = arguments.as_slice() else {
+            return Ok(false);
+        };
+        let simple_argument = |arg: &FunctionArgument| {
+            arg.default.is_none()
+                && !arg.var_arg
+                && !arg.rest
+                && !self.identifiers.contains_key(&arg.name)
+        };
+        if acc.name == next.name
+            || !simple_argument(acc)
+            || !simple_argument(next)
+            || !self.inline_body(body)
+        {
+            return Ok(false);
+        }
+
+        let rigz_type = self.rigz_type(this_exp)?;
+        if !matches!(rigz_type, RigzType::List(_)) {
+            return Ok(false);
+        }
+        // invalid calls are left to the function call for the usual errors
+        match self.best_matched_function("reduce", Some(rigz_type), args) {
+            Ok(BestMatch {
+                fcs: CallSignature::Function(..),
+                ..
+            }) => {}
+            _ => return Ok(false),
+        }
+
+        self.parse_expression(init.clone())?;
+        self.parse_expression(this_exp.clone())?;
+        self.builder.add_reduce_start_instruction();
+        let body = body.as_ref().clone();
+        self.parse_inline_loop(
+            vec![next.name.clone()],
+            Some((acc.name.clone(), acc.function_type.clone())),
+            |p| p.parse_expression(body),
+        )?;
+        Ok(true)
+    }
+
+    /// `List.par_map`, `List.par_filter`, & `List.par_each` run the function on a worker pool when the receiver is a
+    /// List, otherwise the Collections definitions run one element at a time
+    fn call_parallel(
+        &mut self,
+        this_exp: &Expression,
+        name: &str,
+        args: &RigzArguments,
+    ) -> Result<bool, ValidationError> {
+        let op = match name {
+            "par_map" => Parallel::Map,
+            "par_filter" => Parallel::Filter,
+            _ => Parallel::Each,
+        };
+        let rigz_type = self.rigz_type(this_exp)?;
+        if !matches!(rigz_type, RigzType::List(_)) {
+            return Ok(false);
+        }
+        // invalid calls are left to the function call for the usual errors
+        let fcs = match self.best_matched_function(name, Some(rigz_type.clone()), args) {
+            Ok(BestMatch {
+                fcs: CallSignature::Function(fcs, _),
+                ..
+            }) => fcs,
+            _ => return Ok(false),
+        };
+        self.setup_call_args(args.clone(), fcs, Some(&rigz_type))?;
+        self.parse_expression(this_exp.clone())?;
+        self.builder.add_parallel_instruction(op);
+        Ok(true)
+    }
+
+    /// `Server.route "/path", |req| ...` defines the handler as a detached `@on("http:/path")` function, so each
+    /// request runs in its own process & is answered with the handler's result by `req.respond`. The call only
+    /// records the path for `Server.listen`
+    fn server_route(&mut self, args: RigzArguments) -> Result<(), ValidationError> {
+        let invalid = || {
+            ValidationError::InvalidFunction(
+                "Server.route expects a path & a handler, i.e. Server.route \"/path\", |req| ..."
+                    .to_string(),
+            )
+        };
+        let RigzArguments::Positional(args) = args else {
+            return Err(invalid());
+        };
+        let Ok([path, handler]) = <[Expression; 2]>::try_from(args) else {
+            return Err(invalid());
+        };
+        let Expression::Value(PrimitiveValue::String(p)) = &path else {
+            return Err(ValidationError::InvalidFunction(format!(
+                "Server.route paths must be string literals, received {path:?}"
+            )));
+        };
+        let (mut arguments, body) = match handler {
+            Expression::Lambda {
+                arguments,
+                var_args_start: None,
+                body,
+            } if arguments.len() == 1 => (arguments, *body),
+            h => {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "Server.route handlers take the request, |req| ..., received {h:?}"
+                )))
+            }
+        };
+        self.check_module_exists("route")?;
+        let handler_type = self
+            .function_scopes
+            .get("route")
+            .into_iter()
+            .flatten()
+            .find_map(|cs| match cs {
+                CallSignature::Function(fcs, _) => match fcs.arguments.get(1) {
+                    Some(a) if matches!(a.function_type.rigz_type, RigzType::Function(..)) => {
+                        Some(a.function_type.rigz_type.clone())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .ok_or_else(|| {
+                ValidationError::InvalidFunction("Server module is not imported".to_string())
+            })?;
+        infer_lambda_arguments(&mut arguments, &handler_type, None);
+        let mut body = match body {
+            Expression::Scope(s) => s,
+            e => Scope {
+                elements: vec![e.into()],
+                ..Default::default()
+            },
+        };
+        // processes can't call functions, the result of the handler is passed to `req.respond` in place
+        let result = match body.elements.pop() {
+            Some(Element::Expression(e)) => e,
+            Some(s) => {
+                body.elements.push(s);
+                Expression::Value(PrimitiveValue::None)
+            }
+            None => Expression::Value(PrimitiveValue::None),
+        };
+        let respond = Expression::Function(FunctionExpression::InstanceFunctionCall(
+            Box::new(Expression::identifier(arguments[0].name.as_str())),
+            vec!["respond".to_string()],
+            RigzArguments::Positional(vec![result]),
+        ));
+        body.elements.push(respond.into());
+        let event = format!("{ROUTE_EVENT_PREFIX}{p}");
+        self.parse_function_definition(FunctionDefinition {
+            name: event.clone(),
+            type_definition: FunctionSignature {
+                arguments,
+                return_type: FunctionType::new(RigzType::Any),
+                self_type: None,
+                var_args_start: None,
+                arg_type: ArgType::Positional,
+            },
+            body,
+            lifecycle: Some(Lifecycle::On(EventLifecycle {
+                detached: true,
+                ..EventLifecycle::new(event)
+            })),
+        })?;
+        if !self.server_routes.iter().any(|r| p == r) {
+            self.server_routes.push(p.to_string());
+        }
+        self.parse_value(ObjectValue::default());
+        Ok(())
+    }
+
+    /// Routes are kept by the program instead of the module, `Server.listen` receives the paths of the
+    /// `Server.route` calls parsed before it
+    fn server_listen(
+        &mut self,
+        rigz_type: RigzType,
+        args: RigzArguments,
+    ) -> Result<(), ValidationError> {
+        let routes = Expression::List(
+            self.server_routes
+                .iter()
+                .map(|r| Expression::Value(r.as_str().into()))
+                .collect(),
+        );
+        let args = match args {
+            RigzArguments::Positional(args) => {
+                RigzArguments::Mixed(args, vec![("routes".to_string(), routes)])
+            }
+            RigzArguments::Mixed(args, mut named) => {
+                named.push(("routes".to_string(), routes));
+                RigzArguments::Mixed(args, named)
+            }
+            RigzArguments::Named(mut named) => {
+                named.push(("routes".to_string(), routes));
+                RigzArguments::Named(named)
+            }
+        };
+        self.call_function(Some(rigz_type), "listen", args)
+    }
+
     fn call_extension_function(
         &mut self,
         this_exp: Expression,
@@ -2016,10 +2705,10 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             fcs,
             mutable,
             vm_module,
-        } = self.best_matched_function(name, Some(rigz_type), &arguments)?;
+        } = self.best_matched_function(name, Some(rigz_type.clone()), &arguments)?;
         match fcs {
             CallSignature::Function(fcs, call) => {
-                let len = self.setup_call_args(arguments, fcs)?;
+                let len = self.setup_call_args(arguments, fcs, Some(&rigz_type))?;
                 self.parse_extension_expression(mutable, this_exp)?;
                 self.process_extension_call(name.to_string(), vm_module, mutable, len, call);
             }
@@ -2078,7 +2767,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         expression: Expression,
     ) -> Result<(), ValidationError> {
         match expression {
-            Expression::Identifier(id) => {
+            Expression::Identifier(id, _) => {
                 let id = id.to_string();
                 if mutable {
                     self.builder.add_get_mutable_variable_instruction(id);
@@ -2093,12 +2782,14 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         Ok(())
     }
 
-    fn parse_contents<P: Debug>(
+    fn parse_contents(
         &mut self,
         contents: String,
-        path: P,
+        path: String,
+        lazy: bool,
     ) -> Result<usize, ValidationError> {
         let input = contents.as_str();
+        let timer = self.timings.start();
         let parser = match Parser::prepare(input, self.parser_options.clone()) {
             Ok(p) => p,
             Err(e) => {
@@ -2107,7 +2798,9 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 )))
             }
         };
+        self.timings.stop(timer, Some(&path), Phase::Lex);
 
+        let timer = self.timings.start();
         let program = match parser.parse() {
             Ok(p) => p.into(),
             Err(e) => {
@@ -2116,58 +2809,127 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 )))
             }
         };
+        self.timings.stop(timer, Some(&path), Phase::Parse);
+        self.parse_imported_program(program, path, lazy)
+    }
+
+    /// Imported programs run in their own scope, the scope is returned
+    fn parse_imported_program(
+        &mut self,
+        program: Program,
+        path: String,
+        lazy: bool,
+    ) -> Result<usize, ValidationError> {
+        let timer = self.timings.start();
         let current = self.builder.current_scope();
         let dest = self.builder.enter_scope(format!("{path:?}"), vec![], None);
+        // eager imports within a lazy import run with it
+        let outer = match lazy {
+            true => self.lazy_import.replace(dest),
+            false => self.lazy_import,
+        };
         // skip validation, imports don't need to end with an expression
-        if let Err(e) = self.parse_scoped_program(program, Some(current)) {
+        let result = self.parse_scoped_program(program, Some(current));
+        self.lazy_import = outer;
+        if let Err(e) = result {
             return Err(ValidationError::InvalidImport(format!(
                 "Failed to process {path:?} - {e}"
             )));
         }
+        self.timings.stop(timer, Some(&path), Phase::Prepare);
 
         Ok(dest)
     }
 
-    fn parse_file(&mut self, parse: &PathBuf) -> Result<usize, ValidationError> {
-        let raw = match std::fs::read_to_string(parse) {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(ValidationError::InvalidImport(format!(
-                    "Failed to read {parse:?} - {e}"
-                )))
+    /// Lazy imports aren't called here, their functions call them the first time one of them runs.
+    /// Embedded imports are parsed from their block instead of being resolved, `bundle` writes their paths
+    /// relative to the entry so relative imports within them aren't joined with the embedded file's directory.
+    fn parse_import_path(
+        &mut self,
+        import_path: ImportPath,
+        lazy: bool,
+        embedded: Option<Scope>,
+    ) -> Result<(), ValidationError> {
+        if let Some(import) = self.imports.get(&import_path) {
+            if import.lazy && !lazy {
+                self.builder.add_call_once_instruction(import.root);
             }
+            return Ok(());
+        }
+        let path = match &import_path {
+            ImportPath::Url(url) => url.clone(),
+            ImportPath::File(path) => path.display().to_string(),
         };
-        self.parse_contents(raw, parse)
-    }
-
-    fn download(&self, url: &str) -> Result<String, ValidationError> {
-        match ureq::get(url).call().map(|r| r.into_string()) {
-            Ok(Ok(s)) => Ok(s),
-            Ok(Err(e)) => Err(ValidationError::DownloadFailed(format!(
-                "Failed to parse response {url} - {e}"
-            ))),
-            Err(e) => Err(ValidationError::DownloadFailed(format!(
-                "Failed to download {url} - {e}"
-            ))),
+        let (root, key) = match embedded {
+            Some(scope) => {
+                let program = Program {
+                    elements: scope.elements,
+                    lines: scope.lines,
+                };
+                (
+                    self.parse_imported_program(program, path.clone(), lazy)?,
+                    path,
+                )
+            }
+            None => {
+                let resolved = match &self.parser_options.import_resolver {
+                    Some(resolver) => resolver.resolve(&import_path, &self.parser_options),
+                    None => DefaultImportResolver.resolve(&import_path, &self.parser_options),
+                }?;
+                // the same source imported through another path
+                if let Some(import) = self.imports.values().find(|i| i.key == resolved.key) {
+                    if import.lazy && !lazy {
+                        self.builder.add_call_once_instruction(import.root);
+                    }
+                    return Ok(());
+                }
+                // relative imports within a file are joined with its directory
+                let directory = match &import_path {
+                    ImportPath::File(file) => file.parent().map(|p| p.to_path_buf()),
+                    ImportPath::Url(_) => None,
+                };
+                let outer = match directory {
+                    Some(d) => self.parser_options.current_directory.replace(d),
+                    None => self.parser_options.current_directory.clone(),
+                };
+                let root = self.parse_contents(resolved.source, path, lazy);
+                self.parser_options.current_directory = outer;
+                (root?, resolved.key)
+            }
+        };
+        if !lazy {
+            self.builder.add_call_instruction(root);
         }
-    }
-
-    fn parse_url(&mut self, url: &str) -> Result<usize, ValidationError> {
-        let contents = self.download(url)?;
-        self.parse_contents(contents, url)
-    }
-
-    fn parse_import_path(&mut self, import_path: ImportPath) -> Result<(), ValidationError> {
-        let root = match &import_path {
-            ImportPath::Url(url) => self.parse_url(url),
-            ImportPath::File(path) => self.parse_file(path),
-        }?;
-        self.builder.add_call_instruction(root);
-        self.imports.insert(import_path, Imports { root });
+        self.imports
+            .insert(import_path, Imports { root, key, lazy });
         Ok(())
     }
 
     fn parse_import(&mut self, import: ImportValue) -> Result<(), ValidationError> {
+        let (import, lazy) = match import {
+            ImportValue::Lazy(import) => (*import, true),
+            import => (import, false),
+        };
+        let (import, embedded) = match import {
+            ImportValue::Embedded(import, scope) => (*import, Some(scope)),
+            import => (import, None),
+        };
+        if self.comptime {
+            match &import {
+                ImportValue::TypeValue(m)
+                    if !COMPTIME_SIDE_EFFECT_MODULES.contains(&m.as_str()) => {}
+                ImportValue::TypeValue(m) => {
+                    return Err(ValidationError::Comptime(format!(
+                        "{m} module isn't allowed in comptime blocks"
+                    )))
+                }
+                i => {
+                    return Err(ValidationError::Comptime(format!(
+                        "imports aren't allowed in comptime blocks, received {i:?}"
+                    )))
+                }
+            }
+        }
         let name = match import {
             ImportValue::TypeValue(tv) => tv,
             ImportValue::FilePath(f) => {
@@ -2192,18 +2954,20 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     }
                     Some(p) => ImportPath::File(p.join(&f)),
                 };
-                if self.imports.contains_key(&parse) {
-                    return Ok(());
-                }
-                return self.parse_import_path(parse);
+                return self.parse_import_path(parse, lazy, embedded);
             }
             ImportValue::UrlPath(url) => {
-                let path = ImportPath::Url(url);
-                if self.imports.contains_key(&path) {
-                    return Ok(());
-                }
-
-                return self.parse_import_path(path);
+                return self.parse_import_path(ImportPath::Url(url), lazy, embedded);
+            }
+            ImportValue::Lazy(import) => {
+                return Err(ValidationError::InvalidImport(format!(
+                    "Invalid lazy import {import:?}"
+                )))
+            }
+            ImportValue::Embedded(import, _) => {
+                return Err(ValidationError::InvalidImport(format!(
+                    "Invalid embedded import {import:?}"
+                )))
             } // todo support `import "<URL | path>" as foo`
               // todo support `import dep` to support external resources, like package.json or Gemfile
         };
@@ -2229,6 +2993,26 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         Ok(())
     }
 
+    /// `s[1..3]` is parsed as calling `s` with a list, for variables that aren't functions it's an index instead
+    pub(crate) fn variable_index(&self, name: &str, args: &RigzArguments) -> Option<Expression> {
+        let RigzArguments::Positional(args) = args else {
+            return None;
+        };
+        let [Expression::List(index)] = args.as_slice() else {
+            return None;
+        };
+        if index.len() != 1
+            || !self.identifiers.contains_key(name)
+            || self.function_scopes.contains_key(name)
+        {
+            return None;
+        }
+        Some(Expression::Index(
+            Box::new(Expression::identifier(name)),
+            Box::new(index[0].clone()),
+        ))
+    }
+
     fn get_function(&self, name: &str) -> Result<FunctionCallSignatures, ValidationError> {
         match self.function_scopes.get(name) {
             None => Err(ValidationError::InvalidFunction(format!(
@@ -2242,20 +3026,124 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         self.builder.add_load_instruction(value.into());
     }
 
+    // not deduplicated through `constants`, loose equality would merge values like 0 & 0.0
+    fn parse_folded_value(&mut self, value: PrimitiveValue) {
+        let index = self.builder.add_constant(value.into());
+        self.builder
+            .add_load_instruction(LoadValue::Constant(index));
+    }
+
+    /// Records the source line of each element in the current scope's line table
+    fn parse_elements(
+        &mut self,
+        elements: Vec<Element>,
+        lines: Vec<usize>,
+    ) -> Result<(), ValidationError> {
+        for (index, element) in elements.into_iter().enumerate() {
+            if let Some(line) = lines.get(index) {
+                self.builder.set_line(*line);
+            }
+            self.parse_element(element)?;
+        }
+        Ok(())
+    }
+
     // dont use this for function scopes!
     fn parse_scope(&mut self, scope: Scope, named: &str) -> Result<usize, ValidationError> {
         let current_vars = self.identifiers.clone();
         let current = self.builder.current_scope();
         self.builder.enter_scope(named.to_string(), vec![], None);
         let res = self.builder.current_scope();
-        for e in scope.elements {
-            self.parse_element(e)?;
-        }
+        self.parse_elements(scope.elements, scope.lines)?;
         self.builder.exit_scope(current);
         self.identifiers = current_vars;
         Ok(res)
     }
 
+    /// Compiles the lambda replacing `module.function`, arguments are bound in the same order as
+    /// the module call so the override runs with the stack the module call would have used
+    fn parse_module_override(
+        &mut self,
+        module: &str,
+        function: &str,
+        value: Expression,
+    ) -> Result<usize, ValidationError> {
+        let name = format!("{module}.{function}");
+        let Expression::Lambda {
+            arguments,
+            var_args_start: None,
+            body,
+        } = value
+        else {
+            return Err(ValidationError::InvalidFunction(format!(
+                "with {name} expects a lambda without var args"
+            )));
+        };
+
+        self.check_module_exists(function)?;
+        let mut signatures: Vec<_> = self
+            .function_scopes
+            .get(function)
+            .into_iter()
+            .flatten()
+            .filter_map(|cs| match cs {
+                CallSignature::Function(fcs, CallSite::Module(m)) if m == module => Some((
+                    fcs.arguments.len(),
+                    fcs.self_type.as_ref().map(|t| t.mutable),
+                )),
+                _ => None,
+            })
+            .collect();
+        signatures.sort();
+        signatures.dedup();
+        let (arg_count, extension) = match signatures.as_slice() {
+            [] => {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "{name} is not a module function"
+                )))
+            }
+            [(_, Some(true))] => {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "Mutable extension {name} cannot be overridden"
+                )))
+            }
+            [(count, self_type)] => (*count, self_type.is_some()),
+            _ => {
+                return Err(ValidationError::InvalidFunction(format!(
+                    "{name} has multiple signatures, it cannot be overridden"
+                )))
+            }
+        };
+        // extension overrides receive self as the first argument
+        let expected = arg_count + usize::from(extension);
+        if arguments.len() != expected {
+            return Err(ValidationError::InvalidFunction(format!(
+                "with {name} expects a lambda with {expected} arguments, received {}",
+                arguments.len()
+            )));
+        }
+
+        let mut args: Vec<_> = arguments.iter().map(|a| (a.name.clone(), false)).collect();
+        // self is popped before the arguments of extension calls
+        args[usize::from(extension)..].reverse();
+        let current_vars = self.identifiers.clone();
+        for argument in arguments {
+            self.identifiers
+                .insert(argument.name.into(), argument.function_type);
+        }
+        let current = self.builder.current_scope();
+        let scope = self.builder.enter_scope(name, args, None);
+        self.return_types.push(RigzType::Any);
+        match *body {
+            Expression::Scope(s) => self.parse_elements(s.elements, s.lines)?,
+            e => self.parse_expression(e)?,
+        }
+        self.return_types.pop();
+        self.builder.exit_scope(current);
+        self.identifiers = current_vars;
+        Ok(scope)
+    }
+
     fn parse_anon_lambda(
         &mut self,
         _fcs: &FunctionCallSignature,
@@ -2274,6 +3162,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             Expression::Scope(s) => s,
             e => Scope {
                 elements: vec![e.into()],
+                ..Default::default()
             },
         };
 
@@ -2292,19 +3181,20 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             .map(|a| {
                 (
                     a.name.clone(),
-                    self.identifiers.insert(a.name, a.function_type.clone()),
+                    self.identifiers
+                        .insert(a.name.into(), a.function_type.clone()),
                 )
             })
             .collect();
-        for exp in s.elements {
-            self.parse_element(exp)?;
-        }
+        self.return_types.push(RigzType::Any);
+        self.parse_elements(s.elements, s.lines)?;
+        self.return_types.pop();
         old.into_iter().for_each(|(name, rt)| match rt {
             None => {
                 self.identifiers.remove(&name);
             }
             Some(s) => {
-                self.identifiers.insert(name, s);
+                self.identifiers.insert(name.into(), s);
             }
         });
         self.builder.exit_scope(current);
@@ -2312,4 +3202,158 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
         self.builder.add_load_instruction(LoadValue::ScopeId(anon));
         Ok(())
     }
+
+    /// `expr?` is lowered to `v = expr; if v.is_err; return v; else v end`
+    fn parse_propagate(&mut self, expression: Expression) -> Result<(), ValidationError> {
+        match self.return_types.last() {
+            None => {
+                return Err(ValidationError::InvalidType(
+                    "`?` can only be used within a function, use try or catch instead".to_string(),
+                ))
+            }
+            Some(rt) if !rt.can_return_error() => {
+                return Err(ValidationError::InvalidType(format!(
+                    "`?` requires a return type that allows errors, found {rt}"
+                )))
+            }
+            Some(_) => {}
+        }
+
+        // not a valid identifier, the value is overwritten by the next `?` in this scope
+        let var = "?propagate".to_string();
+        let rigz_type = self.rigz_type(&expression)?;
+        self.parse_expression(expression)?;
+        self.builder.add_load_mut_instruction(var.clone());
+        let old = self.identifiers.insert(
+            var.as_str().into(),
+            FunctionType {
+                rigz_type,
+                mutable: true,
+            },
+        );
+        let value = || Expression::identifier(var.as_str());
+        let lowered = Expression::If {
+            condition: Box::new(Expression::Function(
+                FunctionExpression::InstanceFunctionCall(
+                    Box::new(value()),
+                    vec!["is_err".to_string()],
+                    RigzArguments::Positional(vec![]),
+                ),
+            )),
+            then: Scope {
+                elements: vec![Expression::Return(Some(Box::new(value()))).into()],
+                ..Default::default()
+            },
+            branch: Some(Scope {
+                elements: vec![value().into()],
+                ..Default::default()
+            }),
+        };
+        let res = self.parse_expression(lowered);
+        match old {
+            None => self.identifiers.remove(&var),
+            Some(t) => self.identifiers.insert(var.into(), t),
+        };
+        res
+    }
+
+    /// `Instruction::Select` leaves the message & the index of the arm that received it,
+    /// the arms are lowered to an if chain over the index with `after` as the final branch
+    fn parse_select(
+        &mut self,
+        arms: Vec<SelectArm>,
+        after: Option<(Box<Expression>, Scope)>,
+    ) -> Result<(), ValidationError> {
+        let sources = arms.len();
+        let (source_expressions, bodies): (Vec<_>, Vec<_>) = arms
+            .into_iter()
+            .map(|a| (a.source, (a.binding, a.body)))
+            .unzip();
+        for source in source_expressions.into_iter().rev() {
+            self.parse_expression(source)?;
+        }
+        let after = match after {
+            None => None,
+            Some((timeout, body)) => {
+                self.parse_expression(*timeout)?;
+                Some(body)
+            }
+        };
+        self.builder
+            .add_select_instruction(sources, after.is_some());
+
+        // not valid identifiers, the values are overwritten by the next select in this scope
+        let arm = "?select_arm".to_string();
+        let value = "?select_value".to_string();
+        self.builder.add_load_mut_instruction(arm.clone());
+        self.builder.add_load_mut_instruction(value.clone());
+        let old_arm = self.identifiers.insert(
+            arm.as_str().into(),
+            FunctionType {
+                rigz_type: RigzType::Int,
+                mutable: true,
+            },
+        );
+        let old_value = self.identifiers.insert(
+            value.as_str().into(),
+            FunctionType {
+                rigz_type: RigzType::Any,
+                mutable: true,
+            },
+        );
+
+        let mut lowered = after.map(Expression::Scope);
+        for (index, (binding, body)) in bodies.into_iter().enumerate().rev() {
+            let then = match binding {
+                None => body,
+                Some(binding) => {
+                    let mut elements = Vec::with_capacity(body.elements.len() + 1);
+                    elements.push(Element::Statement(Statement::Assignment {
+                        lhs: Assign::Identifier(binding.into(), false),
+                        expression: Expression::identifier(value.as_str()),
+                        shadow: true,
+                    }));
+                    elements.extend(body.elements);
+                    let lines = body
+                        .lines
+                        .first()
+                        .copied()
+                        .into_iter()
+                        .chain(body.lines)
+                        .collect();
+                    Scope { elements, lines }
+                }
+            };
+            lowered = Some(match lowered {
+                // the last arm doesn't need to check the index without a timeout
+                None => Expression::Scope(then),
+                Some(branch) => Expression::If {
+                    condition: Box::new(Expression::binary(
+                        Expression::identifier(arm.as_str()),
+                        BinaryOperation::Eq,
+                        Expression::Value(PrimitiveValue::from(index as i64)),
+                    )),
+                    then,
+                    branch: Some(match branch {
+                        Expression::Scope(s) => s,
+                        e => Scope {
+                            elements: vec![e.into()],
+                            ..Default::default()
+                        },
+                    }),
+                },
+            });
+        }
+        let res = match lowered {
+            Some(lowered) => self.parse_expression(lowered),
+            None => Ok(()),
+        };
+        for (var, old) in [(arm, old_arm), (value, old_value)] {
+            match old {
+                None => self.identifiers.remove(&var),
+                Some(t) => self.identifiers.insert(var.into(), t),
+            };
+        }
+        res
+    }
 }
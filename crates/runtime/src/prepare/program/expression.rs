@@ -1,12 +1,365 @@
-use crate::prepare::{CallSignature, FunctionCallSignatures, ProgramParser};
+use crate::prepare::{CallSignature, CallSite, FunctionCallSignatures, ProgramParser};
 use itertools::Itertools;
-use rigz_ast::{Element, Expression, FunctionExpression, Scope, ValidationError};
-use rigz_core::{PrimitiveValue, RigzType, UnaryOperation, ValueRange, WithTypeInfo};
+use rigz_ast::{
+    AssignIndex, Element, Expression, FunctionArgument, FunctionExpression, RigzArguments, Scope,
+    ValidationError,
+};
+use rigz_core::{
+    BinaryOperation, CustomType, PrimitiveValue, RigzType, UnaryOperation, ValueRange, WithTypeInfo,
+};
 use rigz_vm::RigzBuilder;
 use std::cmp::Ordering;
 use std::collections::HashSet;
 
+#[derive(Clone, Copy, PartialEq)]
+enum TimeUnit {
+    Duration,
+    DateTime,
+    Number,
+}
+
+fn time_unit(rigz_type: &RigzType) -> Option<TimeUnit> {
+    match rigz_type {
+        RigzType::Custom(c) if c.name == "Duration" => Some(TimeUnit::Duration),
+        RigzType::Custom(c) if c.name == "DateTime" => Some(TimeUnit::DateTime),
+        RigzType::Int | RigzType::Float | RigzType::Number => Some(TimeUnit::Number),
+        // `Date.parse(..) + 1.days`, errors are propagated by the operation
+        RigzType::Wrapper {
+            base_type,
+            optional: false,
+            ..
+        } => time_unit(base_type),
+        _ => None,
+    }
+}
+
+/// Result of arithmetic on `DateTime` & `Duration`, None if neither side is a time value.
+/// Mixing units, i.e. `Time.now + Time.now` or `Time.seconds(1) + 5`, is an error
+pub(crate) fn time_operation_type(
+    lhs: &RigzType,
+    op: &BinaryOperation,
+    rhs: &RigzType,
+) -> Result<Option<RigzType>, ValidationError> {
+    use TimeUnit::*;
+    let (Some(l), Some(r)) = (time_unit(lhs), time_unit(rhs)) else {
+        return Ok(None);
+    };
+    if l == Number && r == Number {
+        return Ok(None);
+    }
+    let result = match (l, op, r) {
+        (DateTime, BinaryOperation::Sub, DateTime) => "Duration",
+        (DateTime, BinaryOperation::Add | BinaryOperation::Sub, Duration)
+        | (Duration, BinaryOperation::Add, DateTime) => "DateTime",
+        (Duration, BinaryOperation::Add | BinaryOperation::Sub, Duration)
+        | (Duration, BinaryOperation::Mul | BinaryOperation::Div, Number)
+        | (Number, BinaryOperation::Mul, Duration) => "Duration",
+        (Duration, BinaryOperation::Div, Duration) => return Ok(Some(RigzType::Float)),
+        (
+            _,
+            BinaryOperation::Add
+            | BinaryOperation::Sub
+            | BinaryOperation::Mul
+            | BinaryOperation::Div
+            | BinaryOperation::Rem,
+            _,
+        ) => {
+            return Err(ValidationError::InvalidType(format!(
+                "{lhs} {op} {rhs} mixes time units, use 5.seconds, Time.millis, etc. to convert numbers to Duration"
+            )))
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(RigzType::Custom(CustomType {
+        name: result.to_string(),
+        fields: vec![],
+    })))
+}
+
+/// Lambda arguments without a type use the matching argument of `expected` (`|Any| -> Bool` in Std signatures),
+/// if that is also Any, the element types of `this` are used for lambdas called on lists & maps
+pub(crate) fn infer_lambda_arguments(
+    arguments: &mut [FunctionArgument],
+    expected: &RigzType,
+    this: Option<&RigzType>,
+) {
+    let RigzType::Function(params, _) = expected else {
+        return;
+    };
+    let elements = match this {
+        Some(RigzType::List(e)) => vec![e.as_ref().clone()],
+        Some(RigzType::Map(k, v)) => vec![k.as_ref().clone(), v.as_ref().clone()],
+        _ => vec![],
+    };
+    let len = arguments.len();
+    for (index, arg) in arguments.iter_mut().enumerate() {
+        if arg.function_type.rigz_type != RigzType::Any {
+            continue;
+        }
+        let inferred = match params.get(index) {
+            Some(t) if *t != RigzType::Any => t.clone(),
+            _ if elements.len() == len => elements[index].clone(),
+            _ => continue,
+        };
+        arg.function_type.rigz_type = inferred;
+    }
+}
+
+/// Std extension functions are declared on `List` & `Map`, inferred element types shouldn't prevent matching them
+pub(crate) fn matches_self_type(self_type: &RigzType, this: &RigzType) -> bool {
+    if self_type == this {
+        return true;
+    }
+    match this {
+        RigzType::List(_) => *self_type == RigzType::List(Box::new(RigzType::Any)),
+        RigzType::Map(_, _) => {
+            *self_type == RigzType::Map(Box::new(RigzType::Any), Box::new(RigzType::Any))
+        }
+        // `(File.mmap path).next`, errors are propagated by the call
+        RigzType::Wrapper {
+            base_type,
+            optional: false,
+            ..
+        } => matches_self_type(self_type, base_type),
+        _ => false,
+    }
+}
+
+/// Fallback when no extension function is declared for `this`, i.e. Int can use functions declared for Number or Any
+pub(crate) fn widens_to(this: &RigzType, self_type: &RigzType) -> bool {
+    match self_type {
+        RigzType::Any => true,
+        RigzType::Number => matches!(this, RigzType::Int | RigzType::Float),
+        _ => matches_self_type(self_type, this),
+    }
+}
+
+/// Whether a value of `actual` can be stored where `expected` is declared, types that aren't known until
+/// runtime (Any, unions, optional values) are accepted
+pub(crate) fn assignable(expected: &RigzType, actual: &RigzType) -> bool {
+    match (expected, actual) {
+        (RigzType::Any, _)
+        | (
+            _,
+            RigzType::Any
+            | RigzType::This
+            | RigzType::Wrapper { .. }
+            | RigzType::Union(_)
+            | RigzType::Composite(_),
+        ) => true,
+        (RigzType::Number, RigzType::Int | RigzType::Float)
+        | (RigzType::Int | RigzType::Float, RigzType::Number) => true,
+        (RigzType::Wrapper { optional, .. }, RigzType::None) => *optional,
+        (RigzType::Wrapper { base_type, .. }, actual) => assignable(base_type, actual),
+        (RigzType::Union(types), actual) => types.iter().any(|t| assignable(t, actual)),
+        (RigzType::List(expected), RigzType::List(actual)) => assignable(expected, actual),
+        (RigzType::Map(ek, ev), RigzType::Map(ak, av)) => assignable(ek, ak) && assignable(ev, av),
+        (expected, actual) => expected == actual,
+    }
+}
+
+fn check_map_types(
+    map: &RigzType,
+    key: Option<RigzType>,
+    value: Option<RigzType>,
+) -> Result<(), ValidationError> {
+    let RigzType::Map(key_type, value_type) = map else {
+        return Ok(());
+    };
+    if let Some(key) = key.filter(|k| !assignable(key_type, k)) {
+        return Err(ValidationError::InvalidType(format!(
+            "{key} cannot be used as a key of {map}"
+        )));
+    }
+    if let Some(value) = value.filter(|v| !assignable(value_type, v)) {
+        return Err(ValidationError::InvalidType(format!(
+            "{value} cannot be used as a value of {map}"
+        )));
+    }
+    Ok(())
+}
+
+fn lambda_argument(arguments: &RigzArguments) -> Option<(Vec<FunctionArgument>, &Expression)> {
+    let last = match arguments {
+        RigzArguments::Positional(a) | RigzArguments::Mixed(a, _) => a.last(),
+        RigzArguments::Named(_) => None,
+    };
+    match last {
+        Some(Expression::Lambda {
+            arguments, body, ..
+        }) => Some((arguments.clone(), body.as_ref())),
+        _ => None,
+    }
+}
+
 impl<T: RigzBuilder> ProgramParser<'_, T> {
+    /// Type of the lambda body with its arguments in scope
+    pub(crate) fn lambda_return_type(
+        &mut self,
+        arguments: &[FunctionArgument],
+        body: &Expression,
+    ) -> Result<RigzType, ValidationError> {
+        let old: Vec<_> = arguments
+            .iter()
+            .map(|a| {
+                (
+                    a.name.clone(),
+                    self.identifiers
+                        .insert(a.name.as_str().into(), a.function_type.clone()),
+                )
+            })
+            .collect();
+        let result = self.rigz_type(body);
+        for (name, previous) in old {
+            match previous {
+                None => {
+                    self.identifiers.remove(&name);
+                }
+                Some(p) => {
+                    self.identifiers.insert(name.into(), p);
+                }
+            }
+        }
+        result
+    }
+
+    /// Std signatures return `List` for `map` & `filter`, use the lambda to get the element type.
+    /// Lambdas expected to return `Bool` keep the type of `this`, lambdas returning `Any` replace the element type.
+    fn refine_lambda_result(
+        &mut self,
+        this: &RigzType,
+        name: &str,
+        arguments: &RigzArguments,
+        result: RigzType,
+    ) -> Result<RigzType, ValidationError> {
+        let RigzType::List(element) = &result else {
+            return Ok(result);
+        };
+        if **element != RigzType::Any || !matches!(this, RigzType::List(_) | RigzType::Map(_, _)) {
+            return Ok(result);
+        }
+        let Some((mut lambda_args, body)) = lambda_argument(arguments) else {
+            return Ok(result);
+        };
+        let expected = self.function_scopes.get(name).and_then(|f| {
+            f.iter().find_map(|cs| match cs {
+                CallSignature::Function(f, _) => f
+                    .self_type
+                    .as_ref()
+                    .filter(|t| matches_self_type(&t.rigz_type, this))
+                    .and_then(|_| f.arguments.last())
+                    .map(|a| a.function_type.rigz_type.clone()),
+                CallSignature::Lambda(..) => None,
+            })
+        });
+        let Some(expected) = expected else {
+            return Ok(result);
+        };
+        let RigzType::Function(_, expected_result) = &expected else {
+            return Ok(result);
+        };
+        infer_lambda_arguments(&mut lambda_args, &expected, Some(this));
+        let refined = match expected_result.as_ref() {
+            RigzType::Bool if matches!(this, RigzType::List(_)) => this.clone(),
+            // lambda bodies that can't be typed yet are validated when they're parsed
+            RigzType::Any => match self.lambda_return_type(&lambda_args, body) {
+                Ok(t) => RigzType::List(Box::new(t)),
+                Err(_) => result,
+            },
+            _ => result,
+        };
+        Ok(refined)
+    }
+
+    /// Type of a map variable declared with key or value types, i.e. `m: {String, Int}` or `m = {}: {String, Int}`
+    fn typed_map(&self, base: &Expression) -> Option<RigzType> {
+        let Expression::Identifier(id, _) = base else {
+            return None;
+        };
+        match &self.identifiers.get(id)?.rigz_type {
+            RigzType::Map(k, v) if **k != RigzType::Any || **v != RigzType::Any => {
+                Some(RigzType::Map(k.clone(), v.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Expressions that can't be typed yet are checked at runtime
+    fn known_type(&mut self, expression: &Expression) -> Option<RigzType> {
+        self.rigz_type(expression).ok()
+    }
+
+    /// Entries of a map literal assigned to a typed map, identifier keys are strings
+    pub(crate) fn check_map_literal(
+        &mut self,
+        map: &RigzType,
+        entries: &[(Expression, Expression)],
+    ) -> Result<(), ValidationError> {
+        for (key, value) in entries {
+            let key = match key {
+                Expression::Identifier(_, _) => Some(RigzType::String),
+                key => self.known_type(key),
+            };
+            let value = self.known_type(value);
+            check_map_types(map, key, value)?;
+        }
+        Ok(())
+    }
+
+    /// `m.insert key, value` & `m.get key` on typed maps, mixing key types would break later lookups
+    pub(crate) fn check_map_call(
+        &mut self,
+        base: &Expression,
+        calls: &[String],
+        args: &RigzArguments,
+    ) -> Result<(), ValidationError> {
+        let Some(map) = self.typed_map(base) else {
+            return Ok(());
+        };
+        let (RigzArguments::Positional(args), [function]) = (args, calls) else {
+            return Ok(());
+        };
+        let (key, value) = match (function.as_str(), args.as_slice()) {
+            ("insert", [key, value]) => (key, Some(value)),
+            ("get", [key]) => (key, None),
+            _ => return Ok(()),
+        };
+        let key = self.known_type(key);
+        let value = value.and_then(|v| self.known_type(v));
+        check_map_types(&map, key, value)
+    }
+
+    /// `m[key]` on typed maps
+    pub(crate) fn check_map_index(
+        &mut self,
+        base: &Expression,
+        index: &Expression,
+    ) -> Result<(), ValidationError> {
+        let Some(map) = self.typed_map(base) else {
+            return Ok(());
+        };
+        let key = self.known_type(index);
+        check_map_types(&map, key, None)
+    }
+
+    /// `m[key] = value` & `m.key = value` on typed maps
+    pub(crate) fn check_map_set(
+        &mut self,
+        base: &Expression,
+        calls: &[AssignIndex],
+        value: &Expression,
+    ) -> Result<(), ValidationError> {
+        let (Some(map), [index]) = (self.typed_map(base), calls) else {
+            return Ok(());
+        };
+        let key = match index {
+            AssignIndex::Identifier(_) => Some(RigzType::String),
+            AssignIndex::Index(index) => self.known_type(index),
+        };
+        let value = self.known_type(value);
+        check_map_types(&map, key, value)
+    }
+
     fn scope_type(&mut self, scope: &Scope) -> Result<RigzType, ValidationError> {
         let e = match scope.elements.last() {
             None => {
@@ -32,7 +385,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             Expression::This => self.identifiers["self"].clone().rigz_type,
             Expression::Value(v) => v.rigz_type(),
             Expression::Error(_) => RigzType::Error,
-            Expression::Identifier(a) => match self.identifiers.get(a) {
+            Expression::Identifier(a, _) => match self.identifiers.get(a) {
                 None => {
                     self.check_module_exists(a)?;
                     match self.function_scopes.get(a) {
@@ -53,10 +406,18 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 }
                 Some(v) => v.clone().rigz_type,
             },
-            Expression::BinExp(lhs, _, rhs) => {
+            Expression::BinExp(lhs, op, rhs) => {
                 let rhs = self.rigz_type(rhs)?;
                 let lhs = self.rigz_type(lhs)?;
 
+                if let Some(t) = time_operation_type(&lhs, op, &rhs)? {
+                    return Ok(t);
+                }
+
+                if matches!(op, BinaryOperation::Range | BinaryOperation::RangeInclusive) {
+                    return Ok(RigzType::Range);
+                }
+
                 match lhs.partial_cmp(&rhs) {
                     None => RigzType::Any,
                     Some(ord) => match ord {
@@ -77,8 +438,8 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 | UnaryOperation::PrintLn
                 | UnaryOperation::EPrintLn => RigzType::None,
             },
-            Expression::Cast(_, r) => r.clone(),
-            Expression::Scope(s) => self.scope_type(s)?,
+            Expression::Cast(_, r) | Expression::Annotated(_, r) => r.clone(),
+            Expression::Scope(s) | Expression::Lazy(s) => self.scope_type(s)?,
             Expression::Function(fe) => self.function_type(fe)?,
             Expression::Symbol(_) => RigzType::String,
             Expression::If { then, branch, .. } => match branch {
@@ -98,7 +459,9 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 None => RigzType::None,
                 Some(e) => self.rigz_type(e)?,
             },
-            Expression::Lambda { body, .. } => self.rigz_type(body)?,
+            Expression::Lambda {
+                arguments, body, ..
+            } => self.lambda_return_type(arguments, body)?,
             Expression::ForList { body, .. } => RigzType::List(self.rigz_type(body)?.into()),
             Expression::ForMap { key, value, .. } => match value {
                 None => {
@@ -131,7 +494,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 if let Expression::Value(PrimitiveValue::Range(v)) = base.as_ref() {
                     match v {
                         ValueRange::Int(_) => RigzType::Int,
-                        ValueRange::Char(_) => RigzType::String,
+                        ValueRange::Char(_) | ValueRange::Date(_) => RigzType::String,
                     }
                 } else {
                     let base = self.rigz_type(base)?;
@@ -142,7 +505,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 // todo check base arg
                 self.function_type(next)?
             }
-            Expression::Try(e) => self.rigz_type(e)?,
+            Expression::Try(e) | Expression::Propagate(e) => self.rigz_type(e)?,
             Expression::Catch { base, catch, .. } => {
                 let base = self.rigz_type(base)?;
                 let catch = self.scope_type(catch)?;
@@ -152,6 +515,13 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     RigzType::Union(vec![base, catch])
                 }
             }
+            Expression::With { body, .. } => self.scope_type(body)?,
+            // arm bindings are only declared while the arms are compiled
+            Expression::Select { .. } => RigzType::Any,
+            // the block isn't evaluated until it's parsed
+            Expression::Comptime(_) => RigzType::Any,
+            // only valid within a macro, calls are expanded by the parser
+            Expression::Unquote(_) => RigzType::Any,
         };
         Ok(t)
     }
@@ -167,7 +537,8 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
             RigzType::String => RigzType::String,
             RigzType::List(l) | RigzType::Map(_, l) => *l,
             RigzType::Type => RigzType::Error,
-            RigzType::Range => unreachable!(),
+            // int, char, or date ranges built at runtime
+            RigzType::Range => RigzType::Any,
             RigzType::This => {
                 // todo improve this logic, move most of this to a function
                 match self.identifiers.get("self") {
@@ -186,7 +557,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
 
     fn function_type(&mut self, fe: &FunctionExpression) -> Result<RigzType, ValidationError> {
         let e = match fe {
-            FunctionExpression::FunctionCall(name, _) => {
+            FunctionExpression::FunctionCall(name, args) => {
                 match name.as_str() {
                     "puts" | "log" | "sleep" => return Ok(RigzType::None),
                     "spawn" => return Ok(RigzType::Int),
@@ -195,6 +566,9 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     _ => {}
                 }
 
+                if let Some(index) = self.variable_index(name, args) {
+                    return self.rigz_type(&index);
+                }
                 self.check_module_exists(name)?;
                 match self.function_scopes.get(name) {
                     None => {
@@ -220,8 +594,16 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                             let matched: HashSet<_> = f
                                 .iter()
                                 .filter_map(|cs| match cs {
-                                    CallSignature::Function(f, _) => match &f.self_type {
-                                        None => None,
+                                    CallSignature::Function(f, site) => match &f.self_type {
+                                        // `Time.seconds 1` calls the module function, not `Number.seconds`
+                                        None => match (site, r) {
+                                            (CallSite::Module(m), RigzType::Custom(c))
+                                                if *m == c.name =>
+                                            {
+                                                Some(f.return_type.rigz_type.clone())
+                                            }
+                                            _ => None,
+                                        },
                                         Some(ft) => {
                                             if &ft.rigz_type == r {
                                                 Some(f.return_type.rigz_type.clone())
@@ -248,7 +630,7 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                     }
                 }
             }
-            FunctionExpression::InstanceFunctionCall(ex, calls, _) => {
+            FunctionExpression::InstanceFunctionCall(ex, calls, args) => {
                 let this = self.rigz_type(ex)?;
                 let this = match this {
                     RigzType::This => match self.identifiers.get("self") {
@@ -267,7 +649,16 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                 };
                 // todo need to handle call chaining
                 self.check_module_exists(name)?;
-                match self.function_scopes.get(name) {
+                // frozen values keep the type they wrap, `[1].freeze.first`
+                if name == "freeze" && calls.len() == 1 {
+                    return Ok(this);
+                }
+                if calls.len() == 1 && args.is_empty() {
+                    if let Some(t) = self.field_type(&this, name) {
+                        return Ok(t);
+                    }
+                }
+                let result = match self.function_scopes.get(name) {
                     None => {
                         return Err(ValidationError::InvalidFunction(format!(
                             "extension function {this}.{name} does not exist",
@@ -277,17 +668,22 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                         // todo ignore extension functions here
                         if f.len() > 1 {
                             // todo support union types
-                            let matched: HashSet<_> = f
-                                .iter()
-                                .filter_map(|cs| match cs {
-                                    CallSignature::Function(f, _) => f
-                                        .self_type
-                                        .as_ref()
-                                        .filter(|t| t.rigz_type == this)
-                                        .map(|_| f.return_type.rigz_type.clone()),
-                                    CallSignature::Lambda(_, _, ret) => Some(ret.clone()),
-                                })
-                                .collect();
+                            let matching = |matches: &dyn Fn(&RigzType) -> bool| -> HashSet<_> {
+                                f.iter()
+                                    .filter_map(|cs| match cs {
+                                        CallSignature::Function(f, _) => f
+                                            .self_type
+                                            .as_ref()
+                                            .filter(|t| matches(&t.rigz_type))
+                                            .map(|_| f.return_type.rigz_type.clone()),
+                                        CallSignature::Lambda(_, _, ret) => Some(ret.clone()),
+                                    })
+                                    .collect()
+                            };
+                            let mut matched = matching(&|t| matches_self_type(t, &this));
+                            if matched.is_empty() {
+                                matched = matching(&|t| widens_to(&this, t));
+                            }
                             match matched.len() {
                                 0 => {
                                     return Err(ValidationError::InvalidFunction(format!(
@@ -297,16 +693,22 @@ impl<T: RigzBuilder> ProgramParser<'_, T> {
                                 1 => matched.iter().next().cloned().unwrap(),
                                 _ => {
                                     dbg!(f);
-                                    this
+                                    this.clone()
                                 }
                             }
                         } else {
                             f[0].rigz_type()
                         }
                     }
+                };
+                self.refine_lambda_result(&this, name, args, result)?
+            }
+            FunctionExpression::TypeConstructor(r, _) => {
+                match self.constructed_object(&r.to_string()) {
+                    None => r.clone(),
+                    Some(dec) => dec.rigz_type.as_ref().clone(),
                 }
             }
-            FunctionExpression::TypeConstructor(r, _) => r.clone(),
         };
         Ok(e)
     }
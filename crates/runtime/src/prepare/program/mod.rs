@@ -1,18 +1,32 @@
 pub(crate) mod expression;
 
 use crate::prepare::ProgramParser;
+use crate::timings::Phase;
 use crate::{Runtime, RuntimeError};
-use rigz_ast::{Element, ParserOptions};
+use rigz_ast::{Element, ParserOptions, StableHasher};
+use rigz_vm::VMBuilder;
+use std::hash::Hash;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Program {
     pub elements: Vec<Element>,
+    pub lines: Vec<usize>,
+}
+
+impl Program {
+    /// Same as `rigz_ast::Program::digest`, only the elements are hashed
+    pub fn digest(&self) -> String {
+        let mut hasher = StableHasher::new();
+        self.elements.hash(&mut hasher);
+        hasher.finalize_hex()
+    }
 }
 
 impl From<rigz_ast::Program> for Program {
     fn from(value: rigz_ast::Program) -> Self {
         Program {
             elements: value.elements,
+            lines: value.lines,
         }
     }
 }
@@ -20,9 +34,7 @@ impl From<rigz_ast::Program> for Program {
 impl<'lex> Program {
     #[inline]
     pub fn create_runtime(self) -> Result<Runtime<'lex>, RuntimeError> {
-        let mut builder = ProgramParser::new();
-        builder.parse_program(self).map_err(|e| e.into())?;
-        Ok(builder.create().into())
+        self.prepare(ProgramParser::new())
     }
 
     #[inline]
@@ -30,15 +42,21 @@ impl<'lex> Program {
         self,
         options: ParserOptions,
     ) -> Result<Runtime<'lex>, RuntimeError> {
-        let mut builder = ProgramParser::with_options(options);
-        builder.parse_program(self).map_err(|e| e.into())?;
-        Ok(builder.create().into())
+        self.prepare(ProgramParser::with_options(options))
     }
 
     #[inline]
     pub fn create_runtime_without_modules(self) -> Result<Runtime<'lex>, RuntimeError> {
-        let mut builder = ProgramParser::default();
+        self.prepare(ProgramParser::default())
+    }
+
+    fn prepare(
+        self,
+        mut builder: ProgramParser<'lex, VMBuilder>,
+    ) -> Result<Runtime<'lex>, RuntimeError> {
+        let timer = builder.timings.start();
         builder.parse_program(self).map_err(|e| e.into())?;
+        builder.timings.stop(timer, None, Phase::Prepare);
         Ok(builder.create().into())
     }
 }
@@ -1,14 +1,22 @@
-use crate::prepare::{Program, ProgramParser};
-use rigz_ast::{ParsedModule, Parser, ParserOptions, ParsingError, ValidationError};
+use crate::prepare::{ModuleDefinition, Program, ProgramParser};
+use crate::timings::{Phase, Timings};
+#[cfg(feature = "threaded")]
+use crate::ProcessModule;
+use log::warn;
+use rigz_ast::{
+    LintConfig, ModuleTraitDefinition, ParsedModule, Parser, ParserOptions, ParsingError,
+    ValidationError,
+};
 use rigz_core::{ObjectValue, TestResults, VMError};
-use rigz_vm::{VMOptions, VM};
+use rigz_vm::{ProcessInfo, VMOptions, VM};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::time::Duration;
 
 #[derive(Default, Debug, Clone)]
 pub struct RuntimeOptions {
-    vm: VMOptions,
+    /// Replaces the VM's options when the runtime is created or `with_options` is called
+    pub vm: VMOptions,
 }
 
 pub struct Runtime<'vm> {
@@ -69,6 +77,33 @@ impl Into<RuntimeError> for ValidationError {
     }
 }
 
+fn parse(
+    input: &str,
+    parser_options: ParserOptions,
+    timings: &mut Timings,
+) -> Result<rigz_ast::Program, RuntimeError> {
+    let timer = timings.start();
+    let parser = Parser::prepare(input, parser_options).map_err(|e| e.into())?;
+    timings.stop(timer, None, Phase::Lex);
+    let timer = timings.start();
+    let program = parser.parse().map_err(|e| e.into())?;
+    timings.stop(timer, None, Phase::Parse);
+    Ok(program)
+}
+
+fn validate(
+    program: &mut rigz_ast::Program,
+    lint: &LintConfig,
+    timings: &mut Timings,
+) -> Result<(), RuntimeError> {
+    let timer = timings.start();
+    for warning in program.validate_with(lint).map_err(|e| e.into())? {
+        warn!("{warning}")
+    }
+    timings.stop(timer, None, Phase::Validate);
+    Ok(())
+}
+
 impl Default for Runtime<'_> {
     /// Does not include default modules, use Runtime::new() instead
     fn default() -> Self {
@@ -101,14 +136,27 @@ impl Runtime<'_> {
         Ok(runtime)
     }
 
+    /// Precompiled program for `from_bytecode`, must be called before the runtime is run
+    pub fn bytecode(&self) -> Result<Vec<u8>, RuntimeError> {
+        self.vm().serialize().map_err(|e| e.into())
+    }
+
+    /// Skips parsing & prepare, default modules are registered before the bytecode is loaded
+    pub fn from_bytecode(bytes: Vec<u8>) -> Result<Runtime<'static>, RuntimeError> {
+        let mut runtime = Runtime::new();
+        runtime.vm_mut().deserialize(bytes).map_err(|e| e.into())?;
+        Ok(runtime)
+    }
+
     pub fn new() -> Self {
         Runtime {
-            parser: ProgramParser::new(),
+            parser: ProgramParser::new().create(),
             runtime_options: Default::default(),
         }
     }
 
     pub fn with_options(&mut self, options: RuntimeOptions) {
+        self.vm_mut().options = options.vm;
         self.runtime_options = options;
     }
 
@@ -117,11 +165,13 @@ impl Runtime<'_> {
     }
 
     pub fn create(input: String) -> Result<Self, RuntimeError> {
-        let parser = Parser::prepare(&input, ParserOptions::default()).map_err(|e| e.into())?;
-        let program = parser.parse().map_err(|e| e.into())?;
-        program.validate().map_err(|e| e.into())?;
+        let mut timings = Timings::default();
+        let mut program = parse(&input, ParserOptions::default(), &mut timings)?;
+        validate(&mut program, &LintConfig::default(), &mut timings)?;
         let program: Program = program.into();
-        program.create_runtime()
+        let mut runtime = program.create_runtime()?;
+        runtime.parser.timings.prepend(timings);
+        Ok(runtime)
     }
 
     pub fn create_with_options(
@@ -129,22 +179,25 @@ impl Runtime<'_> {
         runtime_options: RuntimeOptions,
         parser_options: ParserOptions,
     ) -> Result<Self, RuntimeError> {
-        let parser = Parser::prepare(&input, ParserOptions::default()).map_err(|e| e.into())?;
-        let program = parser.parse().map_err(|e| e.into())?;
-        program.validate().map_err(|e| e.into())?;
+        let mut timings = Timings::default();
+        let mut program = parse(&input, parser_options.clone(), &mut timings)?;
+        validate(&mut program, &parser_options.lint, &mut timings)?;
         let program: Program = program.into();
         let mut runtime = program.create_runtime_with_options(parser_options)?;
-        runtime.runtime_options = runtime_options;
+        runtime.with_options(runtime_options);
+        runtime.parser.timings.prepend(timings);
         Ok(runtime)
     }
 
     /// Use register_module to add modules
     pub fn create_without_modules(input: String) -> Result<Self, RuntimeError> {
-        let parser = Parser::prepare(&input, ParserOptions::default()).map_err(|e| e.into())?;
-        let program = parser.parse().map_err(|e| e.into())?;
-        program.validate().map_err(|e| e.into())?;
+        let mut timings = Timings::default();
+        let mut program = parse(&input, ParserOptions::default(), &mut timings)?;
+        validate(&mut program, &LintConfig::default(), &mut timings)?;
         let program: Program = program.into();
-        program.create_runtime_without_modules()
+        let mut runtime = program.create_runtime_without_modules()?;
+        runtime.parser.timings.prepend(timings);
+        Ok(runtime)
     }
 
     /// Meant for REPL, skips requirement that programs must end in expression
@@ -177,21 +230,63 @@ impl Runtime<'_> {
         self.parser.register_module(module)
     }
 
+    /// Registers the `Process` module so scripts can list & cancel processes, programs must be compiled afterward
+    /// (i.e. with `eval`) to import it
+    #[cfg(feature = "threaded")]
+    pub fn allow_process_control(&mut self) -> Result<(), ValidationError> {
+        let handle = self.vm().process_handle();
+        self.register_module(ProcessModule::new(handle))
+    }
+
+    /// Processes of the current run, use `vm().process_handle()` to list or cancel them while `run` is blocking
+    pub fn processes(&self) -> Vec<ProcessInfo> {
+        self.vm().processes()
+    }
+
+    pub fn cancel(&self, pid: usize) -> Result<(), RuntimeError> {
+        self.vm().cancel(pid).map_err(|e| e.into())
+    }
+
     pub fn run(&mut self) -> Result<ObjectValue, RuntimeError> {
-        self.parser.builder.eval().map_err(|e| e.into())
+        let timer = self.parser.timings.start();
+        let result = self.parser.builder.eval();
+        self.parser.timings.stop(timer, None, Phase::Execute);
+        result.map_err(|e| e.into())
     }
 
     pub fn run_within(&mut self, duration: Duration) -> Result<ObjectValue, RuntimeError> {
-        self.parser
-            .builder
-            .run_within(duration)
-            .map_err(|e| e.into())
+        let timer = self.parser.timings.start();
+        let result = self.parser.builder.run_within(duration);
+        self.parser.timings.stop(timer, None, Phase::Execute);
+        result.map_err(|e| e.into())
+    }
+
+    /// Time spent lexing, parsing, validating, preparing, & running the program and its imports
+    pub fn timings(&self) -> &Timings {
+        &self.parser.timings
     }
 
     pub fn test(&mut self) -> TestResults {
         self.parser.builder.test()
     }
 
+    /// Trait definitions of the registered modules, modules that were already imported are skipped
+    pub fn module_definitions(&self) -> Vec<&ModuleTraitDefinition> {
+        self.parser
+            .modules
+            .values()
+            .filter_map(|m| match m {
+                ModuleDefinition::Imported => None,
+                ModuleDefinition::Module(m) => Some(m),
+            })
+            .collect()
+    }
+
+    /// Resets the budget set by `VMOptions::max_instructions`
+    pub fn refill_fuel(&mut self) {
+        self.parser.builder.refill_fuel()
+    }
+
     pub fn eval(&mut self, input: String) -> Result<ObjectValue, RuntimeError> {
         self.parser.repl(input)?;
         self.run()
@@ -212,6 +307,12 @@ pub fn eval(input: String) -> Result<ObjectValue, RuntimeError> {
     runtime.run()
 }
 
+/// Same as `eval`, the program stops with `VMError::TimeoutError` once `duration` has passed
+pub fn eval_within(input: String, duration: Duration) -> Result<ObjectValue, RuntimeError> {
+    let mut runtime = Runtime::create(input)?;
+    runtime.run_within(duration)
+}
+
 pub fn test(input: String) -> Result<TestResults, RuntimeError> {
     let mut runtime = Runtime::create(input)?;
     Ok(runtime.test())
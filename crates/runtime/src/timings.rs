@@ -0,0 +1,211 @@
+use rigz_vm::Instant;
+use serde_json::json;
+use std::fmt::{Display, Formatter, Write};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    Lex,
+    Parse,
+    Validate,
+    Prepare,
+    Execute,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Lex,
+        Phase::Parse,
+        Phase::Validate,
+        Phase::Prepare,
+        Phase::Execute,
+    ];
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::Validate => "validate",
+            Phase::Prepare => "prepare",
+            Phase::Execute => "execute",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// none for the program passed to the runtime, imports use their path or url
+    pub file: Option<String>,
+    pub phase: Phase,
+    pub duration: Duration,
+}
+
+/// Started by `Timings::start`, anything recorded before the timer stops is excluded from its phase
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timer {
+    start: Instant,
+    recorded: Duration,
+}
+
+/// Time spent in each phase for every file, in the order the phases finished.
+/// Imports are lexed, parsed, & prepared while the file importing them is prepared, their time isn't counted twice.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub phases: Vec<Timing>,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|t| t.duration).sum()
+    }
+
+    pub(crate) fn start(&self) -> Timer {
+        Timer {
+            start: Instant::now(),
+            recorded: self.total(),
+        }
+    }
+
+    pub(crate) fn stop(&mut self, timer: Timer, file: Option<&str>, phase: Phase) {
+        let nested = self.total().saturating_sub(timer.recorded);
+        self.phases.push(Timing {
+            file: file.map(|f| f.to_string()),
+            phase,
+            duration: timer.start.elapsed().saturating_sub(nested),
+        })
+    }
+
+    /// Phases recorded before the runtime was created
+    pub(crate) fn prepend(&mut self, mut earlier: Timings) {
+        earlier.phases.append(&mut self.phases);
+        self.phases = earlier.phases;
+    }
+
+    /// Files in the order they were first recorded, with the time spent in each phase
+    fn by_file(&self) -> Vec<(Option<&str>, [Duration; 5])> {
+        let mut files: Vec<(Option<&str>, [Duration; 5])> = Vec::new();
+        for timing in &self.phases {
+            let file = timing.file.as_deref();
+            let index = match files.iter().position(|(f, _)| *f == file) {
+                Some(i) => i,
+                None => {
+                    files.push((file, Default::default()));
+                    files.len() - 1
+                }
+            };
+            files[index].1[timing.phase as usize] += timing.duration;
+        }
+        files
+    }
+
+    /// Table of phases per file, `main` is the name used for the program passed to the runtime
+    pub fn to_text(&self, main: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Total: {:.2?}", self.total());
+        let _ = writeln!(out);
+        for phase in Phase::ALL {
+            let _ = write!(out, "{:>12}", phase.to_string());
+        }
+        let _ = writeln!(out, "{:>12}  file", "total");
+        let mut totals = [Duration::ZERO; 5];
+        let files = self.by_file();
+        for (file, durations) in &files {
+            for (total, duration) in totals.iter_mut().zip(durations) {
+                *total += *duration;
+            }
+            write_row(&mut out, durations, file.unwrap_or(main));
+        }
+        if files.len() > 1 {
+            write_row(&mut out, &totals, "(all files)");
+        }
+        out
+    }
+
+    /// JSON object with the total & every recorded phase in nanoseconds
+    pub fn to_json(&self, main: &str) -> String {
+        let phases: Vec<_> = self
+            .phases
+            .iter()
+            .map(|t| {
+                json!({
+                    "file": t.file.as_deref().unwrap_or(main),
+                    "phase": t.phase.to_string(),
+                    "nanos": t.duration.as_nanos() as u64,
+                })
+            })
+            .collect();
+        json!({
+            "total_nanos": self.total().as_nanos() as u64,
+            "phases": phases,
+        })
+        .to_string()
+    }
+}
+
+fn write_row(out: &mut String, durations: &[Duration; 5], file: &str) {
+    for duration in durations {
+        if duration.is_zero() {
+            let _ = write!(out, "{:>12}", "-");
+        } else {
+            let _ = write!(out, "{:>12}", format!("{duration:.2?}"));
+        }
+    }
+    let total: Duration = durations.iter().sum();
+    let _ = writeln!(out, "{:>12}  {file}", format!("{total:.2?}"));
+}
+
+#[cfg(test)]
+pub mod timings_tests {
+    use crate::timings::{Phase, Timing, Timings};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use wasm_bindgen_test::*;
+
+    fn timing(file: Option<&str>, phase: Phase, millis: u64) -> Timing {
+        Timing {
+            file: file.map(|f| f.to_string()),
+            phase,
+            duration: Duration::from_millis(millis),
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn nested_phases_are_excluded() {
+        let mut timings = Timings::default();
+        let timer = timings.start();
+        // an import prepared while the main file is prepared
+        timings
+            .phases
+            .push(timing(Some("lib.rg"), Phase::Prepare, 1000));
+        timings.stop(timer, None, Phase::Prepare);
+        assert_eq!(timings.phases[1].duration, Duration::ZERO);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn grouped_by_file() {
+        let timings = Timings {
+            phases: vec![
+                timing(None, Phase::Lex, 1),
+                timing(Some("lib.rg"), Phase::Lex, 2),
+                timing(None, Phase::Prepare, 3),
+                timing(None, Phase::Execute, 4),
+            ],
+        };
+        let text = timings.to_text("main.rg");
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "Total: 10.00ms");
+        assert!(lines[3].ends_with("8.00ms  main.rg"), "{}", lines[3]);
+        assert!(lines[4].ends_with("2.00ms  lib.rg"), "{}", lines[4]);
+        assert!(lines[5].ends_with("10.00ms  (all files)"), "{}", lines[5]);
+
+        let json: Value = serde_json::from_str(&timings.to_json("main.rg")).unwrap();
+        assert_eq!(json["total_nanos"], 10_000_000);
+        assert_eq!(
+            json["phases"][1],
+            json!({"file": "lib.rg", "phase": "lex", "nanos": 2_000_000})
+        );
+    }
+}
@@ -89,12 +89,179 @@ pub mod runtime {
 
     pub mod invalid {
         use super::*;
+        use rigz_ast::ValidationError;
         use rigz_core::VMError;
 
         run_invalid! {
             // last statement must be an expression
             assign("a = 3 * 2")
             var_once_in_fn_def("fn foo(var foo, var bar) = none")
+            on_zero_capacity(r#"
+            @on("message", capacity: 0)
+            fn foo(a) = a
+            send 'message', 1
+            "#)
+            on_invalid_overflow(r#"
+            @on("message", overflow: "drop_all")
+            fn foo(a) = a
+            send 'message', 1
+            "#)
+            on_max_restarts_without_restart(r#"
+            @on("message", max_restarts: 2)
+            fn foo(a) = a
+            send 'message', 1
+            "#)
+            memo_invalid_option(r#"
+            @memo(path: "cache.db")
+            fn foo(a) = a
+            foo 1
+            "#)
+            memo_limit_zero(r#"
+            @memo(limit: 0)
+            fn foo(a) = a
+            foo 1
+            "#)
+            memo_ttl_zero(r#"
+            @memo(ttl: 0)
+            fn foo(a) = a
+            foo 1
+            "#)
+            fn_in_if(r#"
+            if true
+                fn foo = 1
+            end
+            foo
+            "#)
+            annotated_element_types_checked(r#"
+            m: {String, Int} = {}: {String, String}
+            m
+            "#)
+            typed_map_literal_key(r#"
+            m: {String, Int} = {a = 1, 2 = 3}
+            m
+            "#)
+            typed_map_literal_value(r#"
+            m: {String, Int} = {a = 1, b = 'c'}
+            m
+            "#)
+            typed_map_insert_key(r#"
+            mut m = {}: {String, Int}
+            m.insert 1, 2
+            m
+            "#)
+            typed_map_get_key(r#"
+            m = {}: {String, Int}
+            m.get 1
+            "#)
+            typed_map_index_key(r#"
+            m: {String, Int} = {a = 1}
+            m.1
+            "#)
+            typed_map_set_value(r#"
+            mut m = {}: {String, Int}
+            m.a = 'b'
+            m
+            "#)
+            time_instant_plus_instant(r#"
+            import Time
+            t = (Time.now) + (Time.now)
+            t
+            "#)
+            time_duration_plus_number(r#"
+            import Time
+            d = (Time.seconds 1) + 5
+            d
+            "#)
+            sleep_instant(r#"
+            import Time
+            sleep (Time.now)
+            "#)
+            regex_literal_invalid(r#"
+            r = /(a/
+            "#)
+            datetime_plus_number(r#"
+            import Date
+            d = Date.now + 5
+            d
+            "#)
+            sleep_datetime(r#"
+            import Date
+            sleep Date.now
+            "#)
+            module_override_argument_count(r#"
+            import JSON
+            with JSON.parse = |a, b| a do
+                JSON.parse '5'
+            end
+            "#)
+            module_override_not_lambda(r#"
+            import JSON
+            with JSON.parse = 5 do
+                JSON.parse '5'
+            end
+            "#)
+            propagate_outside_function(r#"
+            a = 1
+            a?
+            "#)
+            propagate_return_type_without_error(r#"
+            fn foo(a) -> Int = a?
+            foo 1
+            "#)
+            clauses_different_arity(r#"
+            fn foo(0) = 0
+            fn foo(a, b) = a + b
+            foo 0
+            "#)
+            clause_declaration_without_body(r#"
+            trait Foo
+                fn foo(0) -> Int
+            end
+            1
+            "#)
+            clause_invalid_list_pattern(r#"
+            fn foo([a + 1]) = a
+            foo [1]
+            "#)
+            select_without_arms(r#"
+            select
+                after 0 => 1
+            end
+            "#)
+            comptime_uses_program_variable(r#"
+            a = 2
+            comptime do
+                a * 2
+            end
+            "#)
+            comptime_io(r#"
+            import Env
+            comptime do
+                Env.get 'HOME'
+            end
+            "#)
+            comptime_error(r#"
+            comptime do
+                raise "not at compile time"
+            end
+            "#)
+            comptime_puts(r#"
+            comptime do
+                puts "printed at compile time"
+                1
+            end
+            "#)
+            comptime_file_module(r#"
+            comptime do
+                import File
+                File.write "comptime.txt", "1"
+            end
+            "#)
+            comptime_spawn(r#"
+            comptime do
+                spawn do = 1
+            end
+            "#)
         }
 
         run_error! {
@@ -110,9 +277,73 @@ pub mod runtime {
             try_fail(r#"
             try raise "Failure"
             "# = VMError::RuntimeError("Failure".to_string()))
+            list_windows_zero("[1, 2].windows 0" = VMError::RuntimeError("List.windows size must be greater than 0".to_string()))
+            string_step_zero("'abc'.step 0" = VMError::RuntimeError("String.step must not be 0".to_string()))
+            datetime_parse_invalid(r#"
+            import Date
+            Date.parse "05/01/2024"
+            "# = VMError::ConversionError("Cannot convert \"05/01/2024\" to DateTime - input contains invalid characters".to_string()))
+            regex_new_invalid(r#"
+            Regex.new "(a"
+            "# = VMError::ConversionError("Cannot convert \"(a\" to Regex - regex parse error:\n    (a\n    ^\nerror: unclosed group".to_string()))
+            string_matches_invalid(r#"
+            "a".matches "(a"
+            "# = VMError::ConversionError("Cannot convert \"(a\" to Regex - regex parse error:\n    (a\n    ^\nerror: unclosed group".to_string()))
+            uuid_parse_invalid(r#"
+            import UUID
+            UUID.parse "67e55044-10b1"
+            "# = VMError::ConversionError("Cannot convert \"67e55044-10b1\" to Uuid - failed to parse a UUID".to_string()))
+            datetime_format_invalid(r#"
+            d = DateTime.new 0
+            d.format "%Q"
+            "# = VMError::RuntimeError("Invalid DateTime format \"%Q\"".to_string()))
+            invalid_range("'ab'..'c'" = VMError::UnsupportedOperation("Cannot create range from ab to c, expected numbers, characters, or dates (YYYY-MM-DD)".to_string()))
+            clause_no_match(r#"
+            fn one(1) = "one"
+            one 2
+            "# = VMError::RuntimeError("No clause of one matches the arguments".to_string()))
+            clause_destructure_no_match(r#"
+            fn add([a, b]) = a + b
+            add [1]
+            "# = VMError::RuntimeError("No clause of add matches the arguments".to_string()))
+            map_args_unknown_key(r#"
+            fn sub{a, b}
+                a - b
+            end
+            sub {a = 5, b = 2, c = 1}
+            "# = ValidationError::InvalidFunction("Unknown argument c for sub".to_string()))
+            map_args_missing_key(r#"
+            fn sub{a, b}
+                a - b
+            end
+            sub {a = 5}
+            "# = ValidationError::MissingExpression("Missing argument b for sub".to_string()))
+            map_args_duplicate_key(r#"
+            fn sub{a, b}
+                a - b
+            end
+            sub 5, a: 1, b: 2
+            "# = ValidationError::InvalidFunction("Argument a passed more than once to sub".to_string()))
+            map_args_runtime_map(r#"
+            fn sub{a, b}
+                a - b
+            end
+            v = {a = 5, b = 2}
+            sub v
+            "# = ValidationError::InvalidFunction("sub takes map arguments, its arguments can't be checked for a map created at runtime, use `sub {a = 1}` or `sub a: 1`".to_string()))
+            list_args_too_many(r#"
+            fn sub[a, b]
+                a - b
+            end
+            sub [5, 2, 1]
+            "# = ValidationError::InvalidFunction("Too many values for sub, expected at most 2 got 3".to_string()))
         }
 
         run_error_starts_with! {
+            time_instant_overflow(r#"
+            import Time
+            (Time.now) + (Time.nanos 9223372036854775807)
+            "# = "Time overflow")
             on_timeout_works(r#"
             @on("message")
             fn foo(a)
@@ -123,21 +354,123 @@ pub mod runtime {
             pids = send 'message', 21
             receive pids.0, 0
             "# = "`receive` timed out after 0ms")
+            on_restart_gives_up(r#"
+            import Channel
+            @on("job", restart: "on_failure", max_restarts: 1, backoff: 0)
+            fn job(attempts) = attempts.receive 0
+
+            fn failure = raise "failed attempt"
+            attempts = Channel.new
+            attempts.send failure
+            attempts.send failure
+            attempts.send "done"
+            pids = send 'job', attempts
+            receive pids.0
+            "# = "failed attempt")
+            channel_receive_timeout(r#"
+            import Channel
+            ch = Channel.new
+            ch.receive 0
+            "# = "`receive` timed out after 0ms")
+            channel_closed(r#"
+            import Channel
+            ch = Channel.new
+            ch.send 1
+            ch.close
+            ch.receive
+            ch.receive
+            "# = "`receive` failed, channel is closed")
+        }
+
+        // overflow is promoted to a BigInt instead, see `mod bigint`
+        #[cfg(not(feature = "bigint"))]
+        run_error! {
+            int_overflow(r#"
+            a = 9223372036854775807
+            a + 1
+            "# = VMError::RuntimeError("Integer overflow: 9223372036854775807 + 1".to_string()))
+            int_shl_overflow(r#"
+            a = 9223372036854775807
+            a << 1
+            "# = VMError::RuntimeError("Integer overflow: 9223372036854775807 << 1".to_string()))
+            int_neg_overflow(r#"
+            a = 9223372036854775807
+            -(a - 9223372036854775807 - 9223372036854775807 - 1)
+            "# = VMError::RuntimeError("Integer overflow: -(-9223372036854775808)".to_string()))
+        }
+
+        run_error! {
+            frozen_list_push(r#"
+            mut a = [1, 2].freeze
+            a.push 3
+            "# = VMError::UnsupportedOperation("Cannot mutate frozen value [1,2]".to_string()))
+            frozen_map_set(r#"
+            mut m = {a = 1}.freeze
+            m.a = 2
+            m
+            "# = VMError::UnsupportedOperation("Cannot mutate frozen value {a = 1}".to_string()))
         }
     }
 
     pub mod valid {
         use super::*;
-        use rigz_core::{IndexMap, ObjectValue, VMError};
+        use rigz_core::{IndexMap, ObjectValue};
 
         run_expected! {
             raw_value("'Hello World'" = "Hello World")
             addition("2 + 2" = 4)
             list_index("[1, 2, 3][2]" = 3)
             list_index_getter("[1, 2, 3].2" = 3)
+            string_slice("s = 'hello world'; s[0..5]" = "hello")
+            string_slice_negative("s = 'hello world'; s[-5..]" = "world")
+            string_slice_inclusive("s = 'abcdef'; s[1..=3]" = "bcd")
+            string_index_negative("s = 'hello'; s[-1]" = "o")
+            string_slice_graphemes("s = 'cafe\u{301}s'; s[-2..]" = "e\u{301}s")
+            string_step("'abcdef'.step 2" = "ace")
+            decimal_is_exact("0.1d + 0.2d == 0.3d" = true)
+            decimal_keeps_scale("a = 19.99d * 3; a.to_s" = "59.97")
+            decimal_int_arithmetic("a = 10 - 0.01d; a.to_s" = "9.99")
+            decimal_float_arithmetic("0.5 + 0.25d" = 0.75)
+            decimal_to_d("a = '1.50'.to_d; a.to_s" = "1.50")
+            decimal_compare("1.50d == 1.5 && 2.01d > 2" = true)
+            decimal_round("2.5d.round" = 3)
+            decimal_format("1.555d.format digits: 2" = "1.56")
+            list_spread("a = [2, 3]; [1, ..a, 4, ..a]" = vec![1, 2, 3, 4, 2, 3])
+            list_spread_range("[..1..4]" = vec![1, 2, 3])
+            map_spread(r#"
+            defaults = {color = "red", size = 2}
+            {..defaults, size = 3}
+            "# = IndexMap::from([("color", ObjectValue::from("red")), ("size", 3.into())]))
+            map_spread_overrides(r#"
+            overrides = {size = 3}
+            {size = 2, ..overrides}
+            "# = IndexMap::from([("size", 3)]))
+            set_spread(r#"
+            import Set
+            s = Set.from_list [3, 4]
+            u = Set.from_list [1, ..s, 4]
+            u.len
+            "# = 3)
+            string_step_reverse("'héllo'.step -1" = "olléh")
+            string_lengths(r#"
+            s = "héllo 🇯🇵"
+            [s.len, s.char_len, s.byte_len]
+            "# = vec![7, 8, 15])
+            string_iteration_modes(r#"
+            s = "é🇯🇵"
+            [s.graphemes, s.chars, s.bytes]
+            "# = vec![
+                ObjectValue::List(vec!["é".into(), "🇯🇵".into()]),
+                ObjectValue::List(vec!["é".into(), "🇯".into(), "🇵".into()]),
+                ObjectValue::List(vec![195, 169, 240, 159, 135, 175, 240, 159, 135, 181].into_iter().map(ObjectValue::from).collect()),
+            ])
+            string_for_graphemes(r#"
+            s = "hé🇯🇵"
+            [for c in s: c]
+            "# = vec!["h", "é", "🇯🇵"])
             map_sum("{1, 2, 3}.sum" = 6)
             split_first("[1, 2, 3].split_first" = ObjectValue::Tuple(vec![1.into(), vec![2, 3].into()]))
-            split_first_map("{1, 2, 3}.split_first" = ObjectValue::Tuple(vec![ObjectValue::Tuple(vec![1.into(), 1.into()].into()), ObjectValue::Map(IndexMap::from([(2.into(), 2.into()), (3.into(), 3.into())]))]))
+            split_first_map("{1, 2, 3}.split_first" = ObjectValue::Tuple(vec![ObjectValue::Tuple(vec![1.into(), 1.into()]), ObjectValue::Map(IndexMap::from([(2.into(), 2.into()), (3.into(), 3.into())]))]))
             split_first_assign("(first, rest) = [1, 2, 3].split_first; first + rest" = vec![1, 2, 3])
             complex_expression_ignore_precedence("1 + 2 * 3 - 4 / 5" = 1)
             ignore_precedence("2 + 1 * 3" = 9)
@@ -162,6 +495,34 @@ pub mod runtime {
             end
             foo bar: 3
             "# = 72)
+            map_args_literal(r#"
+            fn sub{a, b}
+                a - b
+            end
+            sub {b = 2, a = 5}
+            "# = 3)
+            map_args_named_out_of_order(r#"
+            fn sub{a, b}
+                a - b
+            end
+            sub b: 2, a: 5
+            "# = 3)
+            map_args_default(r#"
+            fn sub{a, b = 2}
+                a - b
+            end
+            sub {a = 5}
+            "# = 3)
+            list_args_literal(r#"
+            fn sub[a, b]
+                a - b
+            end
+            sub [5, 2]
+            "# = 3)
+            list_args_rest(r#"
+            fn rest[a, ..b] = b
+            rest [1, 2, 3]
+            "# = vec![2, 3])
             call_function_multiple_times(r#"
             fn foo(number: Number) -> Number
                 number * 2
@@ -245,7 +606,93 @@ pub mod runtime {
                 14
             end"# = PrimitiveValue::None)
             to_json("import JSON; {a=5}.to_json" = r#"{"a":5}"#)
+            list_sort_mixed(r#"mut a = [3, "a", none, 1.5, false]; a.sort; a"# = vec![ObjectValue::default(), false.into(), 1.5.into(), 3.into(), "a".into()])
+            any_cmp(r#"[(1.cmp 2), ("b".cmp "a"), (2.cmp 2.0), ("a".cmp 1), (none.cmp [])]"# = vec![-1, 1, 0, 1, -1])
             json_parse("import JSON; JSON.parse '5'" = 5)
+            json_parse_list(r#"import JSON; JSON.parse '[1, 2, "3"]'"# = vec![ObjectValue::from(1), 2.into(), "3".into()])
+            json_generate(r#"import JSON; JSON.generate {a = [1, 2.5, none], 1 = true}"# = r#"{"a":[1,2.5,null],"1":true}"#)
+            json_generate_pretty(r#"import JSON; JSON.generate [1], pretty: true"# = "[\n  1\n]")
+            yaml_parse(r#"import YAML; YAML.parse "{name: ci, steps: [build, test], cache: ~}""# = {
+                let mut m = IndexMap::new();
+                m.insert("name".into(), "ci".into());
+                m.insert("steps".into(), ObjectValue::List(vec!["build".into(), "test".into()]));
+                m.insert("cache".into(), ObjectValue::default());
+                ObjectValue::Map(m)
+            })
+            toml_parse(r#"import TOML; TOML.parse 'package = { name = "rigz", version = 1, released = 1979-05-27 }'"# = {
+                let mut package = IndexMap::new();
+                package.insert("name".into(), "rigz".into());
+                package.insert("version".into(), 1.into());
+                package.insert("released".into(), "1979-05-27".into());
+                let mut m = IndexMap::new();
+                m.insert("package".into(), ObjectValue::Map(package));
+                ObjectValue::Map(m)
+            })
+            yaml_toml_generate(r#"
+            import YAML
+            import TOML
+            config = {title = "x", owner = {name = "a", tags = [1, 2.5]}}
+            y = YAML.generate config
+            t = TOML.generate config
+            [t, (YAML.parse y) == config, (TOML.parse t) == config]
+            "# = vec![
+                ObjectValue::from("title = \"x\"\n\n[owner]\nname = \"a\"\ntags = [1, 2.5]\n"),
+                true.into(),
+                true.into(),
+            ])
+            csv_parse(r#"import CSV; CSV.parse 'a;b', headers: false, delimiter: ';'"# = vec![vec!["a", "b"]])
+            csv_parse_headers(r#"import CSV; CSV.parse 'name,age'"# = Vec::<ObjectValue>::new())
+            csv_generate(r#"
+            import CSV
+            [(CSV.generate [{name = "ada", age = 36}, {name = "bob", age = none}]), (CSV.generate [[1, 2], [3, 4]], delimiter: '\t')]
+            "# = vec!["name,age\nada,36\nbob,\n", "1\t2\n3\t4\n"])
+            json_object_fields(r#"
+            import JSON
+            object Point
+                attr x, Int
+                attr y, Int
+            end
+            p = Point.new 1, 2
+            s = JSON.generate {point = p}
+            m = JSON.parse s
+            point = m["point"]
+            q = Point.new point.x, point.y
+            [s, (q == p)]
+            "# = vec![ObjectValue::from(r#"{"point":{"x":1,"y":2}}"#), true.into()])
+            module_override(r#"
+            import JSON
+            with JSON.parse = |s| 42 do
+                JSON.parse '5'
+            end
+            "# = 42)
+            module_override_restored(r#"
+            import JSON
+            a = with JSON.parse = |s| 42 do
+                JSON.parse '5'
+            end
+            a + (JSON.parse '5')
+            "# = 47)
+            module_override_nested_call(r#"
+            import JSON
+            fn read(input)
+                JSON.parse input
+            end
+            with JSON.parse = |s| s + '!' do
+                read 'a'
+            end
+            "# = "a!")
+            module_override_calls_original(r#"
+            import JSON
+            with JSON.parse = { |s| (JSON.parse s) + 1 } do
+                JSON.parse '5'
+            end
+            "# = 6)
+            module_override_extension(r#"
+            import JSON
+            with JSON.to_json = |v| 'fake' do
+                1.to_json
+            end
+            "# = "fake")
             is("1.is Number" = true)
             fn_calls_fn(r#"
             fn Any.apply(func: |Any| -> Any)
@@ -298,15 +745,62 @@ pub mod runtime {
             func = |v| v.is_num
             [for a in ['a', 'b', 'c', 1, 2, 3]: a if func a]
             "# = vec![1, 2, 3])
+            for_list_nested(r#"[for a in [1, 2]: [for b in [3, 4]: a * b]]"# = vec![vec![3, 4], vec![6, 8]])
+            for_list_shadowed_variable(r#"
+            v = 10
+            [for v in [1, 2]: v]
+            "# = vec![1, 2])
+            for_list_in_function(r#"
+            fn squares(items)
+                [for v in items: v * v]
+            end
+            squares [1, 2, 3]
+            "# = vec![1, 4, 9])
+            for_map_values(r#"{for k, v in {1, 2, 3}: v, k * 2}"# = IndexMap::from([(1, 2), (2, 4), (3, 6)]))
+            for_int_range(r#"[for i in 1..4: i * i]"# = vec![1, 4, 9])
+            for_int_range_variables(r#"
+            a = 1
+            b = 3
+            [for i in a..=b: i * 2]
+            "# = vec![2, 4, 6])
+            for_char_range(r#"[for c in 'a'..='e': c]"# = vec!["a", "b", "c", "d", "e"])
+            for_char_range_exclusive(r#"[for c in 'x'..'z': c]"# = vec!["x", "y"])
+            for_date_range(r#"[for d in '2024-02-27'..='2024-03-01': d]"# = vec!["2024-02-27", "2024-02-28", "2024-02-29", "2024-03-01"])
+            for_date_range_exclusive(r#"[for d in '2023-12-31'..'2024-01-02': d]"# = vec!["2023-12-31", "2024-01-01"])
+            list_reduce(r#"[1, 2, 3].reduce(0, |a, b| a + b)"# = 6)
+            list_reduce_empty(r#"[].reduce(5, |a, b| a + b)"# = 5)
+            list_sum_inline(r#"[1, 2, 3, 4].sum"# = 10)
             trailing_if_false(r#"v = 'a'; v if v.is_num"# = PrimitiveValue::None)
             instance_trailing_if(r#"a = 'a'; a.to_i if a.is_num"# = PrimitiveValue::None)
             filter(r#"[1, 2, 3, 'a', 'b'].filter(|v| v.is_num)"# = vec![1, 2, 3])
             map_filter(r#"{1, 2, 3, 'a', 'b'}.filter(|k, v| v.is_num)"# = IndexMap::from([(1, 1), (2, 2), (3, 3)]))
             map_filter_map(r#"{1, 2, 3, 'a', 'b'}.filter { |k, v| v.is_num }.map(|k, v| (k, v * v))"# = IndexMap::from([(1, 1), (2, 4), (3, 9)]))
+            any_tap(r#"5.tap { |v| v * 100 }"# = 5)
+            any_then(r#"5.then { |v| v * 100 }"# = 500)
+            any_tap_then(r#"'hi'.tap { |v| v + '?' }.then { |v| v + '!' }"# = "hi!")
             map_map_if(r#"{1, 2, 3, 'a', 'b'}.map(|k, v| (k, k * v) if k.is_num && v.is_num)"# = IndexMap::from([(1, 1), (2, 4), (3, 9)]))
             map_map(r#"{1, 2, 3}.map(|k, v| (k, k * v))"# = IndexMap::from([(1, 1), (2, 4), (3, 9)]))
             list_map_filter(r#"[1, 2, 3, 'a', 'b'].filter { |v| v.is_num }.map(|v| v * v)"# = vec![1, 4, 9])
             list_map(r#"[1, 2, 3].map(|a| a * a)"# = vec![1, 4, 9])
+            list_windows(r#"[1, 2, 3, 4].windows 3"# = vec![vec![1, 2, 3], vec![2, 3, 4]])
+            list_pairs(r#"[1, 2, 3].pairs"# = vec![ObjectValue::Tuple(vec![1.into(), 2.into()]), ObjectValue::Tuple(vec![2.into(), 3.into()])])
+            list_flat_map(r#"[1, 2, 3].flat_map(|v| [v, v * 10])"# = vec![1, 10, 2, 20, 3, 30])
+            list_take_while(r#"[1, 2, 3, 1].take_while(|v| v < 3)"# = vec![1, 2])
+            list_drop_while(r#"[1, 2, 3, 1].drop_while(|v| v < 3)"# = vec![3, 1])
+            list_par_map(r#"[1, 2, 3, 4, 5].par_map(|v| v * v)"# = vec![1, 4, 9, 16, 25])
+            list_par_filter(r#"[1, 2, 3, 4, 5].par_filter(|v| v % 2 == 1)"# = vec![1, 3, 5])
+            list_par_each(r#"[1, 2, 3].par_each(|v| v + 1)"# = PrimitiveValue::None)
+            list_par_map_function(r#"
+                fn square(v) = v * v
+                [1, 2, 3].par_map square
+            "# = vec![1, 4, 9])
+            list_map_infers_lambda_args(r#"
+                fn Int.double -> Int = self * 2
+                fn String.double -> String = self + self
+                fn numbers -> [Int] = [1, 2, 3]
+                nums = numbers
+                (nums.filter { |x| x > 1 }).map { |x| x.double }
+            "# = vec![4, 6])
             self_fib_recursive(r#"
             fn Number.fib -> Number
                 if self <= 1
@@ -327,6 +821,12 @@ pub mod runtime {
             a.extend a.clone
             a
             "# = vec![1, 2, 3, 1, 2, 3])
+            map_insert_self(r#"
+            mut m = {}
+            m.insert 'self', m
+            m.insert 'b', 2
+            m
+            "# = ObjectValue::Map(IndexMap::from([("self".into(), ObjectValue::Map(IndexMap::new())), ("b".into(), 2.into())])))
             list_multi_assign(r#"
             mut a = [1, 2, 3]
             a = a + a
@@ -416,6 +916,62 @@ pub mod runtime {
             import Random
             next_bool 1
             "# = true)
+            env_set_get(r#"
+            import Env
+            Env.set 'RIGZ_ENV_SET_GET', 'a'
+            value = Env.fetch 'RIGZ_ENV_SET_GET', 'c'
+            fetched = Env.fetch 'RIGZ_ENV_MISSING', 'b'
+            [value, fetched]
+            "# = vec!["a", "b"])
+            secret_is_redacted(r#"
+            import Secret
+            s = Secret.wrap "hunter2"
+            s.to_s
+            "# = "Secret { value: [REDACTED] }")
+            secret_reveal(r#"
+            import Secret
+            s = Secret.wrap "hunter2"
+            s.reveal
+            "# = "hunter2")
+            constant_time_eq_secret(r#"
+            import Crypto
+            import Secret
+            token = Secret.wrap "abc"
+            [(Crypto.constant_time_eq token, "abc"), (Crypto.constant_time_eq token, "abd"), (Crypto.constant_time_eq "ab", "abc")]
+            "# = vec![true, false, false])
+            float_format_digits(r#"
+            (0.1 + 0.2).format 15
+            "# = "0.3")
+            float_format_scientific(r#"
+            a = 2.0 / 3
+            [(a.format 3), ((a / 10000000).format 2), (42.format 1)]
+            "# = vec!["0.667", "6.7e-8", "42"])
+            time_duration_arithmetic(r#"
+            import Time
+            d = (Time.seconds 1) + (Time.millis 500)
+            d.as_millis
+            "# = 1500.0)
+            time_instant_difference(r#"
+            import Time
+            start = Time.now
+            later = start + (Time.seconds 2)
+            (later - start).as_secs
+            "# = 2.0)
+            time_duration_ratio(r#"
+            import Time
+            (Time.minutes 1) / ((Time.seconds 5) * 3)
+            "# = 4.0)
+            time_now_is_datetime(r#"
+            import Time
+            start = Time.now
+            later = (start.in_zone "Asia/Tokyo") + (Time.minutes 90)
+            [(later - start).as_secs, later.zone, (later.elapsed.as_secs < 0)]
+            "# = vec![ObjectValue::from(5400.0), "Asia/Tokyo".into(), true.into()])
+            sleep_duration(r#"
+            import Time
+            sleep (Time.millis 1)
+            42
+            "# = 42)
             mut_self_clone(r#"
             mut a = 2
             a += a.clone
@@ -495,6 +1051,386 @@ pub mod runtime {
             pids = send 'message', 21, 12
             receive pids
             "# = vec![252, 9])
+            on_restart_on_failure(r#"
+            import Channel
+            @on("job", restart: "on_failure", max_restarts: 2, backoff: 0)
+            fn job(attempts) = attempts.receive 0
+
+            fn failure = raise "failed attempt"
+            attempts = Channel.new
+            attempts.send failure
+            attempts.send failure
+            attempts.send "done"
+            pids = send 'job', attempts
+            [(receive pids.0), attempts.len]
+            "# = vec![ObjectValue::from("done"), 0.into()])
+            on_queues_events(r#"
+            @on("message", capacity: 4, overflow: "drop_oldest")
+            fn foo(a) = a * 2
+
+            send 'message', 1
+            pids = send 'message', 2
+            receive [pids.0, pids.0]
+            "# = vec![2, 4])
+            function_clauses(r#"
+            fn fib(0) = 0
+            fn fib(1) = 1
+            fn fib(n) = (fib n - 1) + (fib n - 2)
+            fib 10
+            "# = 55)
+            function_clauses_multiple_arguments(r#"
+            fn describe(0, _positive) = "zero"
+            fn describe(n, true) = format "positive {}", n
+            fn describe(n, false)
+                format "negative {}", n
+            end
+            [(describe 0, true), (describe 1, true), (describe -1, false)]
+            "# = vec!["zero", "positive 1", "negative -1"])
+            function_clauses_match_kind(r#"
+            fn one(1) = "one"
+            fn one(_value) = "other"
+            [(one 1), (one "1"), (one true), (one 1.0)]
+            "# = vec!["one", "other", "other", "one"])
+            function_clauses_destructure(r#"
+            fn add([a, b]) = a + b
+            add [1, 2]
+            "# = 3)
+            function_clauses_nested_patterns(r#"
+            fn first([0, [y, z]]) = y * z
+            fn first([]) = none
+            fn first([x, _]) = x
+            fn first(_other) = "other"
+            [(first [0, [3, 4]]), (first [0, 3]), (first [5, 6]), (first []), (first [1, 2, 3]), (first "ab")]
+            "# = vec![ObjectValue::from(12), 0.into(), 5.into(), ObjectValue::default(), "other".into(), "other".into()])
+            function_clauses_typed(r#"
+            fn label("a") = "first"
+            fn label(s: String) -> String = s + "!"
+            [(label "a"), (label "abc")]
+            "# = vec!["first", "abc!"])
+            channel_between_processes(r#"
+            import Channel
+            @on("produce")
+            fn produce(ch)
+                ch.send 1
+                ch.send 2
+                ch.close
+            end
+
+            ch = Channel.new
+            send 'produce', ch
+            [ch.receive, ch.receive]
+            "# = vec![1, 2])
+            channel_bounded(r#"
+            import Channel
+            ch = Channel.new 2
+            ch.send 1
+            ch.send 2
+            [ch.capacity, ch.len, ch.try_receive, ch.receive, ch.try_receive || 0]
+            "# = vec![2, 2, 1, 2, 0])
+            mutex_lock(r#"
+            import Sync
+            m = Mutex.new 1
+            m.lock { |v| v + 1 }
+            [m.get, m.is_locked]
+            "# = vec![ObjectValue::from(2), false.into()])
+            mutex_gc_keeps_reachable(r#"
+            import Sync
+            import Channel
+            m = Mutex.new [1]
+            ch = Channel.new
+            ch.send m
+            Sync.gc
+            [m.get, ch.receive == m]
+            "# = vec![ObjectValue::from(vec![1]), true.into()])
+            mutex_lock_error(r#"
+            import Sync
+            m = Mutex.new 1
+            (m.lock { |v| raise "failed" }) catch
+                0
+            end
+            [m.get, m.is_locked]
+            "# = vec![ObjectValue::from(1), false.into()])
+            mutex_lock_function_error(r#"
+            import Sync
+            fn update(v) -> Int!
+                raise "failed {v}" if v > 0
+                v + 1
+            end
+            m = Mutex.new 1
+            (m.lock { |v| update v }) catch
+                0
+            end
+            m.lock { |v| v + 1 }
+            [m.get, m.is_locked]
+            "# = vec![ObjectValue::from(2), false.into()])
+            atomic_between_processes(r#"
+            import Sync
+            @on("count")
+            fn count(counter) = counter.increment
+
+            counter = Atomic.new
+            pids = send 'count', counter
+            receive pids
+            counter.increment
+            "# = 2)
+            task_wait(r#"
+            import Task
+            t = Task.new 42
+            [t.is_done, t.wait]
+            "# = vec![ObjectValue::from(true), 42.into()])
+            task_wait_all(r#"
+            import Task
+            Task.wait_all [(Task.done 1), (Task.done 2)]
+            "# = vec![1, 2])
+            weak_ref_get(r#"
+            import WeakRef
+            a = [1, 2]
+            w = WeakRef.new a
+            [w.get, w.is_alive]
+            "# = vec![ObjectValue::List(vec![1.into(), 2.into()]), true.into()])
+            weak_ref_dropped(r#"
+            import WeakRef
+            w = do
+                b = {x = 1}
+                WeakRef.new b
+            end
+            [w.get, w.is_alive]
+            "# = vec![ObjectValue::default(), false.into()])
+            frozen_reads(r#"
+            a = [1, [2]].freeze
+            nested = a[1]
+            b = a + [3]
+            [a.first, nested.is_frozen, b, a == [1, [2]], a.is_frozen]
+            "# = vec![
+                ObjectValue::from(1),
+                true.into(),
+                ObjectValue::List(vec![1.into(), ObjectValue::List(vec![2.into()]), 3.into()]),
+                true.into(),
+                true.into(),
+            ])
+            frozen_sent_to_process(r#"
+            @on("config")
+            fn read(config) = [config.limits.max, config.is_frozen]
+
+            config = {limits = {max = 10}}.freeze
+            pids = send 'config', config
+            receive pids.0
+            "# = vec![ObjectValue::from(10), true.into()])
+            annotated_empty_map(r#"
+            mut m = {}: {String, Int}
+            m.insert 'a', 1
+            m.a + 1
+            "# = 2)
+            typed_map_literal(r#"
+            mut m: {String, Int} = {a = 1, 'b' = 2}
+            m.c = 3
+            m.b + m.c
+            "# = 5)
+            typed_empty_list(r#"
+            mut a: [Int] = []
+            a.push 2
+            a
+            "# = vec![2])
+            set_insertion_order(r#"
+            import Set
+            mut s = Set.from_list [3, 1, 2, 1]
+            s.remove 1
+            s.insert 0
+            s.insert 3
+            s.to_list
+            "# = vec![3, 2, 0])
+            set_sorted(r#"
+            import Set
+            s = Set.from_list [3, 1, 2]
+            s.sorted
+            "# = vec![1, 2, 3])
+            set_to_s(r#"
+            import Set
+            (Set.new [1, 2, 1]).to_s
+            "# = "Set[1, 2]")
+            set_mixed_numbers(r#"
+            import Set
+            (Set.new [1, 1.0, 2, 0, -0.0, 2.0d]).to_s
+            "# = "Set[1, 2, 0]")
+            duration_humanize(r#"
+            import Time
+            ((Time.minutes 123) + (Time.seconds 1)).humanize
+            "# = "2h 3m")
+            duration_parse(r#"
+            import Time
+            (Time.parse "1s 250ms").as_millis
+            "# = 1250.0)
+            time_strptime_strftime(r#"
+            import Time
+            d = Time.strptime "05/01/2024 9:30", "%m/%d/%Y %H:%M"
+            [d.to_s, (Time.strftime d, "%Y-%m-%d")]
+            "# = vec!["2024-05-01T09:30:00Z", "2024-05-01"])
+            datetime_in_zone(r#"
+            import Time
+            d = Time.strptime "2024-05-01 12:30", "%Y-%m-%d %H:%M"
+            ny = d.in_zone "America/New_York"
+            [ny.to_s, ny.hour, ny.zone, (ny.format "%H:%M %Z"), ny == d, (ny + 1.days).day]
+            "# = vec![ObjectValue::from("2024-05-01T08:30:00-04:00"), 8.into(), "America/New_York".into(), "08:30 EDT".into(), true.into(), 2.into()])
+            number_durations(r#"
+            (2.hours + 30.minutes - 90.seconds).humanize
+            "# = "2h 28m")
+            duration_scaled(r#"
+            (1.5.seconds * 2).as_millis
+            "# = 3000.0)
+            datetime_plus_duration(r#"
+            import Date
+            later = Date.now + 5.minutes
+            now = Date.now
+            later > now
+            "# = true)
+            datetime_format(r#"
+            import Date
+            d = Date.parse "2024-05-01 12:30", "%Y-%m-%d %H:%M"
+            next = d + 1.days
+            next.format "%d/%m/%Y %H:%M"
+            "# = "02/05/2024 12:30")
+            datetime_difference(r#"
+            import Date
+            a = Date.parse "2024-05-01T00:00:00Z"
+            b = Date.parse "2024-05-02T06:00:00+02:00"
+            (b - a).humanize
+            "# = "1d 4h")
+            datetime_fields(r#"
+            d = DateTime.new "2024-02-29T23:59:58Z"
+            [d.year, d.month, d.day, d.hour, d.second]
+            "# = vec![2024, 2, 29, 23, 58])
+            uuid_parse_normalizes(r#"
+            import UUID
+            a = UUID.parse "67E55044-10B1-426F-9247-BB680E5FE0C8"
+            b = Uuid.new "urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8"
+            [a == b, a.to_s]
+            "# = vec![ObjectValue::from(true), "67e55044-10b1-426f-9247-bb680e5fe0c8".into()])
+            uuid_map_key(r#"
+            import UUID
+            a = UUID.parse "67e55044-10b1-426f-9247-bb680e5fe0c8"
+            b = UUID.parse "{67E55044-10B1-426F-9247-BB680E5FE0C8}"
+            mut m = {}
+            m.insert a, 1
+            m.insert b, 2
+            m.values
+            "# = vec![2])
+            uuid_set(r#"
+            import UUID
+            import Set
+            a = UUID.parse "67e55044-10b1-426f-9247-bb680e5fe0c8"
+            s = Set.from_list [a, (UUID.parse "67e55044-10b1-426f-9247-bb680e5fe0c8"), UUID.v4]
+            s.len
+            "# = 2)
+            regex_literal(r#"
+            r = /\d+/
+            [(r.is_match "abc123"), r.source]
+            "# = vec![ObjectValue::from(true), r"\d+".into()])
+            regex_literal_argument(r#"
+            fn digits(r: Regex) = r.is_match "42"
+            digits(/^\d+$/)
+            "# = true)
+            regex_new(r#"
+            a = Regex.new "a/b"
+            [a == /a\/b/, a.to_s]
+            "# = vec![ObjectValue::from(true), r"/a\/b/".into()])
+            regex_division(r#"
+            a = 12
+            b = 3
+            [a / b / 2, 12/3/2]
+            "# = vec![2, 2])
+            regex_division_without_spaces(r#"
+            a = 10; b = 2; c = 4
+            a /b + c / 2
+            "# = 4)
+            regex_division_after_call(r#"
+            a = 10; b = 2; c = 4
+            puts a /b + c / 2
+            "# = ())
+            string_matches(r#"
+            [("abc12".matches(/\d+/)), ("abc".matches '\d')]
+            "# = vec![true, false])
+            string_replace_all(r#"
+            "2024-05-01".replace_all(/(\d+)-(\d+)-(\d+)/, '$3/$2/$1')
+            "# = "01/05/2024")
+            string_captures(r#"
+            [("key=value".captures(/(\w+)=(\w+)/)), ("nope".captures(/(\d)/))]
+            "# = vec![ObjectValue::from(vec!["key=value", "key", "value"]), ObjectValue::default()])
+            regex_module(r#"
+            import Regex
+            [(Regex.escape "a.b"), (Regex.find_all(/\d+/, "a1b22c333")), (Regex.split '\s*,\s*', "a , b,c"), (Regex.replace(/o/, "foo", "0"))]
+            "# = vec![ObjectValue::from("a\\.b"), vec!["1", "22", "333"].into(), vec!["a", "b", "c"].into(), "f0o".into()])
+            uuid_v4(r#"
+            import UUID
+            id = UUID.v4
+            id.version
+            "# = 4)
+            size_humanize(r#"
+            import Size
+            Size.humanize 1468006
+            "# = "1.4 MiB")
+            size_parse(r#"
+            import Size
+            Size.parse "10KB"
+            "# = 10000)
+            select_channels(r#"
+            import Channel
+            ch1 = Channel.new
+            ch2 = Channel.new
+            ch2.send 5
+            select
+                ch1 => |v| v
+                ch2 => |v| v * 2
+            end
+            "# = 10)
+            select_process(r#"
+            pid = spawn do
+                42
+            end
+
+            select
+                pid => |v| do
+                    v + 1
+                end
+            end
+            "# = 43)
+            comptime_lookup_table(r#"
+            squares = comptime do
+                [for v in [1, 2, 3, 4]: v * v]
+            end
+            squares.2
+            "# = 9)
+            comptime_functions(r#"
+            fn double(n) = n * 2
+            total = comptime do
+                fn fib(0) = 0
+                fn fib(1) = 1
+                fn fib(n) = (fib n - 1) + (fib n - 2)
+                fib 10
+            end
+            double total
+            "# = 110)
+            macro_expansion(r#"
+            macro unless_zero(value, body)
+                quote if unquote(value) != 0
+                    unquote(body)
+                else
+                    0
+                end
+            end
+
+            x = 5
+            unless_zero x, do
+                x * 2
+            end
+            "# = 10)
+            select_after(r#"
+            import Channel
+            ch = Channel.new
+            select
+                ch => |v| v
+                after 0 => "timeout"
+            end
+            "# = "timeout")
             to_bits(
                 "2.to_bits" = vec![true, false]
             )
@@ -508,6 +1444,32 @@ pub mod runtime {
 
             receive pid
             "# = 42)
+            spawn_custom_instruction(r#"
+            import Size
+            pid = spawn do
+                Size.humanize 2048
+            end
+            receive pid
+            "# = "2 KiB")
+            on_custom_instruction(r#"
+            import Size
+            @on("size")
+            fn handle(bytes) = Size.humanize bytes
+            r = send "size", 4096
+            receive r
+            "# = vec!["4 KiB"])
+            spawn_par_filter(r#"
+            pid = spawn do
+                [1, 2, 3, 4].par_filter(|v| v % 2 == 0)
+            end
+            receive pid
+            "# = vec![2, 4])
+            on_par_map(r#"
+            @on("go")
+            fn handle(x) = [1, 2, 3].par_map(|v| v * 2)
+            r = send "go", 2
+            receive r
+            "# = vec![vec![2, 4, 6]])
             object_definition(r#"object Foo
                 attr n, Number
 
@@ -520,6 +1482,80 @@ pub mod runtime {
 
             f = Foo.new 7
             f.square"# = 49)
+            object_default_constructor(r#"object Point
+                attr x, Int
+                attr y, Int
+                attr label, String
+            end
+
+            p = Point.new 1, 2, "a"
+            [p.x, p.y, p.label]"# = vec![ObjectValue::from(1), 2.into(), "a".into()])
+            object_eq_hook(r#"object Point
+                attr x, Int
+                attr y, Int
+                attr label, String
+
+                fn Self.eq(other) -> Bool = self.x == other.x && self.y == other.y
+            end
+
+            a = Point.new 1, 2, "a"
+            b = Point.new 1, 2, "b"
+            c = Point.new 2, 1, "a"
+            [(a == b), (a == c)]"# = vec![true, false])
+            object_hash_hook(r#"
+            import Set
+            object Point
+                attr x, Int
+                attr y, Int
+                attr label, String
+
+                fn Self.hash -> Int = self.x * 31 + self.y
+                fn Self.eq(other) -> Bool = self.x == other.x && self.y == other.y
+            end
+
+            s = Set.from_list [(Point.new 1, 2, "a"), (Point.new 1, 2, "b"), (Point.new 2, 1, "c")]
+            points = s.to_list
+            [for p in points: p.label]"# = vec!["a", "c"])
+            object_hash_hook_in_process(r#"
+            import Set
+            object Point
+                attr x, Int
+                attr y, Int
+                attr label, String
+
+                fn Self.hash -> Int = self.x * 31 + self.y
+                fn Self.eq(other) -> Bool = self.x == other.x && self.y == other.y
+            end
+
+            @on("points")
+            fn unique(points) = Set.new points
+
+            pids = send 'points', [(Point.new 1, 2, "a"), (Point.new 1, 2, "b"), (Point.new 2, 1, "c")]
+            points = (receive pids.0).to_list
+            [for p in points: p.label]"# = vec!["a", "c"])
+            object_hash_hook_in_par_map(r#"
+            import Set
+            object Point
+                attr x, Int
+                attr y, Int
+                attr label, String
+
+                fn Self.hash -> Int = self.x * 31 + self.y
+                fn Self.eq(other) -> Bool = self.x == other.x && self.y == other.y
+            end
+
+            fn labels(s) = [for p in (s.to_list): p.label]
+            points = [(Point.new 1, 2, "a"), (Point.new 1, 2, "b"), (Point.new 2, 1, "c")]
+            sets = [points, points].par_map(|p| Set.new p)
+            [for s in sets: labels s]"# = vec![vec!["a", "c"], vec!["a", "c"]])
+            constant_string_concat("'a' + 'b' + 'c'" = "abc")
+            constant_else(r#"
+            if 2 < 1
+                'yes'
+            else
+                'no'
+            end
+            "# = "no")
             try_success(r#"
             try 29
             "# = 29)
@@ -529,6 +1565,34 @@ pub mod runtime {
                 22
             end
             "# = 22)
+            propagate_value(r#"
+            fn check(a) -> Int!
+                if a > 2
+                    raise "too big"
+                end
+                a
+            end
+            fn double(a) -> Int!
+                v = check(a)?
+                v * 2
+            end
+            double 2
+            "# = 4)
+            propagate_error_caught(r#"
+            fn check(a) -> Int!
+                if a > 2
+                    raise "too big"
+                end
+                a
+            end
+            fn double(a) -> Int!
+                v = check(a)?
+                v * 2
+            end
+            (double 3) catch
+                -1
+            end
+            "# = -1)
         }
     }
 
@@ -588,6 +1652,1495 @@ pub mod runtime {
             end
             factorial 15
             "#=1307674368000_i64)
+            memo_limit(r#"
+            @memo(limit: 2, ttl: 60)
+            fn fib(n: Number)
+                if n <= 1
+                    n
+                else
+                    (fib n - 1) + (fib n - 2)
+                end
+            end
+            fib 20
+            "# = 6765)
+        }
+    }
+
+    pub mod bytecode {
+        use super::*;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn run_from_bytecode() {
+            let input = r#"
+            import Random
+            fn double(a) = a * 2
+            mut rand = Random.create 49
+            rand.next_int
+            double 21
+            "#;
+            let runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            let bytes = runtime.bytecode().expect("failed to serialize runtime");
+            let mut runtime = Runtime::from_bytecode(bytes).expect("failed to load bytecode");
+            assert_eq!(runtime.run(), Ok(42.into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn decimal_constants() {
+            let input = "price = 19.99d; price * 3";
+            let runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            let bytes = runtime.bytecode().expect("failed to serialize runtime");
+            let mut runtime = Runtime::from_bytecode(bytes).expect("failed to load bytecode");
+            let result = runtime.run().map(|v| v.to_string());
+            assert_eq!(result, Ok("59.97".to_string()));
+        }
+    }
+
+    pub mod parser_options {
+        use super::*;
+        use rigz_ast::{ParserOptions, ParsingError};
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn create_with_options_uses_max_depth() {
+            let input = "((((((1))))))";
+            let options = ParserOptions {
+                max_depth: 4,
+                ..Default::default()
+            };
+            let result =
+                Runtime::create_with_options(input.to_string(), Default::default(), options);
+            assert!(
+                matches!(result, Err(RuntimeError::Parse(ParsingError::TooDeep(4)))),
+                "unexpected result {:?}",
+                result.map(|_| ())
+            );
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn create_with_options_uses_vm_options() {
+            use rigz_core::ErrorCode;
+            use rigz_runtime::runtime::RuntimeOptions;
+            use rigz_vm::VMOptions;
+
+            let input = r#"
+            fn fib(n: Number) -> Number
+                if n <= 1
+                    n
+                else
+                    (fib n - 1) + (fib n - 2)
+                end
+            end
+            fib 15
+            "#;
+            let options = RuntimeOptions {
+                vm: VMOptions {
+                    max_instructions: Some(100),
+                    ..Default::default()
+                },
+            };
+            let mut runtime =
+                Runtime::create_with_options(input.to_string(), options, Default::default())
+                    .expect("failed to create runtime");
+            let Err(RuntimeError::Run(e)) = runtime.run() else {
+                panic!("budget was not enforced")
+            };
+            assert_eq!(e.code(), ErrorCode::BudgetExceeded);
+        }
+    }
+
+    pub mod inline_loops {
+        use super::*;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn comprehension_without_scope() {
+            let input = "[for v in [1, 2, 3]: v * v]";
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            assert!(runtime.vm().scopes.iter().all(|s| s.named != "for-list"));
+            assert_eq!(runtime.run(), Ok(vec![1, 4, 9].into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn shadowed_variable_uses_scope() {
+            let input = r#"
+            v = 10
+            [for v in [1, 2]: v]
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            assert!(runtime.vm().scopes.iter().any(|s| s.named == "for-list"));
+            assert_eq!(runtime.run(), Ok(vec![1, 2].into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn expensive_body_uses_scope() {
+            let input = "[for v in [1, 2, 3]: v * v + v]";
+            let options = rigz_ast::ParserOptions {
+                max_inline_cost: 2,
+                ..Default::default()
+            };
+            let mut runtime = Runtime::create_unverified_with_options(input.to_string(), options)
+                .expect("failed to create runtime");
+            assert!(runtime.vm().scopes.iter().any(|s| s.named == "for-list"));
+            assert_eq!(runtime.run(), Ok(vec![2, 6, 12].into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn function_with_statements_in_body() {
+            let input = r#"
+            fn f(x)
+                x
+                puts x
+                x * 3
+            end
+            [for v in [1, 2]: f v]
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            assert_eq!(runtime.run(), Ok(vec![3, 6].into()));
+        }
+    }
+
+    pub mod costs {
+        use super::*;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn static_and_average_costs() {
+            let input = r#"
+            fn double(a) = a * 2
+            fn quadruple(a) = double double a
+            fn unused(a) = a
+            quadruple 3
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.profile = true;
+            assert_eq!(runtime.run(), Ok(12.into()));
+
+            let report = runtime.vm().cost_report();
+            let double = report.get("double").expect("missing double");
+            let quadruple = report.get("quadruple").expect("missing quadruple");
+            let unused = report.get("unused").expect("missing unused");
+            assert_eq!(double.calls, 2);
+            assert_eq!(quadruple.calls, 1);
+            assert!(double.instructions > 0);
+            // includes both calls to double
+            assert!(quadruple.average > double.average.map(|a| a * 2.0));
+            assert_eq!(unused.average, None);
+            assert_eq!(report.functions[0].name, "quadruple");
+            assert!(report.to_table().contains("quadruple"));
+        }
+    }
+
+    pub mod inline_caches {
+        use super::*;
+        use rigz_core::ObjectValue;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn call_site_reuses_module() {
+            let input = "[for v in [1, 4, 9, 16]: v.sqrt]";
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.enable_metrics = true;
+            assert!(runtime.run().is_ok());
+            let metrics = &runtime.vm().metrics;
+            assert_eq!(metrics.inline_cache_misses, 1);
+            assert_eq!(metrics.inline_cache_hits, 3);
+            assert_eq!(runtime.vm().inline_caches.len(), 1);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn receiver_type_change_misses() {
+            let input = "[for v in [1, 'a', 2]: v.to_s]";
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.enable_metrics = true;
+            assert!(runtime.run().is_ok());
+            assert_eq!(runtime.vm().metrics.inline_cache_misses, 3);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn call_site_reuses_overload() {
+            let input = r#"
+            m = {a = 1}
+            l = [3]
+            [for v in [1, 2]: [l.first, m.first]]
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.enable_metrics = true;
+            let expected: ObjectValue =
+                vec![ObjectValue::from(vec![3, 1]), ObjectValue::from(vec![3, 1])].into();
+            assert_eq!(runtime.run(), Ok(expected));
+            assert_eq!(runtime.vm().metrics.inline_cache_misses, 2);
+            assert_eq!(runtime.vm().metrics.inline_cache_hits, 2);
+        }
+    }
+
+    pub mod fuel {
+        use super::*;
+        use rigz_core::ErrorCode;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn budget_exceeded() {
+            let input = r#"
+            fn fib(n: Number) -> Number
+                if n <= 1
+                    n
+                else
+                    (fib n - 1) + (fib n - 2)
+                end
+            end
+            fib 15
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.max_instructions = Some(100);
+            let Err(RuntimeError::Run(e)) = runtime.run() else {
+                panic!("budget was not enforced")
+            };
+            assert_eq!(e.code(), ErrorCode::BudgetExceeded);
+            assert_eq!(runtime.vm().remaining_fuel(), Some(0));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn refill() {
+            let mut runtime =
+                Runtime::create("a = 2; a * 21".to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.max_instructions = Some(1000);
+            assert_eq!(runtime.run(), Ok(42.into()));
+            assert!(runtime.vm().remaining_fuel() < Some(1000));
+            runtime.refill_fuel();
+            assert_eq!(runtime.vm().remaining_fuel(), Some(1000));
+        }
+    }
+
+    pub mod timeout {
+        use super::*;
+        use rigz_core::ErrorCode;
+        use rigz_runtime::{eval_within, Runtime};
+        use std::time::{Duration, Instant};
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn interrupts_function_calls() {
+            let input = r#"
+            fn fib(n: Number) -> Number
+                if n <= 1
+                    n
+                else
+                    (fib n - 1) + (fib n - 2)
+                end
+            end
+            fib 40
+            "#;
+            let start = Instant::now();
+            let Err(RuntimeError::Run(e)) =
+                eval_within(input.to_string(), Duration::from_millis(50))
+            else {
+                panic!("timeout was not enforced")
+            };
+            assert_eq!(e.code(), ErrorCode::Timeout);
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn interrupts_sleep() {
+            let start = Instant::now();
+            let Err(RuntimeError::Run(e)) =
+                eval_within("sleep 10000; 1".to_string(), Duration::from_millis(50))
+            else {
+                panic!("timeout was not enforced")
+            };
+            assert_eq!(e.code(), ErrorCode::Timeout);
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn deadline_is_cleared() {
+            let mut runtime = Runtime::new();
+            assert_eq!(
+                runtime.eval_within("a = 2; a * 21".to_string(), Duration::from_secs(5)),
+                Ok(42.into())
+            );
+            assert_eq!(runtime.eval("a + 1".to_string()), Ok(3.into()));
+        }
+    }
+
+    pub mod deterministic {
+        use super::*;
+        use rigz_core::ObjectValue;
+        use rigz_runtime::Runtime;
+
+        fn run_deterministic(input: &str, seed: u64) -> Result<ObjectValue, RuntimeError> {
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.deterministic = true;
+            runtime.vm_mut().options.seed = seed;
+            runtime.run()
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn seeded_randomness() {
+            let input = r#"
+            import Random
+            import UUID
+            [Random.next_int, Random.next_float, (Random.create).next_int, UUID.v4]
+            "#;
+            let first = run_deterministic(input, 7);
+            assert!(first.is_ok(), "{first:?}");
+            assert_eq!(first, run_deterministic(input, 7));
+            assert_ne!(first, run_deterministic(input, 8));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn virtual_clock() {
+            let input = r#"
+            import Time
+            start = Time.now
+            sleep (Time.seconds 60)
+            [start.as_millis, (Time.now - start).as_secs]
+            "#;
+            assert_eq!(
+                run_deterministic(input, 0),
+                Ok(vec![ObjectValue::from(946_684_800_000i64), 60.0.into()].into())
+            );
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn time_measure() {
+            let input = r#"
+            import Time
+            d = Time.measure do
+                sleep (Time.seconds 2)
+            end
+            e = Time.measure { || 1 }
+            [d.as_secs, e.as_nanos]
+            "#;
+            assert_eq!(
+                run_deterministic(input, 0),
+                Ok(vec![ObjectValue::from(2.0), 0.into()].into())
+            );
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn nondeterministic_modules_are_rejected() {
+            let input = r#"
+            import Env
+            Env.get 'HOME'
+            "#;
+            let Err(RuntimeError::Run(e)) = run_deterministic(input, 0) else {
+                panic!("Env was allowed")
+            };
+            assert!(e.to_string().contains("nondeterministic"), "{e}");
+        }
+    }
+
+    pub mod cooperative_processes {
+        use super::*;
+        use rigz_runtime::Runtime;
+        use rigz_vm::ProcessExecutor;
+        use std::time::{Duration, Instant};
+
+        fn runtime(input: String, workers: usize, quantum: usize) -> Runtime<'static> {
+            let mut runtime = Runtime::create(input).expect("failed to create runtime");
+            runtime.vm_mut().options.executor = ProcessExecutor::Cooperative { workers, quantum };
+            runtime
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn results_are_received() {
+            let input = r#"
+            a = spawn do
+                sleep 20
+                1
+            end
+            b = spawn do
+                sleep 10
+                2
+            end
+            c = spawn do = 3
+            receive [a, b, c]
+            "#;
+            let mut runtime = runtime(input.to_string(), 1, 4);
+            assert_eq!(runtime.run(), Ok(vec![1, 2, 3].into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn sleeping_processes_share_a_thread() {
+            let processes = 200;
+            let mut input = String::new();
+            for i in 0..processes {
+                input.push_str(&format!("p{i} = spawn do\n  sleep 50\n  {i}\nend\n"));
+            }
+            let pids: Vec<_> = (0..processes).map(|i| format!("p{i}")).collect();
+            input.push_str(&format!("receive [{}]", pids.join(", ")));
+
+            let start = Instant::now();
+            let mut runtime = runtime(input, 1, 64);
+            let expected: Vec<_> = (0..processes).collect();
+            assert_eq!(runtime.run(), Ok(expected.into()));
+            // 200 * 50ms when the processes sleep one after the other
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn par_map_runs_in_order() {
+            let input = r#"
+            pid = spawn do
+                [1, 2, 3].par_map(|v| v * 2)
+            end
+            receive pid
+            "#;
+            let mut runtime = runtime(input.to_string(), 1, 4);
+            assert_eq!(runtime.run(), Ok(vec![2, 4, 6].into()));
+        }
+
+        #[cfg(feature = "threaded")]
+        #[wasm_bindgen_test(unsupported = test)]
+        fn waiting_acquire_yields() {
+            // the first process never gets the mutex, with one worker the second only runs if it yields
+            let input = r#"
+            import Process
+            import Sync
+            a = spawn do
+                m = Mutex.new 0
+                m.acquire
+                m.acquire
+            end
+            b = spawn do = 1
+            result = receive b, 1000
+            Process.kill a
+            result
+            "#;
+            let mut runtime = Runtime::new();
+            runtime.allow_process_control().unwrap();
+            runtime.vm_mut().options.executor = ProcessExecutor::Cooperative {
+                workers: 1,
+                quantum: 4,
+            };
+            assert_eq!(runtime.eval(input.to_string()), Ok(1.into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn waiting_task_yields() {
+            // accepts connections without ever responding
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let processes = 50;
+            let mut input = "import Http\n".to_string();
+            for i in 0..processes {
+                input.push_str(&format!(
+                    "p{i} = spawn do\n  t = Http.get_async 'http://127.0.0.1:{port}'\n  t.wait 100\n  {i}\nend\n"
+                ));
+            }
+            let pids: Vec<_> = (0..processes).map(|i| format!("p{i}")).collect();
+            input.push_str(&format!("receive [{}]", pids.join(", ")));
+
+            let start = Instant::now();
+            let mut runtime = runtime(input, 1, 64);
+            let expected: Vec<_> = (0..processes).collect();
+            assert_eq!(runtime.run(), Ok(expected.into()));
+            // 50 * 100ms when each wait blocks the only worker
+            assert!(start.elapsed() < Duration::from_millis(2500));
+            drop(listener);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn read_async_in_process() {
+            let path =
+                std::env::temp_dir().join(format!("rigz_read_async_{}.txt", std::process::id()));
+            std::fs::write(&path, "hello").unwrap();
+            let input = format!(
+                "import File\np = spawn do\n  t = File.read_async '{}', 'utf-8'\n  t.wait\nend\nreceive p",
+                path.display()
+            );
+            let mut runtime = runtime(input, 1, 4);
+            assert_eq!(runtime.run(), Ok("hello".into()));
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn waiting_receive_yields() {
+            let processes = 50;
+            let mut input = "import Channel\n".to_string();
+            for i in 0..processes {
+                input.push_str(&format!(
+                    "p{i} = spawn do\n  ch = Channel.new\n  ch.receive 100\n  {i}\nend\n"
+                ));
+            }
+            let pids: Vec<_> = (0..processes).map(|i| format!("p{i}")).collect();
+            input.push_str(&format!("receive [{}]", pids.join(", ")));
+
+            let start = Instant::now();
+            let mut runtime = runtime(input, 1, 64);
+            let expected: Vec<_> = (0..processes).collect();
+            assert_eq!(runtime.run(), Ok(expected.into()));
+            // 50 * 100ms when each receive blocks the only worker
+            assert!(start.elapsed() < Duration::from_millis(2500));
+        }
+    }
+
+    pub mod persistent_memo {
+        use super::*;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn results_survive_runs() {
+            let path = std::env::temp_dir().join(format!("rigz_memo_{}.db", std::process::id()));
+            let program = |suffix: &str| {
+                format!(
+                    r#"
+            import Env
+            @memo(persist: "{}")
+            fn lookup(name)
+                Env.fetch 'RIGZ_PERSIST_MEMO', 'first'
+            end
+            lookup 'a'{suffix}
+            "#,
+                    path.display()
+                )
+            };
+            assert_eq!(eval(program("")), Ok("first".into()));
+            std::env::set_var("RIGZ_PERSIST_MEMO", "second");
+            // formatting & comments don't change the program digest
+            assert_eq!(eval(program(" # cached")), Ok("first".into()));
+            // changing the program invalidates its results
+            assert_eq!(
+                eval(program("\n            lookup 'a'")),
+                Ok("second".into())
+            );
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn persisted_results_expire() {
+            let path =
+                std::env::temp_dir().join(format!("rigz_memo_ttl_{}.db", std::process::id()));
+            let program = format!(
+                r#"
+            import Env
+            @memo(persist: "{}", ttl: 0.05)
+            fn lookup(name)
+                Env.fetch 'RIGZ_MEMO_TTL', 'first'
+            end
+            lookup 'a'
+            "#,
+                path.display()
+            );
+            assert_eq!(eval(program.clone()), Ok("first".into()));
+            std::env::set_var("RIGZ_MEMO_TTL", "second");
+            assert_eq!(eval(program.clone()), Ok("first".into()));
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            assert_eq!(eval(program), Ok("second".into()));
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    pub mod csv_rows {
+        use super::*;
+        use rigz_core::{IndexMap, ObjectValue, VMError};
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn rows_are_streamed() {
+            let path = std::env::temp_dir().join(format!("rigz_rows_{}.csv", std::process::id()));
+            std::fs::write(&path, "name,age\nada,36\nbob,41\ncy,7\n").unwrap();
+            let input = format!(
+                r#"
+            import CSV
+            rows = CSV.rows "{0}"
+            first = rows.next
+            CSV.each_row("{0}", |row| puts row.get "name")
+            [first, ([for r in rows: r]), rows.next]
+            "#,
+                path.display()
+            );
+            let mut runtime = Runtime::create(input).expect("failed to create runtime");
+            runtime.vm().context.start_capture();
+            let result = runtime.run();
+            let output: Vec<_> = runtime
+                .vm()
+                .context
+                .stop_capture()
+                .into_iter()
+                .map(|c| c.content)
+                .collect();
+            std::fs::remove_file(path).unwrap();
+            let row = |name: &str, age: &str| {
+                let mut m = IndexMap::new();
+                m.insert("name".into(), name.into());
+                m.insert("age".into(), age.into());
+                ObjectValue::Map(m)
+            };
+            assert_eq!(
+                result,
+                Ok(vec![
+                    row("ada", "36"),
+                    vec![row("bob", "41"), row("cy", "7")].into(),
+                    ObjectValue::default(),
+                ]
+                .into())
+            );
+            assert_eq!(output, vec!["ada\n", "bob\n", "cy\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn missing_file() {
+            let result = eval(r#"import CSV; CSV.rows "/missing/rigz.csv""#.to_string());
+            assert!(
+                matches!(result, Err(RuntimeError::Run(VMError::RuntimeError(ref e))) if e.contains("/missing/rigz.csv")),
+                "{result:?}"
+            );
+        }
+    }
+
+    pub mod file_mmap {
+        use super::*;
+        use rigz_core::ObjectValue;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn lines_are_streamed() {
+            let path = std::env::temp_dir().join(format!("rigz_mmap_{}.log", std::process::id()));
+            std::fs::write(&path, "ok\nerror: a\nok\nerror: b\n").unwrap();
+            let input = format!(
+                r#"
+            import File
+            lines = File.mmap "{0}"
+            first = lines.next
+            written = (File.write "{0}", "truncated") catch
+                "locked"
+            end
+            [first, [for line in lines: line], written, lines.len]
+            "#,
+                path.display()
+            );
+            let result = eval(input);
+            std::fs::remove_file(path).unwrap();
+            let rest: Vec<ObjectValue> = vec!["error: a".into(), "ok".into(), "error: b".into()];
+            assert_eq!(
+                result,
+                Ok(vec![
+                    ObjectValue::from("ok"),
+                    rest.into(),
+                    "locked".into(),
+                    24.into()
+                ]
+                .into())
+            );
+        }
+    }
+
+    pub mod server {
+        use super::*;
+        use rigz_runtime::Runtime;
+        use std::time::Duration;
+
+        fn get(url: &str) -> (u16, String, Option<String>) {
+            let response = match ureq::get(url).call() {
+                Ok(r) => r,
+                Err(ureq::Error::Status(_, r)) => r,
+                Err(e) => panic!("{url} failed - {e}"),
+            };
+            let status = response.status();
+            let header = response.header("X-Rigz").map(String::from);
+            (status, response.into_string().unwrap(), header)
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn routes_are_handled() {
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let client = std::thread::spawn(move || {
+                let base = format!("http://127.0.0.1:{port}");
+                for _ in 0..100 {
+                    if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                vec![
+                    get(&format!("{base}/hello?name=rigz")),
+                    get(&format!("{base}/users")),
+                    get(&format!("{base}/missing")),
+                ]
+            });
+            let input = format!(
+                r#"
+            import Server
+            Server.route "/hello", |req| "hi " + req.query.name
+            Server.route "/users", |req| {{ status = 201, body = [1, 2], headers = {{ "X-Rigz" = req.method }} }}
+            Server.listen {port}, requests: 3
+            "done"
+            "#
+            );
+            let mut runtime = Runtime::create(input).expect("failed to create runtime");
+            let result = runtime.run();
+            assert_eq!(result, Ok("done".into()));
+            assert_eq!(
+                client.join().unwrap(),
+                vec![
+                    (200, "hi rigz".to_string(), None),
+                    (201, "[1,2]".to_string(), Some("GET".to_string())),
+                    (404, "No route for /missing".to_string(), None),
+                ]
+            );
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn routes_are_per_program() {
+            let mut other = Runtime::create(
+                r#"import Server; Server.route "/other", |req| "other""#.to_string(),
+            )
+            .expect("failed to create runtime");
+            assert_eq!(other.run(), Ok(PrimitiveValue::None.into()));
+
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let client = std::thread::spawn(move || {
+                for _ in 0..100 {
+                    if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                get(&format!("http://127.0.0.1:{port}/other"))
+            });
+            let input = format!(
+                r#"
+            import Server
+            Server.listen {port}, requests: 1
+            "done"
+            "#
+            );
+            let mut runtime = Runtime::create(input).expect("failed to create runtime");
+            assert_eq!(runtime.run(), Ok("done".into()));
+            assert_eq!(
+                client.join().unwrap(),
+                (404, "No route for /other".to_string(), None)
+            );
+        }
+
+        fn free_port() -> u16 {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port()
+        }
+
+        fn wait_for(port: u16) {
+            for _ in 0..100 {
+                if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn requests_are_handled_concurrently() {
+            let port = free_port();
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let clients: Vec<_> = ["/slow", "/fast"]
+                .into_iter()
+                .enumerate()
+                .map(|(i, path)| {
+                    let sender = sender.clone();
+                    std::thread::spawn(move || {
+                        wait_for(port);
+                        std::thread::sleep(Duration::from_millis(200 * i as u64));
+                        let (_, body, _) = get(&format!("http://127.0.0.1:{port}{path}"));
+                        sender.send(body).unwrap();
+                    })
+                })
+                .collect();
+            let input = format!(
+                r#"
+            import Server
+            Server.route "/slow", |req| do
+                sleep 1000
+                "slow"
+            end
+            Server.route "/fast", |req| "fast"
+            Server.listen {port}, requests: 2
+            "done"
+            "#
+            );
+            let mut runtime = Runtime::create(input).expect("failed to create runtime");
+            assert_eq!(runtime.run(), Ok("done".into()));
+            for c in clients {
+                c.join().unwrap();
+            }
+            let order: Vec<_> = receiver.try_iter().collect();
+            assert_eq!(order, vec!["fast".to_string(), "slow".to_string()]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn invalid_requests_and_responses() {
+            let port = free_port();
+            let client = std::thread::spawn(move || {
+                wait_for(port);
+                let base = format!("http://127.0.0.1:{port}");
+                let binary = match ureq::post(&format!("{base}/echo")).send_bytes(&[0xff, 0xfe]) {
+                    Ok(r) | Err(ureq::Error::Status(_, r)) => {
+                        (r.status(), r.into_string().unwrap(), None)
+                    }
+                    Err(e) => panic!("{base}/echo failed - {e}"),
+                };
+                vec![
+                    binary,
+                    get(&format!("{base}/status")),
+                    get(&format!("{base}/fails")),
+                    get(&format!("{base}/echo")),
+                ]
+            });
+            let input = format!(
+                r#"
+            import Server
+            Server.route "/echo", |req| req.method
+            Server.route "/status", |req| {{ status = 42 }}
+            Server.route "/fails", |req| raise "broken"
+            Server.listen {port}, requests: 4
+            "done"
+            "#
+            );
+            let mut runtime = Runtime::create(input).expect("failed to create runtime");
+            assert_eq!(runtime.run(), Ok("done".into()));
+            let responses = client.join().unwrap();
+            assert_eq!(
+                responses[0],
+                (400, "Request body is not valid utf-8".to_string(), None)
+            );
+            assert_eq!(
+                responses[1],
+                (
+                    500,
+                    "Invalid response status 42, expected 100 to 999".to_string(),
+                    None
+                )
+            );
+            assert_eq!(responses[2].0, 500);
+            assert_eq!(responses[3], (200, "GET".to_string(), None));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn listener_defaults_to_localhost() {
+            let input = r#"
+            import Server
+            server = Server::Listener.new 0
+            server.address
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            let address = runtime.run().unwrap().to_string();
+            assert!(address.starts_with("127.0.0.1:"), "{address}");
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn handlers_must_be_lambdas() {
+            let result = Runtime::create(
+                r#"import Server; fn greet(req) = "hi"; Server.route "/hello", greet"#.to_string(),
+            );
+            assert!(result.is_err());
+        }
+    }
+
+    pub mod imports {
+        use super::*;
+        use rigz_ast::{
+            ImportPath, ImportResolver, ParserOptions, ResolvedImport, ValidationError,
+        };
+        use rigz_core::ObjectValue;
+        use rigz_runtime::Runtime;
+        use std::path::PathBuf;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Debug, Default)]
+        struct MemoryResolver {
+            resolved: Mutex<Vec<ImportPath>>,
+        }
+
+        impl ImportResolver for MemoryResolver {
+            fn resolve(
+                &self,
+                path: &ImportPath,
+                _options: &ParserOptions,
+            ) -> Result<ResolvedImport, ValidationError> {
+                self.resolved.lock().unwrap().push(path.clone());
+                let (key, source) = match path {
+                    ImportPath::File(f) if f.ends_with("math.rg") => {
+                        ("math", "fn double(a) = a * 2")
+                    }
+                    ImportPath::File(f) if f.ends_with("lazy.rg") => {
+                        ("lazy", "puts \"loaded\"\nfn triple(a) = a * 3")
+                    }
+                    p => {
+                        return Err(ValidationError::InvalidImport(format!(
+                            "{p:?} does not exist"
+                        )))
+                    }
+                };
+                Ok(ResolvedImport {
+                    key: key.to_string(),
+                    source: source.to_string(),
+                })
+            }
+        }
+
+        fn options(resolver: Arc<MemoryResolver>) -> ParserOptions {
+            ParserOptions {
+                current_directory: Some(PathBuf::from("/virtual")),
+                import_resolver: Some(resolver),
+                ..Default::default()
+            }
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn custom_resolver() {
+            let resolver = Arc::new(MemoryResolver::default());
+            let input = r#"
+            import "math.rg"
+            import "lib/../math.rg"
+            double 21
+            "#;
+            let mut runtime = Runtime::create_unverified_with_options(
+                input.to_string(),
+                options(resolver.clone()),
+            )
+            .expect("failed to create runtime");
+            assert_eq!(runtime.run(), Ok(42.into()));
+            assert_eq!(
+                *resolver.resolved.lock().unwrap(),
+                vec![
+                    ImportPath::File(PathBuf::from("/virtual/math.rg")),
+                    ImportPath::File(PathBuf::from("/virtual/lib/../math.rg")),
+                ]
+            );
+        }
+
+        fn run_captured(input: &str) -> (Result<ObjectValue, RuntimeError>, Vec<String>) {
+            let resolver = Arc::new(MemoryResolver::default());
+            let mut runtime =
+                Runtime::create_unverified_with_options(input.to_string(), options(resolver))
+                    .expect("failed to create runtime");
+            runtime.vm().context.start_capture();
+            let result = runtime.run();
+            let captured = runtime.vm().context.stop_capture();
+            (result, captured.into_iter().map(|c| c.content).collect())
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn lazy_import_runs_on_first_call() {
+            let (result, output) = run_captured(
+                r#"
+            import lazy "lazy.rg"
+            puts "main"
+            a = triple 2
+            b = triple 3
+            a + b
+            "#,
+            );
+            assert_eq!(result, Ok(15.into()));
+            assert_eq!(output, vec!["main\n", "loaded\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn unused_lazy_import_does_not_run() {
+            let (result, output) = run_captured("import lazy \"lazy.rg\"\n1");
+            assert_eq!(result, Ok(1.into()));
+            assert!(output.is_empty(), "{output:?}");
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn import_after_lazy_import_runs_it() {
+            let (result, output) = run_captured(
+                r#"
+            import lazy "lazy.rg"
+            import "lazy.rg"
+            puts "main"
+            triple 2
+            "#,
+            );
+            assert_eq!(result, Ok(6.into()));
+            assert_eq!(output, vec!["loaded\n", "main\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn embedded_imports_are_not_resolved() {
+            let resolver = Arc::new(MemoryResolver::default());
+            let input = r#"
+            import "math.rg" do # bundled
+                fn double(a) = a * 2
+            end
+            import "math.rg"
+            double 21
+            "#;
+            let mut runtime = Runtime::create_unverified_with_options(
+                input.to_string(),
+                options(resolver.clone()),
+            )
+            .expect("failed to create runtime");
+            assert_eq!(runtime.run(), Ok(42.into()));
+            assert!(resolver.resolved.lock().unwrap().is_empty());
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn embedded_import_runs_in_its_own_scope() {
+            let (result, output) = run_captured(
+                r#"
+            x = 1
+            import "lib.rg" do
+                x = 2
+                puts x
+                fn get = 3
+            end
+            [x, get]
+            "#,
+            );
+            assert_eq!(result, Ok(vec![1, 3].into()));
+            assert_eq!(output, vec!["2\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn embedded_lazy_import_runs_on_first_call() {
+            let (result, output) = run_captured(
+                r#"
+            import lazy "lazy.rg" do
+                puts "loaded"
+                fn triple(a) = a * 3
+            end
+            puts "main"
+            triple 2
+            "#,
+            );
+            assert_eq!(result, Ok(6.into()));
+            assert_eq!(output, vec!["main\n", "loaded\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn lazy_value_runs_once() {
+            let (result, output) = run_captured(
+                r#"
+            fn expensive
+                puts "ran"
+                21
+            end
+            a = lazy do expensive end
+            puts "main"
+            a + a
+            "#,
+            );
+            assert_eq!(result, Ok(42.into()));
+            assert_eq!(output, vec!["main\n", "ran\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn lazy_default_argument() {
+            let (result, output) = run_captured(
+                r#"
+            fn expensive
+                puts "ran"
+                2
+            end
+            fn pick(flag: Bool, fallback = lazy do expensive end)
+                if flag
+                    1
+                else
+                    fallback
+                end
+            end
+            a = pick true
+            b = pick false
+            [a, b]
+            "#,
+            );
+            assert_eq!(result, Ok(vec![1, 2].into()));
+            assert_eq!(output, vec!["ran\n"]);
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn custom_resolver_error() {
+            let resolver = Arc::new(MemoryResolver::default());
+            let result = Runtime::create_unverified_with_options(
+                "import \"missing.rg\"\n1".to_string(),
+                options(resolver),
+            );
+            assert!(
+                matches!(result, Err(RuntimeError::Validation(ValidationError::InvalidImport(ref e))) if e.contains("missing.rg")),
+                "{:?}",
+                result.err()
+            );
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn comptime_import_is_rejected() {
+            let resolver = Arc::new(MemoryResolver::default());
+            let result = Runtime::create_unverified_with_options(
+                "comptime do\n    import \"math.rg\"\n    double 21\nend".to_string(),
+                options(resolver.clone()),
+            );
+            assert!(
+                matches!(result, Err(RuntimeError::Validation(ValidationError::Comptime(ref e))) if e.contains("imports aren't allowed")),
+                "{:?}",
+                result.err()
+            );
+            assert!(resolver.resolved.lock().unwrap().is_empty());
+        }
+    }
+
+    pub mod coverage {
+        use super::*;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn lines_and_functions() {
+            let input = r#"
+            fn double(a)
+                a * 2
+            end
+            fn triple(a)
+                a * 3
+            end
+            double 21
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.coverage = true;
+            assert_eq!(runtime.run(), Ok(42.into()));
+
+            let report = runtime.vm().coverage_report();
+            let lines = report.lines();
+            assert_eq!(lines.get(&3), Some(&1));
+            assert_eq!(lines.get(&6), Some(&0));
+            assert_eq!(lines.get(&8), Some(&1));
+            let lcov = report.to_lcov("main.rg");
+            assert!(lcov.starts_with("TN:\nSF:main.rg\n"), "{lcov}");
+            assert!(lcov.contains("FNDA:1,double\n"), "{lcov}");
+            assert!(lcov.contains("FNDA:0,triple\n"), "{lcov}");
+            assert!(lcov.contains("DA:6,0\n"), "{lcov}");
+            assert!(lcov.ends_with("end_of_record\n"), "{lcov}");
+
+            runtime.vm_mut().reset_coverage();
+            assert_eq!(runtime.vm().coverage_report().lines().get(&3), Some(&0));
+        }
+    }
+
+    pub mod debug_info {
+        use super::*;
+        use rigz_core::VMError;
+        use rigz_runtime::Runtime;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn line_table() {
+            let input = r#"
+            fn double(a)
+                a * 2
+            end
+
+            x = 21
+            double x
+            "#;
+            let runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            let scopes = &runtime.vm().scopes;
+            let double = scopes
+                .iter()
+                .find(|s| s.named == "double")
+                .expect("double scope missing");
+            assert_eq!(double.line(0), Some(3));
+            assert_eq!(scopes[0].line(0), Some(6));
+
+            let bytes = runtime.bytecode().expect("failed to serialize runtime");
+            let mut loaded = Runtime::from_bytecode(bytes).expect("failed to load bytecode");
+            assert_eq!(loaded.vm().scopes[0].lines, scopes[0].lines);
+            assert_eq!(loaded.run(), Ok(42.into()));
+            assert_eq!(loaded.vm().line(), Some(7));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn traceback() {
+            let input = r#"
+            fn inner
+                raise 'failed'
+            end
+            fn outer
+                inner
+            end
+            outer
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            runtime.vm_mut().options.enable_traceback = true;
+            let Err(RuntimeError::Run(e)) = runtime.run() else {
+                panic!("expected run to fail")
+            };
+            assert_eq!(e.untraced(), &VMError::RuntimeError("failed".to_string()));
+            let frames: Vec<_> = e
+                .traceback()
+                .iter()
+                .map(|f| (f.name.as_str(), f.line))
+                .collect();
+            assert_eq!(
+                frames,
+                vec![("inner", Some(3)), ("outer", Some(6)), ("main", Some(8))]
+            );
+        }
+    }
+
+    pub mod contexts {
+        use super::*;
+        use rigz_core::{ObjectValue, RuntimeContext};
+        use rigz_runtime::Runtime;
+        use std::thread;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn runtimes_on_threads_are_isolated() {
+            let handles: Vec<_> = (1..5)
+                .map(|digits| {
+                    thread::spawn(move || {
+                        let input = format!(
+                            r#"
+                            Number.set_float_format({digits})
+                            puts {digits}
+                            (2.0 / 3).to_s
+                            "#
+                        );
+                        let mut runtime = Runtime::create(input).expect("failed to create runtime");
+                        runtime.vm().context.start_capture();
+                        let result = runtime.run();
+                        let captured = runtime.vm().context.stop_capture();
+                        let expected = format!("{:.*}", digits, 2.0 / 3.0);
+                        assert_eq!(result, Ok(expected.into()));
+                        assert_eq!(captured.len(), 1);
+                        assert_eq!(captured[0].content, format!("{digits}\n"));
+                    })
+                })
+                .collect();
+            handles.into_iter().for_each(|h| h.join().unwrap());
+        }
+
+        fn program(i: usize) -> String {
+            // even runtimes compare points by x, odd ones by y
+            let field = if i.is_multiple_of(2) { "x" } else { "y" };
+            format!(
+                r#"
+                import Random
+                import Set
+                object Point
+                    attr x, Int
+                    attr y, Int
+
+                    fn Self.hash -> Int = self.{field}
+                    fn Self.eq(other) -> Bool = self.{field} == other.{field}
+                end
+
+                n = {i}
+                key = "runtime_" + n.to_s
+                mut m = {{}}
+                m.insert key, Random.next_int
+                s = Set.from_list [(Point.new 1, 2), (Point.new 1, 3), (Point.new 1, 4)]
+                points = s.to_list
+                [m, (Random.next_float), [for p in points: p.y]]
+                "#
+            )
+        }
+
+        fn run(i: usize) -> (Result<ObjectValue, RuntimeError>, RuntimeContext) {
+            let mut runtime = Runtime::create(program(i)).expect("failed to create runtime");
+            runtime.vm_mut().options.deterministic = true;
+            runtime.vm_mut().options.seed = i as u64;
+            let result = runtime.run();
+            (result, runtime.vm().context.clone())
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn concurrent_runtimes_keep_their_state() {
+            let expected: Vec<_> = (0..4).map(|i| run(i).0).collect();
+            let handles: Vec<_> = (0..4).map(|i| thread::spawn(move || run(i))).collect();
+            for (i, handle) in handles.into_iter().enumerate() {
+                let (result, context) = handle.join().unwrap();
+                assert_eq!(result, expected[i]);
+                let Ok(ObjectValue::List(values)) = result else {
+                    panic!("runtime {i} failed {result:?}")
+                };
+                let unique: ObjectValue = match i % 2 {
+                    0 => vec![2].into(),
+                    _ => vec![2, 3, 4].into(),
+                };
+                assert_eq!(values[2], unique);
+                for j in 0..4 {
+                    let key = format!("runtime_{j}");
+                    assert_eq!(context.intern_table().contains(&key), i == j, "{key}");
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    pub mod bigint {
+        use super::*;
+        use rigz_core::{BigInt, ObjectValue};
+
+        fn big(value: &str) -> ObjectValue {
+            BigInt::parse(value).unwrap()
+        }
+
+        run_expected! {
+            add_overflow_promotes(r#"
+            a = 9223372036854775807
+            a + 1
+            "# = big("9223372036854775808"))
+            mul_overflow_promotes(r#"
+            a = 4294967296
+            a * a * a
+            "# = big("79228162514264337593543950336"))
+            demotes_when_it_fits(r#"
+            a = 9223372036854775807
+            a + 1 - 2
+            "# = 9223372036854775806i64)
+            large_literal("123_456_789_012_345_678_901_234_567_890" = big("123456789012345678901234567890"))
+            negative_large_literal("-99999999999999999999 + 1" = big("-99999999999999999998"))
+            compares_to_int("99999999999999999999 > 9223372036854775807" = true)
+            large_to_s("99999999999999999999.to_s" = "99999999999999999999")
+        }
+    }
+
+    pub mod signals {
+        use super::*;
+        use rigz_core::{Signal, VMError};
+        use rigz_runtime::{Runtime, RuntimeError};
+        use std::thread;
+        use std::time::Duration;
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn handler_runs_before_stopping() {
+            let input = r#"
+            @on("signal:TERM")
+            fn cleanup
+                puts "cleaned up"
+            end
+
+            sleep 10000
+            "done"
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            let handle = runtime.vm().signal_handle();
+            let sender = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                handle.send(Signal::Term);
+            });
+            runtime.vm().context.start_capture();
+            let result = runtime.run();
+            let captured = runtime.vm().context.stop_capture();
+            sender.join().unwrap();
+            let Err(RuntimeError::Run(e)) = result else {
+                panic!("expected run to be interrupted, received {result:?}")
+            };
+            assert_eq!(
+                e.untraced(),
+                &VMError::RuntimeError("Interrupted by SIGTERM".to_string())
+            );
+            assert_eq!(runtime.vm().received_signal(), Some(Signal::Term));
+            assert!(captured.iter().any(|c| c.content == "cleaned up\n"));
+        }
+    }
+
+    #[cfg(feature = "threaded")]
+    pub mod processes {
+        use super::*;
+        use rigz_core::{ObjectValue, VMError};
+        use rigz_runtime::{Runtime, RuntimeError};
+        use rigz_vm::ProcessState;
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn host_cancels_process() {
+            let input = r#"
+            pid = spawn do
+                sleep 100000
+                1
+            end
+            receive pid
+            "#;
+            let mut runtime = Runtime::create(input.to_string()).expect("failed to create runtime");
+            let handle = runtime.vm().process_handle();
+            let canceller = thread::spawn(move || loop {
+                let processes = handle.list();
+                if processes.first().map(|p| p.state) == Some(ProcessState::Running) {
+                    assert_eq!(processes[0].scope, "spawn");
+                    handle.cancel(0).unwrap();
+                    return handle.list()[0].state;
+                }
+                thread::sleep(Duration::from_millis(5));
+            });
+            let start = Instant::now();
+            let result = runtime.run();
+            assert_eq!(canceller.join().unwrap(), ProcessState::Cancelled);
+            assert!(start.elapsed() < Duration::from_secs(5));
+            let Err(RuntimeError::Run(e)) = result else {
+                panic!("expected process to be cancelled, received {result:?}")
+            };
+            assert_eq!(
+                e.untraced(),
+                &VMError::RuntimeError("Process was cancelled".to_string())
+            );
+            assert!(runtime.processes().is_empty());
+            assert!(runtime.cancel(0).is_err());
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn script_lists_and_kills() {
+            let input = r#"
+            import Process
+            pid = spawn do
+                sleep 100000
+                1
+            end
+            sleep 20
+            running = Process.list
+            Process.kill pid
+            result = receive pid
+            cancelled = Process.list
+            a = running.first
+            b = cancelled.first
+            [a.pid, a.state, b.state, result]
+            "#;
+            let mut runtime = Runtime::new();
+            runtime.allow_process_control().unwrap();
+            let expected: ObjectValue = vec![
+                ObjectValue::from(0),
+                "running".into(),
+                "cancelled".into(),
+                VMError::RuntimeError("Process was cancelled".to_string()).into(),
+            ]
+            .into();
+            assert_eq!(runtime.eval(input.to_string()), Ok(expected));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn runtimes_have_their_own_modules() {
+            let input = r#"
+            import Process
+            pid = spawn do
+                sleep 2000
+                1
+            end
+            sleep 20
+            running = Process.list
+            Process.kill pid
+            a = running.first
+            a.state
+            "#;
+            let mut first = Runtime::new();
+            first.allow_process_control().unwrap();
+            // registered last, the first runtime must still list & kill its own processes
+            let mut second = Runtime::new();
+            second.allow_process_control().unwrap();
+            assert_eq!(first.eval(input.to_string()), Ok("running".into()));
+        }
+
+        #[wasm_bindgen_test(unsupported = test)]
+        fn process_module_is_not_a_default() {
+            let result = Runtime::create("import Process\n1".to_string());
+            assert!(matches!(result, Err(RuntimeError::Validation(_))));
         }
     }
 }
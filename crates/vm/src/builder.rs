@@ -1,15 +1,16 @@
+use crate::prelude::*;
 use crate::vm::VMOptions;
 use crate::ModulesMap;
-use crate::{Instruction, LoadValue, Scope, VM};
+use crate::{CustomInstruction, Instruction, LoadValue, ObjectHookScopes, Parallel, Scope, VM};
+use alloc::sync::Arc;
+use core::fmt::Debug;
 use log::Level;
 use rigz_core::{
-    BinaryOperation, Dependency, Lifecycle, Module, ObjectValue, RigzType, UnaryOperation,
+    BinaryOperation, Dependency, IndexMap, Interned, Lifecycle, Module, ObjectValue, RigzType,
+    UnaryOperation,
 };
-use std::fmt::Debug;
-use std::sync::Arc;
-// todo use Rodeo (single threaded here + runtime), use Reference<(Threaded or not)Resolver> in VM
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct VMBuilder {
     pub sp: usize,
     pub scopes: Vec<Scope>,
@@ -18,6 +19,34 @@ pub struct VMBuilder {
     pub options: VMOptions,
     pub lifecycles: Vec<Lifecycle>,
     pub constants: Vec<ObjectValue>,
+    pub custom_instructions: Vec<CustomInstruction>,
+    pub object_hooks: IndexMap<String, ObjectHookScopes>,
+}
+
+/// Clones get their own modules map, modules registered afterward (i.e. `Process`) aren't seen by other clones
+impl Clone for VMBuilder {
+    fn clone(&self) -> Self {
+        #[cfg(feature = "threaded")]
+        let modules = alloc::sync::Arc::new(
+            self.modules
+                .iter()
+                .map(|m| (*m.key(), m.value().clone()))
+                .collect(),
+        );
+        #[cfg(not(feature = "threaded"))]
+        let modules = self.modules.clone();
+        Self {
+            sp: self.sp,
+            scopes: self.scopes.clone(),
+            modules,
+            dependencies: self.dependencies.clone(),
+            options: self.options,
+            lifecycles: self.lifecycles.clone(),
+            constants: self.constants.clone(),
+            custom_instructions: self.custom_instructions.clone(),
+            object_hooks: self.object_hooks.clone(),
+        }
+    }
 }
 
 impl Default for VMBuilder {
@@ -31,6 +60,8 @@ impl Default for VMBuilder {
             options: Default::default(),
             lifecycles: Default::default(),
             constants: Default::default(),
+            custom_instructions: Default::default(),
+            object_hooks: Default::default(),
         }
     }
 }
@@ -64,6 +95,12 @@ pub trait RigzBuilder: Debug + Default {
 
     fn add_instruction(&mut self, instruction: Instruction) -> &mut Self;
 
+    /// Index the next instruction will have in the current scope, used as a jump target
+    fn next_instruction_index(&self) -> usize;
+
+    /// Replaces an instruction in the current scope, used to set jump targets after the body is added
+    fn set_instruction(&mut self, index: usize, instruction: Instruction) -> &mut Self;
+
     fn build(self) -> VM;
 
     fn current_scope(&self) -> usize;
@@ -89,14 +126,26 @@ pub trait RigzBuilder: Debug + Default {
 
     fn register_dependency(&mut self, dependency: Arc<Dependency>) -> usize;
 
+    /// Objects of `object` (by type name) are hashed & compared with these scopes while the VM runs
+    fn set_object_hooks(&mut self, object: String, hooks: ObjectHookScopes) -> &mut Self;
+
     #[cfg(feature = "threaded")]
     fn register_module<M: Module + Send + Sync + 'static>(&mut self, module: M) -> &mut Self;
 
     #[cfg(not(feature = "threaded"))]
     fn register_module<M: Module + 'static>(&mut self, module: M) -> &mut Self;
 
+    /// Returns the opcode for `Instruction::Custom`, registering `module.func` again replaces its handler
+    fn register_instruction(&mut self, instruction: CustomInstruction) -> usize;
+
+    /// Opcode of the handler registered for `module.func`
+    fn find_instruction(&self, module: &str, func: &str) -> Option<usize>;
+
     fn with_options(&mut self, options: VMOptions) -> &mut Self;
 
+    /// Source line for the instructions added to the current scope after this call
+    fn set_line(&mut self, line: usize) -> &mut Self;
+
     generate_bin_op_methods! {
         add_add_instruction => Add,
         add_bitand_instruction => BitAnd,
@@ -138,6 +187,60 @@ pub trait RigzBuilder: Debug + Default {
         self.add_instruction(Instruction::ForMap { scope })
     }
 
+    #[inline]
+    fn add_for_list_start_instruction(&mut self) -> &mut Self {
+        self.add_instruction(Instruction::ForListStart)
+    }
+
+    #[inline]
+    fn add_for_map_start_instruction(&mut self) -> &mut Self {
+        self.add_instruction(Instruction::ForMapStart)
+    }
+
+    #[inline]
+    fn add_reduce_start_instruction(&mut self) -> &mut Self {
+        self.add_instruction(Instruction::ReduceStart)
+    }
+
+    #[inline]
+    fn add_for_next_instruction(
+        &mut self,
+        vars: Vec<String>,
+        acc: Option<String>,
+        end: usize,
+    ) -> &mut Self {
+        self.add_instruction(Instruction::ForNext { vars, acc, end })
+    }
+
+    #[inline]
+    fn add_for_push_instruction(&mut self, start: usize) -> &mut Self {
+        self.add_instruction(Instruction::ForPush(start))
+    }
+
+    #[inline]
+    fn add_reduce_push_instruction(&mut self, start: usize) -> &mut Self {
+        self.add_instruction(Instruction::ReducePush(start))
+    }
+
+    #[inline]
+    fn add_custom_instruction(&mut self, opcode: usize, args: usize) -> &mut Self {
+        self.add_instruction(Instruction::Custom { opcode, args })
+    }
+
+    #[inline]
+    fn add_override_module_instruction(
+        &mut self,
+        module: String,
+        func: String,
+        scope: usize,
+    ) -> &mut Self {
+        self.add_instruction(Instruction::OverrideModule {
+            module,
+            func,
+            scope,
+        })
+    }
+
     #[inline]
     fn add_unary_instruction(&mut self, op: UnaryOperation) -> &mut Self {
         self.add_instruction(Instruction::Unary(op))
@@ -243,6 +346,11 @@ pub trait RigzBuilder: Debug + Default {
         self.add_instruction(Instruction::Call(scope))
     }
 
+    #[inline]
+    fn add_call_once_instruction(&mut self, scope: usize) -> &mut Self {
+        self.add_instruction(Instruction::CallOnce(scope))
+    }
+
     #[inline]
     fn add_call_memo_instruction(&mut self, scope: usize) -> &mut Self {
         self.add_instruction(Instruction::CallMemo(scope))
@@ -292,38 +400,38 @@ pub trait RigzBuilder: Debug + Default {
     }
 
     #[inline]
-    fn add_get_variable_reference_instruction(&mut self, name: String) -> &mut Self {
-        self.add_instruction(Instruction::GetVariableReference(name))
+    fn add_get_variable_reference_instruction(&mut self, name: impl Into<Interned>) -> &mut Self {
+        self.add_instruction(Instruction::GetVariableReference(name.into()))
     }
 
     #[inline]
-    fn add_get_variable_instruction(&mut self, name: String) -> &mut Self {
-        self.add_instruction(Instruction::GetVariable(name))
+    fn add_get_variable_instruction(&mut self, name: impl Into<Interned>) -> &mut Self {
+        self.add_instruction(Instruction::GetVariable(name.into()))
     }
 
     #[inline]
-    fn add_get_mutable_variable_instruction(&mut self, name: String) -> &mut Self {
-        self.add_instruction(Instruction::GetMutableVariable(name))
+    fn add_get_mutable_variable_instruction(&mut self, name: impl Into<Interned>) -> &mut Self {
+        self.add_instruction(Instruction::GetMutableVariable(name.into()))
     }
 
     #[inline]
     fn add_get_self_instruction(&mut self) -> &mut Self {
-        self.add_instruction(Instruction::GetVariable("self".to_string()))
+        self.add_instruction(Instruction::GetVariable("self".into()))
     }
 
     #[inline]
     fn add_get_self_mut_instruction(&mut self) -> &mut Self {
-        self.add_instruction(Instruction::GetMutableVariable("self".to_string()))
+        self.add_instruction(Instruction::GetMutableVariable("self".into()))
     }
 
     #[inline]
-    fn add_load_let_instruction(&mut self, name: String) -> &mut Self {
-        self.add_instruction(Instruction::LoadLet(name))
+    fn add_load_let_instruction(&mut self, name: impl Into<Interned>) -> &mut Self {
+        self.add_instruction(Instruction::LoadLet(name.into()))
     }
 
     #[inline]
-    fn add_load_mut_instruction(&mut self, name: String) -> &mut Self {
-        self.add_instruction(Instruction::LoadMut(name))
+    fn add_load_mut_instruction(&mut self, name: impl Into<Interned>) -> &mut Self {
+        self.add_instruction(Instruction::LoadMut(name.into()))
     }
 
     #[inline]
@@ -371,6 +479,16 @@ pub trait RigzBuilder: Debug + Default {
         self.add_instruction(Instruction::Receive(args))
     }
 
+    #[inline]
+    fn add_select_instruction(&mut self, sources: usize, timeout: bool) -> &mut Self {
+        self.add_instruction(Instruction::Select(sources, timeout))
+    }
+
+    #[inline]
+    fn add_parallel_instruction(&mut self, op: Parallel) -> &mut Self {
+        self.add_instruction(Instruction::Parallel(op))
+    }
+
     #[inline]
     fn add_spawn_instruction(&mut self, scope_id: usize, timeout: bool) -> &mut Self {
         self.add_instruction(Instruction::Spawn(scope_id, timeout))
@@ -413,13 +531,19 @@ macro_rules! generate_builder {
             next
         }
 
+        #[inline]
+        fn set_object_hooks(&mut self, object: String, hooks: ObjectHookScopes) -> &mut Self {
+            self.object_hooks.insert(object, hooks);
+            self
+        }
+
         #[inline]
         fn convert_to_lazy_scope(&mut self, scope_id: usize, variable: String) -> &mut Self {
             let scope = &mut self.scopes[scope_id];
             let last = scope.instructions.len() - 1;
             scope
                 .instructions
-                .insert(last, Instruction::PersistScope(variable));
+                .insert(last, Instruction::PersistScope(variable.into()));
             self
         }
 
@@ -448,29 +572,67 @@ macro_rules! generate_builder {
         #[inline]
         #[cfg(feature = "threaded")]
         fn register_module<M: Module + Send + Sync + 'static>(&mut self, module: M) -> &mut Self {
-            self.modules.insert(M::name(), std::sync::Arc::new(module));
+            self.modules
+                .insert(M::name(), alloc::sync::Arc::new(module));
             self
         }
 
         #[inline]
         #[cfg(not(feature = "threaded"))]
         fn register_module<M: Module + 'static>(&mut self, module: M) -> &mut Self {
-            self.modules.insert(M::name(), std::rc::Rc::new(module));
+            self.modules.insert(M::name(), alloc::rc::Rc::new(module));
             self
         }
 
+        fn register_instruction(&mut self, instruction: CustomInstruction) -> usize {
+            match self.find_instruction(&instruction.module, &instruction.func) {
+                Some(opcode) => {
+                    self.custom_instructions[opcode] = instruction;
+                    opcode
+                }
+                None => {
+                    self.custom_instructions.push(instruction);
+                    self.custom_instructions.len() - 1
+                }
+            }
+        }
+
+        #[inline]
+        fn find_instruction(&self, module: &str, func: &str) -> Option<usize> {
+            self.custom_instructions
+                .iter()
+                .position(|c| c.module == module && c.func == func)
+        }
+
         #[inline]
         fn with_options(&mut self, options: VMOptions) -> &mut Self {
             self.options = options;
             self
         }
 
+        #[inline]
+        fn set_line(&mut self, line: usize) -> &mut Self {
+            self.scopes[self.sp].set_line(line);
+            self
+        }
+
         #[inline]
         fn add_instruction(&mut self, instruction: Instruction) -> &mut Self {
             self.scopes[self.sp].instructions.push(instruction);
             self
         }
 
+        #[inline]
+        fn next_instruction_index(&self) -> usize {
+            self.scopes[self.sp].instructions.len()
+        }
+
+        #[inline]
+        fn set_instruction(&mut self, index: usize, instruction: Instruction) -> &mut Self {
+            self.scopes[self.sp].instructions[index] = instruction;
+            self
+        }
+
         #[inline]
         fn add_constant(&mut self, value: ObjectValue) -> usize {
             let index = self.constants.len();
@@ -488,10 +650,12 @@ impl RigzBuilder for VMBuilder {
         VM {
             scopes: self.scopes,
             modules: self.modules,
-            dependencies: self.dependencies.into(),
+            dependencies: self.dependencies,
             options: self.options,
             lifecycles: self.lifecycles,
             constants: self.constants,
+            custom_instructions: self.custom_instructions,
+            object_hooks: self.object_hooks,
             ..Default::default()
         }
     }
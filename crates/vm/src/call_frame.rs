@@ -1,9 +1,10 @@
+use crate::prelude::*;
+use alloc::vec::IntoIter;
+use core::cell::RefCell;
+use core::fmt::Display;
+use core::ops::Index;
 use log_derive::{logfn, logfn_inputs};
-use rigz_core::{IndexMap, IndexMapEntry, Snapshot, StackValue, VMError};
-use std::cell::RefCell;
-use std::fmt::Display;
-use std::ops::Index;
-use std::vec::IntoIter;
+use rigz_core::{IndexMap, IndexMapEntry, Interned, Snapshot, StackValue, VMError};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Variable {
@@ -88,7 +89,7 @@ impl Frames {
 
     #[inline]
     #[logfn_inputs(Trace, fmt = "load_let(frames={:#?} name={}, value={:?})")]
-    pub fn load_let(&self, name: String, value: StackValue) -> Result<(), VMError> {
+    pub fn load_let(&self, name: Interned, value: StackValue) -> Result<(), VMError> {
         match self.current.borrow_mut().variables.entry(name) {
             IndexMapEntry::Occupied(v) => {
                 return Err(VMError::UnsupportedOperation(format!(
@@ -103,6 +104,23 @@ impl Frames {
         Ok(())
     }
 
+    /// Loop variables are rebound every iteration, unlike `load_let`
+    #[inline]
+    pub fn set_loop_variable(&self, name: &str, value: StackValue) {
+        let mut current = self.current.borrow_mut();
+        match current.variables.get_mut(name) {
+            Some(v) => *v = Variable::Let(value),
+            None => {
+                current.variables.insert(name.into(), Variable::Let(value));
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove_variable(&self, name: &str) {
+        self.current.borrow_mut().variables.shift_remove(name);
+    }
+
     #[logfn(Trace)]
     #[logfn_inputs(Trace, fmt = "get_variable(frames={:#p} name={})")]
     pub fn get_variable(&self, name: &str) -> Option<StackValue> {
@@ -117,7 +135,7 @@ impl Frames {
 
     #[inline]
     #[logfn_inputs(Trace, fmt = "load_mut(frames={:#?} name={}, value={:?})")]
-    pub fn load_mut(&self, name: String, value: StackValue) -> Result<(), VMError> {
+    pub fn load_mut(&self, name: Interned, value: StackValue) -> Result<(), VMError> {
         match self.current.borrow_mut().variables.entry(name) {
             IndexMapEntry::Occupied(mut var) => match var.get() {
                 Variable::Let(_) => {
@@ -152,8 +170,10 @@ impl Default for Frames {
 pub struct CallFrame {
     pub scope_id: usize,
     pub pc: usize,
-    pub variables: IndexMap<String, Variable>,
+    pub variables: IndexMap<Interned, Variable>,
     pub parent: Option<usize>,
+    /// Stack depth once the arguments are loaded, unused statement values above it are dropped on return
+    pub stack: usize,
 }
 
 impl Snapshot for CallFrame {
@@ -162,6 +182,7 @@ impl Snapshot for CallFrame {
         res.extend(self.pc.as_bytes());
         res.extend(self.variables.as_bytes());
         res.extend(self.parent.as_bytes());
+        res.extend(self.stack.as_bytes());
         res
     }
 
@@ -170,11 +191,13 @@ impl Snapshot for CallFrame {
         let pc = Snapshot::from_bytes(bytes, location)?;
         let variables = Snapshot::from_bytes(bytes, location)?;
         let parent = Snapshot::from_bytes(bytes, location)?;
+        let stack = Snapshot::from_bytes(bytes, location)?;
         Ok(CallFrame {
             scope_id,
             pc,
             variables,
             parent,
+            stack,
         })
     }
 }
@@ -0,0 +1,65 @@
+use crate::prelude::*;
+use rigz_core::InstructionHandler;
+
+/// Handler for `Instruction::Custom`, the opcode is its index in the VM's table.
+/// Handlers aren't part of snapshots or bytecode, they must be registered in the same order before loading either
+#[derive(Clone, Debug)]
+pub struct CustomInstruction {
+    pub module: String,
+    pub func: String,
+    pub handler: InstructionHandler,
+    /// false for modules that can't be called while `VMOptions::deterministic` is set
+    pub deterministic: bool,
+}
+
+impl CustomInstruction {
+    pub fn new(
+        module: impl Into<String>,
+        func: impl Into<String>,
+        handler: InstructionHandler,
+    ) -> Self {
+        Self {
+            module: module.into(),
+            func: func.into(),
+            handler,
+            deterministic: true,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod custom_tests {
+    use crate::{CustomInstruction, RigzBuilder, VMBuilder};
+    use rigz_core::{AsPrimitive, ObjectValue, RigzArgs, VMError};
+    use wasm_bindgen_test::*;
+
+    fn double(args: RigzArgs) -> Result<ObjectValue, VMError> {
+        let [v] = args.take()?;
+        let n = v.borrow().to_number()?;
+        Ok((&n + &n).into())
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn runs_registered_handler() {
+        let mut builder = VMBuilder::new();
+        let opcode = builder.register_instruction(CustomInstruction::new("Test", "double", double));
+        assert_eq!(builder.find_instruction("Test", "double"), Some(opcode));
+        builder
+            .add_load_instruction(21.into())
+            .add_custom_instruction(opcode, 1)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        assert_eq!(vm.run(), ObjectValue::from(42));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unregistered_opcode() {
+        let mut builder = VMBuilder::new();
+        builder.add_custom_instruction(3, 0).add_halt_instruction();
+        let mut vm = builder.build();
+        assert_eq!(
+            vm.run(),
+            VMError::RuntimeError("Custom instruction 3 is not registered".to_string()).into()
+        );
+    }
+}
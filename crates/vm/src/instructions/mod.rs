@@ -1,13 +1,17 @@
+mod custom;
 mod runner;
 
+use crate::prelude::*;
+use alloc::sync::Arc;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
+pub use custom::CustomInstruction;
 use log::Level;
 use rigz_core::{
-    BinaryOperation, ObjectValue, RigzType, Snapshot, StackValue, UnaryOperation, VMError,
+    BinaryOperation, Interned, ObjectValue, RigzType, Snapshot, StackValue, Thunk, UnaryOperation,
+    VMError,
 };
-pub use runner::{CallType, ResolvedModule, Runner};
-use std::fmt::Display;
-use std::sync::Arc;
-use std::vec::IntoIter;
+pub use runner::{eval_binary_operation, eval_unary, CallType, ResolvedModule, Runner};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum VMCallSite {
@@ -21,19 +25,19 @@ impl Snapshot for VMCallSite {
         match self {
             VMCallSite::Scope(i) => {
                 let mut res = vec![0];
-                res.extend(i.as_bytes());
+                res.extend(Snapshot::as_bytes(i));
                 res
             }
             VMCallSite::Module { module, func } => {
                 let mut res = vec![1];
-                res.extend(module.as_bytes());
-                res.extend(func.as_bytes());
+                res.extend(Snapshot::as_bytes(module));
+                res.extend(Snapshot::as_bytes(func));
                 res
             }
             VMCallSite::VMModule { module, func } => {
                 let mut res = vec![2];
-                res.extend(module.as_bytes());
-                res.extend(func.as_bytes());
+                res.extend(Snapshot::as_bytes(module));
+                res.extend(Snapshot::as_bytes(func));
                 res
             }
         }
@@ -80,12 +84,12 @@ impl Snapshot for VMArg {
         match self {
             VMArg::Type(rt) => {
                 let mut res = vec![0];
-                res.extend(rt.as_bytes());
+                res.extend(Snapshot::as_bytes(rt));
                 res
             }
             VMArg::Value(v) => {
                 let mut res = vec![1];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
         }
@@ -114,11 +118,60 @@ impl Snapshot for VMArg {
     }
 }
 
+/// How `Instruction::Parallel` combines the results of the function, see `List.par_map`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parallel {
+    Map,
+    Filter,
+    Each,
+}
+
+impl Display for Parallel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Parallel::Map => write!(f, "par_map"),
+            Parallel::Filter => write!(f, "par_filter"),
+            Parallel::Each => write!(f, "par_each"),
+        }
+    }
+}
+
+impl Snapshot for Parallel {
+    fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            Parallel::Map => vec![0],
+            Parallel::Filter => vec![1],
+            Parallel::Each => vec![2],
+        }
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        let v = match bytes.next() {
+            Some(0) => Parallel::Map,
+            Some(1) => Parallel::Filter,
+            Some(2) => Parallel::Each,
+            Some(b) => {
+                return Err(VMError::RuntimeError(format!(
+                    "Invalid Parallel byte {b} - {location}"
+                )))
+            }
+            None => {
+                return Err(VMError::RuntimeError(format!(
+                    "Missing Parallel byte {location}"
+                )))
+            }
+        };
+        Ok(v)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LoadValue {
     ScopeId(usize),
     Value(ObjectValue),
     Constant(usize),
+    /// `lazy do ... end`, a new thunk for the scope each time the instruction runs
+    Lazy(usize),
 }
 
 impl Snapshot for LoadValue {
@@ -127,15 +180,19 @@ impl Snapshot for LoadValue {
         match self {
             LoadValue::ScopeId(s) => {
                 results.push(0);
-                results.extend(s.as_bytes());
+                results.extend(Snapshot::as_bytes(s));
             }
             LoadValue::Value(v) => {
                 results.push(1);
-                results.extend(v.as_bytes());
+                results.extend(Snapshot::as_bytes(v));
             }
             LoadValue::Constant(c) => {
                 results.push(2);
-                results.extend(c.as_bytes());
+                results.extend(Snapshot::as_bytes(c));
+            }
+            LoadValue::Lazy(s) => {
+                results.push(3);
+                results.extend(Snapshot::as_bytes(s));
             }
         }
         results
@@ -150,6 +207,7 @@ impl Snapshot for LoadValue {
             0 => LoadValue::ScopeId(Snapshot::from_bytes(bytes, location)?),
             1 => LoadValue::Value(Snapshot::from_bytes(bytes, location)?),
             2 => LoadValue::Constant(Snapshot::from_bytes(bytes, location)?),
+            3 => LoadValue::Lazy(Snapshot::from_bytes(bytes, location)?),
             _ => {
                 return Err(VMError::RuntimeError(format!(
                     "{location} Invalid LoadValue type {tv}"
@@ -174,6 +232,7 @@ impl From<LoadValue> for StackValue {
             LoadValue::ScopeId(s) => StackValue::ScopeId(s),
             LoadValue::Value(v) => StackValue::Value(v.into()),
             LoadValue::Constant(c) => StackValue::Constant(c),
+            LoadValue::Lazy(s) => StackValue::Lazy(Thunk::new(s)),
         }
     }
 }
@@ -190,6 +249,8 @@ pub enum Instruction {
     InstanceSet,
     InstanceSetMut,
     Call(usize),
+    /// Calls the scope the first time it's reached & skips it afterward, runs lazy imports before their functions
+    CallOnce(usize),
     CreateDependency(usize, usize),
     CallMemo(usize),
     CallMatchingSelf(Vec<(VMArg, Vec<VMArg>, VMCallSite)>),
@@ -212,12 +273,12 @@ pub enum Instruction {
         rigz_type: RigzType,
     },
     Ret,
-    GetVariable(String),
-    GetMutableVariable(String),
-    GetVariableReference(String),
-    LoadLet(String),
-    LoadMut(String),
-    PersistScope(String),
+    GetVariable(Interned),
+    GetMutableVariable(Interned),
+    GetVariableReference(Interned),
+    LoadLet(Interned),
+    LoadMut(Interned),
+    PersistScope(Interned),
     // requires modules, enabled by default
     /// Module instructions will clone your module, ideally modules implement Copy + Clone
     CallModule {
@@ -248,6 +309,17 @@ pub enum Instruction {
         func: String,
         args: usize,
     },
+    /// Calls to `module.func` use `scope` instead until the current call frame exits
+    OverrideModule {
+        module: String,
+        func: String,
+        scope: usize,
+    },
+    /// Runs the handler registered at `opcode` with `args` values, see `RigzBuilder::register_instruction`
+    Custom {
+        opcode: usize,
+        args: usize,
+    },
     // CallVMExtension {
     //     module: String,
     //     func: String,
@@ -259,10 +331,31 @@ pub enum Instruction {
     ForMap {
         scope: usize,
     },
+    /// Inlined comprehension, pushes the result followed by the remaining elements
+    ForListStart,
+    ForMapStart,
+    /// Inlined reduce, the initial value below the list is used as the accumulator
+    ReduceStart,
+    /// Binds the next element to `vars` (and the accumulator to `acc`), jumps to `end` once all elements are used
+    ForNext {
+        vars: Vec<String>,
+        acc: Option<String>,
+        end: usize,
+    },
+    /// Adds the body's value to the comprehension result and jumps back to the `ForNext` at this index
+    ForPush(usize),
+    /// Replaces the accumulator with the body's value and jumps back to the `ForNext` at this index
+    ReducePush(usize),
     Sleep,
     Send(usize),
     Spawn(usize, bool),
     Receive(usize),
+    /// Waits for the first of `sources` to receive a message, the timeout (ms) is above the sources when set.
+    /// Pushes the message then the index of its source, the timeout's index is `sources`
+    Select(usize, bool),
+    /// Runs the function below the list once per element on a worker pool, the function can't use variables from
+    /// the scope that created it
+    Parallel(Parallel),
     Try,
     Catch(usize),
     /// Danger Zone, use these instructions at your own risk (sorted by risk)
@@ -275,6 +368,77 @@ pub enum Instruction {
     RemoveInstruction(usize, usize),
 }
 
+impl Instruction {
+    /// Variant name, used as a label for metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::Halt => "Halt",
+            Instruction::HaltIfError => "HaltIfError",
+            Instruction::Unary(..) => "Unary",
+            Instruction::Binary(..) => "Binary",
+            Instruction::BinaryAssign(..) => "BinaryAssign",
+            Instruction::Load(..) => "Load",
+            Instruction::InstanceGet(..) => "InstanceGet",
+            Instruction::InstanceSet => "InstanceSet",
+            Instruction::InstanceSetMut => "InstanceSetMut",
+            Instruction::Call(..) => "Call",
+            Instruction::CallOnce(..) => "CallOnce",
+            Instruction::CreateDependency(..) => "CreateDependency",
+            Instruction::CallMemo(..) => "CallMemo",
+            Instruction::CallMatchingSelf(..) => "CallMatchingSelf",
+            Instruction::CallMatchingSelfMemo(..) => "CallMatchingSelfMemo",
+            Instruction::CallMatching(..) => "CallMatching",
+            Instruction::CallMatchingMemo(..) => "CallMatchingMemo",
+            Instruction::CreateObject(..) => "CreateObject",
+            Instruction::Log(..) => "Log",
+            Instruction::Puts(..) => "Puts",
+            Instruction::CallEq(..) => "CallEq",
+            Instruction::CallNeq(..) => "CallNeq",
+            Instruction::IfElse { .. } => "IfElse",
+            Instruction::If(..) => "If",
+            Instruction::Unless(..) => "Unless",
+            Instruction::Cast { .. } => "Cast",
+            Instruction::Ret => "Ret",
+            Instruction::GetVariable(..) => "GetVariable",
+            Instruction::GetMutableVariable(..) => "GetMutableVariable",
+            Instruction::GetVariableReference(..) => "GetVariableReference",
+            Instruction::LoadLet(..) => "LoadLet",
+            Instruction::LoadMut(..) => "LoadMut",
+            Instruction::PersistScope(..) => "PersistScope",
+            Instruction::CallModule { .. } => "CallModule",
+            Instruction::CallExtension { .. } => "CallExtension",
+            Instruction::CallMutableExtension { .. } => "CallMutableExtension",
+            Instruction::CallObject { .. } => "CallObject",
+            Instruction::CallObjectExtension { .. } => "CallObjectExtension",
+            Instruction::CallMutableObjectExtension { .. } => "CallMutableObjectExtension",
+            Instruction::ForList { .. } => "ForList",
+            Instruction::ForMap { .. } => "ForMap",
+            Instruction::ForListStart => "ForListStart",
+            Instruction::ForMapStart => "ForMapStart",
+            Instruction::ReduceStart => "ReduceStart",
+            Instruction::ForNext { .. } => "ForNext",
+            Instruction::ForPush(..) => "ForPush",
+            Instruction::ReducePush(..) => "ReducePush",
+            Instruction::OverrideModule { .. } => "OverrideModule",
+            Instruction::Custom { .. } => "Custom",
+            Instruction::Sleep => "Sleep",
+            Instruction::Send(..) => "Send",
+            Instruction::Spawn(..) => "Spawn",
+            Instruction::Receive(..) => "Receive",
+            Instruction::Select(..) => "Select",
+            Instruction::Parallel(..) => "Parallel",
+            Instruction::Try => "Try",
+            Instruction::Catch(..) => "Catch",
+            Instruction::Pop(..) => "Pop",
+            Instruction::Goto(..) => "Goto",
+            Instruction::AddInstruction(..) => "AddInstruction",
+            Instruction::InsertAtInstruction(..) => "InsertAtInstruction",
+            Instruction::UpdateInstruction(..) => "UpdateInstruction",
+            Instruction::RemoveInstruction(..) => "RemoveInstruction",
+        }
+    }
+}
+
 impl Snapshot for Instruction {
     fn as_bytes(&self) -> Vec<u8> {
         match self {
@@ -282,81 +446,81 @@ impl Snapshot for Instruction {
             Instruction::HaltIfError => vec![1],
             Instruction::Unary(a) => {
                 let mut res = vec![2];
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::Binary(a) => {
                 let mut res = vec![3];
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::BinaryAssign(a) => {
                 let mut res = vec![4];
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::Load(v) => {
                 let mut res = vec![5];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::InstanceGet(v) => {
                 let mut res = vec![6];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::InstanceSet => vec![7],
             Instruction::InstanceSetMut => vec![8],
             Instruction::Call(s) => {
                 let mut res = vec![9];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             Instruction::CallMemo(s) => {
                 let mut res = vec![10];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             Instruction::CallMatchingSelf(s) => {
                 let mut res = vec![11];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             Instruction::CallMatchingSelfMemo(s) => {
                 let mut res = vec![12];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             Instruction::CallMatching(s) => {
                 let mut res = vec![13];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             Instruction::CallMatchingMemo(s) => {
                 let mut res = vec![14];
-                res.extend(s.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
             Instruction::Log(l, t, a) => {
                 let mut res = vec![15];
-                res.extend(l.as_bytes());
-                res.extend(t.as_bytes());
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(l));
+                res.extend(Snapshot::as_bytes(t));
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::Puts(a) => {
                 let mut res = vec![16];
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::CallEq(a) => {
                 let mut res = vec![17];
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::CallNeq(a) => {
                 let mut res = vec![18];
-                res.extend(a.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
                 res
             }
             Instruction::IfElse {
@@ -364,182 +528,235 @@ impl Snapshot for Instruction {
                 else_scope,
             } => {
                 let mut res = vec![19];
-                res.extend(if_scope.as_bytes());
-                res.extend(else_scope.as_bytes());
+                res.extend(Snapshot::as_bytes(if_scope));
+                res.extend(Snapshot::as_bytes(else_scope));
                 res
             }
             Instruction::If(i) => {
                 let mut res = vec![20];
-                res.extend(i.as_bytes());
+                res.extend(Snapshot::as_bytes(i));
                 res
             }
             Instruction::Unless(u) => {
                 let mut res = vec![21];
-                res.extend(u.as_bytes());
+                res.extend(Snapshot::as_bytes(u));
                 res
             }
             Instruction::Cast { rigz_type } => {
                 let mut res = vec![22];
-                res.extend(rigz_type.as_bytes());
+                res.extend(Snapshot::as_bytes(rigz_type));
                 res
             }
             Instruction::Ret => vec![23],
             Instruction::GetVariable(v) => {
                 let mut res = vec![24];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::GetMutableVariable(v) => {
                 let mut res = vec![25];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::GetVariableReference(v) => {
                 let mut res = vec![26];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::LoadLet(v) => {
                 let mut res = vec![27];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::LoadMut(v) => {
                 let mut res = vec![28];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::PersistScope(v) => {
                 let mut res = vec![29];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::CallModule { module, func, args } => {
                 let mut res = vec![30];
                 res.extend(Snapshot::as_bytes(module));
                 res.extend(Snapshot::as_bytes(func));
-                res.extend(args.as_bytes());
+                res.extend(Snapshot::as_bytes(args));
                 res
             }
             Instruction::CallExtension { module, func, args } => {
                 let mut res = vec![31];
                 res.extend(Snapshot::as_bytes(module));
                 res.extend(Snapshot::as_bytes(func));
-                res.extend(args.as_bytes());
+                res.extend(Snapshot::as_bytes(args));
                 res
             }
             Instruction::CallMutableExtension { module, func, args } => {
                 let mut res = vec![32];
                 res.extend(Snapshot::as_bytes(module));
                 res.extend(Snapshot::as_bytes(func));
-                res.extend(args.as_bytes());
+                res.extend(Snapshot::as_bytes(args));
                 res
             }
             // Instruction::CallVMExtension { module, func, args } => {
             //     let mut res = vec![33];
             //     res.extend(Snapshot::as_bytes(module));
             //     res.extend(Snapshot::as_bytes(func));
-            //     res.extend(args.as_bytes());
+            //     res.extend(Snapshot::as_bytes(args));
             //     res
             // }
             Instruction::ForList { scope } => {
                 let mut res = vec![34];
-                res.extend(scope.as_bytes());
+                res.extend(Snapshot::as_bytes(scope));
                 res
             }
             Instruction::ForMap { scope } => {
                 let mut res = vec![35];
-                res.extend(scope.as_bytes());
+                res.extend(Snapshot::as_bytes(scope));
                 res
             }
             Instruction::Sleep => vec![36],
             Instruction::Send(v) => {
                 let mut res = vec![37];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::Spawn(a, b) => {
                 let mut res = vec![38];
-                res.extend(a.as_bytes());
-                res.extend(b.as_bytes());
+                res.extend(Snapshot::as_bytes(a));
+                res.extend(Snapshot::as_bytes(b));
                 res
             }
             Instruction::Receive(v) => {
                 let mut res = vec![39];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::Pop(v) => {
                 let mut res = vec![40];
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::Goto(s, v) => {
                 let mut res = vec![41];
-                res.extend(s.as_bytes());
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::AddInstruction(s, i) => {
                 let mut res = vec![42];
-                res.extend(s.as_bytes());
-                res.extend(i.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
+                res.extend(Snapshot::as_bytes(i));
                 res
             }
             Instruction::InsertAtInstruction(s, v, i) => {
                 let mut res = vec![43];
-                res.extend(s.as_bytes());
-                res.extend(v.as_bytes());
-                res.extend(i.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
+                res.extend(Snapshot::as_bytes(v));
+                res.extend(Snapshot::as_bytes(i));
                 res
             }
             Instruction::UpdateInstruction(s, v, i) => {
                 let mut res = vec![44];
-                res.extend(s.as_bytes());
-                res.extend(v.as_bytes());
-                res.extend(i.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
+                res.extend(Snapshot::as_bytes(v));
+                res.extend(Snapshot::as_bytes(i));
                 res
             }
             Instruction::RemoveInstruction(s, v) => {
                 let mut res = vec![45];
-                res.extend(s.as_bytes());
-                res.extend(v.as_bytes());
+                res.extend(Snapshot::as_bytes(s));
+                res.extend(Snapshot::as_bytes(v));
                 res
             }
             Instruction::CreateObject(o) => {
                 let mut res = vec![46];
-                res.extend(o.as_bytes());
+                res.extend(Snapshot::as_bytes(o));
                 res
             }
             Instruction::CreateDependency(args, dep) => {
                 let mut res = vec![47];
-                res.extend(args.as_bytes());
-                res.extend(dep.as_bytes());
+                res.extend(Snapshot::as_bytes(args));
+                res.extend(Snapshot::as_bytes(dep));
                 res
             }
             Instruction::CallObject { dep, func, args } => {
                 let mut res = vec![48];
-                res.extend(dep.as_bytes());
-                res.extend(func.as_bytes());
-                res.extend(args.as_bytes());
+                res.extend(Snapshot::as_bytes(dep));
+                res.extend(Snapshot::as_bytes(func));
+                res.extend(Snapshot::as_bytes(args));
                 res
             }
             Instruction::CallObjectExtension { func, args } => {
                 let mut res = vec![49];
-                res.extend(func.as_bytes());
-                res.extend(args.as_bytes());
+                res.extend(Snapshot::as_bytes(func));
+                res.extend(Snapshot::as_bytes(args));
                 res
             }
             Instruction::CallMutableObjectExtension { func, args } => {
                 let mut res = vec![50];
-                res.extend(func.as_bytes());
-                res.extend(args.as_bytes());
+                res.extend(Snapshot::as_bytes(func));
+                res.extend(Snapshot::as_bytes(args));
                 res
             }
             Instruction::Try => vec![51],
             Instruction::Catch(scope) => {
                 let mut res = vec![52];
-                res.extend(scope.as_bytes());
+                res.extend(Snapshot::as_bytes(scope));
+                res
+            }
+            Instruction::ForListStart => vec![53],
+            Instruction::ForMapStart => vec![54],
+            Instruction::ReduceStart => vec![55],
+            Instruction::ForNext { vars, acc, end } => {
+                let mut res = vec![56];
+                res.extend(Snapshot::as_bytes(vars));
+                res.extend(Snapshot::as_bytes(acc));
+                res.extend(Snapshot::as_bytes(end));
+                res
+            }
+            Instruction::ForPush(start) => {
+                let mut res = vec![57];
+                res.extend(Snapshot::as_bytes(start));
+                res
+            }
+            Instruction::ReducePush(start) => {
+                let mut res = vec![58];
+                res.extend(Snapshot::as_bytes(start));
+                res
+            }
+            Instruction::OverrideModule {
+                module,
+                func,
+                scope,
+            } => {
+                let mut res = vec![59];
+                res.extend(Snapshot::as_bytes(module));
+                res.extend(Snapshot::as_bytes(func));
+                res.extend(Snapshot::as_bytes(scope));
+                res
+            }
+            Instruction::Select(sources, timeout) => {
+                let mut res = vec![60];
+                res.extend(Snapshot::as_bytes(sources));
+                res.extend(Snapshot::as_bytes(timeout));
+                res
+            }
+            Instruction::Parallel(op) => {
+                let mut res = vec![61];
+                res.extend(Snapshot::as_bytes(op));
+                res
+            }
+            Instruction::Custom { opcode, args } => {
+                let mut res = vec![62];
+                res.extend(Snapshot::as_bytes(opcode));
+                res.extend(Snapshot::as_bytes(args));
+                res
+            }
+            Instruction::CallOnce(s) => {
+                let mut res = vec![63];
+                res.extend(Snapshot::as_bytes(s));
                 res
             }
         }
@@ -670,6 +887,31 @@ impl Snapshot for Instruction {
             },
             51 => Instruction::Try,
             52 => Instruction::Catch(Snapshot::from_bytes(bytes, location)?),
+            53 => Instruction::ForListStart,
+            54 => Instruction::ForMapStart,
+            55 => Instruction::ReduceStart,
+            56 => Instruction::ForNext {
+                vars: Snapshot::from_bytes(bytes, location)?,
+                acc: Snapshot::from_bytes(bytes, location)?,
+                end: Snapshot::from_bytes(bytes, location)?,
+            },
+            57 => Instruction::ForPush(Snapshot::from_bytes(bytes, location)?),
+            58 => Instruction::ReducePush(Snapshot::from_bytes(bytes, location)?),
+            59 => Instruction::OverrideModule {
+                module: Snapshot::from_bytes(bytes, location)?,
+                func: Snapshot::from_bytes(bytes, location)?,
+                scope: Snapshot::from_bytes(bytes, location)?,
+            },
+            60 => Instruction::Select(
+                Snapshot::from_bytes(bytes, location)?,
+                Snapshot::from_bytes(bytes, location)?,
+            ),
+            61 => Instruction::Parallel(Snapshot::from_bytes(bytes, location)?),
+            62 => Instruction::Custom {
+                opcode: Snapshot::from_bytes(bytes, location)?,
+                args: Snapshot::from_bytes(bytes, location)?,
+            },
+            63 => Instruction::CallOnce(Snapshot::from_bytes(bytes, location)?),
             b => {
                 return Err(VMError::RuntimeError(format!(
                     "Illegal instruction byte {b} {location}"
@@ -1,15 +1,15 @@
-use crate::{err, errln, out, outln, CallFrame, Instruction, Scope, VMOptions, VMState};
+use crate::prelude::*;
+use crate::{err, errln, out, outln, CallFrame, Instruction, Parallel, Scope, VMOptions, VMState};
+use alloc::string::ToString;
+use core::fmt::Display;
+use core::ops::{Deref, DerefMut};
+use core::time::Duration;
 use log::log;
 use rigz_core::{
-    AsPrimitive, BinaryOperation, IndexMap, Logical, Module, ObjectValue, PrimitiveValue,
-    Reference, ResolveValue, Reverse, RigzArgs, RigzObject, StackValue, UnaryOperation, VMError,
+    AsPrimitive, BinaryOperation, IndexMap, Interned, Logical, Module, Object, ObjectValue,
+    OverflowPolicy, PrimitiveValue, Reference, ResolveValue, Reverse, RigzArgs, RigzObject, Shared,
+    StackValue, TraceFrame, UnaryOperation, VMError, ValueRange,
 };
-use std::cell::{Ref, RefCell};
-use std::fmt::Display;
-use std::ops::{Deref, DerefMut};
-use std::rc::Rc;
-use std::string::ToString;
-use std::time::Duration;
 
 #[macro_export]
 macro_rules! runner_common {
@@ -21,6 +21,7 @@ macro_rules! runner_common {
 
         #[inline]
         fn store_value(&mut self, value: StackValue) {
+            self.trace_value(&value);
             self.stack.store_value(value)
         }
 
@@ -48,7 +49,12 @@ macro_rules! runner_common {
                     let m = m.value().clone();
                     #[cfg(not(feature = "threaded"))]
                     let m = m.clone();
-                    return Some(m);
+                    if !self.options().deterministic || m.deterministic() {
+                        return Some(m);
+                    }
+                    VMError::UnsupportedOperation(format!(
+                        "{module} is nondeterministic, it can't be used with VMOptions::deterministic"
+                    ))
                 }
             };
             self.store_value(e.into());
@@ -79,7 +85,7 @@ macro_rules! runner_common {
         }
 
         #[inline]
-        fn persist_scope(&mut self, var: String) -> Option<VMError> {
+        fn persist_scope(&mut self, var: Interned) -> Option<VMError> {
             let next = self.next_resolved_value("persist_scope");
             self.store_value(next.clone().into());
             let current = self.frames.current.borrow();
@@ -94,7 +100,7 @@ macro_rules! runner_common {
                 Some(None) => self.frames.current.borrow_mut(),
                 Some(Some(id)) => self.frames.frames[id].borrow_mut(),
             };
-            let old = match frame.variables.get_mut(&var).unwrap() {
+            let old = match frame.variables.get_mut(var.as_str()).unwrap() {
                 Variable::Let(v) => v,
                 Variable::Mut(v) => v,
             };
@@ -103,17 +109,32 @@ macro_rules! runner_common {
         }
 
         #[inline]
-        fn load_mut(&mut self, name: String) -> Result<(), VMError> {
+        fn load_mut(&mut self, name: Interned) -> Result<(), VMError> {
             let v = self.next_value(format!("load_mut - {name}"));
             self.frames.load_mut(name, v)
         }
 
         #[inline]
-        fn load_let(&mut self, name: String) -> Result<(), VMError> {
+        fn load_let(&mut self, name: Interned) -> Result<(), VMError> {
             let v = self.next_value(format!("load_let - {name}"));
             self.frames.load_let(name, v)
         }
 
+        #[inline]
+        fn set_loop_variable(&mut self, name: &str, value: StackValue) {
+            self.frames.set_loop_variable(name, value)
+        }
+
+        #[inline]
+        fn remove_variable(&mut self, name: &str) {
+            self.frames.remove_variable(name)
+        }
+
+        #[inline]
+        fn jump(&mut self, pc: usize) {
+            self.frames.current.borrow_mut().pc = pc;
+        }
+
         #[inline]
         fn get_variable(&mut self, name: &str) {
             let r = self.frames.get_variable(name);
@@ -167,6 +188,18 @@ macro_rules! runner_common {
             module.call_extension(this, func, args)
         }
 
+        #[inline]
+        fn call_resolved_extension(
+            &mut self,
+            module: ResolvedModule,
+            overload: usize,
+            args: usize,
+        ) -> Result<ObjectValue, VMError> {
+            let this = self.next_resolved_value("call_extension");
+            let args = self.resolve_args(args).into();
+            module.call_resolved_extension(overload, this, args)
+        }
+
         #[inline]
         fn call_mutable_extension(
             &mut self,
@@ -181,10 +214,72 @@ macro_rules! runner_common {
     };
 }
 
+/// Values of `for v in value`, streams are read one value at a time instead of being converted to a list
+enum ForValues {
+    List(alloc::vec::IntoIter<ObjectValue>),
+    Stream(Box<dyn Object>),
+}
+
+impl ForValues {
+    fn new(value: &ObjectValue) -> Result<Self, VMError> {
+        match value {
+            ObjectValue::Object(o) if o.is_stream() => Ok(ForValues::Stream(o.clone())),
+            v => Ok(ForValues::List(v.iter_values()?.into_iter())),
+        }
+    }
+}
+
+impl Iterator for ForValues {
+    type Item = Result<ObjectValue, VMError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ForValues::List(l) => l.next().map(Ok),
+            ForValues::Stream(o) => o.next_value().transpose(),
+        }
+    }
+}
+
+/// Comprehension results must be a (key, value) tuple or none
+fn insert_for_map_entry(result: &mut IndexMap<ObjectValue, ObjectValue>, value: ObjectValue) {
+    match value {
+        ObjectValue::Primitive(PrimitiveValue::None) => {}
+        ObjectValue::Tuple(mut t) if t.len() >= 2 => {
+            // todo this should be == 2 but same tuple is reused appending to front
+            let v = t.remove(1);
+            let k = t.remove(0);
+            if k != ObjectValue::default() && v != ObjectValue::default() {
+                result.insert(k.map_key(), v);
+            }
+        }
+        // todo should a single value be both the key & value?
+        _ => {
+            let e: ObjectValue =
+                VMError::UnsupportedOperation(format!("Invalid args in for-map {value}")).into();
+            result.insert(e.clone(), e);
+        }
+    }
+}
+
 #[inline]
-pub fn eval_unary(unary_operation: UnaryOperation, val: &ObjectValue) -> ObjectValue {
+pub fn eval_unary(
+    unary_operation: UnaryOperation,
+    val: &ObjectValue,
+    overflow: OverflowPolicy,
+) -> ObjectValue {
     match unary_operation {
-        UnaryOperation::Neg => -val,
+        UnaryOperation::Neg => match val {
+            ObjectValue::Primitive(PrimitiveValue::Number(n)) => match n.negate(overflow) {
+                Ok(n) => n.into(),
+                // -i64::MIN is promoted to a BigInt like checked binary operations
+                #[cfg(feature = "bigint")]
+                Err(e) if overflow == OverflowPolicy::Checked => rigz_core::BigInt::from(0)
+                    .binary_operation(BinaryOperation::Sub, val, false)
+                    .unwrap_or_else(|| e.into()),
+                Err(e) => e.into(),
+            },
+            v => -v,
+        },
         UnaryOperation::Not => !val,
         UnaryOperation::PrintLn => {
             outln!("{}", val);
@@ -206,19 +301,42 @@ pub fn eval_unary(unary_operation: UnaryOperation, val: &ObjectValue) -> ObjectV
     }
 }
 
+/// `overflow` only applies to Int arithmetic, see [`rigz_core::Number::int_operation`]. With the `bigint` feature
+/// checked overflow returns a `BigInt` instead of an error
 #[inline]
 pub fn eval_binary_operation(
     binary_operation: BinaryOperation,
     lhs: &ObjectValue,
     rhs: &ObjectValue,
+    overflow: OverflowPolicy,
 ) -> ObjectValue {
+    if let (
+        ObjectValue::Primitive(PrimitiveValue::Number(a)),
+        ObjectValue::Primitive(PrimitiveValue::Number(b)),
+    ) = (lhs, rhs)
+    {
+        if let Some(result) = a.int_operation(binary_operation, *b, overflow) {
+            return match result {
+                Ok(n) => n.into(),
+                // checked overflow is promoted to a BigInt instead of failing
+                #[cfg(feature = "bigint")]
+                Err(e) if overflow == OverflowPolicy::Checked => {
+                    rigz_core::BigInt::from(a.to_int())
+                        .binary_operation(binary_operation, rhs, false)
+                        .unwrap_or_else(|| e.into())
+                }
+                Err(e) => e.into(),
+            };
+        }
+    }
+
     match binary_operation {
         BinaryOperation::Add => lhs + rhs,
         BinaryOperation::Sub => lhs - rhs,
         BinaryOperation::Shr => lhs >> rhs,
         BinaryOperation::Shl => lhs << rhs,
-        BinaryOperation::Eq => (lhs == rhs).into(),
-        BinaryOperation::Neq => (lhs != rhs).into(),
+        BinaryOperation::Eq => lhs.loose_eq(rhs).into(),
+        BinaryOperation::Neq => (!lhs.loose_eq(rhs)).into(),
         BinaryOperation::Mul => lhs * rhs,
         BinaryOperation::Div => lhs / rhs,
         BinaryOperation::Rem => lhs % rhs,
@@ -233,6 +351,21 @@ pub fn eval_binary_operation(
         BinaryOperation::Lt => (lhs < rhs).into(),
         BinaryOperation::Lte => (lhs <= rhs).into(),
         BinaryOperation::Elvis => lhs.or(rhs),
+        BinaryOperation::Range | BinaryOperation::RangeInclusive => {
+            let inclusive = binary_operation == BinaryOperation::RangeInclusive;
+            let range = match (lhs, rhs) {
+                (ObjectValue::Primitive(a), ObjectValue::Primitive(b)) => {
+                    ValueRange::new(a, b, inclusive)
+                }
+                _ => Err(VMError::UnsupportedOperation(format!(
+                    "Cannot create range from {lhs} to {rhs}"
+                ))),
+            };
+            match range {
+                Ok(r) => r.into(),
+                Err(e) => e.into(),
+            }
+        }
     }
 }
 
@@ -252,6 +385,43 @@ pub type ResolvedModule = Reference<dyn Module>;
 pub trait Runner: ResolveValue {
     fn store_value(&mut self, value: StackValue);
 
+    /// Active call frames, innermost frame first. Runners without call frames return an empty traceback
+    fn traceback(&self) -> Vec<TraceFrame> {
+        vec![]
+    }
+
+    /// Calls to `module.func` use `scope` until the current call frame exits
+    fn override_module(&mut self, module: &str, func: &str, _scope: usize) -> Result<(), VMError> {
+        Err(VMError::UnsupportedOperation(format!(
+            "Cannot override {module}.{func} in {}",
+            self.location()
+        )))
+    }
+
+    /// Scope replacing `module.func` for the current call, if any
+    fn module_override(&self, _module: &str, _func: &str) -> Option<usize> {
+        None
+    }
+
+    /// Attaches the current traceback to new errors when `VMOptions::enable_traceback` is set
+    #[inline]
+    fn trace_value(&self, value: &StackValue) {
+        if !self.options().enable_traceback {
+            return;
+        }
+        let StackValue::Value(v) = value else {
+            return;
+        };
+        let Some(mut v) = v.try_borrow_mut() else {
+            return;
+        };
+        if let ObjectValue::Primitive(PrimitiveValue::Error(e)) = v.deref_mut() {
+            if e.traceback().is_empty() {
+                *e = e.clone().with_traceback(self.traceback());
+            }
+        }
+    }
+
     fn pop(&mut self) -> Option<StackValue>;
 
     fn next_value<T: Display>(&mut self, location: T) -> StackValue;
@@ -266,8 +436,26 @@ pub trait Runner: ResolveValue {
 
     fn get_module(&mut self, module: String) -> Option<ResolvedModule>;
 
-    fn load_mut(&mut self, name: String) -> Result<(), VMError>;
-    fn load_let(&mut self, name: String) -> Result<(), VMError>;
+    /// Module & overload of `func` (see `Module::resolve_extension`) for the receiver on top of the stack.
+    /// The VM caches both per call site & receiver type.
+    #[inline]
+    fn resolve_extension(
+        &mut self,
+        module: String,
+        func: &str,
+    ) -> Option<(ResolvedModule, Option<usize>)> {
+        let _ = func;
+        self.get_module(module).map(|m| (m, None))
+    }
+
+    fn load_mut(&mut self, name: Interned) -> Result<(), VMError>;
+    fn load_let(&mut self, name: Interned) -> Result<(), VMError>;
+
+    fn set_loop_variable(&mut self, name: &str, value: StackValue);
+    fn remove_variable(&mut self, name: &str);
+
+    /// Moves to `pc` in the current scope, used by inlined loops
+    fn jump(&mut self, pc: usize);
 
     fn find_variable(
         &self,
@@ -280,9 +468,9 @@ pub trait Runner: ResolveValue {
     #[inline]
     fn set_this(&mut self, mutable: bool) -> Result<(), VMError> {
         if mutable {
-            self.load_mut("self".to_string())
+            self.load_mut("self".into())
         } else {
-            self.load_let("self".to_string())
+            self.load_let("self".into())
         }
     }
 
@@ -290,6 +478,9 @@ pub trait Runner: ResolveValue {
 
     fn call_frame_memo(&mut self, scope_index: usize) -> Result<(), VMError>;
 
+    /// `call_frame` the first time `scope_index` is called, later calls do nothing
+    fn call_once(&mut self, scope_index: usize) -> Result<(), VMError>;
+
     fn call_dependency(
         &mut self,
         arg: RigzArgs,
@@ -298,8 +489,12 @@ pub trait Runner: ResolveValue {
     ) -> Result<ObjectValue, VMError>;
 
     #[inline]
-    fn apply_unary(&mut self, unary_operation: UnaryOperation, val: Rc<RefCell<ObjectValue>>) {
-        let val = eval_unary(unary_operation, val.borrow().deref());
+    fn apply_unary(&mut self, unary_operation: UnaryOperation, val: Shared<ObjectValue>) {
+        let val = eval_unary(
+            unary_operation,
+            val.borrow().deref(),
+            self.options().overflow,
+        );
         self.store_value(val.into());
     }
 
@@ -313,10 +508,15 @@ pub trait Runner: ResolveValue {
     fn apply_binary(
         &mut self,
         binary_operation: BinaryOperation,
-        lhs: Rc<RefCell<ObjectValue>>,
-        rhs: Rc<RefCell<ObjectValue>>,
+        lhs: Shared<ObjectValue>,
+        rhs: Shared<ObjectValue>,
     ) {
-        let v = eval_binary_operation(binary_operation, lhs.borrow().deref(), rhs.borrow().deref());
+        let v = eval_binary_operation(
+            binary_operation,
+            lhs.borrow().deref(),
+            rhs.borrow().deref(),
+            self.options().overflow,
+        );
         self.store_value(v.into())
     }
 
@@ -331,23 +531,28 @@ pub trait Runner: ResolveValue {
     fn handle_binary_assign(&mut self, op: BinaryOperation) {
         let rhs = self.next_resolved_value("handle_binary_assign - rhs");
         let lhs = self.next_resolved_value("handle_binary_assign - lhs");
-        let v = eval_binary_operation(op, lhs.borrow().deref(), rhs.borrow().deref());
+        let v = eval_binary_operation(
+            op,
+            lhs.borrow().deref(),
+            rhs.borrow().deref(),
+            self.options().overflow,
+        );
         *lhs.borrow_mut().deref_mut() = v;
     }
 
     #[inline]
-    fn next_resolved_value<T: Display>(&mut self, location: T) -> Rc<RefCell<ObjectValue>> {
+    fn next_resolved_value<T: Display>(&mut self, location: T) -> Shared<ObjectValue> {
         self.next_value(location).resolve(self)
     }
 
     #[inline]
-    fn resolve_args(&mut self, count: usize) -> Vec<Rc<RefCell<ObjectValue>>> {
+    fn resolve_args(&mut self, count: usize) -> Vec<Shared<ObjectValue>> {
         (0..count)
             .map(|_| self.next_resolved_value("resolve_args"))
             .collect()
     }
 
-    fn persist_scope(&mut self, var: String) -> Option<VMError>;
+    fn persist_scope(&mut self, var: Interned) -> Option<VMError>;
 
     fn goto(&mut self, scope_id: usize, pc: usize) -> Result<(), VMError>;
 
@@ -355,6 +560,13 @@ pub trait Runner: ResolveValue {
 
     fn receive(&mut self, args: usize) -> Result<(), VMError>;
 
+    fn select(&mut self, sources: usize, timeout: bool) -> Result<(), VMError>;
+
+    fn parallel(&mut self, op: Parallel) -> Result<(), VMError>;
+
+    /// Runs the handler for `opcode` with `args` values, stores its result
+    fn custom(&mut self, opcode: usize, args: usize) -> Result<(), VMError>;
+
     fn spawn(&mut self, scope_id: usize, timeout: Option<usize>) -> Result<(), VMError>;
 
     fn get_variable(&mut self, name: &str);
@@ -377,6 +589,13 @@ pub trait Runner: ResolveValue {
         args: usize,
     ) -> Result<ObjectValue, VMError>;
 
+    fn call_resolved_extension(
+        &mut self,
+        module: ResolvedModule,
+        overload: usize,
+        args: usize,
+    ) -> Result<ObjectValue, VMError>;
+
     fn call_mutable_extension(
         &mut self,
         module: ResolvedModule,
@@ -391,8 +610,8 @@ pub trait Runner: ResolveValue {
     //     args: usize,
     // ) -> Result<ObjectValue, VMError>;
 
-    // todo this should always be interruptible (if called by run_within, amount of time slept should be removed)
-    fn sleep(&self, duration: Duration);
+    /// Sleeps for at most the time left before the `run_within` deadline
+    fn sleep(&mut self, duration: Duration);
 
     #[allow(unused_variables)]
     #[inline]
@@ -429,18 +648,32 @@ pub trait Runner: ResolveValue {
                     return e.into();
                 }
             }
+            Instruction::CallOnce(scope) => {
+                if let Err(e) = self.call_once(scope) {
+                    return e.into();
+                }
+            }
             Instruction::CallModule { module, func, args } => {
-                if let Some(module) = self.get_module(module) {
+                if let Some(scope) = self.module_override(&module, &func) {
+                    if let Err(e) = self.call_frame(scope) {
+                        return e.into();
+                    }
+                } else if let Some(module) = self.get_module(module) {
                     let v = self.call(module, func, args).unwrap_or_else(|e| e.into());
                     self.store_value(v.into());
                 };
             }
             Instruction::CallExtension { module, func, args } => {
-                if let Some(module) = self.get_module(module) {
-                    let v = self
-                        .call_extension(module, func, args)
-                        .unwrap_or_else(|e| e.into());
-                    self.store_value(v.into());
+                if let Some(scope) = self.module_override(&module, &func) {
+                    if let Err(e) = self.call_frame(scope) {
+                        return e.into();
+                    }
+                } else if let Some((module, overload)) = self.resolve_extension(module, &func) {
+                    let v = match overload {
+                        Some(overload) => self.call_resolved_extension(module, overload, args),
+                        None => self.call_extension(module, func, args),
+                    };
+                    self.store_value(v.unwrap_or_else(|e| e.into()).into());
                 };
             }
             Instruction::CallMutableExtension { module, func, args } => {
@@ -639,11 +872,15 @@ pub trait Runner: ResolveValue {
             }
             Instruction::ForList { scope } => {
                 let mut result = vec![];
-                let this = match self.next_resolved_value("for-list").borrow().to_list() {
+                let this = match ForValues::new(&self.next_resolved_value("for-list").borrow()) {
                     Ok(l) => l,
                     Err(e) => return e.into(),
                 };
                 for value in this {
+                    let value = match value {
+                        Ok(v) => v,
+                        Err(e) => return e.into(),
+                    };
                     self.store_value(value.into());
                     // todo ideally this doesn't need a call frame per intermediate, it should be possible to reuse the current scope/fram
                     // the process_ret instruction for the scope is the reason this is needed
@@ -656,7 +893,7 @@ pub trait Runner: ResolveValue {
                 self.store_value(result.into());
             }
             Instruction::ForMap { scope } => {
-                let mut result = IndexMap::new();
+                let mut result = IndexMap::default();
                 let this = match self.next_resolved_value("for-map").borrow().to_map() {
                     Ok(map) => map,
                     Err(e) => return e.into(),
@@ -666,27 +903,123 @@ pub trait Runner: ResolveValue {
                     self.store_value(k.into());
                     let value = self.handle_scope(scope);
                     let value = value.borrow().clone();
-                    match value {
-                        ObjectValue::Primitive(PrimitiveValue::None) => {}
-                        ObjectValue::Tuple(mut t) if t.len() >= 2 => {
-                            // todo this should be == 2 but same tuple is reused appending to front
-                            let v = t.remove(1);
-                            let k = t.remove(0);
-                            if k != ObjectValue::default() && v != ObjectValue::default() {
-                                result.insert(k, v);
-                            }
-                        }
-                        // todo should a single value be both the key & value?
-                        _ => {
-                            let e: ObjectValue = VMError::UnsupportedOperation(format!(
-                                "Invalid args in for-map {value}"
-                            ))
-                            .into();
-                            result.insert(e.clone(), e);
+                    insert_for_map_entry(&mut result, value);
+                }
+                self.store_value(result.into());
+            }
+            Instruction::ForListStart => {
+                let this =
+                    match ForValues::new(&self.next_resolved_value("for-list start").borrow()) {
+                        Ok(l) => l,
+                        Err(e) => return e.into(),
+                    };
+                self.store_value(ObjectValue::List(vec![]).into());
+                let remaining = match this {
+                    // reversed so each iteration can pop the next element
+                    ForValues::List(l) => ObjectValue::List(l.rev().collect()),
+                    ForValues::Stream(o) => ObjectValue::Object(o),
+                };
+                self.store_value(remaining.into());
+            }
+            Instruction::ForMapStart => {
+                let this = match self.next_resolved_value("for-map start").borrow().to_map() {
+                    Ok(m) => m,
+                    Err(e) => return e.into(),
+                };
+                self.store_value(ObjectValue::Map(IndexMap::default()).into());
+                let entries = this
+                    .into_iter()
+                    .rev()
+                    .map(|(k, v)| ObjectValue::Tuple(vec![k, v]))
+                    .collect();
+                self.store_value(ObjectValue::List(entries).into());
+            }
+            Instruction::ReduceStart => {
+                // initial value is left on the stack as the accumulator
+                let this = match self.next_resolved_value("reduce start").borrow().to_list() {
+                    Ok(l) => l,
+                    Err(e) => return e.into(),
+                };
+                self.store_value(ObjectValue::List(this.into_iter().rev().collect()).into());
+            }
+            Instruction::ForNext { vars, acc, end } => {
+                let remaining = self.next_resolved_value("for-next");
+                let next = match remaining.borrow_mut().deref_mut() {
+                    ObjectValue::List(l) => l.pop(),
+                    ObjectValue::Object(o) if o.is_stream() => match o.next_value() {
+                        Ok(v) => v,
+                        Err(e) => return e.into(),
+                    },
+                    v => return VMError::RuntimeError(format!("Invalid loop state {v}")).into(),
+                };
+                let Some(next) = next else {
+                    // result or accumulator is left on the stack
+                    for var in vars.iter().chain(acc.iter()) {
+                        self.remove_variable(var);
+                    }
+                    self.jump(end);
+                    return VMState::Running;
+                };
+                if let Some(acc) = acc {
+                    let current = self.next_value("for-next accumulator");
+                    self.set_loop_variable(&acc, current.clone());
+                    self.store_value(current);
+                }
+                self.store_value(StackValue::Value(remaining));
+                match (vars.as_slice(), next) {
+                    ([var], next) => self.set_loop_variable(var, next.into()),
+                    ([k, v], ObjectValue::Tuple(mut t)) if t.len() == 2 => {
+                        let value = t.remove(1);
+                        let key = t.remove(0);
+                        self.set_loop_variable(k, key.into());
+                        self.set_loop_variable(v, value.into());
+                    }
+                    (_, next) => {
+                        return VMError::RuntimeError(format!(
+                            "Cannot bind {next} to loop variables {vars:?}"
+                        ))
+                        .into()
+                    }
+                }
+            }
+            Instruction::ForPush(start) => {
+                let value = self.next_resolved_value("for-push").borrow().clone();
+                let remaining = self.next_value("for-push remaining");
+                let result = self.next_resolved_value("for-push result");
+                match result.borrow_mut().deref_mut() {
+                    ObjectValue::List(l) => {
+                        if value != ObjectValue::default() {
+                            l.push(value)
                         }
                     }
+                    ObjectValue::Map(m) => insert_for_map_entry(m, value),
+                    v => return VMError::RuntimeError(format!("Invalid loop result {v}")).into(),
+                }
+                self.store_value(StackValue::Value(result));
+                self.store_value(remaining);
+                self.jump(start);
+            }
+            Instruction::OverrideModule {
+                module,
+                func,
+                scope,
+            } => {
+                if let Err(e) = self.override_module(&module, &func, scope) {
+                    return e.into();
+                }
+            }
+            Instruction::ReducePush(start) => {
+                let value = self.next_value("reduce-push");
+                let remaining = self.next_value("reduce-push remaining");
+                self.next_value("reduce-push accumulator");
+                self.store_value(value);
+                self.store_value(remaining);
+                self.jump(start);
+            }
+            Instruction::Custom { opcode, args } => {
+                if let Err(e) = self.custom(opcode, args) {
+                    return e.into();
                 }
-                self.store_value(result.into());
             }
             Instruction::Send(args) => {
                 if let Err(o) = self.send(args) {
@@ -713,6 +1046,16 @@ pub trait Runner: ResolveValue {
                     return o.into();
                 }
             }
+            Instruction::Select(sources, timeout) => {
+                if let Err(o) = self.select(sources, timeout) {
+                    return o.into();
+                }
+            }
+            Instruction::Parallel(op) => {
+                if let Err(o) = self.parallel(op) {
+                    return o.into();
+                }
+            }
             Instruction::Sleep => {
                 let v = self.next_resolved_value("sleep");
                 let duration = match v.borrow().to_usize() {
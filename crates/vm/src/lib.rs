@@ -1,7 +1,13 @@
+// without the `std` feature only core & alloc are available, see Cargo.toml
+#![cfg_attr(not(feature = "std"), no_std)]
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+
 mod builder;
 mod call_frame;
 mod instructions;
 mod macros;
+mod prelude;
 mod scope;
 mod vm;
 
@@ -11,6 +17,14 @@ mod stack;
 pub use builder::{RigzBuilder, VMBuilder};
 pub use call_frame::{CallFrame, Variable};
 pub use instructions::*;
+#[cfg(feature = "std")]
+pub use process::{coroutine_wait_start, retry_call};
+pub use process::{ProcessExecutor, ProcessHandle, ProcessInfo, ProcessState};
 pub use scope::Scope;
 pub use stack::VMStack;
 pub use vm::*;
+
+#[doc(hidden)]
+pub use macros::write_output;
+#[doc(hidden)]
+pub use rigz_core::OutputStream;
@@ -12,66 +12,67 @@ macro_rules! handle_js {
     };
 }
 
+#[macro_export]
+macro_rules! write_output {
+    ($stream: ident, $content: expr) => {
+        $crate::write_output($crate::OutputStream::$stream, $content)
+    };
+}
+
+/// Writes output that isn't captured to the console, without std it's dropped unless an output handler is set
+#[doc(hidden)]
+pub fn write_output(stream: rigz_core::OutputStream, content: alloc::string::String) {
+    #[allow(unused_variables)]
+    if let Err(content) = rigz_core::capture_output(stream, content) {
+        #[cfg(feature = "js")]
+        match stream {
+            rigz_core::OutputStream::Stdout => {
+                web_sys::console::log_1(&content.trim_end_matches('\n').into())
+            }
+            rigz_core::OutputStream::Stderr => {
+                web_sys::console::error_1(&content.trim_end_matches('\n').into())
+            }
+        }
+        #[cfg(all(feature = "std", not(feature = "js")))]
+        match stream {
+            rigz_core::OutputStream::Stdout => print!("{content}"),
+            rigz_core::OutputStream::Stderr => eprint!("{content}"),
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! outln {
     () => {
-        $crate::handle_js! {
-            web_sys::console::log_0(),
-            println!()
-        }
+        $crate::write_output!(Stdout, "\n".to_string())
+    };
+    ($($arg:tt)*) => {
+        $crate::write_output!(Stdout, format!("{}\n", format_args!($($arg)*)))
     };
-    ($($arg:tt)*) => {{
-        $crate::handle_js! {
-            web_sys::console::log_1(&format_args!($($arg)*).to_string().into()),
-            println!($($arg)*)
-        }
-    }};
 }
 
 #[macro_export]
 macro_rules! out {
-    () => {
-        $crate::handle_js! {
-            web_sys::console::log_0(),
-            print!()
-        }
+    () => {};
+    ($($arg:tt)*) => {
+        $crate::write_output!(Stdout, format!($($arg)*))
     };
-    ($($arg:tt)*) => {{
-        $crate::handle_js! {
-            web_sys::console::log_1(&format_args!($($arg)*).to_string().into()),
-            print!($($arg)*)
-        }
-    }};
 }
 
 #[macro_export]
 macro_rules! err {
-    () => {
-        $crate::handle_js! {
-           web_sys::console::error_0(),
-           eprint!()
-        }
+    () => {};
+    ($($arg:tt)*) => {
+        $crate::write_output!(Stderr, format!($($arg)*))
     };
-    ($($arg:tt)*) => {{
-        $crate::handle_js! {
-           web_sys::console::error_1(&format_args!($($arg)*).to_string().into()),
-           eprint!($($arg)*)
-        }
-    }};
 }
 
 #[macro_export]
 macro_rules! errln {
     () => {
-        $crate::handle_js! {
-           web_sys::console::error_0(),
-           eprintln!()
-        }
+        $crate::write_output!(Stderr, "\n".to_string())
+    };
+    ($($arg:tt)*) => {
+        $crate::write_output!(Stderr, format!("{}\n", format_args!($($arg)*)))
     };
-    ($($arg:tt)*) => {{
-        $crate::handle_js! {
-           web_sys::console::error_1(&format_args!($($arg)*).to_string().into()),
-           eprintln!($($arg)*)
-        }
-    }};
 }
@@ -0,0 +1,57 @@
+use crate::Instant;
+use core::cell::Cell;
+#[cfg(feature = "threaded")]
+use core::time::Duration;
+
+/// How long a coroutine sleeps before running a call that is waiting again
+#[cfg(feature = "threaded")]
+pub(crate) const RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Copy, Clone, Debug)]
+struct Waiting {
+    since: Instant,
+    #[cfg(feature = "threaded")]
+    retry: bool,
+}
+
+thread_local! {
+    /// Set while a coroutine of `ProcessExecutor::Cooperative` runs a module or object call
+    static WAITING: Cell<Option<Waiting>> = const { Cell::new(None) };
+}
+
+/// None outside of a coroutine, calls that wait for another process (i.e. `Channel.receive`) block the thread.
+/// Inside a coroutine blocking would stop every other coroutine on the worker thread, the call returns after
+/// `retry_call` instead and is run again with the same arguments once the other coroutines had a chance to run.
+/// Returns when the call first started waiting, deadlines are based on it so retries don't restart a timeout.
+pub fn coroutine_wait_start() -> Option<Instant> {
+    WAITING.with(|w| w.get().map(|w| w.since))
+}
+
+/// Discards the result of the current call, it's run again after the coroutine yields.
+/// Only valid when `coroutine_wait_start` returned a value
+pub fn retry_call() {
+    // coroutines only run with `threaded`, without it there's never a call to retry
+    #[cfg(feature = "threaded")]
+    WAITING.with(|w| {
+        if let Some(waiting) = w.get() {
+            w.set(Some(Waiting {
+                retry: true,
+                ..waiting
+            }))
+        }
+    });
+}
+
+/// Runs `call` as a retryable call that started waiting at `since`, returns true if it requested a retry
+#[cfg(feature = "threaded")]
+pub(crate) fn run_retryable<F: FnOnce()>(since: Instant, call: F) -> bool {
+    let previous = WAITING.with(|w| {
+        w.replace(Some(Waiting {
+            since,
+            retry: false,
+        }))
+    });
+    call();
+    let waiting = WAITING.with(|w| w.replace(previous));
+    waiting.is_some_and(|w| w.retry)
+}
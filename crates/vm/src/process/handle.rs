@@ -0,0 +1,182 @@
+use crate::prelude::*;
+use crate::process::Process;
+use crate::VM;
+use core::fmt::{Display, Formatter};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use rigz_core::{MutableReference, Reference, VMError};
+
+/// What a process is doing, see `ProcessHandle::list`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProcessState {
+    /// Waiting for a thread, or an `@on` handler waiting for events
+    Idle,
+    Running,
+    Finished,
+    Cancelled,
+}
+
+impl Display for ProcessState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let state = match self {
+            ProcessState::Idle => "idle",
+            ProcessState::Running => "running",
+            ProcessState::Finished => "finished",
+            ProcessState::Cancelled => "cancelled",
+        };
+        write!(f, "{state}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: usize,
+    pub scope: String,
+    pub state: ProcessState,
+    /// finished results that haven't been received yet
+    pub mailbox_depth: usize,
+}
+
+/// Updated by whichever thread runs the process, read by `ProcessHandle`
+#[derive(Debug, Default)]
+pub(crate) struct ProcessStatus {
+    running: AtomicU8,
+    cancelled: AtomicBool,
+    #[cfg(feature = "threaded")]
+    received: AtomicBool,
+}
+
+impl ProcessStatus {
+    const IDLE: u8 = 0;
+    const RUNNING: u8 = 1;
+
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn state(&self) -> ProcessState {
+        if self.is_cancelled() {
+            return ProcessState::Cancelled;
+        }
+        match self.running.load(Ordering::Acquire) {
+            Self::IDLE => ProcessState::Idle,
+            Self::RUNNING => ProcessState::Running,
+            _ => ProcessState::Finished,
+        }
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// Processes only run with `threaded`
+#[cfg(feature = "threaded")]
+impl ProcessStatus {
+    const FINISHED: u8 = 2;
+
+    #[inline]
+    pub(crate) fn start(&self) {
+        self.running.store(Self::RUNNING, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn finish(&self) {
+        self.running.store(Self::FINISHED, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn receive(&self) {
+        self.received.store(true, Ordering::Release);
+    }
+
+    /// Results of processes that weren't received
+    pub(crate) fn unreceived(&self) -> usize {
+        let finished = self.running.load(Ordering::Acquire) == Self::FINISHED;
+        (finished && !self.received.load(Ordering::Acquire)) as usize
+    }
+}
+
+/// Processes started by the current run in pid order, kept apart from the `ProcessManager` since it stays locked
+/// while `receive` waits for a process
+pub(crate) type ProcessTable = MutableReference<Vec<Reference<Process>>>;
+
+/// Lists & cancels the processes of a VM, i.e. so a host can stop a runaway `spawn` from another thread
+#[derive(Clone, Debug)]
+pub struct ProcessHandle {
+    pub(crate) table: ProcessTable,
+}
+
+impl ProcessHandle {
+    /// Spawned processes & `@on` handlers, the list is cleared once the run finishes
+    pub fn list(&self) -> Vec<ProcessInfo> {
+        self.table
+            .apply(|t| t.iter().enumerate().map(|(pid, p)| p.info(pid)).collect())
+    }
+
+    /// The process stops before its next instruction & fails with `Process was cancelled`,
+    /// `@on` handlers fail their remaining events and reject new ones
+    pub fn cancel(&self, pid: usize) -> Result<(), VMError> {
+        self.table.apply(|t| match t.get(pid) {
+            None => Err(VMError::RuntimeError(format!(
+                "Process {pid} does not exist"
+            ))),
+            Some(p) if p.info(pid).state == ProcessState::Finished => Err(VMError::RuntimeError(
+                format!("Process {pid} already finished"),
+            )),
+            Some(p) => {
+                p.status.cancel();
+                Ok(())
+            }
+        })
+    }
+}
+
+impl VM {
+    pub fn process_handle(&self) -> ProcessHandle {
+        self.process_manager.apply(|p| p.handle())
+    }
+
+    /// See `ProcessHandle::list`
+    #[inline]
+    pub fn processes(&self) -> Vec<ProcessInfo> {
+        self.process_handle().list()
+    }
+
+    /// See `ProcessHandle::cancel`
+    #[inline]
+    pub fn cancel(&self, pid: usize) -> Result<(), VMError> {
+        self.process_handle().cancel(pid)
+    }
+}
+
+#[cfg(test)]
+pub mod handle_tests {
+    use crate::VM;
+    #[cfg(feature = "threaded")]
+    use crate::{process::ProcessStatus, ProcessState};
+    use wasm_bindgen_test::*;
+
+    #[cfg(feature = "threaded")]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn status_transitions() {
+        let status = ProcessStatus::default();
+        assert_eq!(status.state(), ProcessState::Idle);
+        status.start();
+        assert_eq!(status.state(), ProcessState::Running);
+        status.finish();
+        assert_eq!(status.state(), ProcessState::Finished);
+        assert_eq!(status.unreceived(), 1);
+        status.receive();
+        assert_eq!(status.unreceived(), 0);
+        status.cancel();
+        assert_eq!(status.state(), ProcessState::Cancelled);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unknown_process() {
+        let vm = VM::default();
+        assert!(vm.processes().is_empty());
+        assert!(vm.cancel(0).is_err());
+    }
+}
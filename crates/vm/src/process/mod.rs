@@ -4,11 +4,47 @@ mod threaded;
 #[cfg(feature = "threaded")]
 pub(crate) type Process = threaded::Process;
 
+#[cfg(feature = "std")]
+mod cooperative;
+mod handle;
 mod process_manager;
 #[cfg(not(feature = "threaded"))]
 mod single;
 
+#[cfg(feature = "std")]
+pub use cooperative::{coroutine_wait_start, retry_call};
+#[cfg(feature = "threaded")]
+pub(crate) use cooperative::{run_retryable, RETRY_INTERVAL};
+pub use handle::{ProcessHandle, ProcessInfo, ProcessState};
+pub(crate) use handle::{ProcessStatus, ProcessTable};
 pub(crate) use process_manager::ProcessManager;
 
 #[cfg(not(feature = "threaded"))]
 pub type Process = single::Process;
+
+/// How `spawn` runs processes, `@on` handlers always run on the blocking thread pool
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ProcessExecutor {
+    /// Each process gets a thread from the blocking thread pool
+    #[default]
+    Threads,
+    /// Processes are coroutines spread across `workers` native threads, a process yields to the others on its
+    /// thread after running `quantum` instructions and while it sleeps, so thousands of processes can run at once.
+    /// Calls that wait for another process or a task (`Channel.receive`, `Mutex.acquire`, `Task.wait`) yield instead
+    /// of blocking the thread, see `coroutine_wait_start`. Blocking module calls (i.e. `Http.get`) block every process
+    /// on the same thread, their `_async` variants don't.
+    Cooperative { workers: usize, quantum: usize },
+}
+
+impl ProcessExecutor {
+    pub const DEFAULT_QUANTUM: usize = 1024;
+
+    /// One worker per available core
+    #[cfg(feature = "std")]
+    pub fn cooperative() -> Self {
+        ProcessExecutor::Cooperative {
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            quantum: Self::DEFAULT_QUANTUM,
+        }
+    }
+}
@@ -1,27 +1,27 @@
-use crate::process::Process;
-use crate::{ModulesMap, Scope, VMOptions, VM};
-use log::warn;
-use rigz_core::{AsPrimitive, Lifecycle, MutableReference, ObjectValue, Reference, VMError};
-use std::cell::RefCell;
-use std::fmt::Debug;
-use std::rc::Rc;
-use std::time::Duration;
-
-#[derive(Debug)]
-pub(crate) enum VMMessage {}
-
-#[derive(Debug)]
-pub(crate) enum ProcessMessage {}
-
-#[derive(Debug)]
-pub(crate) struct VMMessenger {}
+use crate::prelude::*;
+#[cfg(feature = "threaded")]
+use crate::process::threaded::{Delivery, Scheduler};
+use crate::process::{Process, ProcessHandle, ProcessTable};
+#[cfg(feature = "threaded")]
+use crate::ProcessExecutor;
+use crate::{ProcessMetrics, Scope, SharedProgram, VM};
+use core::fmt::Debug;
+use rigz_core::{
+    AsPrimitive, Lifecycle, MutableReference, ObjectValue, Reference, Shared, VMError,
+};
+#[cfg(feature = "threaded")]
+use {core::time::Duration, log::warn};
 
 #[derive(Debug)]
 pub(crate) struct ProcessManager {
     #[cfg(feature = "threaded")]
     pub(crate) handle: tokio::runtime::Handle,
     processes: SpawnedProcesses,
-    vm_messenger: Option<VMMessenger>,
+    /// same processes as `processes`, shared with `ProcessHandle`
+    table: ProcessTable,
+    /// created by the first process spawned with `ProcessExecutor::Cooperative`
+    #[cfg(feature = "threaded")]
+    scheduler: Option<Scheduler>,
 }
 
 #[cfg(feature = "threaded")]
@@ -33,8 +33,11 @@ pub(crate) type SpawnedProcesses = Vec<(
 #[cfg(not(feature = "threaded"))]
 pub(crate) type SpawnedProcesses = Vec<Reference<Process>>;
 
+/// Queues the event for an `@on` handler, starting a worker if the handler is idle. `detached` handlers start a
+/// worker for every event instead, their results are dropped.
+/// Returns the pid, none if the event was dropped or detached, or the error from a full mailbox.
 #[cfg(feature = "threaded")]
-fn run_process(
+fn deliver(
     handle: &tokio::runtime::Handle,
     id: usize,
     running: &mut (
@@ -43,20 +46,32 @@ fn run_process(
     ),
     args: Vec<ObjectValue>,
 ) -> ObjectValue {
-    let (p, running) = running;
-    let p = p.clone();
-    let current = running.take();
-    let t = match current {
-        None => handle.spawn_blocking(move || p.run(args)),
-        Some(_) => {
-            return VMError::todo(format!(
-                "overwriting running tasks is not supported - Process {id}"
-            ))
-            .into()
-        }
+    let (p, worker) = running;
+    let Some(mailbox) = &p.mailbox else {
+        return VMError::RuntimeError(format!("Process {id} does not handle events")).into();
     };
-    *running = Some(t);
-    (id as i64).into()
+    if p.status.is_cancelled() {
+        return VMError::RuntimeError(format!("Process {id} was cancelled")).into();
+    }
+    if matches!(&p.scope.lifecycle, Some(Lifecycle::On(e)) if e.detached) {
+        let p = p.clone();
+        handle.spawn_blocking(move || p.run_detached(id, args));
+        return ObjectValue::default();
+    }
+    match mailbox.push(args) {
+        Ok(Delivery::Start) => {
+            let p = p.clone();
+            // the previous worker stopped once its queue was empty
+            *worker = Some(handle.spawn_blocking(move || {
+                p.run_events(id);
+                ObjectValue::default()
+            }));
+            (id as i64).into()
+        }
+        Ok(Delivery::Queued) => (id as i64).into(),
+        Ok(Delivery::Dropped) => ObjectValue::default(),
+        Err(e) => VMError::RuntimeError(format!("Process {id}: {e}")).into(),
+    }
 }
 
 #[cfg(feature = "threaded")]
@@ -82,7 +97,7 @@ impl ProcessManager {
     pub(crate) fn new() -> Self {
         Self {
             processes: Vec::new(),
-            vm_messenger: None,
+            table: Vec::new().into(),
         }
     }
 
@@ -96,11 +111,33 @@ impl ProcessManager {
         Ok(Self {
             handle,
             processes: Vec::new(),
-            vm_messenger: None,
+            table: Vec::new().into(),
+            scheduler: None,
         })
     }
 
+    #[cfg(feature = "threaded")]
+    fn scheduler(&mut self, workers: usize) -> Result<&Scheduler, VMError> {
+        let scheduler = match self.scheduler.take() {
+            Some(s) if s.workers() == workers.max(1) => s,
+            // running coroutines finish on the previous workers
+            _ => Scheduler::new(workers)?,
+        };
+        Ok(self.scheduler.insert(scheduler))
+    }
+
+    pub(crate) fn handle(&self) -> ProcessHandle {
+        ProcessHandle {
+            table: self.table.clone(),
+        }
+    }
+
     pub(crate) fn add(&mut self, processes: SpawnedProcesses) {
+        #[cfg(feature = "threaded")]
+        let added = processes.iter().map(|(p, _)| p.clone());
+        #[cfg(not(feature = "threaded"))]
+        let added = processes.iter().cloned();
+        self.table.update(|t| t.extend(added));
         self.processes.extend(processes);
     }
 
@@ -108,37 +145,124 @@ impl ProcessManager {
         &mut self,
         scope: Scope,
         args: Vec<ObjectValue>,
-        options: VMOptions,
-        modules: ModulesMap,
+        program: SharedProgram,
         timeout: Option<usize>,
         process_manager: MutableReference<ProcessManager>,
     ) -> Result<usize, VMError> {
         let pid = self.processes.len();
-        let p: Reference<Process> =
-            Process::new(scope, options, modules, timeout, process_manager).into();
+        #[cfg(feature = "threaded")]
+        let options = program.options;
+        let p: Reference<Process> = Process::new(scope, program, timeout, process_manager).into();
+        self.table.update(|t| t.push(p.clone()));
         #[cfg(feature = "threaded")]
         {
             let arc = p.clone();
-            let t = self.handle.spawn_blocking(move || arc.run(args));
+            let t = match options.executor {
+                // deterministic processes run on a thread of their own, see `RuntimeContext::for_process`
+                ProcessExecutor::Cooperative { workers, quantum } if !options.deterministic => {
+                    let (sender, receiver) = tokio::sync::oneshot::channel();
+                    self.scheduler(workers)?.schedule(Box::new(move || {
+                        Box::pin(async move {
+                            let _ = sender.send(arc.run_cooperative(pid, args, quantum).await);
+                        })
+                    }))?;
+                    self.handle.spawn(async move {
+                        receiver.await.unwrap_or_else(|_| {
+                            VMError::RuntimeError(format!("Process {pid} stopped before finishing"))
+                                .into()
+                        })
+                    })
+                }
+                _ => self.handle.spawn_blocking(move || arc.run(pid, args)),
+            };
             self.processes.push((p, Some(t)));
         }
 
         #[cfg(not(feature = "threaded"))]
         {
+            // single threaded processes don't run, receiving their result is an error
+            drop(args);
             self.processes.push(p);
         }
         Ok(pid)
     }
 
+    /// Runs `scope` once per value with a worker per core, results are in the same order as `values`.
+    /// The process manager isn't locked while the workers run so the function can sleep.
     #[cfg(feature = "threaded")]
-    pub(crate) fn send(
-        &mut self,
-        args: Vec<Rc<RefCell<ObjectValue>>>,
-    ) -> Result<ObjectValue, VMError> {
+    pub(crate) fn parallel(
+        process_manager: &MutableReference<ProcessManager>,
+        scope: Scope,
+        values: Vec<ObjectValue>,
+        program: SharedProgram,
+    ) -> Result<Vec<ObjectValue>, VMError> {
+        let handle = process_manager.apply(|pm| pm.handle.clone());
+        Self::parallel_on(&handle, process_manager, scope, values, program)
+    }
+
+    /// Used by processes, the VM holds the process manager while it waits in `receive` so a process
+    /// can't lock it to get the handle
+    #[cfg(feature = "threaded")]
+    pub(crate) fn parallel_on(
+        handle: &tokio::runtime::Handle,
+        process_manager: &MutableReference<ProcessManager>,
+        scope: Scope,
+        values: Vec<ObjectValue>,
+        program: SharedProgram,
+    ) -> Result<Vec<ObjectValue>, VMError> {
+        // workers share the process's seeded generator, a single worker keeps the order values see it in
+        let workers = match program.options.deterministic {
+            true => 1,
+            false => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let size = values.len().div_ceil(workers).max(1);
+        let p: Reference<Process> =
+            Process::new(scope, program, None, process_manager.clone()).into();
+        let workers: Vec<_> = values
+            .chunks(size)
+            .map(|chunk| {
+                let p = p.clone();
+                let chunk = chunk.to_vec();
+                handle.spawn_blocking(move || p.run_each(chunk))
+            })
+            .collect();
+        let mut results = Vec::with_capacity(values.len());
+        for worker in workers {
+            let chunk = handle
+                .block_on(worker)
+                .map_err(|e| VMError::RuntimeError(format!("Parallel worker failed: {e}")))?;
+            results.extend(chunk);
+        }
+        Ok(results)
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    pub(crate) fn parallel(
+        _process_manager: &MutableReference<ProcessManager>,
+        _scope: Scope,
+        _values: Vec<ObjectValue>,
+        _program: SharedProgram,
+    ) -> Result<Vec<ObjectValue>, VMError> {
+        Err(VMError::todo(
+            "parallel iteration is not implemented for single threaded processes",
+        ))
+    }
+
+    #[cfg(feature = "threaded")]
+    pub(crate) fn send(&mut self, args: Vec<Shared<ObjectValue>>) -> Result<ObjectValue, VMError> {
         let mut args = args.into_iter().map(|v| v.borrow().clone());
         // todo message or pid
         let message = args.next().unwrap().to_string();
-        let args = Vec::from_iter(args);
+        self.send_event(&message, Vec::from_iter(args))
+    }
+
+    /// Delivers the event to every matching `@on` handler, used by `send` & OS signals
+    #[cfg(feature = "threaded")]
+    pub(crate) fn send_event(
+        &mut self,
+        message: &str,
+        args: Vec<ObjectValue>,
+    ) -> Result<ObjectValue, VMError> {
         let res: Vec<_> = self
             .processes
             .iter_mut()
@@ -147,7 +271,7 @@ impl ProcessManager {
                 Some(Lifecycle::On(e)) => e.event == message,
                 _ => false,
             })
-            .map(|(id, running)| run_process(&self.handle, id, running, args.clone()))
+            .map(|(id, running)| deliver(&self.handle, id, running, args.clone()))
             .collect();
 
         if res.is_empty() {
@@ -160,10 +284,7 @@ impl ProcessManager {
     }
 
     #[cfg(not(feature = "threaded"))]
-    pub(crate) fn send(
-        &mut self,
-        args: Vec<Rc<RefCell<ObjectValue>>>,
-    ) -> Result<ObjectValue, VMError> {
+    pub(crate) fn send(&mut self, _args: Vec<Shared<ObjectValue>>) -> Result<ObjectValue, VMError> {
         Err(VMError::todo(
             "send is not implemented for single threaded processes",
         ))
@@ -171,7 +292,7 @@ impl ProcessManager {
 
     pub(crate) fn receive(
         &mut self,
-        args: Vec<Rc<RefCell<ObjectValue>>>,
+        args: Vec<Shared<ObjectValue>>,
     ) -> Result<ObjectValue, VMError> {
         let mut args = args.into_iter().map(|v| v.borrow().clone());
         let v = args.next().unwrap();
@@ -206,6 +327,12 @@ impl ProcessManager {
     fn handle_receive(&mut self, pid: usize, timeout: Option<usize>) -> ObjectValue {
         match self.processes.get_mut(pid) {
             None => VMError::RuntimeError(format!("Process {pid} does not exist")).into(),
+            Some((p, _)) if p.mailbox.is_some() => {
+                let mailbox = p.mailbox.as_ref().unwrap();
+                mailbox
+                    .receive(timeout.or(p.timeout))
+                    .unwrap_or_else(|e| e.into())
+            }
             Some((p, t)) => {
                 let running = match t {
                     None => {
@@ -235,6 +362,7 @@ impl ProcessManager {
                     }
                 });
                 *t = None;
+                p.status.receive();
 
                 res.unwrap_or_else(|e| {
                     VMError::RuntimeError(format!("Process {pid} failed: {e}")).into()
@@ -244,20 +372,56 @@ impl ProcessManager {
     }
 
     #[cfg(not(feature = "threaded"))]
-    fn handle_receive(&mut self, pid: usize, timeout: Option<usize>) -> ObjectValue {
+    fn handle_receive(&mut self, pid: usize, _timeout: Option<usize>) -> ObjectValue {
         VMError::todo(format!(
             "receive is not implemented for single threaded processes - {pid}"
         ))
         .into()
     }
 
+    /// Result of a process without waiting, `None` while it is still running
+    #[cfg(feature = "threaded")]
+    pub(crate) fn try_receive(&mut self, pid: usize) -> Result<Option<ObjectValue>, VMError> {
+        match self.processes.get_mut(pid) {
+            None => Err(VMError::RuntimeError(format!(
+                "Process {pid} does not exist"
+            ))),
+            Some((p, _)) if p.mailbox.is_some() => p.mailbox.as_ref().unwrap().try_receive(),
+            Some((_, None)) => Err(VMError::RuntimeError(format!(
+                "Process {pid} is not running"
+            ))),
+            Some((_, Some(t))) if !t.is_finished() => Ok(None),
+            Some((p, t)) => {
+                p.status.receive();
+                let running = t.take().unwrap();
+                let res = self.handle.block_on(running).unwrap_or_else(|e| {
+                    VMError::RuntimeError(format!("Process {pid} failed: {e}")).into()
+                });
+                Ok(Some(res))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    pub(crate) fn try_receive(&mut self, pid: usize) -> Result<Option<ObjectValue>, VMError> {
+        Err(VMError::todo(format!(
+            "select is not implemented for single threaded processes - {pid}"
+        )))
+    }
+
     #[cfg(feature = "threaded")]
     pub(crate) fn close(&mut self, result: ObjectValue) -> ObjectValue {
         let mut errors: Vec<VMError> = vec![];
-        for (id, (_, handle)) in self.processes.drain(..).enumerate() {
+        for (id, (p, handle)) in self.processes.drain(..).enumerate() {
             match handle {
                 None => {}
                 Some(t) => match self.handle.block_on(t) {
+                    // event handlers keep their results in the mailbox
+                    Ok(_) if p.mailbox.is_some() => {
+                        for v in p.mailbox.as_ref().unwrap().drain() {
+                            warn!("Orphaned value from Process {id} - {v}")
+                        }
+                    }
                     Ok(v) => {
                         warn!("Orphaned value from Process {id} - {v}")
                     }
@@ -267,6 +431,8 @@ impl ProcessManager {
                 },
             }
         }
+        // processes stay listed while close waits for them so they can still be cancelled
+        self.table.update(|t| t.clear());
 
         if errors.is_empty() {
             result
@@ -289,24 +455,62 @@ impl ProcessManager {
 
     #[cfg(not(feature = "threaded"))]
     pub(crate) fn close(&mut self, result: ObjectValue) -> ObjectValue {
+        self.processes.clear();
+        self.table.update(|t| t.clear());
         result
     }
 
+    #[cfg(feature = "threaded")]
+    pub(crate) fn metrics(&self) -> Vec<ProcessMetrics> {
+        self.processes
+            .iter()
+            .enumerate()
+            .map(|(pid, (p, handle))| {
+                if let Some(mailbox) = &p.mailbox {
+                    let lag = mailbox.lag();
+                    return ProcessMetrics {
+                        pid,
+                        scope: p.scope.named.clone(),
+                        running: lag.running,
+                        mailbox_depth: Some(lag.results),
+                        pending_events: lag.pending,
+                        delivered_events: lag.delivered,
+                        dropped_events: lag.dropped,
+                        restarts: lag.restarts,
+                    };
+                }
+                ProcessMetrics {
+                    pid,
+                    scope: p.scope.named.clone(),
+                    running: handle.as_ref().is_some_and(|h| !h.is_finished()),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "threaded"))]
+    pub(crate) fn metrics(&self) -> Vec<ProcessMetrics> {
+        self.processes
+            .iter()
+            .enumerate()
+            .map(|(pid, p)| ProcessMetrics {
+                pid,
+                scope: p.scope.named.clone(),
+                running: false,
+                ..Default::default()
+            })
+            .collect()
+    }
+
     // todo return channel
-    pub(crate) fn create_on_processes(vm: &VM) -> SpawnedProcesses {
+    pub(crate) fn create_on_processes(vm: &VM, program: SharedProgram) -> SpawnedProcesses {
         let scopes = vm
             .scopes
             .iter()
             .filter(|s| matches!(s.lifecycle, Some(Lifecycle::On(_))))
             .map(|s| {
-                Process::new(
-                    s.clone(),
-                    vm.options,
-                    vm.modules.clone(),
-                    None,
-                    vm.process_manager.clone(),
-                )
-                .into()
+                Process::new(s.clone(), program.clone(), None, vm.process_manager.clone()).into()
             });
 
         #[cfg(feature = "threaded")]
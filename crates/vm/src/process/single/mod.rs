@@ -1,60 +1,33 @@
-use crate::process::ProcessManager;
-use crate::{ModulesMap, Scope, VMOptions};
-use rigz_core::{Lifecycle, MutableReference, ObjectValue, VMError};
+use crate::process::{ProcessManager, ProcessStatus};
+use crate::{ProcessInfo, Scope, SharedProgram};
+use rigz_core::MutableReference;
 
+/// Without `threaded` processes are listed but never run, see `ProcessManager::handle_receive`
 #[derive(Debug)]
 pub struct Process {
     pub scope: Scope,
-    options: VMOptions,
-    modules: ModulesMap,
-    timeout: Option<usize>,
-    process_manager: MutableReference<ProcessManager>,
+    pub(crate) status: ProcessStatus,
 }
 
 impl Process {
     pub fn new(
         scope: Scope,
-        options: VMOptions,
-        modules: ModulesMap,
-        timeout: Option<usize>,
-        process_manager: MutableReference<ProcessManager>,
+        _program: SharedProgram,
+        _timeout: Option<usize>,
+        _process_manager: MutableReference<ProcessManager>,
     ) -> Self {
         Self {
             scope,
-            options,
-            modules,
-            timeout,
-            process_manager,
+            status: Default::default(),
         }
     }
 
-    pub fn spawn(
-        scope: Scope,
-        options: VMOptions,
-        modules: ModulesMap,
-        timeout: Option<usize>,
-        process_manager: MutableReference<ProcessManager>,
-    ) -> Self {
-        Self::new(scope, options, modules, timeout, process_manager)
-    }
-
-    pub fn lifecycle(&self) -> Option<&Lifecycle> {
-        self.scope.lifecycle.as_ref()
-    }
-
-    pub fn close(self) -> Result<(), VMError> {
-        Err(VMError::todo(
-            "`close` is not implemented for Single Threaded Process".to_string(),
-        ))
-    }
-
-    pub fn receive(&self, timeout: Option<usize>) -> ObjectValue {
-        VMError::todo("`receive` is not implemented for Single Threaded Process".to_string()).into()
-    }
-
-    pub fn send(&self, args: Vec<ObjectValue>) -> Result<(), VMError> {
-        Err(VMError::todo(
-            "`send` is not implemented for Single Threaded Process".to_string(),
-        ))
+    pub(crate) fn info(&self, pid: usize) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            scope: self.scope.named.clone(),
+            state: self.status.state(),
+            mailbox_depth: 0,
+        }
     }
 }
@@ -0,0 +1,256 @@
+use crate::prelude::*;
+use alloc::collections::VecDeque;
+use rigz_core::{Backpressure, EventLifecycle, ObjectValue, VMError};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct MailboxState {
+    pending: VecDeque<Vec<ObjectValue>>,
+    results: VecDeque<ObjectValue>,
+    // a worker is handling events, it stops once `pending` is empty
+    running: bool,
+    delivered: usize,
+    dropped: usize,
+    restarts: usize,
+}
+
+/// Result of `Mailbox::push`
+#[derive(Debug, PartialEq)]
+pub(crate) enum Delivery {
+    /// no worker is running, the caller must start one
+    Start,
+    Queued,
+    Dropped,
+}
+
+/// Bounded queue of events for an `@on` handler, results are kept in order until received
+#[derive(Debug)]
+pub(crate) struct Mailbox {
+    capacity: usize,
+    overflow: Backpressure,
+    state: Mutex<MailboxState>,
+    // notified when an event is taken or a result is added
+    changed: Condvar,
+}
+
+/// Queue sizes for metrics, `pending` is how far the handler is behind the producers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct MailboxLag {
+    pub(crate) pending: usize,
+    pub(crate) results: usize,
+    pub(crate) delivered: usize,
+    pub(crate) dropped: usize,
+    pub(crate) restarts: usize,
+    pub(crate) running: bool,
+}
+
+impl Mailbox {
+    pub(crate) fn new(lifecycle: &EventLifecycle) -> Self {
+        Self {
+            capacity: lifecycle.capacity.max(1),
+            overflow: lifecycle.overflow,
+            state: Default::default(),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MailboxState> {
+        // a handler panicking doesn't leave the queues in an invalid state
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn push(&self, args: Vec<ObjectValue>) -> Result<Delivery, VMError> {
+        let mut state = self.state();
+        if !state.running {
+            state.running = true;
+            state.pending.push_back(args);
+            return Ok(Delivery::Start);
+        }
+
+        if state.pending.len() >= self.capacity {
+            match self.overflow {
+                Backpressure::Block => {
+                    // the worker keeps running while events are pending, so this always wakes up
+                    while state.pending.len() >= self.capacity {
+                        state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                }
+                Backpressure::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(Delivery::Dropped);
+                }
+                Backpressure::DropOldest => {
+                    state.pending.pop_front();
+                    state.dropped += 1;
+                }
+                Backpressure::Error => {
+                    state.dropped += 1;
+                    return Err(VMError::RuntimeError(format!(
+                        "Mailbox is full, {} events are waiting",
+                        self.capacity
+                    )));
+                }
+            }
+        }
+        state.pending.push_back(args);
+        Ok(Delivery::Queued)
+    }
+
+    /// Next event for the worker, the worker must stop when this returns None
+    pub(crate) fn next_event(&self) -> Option<Vec<ObjectValue>> {
+        let mut state = self.state();
+        let next = state.pending.pop_front();
+        if next.is_none() {
+            state.running = false;
+        }
+        self.changed.notify_all();
+        next
+    }
+
+    pub(crate) fn complete(&self, result: ObjectValue) {
+        let mut state = self.state();
+        state.delivered += 1;
+        state.results.push_back(result);
+        self.changed.notify_all();
+    }
+
+    /// Oldest result that hasn't been received, waits for the handler if it is still running
+    pub(crate) fn receive(&self, timeout: Option<usize>) -> Result<ObjectValue, VMError> {
+        let deadline = timeout.map(|t| (t, Instant::now() + Duration::from_millis(t as u64)));
+        let mut state = self.state();
+        loop {
+            if let Some(r) = state.results.pop_front() {
+                return Ok(r);
+            }
+            if !state.running {
+                return Err(VMError::RuntimeError(
+                    "`receive` has no result waiting, the handler is not running".to_string(),
+                ));
+            }
+            state = match deadline {
+                None => self.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+                Some((time, deadline)) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(VMError::RuntimeError(format!(
+                            "`receive` timed out after {time}ms"
+                        )));
+                    }
+                    self.changed
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+
+    /// Oldest result without waiting, `None` while the handler is still running
+    pub(crate) fn try_receive(&self) -> Result<Option<ObjectValue>, VMError> {
+        let mut state = self.state();
+        match state.results.pop_front() {
+            Some(r) => Ok(Some(r)),
+            None if state.running => Ok(None),
+            None => Err(VMError::RuntimeError(
+                "`receive` has no result waiting, the handler is not running".to_string(),
+            )),
+        }
+    }
+
+    /// Counts an event handled again by the supervisor
+    pub(crate) fn restarted(&self) {
+        self.state().restarts += 1;
+    }
+
+    /// Results nobody received, used when the VM closes
+    pub(crate) fn drain(&self) -> Vec<ObjectValue> {
+        self.state().results.drain(..).collect()
+    }
+
+    pub(crate) fn lag(&self) -> MailboxLag {
+        let state = self.state();
+        MailboxLag {
+            pending: state.pending.len(),
+            results: state.results.len(),
+            delivered: state.delivered,
+            dropped: state.dropped,
+            restarts: state.restarts,
+            running: state.running,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod mailbox_tests {
+    use crate::process::threaded::mailbox::{Delivery, Mailbox};
+    use rigz_core::{Backpressure, EventLifecycle, ObjectValue};
+    use wasm_bindgen_test::*;
+
+    fn mailbox(overflow: Backpressure) -> Mailbox {
+        Mailbox::new(&EventLifecycle {
+            capacity: 1,
+            overflow,
+            ..EventLifecycle::new("message")
+        })
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn drop_newest() {
+        let m = mailbox(Backpressure::DropNewest);
+        assert_eq!(m.push(vec![1.into()]), Ok(Delivery::Start));
+        assert_eq!(m.next_event(), Some(vec![1.into()]));
+        assert_eq!(m.push(vec![2.into()]), Ok(Delivery::Queued));
+        assert_eq!(m.push(vec![3.into()]), Ok(Delivery::Dropped));
+        assert_eq!(m.next_event(), Some(vec![2.into()]));
+        assert_eq!(m.next_event(), None);
+        assert_eq!(m.lag().dropped, 1);
+        assert!(!m.lag().running);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn drop_oldest() {
+        let m = mailbox(Backpressure::DropOldest);
+        assert_eq!(m.push(vec![1.into()]), Ok(Delivery::Start));
+        m.next_event();
+        assert_eq!(m.push(vec![2.into()]), Ok(Delivery::Queued));
+        assert_eq!(m.push(vec![3.into()]), Ok(Delivery::Queued));
+        assert_eq!(m.lag().pending, 1);
+        assert_eq!(m.next_event(), Some(vec![3.into()]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn error_when_full() {
+        let m = mailbox(Backpressure::Error);
+        m.push(vec![]).unwrap();
+        m.next_event();
+        m.push(vec![]).unwrap();
+        assert!(m.push(vec![]).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn receive_in_order() {
+        let m = mailbox(Backpressure::Block);
+        m.push(vec![]).unwrap();
+        m.complete(ObjectValue::from(1));
+        m.complete(ObjectValue::from(2));
+        assert_eq!(m.receive(Some(0)), Ok(1.into()));
+        assert_eq!(m.receive(None), Ok(2.into()));
+        assert!(m.receive(Some(0)).is_err());
+        m.next_event();
+        m.next_event();
+        assert!(m.receive(None).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn try_receive_does_not_wait() {
+        let m = mailbox(Backpressure::Block);
+        m.push(vec![]).unwrap();
+        assert_eq!(m.try_receive(), Ok(None));
+        m.complete(ObjectValue::from(1));
+        assert_eq!(m.try_receive(), Ok(Some(1.into())));
+        m.next_event();
+        m.next_event();
+        assert!(m.try_receive().is_err());
+    }
+}
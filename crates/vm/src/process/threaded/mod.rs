@@ -1,44 +1,196 @@
+mod mailbox;
 mod runner;
+mod scheduler;
 
-use crate::process::ProcessManager;
-use crate::{ModulesMap, Scope, VMOptions};
-use rigz_core::{MutableReference, ObjectValue};
+use crate::prelude::*;
+use crate::process::{ProcessManager, ProcessStatus};
+use crate::{ProcessInfo, ProcessState, Scope, SharedProgram};
+use core::time::Duration;
+pub(crate) use mailbox::{Delivery, Mailbox};
+use rigz_core::{
+    current_context, enter_context, set_output_source, EventLifecycle, Lifecycle, MutableReference,
+    ObjectValue, OutputSource, PrimitiveValue, Restart, RuntimeContext, VMError,
+};
 use runner::ProcessRunner;
+pub(crate) use scheduler::Scheduler;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
 
 #[derive(Debug)]
 pub(crate) struct Process {
     pub scope: Scope,
-    options: VMOptions,
-    modules: ModulesMap,
+    /// Objects, modules, & custom instructions of the VM, object hooks are entered on whichever thread runs the
+    /// process, like `context`
+    program: SharedProgram,
     pub(crate) timeout: Option<usize>,
     process_manager: MutableReference<ProcessManager>,
+    /// `@on` handlers receive events through a bounded queue instead of one run per `send`
+    pub(crate) mailbox: Option<Mailbox>,
+    /// Context of the VM that started the process with its own seeded generator & virtual clock, entered on
+    /// whichever thread runs it
+    context: RuntimeContext,
+    pub(crate) status: ProcessStatus,
 }
 
 impl Process {
     pub(crate) fn new(
         scope: Scope,
-        options: VMOptions,
-        modules: ModulesMap,
+        program: SharedProgram,
         timeout: Option<usize>,
         process_manager: MutableReference<ProcessManager>,
     ) -> Self {
+        let mailbox = match &scope.lifecycle {
+            Some(Lifecycle::On(e)) => Some(Mailbox::new(e)),
+            _ => None,
+        };
         Self {
+            mailbox,
             scope,
-            options,
-            modules,
+            program,
             timeout,
             process_manager,
+            context: current_context().for_process(),
+            status: Default::default(),
         }
     }
 
-    pub(crate) fn run(&self, args: Vec<ObjectValue>) -> ObjectValue {
+    pub(crate) fn info(&self, pid: usize) -> ProcessInfo {
+        let (state, mailbox_depth) = match &self.mailbox {
+            // handlers start a worker per batch of events instead of running once
+            Some(mailbox) => {
+                let lag = mailbox.lag();
+                let state = match self.status.state() {
+                    ProcessState::Cancelled => ProcessState::Cancelled,
+                    _ if lag.running => ProcessState::Running,
+                    _ => ProcessState::Idle,
+                };
+                (state, lag.results)
+            }
+            None => (self.status.state(), self.status.unreceived()),
+        };
+        ProcessInfo {
+            pid,
+            scope: self.scope.named.clone(),
+            state,
+            mailbox_depth,
+        }
+    }
+
+    pub(crate) fn run(&self, pid: usize, args: Vec<ObjectValue>) -> ObjectValue {
+        let _context = enter_context(self.context.clone());
+        let _hooks = self.program.has_hooks().then(|| self.program.enter());
+        // blocking threads are reused so the previous source is restored once finished
+        let previous = set_output_source(OutputSource::process(pid, self.scope.named.as_str()));
+        self.status.start();
         let mut runner = ProcessRunner::new(
             &self.scope,
             args,
-            &self.options,
-            self.modules.clone(),
+            &self.program,
             self.process_manager.clone(),
+            &self.status,
         );
-        runner.run()
+        let result = runner.run();
+        self.status.finish();
+        set_output_source(previous);
+        result
+    }
+
+    /// Runs the scope once for each value, used by the workers of `ProcessManager::parallel`
+    pub(crate) fn run_each(&self, values: Vec<ObjectValue>) -> Vec<ObjectValue> {
+        let _context = enter_context(self.context.clone());
+        let _hooks = self.program.has_hooks().then(|| self.program.enter());
+        let previous = set_output_source(OutputSource::main(self.scope.named.as_str()));
+        let results = values
+            .into_iter()
+            .map(|v| {
+                ProcessRunner::new(
+                    &self.scope,
+                    vec![v],
+                    &self.program,
+                    self.process_manager.clone(),
+                    &self.status,
+                )
+                .run()
+            })
+            .collect();
+        set_output_source(previous);
+        results
+    }
+
+    /// Runs on a `Scheduler` worker as a coroutine, see `ProcessExecutor::Cooperative`
+    pub(crate) async fn run_cooperative(
+        &self,
+        pid: usize,
+        args: Vec<ObjectValue>,
+        quantum: usize,
+    ) -> ObjectValue {
+        self.status.start();
+        let mut runner = ProcessRunner::new(
+            &self.scope,
+            args,
+            &self.program,
+            self.process_manager.clone(),
+            &self.status,
+        );
+        let source = OutputSource::process(pid, self.scope.named.as_str());
+        let result = runner
+            .run_cooperative(source, self.context.clone(), quantum)
+            .await;
+        self.status.finish();
+        result
+    }
+
+    /// Handles queued events until the mailbox is empty, results are received through the mailbox
+    pub(crate) fn run_events(&self, pid: usize) {
+        let Some(mailbox) = &self.mailbox else {
+            return;
+        };
+        while let Some(args) = mailbox.next_event() {
+            mailbox.complete(self.supervise(pid, args));
+        }
+    }
+
+    /// Handles one event of a `detached` handler, failures are logged since nobody receives the result
+    pub(crate) fn run_detached(&self, pid: usize, args: Vec<ObjectValue>) {
+        if let ObjectValue::Primitive(PrimitiveValue::Error(e)) = self.supervise(pid, args) {
+            log::warn!("Process {pid} failed to handle {} - {e}", self.scope.named)
+        }
+    }
+
+    /// Handles the event with a new VM until it succeeds or `EventLifecycle::restart` gives up.
+    /// Panics are returned as errors so the handler keeps taking events
+    fn supervise(&self, pid: usize, args: Vec<ObjectValue>) -> ObjectValue {
+        let Some(Lifecycle::On(lifecycle)) = &self.scope.lifecycle else {
+            return self.run(pid, args);
+        };
+        let mut restarts = 0;
+        let mut backoff = lifecycle.backoff;
+        loop {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| self.run(pid, args.clone())))
+                .unwrap_or_else(|_| {
+                    VMError::RuntimeError(format!(
+                        "Process {pid} panicked while handling {}",
+                        lifecycle.event
+                    ))
+                    .into()
+                });
+            let restart = match lifecycle.restart {
+                Restart::Never => false,
+                Restart::OnFailure => restarts < lifecycle.max_restarts,
+                Restart::Always => true,
+            };
+            if !restart
+                || self.status.is_cancelled()
+                || !matches!(result, ObjectValue::Primitive(PrimitiveValue::Error(_)))
+            {
+                return result;
+            }
+            restarts += 1;
+            if let Some(mailbox) = &self.mailbox {
+                mailbox.restarted();
+            }
+            thread::sleep(Duration::from_millis(backoff as u64));
+            backoff = backoff.saturating_mul(2).min(EventLifecycle::MAX_BACKOFF);
+        }
     }
 }
@@ -1,16 +1,23 @@
 use crate::call_frame::{CallFrame, Frames};
-use crate::process::ProcessManager;
+use crate::prelude::*;
+use crate::process::{run_retryable, ProcessManager, ProcessStatus, RETRY_INTERVAL};
 use crate::{
-    runner_common, CallType, Instruction, ModulesMap, ResolvedModule, Runner, Scope, VMOptions,
-    VMStack, VMState, Variable,
+    runner_common, CallType, Instruction, ModulesMap, Parallel, ResolvedModule, Runner, Scope,
+    SharedProgram, VMOptions, VMStack, VMState, Variable,
 };
+use core::cell::Cell;
+use core::fmt::Display;
+use core::ops::Deref;
 use log_derive::{logfn, logfn_inputs};
-use rigz_core::{MutableReference, ObjectValue, ResolveValue, RigzArgs, StackValue, VMError};
-use std::cell::RefCell;
-use std::fmt::Display;
-use std::ops::Deref;
-use std::rc::Rc;
-use std::time::Duration;
+use rigz_core::{
+    advance_virtual_clock, enter_context, enter_deterministic, set_output_source, AsPrimitive,
+    Interned, MutableReference, ObjectValue, OutputSource, ResolveValue, RigzArgs, RuntimeContext,
+    Shared, StackValue, VMError,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CANCEL_INTERVAL: Duration = Duration::from_millis(10);
 
 pub(crate) struct ProcessRunner<'s> {
     scope: &'s Scope,
@@ -18,7 +25,15 @@ pub(crate) struct ProcessRunner<'s> {
     stack: VMStack,
     options: &'s VMOptions,
     modules: ModulesMap,
+    /// objects, modules, & custom instructions of the VM that started the process
+    program: &'s SharedProgram,
     process_manager: MutableReference<ProcessManager>,
+    /// set by `sleep` while running as a coroutine, the coroutine sleeps instead of the thread
+    pending_sleep: Option<Cell<Option<Duration>>>,
+    /// when the current call started waiting, see `coroutine_wait_start`
+    waiting_since: Option<crate::Instant>,
+    /// checked before each instruction, see `ProcessHandle::cancel`
+    status: &'s ProcessStatus,
 }
 
 #[allow(unused_variables)]
@@ -27,12 +42,12 @@ impl ResolveValue for ProcessRunner<'_> {
         "Process"
     }
 
-    fn handle_scope(&mut self, scope: usize) -> Rc<RefCell<ObjectValue>> {
+    fn handle_scope(&mut self, scope: usize) -> Shared<ObjectValue> {
         let o: ObjectValue = VMError::todo("Process does not implement `handle_scope`").into();
         o.into()
     }
 
-    fn get_constant(&self, constant_id: usize) -> Rc<RefCell<ObjectValue>> {
+    fn get_constant(&self, constant_id: usize) -> Shared<ObjectValue> {
         let o: ObjectValue = VMError::todo("Process does not implement `get_constant`").into();
         o.into()
     }
@@ -42,17 +57,21 @@ impl<'s> ProcessRunner<'s> {
     pub(crate) fn new(
         scope: &'s Scope,
         args: Vec<ObjectValue>,
-        options: &'s VMOptions,
-        modules: ModulesMap,
+        program: &'s SharedProgram,
         process_manager: MutableReference<ProcessManager>,
+        status: &'s ProcessStatus,
     ) -> Self {
         Self {
             scope,
             frames: Default::default(),
             stack: VMStack::new(args.into_iter().map(|v| v.into()).collect()),
-            options,
-            modules,
+            options: &program.options,
+            modules: program.modules.clone(),
+            program,
             process_manager,
+            pending_sleep: None,
+            waiting_since: None,
+            status,
         }
     }
 }
@@ -78,6 +97,10 @@ impl Runner for ProcessRunner<'_> {
         ))
     }
 
+    fn call_once(&mut self, scope_index: usize) -> Result<(), VMError> {
+        Err(VMError::todo("Process does not implement `call_once`"))
+    }
+
     fn goto(&mut self, scope_id: usize, pc: usize) -> Result<(), VMError> {
         Err(VMError::todo("Process does not implement `goto`"))
     }
@@ -90,6 +113,98 @@ impl Runner for ProcessRunner<'_> {
         Err(VMError::todo("Process does not implement `receive`"))
     }
 
+    fn select(&mut self, sources: usize, timeout: bool) -> Result<(), VMError> {
+        Err(VMError::todo("Process does not implement `select`"))
+    }
+
+    /// Coroutines can't block on the workers so they run the function once per value instead
+    fn parallel(&mut self, op: Parallel) -> Result<(), VMError> {
+        let list = self.next_resolved_value("parallel");
+        let values = match list.borrow().deref() {
+            ObjectValue::List(l) => l.clone(),
+            v => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "Cannot call {op} on {v}, expected List"
+                )))
+            }
+        };
+        let program = self.program;
+        let scope = match self.next_value("parallel") {
+            StackValue::ScopeId(s) => match program.scopes.get(s) {
+                None => {
+                    return Err(VMError::ScopeDoesNotExist(format!(
+                        "Scope does not exist - {s}"
+                    )))
+                }
+                Some(s) => s,
+            },
+            v => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "{op} expects a function, received {:?}",
+                    v.resolve(self).borrow()
+                )))
+            }
+        };
+        let results = match self.pending_sleep {
+            Some(_) => values
+                .iter()
+                .map(|v| {
+                    ProcessRunner::new(
+                        scope,
+                        vec![v.clone()],
+                        program,
+                        self.process_manager.clone(),
+                        self.status,
+                    )
+                    .run()
+                })
+                .collect(),
+            // processes run on the blocking threads of the process manager's runtime
+            None => ProcessManager::parallel_on(
+                &tokio::runtime::Handle::current(),
+                &self.process_manager,
+                scope.clone(),
+                values.clone(),
+                program.clone(),
+            )?,
+        };
+        if let Some(e) = results.iter().find(|r| r.is_error()) {
+            self.store_value(e.clone().into());
+            return Ok(());
+        }
+        let value: ObjectValue = match op {
+            Parallel::Map => results.into(),
+            Parallel::Filter => values
+                .into_iter()
+                .zip(results)
+                .filter_map(|(v, keep)| keep.to_bool().then_some(v))
+                .collect::<Vec<_>>()
+                .into(),
+            Parallel::Each => ObjectValue::default(),
+        };
+        self.store_value(value.into());
+        Ok(())
+    }
+
+    fn custom(&mut self, opcode: usize, args: usize) -> Result<(), VMError> {
+        let Some(custom) = self.program.custom_instructions.get(opcode) else {
+            return Err(VMError::RuntimeError(format!(
+                "Custom instruction {opcode} is not registered"
+            )));
+        };
+        if self.options.deterministic && !custom.deterministic {
+            return Err(VMError::UnsupportedOperation(format!(
+                "{} is nondeterministic, it can't be used with VMOptions::deterministic",
+                custom.module
+            )));
+        }
+        let handler = custom.handler;
+        let args = self.resolve_args(args).into();
+        let v = handler(args).unwrap_or_else(|e| e.into());
+        self.store_value(v.into());
+        Ok(())
+    }
+
     fn spawn(&mut self, scope_id: usize, timeout: Option<usize>) -> Result<(), VMError> {
         Err(VMError::todo("Process does not implement `spawn`"))
     }
@@ -100,7 +215,8 @@ impl Runner for ProcessRunner<'_> {
         func: String,
         args: usize,
     ) -> Result<ObjectValue, VMError> {
-        Err(VMError::todo("Process does not implement `call`"))
+        let args = self.resolve_args(args).into();
+        module.call(func, args)
     }
 
     // fn vm_extension(
@@ -112,58 +228,184 @@ impl Runner for ProcessRunner<'_> {
     //     Err(VMError::todo("Process does not implement `vm_extension`"))
     // }
 
-    fn sleep(&self, duration: Duration) {
-        #[cfg(feature = "threaded")]
-        self.process_manager
-            .apply(move |pm| pm.handle.block_on(tokio::time::sleep(duration)));
+    fn sleep(&mut self, duration: Duration) {
+        if advance_virtual_clock(duration.as_nanos() as i64) {
+            return;
+        }
+        if let Some(pending) = &self.pending_sleep {
+            pending.set(Some(duration));
+            return;
+        }
 
-        #[cfg(not(feature = "threaded"))]
-        thread::sleep(duration)
+        // processes run on blocking threads, locking the process manager here would deadlock with `close`.
+        // Sleeps in slices so a cancelled process stops without waiting out the duration
+        let deadline = Instant::now() + duration;
+        while !self.status.is_cancelled() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            thread::sleep(remaining.min(CANCEL_INTERVAL))
+        }
     }
 
     fn call_dependency(
         &mut self,
-        arg: RigzArgs,
+        args: RigzArgs,
         dep: usize,
         call_type: CallType,
     ) -> Result<ObjectValue, VMError> {
-        Err(VMError::todo(format!(
-            "Process does not implement call dependency {dep}"
-        )))
+        let Some(dep) = self.program.dependencies.get(dep) else {
+            return Err(VMError::RuntimeError(format!("Dependency not found {dep}")));
+        };
+        match call_type {
+            CallType::Create => Ok((dep.create)(args)?.into()),
+            CallType::Call(func) => (dep.call)(func, args),
+        }
     }
 }
 
 impl ProcessRunner<'_> {
     pub fn run(&mut self) -> ObjectValue {
-        for (arg, mutable) in self.scope.args.clone() {
-            let v = if mutable {
-                self.load_mut(arg)
-            } else {
-                self.load_let(arg)
-            };
-            if let Err(e) = v {
-                return e.into();
+        let _deterministic = self
+            .options
+            .deterministic
+            .then(|| enter_deterministic(self.options.seed));
+        if let Err(e) = self.load_args() {
+            return e.into();
+        }
+        loop {
+            if let Some(v) = self.step() {
+                return v;
             }
         }
+    }
 
+    /// Runs as a coroutine of `ProcessExecutor::Cooperative`, other coroutines on the thread run after every
+    /// `quantum` instructions & while this one sleeps
+    pub async fn run_cooperative(
+        &mut self,
+        source: OutputSource,
+        context: RuntimeContext,
+        quantum: usize,
+    ) -> ObjectValue {
+        self.pending_sleep = Some(Cell::new(None));
+        let previous = set_output_source(source.clone());
+        let loaded = {
+            let _context = enter_context(context.clone());
+            self.load_args()
+        };
+        set_output_source(previous);
+        if let Err(e) = loaded {
+            return e.into();
+        }
+
+        let program = self.program;
         loop {
-            let pc = self.frames.current.borrow().pc;
-            if pc >= self.scope.instructions.len() {
-                break;
+            // other coroutines on this thread set their own source, context, & object hooks between slices
+            let previous = set_output_source(source.clone());
+            let mut result = None;
+            {
+                let _context = enter_context(context.clone());
+                let _hooks = program.has_hooks().then(|| program.enter());
+                for _ in 0..quantum.max(1) {
+                    result = self.step();
+                    if result.is_some() || self.sleeping() {
+                        break;
+                    }
+                }
+            }
+            set_output_source(previous);
+            if let Some(v) = result {
+                return v;
+            }
+            match self.pending_sleep.as_ref().and_then(Cell::take) {
+                Some(duration) => {
+                    let deadline = tokio::time::Instant::now() + duration;
+                    while !self.status.is_cancelled() && tokio::time::Instant::now() < deadline {
+                        let next = tokio::time::Instant::now() + CANCEL_INTERVAL;
+                        tokio::time::sleep_until(deadline.min(next)).await
+                    }
+                }
+                None => tokio::task::yield_now().await,
             }
-            let instruction = self.scope.instructions[pc].clone();
-            self.frames.current.borrow_mut().pc += 1;
-            let state: VMState = if let Instruction::Ret = instruction {
-                VMState::Ran(self.stack.next_value("process_run").resolve(self))
+        }
+    }
+
+    /// Runs a call of a coroutine, calls that would block restore the stack & run again after the coroutine
+    /// sleeps for `RETRY_INTERVAL`, returns None in that case
+    fn retryable_call(&mut self, pc: usize, instruction: Instruction) -> Option<VMState> {
+        let stack: Vec<_> = self.stack.iter().cloned().collect();
+        let since = self.waiting_since.unwrap_or_else(crate::Instant::now);
+        let mut state = None;
+        if run_retryable(since, || {
+            state = Some(self.process_core_instruction(instruction))
+        }) {
+            self.stack = VMStack::new(stack);
+            self.frames.current.borrow_mut().pc = pc;
+            self.waiting_since = Some(since);
+            if let Some(pending) = &self.pending_sleep {
+                pending.set(Some(RETRY_INTERVAL));
+            }
+            return None;
+        }
+        self.waiting_since = None;
+        state
+    }
+
+    #[inline]
+    fn sleeping(&self) -> bool {
+        self.pending_sleep
+            .as_ref()
+            .is_some_and(|p| p.get().is_some())
+    }
+
+    fn load_args(&mut self) -> Result<(), VMError> {
+        for (arg, mutable) in self.scope.args.clone() {
+            if mutable {
+                self.load_mut(arg)?;
             } else {
-                self.process_core_instruction(instruction)
-            };
+                self.load_let(arg)?;
+            }
+        }
+        Ok(())
+    }
 
-            match state {
-                VMState::Running => {}
-                VMState::Done(v) | VMState::Ran(v) => return v.borrow().clone(),
+    /// Runs the next instruction, returns the result once the process is finished
+    fn step(&mut self) -> Option<ObjectValue> {
+        if self.status.is_cancelled() {
+            return Some(VMError::RuntimeError("Process was cancelled".to_string()).into());
+        }
+        let pc = self.frames.current.borrow().pc;
+        if pc >= self.scope.instructions.len() {
+            return Some(VMError::RuntimeError("No return found in scope".to_string()).into());
+        }
+        let instruction = self.scope.instructions[pc].clone();
+        self.frames.current.borrow_mut().pc += 1;
+        let state: VMState = match instruction {
+            Instruction::Ret => VMState::Ran(self.stack.next_value("process_run").resolve(self)),
+            instruction if self.pending_sleep.is_some() && is_call(&instruction) => {
+                self.retryable_call(pc, instruction)?
             }
+            instruction => self.process_core_instruction(instruction),
+        };
+
+        match state {
+            VMState::Running => None,
+            VMState::Done(v) | VMState::Ran(v) => Some(v.borrow().clone()),
         }
-        VMError::RuntimeError("No return found in scope".to_string()).into()
     }
 }
+
+/// Module & object calls, the only instructions that can wait for another process
+fn is_call(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::CallModule { .. }
+            | Instruction::CallExtension { .. }
+            | Instruction::CallMutableExtension { .. }
+            | Instruction::CallObject { .. }
+            | Instruction::CallObjectExtension { .. }
+            | Instruction::CallMutableObjectExtension { .. }
+    )
+}
@@ -0,0 +1,67 @@
+use crate::prelude::*;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rigz_core::VMError;
+use std::thread;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Creates the coroutine on the worker thread, process runners aren't `Send`
+pub(crate) type Job = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+/// Worker threads of `ProcessExecutor::Cooperative`, each runs its coroutines on a single threaded runtime.
+/// Workers stop once the scheduler is dropped and their coroutines have finished.
+#[derive(Debug)]
+pub(crate) struct Scheduler {
+    workers: Vec<UnboundedSender<Job>>,
+    next: AtomicUsize,
+}
+
+impl Scheduler {
+    pub(crate) fn new(workers: usize) -> Result<Self, VMError> {
+        let workers = (0..workers.max(1))
+            .map(|index| {
+                let (sender, receiver) = unbounded_channel();
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .build()
+                    .map_err(|e| {
+                        VMError::RuntimeError(format!("Failed to create process worker {e}"))
+                    })?;
+                thread::Builder::new()
+                    .name(format!("rigz-process-{index}"))
+                    .spawn(move || work(runtime, receiver))
+                    .map_err(|e| {
+                        VMError::RuntimeError(format!("Failed to start process worker {e}"))
+                    })?;
+                Ok(sender)
+            })
+            .collect::<Result<_, VMError>>()?;
+        Ok(Scheduler {
+            workers,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub(crate) fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Jobs are assigned to workers round robin
+    pub(crate) fn schedule(&self, job: Job) -> Result<(), VMError> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        self.workers[index]
+            .send(job)
+            .map_err(|_| VMError::RuntimeError(format!("Process worker {index} stopped")))
+    }
+}
+
+fn work(runtime: tokio::runtime::Runtime, mut receiver: UnboundedReceiver<Job>) {
+    let local = tokio::task::LocalSet::new();
+    local.spawn_local(async move {
+        while let Some(job) = receiver.recv().await {
+            tokio::task::spawn_local(job());
+        }
+    });
+    runtime.block_on(local);
+}
@@ -1,15 +1,18 @@
+use crate::prelude::*;
 use crate::Instruction;
-use rigz_core::{Lifecycle, Snapshot, VMError};
-use std::fmt::Display;
-use std::vec::IntoIter;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
+use rigz_core::{Interned, Lifecycle, Snapshot, VMError};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Scope {
     pub instructions: Vec<Instruction>,
     pub lifecycle: Option<Lifecycle>,
     pub named: String,
-    pub args: Vec<(String, bool)>,
+    pub args: Vec<(Interned, bool)>,
     pub set_self: Option<bool>,
+    /// (first instruction, source line) pairs sorted by instruction, empty when no debug info was recorded
+    pub lines: Vec<(usize, usize)>,
 }
 
 impl Default for Scope {
@@ -20,6 +23,7 @@ impl Default for Scope {
             lifecycle: None,
             args: vec![],
             set_self: None,
+            lines: vec![],
         }
     }
 }
@@ -31,6 +35,7 @@ impl Snapshot for Scope {
         res.extend(self.lifecycle.as_bytes());
         res.extend(self.args.as_bytes());
         res.extend(self.set_self.as_bytes());
+        res.extend(self.lines.as_bytes());
         res
     }
 
@@ -40,22 +45,29 @@ impl Snapshot for Scope {
         let lifecycle = Snapshot::from_bytes(bytes, location)?;
         let args = Snapshot::from_bytes(bytes, location)?;
         let set_self = Snapshot::from_bytes(bytes, location)?;
+        let lines = Snapshot::from_bytes(bytes, location)?;
         Ok(Scope {
             instructions,
             lifecycle,
             named,
             args,
             set_self,
+            lines,
         })
     }
 }
 
 impl Scope {
+    /// Names of scopes created for blocks (`do`, `if`, loops, ...) rather than functions
+    pub const BLOCK_NAMES: [&'static str; 8] = [
+        "do", "if", "else", "unless", "with", "for-list", "for-map", "catch",
+    ];
+
     #[inline]
     pub fn new(named: String, args: Vec<(String, bool)>, set_self: Option<bool>) -> Self {
         Scope {
             named,
-            args,
+            args: interned_args(args),
             set_self,
             ..Default::default()
         }
@@ -71,9 +83,61 @@ impl Scope {
         Scope {
             lifecycle: Some(lifecycle),
             named,
-            args,
+            args: interned_args(args),
             set_self,
             ..Default::default()
         }
     }
+
+    /// Records `line` for the next instruction added to this scope
+    #[inline]
+    pub fn set_line(&mut self, line: usize) {
+        let pc = self.instructions.len();
+        match self.lines.last_mut() {
+            Some((_, l)) if *l == line => {}
+            Some((p, l)) if *p == pc => *l = line,
+            _ => self.lines.push((pc, line)),
+        }
+    }
+
+    #[inline]
+    pub fn is_block(&self) -> bool {
+        Self::BLOCK_NAMES.contains(&self.named.as_str())
+    }
+
+    /// Source line of the instruction at `pc`
+    pub fn line(&self, pc: usize) -> Option<usize> {
+        match self.lines.partition_point(|(p, _)| *p <= pc) {
+            0 => None,
+            i => Some(self.lines[i - 1].1),
+        }
+    }
+}
+
+#[inline]
+fn interned_args(args: Vec<(String, bool)>) -> Vec<(Interned, bool)> {
+    args.into_iter().map(|(a, m)| (a.into(), m)).collect()
+}
+
+#[cfg(test)]
+pub mod scope_tests {
+    use crate::{Instruction, Scope};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn line_table() {
+        let mut scope = Scope::default();
+        scope.set_line(2);
+        scope.instructions.push(Instruction::Load(1.into()));
+        scope.instructions.push(Instruction::Load(2.into()));
+        scope.set_line(2);
+        scope.set_line(4);
+        scope.instructions.push(Instruction::Halt);
+        assert_eq!(scope.lines, vec![(0, 2), (2, 4)]);
+        assert_eq!(scope.line(0), Some(2));
+        assert_eq!(scope.line(1), Some(2));
+        assert_eq!(scope.line(2), Some(4));
+        assert_eq!(scope.line(10), Some(4));
+        assert_eq!(Scope::default().line(0), None);
+    }
 }
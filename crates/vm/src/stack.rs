@@ -1,6 +1,7 @@
+use crate::prelude::*;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
 use rigz_core::{Snapshot, StackValue, VMError};
-use std::fmt::Display;
-use std::vec::IntoIter;
 
 #[derive(Debug, Default)]
 pub struct VMStack(Vec<StackValue>);
@@ -21,11 +22,27 @@ impl VMStack {
         VMStack(stack)
     }
 
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     #[inline]
     pub fn clear(&mut self) {
         self.0.clear()
     }
 
+    /// Drops every value above `len`, no-op when the stack is already shorter
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
     #[inline]
     pub fn pop(&mut self) -> Option<StackValue> {
         self.0.pop()
@@ -41,6 +58,11 @@ impl VMStack {
         self.push(value)
     }
 
+    #[inline]
+    pub fn iter(&self) -> core::slice::Iter<'_, StackValue> {
+        self.0.iter()
+    }
+
     #[inline]
     pub fn last(&self) -> Option<&StackValue> {
         self.0.last()
@@ -0,0 +1,88 @@
+use crate::prelude::*;
+use crate::VM;
+use core::fmt::{Display, Formatter, Write};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionCost {
+    pub scope_id: usize,
+    pub name: String,
+    /// instructions in the function's scope, loops & blocks it runs in their own scope aren't included
+    pub instructions: usize,
+    /// calls recorded while `VMOptions::profile` was set
+    pub calls: u64,
+    /// instructions run per call, including the scopes it called, `None` until the function is called while profiling
+    pub average: Option<f64>,
+}
+
+/// Created by `VM::cost_report`, functions are sorted by average cost then instructions, most expensive first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CostReport {
+    pub functions: Vec<FunctionCost>,
+}
+
+impl CostReport {
+    pub fn get(&self, name: &str) -> Option<&FunctionCost> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:>12} {:>8} {:>12}  function",
+            "instructions", "calls", "average"
+        );
+        for function in &self.functions {
+            let average = function
+                .average
+                .map_or_else(|| "-".to_string(), |a| format!("{a:.1}"));
+            let _ = writeln!(
+                out,
+                "{:>12} {:>8} {:>12}  {} ({})",
+                function.instructions, function.calls, average, function.name, function.scope_id
+            );
+        }
+        out
+    }
+}
+
+impl Display for CostReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.to_table())
+    }
+}
+
+impl VM {
+    /// Static instruction counts of each function, averages use the calls & instructions recorded while
+    /// `VMOptions::profile` is set
+    pub fn cost_report(&self) -> CostReport {
+        let profile = self.profile_report();
+        let mut functions: Vec<_> = self
+            .scopes
+            .iter()
+            .enumerate()
+            .filter(|(scope_id, scope)| *scope_id != 0 && !scope.is_block())
+            .map(|(scope_id, scope)| {
+                let profiled = profile.scopes.iter().find(|s| s.scope_id == scope_id);
+                let calls = profiled.map_or(0, |s| s.calls);
+                FunctionCost {
+                    scope_id,
+                    name: scope.named.clone(),
+                    instructions: scope.instructions.len(),
+                    calls,
+                    average: profiled
+                        .filter(|_| calls > 0)
+                        .map(|s| s.instructions as f64 / calls as f64),
+                }
+            })
+            .collect();
+        functions.sort_by(|a, b| {
+            b.average
+                .unwrap_or(-1.0)
+                .total_cmp(&a.average.unwrap_or(-1.0))
+                .then(b.instructions.cmp(&a.instructions))
+                .then(a.scope_id.cmp(&b.scope_id))
+        });
+        CostReport { functions }
+    }
+}
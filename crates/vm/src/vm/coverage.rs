@@ -0,0 +1,138 @@
+use crate::prelude::*;
+use crate::VM;
+use alloc::collections::BTreeMap;
+use core::fmt::Write;
+
+/// Collected while `VMOptions::coverage` is set, the number of times each instruction of a scope was run
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Coverage {
+    hits: HashMap<usize, Vec<u64>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScopeCoverage {
+    pub scope_id: usize,
+    pub name: String,
+    /// false for the main scope & blocks
+    pub function: bool,
+    /// times the scope's first instruction was run
+    pub calls: u64,
+    pub instructions: usize,
+    pub executed_instructions: usize,
+    /// hit count of each source line in the scope's line table, sorted by line
+    pub lines: Vec<(usize, u64)>,
+}
+
+/// Created by `VM::coverage_report`, only scopes with a line table are included
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub scopes: Vec<ScopeCoverage>,
+}
+
+impl CoverageReport {
+    /// Hit count of each source line, lines shared by several scopes use the highest count
+    pub fn lines(&self) -> BTreeMap<usize, u64> {
+        let mut lines = BTreeMap::new();
+        for scope in &self.scopes {
+            for &(line, hits) in &scope.lines {
+                let count = lines.entry(line).or_default();
+                *count = hits.max(*count);
+            }
+        }
+        lines
+    }
+
+    /// Percentage of lines that were run at least once, 100 when there are no lines
+    pub fn line_rate(&self) -> f64 {
+        let lines = self.lines();
+        if lines.is_empty() {
+            return 100.0;
+        }
+        let hit = lines.values().filter(|h| **h > 0).count();
+        hit as f64 * 100.0 / lines.len() as f64
+    }
+
+    /// A single lcov record for `source_file`, read by genhtml, codecov, & most editors
+    pub fn to_lcov(&self, source_file: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "TN:");
+        let _ = writeln!(out, "SF:{source_file}");
+        let functions: Vec<_> = self.scopes.iter().filter(|s| s.function).collect();
+        for scope in &functions {
+            let _ = writeln!(out, "FN:{},{}", scope.lines[0].0, scope.name);
+        }
+        for scope in &functions {
+            let _ = writeln!(out, "FNDA:{},{}", scope.calls, scope.name);
+        }
+        let _ = writeln!(out, "FNF:{}", functions.len());
+        let _ = writeln!(
+            out,
+            "FNH:{}",
+            functions.iter().filter(|s| s.calls > 0).count()
+        );
+        let lines = self.lines();
+        for (line, hits) in &lines {
+            let _ = writeln!(out, "DA:{line},{hits}");
+        }
+        let _ = writeln!(out, "LF:{}", lines.len());
+        let _ = writeln!(out, "LH:{}", lines.values().filter(|h| **h > 0).count());
+        let _ = writeln!(out, "end_of_record");
+        out
+    }
+}
+
+impl VM {
+    #[inline]
+    pub(crate) fn cover_instruction(&mut self) {
+        // next_instruction already moved pc to the following instruction
+        let pc = self.frames.current.borrow().pc.saturating_sub(1);
+        let hits = self.coverage.hits.entry(self.sp).or_insert_with(|| {
+            let len = self.scopes.get(self.sp).map_or(0, |s| s.instructions.len());
+            vec![0; len]
+        });
+        if let Some(hits) = hits.get_mut(pc) {
+            *hits += 1;
+        }
+    }
+
+    /// Instructions & lines run while `VMOptions::coverage` is set, lines come from each scope's line table
+    pub fn coverage_report(&self) -> CoverageReport {
+        let scopes = self
+            .scopes
+            .iter()
+            .enumerate()
+            .filter(|(_, scope)| !scope.lines.is_empty())
+            .map(|(scope_id, scope)| {
+                let hits = self.coverage.hits.get(&scope_id);
+                let hit = |pc: usize| hits.and_then(|h| h.get(pc)).copied().unwrap_or_default();
+                let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+                for (index, &(start, line)) in scope.lines.iter().enumerate() {
+                    let end = scope
+                        .lines
+                        .get(index + 1)
+                        .map_or(scope.instructions.len(), |(next, _)| *next);
+                    let hits = (start..end).map(hit).max().unwrap_or_default();
+                    let count = lines.entry(line).or_default();
+                    *count = hits.max(*count);
+                }
+                ScopeCoverage {
+                    scope_id,
+                    name: scope.named.clone(),
+                    function: scope_id != 0 && !scope.is_block(),
+                    calls: hit(0),
+                    instructions: scope.instructions.len(),
+                    executed_instructions: (0..scope.instructions.len())
+                        .filter(|pc| hit(*pc) > 0)
+                        .count(),
+                    lines: lines.into_iter().collect(),
+                }
+            })
+            .collect();
+        CoverageReport { scopes }
+    }
+
+    /// Clears hit counts collected by `VMOptions::coverage`
+    pub fn reset_coverage(&mut self) {
+        self.coverage = Coverage::default();
+    }
+}
@@ -0,0 +1,311 @@
+use crate::prelude::*;
+use crate::{Instruction, Runner, VMState, Variable, VM};
+use core::fmt::{Debug, Formatter};
+use core::sync::atomic::{AtomicBool, Ordering};
+use rigz_core::{ObjectValue, StackValue, TraceFrame, VMError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    Breakpoint,
+    /// `VM::pause` or `DebugHandle::pause` was called
+    Paused,
+    /// the previous hook returned `DebugAction::Step`
+    Step,
+}
+
+/// Returned by the breakpoint hook to decide how the VM continues
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugAction {
+    Continue,
+    /// pause again before the next instruction
+    Step,
+    /// stop the VM with an error
+    Stop,
+}
+
+/// Variable in the current call frame. Values that haven't been evaluated yet (scopes) are `None`,
+/// reading them would run the scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Local {
+    pub name: String,
+    pub mutable: bool,
+    pub value: Option<ObjectValue>,
+}
+
+/// Everything passed to the hook, the instruction at `scope_id`, `pc` has not run yet
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugState {
+    pub reason: PauseReason,
+    pub scope_id: usize,
+    pub pc: usize,
+    pub instruction: Instruction,
+    /// Only available when the program was parsed with line tables
+    pub line: Option<usize>,
+    /// innermost frame first
+    pub frames: Vec<TraceFrame>,
+    pub locals: Vec<Local>,
+    /// bottom of the stack first, unevaluated values are `None`
+    pub stack: Vec<Option<ObjectValue>>,
+}
+
+#[derive(Debug, Default)]
+struct DebugShared {
+    requested: AtomicBool,
+    lock: Mutex<()>,
+    resumed: Condvar,
+    breakpoints: RwLock<HashSet<(usize, usize)>>,
+    // mirrors `!breakpoints.is_empty()` so the VM doesn't take the lock for every instruction
+    has_breakpoints: AtomicBool,
+}
+
+/// Pauses the VM or changes its breakpoints from another thread or from the breakpoint hook,
+/// the VM stops before its next instruction
+#[derive(Clone, Debug, Default)]
+pub struct DebugHandle(Arc<DebugShared>);
+
+impl DebugHandle {
+    pub fn pause(&self) {
+        self.0.requested.store(true, Ordering::Release);
+    }
+
+    /// Wakes a VM waiting without a hook, otherwise cancels a pause that wasn't reached yet
+    pub fn resume(&self) {
+        let _lock = self.0.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.0.requested.store(false, Ordering::Release);
+        self.0.resumed.notify_all();
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.0.requested.load(Ordering::Acquire)
+    }
+
+    /// Pauses before the instruction at `pc` in `scope` runs
+    pub fn set_breakpoint(&self, scope: usize, pc: usize) {
+        self.update_breakpoints(|b| {
+            b.insert((scope, pc));
+        })
+    }
+
+    /// Returns false if there was no breakpoint at this location
+    pub fn remove_breakpoint(&self, scope: usize, pc: usize) -> bool {
+        let mut removed = false;
+        self.update_breakpoints(|b| removed = b.remove(&(scope, pc)));
+        removed
+    }
+
+    pub fn clear_breakpoints(&self) {
+        self.update_breakpoints(|b| b.clear())
+    }
+
+    /// Sorted by scope, then instruction
+    pub fn breakpoints(&self) -> Vec<(usize, usize)> {
+        let breakpoints = self.0.breakpoints.read().unwrap_or_else(|e| e.into_inner());
+        let mut breakpoints: Vec<_> = breakpoints.iter().copied().collect();
+        breakpoints.sort();
+        breakpoints
+    }
+
+    #[inline]
+    fn has_breakpoints(&self) -> bool {
+        self.0.has_breakpoints.load(Ordering::Acquire)
+    }
+
+    fn is_breakpoint(&self, scope: usize, pc: usize) -> bool {
+        let breakpoints = self.0.breakpoints.read().unwrap_or_else(|e| e.into_inner());
+        breakpoints.contains(&(scope, pc))
+    }
+
+    fn update_breakpoints(&self, update: impl FnOnce(&mut HashSet<(usize, usize)>)) {
+        let mut breakpoints = self
+            .0
+            .breakpoints
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        update(&mut breakpoints);
+        self.0
+            .has_breakpoints
+            .store(!breakpoints.is_empty(), Ordering::Release);
+    }
+
+    fn wait(&self) {
+        let mut lock = self.0.lock.lock().unwrap_or_else(|e| e.into_inner());
+        while self.is_paused() {
+            lock = self.0.resumed.wait(lock).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+#[cfg(not(feature = "send"))]
+pub type BreakpointHook = Box<dyn FnMut(&DebugState) -> DebugAction>;
+
+/// Hooks move with the VM, so they must be `Send` too
+#[cfg(feature = "send")]
+pub type BreakpointHook = Box<dyn FnMut(&DebugState) -> DebugAction + Send>;
+
+#[derive(Default)]
+pub(crate) struct Debugger {
+    handle: DebugHandle,
+    hook: Option<BreakpointHook>,
+    stepping: bool,
+    // set by `DebugAction::Stop`, every instruction fails until the VM runs again so nested scopes stop too
+    pub(crate) stopped: bool,
+}
+
+impl Debug for Debugger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("handle", &self.handle)
+            .field("hook", &self.hook.is_some())
+            .field("stepping", &self.stepping)
+            .field("stopped", &self.stopped)
+            .finish()
+    }
+}
+
+impl Debugger {
+    #[inline]
+    pub(crate) fn active(&self) -> bool {
+        self.stepping || self.stopped || self.handle.has_breakpoints() || self.handle.is_paused()
+    }
+}
+
+impl VM {
+    /// Pauses before the instruction at `pc` in `scope` runs, see `VM::on_break`
+    pub fn set_breakpoint(&mut self, scope: usize, pc: usize) {
+        self.debugger.handle.set_breakpoint(scope, pc)
+    }
+
+    /// Returns false if there was no breakpoint at this location
+    pub fn remove_breakpoint(&mut self, scope: usize, pc: usize) -> bool {
+        self.debugger.handle.remove_breakpoint(scope, pc)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.handle.clear_breakpoints()
+    }
+
+    /// Called when the VM pauses, the VM continues based on the returned action.
+    /// Without a hook the VM blocks until `resume` is called from another thread.
+    #[cfg(not(feature = "send"))]
+    pub fn on_break<F: FnMut(&DebugState) -> DebugAction + 'static>(&mut self, hook: F) {
+        self.debugger.hook = Some(Box::new(hook));
+    }
+
+    /// Called when the VM pauses, the VM continues based on the returned action.
+    /// Without a hook the VM blocks until `resume` is called from another thread.
+    #[cfg(feature = "send")]
+    pub fn on_break<F: FnMut(&DebugState) -> DebugAction + Send + 'static>(&mut self, hook: F) {
+        self.debugger.hook = Some(Box::new(hook));
+    }
+
+    /// The VM pauses before its next instruction
+    pub fn pause(&self) {
+        self.debugger.handle.pause()
+    }
+
+    pub fn resume(&self) {
+        self.debugger.handle.resume()
+    }
+
+    /// Used to pause, resume, or change breakpoints while the VM runs on another thread or from the hook
+    pub fn debug_handle(&self) -> DebugHandle {
+        self.debugger.handle.clone()
+    }
+
+    /// Values are read without evaluating scopes, the VM state isn't changed
+    fn peek_value(&self, value: &StackValue) -> Option<ObjectValue> {
+        match value {
+            StackValue::Value(v) => Some(v.borrow().clone()),
+            StackValue::Constant(c) => self.constants.get(*c).cloned(),
+            StackValue::ScopeId(_) => None,
+            StackValue::Lazy(t) => t.value(),
+        }
+    }
+
+    fn debug_state(&self, reason: PauseReason, pc: usize, instruction: &Instruction) -> DebugState {
+        let locals = self
+            .frames
+            .current
+            .borrow()
+            .variables
+            .iter()
+            .map(|(name, v)| {
+                let (mutable, value) = match v {
+                    Variable::Let(v) => (false, v),
+                    Variable::Mut(v) => (true, v),
+                };
+                Local {
+                    name: name.to_string(),
+                    mutable,
+                    value: self.peek_value(value),
+                }
+            })
+            .collect();
+        DebugState {
+            reason,
+            scope_id: self.sp,
+            pc,
+            instruction: instruction.clone(),
+            line: self.scopes[self.sp].line(pc),
+            frames: self.traceback(),
+            locals,
+            stack: self.stack.iter().map(|v| self.peek_value(v)).collect(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn check_breakpoint(&mut self, instruction: &Instruction) -> Option<VMState> {
+        if !self.debugger.active() {
+            return None;
+        }
+        if self.debugger.stopped {
+            return Some(Self::stopped());
+        }
+        // next_instruction already moved pc to the following instruction
+        let pc = self.frames.current.borrow().pc.saturating_sub(1);
+        let reason = if self.debugger.handle.is_paused() {
+            PauseReason::Paused
+        } else if self.debugger.stepping {
+            PauseReason::Step
+        } else if self.debugger.handle.is_breakpoint(self.sp, pc) {
+            PauseReason::Breakpoint
+        } else {
+            return None;
+        };
+        self.debugger.stepping = false;
+
+        let action = match self.debugger.hook.take() {
+            None => {
+                self.debugger.handle.pause();
+                self.debugger.handle.wait();
+                DebugAction::Continue
+            }
+            Some(mut hook) => {
+                let state = self.debug_state(reason, pc, instruction);
+                let action = hook(&state);
+                self.debugger.hook = Some(hook);
+                self.debugger.handle.resume();
+                action
+            }
+        };
+
+        match action {
+            DebugAction::Continue => None,
+            DebugAction::Step => {
+                self.debugger.stepping = true;
+                None
+            }
+            DebugAction::Stop => {
+                self.debugger.stopped = true;
+                Some(Self::stopped())
+            }
+        }
+    }
+
+    fn stopped() -> VMState {
+        let e: ObjectValue = VMError::RuntimeError("Stopped by debugger".to_string()).into();
+        VMState::Done(e.into())
+    }
+}
@@ -0,0 +1,247 @@
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::CallFrame;
+use crate::{CustomInstruction, ModulesMap, Scope, VMOptions, VM};
+use alloc::sync::Arc;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
+use rigz_core::{Dependency, IndexMap, ObjectValue, Snapshot, VMError};
+#[cfg(feature = "std")]
+use {
+    alloc::rc::Rc,
+    core::{cell::RefCell, ptr},
+    rigz_core::{
+        enter_object_hooks, with_local, AsPrimitive, ObjectHooks, ObjectHooksGuard, RigzObject,
+        RuntimeContext, StackValue,
+    },
+};
+
+/// Scopes of an object's `fn Self.hash -> Int` & `fn Self.eq(other) -> Bool`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ObjectHookScopes {
+    pub hash: Option<usize>,
+    pub eq: Option<usize>,
+}
+
+impl Snapshot for ObjectHookScopes {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut res = self.hash.as_bytes();
+        res.extend(self.eq.as_bytes());
+        res
+    }
+
+    fn from_bytes<D: Display>(bytes: &mut IntoIter<u8>, location: &D) -> Result<Self, VMError> {
+        let hash = Snapshot::from_bytes(bytes, location)?;
+        let eq = Snapshot::from_bytes(bytes, location)?;
+        Ok(ObjectHookScopes { hash, eq })
+    }
+}
+
+/// Scopes, modules, & constants of the VM, cached by the VM & shared with its processes & object hooks.
+/// Built again once scopes, constants, or modules are added or replaced
+#[derive(Debug)]
+// only hashes & compares objects with std, see `enter`
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) struct VMProgram {
+    hooks: IndexMap<String, ObjectHookScopes>,
+    version: ProgramVersion,
+    pub(crate) scopes: Vec<Scope>,
+    pub(crate) modules: ModulesMap,
+    pub(crate) dependencies: Vec<Arc<Dependency>>,
+    pub(crate) options: VMOptions,
+    constants: Vec<ObjectValue>,
+    pub(crate) custom_instructions: Vec<CustomInstruction>,
+    #[cfg(feature = "std")]
+    context: RuntimeContext,
+}
+
+/// Processes share the program across threads with `threaded`, otherwise it stays on the VM's thread
+#[cfg(feature = "threaded")]
+pub(crate) type SharedProgram = Arc<VMProgram>;
+
+#[cfg(not(feature = "threaded"))]
+pub(crate) type SharedProgram = alloc::rc::Rc<VMProgram>;
+
+#[cfg(feature = "threaded")]
+type WeakProgram = alloc::sync::Weak<VMProgram>;
+
+#[cfg(all(feature = "std", not(feature = "threaded")))]
+type WeakProgram = alloc::rc::Weak<VMProgram>;
+
+#[derive(Clone, Debug, PartialEq)]
+struct ProgramVersion {
+    scopes: usize,
+    constants: usize,
+    modules: usize,
+    dependencies: usize,
+    custom_instructions: usize,
+    options: VMOptions,
+}
+
+impl ProgramVersion {
+    fn of(vm: &VM) -> Self {
+        ProgramVersion {
+            scopes: vm.scopes.len(),
+            constants: vm.constants.len(),
+            modules: vm.modules.len(),
+            dependencies: vm.dependencies.len(),
+            custom_instructions: vm.custom_instructions.len(),
+            options: vm.options,
+        }
+    }
+}
+
+/// VMs of finished runners, reused by the next runner of the same program in this context & thread
+#[cfg(feature = "std")]
+type Idle = Vec<(WeakProgram, VM)>;
+
+impl VMProgram {
+    pub(crate) fn new(vm: &VM) -> Self {
+        VMProgram {
+            hooks: vm.object_hooks.clone(),
+            version: ProgramVersion::of(vm),
+            scopes: vm.scopes.clone(),
+            modules: vm.modules.clone(),
+            dependencies: vm.shared_dependencies(),
+            options: vm.options,
+            constants: vm.constants.clone(),
+            custom_instructions: vm.custom_instructions.clone(),
+            #[cfg(feature = "std")]
+            context: vm.context.clone(),
+        }
+    }
+
+    /// false once the VM changed in a way hooks or processes could see
+    pub(crate) fn is_current(&self, vm: &VM) -> bool {
+        self.hooks == vm.object_hooks && self.version == ProgramVersion::of(vm)
+    }
+
+    /// true if any object defines `hash` or `eq`, otherwise there's nothing to `enter`
+    #[cfg(feature = "threaded")]
+    #[inline]
+    pub(crate) fn has_hooks(&self) -> bool {
+        !self.hooks.is_empty()
+    }
+
+    /// Objects hashed or compared on the current thread use the hooks until the guard is dropped
+    #[cfg(feature = "std")]
+    pub(crate) fn enter(self: &SharedProgram) -> ObjectHooksGuard {
+        enter_object_hooks(Rc::new(ObjectHookRunner {
+            program: self.clone(),
+            vm: RefCell::new(None),
+        }))
+    }
+
+    /// Hooks are entered per thread, without std objects use their default hash & eq
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn enter(self: &SharedProgram) {}
+
+    #[cfg(feature = "std")]
+    fn take_vm(self: &SharedProgram) -> VM {
+        let idle = with_local(|idle: &mut Idle| {
+            let index = idle
+                .iter()
+                .position(|(p, _)| ptr::eq(p.as_ptr(), SharedProgram::as_ptr(self)))?;
+            Some(idle.swap_remove(index).1)
+        });
+        idle.unwrap_or_else(|| VM {
+            scopes: self.scopes.clone(),
+            modules: self.modules.clone(),
+            dependencies: self.dependencies.clone(),
+            options: self.options,
+            constants: self.constants.clone(),
+            custom_instructions: self.custom_instructions.clone(),
+            context: self.context.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Runs hooks on a copy of the VM, built the first time a hook is called or reused from an earlier runner of
+/// the program. The VM that entered the hooks is running the instruction that hashes or compares the object.
+/// Hooks that hash or compare objects themselves, or fail, use the default for that call
+#[cfg(feature = "std")]
+struct ObjectHookRunner {
+    program: SharedProgram,
+    vm: RefCell<Option<VM>>,
+}
+
+#[cfg(feature = "std")]
+impl Drop for ObjectHookRunner {
+    fn drop(&mut self) {
+        if let Some(vm) = self.vm.get_mut().take() {
+            let program = SharedProgram::downgrade(&self.program);
+            let stale = with_local(|idle: &mut Idle| {
+                // VMs of programs that were rebuilt or dropped won't be used again
+                let (live, stale) = core::mem::take(idle)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|(p, _)| p.strong_count() > 0);
+                *idle = live;
+                idle.push((program, vm));
+                stale
+            });
+            drop(stale);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ObjectHookRunner {
+    fn call(
+        &self,
+        scope: usize,
+        object: &RigzObject,
+        other: Option<&RigzObject>,
+    ) -> Option<ObjectValue> {
+        let mut vm = self.vm.try_borrow_mut().ok()?;
+        let vm = vm.get_or_insert_with(|| self.program.take_vm());
+        vm.stack.clear();
+        vm.frames.reset();
+        vm.sp = scope;
+        vm.frames.current = RefCell::new(CallFrame {
+            scope_id: scope,
+            ..Default::default()
+        });
+        let this: ObjectValue = object.clone().into();
+        vm.frames
+            .load_let("self".into(), StackValue::Value(this.into()))
+            .ok()?;
+        if let (Some(other), Some((arg, _))) = (other, vm.scopes[scope].args.first().cloned()) {
+            let other: ObjectValue = other.clone().into();
+            vm.frames
+                .load_let(arg, StackValue::Value(other.into()))
+                .ok()?;
+        }
+        let value = loop {
+            if let Some(v) = vm.step() {
+                break v;
+            }
+        };
+        match value {
+            ObjectValue::Primitive(rigz_core::PrimitiveValue::Error(_)) => None,
+            v => Some(v),
+        }
+    }
+
+    fn scopes(&self, object: &RigzObject) -> Option<&ObjectHookScopes> {
+        self.program.hooks.get(&object.rigz_type.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ObjectHooks for ObjectHookRunner {
+    fn hash(&self, object: &RigzObject) -> Option<i64> {
+        let scopes = self.scopes(object)?;
+        match scopes.hash {
+            Some(scope) => self.call(scope, object, None)?.to_int().ok(),
+            // equal objects must hash the same, so only the type is hashed
+            None if scopes.eq.is_some() => Some(0),
+            None => None,
+        }
+    }
+
+    fn eq(&self, object: &RigzObject, other: &RigzObject) -> Option<bool> {
+        let scope = self.scopes(object)?.eq?;
+        Some(self.call(scope, object, Some(other))?.to_bool())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::prelude::*;
+use crate::{ResolvedModule, VM};
+use rigz_core::{ObjectValue, RigzType, StackValue};
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    // instructions can be replaced at runtime (REPL, AddInstruction), so the module & function are checked too
+    module: String,
+    func: String,
+    receiver: RigzType,
+    resolved: ResolvedModule,
+    overload: Option<usize>,
+}
+
+/// Overloads resolved by `CallExtension`, one entry per call site (scope, instruction).
+/// Each site is monomorphic, a different receiver type replaces the entry.
+#[derive(Clone, Debug, Default)]
+pub struct InlineCaches {
+    entries: HashMap<(usize, usize), CacheEntry>,
+}
+
+impl InlineCaches {
+    #[inline]
+    pub fn get(
+        &self,
+        site: (usize, usize),
+        module: &str,
+        func: &str,
+        receiver: &RigzType,
+    ) -> Option<(ResolvedModule, Option<usize>)> {
+        match self.entries.get(&site) {
+            Some(e) if &e.receiver == receiver && e.func == func && e.module == module => {
+                Some((e.resolved.clone(), e.overload))
+            }
+            _ => None,
+        }
+    }
+
+    /// `overload` is the result of `Module::resolve_extension` for the receiver
+    #[inline]
+    pub fn insert(
+        &mut self,
+        site: (usize, usize),
+        module: String,
+        func: String,
+        receiver: RigzType,
+        resolved: ResolvedModule,
+        overload: Option<usize>,
+    ) {
+        self.entries.insert(
+            site,
+            CacheEntry {
+                module,
+                func,
+                receiver,
+                resolved,
+                overload,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+}
+
+impl VM {
+    /// Runs `f` with the receiver of an extension call, `None` when it's a scope since resolving it runs it
+    pub(crate) fn with_receiver<T>(&self, f: impl FnOnce(&ObjectValue) -> T) -> Option<T> {
+        match self.stack.last() {
+            Some(StackValue::Value(v)) => Some(f(&v.borrow())),
+            Some(StackValue::Constant(c)) => self.constants.get(*c).map(f),
+            Some(StackValue::Lazy(t)) => t.value().map(|v| f(&v)),
+            Some(StackValue::ScopeId(_)) | None => None,
+        }
+    }
+}
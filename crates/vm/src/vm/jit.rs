@@ -0,0 +1,807 @@
+use crate::prelude::*;
+use crate::{Instruction, LoadValue, Runner, Scope, VM};
+use core::fmt::{Debug, Formatter};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, MemFlags, Signature, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use log::{debug, warn};
+use rigz_core::{
+    BinaryOperation, Interned, Number, ObjectValue, PrimitiveValue, StackValue, UnaryOperation,
+};
+
+/// Passed to every compiled function, the offsets are used by the generated code
+#[repr(C)]
+#[derive(Debug, Default)]
+struct State {
+    depth: i64,
+    max_depth: i64,
+    // set when a function can't finish natively (overflow, division by zero, or max depth)
+    bailed: i64,
+}
+
+const DEPTH: i32 = 0;
+const MAX_DEPTH: i32 = 8;
+const BAILED: i32 = 16;
+
+type Entry = unsafe extern "C" fn(*const i64, *mut State) -> i64;
+
+/// Compiles scopes to native code once they've been called `threshold` times, see `VM::enable_jit`.
+///
+/// Only functions using Int arithmetic, comparisons, `let`, if/else, & calls to other supported functions are
+/// compiled. Compiled code gives up (and the call runs in the VM instead) on overflow, division by zero, or
+/// when `VMOptions::max_depth` is reached, so results & errors always match the VM.
+#[derive(Default)]
+pub(crate) struct Jit {
+    threshold: Option<usize>,
+    calls: HashMap<usize, usize>,
+    entries: HashMap<usize, (Entry, usize)>,
+    unsupported: HashSet<usize>,
+    // internal functions, shared by every entry that calls them
+    functions: HashMap<usize, FuncId>,
+    module: Option<JITModule>,
+}
+
+impl Debug for Jit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Jit")
+            .field("threshold", &self.threshold)
+            .field("compiled", &self.entries.keys().collect::<Vec<_>>())
+            .field("unsupported", &self.unsupported)
+            .finish()
+    }
+}
+
+// compiled code is plain memory owned by the module, nothing in it is tied to the thread that created it
+#[cfg(feature = "send")]
+unsafe impl Send for Jit {}
+
+impl Jit {
+    /// Forgets compiled code, i.e. after a scope is replaced, scopes are compiled again once they reach the threshold
+    pub(crate) fn reset(&mut self) {
+        *self = Jit {
+            threshold: self.threshold,
+            ..Default::default()
+        };
+    }
+}
+
+impl VM {
+    /// Functions called `threshold` times are compiled to native code, unsupported functions keep running in the VM.
+    /// Compiled calls skip profiling, coverage, metrics, instruction budgets, & breakpoints, so the JIT is
+    /// bypassed while any of those are enabled. Deadlines & signals are checked once the compiled call returns.
+    pub fn enable_jit(&mut self, threshold: usize) {
+        self.jit.threshold = Some(threshold.max(1));
+    }
+
+    pub fn disable_jit(&mut self) {
+        self.jit.threshold = None;
+    }
+
+    /// Scopes that have been compiled to native code
+    pub fn jit_compiled(&self) -> Vec<usize> {
+        let mut compiled: Vec<_> = self.jit.entries.keys().copied().collect();
+        compiled.sort();
+        compiled
+    }
+
+    /// Runs `scope_index` natively when it's compiled and every argument is an Int, returns false to run it in the VM
+    pub(crate) fn call_jit(&mut self, scope_index: usize) -> bool {
+        let Some(threshold) = self.jit.threshold else {
+            return false;
+        };
+        if self.options.profile
+            || self.options.coverage
+            || self.options.enable_metrics
+            || self.options.max_instructions.is_some()
+            || self.debugger.active()
+            || self.tracer.active()
+            || self.jit.unsupported.contains(&scope_index)
+        {
+            return false;
+        }
+
+        let Some(&(entry, arg_count)) = self.jit.entries.get(&scope_index) else {
+            let calls = self.jit.calls.entry(scope_index).or_default();
+            *calls += 1;
+            if *calls >= threshold {
+                self.jit.calls.remove(&scope_index);
+                if let Err(e) = self.compile(scope_index) {
+                    debug!("Scope {scope_index} was not compiled - {e}");
+                    self.jit.unsupported.insert(scope_index);
+                }
+            }
+            return false;
+        };
+
+        // the first argument is on top of the stack, matching `call_frame`
+        let mut args = Vec::with_capacity(arg_count);
+        for value in self.stack.iter().rev().take(arg_count) {
+            let int = match value {
+                StackValue::Value(v) => as_int(&v.borrow()),
+                StackValue::Constant(c) => self.constants.get(*c).and_then(as_int),
+                // resolving a scope runs it, the VM handles those arguments
+                StackValue::ScopeId(_) | StackValue::Lazy(_) => None,
+            };
+            match int {
+                Some(i) => args.push(i),
+                None => return false,
+            }
+        }
+        if args.len() != arg_count {
+            return false;
+        }
+
+        let mut state = State {
+            depth: 0,
+            max_depth: self.options.max_depth.saturating_sub(self.frames.len()) as i64,
+            bailed: 0,
+        };
+        // Safety: entries are only created by `compile` for functions taking `arg_count` Ints
+        let result = unsafe { entry(args.as_ptr(), &mut state) };
+        if state.bailed != 0 {
+            return false;
+        }
+        for _ in 0..arg_count {
+            self.stack.pop();
+        }
+        let result: ObjectValue = result.into();
+        self.store_value(result.into());
+        true
+    }
+
+    fn compile(&mut self, root: usize) -> Result<(), String> {
+        let mut functions = Vec::new();
+        let mut pending = vec![root];
+        let mut seen = HashSet::new();
+        while let Some(scope) = pending.pop() {
+            if !seen.insert(scope) || self.jit.functions.contains_key(&scope) {
+                continue;
+            }
+            let (function, calls) = Translator::function(&self.scopes, &self.constants, scope)
+                .ok_or_else(|| {
+                    format!("{} uses unsupported instructions", self.scopes[scope].named)
+                })?;
+            pending.extend(calls);
+            functions.push(function);
+        }
+
+        if self.jit.module.is_none() {
+            self.jit.module = Some(create_module()?);
+        }
+        let module = self.jit.module.as_mut().expect("module was created");
+        for function in &functions {
+            let signature = signature(module, function.args);
+            let id = module
+                .declare_function(
+                    &format!("scope_{}", function.scope),
+                    Linkage::Local,
+                    &signature,
+                )
+                .map_err(|e| e.to_string())?;
+            self.jit.functions.insert(function.scope, id);
+        }
+
+        let mut context = module.make_context();
+        let mut builder_context = FunctionBuilderContext::new();
+        for function in &functions {
+            context.func.signature = signature(module, function.args);
+            Codegen::function(
+                module,
+                &self.jit.functions,
+                &mut context.func,
+                &mut builder_context,
+                function,
+            );
+            let id = self.jit.functions[&function.scope];
+            let defined = module.define_function(id, &mut context);
+            module.clear_context(&mut context);
+            if let Err(e) = defined {
+                // functions can't be removed once declared, the module is replaced so nothing refers to them
+                warn!("Failed to compile scope {} - {e}", function.scope);
+                self.jit.module = None;
+                self.jit.functions.clear();
+                self.jit.entries.clear();
+                return Err(e.to_string());
+            }
+        }
+
+        let arg_count = self.scopes[root].args.len();
+        let mut signature = module.make_signature();
+        let pointer = module.target_config().pointer_type();
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(pointer));
+        signature.returns.push(AbiParam::new(types::I64));
+        let entry = module
+            .declare_function(&format!("entry_{root}"), Linkage::Local, &signature)
+            .map_err(|e| e.to_string())?;
+        context.func.signature = signature;
+        Codegen::entry(
+            module,
+            self.jit.functions[&root],
+            &mut context.func,
+            &mut builder_context,
+            arg_count,
+        );
+        module
+            .define_function(entry, &mut context)
+            .map_err(|e| e.to_string())?;
+        module.clear_context(&mut context);
+        module.finalize_definitions().map_err(|e| e.to_string())?;
+
+        let code = module.get_finalized_function(entry);
+        // Safety: the entry was generated with the `Entry` signature above
+        let entry: Entry = unsafe { core::mem::transmute::<*const u8, Entry>(code) };
+        self.jit.entries.insert(root, (entry, arg_count));
+        Ok(())
+    }
+}
+
+fn create_module() -> Result<JITModule, String> {
+    let mut flags = settings::builder();
+    flags
+        .set("use_colocated_libcalls", "false")
+        .and_then(|_| flags.set("is_pic", "false"))
+        .and_then(|_| flags.set("opt_level", "speed"))
+        .map_err(|e| e.to_string())?;
+    let isa = cranelift_native::builder()
+        .map_err(|e| e.to_string())?
+        .finish(settings::Flags::new(flags))
+        .map_err(|e| e.to_string())?;
+    Ok(JITModule::new(JITBuilder::with_isa(
+        isa,
+        default_libcall_names(),
+    )))
+}
+
+/// Int arguments followed by the `State` pointer
+fn signature(module: &JITModule, args: usize) -> Signature {
+    let mut signature = module.make_signature();
+    signature
+        .params
+        .extend((0..args).map(|_| AbiParam::new(types::I64)));
+    signature
+        .params
+        .push(AbiParam::new(module.target_config().pointer_type()));
+    signature.returns.push(AbiParam::new(types::I64));
+    signature
+}
+
+#[inline]
+fn as_int(value: &ObjectValue) -> Option<i64> {
+    match value {
+        ObjectValue::Primitive(PrimitiveValue::Number(Number::Int(i))) => Some(*i),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Int,
+    /// 0 or 1
+    Bool,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Const(i64),
+    Local(usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOperation, Box<Expr>, Box<Expr>),
+    Call(usize, Vec<Expr>),
+    If(Box<Expr>, Box<Body>, Box<Body>),
+}
+
+/// Instructions of a scope, `let` bindings are evaluated before the value
+#[derive(Clone, Debug)]
+struct Body {
+    lets: Vec<(usize, Expr)>,
+    value: Expr,
+}
+
+#[derive(Clone, Debug)]
+struct Function {
+    scope: usize,
+    args: usize,
+    locals: usize,
+    body: Body,
+}
+
+/// Converts a scope's instructions to expressions by tracking what each instruction leaves on the stack
+struct Translator<'a> {
+    scopes: &'a [Scope],
+    constants: &'a [ObjectValue],
+    locals: usize,
+    calls: Vec<usize>,
+}
+
+impl Translator<'_> {
+    /// Functions that can be compiled along with the scopes they call, none if any instruction is unsupported
+    fn function(
+        scopes: &[Scope],
+        constants: &[ObjectValue],
+        scope_index: usize,
+    ) -> Option<(Function, Vec<usize>)> {
+        let scope = scopes.get(scope_index)?;
+        if !is_function(scope) {
+            return None;
+        }
+        let mut translator = Translator {
+            scopes,
+            constants,
+            locals: scope.args.len(),
+            calls: Vec::new(),
+        };
+        let variables = scope
+            .args
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| (name.clone(), (index, Kind::Int)))
+            .collect();
+        let (body, Kind::Int) = translator.body(scope_index, variables)? else {
+            return None;
+        };
+        let function = Function {
+            scope: scope_index,
+            args: scope.args.len(),
+            locals: translator.locals,
+            body,
+        };
+        Some((function, translator.calls))
+    }
+
+    fn body(
+        &mut self,
+        scope_index: usize,
+        mut variables: HashMap<Interned, (usize, Kind)>,
+    ) -> Option<(Body, Kind)> {
+        let (last, instructions) = self.scopes.get(scope_index)?.instructions.split_last()?;
+        // early returns from nested blocks are left to the VM
+        if *last != Instruction::Ret {
+            return None;
+        }
+        let mut stack: Vec<(Expr, Kind)> = Vec::new();
+        let mut lets = Vec::new();
+        for instruction in instructions {
+            let next = match instruction {
+                Instruction::Load(LoadValue::Value(v)) => constant(v)?,
+                Instruction::Load(LoadValue::Constant(c)) => constant(self.constants.get(*c)?)?,
+                Instruction::GetVariable(name) | Instruction::GetMutableVariable(name) => {
+                    let &(local, kind) = variables.get(name)?;
+                    (Expr::Local(local), kind)
+                }
+                Instruction::LoadLet(name) | Instruction::LoadMut(name) => {
+                    let (value, kind) = stack.pop()?;
+                    let local = self.locals;
+                    self.locals += 1;
+                    lets.push((local, value));
+                    variables.insert(name.clone(), (local, kind));
+                    continue;
+                }
+                Instruction::Unary(UnaryOperation::Neg) => match stack.pop()? {
+                    (value, Kind::Int) => (Expr::Neg(Box::new(value)), Kind::Int),
+                    _ => return None,
+                },
+                Instruction::Unary(UnaryOperation::Not) => match stack.pop()? {
+                    (value, Kind::Bool) => (Expr::Not(Box::new(value)), Kind::Bool),
+                    _ => return None,
+                },
+                Instruction::Binary(op) => {
+                    let (rhs, r) = stack.pop()?;
+                    let (lhs, l) = stack.pop()?;
+                    let kind = binary_kind(*op, l, r)?;
+                    (Expr::Binary(*op, Box::new(lhs), Box::new(rhs)), kind)
+                }
+                Instruction::Call(s) => {
+                    let callee = self.scopes.get(*s)?;
+                    if !is_function(callee) {
+                        return None;
+                    }
+                    let mut args = Vec::with_capacity(callee.args.len());
+                    for _ in 0..callee.args.len() {
+                        match stack.pop()? {
+                            (arg, Kind::Int) => args.push(arg),
+                            _ => return None,
+                        }
+                    }
+                    self.calls.push(*s);
+                    (Expr::Call(*s, args), Kind::Int)
+                }
+                Instruction::IfElse {
+                    if_scope,
+                    else_scope,
+                } => {
+                    let (condition, Kind::Bool) = stack.pop()? else {
+                        return None;
+                    };
+                    let (then, t) = self.block(*if_scope, &variables)?;
+                    let (otherwise, o) = self.block(*else_scope, &variables)?;
+                    if t != o {
+                        return None;
+                    }
+                    let value = Expr::If(Box::new(condition), Box::new(then), Box::new(otherwise));
+                    (value, t)
+                }
+                _ => return None,
+            };
+            stack.push(next);
+        }
+        let [(value, kind)] = <[_; 1]>::try_from(stack).ok()?;
+        Some((Body { lets, value }, kind))
+    }
+
+    /// if & else scopes, variables defined in the block aren't visible after it
+    fn block(
+        &mut self,
+        scope_index: usize,
+        variables: &HashMap<Interned, (usize, Kind)>,
+    ) -> Option<(Body, Kind)> {
+        let scope = self.scopes.get(scope_index)?;
+        if !matches!(scope.named.as_str(), "if" | "else")
+            || !scope.args.is_empty()
+            || scope.set_self.is_some()
+        {
+            return None;
+        }
+        self.body(scope_index, variables.clone())
+    }
+}
+
+fn is_function(scope: &Scope) -> bool {
+    scope.set_self.is_none()
+        && scope.lifecycle.is_none()
+        && scope.named != "main"
+        && !Scope::BLOCK_NAMES.contains(&scope.named.as_str())
+}
+
+fn constant(value: &ObjectValue) -> Option<(Expr, Kind)> {
+    match value {
+        ObjectValue::Primitive(PrimitiveValue::Number(Number::Int(i))) => {
+            Some((Expr::Const(*i), Kind::Int))
+        }
+        ObjectValue::Primitive(PrimitiveValue::Bool(b)) => {
+            Some((Expr::Const(*b as i64), Kind::Bool))
+        }
+        _ => None,
+    }
+}
+
+fn binary_kind(op: BinaryOperation, lhs: Kind, rhs: Kind) -> Option<Kind> {
+    match (op, lhs, rhs) {
+        (
+            BinaryOperation::Add
+            | BinaryOperation::Sub
+            | BinaryOperation::Mul
+            | BinaryOperation::Div
+            | BinaryOperation::Rem
+            | BinaryOperation::BitAnd
+            | BinaryOperation::BitOr
+            | BinaryOperation::BitXor,
+            Kind::Int,
+            Kind::Int,
+        ) => Some(Kind::Int),
+        (
+            BinaryOperation::Lt | BinaryOperation::Lte | BinaryOperation::Gt | BinaryOperation::Gte,
+            Kind::Int,
+            Kind::Int,
+        ) => Some(Kind::Bool),
+        (BinaryOperation::Eq | BinaryOperation::Neq, l, r) if l == r => Some(Kind::Bool),
+        _ => None,
+    }
+}
+
+struct Codegen<'a, 'f> {
+    builder: FunctionBuilder<'f>,
+    module: &'a mut JITModule,
+    functions: &'a HashMap<usize, FuncId>,
+    locals: Vec<Option<Value>>,
+    state: Value,
+    bail: Block,
+}
+
+impl<'a> Codegen<'a, '_> {
+    fn function(
+        module: &'a mut JITModule,
+        functions: &'a HashMap<usize, FuncId>,
+        func: &mut cranelift_codegen::ir::Function,
+        builder_context: &mut FunctionBuilderContext,
+        function: &Function,
+    ) {
+        let mut builder = FunctionBuilder::new(func, builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let params = builder.block_params(entry).to_vec();
+        let state = params[function.args];
+        let mut locals = vec![None; function.locals];
+        for (local, param) in locals.iter_mut().zip(&params[..function.args]) {
+            *local = Some(*param);
+        }
+        let bail = builder.create_block();
+        let mut codegen = Codegen {
+            builder,
+            module,
+            functions,
+            locals,
+            state,
+            bail,
+        };
+
+        let depth = codegen.load(DEPTH);
+        let max_depth = codegen.load(MAX_DEPTH);
+        let too_deep =
+            codegen
+                .builder
+                .ins()
+                .icmp(IntCC::SignedGreaterThanOrEqual, depth, max_depth);
+        codegen.bail_if(too_deep);
+        let deeper = codegen.builder.ins().iadd_imm(depth, 1);
+        codegen.store(deeper, DEPTH);
+
+        let value = codegen.body(&function.body);
+        codegen.store(depth, DEPTH);
+        codegen.builder.ins().return_(&[value]);
+
+        codegen.builder.switch_to_block(bail);
+        let bailed = codegen.builder.ins().iconst(types::I64, 1);
+        codegen.store(bailed, BAILED);
+        let zero = codegen.builder.ins().iconst(types::I64, 0);
+        codegen.builder.ins().return_(&[zero]);
+
+        codegen.builder.seal_all_blocks();
+        codegen.builder.finalize();
+    }
+
+    /// Loads the arguments from the array passed by `VM::call_jit` and calls the function
+    fn entry(
+        module: &mut JITModule,
+        function: FuncId,
+        func: &mut cranelift_codegen::ir::Function,
+        builder_context: &mut FunctionBuilderContext,
+        arg_count: usize,
+    ) {
+        let mut builder = FunctionBuilder::new(func, builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let (args, state) = {
+            let params = builder.block_params(entry);
+            (params[0], params[1])
+        };
+        let mut call_args: Vec<_> = (0..arg_count)
+            .map(|i| {
+                builder
+                    .ins()
+                    .load(types::I64, MemFlags::trusted(), args, (i * 8) as i32)
+            })
+            .collect();
+        call_args.push(state);
+        let callee = module.declare_func_in_func(function, builder.func);
+        let call = builder.ins().call(callee, &call_args);
+        let result = builder.inst_results(call)[0];
+        builder.ins().return_(&[result]);
+        builder.seal_all_blocks();
+        builder.finalize();
+    }
+
+    fn load(&mut self, offset: i32) -> Value {
+        self.builder
+            .ins()
+            .load(types::I64, MemFlags::trusted(), self.state, offset)
+    }
+
+    fn store(&mut self, value: Value, offset: i32) {
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), value, self.state, offset);
+    }
+
+    /// Continues in a new block when `failed` is zero
+    fn bail_if(&mut self, failed: Value) {
+        let next = self.builder.create_block();
+        self.builder.ins().brif(failed, self.bail, &[], next, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    fn body(&mut self, body: &Body) -> Value {
+        for (local, expr) in &body.lets {
+            let value = self.expr(expr);
+            self.locals[*local] = Some(value);
+        }
+        self.expr(&body.value)
+    }
+
+    fn bool(&mut self, condition: Value) -> Value {
+        self.builder.ins().uextend(types::I64, condition)
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Value {
+        match expr {
+            Expr::Const(i) => self.builder.ins().iconst(types::I64, *i),
+            Expr::Local(local) => self.locals[*local].expect("local defined before use"),
+            Expr::Neg(value) => {
+                let value = self.expr(value);
+                let overflow = self.builder.ins().icmp_imm(IntCC::Equal, value, i64::MIN);
+                self.bail_if(overflow);
+                self.builder.ins().ineg(value)
+            }
+            Expr::Not(value) => {
+                let value = self.expr(value);
+                let not = self.builder.ins().icmp_imm(IntCC::Equal, value, 0);
+                self.bool(not)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                self.binary(*op, lhs, rhs)
+            }
+            Expr::Call(scope, args) => {
+                let mut call_args: Vec<_> = args.iter().map(|a| self.expr(a)).collect();
+                call_args.push(self.state);
+                let callee = self
+                    .module
+                    .declare_func_in_func(self.functions[scope], self.builder.func);
+                let call = self.builder.ins().call(callee, &call_args);
+                let result = self.builder.inst_results(call)[0];
+                let bailed = self.load(BAILED);
+                self.bail_if(bailed);
+                result
+            }
+            Expr::If(condition, then, otherwise) => {
+                let condition = self.expr(condition);
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge = self.builder.create_block();
+                self.builder.append_block_param(merge, types::I64);
+                self.builder
+                    .ins()
+                    .brif(condition, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                let value = self.body(then);
+                self.builder.ins().jump(merge, &[value]);
+
+                self.builder.switch_to_block(else_block);
+                let value = self.body(otherwise);
+                self.builder.ins().jump(merge, &[value]);
+
+                self.builder.switch_to_block(merge);
+                self.builder.block_params(merge)[0]
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOperation, lhs: Value, rhs: Value) -> Value {
+        match op {
+            BinaryOperation::Add => {
+                let sum = self.builder.ins().iadd(lhs, rhs);
+                // overflowed if both operands have a different sign than the result
+                let l = self.builder.ins().bxor(lhs, sum);
+                let r = self.builder.ins().bxor(rhs, sum);
+                let both = self.builder.ins().band(l, r);
+                let overflow = self.builder.ins().icmp_imm(IntCC::SignedLessThan, both, 0);
+                self.bail_if(overflow);
+                sum
+            }
+            BinaryOperation::Sub => {
+                let difference = self.builder.ins().isub(lhs, rhs);
+                let operands = self.builder.ins().bxor(lhs, rhs);
+                let result = self.builder.ins().bxor(lhs, difference);
+                let both = self.builder.ins().band(operands, result);
+                let overflow = self.builder.ins().icmp_imm(IntCC::SignedLessThan, both, 0);
+                self.bail_if(overflow);
+                difference
+            }
+            BinaryOperation::Mul => {
+                let low = self.builder.ins().imul(lhs, rhs);
+                let high = self.builder.ins().smulhi(lhs, rhs);
+                let sign = self.builder.ins().sshr_imm(low, 63);
+                let overflow = self.builder.ins().icmp(IntCC::NotEqual, high, sign);
+                self.bail_if(overflow);
+                low
+            }
+            BinaryOperation::Div | BinaryOperation::Rem => {
+                // division by zero & i64::MIN / -1 are handled by the VM
+                let zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+                let negative_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+                let invalid = self.builder.ins().bor(zero, negative_one);
+                self.bail_if(invalid);
+                if op == BinaryOperation::Div {
+                    self.builder.ins().sdiv(lhs, rhs)
+                } else {
+                    self.builder.ins().srem(lhs, rhs)
+                }
+            }
+            BinaryOperation::BitAnd => self.builder.ins().band(lhs, rhs),
+            BinaryOperation::BitOr => self.builder.ins().bor(lhs, rhs),
+            BinaryOperation::BitXor => self.builder.ins().bxor(lhs, rhs),
+            BinaryOperation::Eq => self.compare(IntCC::Equal, lhs, rhs),
+            BinaryOperation::Neq => self.compare(IntCC::NotEqual, lhs, rhs),
+            BinaryOperation::Lt => self.compare(IntCC::SignedLessThan, lhs, rhs),
+            BinaryOperation::Lte => self.compare(IntCC::SignedLessThanOrEqual, lhs, rhs),
+            BinaryOperation::Gt => self.compare(IntCC::SignedGreaterThan, lhs, rhs),
+            BinaryOperation::Gte => self.compare(IntCC::SignedGreaterThanOrEqual, lhs, rhs),
+            op => unreachable!("{op} is rejected by binary_kind"),
+        }
+    }
+
+    fn compare(&mut self, cc: IntCC, lhs: Value, rhs: Value) -> Value {
+        let result = self.builder.ins().icmp(cc, lhs, rhs);
+        self.bool(result)
+    }
+}
+
+#[cfg(test)]
+pub mod jit_tests {
+    use crate::{RigzBuilder, VMBuilder};
+    use rigz_core::BinaryOperation;
+    use wasm_bindgen_test::*;
+
+    fn fib(n: i64) -> (crate::VM, usize) {
+        let mut builder = VMBuilder::new();
+        let fib = builder.enter_scope("fib".to_string(), vec![("n".to_string(), false)], None);
+        builder
+            .add_get_variable_instruction("n".to_string())
+            .add_load_instruction(2.into())
+            .add_binary_instruction(BinaryOperation::Lt);
+        let if_scope = builder.enter_scope("if".to_string(), vec![], None);
+        builder
+            .add_get_variable_instruction("n".to_string())
+            .exit_scope(fib);
+        let else_scope = builder.enter_scope("else".to_string(), vec![], None);
+        builder
+            .add_get_variable_instruction("n".to_string())
+            .add_load_instruction(1.into())
+            .add_binary_instruction(BinaryOperation::Sub)
+            .add_call_instruction(fib)
+            .add_get_variable_instruction("n".to_string())
+            .add_load_instruction(2.into())
+            .add_binary_instruction(BinaryOperation::Sub)
+            .add_call_instruction(fib)
+            .add_binary_instruction(BinaryOperation::Add)
+            .exit_scope(fib);
+        builder
+            .add_if_else_instruction(if_scope, else_scope)
+            .exit_scope(0)
+            .add_load_instruction(n.into())
+            .add_call_instruction(fib)
+            .add_halt_instruction();
+        (builder.build(), fib)
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn hot_function_is_compiled() {
+        let (mut vm, fib) = fib(20);
+        vm.enable_jit(10);
+        assert_eq!(vm.eval(), Ok(6765.into()));
+        assert_eq!(vm.jit_compiled(), vec![fib]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn overflow_runs_in_vm() {
+        let mut builder = VMBuilder::new();
+        let square =
+            builder.enter_scope("square".to_string(), vec![("n".to_string(), false)], None);
+        builder
+            .add_get_variable_instruction("n".to_string())
+            .add_get_variable_instruction("n".to_string())
+            .add_binary_instruction(BinaryOperation::Mul)
+            .exit_scope(0)
+            .add_load_instruction(3.into())
+            .add_call_instruction(square)
+            .add_call_instruction(square)
+            .add_load_instruction(3_037_000_500i64.into())
+            .add_call_instruction(square)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        vm.enable_jit(1);
+        assert!(
+            vm.eval().is_err(),
+            "overflow should fail with the default policy"
+        );
+        assert_eq!(vm.jit_compiled(), vec![square]);
+    }
+}
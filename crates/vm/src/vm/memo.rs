@@ -0,0 +1,284 @@
+use crate::prelude::*;
+use rigz_core::{IndexMap, MemoResult, ObjectValue, PersistentMemo, VMError};
+#[cfg(all(feature = "std", not(feature = "js")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "js")]
+use web_time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "std")]
+use {
+    rigz_core::Snapshot,
+    std::fs,
+    std::io::{ErrorKind, Write},
+    std::path::Path,
+};
+
+/// Start of every `@memo(persist: ...)` file
+#[cfg(feature = "std")]
+const MEMO_HEADER: &[u8; 4] = b"RZMC";
+
+/// Incremented when the layout of the results changes, files with another format are treated as empty
+#[cfg(feature = "std")]
+const MEMO_FORMAT: usize = 3;
+
+/// Files with more superseded records than this (and than live results) are rewritten when they're loaded
+#[cfg(feature = "std")]
+const COMPACT_AFTER: usize = 64;
+
+pub(crate) type MemoResults = IndexMap<Vec<ObjectValue>, MemoResult>;
+
+/// Results for each persisted function, keyed by `PersistentMemo::key`
+#[cfg(feature = "std")]
+type MemoFile = HashMap<String, MemoResults>;
+
+/// A memo file is the header & version followed by one `(key, args, result)` record per cache miss, later records
+/// replace earlier ones with the same key & args.
+#[cfg(feature = "std")]
+type MemoRecord = (String, Vec<ObjectValue>, MemoResult);
+
+#[cfg(feature = "std")]
+fn version() -> String {
+    format!("{}+{MEMO_FORMAT}", env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(feature = "std")]
+fn header() -> Vec<u8> {
+    let mut bytes = MEMO_HEADER.to_vec();
+    bytes.extend(Snapshot::as_bytes(&version()));
+    bytes
+}
+
+#[cfg(feature = "std")]
+struct Replayed {
+    file: MemoFile,
+    /// records that were replaced by a later record
+    superseded: usize,
+    /// the file was written by another version of rigz or ends with a partial record
+    rewrite: bool,
+}
+
+/// Files written by another version of rigz are treated as empty, values may be encoded differently.
+/// A partial record at the end of the file, i.e. from an interrupted write, is dropped
+#[cfg(feature = "std")]
+fn read(path: &Path) -> Result<Replayed, VMError> {
+    let mut replayed = Replayed {
+        file: MemoFile::new(),
+        superseded: 0,
+        rewrite: false,
+    };
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(replayed),
+        Err(e) => {
+            return Err(VMError::RuntimeError(format!(
+                "Failed to read memo cache {} - {e}",
+                path.display()
+            )))
+        }
+    };
+    let Some(bytes) = bytes.strip_prefix(MEMO_HEADER) else {
+        return Err(VMError::RuntimeError(format!(
+            "Invalid memo cache {}, missing RZMC header",
+            path.display()
+        )));
+    };
+    let location = format!("memo cache {}", path.display());
+    let mut bytes = Vec::from(bytes).into_iter();
+    let rigz: String = Snapshot::from_bytes(&mut bytes, &location)?;
+    if rigz != version() {
+        replayed.rewrite = true;
+        return Ok(replayed);
+    }
+    while !bytes.as_slice().is_empty() {
+        let Ok((key, args, result)) = <MemoRecord as Snapshot>::from_bytes(&mut bytes, &location)
+        else {
+            replayed.rewrite = true;
+            break;
+        };
+        let results = replayed.file.entry(key).or_default();
+        if results.shift_remove(&args).is_some() {
+            replayed.superseded += 1;
+        }
+        results.insert(args, result);
+    }
+    Ok(replayed)
+}
+
+/// Writes every result to a temporary file first so readers never see a partial write
+#[cfg(feature = "std")]
+fn write(path: &Path, file: &MemoFile) -> Result<(), VMError> {
+    let mut bytes = header();
+    for (key, results) in file {
+        for (args, result) in results {
+            bytes.extend(Snapshot::as_bytes(&(
+                key.clone(),
+                args.clone(),
+                result.clone(),
+            )));
+        }
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp, bytes)
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            VMError::RuntimeError(format!(
+                "Failed to write memo cache {} - {e}",
+                path.display()
+            ))
+        })
+}
+
+/// Milliseconds since the unix epoch, used for `@memo(ttl: ...)`
+#[cfg(feature = "std")]
+pub(crate) fn now() -> usize {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as usize)
+        .unwrap_or_default()
+}
+
+/// Previously stored results for `memo.key`. Files from another version, with a partial record, or mostly
+/// superseded records are compacted first
+#[cfg(feature = "std")]
+pub(crate) fn load(memo: &PersistentMemo) -> Result<MemoResults, VMError> {
+    let path = Path::new(&memo.path);
+    let Replayed {
+        mut file,
+        superseded,
+        rewrite,
+    } = read(path)?;
+    let live: usize = file.values().map(IndexMap::len).sum();
+    if rewrite || superseded > COMPACT_AFTER.max(live) {
+        write(path, &file)?;
+    }
+    Ok(file.remove(&memo.key).unwrap_or_default())
+}
+
+/// Appends a single result for `memo.key`, creating the file if needed
+#[cfg(feature = "std")]
+pub(crate) fn store(
+    memo: &PersistentMemo,
+    args: &[ObjectValue],
+    result: &MemoResult,
+) -> Result<(), VMError> {
+    let path = Path::new(&memo.path);
+    let failed = |e: std::io::Error| {
+        VMError::RuntimeError(format!(
+            "Failed to write memo cache {} - {e}",
+            path.display()
+        ))
+    };
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(failed)?;
+    let mut bytes = match file.metadata().map_err(failed)?.len() {
+        0 => header(),
+        _ => Vec::new(),
+    };
+    bytes.extend(Snapshot::as_bytes(&(
+        memo.key.clone(),
+        args.to_vec(),
+        result.clone(),
+    )));
+    // a single write so concurrent runs don't interleave records
+    file.write_all(&bytes).map_err(failed)
+}
+
+/// There's no clock without std, results don't expire
+#[cfg(not(feature = "std"))]
+pub(crate) fn now() -> usize {
+    0
+}
+
+/// There's no filesystem without std, `@memo(persist: ...)` fails when the function is first called
+#[cfg(not(feature = "std"))]
+pub(crate) fn load(memo: &PersistentMemo) -> Result<MemoResults, VMError> {
+    Err(VMError::UnsupportedOperation(format!(
+        "Cannot load memo cache {} without std",
+        memo.path
+    )))
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn store(
+    memo: &PersistentMemo,
+    _args: &[ObjectValue],
+    _result: &MemoResult,
+) -> Result<(), VMError> {
+    Err(VMError::UnsupportedOperation(format!(
+        "Cannot write memo cache {} without std",
+        memo.path
+    )))
+}
+
+#[cfg(test)]
+pub mod memo_tests {
+    use crate::vm::memo::{load, store, MemoResults, COMPACT_AFTER};
+    use rigz_core::{MemoResult, ObjectValue, PersistentMemo};
+    use wasm_bindgen_test::*;
+
+    fn memo(path: &std::path::Path, key: &str) -> PersistentMemo {
+        PersistentMemo {
+            path: path.to_string_lossy().to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    fn result(value: i64) -> MemoResult {
+        MemoResult {
+            value: ObjectValue::from(value),
+            created: 1,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn store_and_load() {
+        let path = std::env::temp_dir().join(format!("rigz_memo_{}.db", std::process::id()));
+        assert_eq!(load(&memo(&path, "a")), Ok(MemoResults::new()));
+
+        store(&memo(&path, "a"), &[2.into()], &result(4)).unwrap();
+        store(&memo(&path, "b"), &[2.into()], &result(5)).unwrap();
+        store(&memo(&path, "a"), &[3.into()], &result(9)).unwrap();
+        assert_eq!(
+            load(&memo(&path, "a")),
+            Ok(MemoResults::from([
+                (vec![2.into()], result(4)),
+                (vec![3.into()], result(9)),
+            ]))
+        );
+        assert_eq!(load(&memo(&path, "c")), Ok(MemoResults::new()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn appends_and_compacts() {
+        let path =
+            std::env::temp_dir().join(format!("rigz_memo_compact_{}.db", std::process::id()));
+        let a = memo(&path, "a");
+        store(&a, &[1.into()], &result(1)).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        store(&a, &[2.into()], &result(2)).unwrap();
+        let record = std::fs::metadata(&path).unwrap().len() - len;
+        assert!(record < len, "only the record is appended");
+
+        for i in 0..=COMPACT_AFTER as i64 {
+            store(&a, &[1.into()], &result(i)).unwrap();
+        }
+        let expected = MemoResults::from([
+            (vec![2.into()], result(2)),
+            (vec![1.into()], result(COMPACT_AFTER as i64)),
+        ]);
+        assert_eq!(load(&a), Ok(expected.clone()));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len + record);
+
+        // a partial record from an interrupted write is dropped
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend([1, 2, 3]);
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(load(&a), Ok(expected));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len + record);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use crate::prelude::*;
+use crate::Instruction;
+use alloc::collections::BTreeMap;
+use core::fmt::Write;
+
+/// Counters collected while `VMOptions::enable_metrics` is set
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VMMetrics {
+    pub instructions: BTreeMap<&'static str, u64>,
+    pub scope_calls: BTreeMap<usize, u64>,
+    /// extension calls that reused the overload cached for their call site
+    pub inline_cache_hits: u64,
+    pub inline_cache_misses: u64,
+}
+
+impl VMMetrics {
+    #[inline]
+    pub fn record_instruction(&mut self, instruction: &Instruction) {
+        *self.instructions.entry(instruction.name()).or_default() += 1;
+    }
+
+    #[inline]
+    pub fn record_scope_call(&mut self, scope_id: usize) {
+        *self.scope_calls.entry(scope_id).or_default() += 1;
+    }
+
+    pub fn reset(&mut self) {
+        self.instructions.clear();
+        self.scope_calls.clear();
+        self.inline_cache_hits = 0;
+        self.inline_cache_misses = 0;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessMetrics {
+    pub pid: usize,
+    pub scope: String,
+    pub running: bool,
+    /// results of an `@on` handler waiting to be received, None for spawned processes since they don't have a mailbox
+    pub mailbox_depth: Option<usize>,
+    /// events queued for an `@on` handler that it hasn't started, i.e. how far it lags behind `send`
+    pub pending_events: usize,
+    pub delivered_events: usize,
+    /// events discarded or rejected because the handler's queue was full
+    pub dropped_events: usize,
+    /// events handled again after failing, see `EventLifecycle::restart`
+    pub restarts: usize,
+}
+
+/// Point in time view of the VM, rendered in the Prometheus text exposition format
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub metrics: VMMetrics,
+    pub scope_names: Vec<String>,
+    pub processes: Vec<ProcessMetrics>,
+    pub stack_depth: usize,
+    pub frame_depth: usize,
+    pub scopes: usize,
+    pub loaded_instructions: usize,
+    pub constants: usize,
+    /// strings interned by the VM's context
+    pub interned_strings: usize,
+    /// mutexes, channels, & tasks created by the VM that haven't been freed
+    pub shared_states: usize,
+    /// resident set size of the host process, None where it can't be read
+    pub resident_memory_bytes: Option<u64>,
+}
+
+/// `VmRSS` of /proc/self/status
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub(crate) fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(not(all(feature = "std", target_os = "linux")))]
+pub(crate) fn resident_memory_bytes() -> Option<u64> {
+    None
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl MetricsSnapshot {
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "rigz_instructions_total",
+            "counter",
+            "Instructions executed by the VM",
+        );
+        for (name, count) in &self.metrics.instructions {
+            let _ = writeln!(
+                out,
+                "rigz_instructions_total{{instruction=\"{name}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "rigz_scope_calls_total",
+            "counter",
+            "Calls into each scope",
+        );
+        for (scope, count) in &self.metrics.scope_calls {
+            let named = self
+                .scope_names
+                .get(*scope)
+                .map(|s| escape(s))
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "rigz_scope_calls_total{{scope_id=\"{scope}\",scope=\"{named}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "rigz_inline_cache_total",
+            "counter",
+            "Extension calls by inline cache result",
+        );
+        let _ = writeln!(
+            out,
+            "rigz_inline_cache_total{{result=\"hit\"}} {}",
+            self.metrics.inline_cache_hits
+        );
+        let _ = writeln!(
+            out,
+            "rigz_inline_cache_total{{result=\"miss\"}} {}",
+            self.metrics.inline_cache_misses
+        );
+
+        header(
+            &mut out,
+            "rigz_processes",
+            "gauge",
+            "Processes known to the VM",
+        );
+        let running = self.processes.iter().filter(|p| p.running).count();
+        let _ = writeln!(out, "rigz_processes{{state=\"running\"}} {running}");
+        let _ = writeln!(
+            out,
+            "rigz_processes{{state=\"idle\"}} {}",
+            self.processes.len() - running
+        );
+
+        header(
+            &mut out,
+            "rigz_process_mailbox_depth",
+            "gauge",
+            "Results waiting to be received per process",
+        );
+        for p in &self.processes {
+            let Some(depth) = p.mailbox_depth else {
+                continue;
+            };
+            let _ = writeln!(
+                out,
+                "rigz_process_mailbox_depth{{pid=\"{}\",scope=\"{}\"}} {depth}",
+                p.pid,
+                escape(&p.scope),
+            );
+        }
+
+        type Event = fn(&ProcessMetrics) -> usize;
+        let events: [(&str, &str, &str, Event); 4] = [
+            (
+                "rigz_process_pending_events",
+                "gauge",
+                "Events queued for each handler, how far it lags behind `send`",
+                |p| p.pending_events,
+            ),
+            (
+                "rigz_process_delivered_events_total",
+                "counter",
+                "Events handled by each handler",
+                |p| p.delivered_events,
+            ),
+            (
+                "rigz_process_dropped_events_total",
+                "counter",
+                "Events dropped or rejected because the handler's queue was full",
+                |p| p.dropped_events,
+            ),
+            (
+                "rigz_process_restarts_total",
+                "counter",
+                "Events each handler restarted after they failed",
+                |p| p.restarts,
+            ),
+        ];
+        for (name, kind, help, value) in events {
+            header(&mut out, name, kind, help);
+            for p in &self.processes {
+                let _ = writeln!(
+                    out,
+                    "{name}{{pid=\"{}\",scope=\"{}\"}} {}",
+                    p.pid,
+                    escape(&p.scope),
+                    value(p)
+                );
+            }
+        }
+
+        for (name, help, value) in [
+            (
+                "rigz_stack_depth",
+                "Values on the VM stack",
+                self.stack_depth,
+            ),
+            ("rigz_frame_depth", "Active call frames", self.frame_depth),
+            ("rigz_scopes", "Scopes loaded in the VM", self.scopes),
+            (
+                "rigz_loaded_instructions",
+                "Instructions across all scopes",
+                self.loaded_instructions,
+            ),
+            (
+                "rigz_constants",
+                "Entries in the constant pool",
+                self.constants,
+            ),
+            (
+                "rigz_interned_strings",
+                "Strings interned by the runtime",
+                self.interned_strings,
+            ),
+            (
+                "rigz_shared_states",
+                "Mutexes, channels, & tasks that haven't been freed",
+                self.shared_states,
+            ),
+        ] {
+            header(&mut out, name, "gauge", help);
+            let _ = writeln!(out, "{name} {value}");
+        }
+
+        if let Some(bytes) = self.resident_memory_bytes {
+            header(
+                &mut out,
+                "rigz_resident_memory_bytes",
+                "gauge",
+                "Resident memory of the host process",
+            );
+            let _ = writeln!(out, "rigz_resident_memory_bytes {bytes}");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+pub mod metrics_tests {
+    use crate::vm::{MetricsSnapshot, ProcessMetrics, VMMetrics};
+    use crate::Instruction;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn prometheus_format() {
+        let mut metrics = VMMetrics::default();
+        metrics.record_instruction(&Instruction::Halt);
+        metrics.record_instruction(&Instruction::Ret);
+        metrics.record_instruction(&Instruction::Ret);
+        metrics.record_scope_call(1);
+        metrics.inline_cache_hits = 4;
+        let snapshot = MetricsSnapshot {
+            metrics,
+            scope_names: vec!["main".to_string(), "foo".to_string()],
+            processes: vec![
+                ProcessMetrics {
+                    pid: 0,
+                    scope: "message".to_string(),
+                    running: false,
+                    mailbox_depth: Some(1),
+                    pending_events: 3,
+                    dropped_events: 2,
+                    restarts: 1,
+                    ..Default::default()
+                },
+                ProcessMetrics {
+                    pid: 1,
+                    scope: "spawn".to_string(),
+                    running: true,
+                    ..Default::default()
+                },
+            ],
+            stack_depth: 2,
+            interned_strings: 5,
+            resident_memory_bytes: Some(4096),
+            ..Default::default()
+        };
+        let out = snapshot.to_prometheus();
+        assert!(out.contains("rigz_instructions_total{instruction=\"Ret\"} 2\n"));
+        assert!(out.contains("rigz_instructions_total{instruction=\"Halt\"} 1\n"));
+        assert!(out.contains("rigz_scope_calls_total{scope_id=\"1\",scope=\"foo\"} 1\n"));
+        assert!(out.contains("rigz_inline_cache_total{result=\"hit\"} 4\n"));
+        assert!(out.contains("rigz_processes{state=\"idle\"} 1\n"));
+        assert!(out.contains("rigz_processes{state=\"running\"} 1\n"));
+        assert!(out.contains("rigz_process_mailbox_depth{pid=\"0\",scope=\"message\"} 1\n"));
+        assert!(!out.contains("rigz_process_mailbox_depth{pid=\"1\""));
+        assert!(out.contains("rigz_process_pending_events{pid=\"0\",scope=\"message\"} 3\n"));
+        assert!(out.contains("rigz_process_dropped_events_total{pid=\"0\",scope=\"message\"} 2\n"));
+        assert!(out.contains("rigz_process_restarts_total{pid=\"0\",scope=\"message\"} 1\n"));
+        assert!(out.contains("# TYPE rigz_stack_depth gauge\nrigz_stack_depth 2\n"));
+        assert!(out.contains("rigz_interned_strings 5\n"));
+        assert!(out.contains("rigz_shared_states 0\n"));
+        assert!(out.contains("rigz_resident_memory_bytes 4096\n"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn vm_memory_gauges() {
+        let snapshot = crate::VM::default().metrics();
+        assert!(snapshot.resident_memory_bytes.is_some_and(|b| b > 0));
+        assert!(snapshot
+            .to_prometheus()
+            .contains("# TYPE rigz_resident_memory_bytes gauge\n"));
+    }
+}
@@ -1,32 +1,93 @@
+#[cfg(feature = "std")]
+mod costs;
+mod coverage;
+#[cfg(feature = "std")]
+mod debugger;
+mod hooks;
+mod inline_cache;
+#[cfg(feature = "jit")]
+mod jit;
+mod memo;
+mod metrics;
 mod options;
+#[cfg(feature = "std")]
+mod profiler;
+#[cfg(feature = "std")]
+mod reload;
 mod runner;
+#[cfg(feature = "std")]
+mod signals;
+mod tracer;
 mod values;
 
 use crate::call_frame::Frames;
+#[cfg(not(feature = "js"))]
+use crate::outln;
+use crate::prelude::*;
 use crate::process::ProcessManager;
 use crate::{
-    generate_builder, out, CallFrame, Instruction, RigzBuilder, Runner, Scope, VMStack, Variable,
+    generate_builder, out, CallFrame, CustomInstruction, Instruction, RigzBuilder, Runner, Scope,
+    VMStack, Variable,
 };
+use alloc::sync::Arc;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::time::Duration;
+#[cfg(feature = "std")]
+pub use costs::{CostReport, FunctionCost};
+pub(crate) use coverage::Coverage;
+pub use coverage::{CoverageReport, ScopeCoverage};
+#[cfg(feature = "std")]
+pub(crate) use debugger::Debugger;
+#[cfg(feature = "std")]
+pub use debugger::{BreakpointHook, DebugAction, DebugHandle, DebugState, Local, PauseReason};
+pub use hooks::ObjectHookScopes;
+pub(crate) use hooks::{SharedProgram, VMProgram};
+pub use inline_cache::InlineCaches;
+pub use metrics::{MetricsSnapshot, ProcessMetrics, VMMetrics};
 pub use options::VMOptions;
+#[cfg(feature = "std")]
+pub(crate) use profiler::Profiler;
+#[cfg(feature = "std")]
+pub use profiler::{InstructionProfile, ProfileReport, ScopeProfile};
+#[cfg(feature = "std")]
+pub(crate) use reload::Reloads;
+#[cfg(feature = "std")]
+pub use reload::{changed_scopes, ReloadHandle};
+#[cfg(feature = "std")]
 use rigz_core::{
-    Dependency, Lifecycle, Module, MutableReference, ObjectValue, PrimitiveValue, Snapshot,
-    StackValue, TestResults, VMError,
+    enter_context, enter_deterministic, set_output_source, OutputSource, RuntimeContext,
 };
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::fmt::Debug;
-use std::sync::Arc;
-use std::time::Duration;
+use rigz_core::{
+    Dependency, IndexMap, Lifecycle, Module, MutableReference, ObjectValue, PrimitiveValue,
+    Snapshot, StackValue, TestResults, VMError,
+};
+#[cfg(feature = "std")]
+pub use signals::SignalHandle;
+#[cfg(feature = "std")]
+pub(crate) use signals::Signals;
+pub use tracer::TraceHook;
+pub(crate) use tracer::Tracer;
 pub use values::*;
 
+#[cfg(all(feature = "std", not(feature = "js")))]
+pub use std::time::Instant;
+#[cfg(feature = "js")]
+pub use web_time::Instant;
+
 #[cfg(feature = "threaded")]
 pub type ModulesMap =
-    std::sync::Arc<dashmap::DashMap<&'static str, std::sync::Arc<dyn Module + Send + Sync>>>;
+    alloc::sync::Arc<dashmap::DashMap<&'static str, alloc::sync::Arc<dyn Module + Send + Sync>>>;
 
 #[cfg(not(feature = "threaded"))]
-pub type ModulesMap = HashMap<&'static str, std::rc::Rc<dyn Module>>;
+pub type ModulesMap = HashMap<&'static str, alloc::rc::Rc<dyn Module>>;
+
+pub type Dependencies = Vec<Arc<Dependency>>;
 
-pub type Dependencies = std::sync::RwLock<Vec<Arc<Dependency>>>;
+/// Start of every precompiled program (`.rzbc`)
+pub const BYTECODE_HEADER: &[u8; 4] = b"RZBC";
+/// Incremented when the snapshot format changes, older bytecode must be recompiled
+pub const BYTECODE_VERSION: u8 = 8;
 
 #[derive(Debug)]
 pub struct VM {
@@ -39,7 +100,85 @@ pub struct VM {
     pub options: VMOptions,
     pub lifecycles: Vec<Lifecycle>,
     pub constants: Vec<ObjectValue>,
+    /// Handlers for `Instruction::Custom`, indexed by opcode
+    pub custom_instructions: Vec<CustomInstruction>,
+    /// `hash` & `eq` functions of objects by type name, used while the VM runs to hash & compare those objects
+    pub object_hooks: IndexMap<String, ObjectHookScopes>,
+    /// Built by the first run with `object_hooks`, reused by later runs & processes
+    pub(crate) shared_program: Option<SharedProgram>,
+    pub metrics: VMMetrics,
     pub(crate) process_manager: MutableReference<ProcessManager>,
+    /// Set once a module override is registered, module calls skip the lookup until then
+    pub(crate) module_overrides: bool,
+    pub inline_caches: InlineCaches,
+    /// Instructions run since the last refill, only counted when `VMOptions::max_instructions` is set
+    pub(crate) fuel_used: usize,
+    /// Set by `run_within`, checked every `Deadline::CHECK_INTERVAL` instructions
+    #[cfg(feature = "std")]
+    pub(crate) deadline: Option<Deadline>,
+    #[cfg(feature = "std")]
+    pub(crate) debugger: Debugger,
+    #[cfg(feature = "std")]
+    pub(crate) profiler: Profiler,
+    pub(crate) coverage: Coverage,
+    /// Set by `VM::set_trace_hook`, called before every instruction
+    pub(crate) tracer: Tracer,
+    /// Set once a signal handled by `@on("signal:NAME")` is received, checked before every instruction
+    #[cfg(feature = "std")]
+    pub(crate) signals: Signals,
+    /// Scopes queued by a `ReloadHandle`, checked before every instruction
+    #[cfg(feature = "std")]
+    pub(crate) reloads: Reloads,
+    /// Scopes already run by `Instruction::CallOnce`, cleared by `reset`
+    pub(crate) called_once: HashSet<usize>,
+    /// Captured output & float format, entered while the VM runs and shared with its processes
+    #[cfg(feature = "std")]
+    pub context: RuntimeContext,
+    /// Hot functions compiled to native code, see `VM::enable_jit`
+    #[cfg(feature = "jit")]
+    pub(crate) jit: jit::Jit,
+}
+
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline {
+    pub(crate) at: Instant,
+    duration: Duration,
+    // instructions since the clock was last read
+    ticks: usize,
+}
+
+#[cfg(feature = "std")]
+impl Deadline {
+    /// Reading the clock is much slower than most instructions
+    const CHECK_INTERVAL: usize = 256;
+
+    fn after(duration: Duration) -> Self {
+        Deadline {
+            at: Instant::now() + duration,
+            duration,
+            ticks: 0,
+        }
+    }
+
+    /// Reads the clock on the next check, i.e. after sleeping until the deadline
+    fn check_next(&mut self) {
+        self.ticks = Self::CHECK_INTERVAL;
+    }
+
+    /// Once the deadline has passed every check fails, so nested scopes stop too
+    #[inline]
+    fn expired(&mut self) -> bool {
+        self.ticks += 1;
+        if self.ticks < Self::CHECK_INTERVAL {
+            return false;
+        }
+        if Instant::now() < self.at {
+            self.ticks = 0;
+            return false;
+        }
+        true
+    }
 }
 
 impl RigzBuilder for VM {
@@ -52,15 +191,8 @@ impl RigzBuilder for VM {
 
     #[inline]
     fn register_dependency(&mut self, dependency: Arc<Dependency>) -> usize {
-        let dep = self
-            .dependencies
-            .read()
-            .expect("failed to read dependencies")
-            .len();
-        self.dependencies
-            .get_mut()
-            .expect("failed to lock dependencies")
-            .push(dependency);
+        let dep = self.dependencies.len();
+        self.dependencies.push(dependency);
         dep
     }
 }
@@ -76,6 +208,10 @@ impl Default for VM {
             options: Default::default(),
             lifecycles: Default::default(),
             constants: Default::default(),
+            custom_instructions: Default::default(),
+            object_hooks: Default::default(),
+            shared_program: None,
+            metrics: Default::default(),
             stack: Default::default(),
             #[cfg(feature = "threaded")]
             process_manager: ProcessManager::create()
@@ -83,7 +219,27 @@ impl Default for VM {
                 .into(),
             #[cfg(not(feature = "threaded"))]
             process_manager: ProcessManager::new().into(),
-            dependencies: vec![].into(),
+            dependencies: Vec::new(),
+            module_overrides: false,
+            inline_caches: Default::default(),
+            fuel_used: 0,
+            #[cfg(feature = "std")]
+            deadline: None,
+            #[cfg(feature = "std")]
+            debugger: Default::default(),
+            #[cfg(feature = "std")]
+            profiler: Default::default(),
+            coverage: Default::default(),
+            tracer: Default::default(),
+            #[cfg(feature = "std")]
+            signals: Default::default(),
+            #[cfg(feature = "std")]
+            reloads: Default::default(),
+            called_once: Default::default(),
+            #[cfg(feature = "std")]
+            context: Default::default(),
+            #[cfg(feature = "jit")]
+            jit: Default::default(),
         }
     }
 }
@@ -119,6 +275,7 @@ impl VM {
             Some(c) => {
                 let c = c;
                 let pc = self.frames.current.borrow().pc;
+                let mut base = self.frames.current.borrow().stack;
                 let mut updated = false;
                 loop {
                     let sp = self.sp;
@@ -133,6 +290,10 @@ impl VM {
                                 return VMState::Done(source.resolve(self));
                             }
                             Some(next) => {
+                                // the enclosing frames are returned from as well, keep the lowest base
+                                base = base
+                                    .min(c.borrow().stack)
+                                    .min(self.frames.current.borrow().stack);
                                 self.sp = next.borrow().scope_id;
                                 self.frames.current = next;
                                 updated = true;
@@ -146,6 +307,12 @@ impl VM {
                     self.sp = c.borrow().scope_id;
                     self.frames.current = c;
                 }
+                // only the return value is kept, statements before it may have left values behind
+                if self.stack.len() > base + 1 {
+                    let value = self.next_value("process_ret - return value");
+                    self.stack.truncate(base);
+                    self.stack.push(value);
+                }
                 match ran {
                     false => VMState::Running,
                     true => {
@@ -157,17 +324,119 @@ impl VM {
         }
     }
 
+    #[inline]
+    fn consume_fuel(&mut self) -> Option<VMState> {
+        let max = self.options.max_instructions?;
+        if self.fuel_used >= max {
+            let e: ObjectValue =
+                VMError::BudgetExceeded(format!("Exceeded {max} instructions")).into();
+            return Some(VMState::Done(e.into()));
+        }
+        self.fuel_used += 1;
+        None
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    fn check_deadline(&mut self) -> Option<VMState> {
+        let deadline = self.deadline.as_mut()?;
+        if !deadline.expired() {
+            return None;
+        }
+        let e: ObjectValue =
+            VMError::TimeoutError(format!("Exceeded runtime {:?}", deadline.duration)).into();
+        Some(VMState::Done(e.into()))
+    }
+
+    /// There's no clock without std, `run_within` isn't available
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn check_deadline(&mut self) -> Option<VMState> {
+        None
+    }
+
+    /// Budget & deadline errors stop nested scopes without unwinding their frames, scopes that run out of
+    /// instructions in those frames report the same error instead of a value
+    fn exhausted(&mut self) -> Option<VMState> {
+        match self.options.max_instructions {
+            Some(max) if self.fuel_used >= max => self.consume_fuel(),
+            _ => self.check_deadline(),
+        }
+    }
+
+    /// Resets the instruction budget, i.e. before each REPL input
+    pub fn refill_fuel(&mut self) {
+        self.fuel_used = 0;
+    }
+
+    /// Instructions left before `VMError::BudgetExceeded`, `None` without `VMOptions::max_instructions`
+    pub fn remaining_fuel(&self) -> Option<usize> {
+        self.options
+            .max_instructions
+            .map(|max| max.saturating_sub(self.fuel_used))
+    }
+
     #[inline]
     fn process_instruction(&mut self, instruction: Instruction) -> VMState {
+        if let Some(exhausted) = self.consume_fuel().or_else(|| self.check_deadline()) {
+            return exhausted;
+        }
+        #[cfg(feature = "std")]
+        if let Some(stopped) = self
+            .check_signal()
+            .or_else(|| self.check_breakpoint(&instruction))
+        {
+            return stopped;
+        }
+        if self.options.enable_metrics {
+            self.metrics.record_instruction(&instruction);
+        }
+        #[cfg(feature = "std")]
+        if self.options.profile {
+            self.profile_instruction(&instruction);
+        }
+        if self.options.coverage {
+            self.cover_instruction();
+        }
+        if self.tracer.active() {
+            self.trace_instruction(&instruction);
+        }
         match instruction {
             Instruction::Ret => self.process_ret(false),
+            #[cfg(feature = "jit")]
+            Instruction::Call(scope) if self.call_jit(scope) => VMState::Running,
             instruction => self.process_core_instruction(instruction),
         }
     }
 
     fn process_instruction_scope(&mut self, instruction: Instruction) -> VMState {
+        if let Some(exhausted) = self.consume_fuel().or_else(|| self.check_deadline()) {
+            return exhausted;
+        }
+        #[cfg(feature = "std")]
+        if let Some(stopped) = self
+            .check_signal()
+            .or_else(|| self.check_breakpoint(&instruction))
+        {
+            return stopped;
+        }
+        if self.options.enable_metrics {
+            self.metrics.record_instruction(&instruction);
+        }
+        #[cfg(feature = "std")]
+        if self.options.profile {
+            self.profile_instruction(&instruction);
+        }
+        if self.options.coverage {
+            self.cover_instruction();
+        }
+        if self.tracer.active() {
+            self.trace_instruction(&instruction);
+        }
         match instruction {
             Instruction::Ret => self.process_ret(true),
+            #[cfg(feature = "jit")]
+            Instruction::Call(scope) if self.call_jit(scope) => VMState::Running,
             ins => self.process_core_instruction(ins),
         }
     }
@@ -188,8 +457,9 @@ impl VM {
         }
     }
 
+    #[cfg(feature = "std")]
     pub fn eval_within(&mut self, duration: Duration) -> Result<ObjectValue, VMError> {
-        self.run_within(duration).map_err(|e| e.into())
+        self.run_within(duration)
     }
 
     pub fn add_bindings(&mut self, bindings: HashMap<String, (StackValue, bool)>) {
@@ -200,12 +470,23 @@ impl VM {
             } else {
                 Variable::Let(v)
             };
-            current.variables.insert(k, v);
+            current.variables.insert(k.into(), v);
         }
     }
 
     /// Starts processes for each "On" lifecycle, Errors are returned as Value::Error(VMError)
     pub fn run(&mut self) -> ObjectValue {
+        #[cfg(feature = "std")]
+        let _context = {
+            self.debugger.stopped = false;
+            enter_context(self.context.clone())
+        };
+        #[cfg(feature = "std")]
+        let _deterministic = self
+            .options
+            .deterministic
+            .then(|| enter_deterministic(self.options.seed));
+        let _hooks = self.object_hook_program().map(|p| p.enter());
         self.start_processes();
 
         let mut run = || loop {
@@ -215,14 +496,30 @@ impl VM {
         };
 
         let res = run();
-        self.process_manager.update(move |r| r.close(res))
+        #[cfg(feature = "std")]
+        if self.options.profile {
+            self.profiler.finish();
+        }
+        let res = self.process_manager.update(move |r| r.close(res));
+        #[cfg(feature = "std")]
+        self.stop_listening_for_signals();
+        res
     }
 
     #[inline]
     fn step(&mut self) -> Option<ObjectValue> {
+        #[cfg(feature = "std")]
+        if self.reloads.pending() {
+            self.apply_reloads();
+        }
         let instruction = match self.next_instruction() {
             // TODO this should probably be an error requiring explicit halt, this might still be an error
-            None => return self.stack.pop().map(|e| e.resolve(self).borrow().clone()),
+            None => {
+                if let Some(VMState::Done(e)) = self.exhausted() {
+                    return Some(e.borrow().clone());
+                }
+                return self.stack.pop().map(|e| e.resolve(self).borrow().clone());
+            }
             Some(s) => s,
         };
 
@@ -233,46 +530,72 @@ impl VM {
                 )
             }
             VMState::Running => {}
-            VMState::Done(v) => return Some(v.borrow().clone()),
+            VMState::Done(v) => {
+                // frames are still active for errors that stop the VM
+                self.trace_value(&StackValue::Value(v.clone()));
+                return Some(v.borrow().clone());
+            }
         };
         None
     }
 
+    /// Stops with `VMError::TimeoutError` once `duration` has passed, the clock is checked between instructions
+    /// (including function calls & loops) and `sleep` is cut short at the deadline.
+    /// `@on` handlers run on their own VMs and are not interrupted.
+    #[cfg(feature = "std")]
     pub fn run_within(&mut self, duration: Duration) -> Result<ObjectValue, VMError> {
-        self.start_processes();
-        #[cfg(not(feature = "js"))]
-        let now = std::time::Instant::now();
-        #[cfg(feature = "js")]
-        let now = web_time::Instant::now();
-        let mut run = || loop {
-            let elapsed = now.elapsed();
-            if elapsed > duration {
-                // todo this should be a dedicated error type so it's not misused
-                return VMError::TimeoutError(format!(
-                    "Exceeded runtime {duration:?} - {:?}",
-                    elapsed
-                ))
-                .into();
-            }
-
-            if let Some(v) = self.step() {
-                return v;
-            }
-        };
-        let res = run();
+        let previous = self.deadline.replace(Deadline::after(duration));
+        let res = self.run();
+        self.deadline = previous;
         // todo this needs to be pause processes if timeout error was hit
-        match self.process_manager.update(move |p| p.close(res)) {
+        match res {
             ObjectValue::Primitive(PrimitiveValue::Error(e)) => Err(e),
             o => Ok(o),
         }
     }
 
+    /// Object types, shared with processes so they can create & call objects
+    pub(crate) fn shared_dependencies(&self) -> Vec<Arc<Dependency>> {
+        self.dependencies.clone()
+    }
+
+    /// Program run by processes & object hooks, the cached program is rebuilt if the VM changed since it was built
+    pub(crate) fn shared_program(&mut self) -> SharedProgram {
+        match &self.shared_program {
+            Some(p) if p.is_current(self) => p.clone(),
+            _ => {
+                let program = SharedProgram::new(VMProgram::new(self));
+                self.shared_program = Some(program.clone());
+                program
+            }
+        }
+    }
+
+    /// Hooks of `object_hooks`, None if no object defines them
+    fn object_hook_program(&mut self) -> Option<SharedProgram> {
+        if self.object_hooks.is_empty() {
+            return None;
+        }
+        Some(self.shared_program())
+    }
+
     fn start_processes(&mut self) {
-        let processes = ProcessManager::create_on_processes(self);
-        self.process_manager.update(move |p| p.add(processes));
+        let handlers = self
+            .scopes
+            .iter()
+            .any(|s| matches!(s.lifecycle, Some(Lifecycle::On(_))));
+        if handlers {
+            let program = self.shared_program();
+            let processes = ProcessManager::create_on_processes(self, program);
+            self.process_manager.update(move |p| p.add(processes));
+        }
+        #[cfg(feature = "std")]
+        self.listen_for_signals();
     }
 
     pub fn test(&mut self) -> TestResults {
+        #[cfg(feature = "std")]
+        let _context = enter_context(self.context.clone());
         // todo support parallel tests
         let test_scopes: Vec<_> = self
             .scopes
@@ -294,11 +617,10 @@ impl VM {
 
         let mut passed = 0;
         let mut failed = 0;
-        #[cfg(not(feature = "js"))]
-        let start = std::time::Instant::now();
-        #[cfg(feature = "js")]
-        let start = web_time::Instant::now();
+        #[cfg(feature = "std")]
+        let start = Instant::now();
         let mut failure_messages = Vec::new();
+        let mut failure_output = Vec::new();
         for (s, named) in test_scopes {
             out!("test {named} ... ");
             self.sp = s;
@@ -306,21 +628,32 @@ impl VM {
                 scope_id: s,
                 ..Default::default()
             });
-            let v = self.eval();
+            #[cfg(feature = "std")]
+            let v = {
+                self.context.start_capture();
+                let previous = set_output_source(OutputSource::main(named.as_str()));
+                let v = self.eval();
+                set_output_source(previous);
+                (v, self.context.stop_capture())
+            };
+            // output isn't captured without std
+            #[cfg(not(feature = "std"))]
+            let v = (self.eval(), Vec::new());
             match v {
-                Err(e) => {
-                    #[cfg(not(feature = "js"))]
-                    println!("\x1b[31mFAILED\x1b[0m");
+                (Err(e), output) => {
                     #[cfg(feature = "js")]
                     web_sys::console::log_2(&"%c FAILED".into(), &"color: red".into());
+                    #[cfg(not(feature = "js"))]
+                    outln!("\x1b[31mFAILED\x1b[0m");
                     failed += 1;
+                    failure_output.push((named.to_string(), output));
                     failure_messages.push((named.to_string(), e));
                 }
-                Ok(_) => {
-                    #[cfg(not(feature = "js"))]
-                    println!("\x1b[32mok\x1b[0m");
+                (Ok(_), _) => {
                     #[cfg(feature = "js")]
                     web_sys::console::log_2(&"%c ok".into(), &"color: green".into());
+                    #[cfg(not(feature = "js"))]
+                    outln!("\x1b[32mok\x1b[0m");
                     passed += 1;
                 }
             };
@@ -330,7 +663,11 @@ impl VM {
             passed,
             failed,
             failure_messages,
+            failure_output,
+            #[cfg(feature = "std")]
             duration: start.elapsed(),
+            #[cfg(not(feature = "std"))]
+            duration: Duration::ZERO,
         }
     }
 
@@ -338,7 +675,11 @@ impl VM {
         loop {
             let instruction = match self.next_instruction() {
                 // TODO this should probably be an error requiring explicit halt, result would be none
-                None => return VMState::Done(ObjectValue::default().into()),
+                None => {
+                    return self
+                        .exhausted()
+                        .unwrap_or_else(|| VMState::Done(ObjectValue::default().into()))
+                }
                 Some(s) => s,
             };
 
@@ -349,10 +690,47 @@ impl VM {
         }
     }
 
+    /// Current metrics, counters are only collected when `VMOptions::enable_metrics` is set
+    pub fn metrics(&self) -> MetricsSnapshot {
+        #[cfg(feature = "std")]
+        let (interned_strings, shared_states) = (
+            self.context.intern_table().len(),
+            self.context.shared_states(),
+        );
+        #[cfg(not(feature = "std"))]
+        let (interned_strings, shared_states) = (0, 0);
+        MetricsSnapshot {
+            metrics: self.metrics.clone(),
+            scope_names: self.scopes.iter().map(|s| s.named.clone()).collect(),
+            processes: self.process_manager.apply(|p| p.metrics()),
+            stack_depth: self.stack.len(),
+            frame_depth: self.frames.len(),
+            scopes: self.scopes.len(),
+            loaded_instructions: self.scopes.iter().map(|s| s.instructions.len()).sum(),
+            constants: self.constants.len(),
+            interned_strings,
+            shared_states,
+            resident_memory_bytes: metrics::resident_memory_bytes(),
+        }
+    }
+
+    /// Metrics in the Prometheus text format, suitable for serving from a `/metrics` endpoint
+    #[inline]
+    pub fn metrics_report(&self) -> String {
+        self.metrics().to_prometheus()
+    }
+
+    /// Source line of the last instruction run in the current scope, `None` for scopes without a line table
+    pub fn line(&self) -> Option<usize> {
+        let pc = self.frames.current.borrow().pc;
+        self.scopes[self.sp].line(pc.saturating_sub(1))
+    }
+
     /// All variables are reset and will need to be set again by calling `add_bindings`
     pub fn reset(&mut self) {
         self.sp = 0;
         self.stack.clear();
+        self.called_once.clear();
         self.frames.reset()
     }
 
@@ -366,6 +744,7 @@ impl VM {
         bytes.extend(self.frames.as_bytes());
         bytes.extend(self.lifecycles.as_bytes());
         bytes.extend(self.constants.as_bytes());
+        bytes.extend(self.object_hooks.as_bytes());
         Ok(bytes)
     }
 
@@ -379,8 +758,39 @@ impl VM {
         self.frames = Snapshot::from_bytes(&mut bytes, &"load snapshot: frames")?;
         self.lifecycles = Snapshot::from_bytes(&mut bytes, &"load snapshot: lifecycles")?;
         self.constants = Snapshot::from_bytes(&mut bytes, &"load snapshot: constants")?;
+        self.object_hooks = Snapshot::from_bytes(&mut bytes, &"load snapshot: object hooks")?;
+        self.shared_program = None;
         Ok(())
     }
+
+    /// Precompiled program, should be called before the VM runs. Modules are not included,
+    /// the VM used to deserialize must register the same modules in the same order
+    pub fn serialize(&self) -> Result<Vec<u8>, VMError> {
+        let mut bytes = BYTECODE_HEADER.to_vec();
+        bytes.push(BYTECODE_VERSION);
+        bytes.extend(Snapshot::as_bytes(&env!("CARGO_PKG_VERSION").to_string()));
+        bytes.extend(self.snapshot()?);
+        Ok(bytes)
+    }
+
+    /// Loads a program created by `serialize`, bytecode from other versions of rigz is rejected
+    pub fn deserialize(&mut self, bytes: Vec<u8>) -> Result<(), VMError> {
+        let Some(bytes) = bytes.strip_prefix(BYTECODE_HEADER) else {
+            return Err(VMError::RuntimeError(
+                "Invalid bytecode, missing RZBC header".to_string(),
+            ));
+        };
+        let mut bytes = Vec::from(bytes).into_iter();
+        let version = bytes.next();
+        let rigz: String = Snapshot::from_bytes(&mut bytes, &"bytecode: rigz version")?;
+        if version != Some(BYTECODE_VERSION) || rigz != env!("CARGO_PKG_VERSION") {
+            return Err(VMError::RuntimeError(format!(
+                "Bytecode was compiled by rigz {rigz}, recompile it with rigz {}",
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        self.load_snapshot(bytes.collect())
+    }
 }
 
 #[cfg(test)]
@@ -404,4 +814,21 @@ pub mod vm_tests {
         assert_eq!(vm2.frames, vm.frames);
         assert_eq!(vm2.lifecycles, vm.lifecycles);
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn bytecode() {
+        let mut builder = VMBuilder::new();
+        builder.add_load_instruction(42.into());
+        let vm = builder.build();
+        let bytes = vm.serialize().expect("serialize failed");
+        let mut vm2 = VM::default();
+        vm2.deserialize(bytes.clone()).expect("deserialize failed");
+        assert_eq!(vm2.scopes, vm.scopes);
+        assert_eq!(vm2.eval(), Ok(42.into()));
+
+        let mut vm3 = VM::default();
+        assert!(vm3.deserialize(bytes[4..].to_vec()).is_err());
+        let snapshot = vm.snapshot().expect("snapshot failed");
+        assert!(vm3.deserialize(snapshot).is_err());
+    }
 }
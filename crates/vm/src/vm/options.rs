@@ -1,13 +1,32 @@
-use rigz_core::{Snapshot, VMError};
-use std::fmt::Display;
-use std::vec::IntoIter;
+use crate::prelude::*;
+use crate::ProcessExecutor;
+use alloc::vec::IntoIter;
+use core::fmt::Display;
+use rigz_core::{OverflowPolicy, Snapshot, VMError};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct VMOptions {
     pub enable_logging: bool,
     pub disable_modules: bool,
     pub disable_variable_cleanup: bool,
+    pub enable_metrics: bool,
     pub max_depth: usize,
+    pub overflow: OverflowPolicy,
+    /// Errors created while running include the active call frames, see `VMError::traceback`
+    pub enable_traceback: bool,
+    /// Instructions the VM may run before stopping with `VMError::BudgetExceeded`,
+    /// see `VM::refill_fuel` to continue running after the budget is used
+    pub max_instructions: Option<usize>,
+    /// Records call counts & time per scope and instruction, see `VM::profile_report`
+    pub profile: bool,
+    /// Records how often each instruction runs, see `VM::coverage_report`
+    pub coverage: bool,
+    /// Seeds all randomness with `seed`, freezes the clock behind a virtual time source that only moves on sleep,
+    /// and rejects nondeterministic modules (Http, File, Env) so runs can be replayed
+    pub deterministic: bool,
+    pub seed: u64,
+    /// How `spawn` runs processes, deterministic VMs always use `ProcessExecutor::Threads`
+    pub executor: ProcessExecutor,
 }
 
 impl Default for VMOptions {
@@ -16,7 +35,16 @@ impl Default for VMOptions {
             enable_logging: true,
             disable_modules: false,
             disable_variable_cleanup: false,
+            enable_metrics: false,
             max_depth: 1024,
+            overflow: OverflowPolicy::Checked,
+            enable_traceback: false,
+            max_instructions: None,
+            profile: false,
+            coverage: false,
+            deterministic: false,
+            seed: 0,
+            executor: ProcessExecutor::Threads,
         }
     }
 }
@@ -27,8 +55,23 @@ impl Snapshot for VMOptions {
         options |= self.enable_logging as u8;
         options |= (self.disable_modules as u8) << 1;
         options |= (self.disable_variable_cleanup as u8) << 2;
+        options |= (self.enable_metrics as u8) << 3;
+        options |= (self.overflow as u8) << 4;
+        options |= (self.enable_traceback as u8) << 6;
+        options |= (self.profile as u8) << 7;
         let mut result = vec![options];
         result.extend((self.max_depth as u64).to_le_bytes());
+        result.extend(self.max_instructions.as_bytes());
+        result.push(self.coverage as u8 | (self.deterministic as u8) << 1);
+        result.extend(self.seed.to_le_bytes());
+        match self.executor {
+            ProcessExecutor::Threads => result.push(0),
+            ProcessExecutor::Cooperative { workers, quantum } => {
+                result.push(1);
+                result.extend(workers.as_bytes());
+                result.extend(quantum.as_bytes());
+            }
+        }
         result
     }
 
@@ -37,12 +80,62 @@ impl Snapshot for VMOptions {
             Some(b) => b,
             None => return Err(VMError::RuntimeError(format!("Missing {location} byte"))),
         };
+        let overflow = match (byte >> 4) & 3 {
+            0 => OverflowPolicy::Checked,
+            1 => OverflowPolicy::Wrapping,
+            2 => OverflowPolicy::Saturating,
+            p => {
+                return Err(VMError::RuntimeError(format!(
+                    "Invalid {location} overflow policy {p}"
+                )))
+            }
+        };
         let max_depth = Snapshot::from_bytes(bytes, &format!("{location} max_depth"))?;
+        let max_instructions =
+            Snapshot::from_bytes(bytes, &format!("{location} max_instructions"))?;
+        let Some(flags) = bytes.next() else {
+            return Err(VMError::RuntimeError(format!(
+                "Missing {location} flags byte"
+            )));
+        };
+        let mut seed = [0; 8];
+        for b in seed.iter_mut() {
+            *b = bytes
+                .next()
+                .ok_or_else(|| VMError::RuntimeError(format!("Missing {location} seed bytes")))?;
+        }
+        let seed = u64::from_le_bytes(seed);
+        let executor = match bytes.next() {
+            Some(0) => ProcessExecutor::Threads,
+            Some(1) => ProcessExecutor::Cooperative {
+                workers: Snapshot::from_bytes(bytes, &format!("{location} executor workers"))?,
+                quantum: Snapshot::from_bytes(bytes, &format!("{location} executor quantum"))?,
+            },
+            Some(e) => {
+                return Err(VMError::RuntimeError(format!(
+                    "Invalid {location} executor {e}"
+                )))
+            }
+            None => {
+                return Err(VMError::RuntimeError(format!(
+                    "Missing {location} executor byte"
+                )))
+            }
+        };
         Ok(VMOptions {
             enable_logging: (byte & 1) == 1,
             disable_modules: (byte & 1 << 1) == 2,
             disable_variable_cleanup: (byte & 1 << 2) == 4,
+            enable_metrics: (byte & 1 << 3) == 8,
             max_depth,
+            overflow,
+            enable_traceback: (byte & 1 << 6) == 64,
+            max_instructions,
+            profile: (byte & 1 << 7) == 128,
+            coverage: (flags & 1) == 1,
+            deterministic: (flags & 1 << 1) == 2,
+            seed,
+            executor,
         })
     }
 }
@@ -50,7 +143,8 @@ impl Snapshot for VMOptions {
 #[cfg(test)]
 pub mod tests {
     use crate::vm::VMOptions;
-    use rigz_core::Snapshot;
+    use crate::ProcessExecutor;
+    use rigz_core::{OverflowPolicy, Snapshot};
     use wasm_bindgen_test::*;
 
     #[wasm_bindgen_test(unsupported = test)]
@@ -59,6 +153,18 @@ pub mod tests {
             enable_logging: true,
             disable_modules: true,
             disable_variable_cleanup: true,
+            enable_metrics: true,
+            overflow: OverflowPolicy::Saturating,
+            enable_traceback: true,
+            max_instructions: Some(500),
+            profile: true,
+            coverage: true,
+            deterministic: true,
+            seed: 42,
+            executor: ProcessExecutor::Cooperative {
+                workers: 4,
+                quantum: 100,
+            },
             ..Default::default()
         };
         let byte = options.as_bytes();
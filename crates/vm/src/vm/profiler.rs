@@ -0,0 +1,231 @@
+use super::Instant;
+use crate::prelude::*;
+use crate::{Instruction, VM};
+use alloc::collections::BTreeMap;
+use core::fmt::{Display, Formatter, Write};
+use core::time::Duration;
+use rigz_core::humanize_duration;
+
+/// Collected while `VMOptions::profile` is set, the time between two instructions is recorded for the first one
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Profiler {
+    // start & name of the running instruction, recorded once the next instruction starts
+    running: Option<(Instant, &'static str)>,
+    // scope ids of the running instruction's frames, outermost first
+    path: Vec<usize>,
+    // self time & instructions run by each distinct call stack
+    stacks: HashMap<Vec<usize>, (Duration, u64)>,
+    instructions: BTreeMap<&'static str, (u64, Duration)>,
+    calls: BTreeMap<usize, u64>,
+}
+
+impl Profiler {
+    #[inline]
+    pub(crate) fn record_call(&mut self, scope_id: usize) {
+        *self.calls.entry(scope_id).or_default() += 1;
+    }
+
+    fn record(&mut self, now: Instant) {
+        let Some((start, name)) = self.running.take() else {
+            return;
+        };
+        let elapsed = now.duration_since(start);
+        let (count, time) = self.instructions.entry(name).or_default();
+        *count += 1;
+        *time += elapsed;
+        match self.stacks.get_mut(self.path.as_slice()) {
+            Some((time, count)) => {
+                *time += elapsed;
+                *count += 1;
+            }
+            None => {
+                self.stacks.insert(self.path.clone(), (elapsed, 1));
+            }
+        }
+    }
+
+    /// Records the last instruction, called when the VM stops running
+    pub(crate) fn finish(&mut self) {
+        self.record(Instant::now());
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScopeProfile {
+    pub scope_id: usize,
+    pub name: String,
+    pub calls: u64,
+    /// time spent in the scope, including scopes it called
+    pub total: Duration,
+    /// time spent running the scope's own instructions
+    pub self_time: Duration,
+    /// instructions run by the scope, including scopes it called
+    pub instructions: u64,
+    pub self_instructions: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstructionProfile {
+    pub name: &'static str,
+    pub count: u64,
+    pub time: Duration,
+}
+
+/// Created by `VM::profile_report`, scopes & instructions are sorted by time, slowest first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub total: Duration,
+    pub scopes: Vec<ScopeProfile>,
+    pub instructions: Vec<InstructionProfile>,
+    /// scope names of each call stack, outermost first, with the time spent in the innermost scope
+    pub stacks: Vec<(Vec<String>, Duration)>,
+}
+
+impl ProfileReport {
+    /// Tables of scopes & instructions
+    pub fn to_flat(&self) -> String {
+        let mut out = String::new();
+        let percent = |d: &Duration| {
+            if self.total.is_zero() {
+                0.0
+            } else {
+                d.as_secs_f64() * 100.0 / self.total.as_secs_f64()
+            }
+        };
+        let _ = writeln!(out, "Total: {}", humanize_duration(self.total));
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{:>8} {:>12} {:>7} {:>12} {:>7}  scope",
+            "calls", "total", "%", "self", "%"
+        );
+        for scope in &self.scopes {
+            let _ = writeln!(
+                out,
+                "{:>8} {:>12.2?} {:>6.2}% {:>12.2?} {:>6.2}%  {} ({})",
+                scope.calls,
+                scope.total,
+                percent(&scope.total),
+                scope.self_time,
+                percent(&scope.self_time),
+                scope.name,
+                scope.scope_id
+            );
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{:>10} {:>12} {:>7}  instruction",
+            "count", "time", "%"
+        );
+        for instruction in &self.instructions {
+            let _ = writeln!(
+                out,
+                "{:>10} {:>12.2?} {:>6.2}%  {}",
+                instruction.count,
+                instruction.time,
+                percent(&instruction.time),
+                instruction.name
+            );
+        }
+        out
+    }
+
+    /// One line per call stack, `main;foo;bar <nanoseconds>`, the collapsed format read by flamegraph.pl,
+    /// inferno, & speedscope
+    pub fn to_folded(&self) -> String {
+        let mut out = String::new();
+        for (stack, time) in &self.stacks {
+            let _ = writeln!(out, "{} {}", stack.join(";"), time.as_nanos());
+        }
+        out
+    }
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.to_flat())
+    }
+}
+
+impl VM {
+    #[inline]
+    pub(crate) fn profile_instruction(&mut self, instruction: &Instruction) {
+        let now = Instant::now();
+        self.profiler.record(now);
+        self.profiler.path.clear();
+        let frames = self.frames.frames.iter().chain([&self.frames.current]);
+        self.profiler.path.extend(
+            frames
+                .filter_map(|f| f.try_borrow().ok())
+                .map(|f| f.scope_id),
+        );
+        self.profiler.running = Some((now, instruction.name()));
+    }
+
+    /// Timings collected while `VMOptions::profile` is set
+    pub fn profile_report(&self) -> ProfileReport {
+        let profiler = &self.profiler;
+        let name = |scope_id: usize| {
+            self.scopes
+                .get(scope_id)
+                .map(|s| s.named.clone())
+                .unwrap_or_else(|| format!("scope {scope_id}"))
+        };
+
+        let mut scopes: BTreeMap<usize, ScopeProfile> = BTreeMap::new();
+        for (&scope_id, &calls) in &profiler.calls {
+            scopes
+                .entry(scope_id)
+                .or_insert_with(|| ScopeProfile {
+                    scope_id,
+                    name: name(scope_id),
+                    ..Default::default()
+                })
+                .calls = calls;
+        }
+        let mut total = Duration::ZERO;
+        let mut stacks = Vec::with_capacity(profiler.stacks.len());
+        for (path, &(time, count)) in &profiler.stacks {
+            total += time;
+            for (index, &scope_id) in path.iter().enumerate() {
+                let scope = scopes.entry(scope_id).or_insert_with(|| ScopeProfile {
+                    scope_id,
+                    name: name(scope_id),
+                    ..Default::default()
+                });
+                // recursive scopes appear more than once, their time is only counted once
+                if !path[..index].contains(&scope_id) {
+                    scope.total += time;
+                    scope.instructions += count;
+                }
+                if index + 1 == path.len() {
+                    scope.self_time += time;
+                    scope.self_instructions += count;
+                }
+            }
+            stacks.push((path.iter().map(|&s| name(s)).collect::<Vec<_>>(), time));
+        }
+        stacks.sort();
+
+        let mut scopes: Vec<_> = scopes.into_values().collect();
+        scopes.sort_by(|a, b| b.total.cmp(&a.total).then(a.scope_id.cmp(&b.scope_id)));
+        let mut instructions: Vec<_> = profiler
+            .instructions
+            .iter()
+            .map(|(&name, &(count, time))| InstructionProfile { name, count, time })
+            .collect();
+        instructions.sort_by(|a, b| b.time.cmp(&a.time).then(a.name.cmp(b.name)));
+        ProfileReport {
+            total,
+            scopes,
+            instructions,
+            stacks,
+        }
+    }
+
+    /// Clears timings collected by `VMOptions::profile`
+    pub fn reset_profile(&mut self) {
+        self.profiler = Profiler::default();
+    }
+}
@@ -0,0 +1,202 @@
+use crate::prelude::*;
+use crate::{Scope, VM};
+use core::sync::atomic::{AtomicBool, Ordering};
+use rigz_core::VMError;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Debug, Default)]
+struct ReloadShared {
+    scopes: Mutex<Vec<(usize, Scope)>>,
+    // mirrors `!scopes.is_empty()` so the VM doesn't take the lock for every instruction
+    pending: AtomicBool,
+}
+
+impl ReloadShared {
+    fn scopes(&self) -> MutexGuard<'_, Vec<(usize, Scope)>> {
+        self.scopes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Scopes waiting to replace the running ones, see `VM::reload_handle`
+#[derive(Debug, Default)]
+pub(crate) struct Reloads {
+    shared: Arc<ReloadShared>,
+}
+
+impl Reloads {
+    #[inline]
+    pub(crate) fn pending(&self) -> bool {
+        self.shared.pending.load(Ordering::Acquire)
+    }
+}
+
+/// Replaces scopes of a running VM from another thread, i.e. to reload functions after their file changes
+#[derive(Clone, Debug)]
+pub struct ReloadHandle {
+    shared: Arc<ReloadShared>,
+}
+
+impl ReloadHandle {
+    /// Scopes are replaced before the next instruction that isn't part of them, a newer version of the same scope
+    /// replaces one that is still waiting
+    pub fn reload(&self, scopes: Vec<(usize, Scope)>) {
+        let mut pending = self.shared.scopes();
+        for (index, scope) in scopes {
+            pending.retain(|(i, _)| *i != index);
+            pending.push((index, scope));
+        }
+        self.shared
+            .pending
+            .store(!pending.is_empty(), Ordering::Release);
+    }
+}
+
+/// Scopes of `next` that differ from `current`, both must come from the same program so scope indexes match.
+/// Fails when scopes were added, removed, or reordered (i.e. a function was added) or `main` changed,
+/// those changes require a restart
+pub fn changed_scopes(current: &[Scope], next: &[Scope]) -> Result<Vec<(usize, Scope)>, VMError> {
+    let current_names: Vec<_> = current.iter().map(|s| &s.named).collect();
+    let next_names: Vec<_> = next.iter().map(|s| &s.named).collect();
+    if current_names != next_names {
+        return Err(VMError::UnsupportedOperation(
+            "Cannot reload, functions were added or removed".to_string(),
+        ));
+    }
+    // lines of main shift whenever a function above it changes, only its instructions matter
+    if current.first().map(|s| &s.instructions) != next.first().map(|s| &s.instructions) {
+        return Err(VMError::UnsupportedOperation(
+            "Cannot reload, main changed".to_string(),
+        ));
+    }
+    Ok(current
+        .iter()
+        .zip(next)
+        .enumerate()
+        .skip(1)
+        .filter(|(_, (c, n))| c != n)
+        .map(|(index, (_, n))| (index, n.clone()))
+        .collect())
+}
+
+impl VM {
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle {
+            shared: self.reloads.shared.clone(),
+        }
+    }
+
+    /// Replaces the bytecode of `index`, variables, memoized results of other scopes, & processes are kept.
+    /// Scopes can't be replaced while they're running, use `VM::reload_handle` to wait until they return.
+    pub fn replace_scope(&mut self, index: usize, scope: Scope) -> Result<Scope, VMError> {
+        if index >= self.scopes.len() {
+            return Err(VMError::ScopeDoesNotExist(format!(
+                "Cannot replace scope {index}, it does not exist"
+            )));
+        }
+        if self.is_running(index) {
+            return Err(VMError::UnsupportedOperation(format!(
+                "Cannot replace scope {index} ({}) while it's running",
+                self.scopes[index].named
+            )));
+        }
+        // compiled code may have inlined calls to the old scope
+        #[cfg(feature = "jit")]
+        self.jit.reset();
+        self.shared_program = None;
+        Ok(core::mem::replace(&mut self.scopes[index], scope))
+    }
+
+    fn is_running(&self, index: usize) -> bool {
+        self.frames.current.borrow().scope_id == index
+            || self
+                .frames
+                .frames
+                .iter()
+                .any(|f| f.borrow().scope_id == index)
+    }
+
+    /// Replaces the queued scopes that aren't running, the others wait for the next instruction
+    pub(crate) fn apply_reloads(&mut self) {
+        let shared = self.reloads.shared.clone();
+        let mut pending = shared.scopes();
+        let scopes = core::mem::take(&mut *pending);
+        for (index, scope) in scopes {
+            if self.is_running(index) {
+                pending.push((index, scope));
+            } else if let Err(e) = self.replace_scope(index, scope) {
+                log::warn!("Failed to reload scope {index} - {e}");
+            }
+        }
+        shared.pending.store(!pending.is_empty(), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+pub mod reload_tests {
+    use crate::{changed_scopes, Instruction, RigzBuilder, Scope, VMBuilder};
+    use rigz_core::{BinaryOperation, ObjectValue};
+    use wasm_bindgen_test::*;
+
+    fn function(value: i64) -> Scope {
+        Scope {
+            named: "f".to_string(),
+            instructions: vec![Instruction::Load(value.into()), Instruction::Ret],
+            ..Default::default()
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn replaced_scope_keeps_variables() {
+        let mut builder = VMBuilder::new();
+        builder
+            .add_load_instruction(1.into())
+            .add_load_let_instruction("a".to_string());
+        let f = builder.enter_scope("f".to_string(), vec![], None);
+        builder.add_load_instruction(2.into()).exit_scope(0);
+        builder
+            .add_call_instruction(f)
+            .add_get_variable_instruction("a".to_string())
+            .add_binary_instruction(BinaryOperation::Add)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        assert_eq!(vm.run(), ObjectValue::from(3));
+
+        let old = vm.replace_scope(f, function(10)).unwrap();
+        assert_eq!(old.instructions[0], Instruction::Load(2.into()));
+        // run main again without clearing its variables
+        vm.frames.current.borrow_mut().pc = 2;
+        assert_eq!(vm.run(), ObjectValue::from(11));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn handle_replaces_scope_before_next_instruction() {
+        let mut builder = VMBuilder::new();
+        let f = builder.enter_scope("f".to_string(), vec![], None);
+        builder.add_load_instruction(2.into()).exit_scope(0);
+        builder.add_call_instruction(f).add_halt_instruction();
+        let mut vm = builder.build();
+        vm.reload_handle().reload(vec![(f, function(10))]);
+        assert_eq!(vm.run(), ObjectValue::from(10));
+        assert!(!vm.reloads.pending());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn running_scope_is_not_replaced() {
+        let mut vm = VMBuilder::new().build();
+        assert!(vm.replace_scope(0, Scope::default()).is_err());
+        assert!(vm.replace_scope(1, Scope::default()).is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn changed_scopes_are_found() {
+        let main = Scope::default();
+        let current = vec![main.clone(), function(1), function(2)];
+        let next = vec![main.clone(), function(1), function(3)];
+        assert_eq!(changed_scopes(&current, &next), Ok(vec![(2, function(3))]));
+        assert!(changed_scopes(&current, &next[..2]).is_err());
+
+        let mut changed_main = main;
+        changed_main.instructions.push(Instruction::Halt);
+        assert!(changed_scopes(&current, &[changed_main, function(1), function(2)]).is_err());
+    }
+}
@@ -1,23 +1,126 @@
+use super::memo;
+#[cfg(feature = "std")]
+use super::Instant;
+use crate::prelude::*;
+use crate::process::ProcessManager;
 use crate::{
-    runner_common, CallFrame, CallType, ModulesMap, ResolvedModule, Runner, Scope, VMOptions,
-    Variable, VM,
+    runner_common, CallFrame, CallType, ModulesMap, Parallel, ResolvedModule, Runner, Scope,
+    VMOptions, Variable, VM,
 };
+use core::fmt::Display;
+use core::ops::Deref;
+use core::time::Duration;
 use itertools::Itertools;
 use log_derive::{logfn, logfn_inputs};
-use rigz_core::{Lifecycle, ObjectValue, ResolveValue, RigzArgs, StackValue, VMError};
-use std::fmt::Display;
-use std::ops::Deref;
+#[cfg(feature = "std")]
+use rigz_core::advance_virtual_clock;
+use rigz_core::{
+    AsPrimitive, Interned, Lifecycle, MemoResult, ObjectValue, ResolveValue, RigzArgs, StackValue,
+    TraceFrame, VMError, WithTypeInfo,
+};
+#[cfg(feature = "std")]
 use std::thread;
-use std::time::Duration;
+
+#[cfg(feature = "std")]
+const SELECT_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 #[allow(unused_variables)]
 impl Runner for VM {
     runner_common!();
 
+    fn traceback(&self) -> Vec<TraceFrame> {
+        let frames = self.frames.frames.iter().chain([&self.frames.current]);
+        frames
+            .rev()
+            .filter_map(|f| f.try_borrow().ok())
+            .filter_map(|f| {
+                let scope = self.scopes.get(f.scope_id)?;
+                Some(TraceFrame {
+                    name: scope.named.clone(),
+                    scope_id: f.scope_id,
+                    line: scope.line(f.pc.saturating_sub(1)),
+                })
+            })
+            .collect()
+    }
+
+    fn override_module(&mut self, module: &str, func: &str, scope: usize) -> Result<(), VMError> {
+        self.module_overrides = true;
+        // not a valid identifier, so it can't shadow or be shadowed by variables
+        self.frames.load_let(
+            format!("{module}.{func}").into(),
+            StackValue::ScopeId(scope),
+        )
+    }
+
+    fn resolve_extension(
+        &mut self,
+        module: String,
+        func: &str,
+    ) -> Option<(ResolvedModule, Option<usize>)> {
+        let Some(receiver) = self.with_receiver(|v| v.rigz_type()) else {
+            return self.get_module(module).map(|m| (m, None));
+        };
+
+        let site = (self.sp, self.frames.current.borrow().pc.saturating_sub(1));
+        if let Some(resolved) = self.inline_caches.get(site, &module, func, &receiver) {
+            if self.options.enable_metrics {
+                self.metrics.inline_cache_hits += 1;
+            }
+            return Some(resolved);
+        }
+        if self.options.enable_metrics {
+            self.metrics.inline_cache_misses += 1;
+        }
+        let resolved = self.get_module(module.clone())?;
+        let overload = self
+            .with_receiver(|v| resolved.resolve_extension(v, func))
+            .flatten();
+        self.inline_caches.insert(
+            site,
+            module,
+            func.to_string(),
+            receiver,
+            resolved.clone(),
+            overload,
+        );
+        Some((resolved, overload))
+    }
+
+    fn module_override(&self, module: &str, func: &str) -> Option<usize> {
+        if !self.module_overrides {
+            return None;
+        }
+
+        let Some(StackValue::ScopeId(scope)) =
+            self.frames.get_variable(&format!("{module}.{func}"))
+        else {
+            return None;
+        };
+        // calls made by the override itself use the original function
+        let mut parent = {
+            let current = self.frames.current.borrow();
+            if current.scope_id == scope {
+                return None;
+            }
+            current.parent
+        };
+        while let Some(index) = parent {
+            let frame = self.frames[index].borrow();
+            if frame.scope_id == scope {
+                return None;
+            }
+            parent = frame.parent;
+        }
+        Some(scope)
+    }
+
     fn update_scope<F>(&mut self, index: usize, mut update: F) -> Result<(), VMError>
     where
         F: FnMut(&mut Scope) -> Result<(), VMError>,
     {
+        // instructions may move, so cached call sites could point at different calls
+        self.inline_caches.clear();
         match self.scopes.get_mut(index) {
             None => Err(VMError::ScopeDoesNotExist(format!(
                 "Scope {index} does not exist"
@@ -43,6 +146,14 @@ impl Runner for VM {
             return Err(err);
         }
 
+        if self.options.enable_metrics {
+            self.metrics.record_scope_call(scope_index);
+        }
+        #[cfg(feature = "std")]
+        if self.options.profile {
+            self.profiler.record_call(scope_index);
+        }
+
         let current = self
             .frames
             .current
@@ -61,6 +172,16 @@ impl Runner for VM {
                 self.load_let(arg)?;
             }
         }
+        self.frames.current.borrow_mut().stack = self.stack.len();
+        Ok(())
+    }
+
+    fn call_once(&mut self, scope_index: usize) -> Result<(), VMError> {
+        if self.called_once.contains(&scope_index) {
+            return Ok(());
+        }
+        self.call_frame(scope_index)?;
+        self.called_once.insert(scope_index);
         Ok(())
     }
 
@@ -115,7 +236,12 @@ impl Runner for VM {
                         }
                     };
 
-                    memo.results.get(&call_args).cloned()
+                    if memo.results.is_empty() {
+                        if let Some(persist) = &memo.persist {
+                            memo.results = memo::load(persist)?;
+                        }
+                    }
+                    memo.get(&call_args, memo::now())
                 }
             },
         };
@@ -145,8 +271,17 @@ impl Runner for VM {
                             _ => unreachable!(),
                         };
 
-                        let call_args = call_args.into_iter().map(|v| v.borrow().clone()).collect();
-                        memo.results.insert(call_args, value.borrow().clone());
+                        let call_args: Vec<_> =
+                            call_args.into_iter().map(|v| v.borrow().clone()).collect();
+                        let now = memo::now();
+                        if let Some(persist) = &memo.persist {
+                            let result = MemoResult {
+                                value: value.borrow().clone(),
+                                created: now,
+                            };
+                            memo::store(persist, &call_args, &result)?;
+                        }
+                        memo.insert(call_args, value.borrow().clone(), now);
                         value
                     }
                 }
@@ -163,24 +298,21 @@ impl Runner for VM {
         dep: usize,
         call_type: CallType,
     ) -> Result<ObjectValue, VMError> {
-        match self.dependencies.read() {
-            Ok(deps) => match deps.get(dep) {
-                None => Err(VMError::RuntimeError(format!("Dependency not found {dep}"))),
-                Some(dep) => {
-                    let res = match call_type {
-                        CallType::Create => {
-                            let c = dep.create;
-                            c(args)?.into()
-                        }
-                        CallType::Call(func) => {
-                            let c = dep.call;
-                            c(func, args)?
-                        }
-                    };
-                    Ok(res)
-                }
-            },
-            Err(e) => Err(VMError::RuntimeError(format!("Failed to get deps {e}"))),
+        match self.dependencies.get(dep) {
+            None => Err(VMError::RuntimeError(format!("Dependency not found {dep}"))),
+            Some(dep) => {
+                let res = match call_type {
+                    CallType::Create => {
+                        let c = dep.create;
+                        c(args)?.into()
+                    }
+                    CallType::Call(func) => {
+                        let c = dep.call;
+                        c(func, args)?
+                    }
+                };
+                Ok(res)
+            }
         }
     }
 
@@ -204,6 +336,131 @@ impl Runner for VM {
         Ok(())
     }
 
+    /// Polls each source in order until one has a message, channels are polled without holding the process manager
+    fn select(&mut self, sources: usize, timeout: bool) -> Result<(), VMError> {
+        let timeout = match timeout {
+            false => None,
+            true => Some(self.next_resolved_value("select").borrow().to_usize()?),
+        };
+        let sources: Vec<_> = self
+            .resolve_args(sources)
+            .into_iter()
+            .map(|s| s.borrow().clone())
+            .collect();
+        #[cfg(feature = "std")]
+        let deadline = timeout.map(|t| Instant::now() + Duration::from_millis(t as u64));
+        loop {
+            for (index, source) in sources.iter().enumerate() {
+                let message = match source {
+                    ObjectValue::Object(o) => o.poll_receive(),
+                    s => {
+                        let pid = s.to_usize()?;
+                        self.process_manager.update(|p| p.try_receive(pid))
+                    }
+                };
+                let message = match message {
+                    Ok(None) => continue,
+                    Ok(Some(m)) => m,
+                    Err(e) => e.into(),
+                };
+                self.store_value(message.into());
+                self.store_value((index as i64).into());
+                return Ok(());
+            }
+            #[cfg(feature = "std")]
+            let expired = deadline.is_some_and(|d| Instant::now() >= d);
+            // without threads nothing else runs while the VM waits, sources are only polled once
+            #[cfg(not(feature = "std"))]
+            let expired = match timeout {
+                Some(_) => true,
+                None => {
+                    return Err(VMError::RuntimeError(
+                        "select without a timeout would wait forever".to_string(),
+                    ))
+                }
+            };
+            if expired {
+                // the index after the last source runs the `after` arm
+                self.store_value(ObjectValue::default().into());
+                self.store_value((sources.len() as i64).into());
+                return Ok(());
+            }
+            #[cfg(feature = "std")]
+            thread::sleep(SELECT_POLL_INTERVAL);
+        }
+    }
+
+    /// Errors from the function are returned instead of the result, the first error in list order wins
+    fn parallel(&mut self, op: Parallel) -> Result<(), VMError> {
+        let list = self.next_resolved_value("parallel");
+        let values = match list.borrow().deref() {
+            ObjectValue::List(l) => l.clone(),
+            v => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "Cannot call {op} on {v}, expected List"
+                )))
+            }
+        };
+        let scope = match self.next_value("parallel") {
+            StackValue::ScopeId(s) => match self.scopes.get(s) {
+                None => {
+                    return Err(VMError::ScopeDoesNotExist(format!(
+                        "Scope does not exist - {s}"
+                    )))
+                }
+                Some(s) => s.clone(),
+            },
+            v => {
+                return Err(VMError::UnsupportedOperation(format!(
+                    "{op} expects a function, received {:?}",
+                    v.resolve(self).borrow()
+                )))
+            }
+        };
+        let program = self.shared_program();
+        let results =
+            ProcessManager::parallel(&self.process_manager, scope, values.clone(), program)?;
+        if let Some(e) = results.iter().find(|r| r.is_error()) {
+            self.store_value(e.clone().into());
+            return Ok(());
+        }
+        let value: ObjectValue = match op {
+            Parallel::Map => results.into(),
+            Parallel::Filter => values
+                .into_iter()
+                .zip(results)
+                .filter_map(|(v, keep)| keep.to_bool().then_some(v))
+                .collect::<Vec<_>>()
+                .into(),
+            Parallel::Each => ObjectValue::default(),
+        };
+        self.store_value(value.into());
+        Ok(())
+    }
+
+    /// Overrides of `module.func` still apply, the handler runs like a module call otherwise
+    fn custom(&mut self, opcode: usize, args: usize) -> Result<(), VMError> {
+        let Some(custom) = self.custom_instructions.get(opcode) else {
+            return Err(VMError::RuntimeError(format!(
+                "Custom instruction {opcode} is not registered"
+            )));
+        };
+        let (handler, deterministic) = (custom.handler, custom.deterministic);
+        if let Some(scope) = self.module_override(&custom.module, &custom.func) {
+            return self.call_frame(scope);
+        }
+        if self.options.deterministic && !deterministic {
+            return Err(VMError::UnsupportedOperation(format!(
+                "{} is nondeterministic, it can't be used with VMOptions::deterministic",
+                custom.module
+            )));
+        }
+        let args = self.resolve_args(args).into();
+        let v = handler(args).unwrap_or_else(|e| e.into());
+        self.store_value(v.into());
+        Ok(())
+    }
+
     fn spawn(&mut self, scope_id: usize, timeout: Option<usize>) -> Result<(), VMError> {
         let scope = match self.scopes.get(scope_id) {
             None => {
@@ -213,11 +470,10 @@ impl Runner for VM {
             }
             Some(s) => s.clone(),
         };
-        let options = self.options;
-        let m = self.modules();
+        let program = self.shared_program();
         let pid = self
             .process_manager
-            .update_with_ref(move |p, pm| p.spawn(scope, vec![], options, m, timeout, pm))?;
+            .update_with_ref(move |p, pm| p.spawn(scope, vec![], program, timeout, pm))?;
         self.store_value((pid as i64).into());
         Ok(())
     }
@@ -242,7 +498,22 @@ impl Runner for VM {
         module.call(func, args)
     }
 
-    fn sleep(&self, duration: Duration) {
-        thread::sleep(duration);
+    #[cfg(feature = "std")]
+    fn sleep(&mut self, duration: Duration) {
+        if advance_virtual_clock(duration.as_nanos() as i64) {
+            return;
+        }
+        let duration = match &self.deadline {
+            None => duration,
+            Some(d) => duration.min(d.at.saturating_duration_since(Instant::now())),
+        };
+        self.sleep_until_signal(duration);
+        if let Some(d) = &mut self.deadline {
+            d.check_next();
+        }
     }
+
+    /// There's no clock to wait on without std
+    #[cfg(not(feature = "std"))]
+    fn sleep(&mut self, duration: Duration) {}
 }
@@ -0,0 +1,160 @@
+#[cfg(feature = "threaded")]
+use crate::process::ProcessManager;
+use crate::{VMState, VM};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+#[cfg(all(feature = "threaded", unix))]
+use itertools::Itertools;
+#[cfg(feature = "threaded")]
+use log::warn;
+#[cfg(all(feature = "threaded", unix))]
+use rigz_core::Lifecycle;
+#[cfg(feature = "threaded")]
+use rigz_core::MutableReference;
+use rigz_core::{ObjectValue, Signal, VMError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+#[derive(Debug, Default)]
+struct SignalShared {
+    received: Mutex<Option<Signal>>,
+    // mirrors `received.is_some()` so the VM doesn't take the lock for every instruction
+    pending: AtomicBool,
+    // wakes `sleep` once a signal is received
+    notify: Condvar,
+}
+
+impl SignalShared {
+    fn received(&self) -> MutexGuard<'_, Option<Signal>> {
+        self.received.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Listens for the signals handled by `@on("signal:NAME")` while the VM runs
+#[derive(Debug, Default)]
+pub(crate) struct Signals {
+    shared: Arc<SignalShared>,
+    #[cfg(all(feature = "threaded", unix))]
+    listener: Option<signal_hook::iterator::Handle>,
+}
+
+/// Sends a signal to the VM from another thread, i.e. to forward Ctrl+C from a REPL.
+/// The `@on("signal:NAME")` handlers receive the signal & the VM stops before its next instruction.
+#[derive(Clone, Debug)]
+pub struct SignalHandle {
+    shared: Arc<SignalShared>,
+    #[cfg(feature = "threaded")]
+    process_manager: MutableReference<ProcessManager>,
+}
+
+impl SignalHandle {
+    pub fn send(&self, signal: Signal) {
+        // handlers are queued first, the VM waits for them once it stops
+        #[cfg(feature = "threaded")]
+        if let Err(e) = self
+            .process_manager
+            .update(|p| p.send_event(&signal.event(), Vec::new()))
+        {
+            warn!("Failed to handle {signal} - {e}")
+        }
+        *self.shared.received() = Some(signal);
+        self.shared.pending.store(true, Ordering::Release);
+        self.shared.notify.notify_all();
+    }
+
+    pub fn received(&self) -> Option<Signal> {
+        *self.shared.received()
+    }
+}
+
+impl VM {
+    pub fn signal_handle(&self) -> SignalHandle {
+        SignalHandle {
+            shared: self.signals.shared.clone(),
+            #[cfg(feature = "threaded")]
+            process_manager: self.process_manager.clone(),
+        }
+    }
+
+    /// Signal that stopped the last run, the host should exit with `Signal::exit_code`
+    pub fn received_signal(&self) -> Option<Signal> {
+        *self.signals.shared.received()
+    }
+
+    #[inline]
+    pub(crate) fn check_signal(&self) -> Option<VMState> {
+        if !self.signals.shared.pending.load(Ordering::Acquire) {
+            return None;
+        }
+        let signal = self.received_signal()?;
+        let e: ObjectValue = VMError::RuntimeError(format!("Interrupted by SIG{signal}")).into();
+        Some(VMState::Done(e.into()))
+    }
+
+    /// Waits for the duration unless a signal is received first
+    pub(crate) fn sleep_until_signal(&self, duration: Duration) {
+        let received = self.signals.shared.received();
+        if received.is_some() {
+            return;
+        }
+        let _ = self
+            .signals
+            .shared
+            .notify
+            .wait_timeout_while(received, duration, |r| r.is_none());
+    }
+
+    /// Starts listening for the OS signals handled by `@on("signal:NAME")`, without handlers signals keep
+    /// their default behavior
+    #[cfg(all(feature = "threaded", unix))]
+    pub(crate) fn listen_for_signals(&mut self) {
+        self.reset_signal();
+        let numbers: Vec<_> = self
+            .scopes
+            .iter()
+            .filter_map(|s| match &s.lifecycle {
+                Some(Lifecycle::On(e)) => e.signal().ok().flatten(),
+                _ => None,
+            })
+            .map(|s| s.number())
+            .unique()
+            .collect();
+        if numbers.is_empty() {
+            return;
+        }
+        let mut signals = match signal_hook::iterator::Signals::new(&numbers) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to listen for signals {numbers:?} - {e}");
+                return;
+            }
+        };
+        self.signals.listener = Some(signals.handle());
+        let handle = self.signal_handle();
+        std::thread::spawn(move || {
+            for number in signals.forever() {
+                if let Some(signal) = Signal::from_number(number) {
+                    handle.send(signal);
+                }
+            }
+        });
+    }
+
+    /// Signals can only be sent through `SignalHandle`
+    #[cfg(not(all(feature = "threaded", unix)))]
+    pub(crate) fn listen_for_signals(&mut self) {
+        self.reset_signal();
+    }
+
+    /// Once the listener is closed signals have their default behavior again
+    pub(crate) fn stop_listening_for_signals(&mut self) {
+        #[cfg(all(feature = "threaded", unix))]
+        if let Some(listener) = self.signals.listener.take() {
+            listener.close();
+        }
+    }
+
+    fn reset_signal(&self) {
+        *self.signals.shared.received() = None;
+        self.signals.shared.pending.store(false, Ordering::Release);
+    }
+}
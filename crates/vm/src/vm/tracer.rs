@@ -0,0 +1,94 @@
+use crate::prelude::*;
+use crate::{CallFrame, Instruction, VM};
+use core::fmt::{Debug, Formatter};
+
+#[cfg(not(feature = "send"))]
+pub type TraceHook = Box<dyn FnMut(&Instruction, &CallFrame)>;
+
+/// Hooks move with the VM, so they must be `Send` too
+#[cfg(feature = "send")]
+pub type TraceHook = Box<dyn FnMut(&Instruction, &CallFrame) + Send>;
+
+#[derive(Default)]
+pub(crate) struct Tracer {
+    hook: Option<TraceHook>,
+}
+
+impl Debug for Tracer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Tracer")
+            .field("hook", &self.hook.is_some())
+            .finish()
+    }
+}
+
+impl Tracer {
+    #[inline]
+    pub(crate) fn active(&self) -> bool {
+        self.hook.is_some()
+    }
+}
+
+impl VM {
+    /// Called before each instruction runs with the current frame, `frame.pc` has already moved past the instruction.
+    /// Native code from `VM::enable_jit` is skipped while tracing so every instruction is seen.
+    #[cfg(not(feature = "send"))]
+    pub fn set_trace_hook<F: FnMut(&Instruction, &CallFrame) + 'static>(&mut self, hook: F) {
+        self.tracer.hook = Some(Box::new(hook));
+    }
+
+    /// Called before each instruction runs with the current frame, `frame.pc` has already moved past the instruction.
+    /// Native code from `VM::enable_jit` is skipped while tracing so every instruction is seen.
+    #[cfg(feature = "send")]
+    pub fn set_trace_hook<F: FnMut(&Instruction, &CallFrame) + Send + 'static>(&mut self, hook: F) {
+        self.tracer.hook = Some(Box::new(hook));
+    }
+
+    /// Returns the hook, if one was set
+    pub fn clear_trace_hook(&mut self) -> Option<TraceHook> {
+        self.tracer.hook.take()
+    }
+
+    #[inline]
+    pub(crate) fn trace_instruction(&mut self, instruction: &Instruction) {
+        if let Some(hook) = self.tracer.hook.as_mut() {
+            hook(instruction, &self.frames.current.borrow());
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tracer_tests {
+    use crate::{Instruction, RigzBuilder, VMBuilder};
+    use rigz_core::{BinaryOperation, ObjectValue};
+    use std::sync::{Arc, Mutex};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn hook_sees_every_instruction() {
+        let mut builder = VMBuilder::new();
+        builder
+            .add_load_instruction(2.into())
+            .add_load_instruction(3.into())
+            .add_binary_instruction(BinaryOperation::Add)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        let traced = Arc::new(Mutex::new(vec![]));
+        let t = traced.clone();
+        vm.set_trace_hook(move |instruction, frame| {
+            t.lock().unwrap().push((frame.pc, instruction.clone()))
+        });
+        assert_eq!(vm.run(), ObjectValue::from(5));
+        assert_eq!(
+            *traced.lock().unwrap(),
+            vec![
+                (1, Instruction::Load(2.into())),
+                (2, Instruction::Load(3.into())),
+                (3, Instruction::Binary(BinaryOperation::Add)),
+                (4, Instruction::Halt),
+            ]
+        );
+        assert!(vm.clear_trace_hook().is_some());
+        assert!(!vm.tracer.active());
+    }
+}
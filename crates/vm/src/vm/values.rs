@@ -1,13 +1,11 @@
 use crate::vm::VM;
 use crate::Runner;
-use rigz_core::{ObjectValue, ResolveValue, VMError};
-use std::cell::RefCell;
-use std::rc::Rc;
+use rigz_core::{ObjectValue, ResolveValue, Shared, VMError};
 
 pub enum VMState {
     Running,
-    Done(Rc<RefCell<ObjectValue>>),
-    Ran(Rc<RefCell<ObjectValue>>),
+    Done(Shared<ObjectValue>),
+    Ran(Shared<ObjectValue>),
 }
 
 impl From<VMError> for VMState {
@@ -24,8 +22,10 @@ impl ResolveValue for VM {
     }
 
     #[inline]
-    fn handle_scope(&mut self, scope: usize) -> Rc<RefCell<ObjectValue>> {
-        let current = self.sp;
+    fn handle_scope(&mut self, scope: usize) -> Shared<ObjectValue> {
+        // frames are compared instead of scopes, recursive calls share a scope and an early return from an if
+        // scope returns from the enclosing function too, leaving fewer frames than before the call
+        let depth = self.frames.len();
         match self.call_frame(scope) {
             Ok(_) => {}
             Err(e) => {
@@ -38,7 +38,7 @@ impl ResolveValue for VM {
             VMState::Done(v) => return v,
             VMState::Ran(v) => v,
         };
-        while current != self.sp {
+        while self.frames.len() > depth {
             self.stack.push(v.into());
             v = match self.run_scope() {
                 VMState::Running => unreachable!(),
@@ -49,7 +49,7 @@ impl ResolveValue for VM {
         v
     }
 
-    fn get_constant(&self, index: usize) -> Rc<RefCell<ObjectValue>> {
+    fn get_constant(&self, index: usize) -> Shared<ObjectValue> {
         match self.constants.get(index) {
             None => {
                 let o: ObjectValue =
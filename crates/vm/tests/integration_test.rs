@@ -1,7 +1,7 @@
 mod vm_test {
     use rigz_core::{
-        BinaryOperation, Definition, Lifecycle, Module, ObjectValue, PrimitiveValue, RigzArgs,
-        RigzType, TestLifecycle, TestResults, VMError,
+        BinaryOperation, Definition, Interned, Lifecycle, Module, ObjectValue, PrimitiveValue,
+        RigzArgs, RigzType, TestLifecycle, TestResults, VMError,
     };
     use rigz_vm::{Instruction, LoadValue, RigzBuilder, Scope, VMBuilder, VM};
     use wasm_bindgen_test::*;
@@ -192,7 +192,7 @@ mod vm_test {
 
     #[wasm_bindgen_test(unsupported = test)]
     fn multi_mut_scope() {
-        let a: String = "a".to_string();
+        let a: Interned = "a".into();
         let mut vm = VM::from_scopes(vec![
             Scope {
                 instructions: vec![
@@ -209,10 +209,10 @@ mod vm_test {
             },
             Scope {
                 instructions: vec![
-                    Instruction::GetMutableVariable("self".to_string()),
+                    Instruction::GetMutableVariable("self".into()),
                     Instruction::Load(3.into()),
                     Instruction::BinaryAssign(BinaryOperation::Mul),
-                    Instruction::GetMutableVariable("self".to_string()),
+                    Instruction::GetMutableVariable("self".into()),
                     Instruction::Ret,
                 ],
                 set_self: Some(true),
@@ -224,7 +224,7 @@ mod vm_test {
 
     #[wasm_bindgen_test(unsupported = test)]
     fn multi_mut_scope_get_var_between() {
-        let f: String = "f".to_string();
+        let f: Interned = "f".into();
         let mut vm = VM::from_scopes(vec![
             Scope {
                 instructions: vec![
@@ -243,10 +243,10 @@ mod vm_test {
             },
             Scope {
                 instructions: vec![
-                    Instruction::GetMutableVariable("self".to_string()),
+                    Instruction::GetMutableVariable("self".into()),
                     Instruction::Load(3.into()),
                     Instruction::BinaryAssign(BinaryOperation::Mul),
-                    Instruction::GetMutableVariable("self".to_string()),
+                    Instruction::GetMutableVariable("self".into()),
                     Instruction::Ret,
                 ],
                 set_self: Some(true),
@@ -283,6 +283,7 @@ mod vm_test {
                 lifecycle: Some(Lifecycle::Test(TestLifecycle)),
                 args: Vec::new(),
                 set_self: None,
+                lines: Vec::new(),
             },
         ]);
         assert_eq!(
@@ -291,7 +292,7 @@ mod vm_test {
                 passed: 0,
                 failed: 1,
                 failure_messages: vec![("test".into(), VMError::InvalidModule("Std".to_string()))],
-                duration: Default::default(),
+                ..Default::default()
             }
         )
     }
@@ -313,4 +314,197 @@ mod vm_test {
         let mut vm = builder.build();
         assert_eq!(vm.run(), vec![1, 4, 9].into())
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn profile_counts_calls() {
+        let mut builder = VMBuilder::new();
+        let scope = builder.enter_scope("add".to_string(), vec![], None);
+        builder
+            .add_binary_instruction(BinaryOperation::Add)
+            .exit_scope(0)
+            .add_load_instruction(1.into())
+            .add_load_instruction(2.into())
+            .add_call_instruction(scope)
+            .add_load_instruction(3.into())
+            .add_call_instruction(scope)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        vm.options.profile = true;
+        assert_eq!(vm.eval(), Ok(6.into()));
+
+        let report = vm.profile_report();
+        let add = report
+            .scopes
+            .iter()
+            .find(|s| s.name == "add")
+            .expect("add was not profiled");
+        assert_eq!(add.calls, 2);
+        assert!(add.total <= report.total);
+        let main = report.scopes.iter().find(|s| s.name == "main").unwrap();
+        assert_eq!(main.total, report.total);
+        let add_instructions = report
+            .instructions
+            .iter()
+            .find(|i| i.name == "Binary")
+            .map(|i| i.count);
+        assert_eq!(add_instructions, Some(2));
+        assert!(report.to_folded().contains("main;add "));
+
+        vm.reset_profile();
+        assert!(vm.profile_report().scopes.is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn breakpoint_in_function() {
+        use rigz_core::Shared;
+        use rigz_vm::{DebugAction, PauseReason};
+
+        let mut builder = VMBuilder::new();
+        let scope = builder.enter_scope("add".to_string(), vec![], None);
+        builder
+            .add_binary_instruction(BinaryOperation::Add)
+            .exit_scope(0)
+            .add_load_instruction(1.into())
+            .add_load_mut_instruction("a".to_string())
+            .add_get_variable_instruction("a".to_string())
+            .add_load_instruction(2.into())
+            .add_call_instruction(scope)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        vm.set_breakpoint(scope, 0);
+
+        let states = Shared::new(Vec::new());
+        let hit = states.clone();
+        vm.on_break(move |state| {
+            hit.borrow_mut().push(state.clone());
+            if state.reason == PauseReason::Breakpoint {
+                DebugAction::Step
+            } else {
+                DebugAction::Continue
+            }
+        });
+        assert_eq!(vm.eval(), Ok(3.into()));
+
+        let states = states.borrow();
+        assert_eq!(states.len(), 2);
+        let breakpoint = &states[0];
+        assert_eq!(breakpoint.reason, PauseReason::Breakpoint);
+        assert_eq!((breakpoint.scope_id, breakpoint.pc), (scope, 0));
+        assert_eq!(
+            breakpoint.instruction,
+            Instruction::Binary(BinaryOperation::Add)
+        );
+        assert_eq!(breakpoint.stack, vec![Some(1.into()), Some(2.into())]);
+        assert_eq!(
+            breakpoint
+                .frames
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["add", "main"]
+        );
+        assert_eq!(states[1].reason, PauseReason::Step);
+        assert_eq!(states[1].scope_id, scope);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn breakpoint_locals_and_stop() {
+        use rigz_vm::{DebugAction, Local};
+
+        let mut builder = VMBuilder::new();
+        builder
+            .add_load_instruction(1.into())
+            .add_load_mut_instruction("a".to_string())
+            .add_get_variable_instruction("a".to_string())
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        vm.set_breakpoint(0, 2);
+        vm.on_break(|state| {
+            assert_eq!(
+                state.locals,
+                vec![Local {
+                    name: "a".to_string(),
+                    mutable: true,
+                    value: Some(1.into()),
+                }]
+            );
+            DebugAction::Stop
+        });
+        assert_eq!(
+            vm.eval(),
+            Err(VMError::RuntimeError("Stopped by debugger".to_string()))
+        );
+        assert!(vm.remove_breakpoint(0, 2));
+        assert!(!vm.remove_breakpoint(0, 2));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn breakpoint_set_from_hook() {
+        use rigz_core::Shared;
+        use rigz_vm::DebugAction;
+
+        let mut builder = VMBuilder::new();
+        builder
+            .add_load_instruction(1.into())
+            .add_load_instruction(2.into())
+            .add_binary_instruction(BinaryOperation::Add)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        vm.set_breakpoint(0, 0);
+        let handle = vm.debug_handle();
+        let hits = Shared::new(Vec::new());
+        let hit = hits.clone();
+        vm.on_break(move |state| {
+            hit.borrow_mut().push(state.pc);
+            handle.clear_breakpoints();
+            if state.pc == 0 {
+                handle.set_breakpoint(0, 2);
+            }
+            DebugAction::Continue
+        });
+        assert_eq!(vm.eval(), Ok(3.into()));
+        assert_eq!(*hits.borrow(), vec![0, 2]);
+        assert!(vm.debug_handle().breakpoints().is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn pause_from_another_thread() {
+        use std::thread;
+        use std::time::Duration;
+
+        let mut builder = VMBuilder::new();
+        builder.add_load_instruction(42.into());
+        let mut vm = builder.build();
+        let handle = vm.debug_handle();
+        vm.pause();
+        let resume = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            assert!(handle.is_paused());
+            handle.resume();
+        });
+        assert_eq!(vm.eval(), Ok(42.into()));
+        resume.join().expect("resume failed");
+    }
+
+    #[cfg(feature = "send")]
+    #[wasm_bindgen_test(unsupported = test)]
+    fn vm_moves_across_threads() {
+        use std::thread;
+
+        let mut builder = VMBuilder::new();
+        builder
+            .add_load_instruction(1.into())
+            .add_load_mut_instruction("a".to_string())
+            .add_get_variable_instruction("a".to_string())
+            .add_load_instruction(2.into())
+            .add_binary_instruction(BinaryOperation::Add)
+            .add_halt_instruction();
+        let mut vm = builder.build();
+        let result = thread::spawn(move || {
+            let result = vm.eval();
+            (vm, result)
+        });
+        let (_vm, result) = result.join().expect("thread failed");
+        assert_eq!(result, Ok(3.into()));
+    }
 }
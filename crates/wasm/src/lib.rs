@@ -0,0 +1,71 @@
+use rigz_core::{OutputStream, RuntimeContext};
+use rigz_runtime::Runtime;
+use wasm_bindgen::prelude::*;
+
+/// Result of `eval`, either `value` or `error` is set
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Evaluation {
+    pub value: Option<String>,
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs the script with the default modules, output is captured instead of written to the console.
+/// Scripts stop with an error after `max_instructions` when it's set.
+#[wasm_bindgen]
+pub fn eval(input: String, max_instructions: Option<usize>) -> Evaluation {
+    let context = RuntimeContext::default();
+    context.start_capture();
+    let result = Runtime::create(input).and_then(|mut runtime| {
+        let vm = runtime.vm_mut();
+        vm.context = context.clone();
+        vm.options.max_instructions = max_instructions;
+        runtime.run()
+    });
+
+    let mut evaluation = Evaluation::default();
+    for captured in context.stop_capture() {
+        match captured.stream {
+            OutputStream::Stdout => evaluation.stdout.push_str(&captured.content),
+            OutputStream::Stderr => evaluation.stderr.push_str(&captured.content),
+        }
+    }
+    match result {
+        Ok(v) => evaluation.value = Some(v.to_string()),
+        Err(e) => evaluation.error = Some(e.to_string()),
+    }
+    evaluation
+}
+
+#[cfg(test)]
+pub mod wasm_tests {
+    use crate::eval;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn output_is_captured() {
+        let result = eval("puts 'hi'\n1 + 2".to_string(), None);
+        assert_eq!(result.value, Some("3".to_string()));
+        assert_eq!(result.error, None);
+        assert_eq!(result.stdout, "hi\n");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn instructions_are_limited() {
+        let input = r#"
+            fn fib(n: Number) -> Number
+                if n <= 1
+                    n
+                else
+                    (fib n - 1) + (fib n - 2)
+                end
+            end
+            fib 15
+            "#;
+        let result = eval(input.to_string(), Some(100));
+        assert_eq!(result.value, None);
+        assert!(result.error.is_some());
+    }
+}
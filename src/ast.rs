@@ -11,6 +11,13 @@ pub struct AstArgs {
     main: PathBuf,
     #[arg(short, long, default_value = "false", help = "Print VM before run")]
     vm: bool,
+    #[arg(
+        short,
+        long,
+        default_value = "false",
+        help = "Run main and print the instruction count & average instructions per call of each function"
+    )]
+    costs: bool,
 }
 
 pub(crate) fn ast(args: AstArgs) {
@@ -22,7 +29,15 @@ pub(crate) fn ast(args: AstArgs) {
     let program = rigz_ast::parse(&str, ParserOptions::default()).expect("Failed to read input");
     println!("AST:\n{program:#?}");
     if args.vm {
-        let vm = Runtime::create(str).expect("Failed to create VM");
+        let vm = Runtime::create(str.clone()).expect("Failed to create VM");
         println!("\nVM:\n{:#?}", vm.vm())
     }
+    if args.costs {
+        let mut runtime = Runtime::create(str).expect("Failed to create VM");
+        runtime.vm_mut().options.profile = true;
+        if let Err(e) = runtime.run() {
+            eprintln!("\nRun failed, averages only include calls before the error - {e}");
+        }
+        println!("\nCosts:\n{}", runtime.vm().cost_report())
+    }
 }
@@ -0,0 +1,38 @@
+use clap::Args;
+use rigz_ast::{bundle as bundle_imports, ParserOptions};
+use rigz_runtime::DefaultImportResolver;
+use std::fs::write;
+use std::path::PathBuf;
+use std::process::exit;
+
+#[derive(Args)]
+pub struct BundleArgs {
+    #[arg(help = "Rigz Entrypoint")]
+    main: PathBuf,
+    #[arg(short, long, help = "Output file, prints the bundle when omitted")]
+    output: Option<PathBuf>,
+}
+
+/// Writes a single file with every file & url import embedded and tests removed
+pub(crate) fn bundle(args: BundleArgs) {
+    let parser_options = ParserOptions {
+        current_directory: args.main.parent().map(|p| p.to_path_buf()),
+        ..Default::default()
+    };
+    let bundled = match bundle_imports(&args.main, &parser_options, &DefaultImportResolver) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Bundle Failed: {e}");
+            exit(1)
+        }
+    };
+    match args.output {
+        None => print!("{bundled}"),
+        Some(output) => {
+            if let Err(e) = write(&output, bundled) {
+                eprintln!("Failed to write {} - {e}", output.display());
+                exit(1)
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use clap::Args;
+use rigz_ast::ParserOptions;
+use rigz_runtime::runtime::RuntimeOptions;
+use rigz_runtime::Runtime;
+use std::fs::{read_to_string, write};
+use std::path::PathBuf;
+use std::process::exit;
+
+#[derive(Args)]
+pub struct CompileArgs {
+    #[arg(help = "Rigz Entrypoint")]
+    main: PathBuf,
+    #[arg(
+        short,
+        long,
+        help = "Output file, defaults to the entrypoint with a .rzbc extension"
+    )]
+    output: Option<PathBuf>,
+}
+
+pub(crate) fn compile(args: CompileArgs) {
+    let contents = read_to_string(&args.main).expect("Failed to read main");
+    let parser_options = ParserOptions {
+        current_directory: args.main.parent().map(|p| p.to_path_buf()),
+        ..Default::default()
+    };
+    let runtime =
+        match Runtime::create_with_options(contents, RuntimeOptions::default(), parser_options) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Compile Failed: {e}");
+                exit(1)
+            }
+        };
+    let bytes = match runtime.bytecode() {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Compile Failed: {e}");
+            exit(1)
+        }
+    };
+    let output = args
+        .output
+        .unwrap_or_else(|| args.main.with_extension("rzbc"));
+    if let Err(e) = write(&output, bytes) {
+        eprintln!("Failed to write {} - {e}", output.display());
+        exit(1)
+    }
+}
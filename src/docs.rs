@@ -0,0 +1,839 @@
+use crate::utils::{current_dir, path_to_string, read_rigz_files};
+use clap::Args;
+use rigz_ast::{
+    Element, Expression, FunctionArgument, FunctionDeclaration, FunctionSignature, ImportValue,
+    ObjectDefinition, ParserOptions, Program, Statement, TraitDefinition,
+};
+use rigz_core::{Lifecycle, RigzType};
+use rigz_runtime::Runtime;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[derive(Args)]
+pub struct DocsArgs {
+    #[arg(help = "Files to document, defaults to current directory")]
+    input: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        default_value = "docs",
+        help = "Directory for the generated pages, unused with --serve"
+    )]
+    out: PathBuf,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Serve the documentation locally, pages reload when files change"
+    )]
+    serve: bool,
+    #[arg(long, default_value = "4000", help = "Port used by --serve")]
+    port: u16,
+}
+
+/// Generated pages by file name, every page is at the root so links are the same when served or written to disk
+#[derive(Default)]
+struct Site {
+    pages: BTreeMap<String, String>,
+}
+
+struct SearchEntry {
+    name: String,
+    kind: &'static str,
+    href: String,
+}
+
+struct SourceFile {
+    path: PathBuf,
+    name: String,
+    page: String,
+    contents: Result<String, String>,
+}
+
+struct SiteBuilder {
+    site: Site,
+    entries: Vec<SearchEntry>,
+    /// type, module, & object names to the page documenting them
+    links: HashMap<String, String>,
+    /// module name to the pages of files importing it
+    imported_by: HashMap<String, Vec<(String, String)>>,
+}
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 0; display: flex; color: #222; }
+nav { width: 16rem; padding: 1rem; border-right: 1px solid #ddd; min-height: 100vh; }
+nav ul { list-style: none; padding-left: 0; }
+main { padding: 1rem 2rem; flex: 1; max-width: 60rem; }
+code, pre { font-family: ui-monospace, monospace; background: #f5f5f5; padding: 0.1rem 0.3rem; }
+pre { padding: 0.5rem; overflow-x: auto; }
+section { border-top: 1px solid #eee; padding: 0.5rem 0; }
+small { color: #777; }
+.error { color: #b00; }
+"#;
+
+const SEARCH: &str = r#"
+const search = document.getElementById('search');
+const results = document.getElementById('results');
+search.addEventListener('input', () => {
+  const query = search.value.trim().toLowerCase();
+  results.innerHTML = '';
+  if (!query) return;
+  for (const entry of ENTRIES.filter(e => e.name.toLowerCase().includes(query)).slice(0, 50)) {
+    const li = document.createElement('li');
+    const a = document.createElement('a');
+    a.href = entry.href;
+    a.textContent = entry.name;
+    const kind = document.createElement('small');
+    kind.textContent = ' ' + entry.kind;
+    li.append(a, kind);
+    results.append(li);
+  }
+});
+"#;
+
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// File names are used as page names, anything other than letters, digits, `-`, & `.` becomes `_`
+fn slug(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title} - rigz docs</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<nav>
+<a href="index.html">Index</a>
+<p><input id="search" type="search" placeholder="Search"></p>
+<ul id="results"></ul>
+</nav>
+<main>
+{body}
+</main>
+<script src="search.js"></script>
+</body>
+</html>
+"#,
+        title = escape(title)
+    )
+}
+
+/// Comment lines directly above `line` (1 based), lifecycles between the comment and the definition are skipped
+fn doc_comment(contents: &str, line: usize) -> String {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut doc = Vec::new();
+    let mut index = line.saturating_sub(1);
+    while index > 0 {
+        index -= 1;
+        let l = lines.get(index).map(|l| l.trim()).unwrap_or_default();
+        if let Some(comment) = l.strip_prefix('#') {
+            doc.push(comment.strip_prefix(' ').unwrap_or(comment));
+        } else if !l.starts_with('@') {
+            break;
+        }
+    }
+    doc.reverse();
+    doc.join("\n")
+}
+
+impl SiteBuilder {
+    fn new() -> Self {
+        SiteBuilder {
+            site: Site::default(),
+            entries: Vec::new(),
+            links: HashMap::new(),
+            imported_by: HashMap::new(),
+        }
+    }
+
+    /// Identifiers in the rendered type that name a documented module, type, or object become links
+    fn link_type(&self, rigz_type: &RigzType) -> String {
+        let rendered = rigz_type.to_string();
+        let mut result = String::new();
+        let mut word = String::new();
+        let flush = |word: &mut String, result: &mut String| {
+            if word.is_empty() {
+                return;
+            }
+            match self.links.get(word.as_str()) {
+                None => result.push_str(&escape(word)),
+                Some(href) => result.push_str(&format!(r#"<a href="{href}">{}</a>"#, escape(word))),
+            }
+            word.clear();
+        };
+        for c in rendered.chars() {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut result);
+                result.push_str(&escape(&c.to_string()));
+            }
+        }
+        flush(&mut word, &mut result);
+        result
+    }
+
+    fn argument(&self, argument: &FunctionArgument) -> String {
+        let mut result = String::new();
+        if argument.var_arg {
+            result.push_str("var ");
+        }
+        if argument.rest {
+            result.push_str("..");
+        }
+        result.push_str(&escape(&argument.name));
+        if argument.function_type.rigz_type != RigzType::Any {
+            result.push_str(": ");
+            if argument.function_type.mutable {
+                result.push_str("mut ");
+            }
+            result.push_str(&self.link_type(&argument.function_type.rigz_type));
+        }
+        match &argument.default {
+            None => {}
+            Some(Expression::Value(v)) => {
+                result.push_str(&format!(" = {}", escape(&v.to_string())))
+            }
+            Some(Expression::Symbol(s)) => result.push_str(&format!(" = :{}", escape(s))),
+            Some(_) => result.push_str(" = ..."),
+        }
+        result
+    }
+
+    fn signature(&self, name: &str, signature: &FunctionSignature) -> String {
+        let mut result = "fn ".to_string();
+        if let Some(self_type) = &signature.self_type {
+            if self_type.mutable {
+                result.push_str("mut ");
+            }
+            result.push_str(&self.link_type(&self_type.rigz_type));
+            result.push('.');
+        }
+        result.push_str(&escape(name));
+        if !signature.arguments.is_empty() {
+            let args: Vec<_> = signature
+                .arguments
+                .iter()
+                .map(|a| self.argument(a))
+                .collect();
+            result.push_str(&format!("({})", args.join(", ")));
+        }
+        let return_type = &signature.return_type;
+        if return_type.rigz_type != RigzType::Any {
+            result.push_str(" -> ");
+            if return_type.mutable {
+                result.push_str("mut ");
+            }
+            result.push_str(&self.link_type(&return_type.rigz_type));
+        }
+        result
+    }
+
+    fn qualified_name(name: &str, signature: &FunctionSignature) -> String {
+        match &signature.self_type {
+            None => name.to_string(),
+            Some(t) => format!("{}.{name}", t.rigz_type),
+        }
+    }
+
+    fn function(
+        &mut self,
+        href: &str,
+        name: &str,
+        signature: &FunctionSignature,
+        doc: String,
+    ) -> String {
+        let qualified = Self::qualified_name(name, signature);
+        let anchor = slug(&format!("fn-{qualified}"));
+        self.entries.push(SearchEntry {
+            name: qualified,
+            kind: "function",
+            href: format!("{href}#{anchor}"),
+        });
+        let mut result = format!(
+            r#"<section id="{anchor}"><code>{}</code>"#,
+            self.signature(name, signature)
+        );
+        if !doc.is_empty() {
+            result.push_str(&format!("<pre>{}</pre>", escape(&doc)));
+        }
+        result.push_str("</section>\n");
+        result
+    }
+
+    fn declarations(&mut self, href: &str, functions: &[FunctionDeclaration]) -> String {
+        let mut result = String::new();
+        for f in functions {
+            let section = match f {
+                FunctionDeclaration::Declaration {
+                    name,
+                    type_definition,
+                } => self.function(href, name, type_definition, String::new()),
+                FunctionDeclaration::Definition(d) => {
+                    self.function(href, &d.name, &d.type_definition, String::new())
+                }
+            };
+            result.push_str(&section);
+        }
+        result
+    }
+
+    fn module(&mut self, definition: &TraitDefinition) {
+        let href = format!("module-{}.html", slug(&definition.name));
+        let mut body = format!("<h1>Module {}</h1>\n", escape(&definition.name));
+        if let Some(files) = self.imported_by.get(&definition.name) {
+            let files: Vec<_> = files
+                .iter()
+                .map(|(name, page)| format!(r#"<a href="{page}">{}</a>"#, escape(name)))
+                .collect();
+            body.push_str(&format!("<p>Imported by {}</p>\n", files.join(", ")));
+        }
+        body.push_str(&self.declarations(&href, &definition.functions));
+        self.site
+            .pages
+            .insert(href, page(&format!("Module {}", definition.name), &body));
+    }
+
+    fn object(&mut self, href: &str, object: &ObjectDefinition, doc: String) -> String {
+        let name = object.rigz_type.to_string();
+        let anchor = slug(&format!("object-{name}"));
+        let mut result = format!(
+            r#"<section id="{anchor}"><h2>object {}</h2>"#,
+            escape(&name)
+        );
+        if !doc.is_empty() {
+            result.push_str(&format!("<pre>{}</pre>", escape(&doc)));
+        }
+        if !object.fields.is_empty() {
+            result.push_str("<ul>");
+            for field in &object.fields {
+                result.push_str(&format!(
+                    "<li><code>{}: {}</code></li>",
+                    escape(&field.name),
+                    self.link_type(&field.attr_type.rigz_type)
+                ));
+            }
+            result.push_str("</ul>");
+        }
+        result.push_str(&self.declarations(href, &object.functions));
+        result.push_str("</section>\n");
+        result
+    }
+
+    fn file(
+        &mut self,
+        file: &SourceFile,
+        program: Result<&Program, &String>,
+        files: &[SourceFile],
+    ) {
+        let href = file.page.clone();
+        let mut body = format!("<h1>{}</h1>\n", escape(&file.name));
+        let (program, contents) = match (program, &file.contents) {
+            (Ok(p), Ok(c)) => (p, c),
+            (Err(e), _) => {
+                body.push_str(&format!(r#"<pre class="error">{}</pre>"#, escape(e)));
+                self.site.pages.insert(href, page(&file.name, &body));
+                return;
+            }
+            (_, Err(e)) => {
+                body.push_str(&format!(r#"<pre class="error">{}</pre>"#, escape(e)));
+                self.site.pages.insert(href, page(&file.name, &body));
+                return;
+            }
+        };
+
+        let mut imports = Vec::new();
+        let mut sections = String::new();
+        for (index, element) in program.elements.iter().enumerate() {
+            let doc = program
+                .lines
+                .get(index)
+                .map(|l| doc_comment(contents, *l))
+                .unwrap_or_default();
+            let Element::Statement(statement) = element else {
+                continue;
+            };
+            match statement {
+                Statement::Import(ImportValue::TypeValue(module)) => {
+                    let link = match self.links.get(module) {
+                        None => escape(module),
+                        Some(href) => format!(r#"<a href="{href}">{}</a>"#, escape(module)),
+                    };
+                    imports.push(link);
+                }
+                Statement::Import(ImportValue::FilePath(path)) => {
+                    let target = file
+                        .path
+                        .parent()
+                        .map(|p| p.join(path))
+                        .unwrap_or_else(|| PathBuf::from(path));
+                    let link = files
+                        .iter()
+                        .find(|f| f.path == target || f.path.ends_with(path))
+                        .map(|f| format!(r#"<a href="{}">{}</a>"#, f.page, escape(path)))
+                        .unwrap_or_else(|| escape(path));
+                    imports.push(link);
+                }
+                Statement::Import(ImportValue::UrlPath(url)) => {
+                    imports.push(format!(r#"<a href="{0}">{0}</a>"#, escape(url)));
+                }
+                Statement::FunctionDefinition(f) => {
+                    let lifecycle = match &f.lifecycle {
+                        Some(Lifecycle::Test(_)) => continue,
+                        Some(Lifecycle::On(e)) => {
+                            format!(r#"<small>@on("{}")</small> "#, escape(&e.event))
+                        }
+                        Some(Lifecycle::Memo(_)) => "<small>@memo</small> ".to_string(),
+                        _ => String::new(),
+                    };
+                    sections.push_str(&lifecycle);
+                    sections.push_str(&self.function(&href, &f.name, &f.type_definition, doc));
+                }
+                Statement::Trait(t) => {
+                    self.entries.push(SearchEntry {
+                        name: t.name.clone(),
+                        kind: "trait",
+                        href: format!("{href}#{}", slug(&format!("trait-{}", t.name))),
+                    });
+                    sections.push_str(&format!(
+                        r#"<section id="{}"><h2>trait {}</h2>"#,
+                        slug(&format!("trait-{}", t.name)),
+                        escape(&t.name)
+                    ));
+                    if !doc.is_empty() {
+                        sections.push_str(&format!("<pre>{}</pre>", escape(&doc)));
+                    }
+                    sections.push_str(&self.declarations(&href, &t.functions));
+                    sections.push_str("</section>\n");
+                }
+                Statement::TraitImpl {
+                    base_trait,
+                    concrete,
+                    definitions,
+                } => {
+                    sections.push_str(&format!(
+                        "<section><h2>impl {} for {}</h2>",
+                        self.link_type(base_trait),
+                        self.link_type(concrete)
+                    ));
+                    for d in definitions {
+                        sections.push_str(&self.function(
+                            &href,
+                            &d.name,
+                            &d.type_definition,
+                            String::new(),
+                        ));
+                    }
+                    sections.push_str("</section>\n");
+                }
+                Statement::ObjectDefinition(o) => {
+                    sections.push_str(&self.object(&href, o, doc));
+                }
+                Statement::TypeDefinition(name, rigz_type) => {
+                    sections.push_str(&format!(
+                        r#"<section id="{}"><code>type {} = {}</code>"#,
+                        slug(&format!("type-{name}")),
+                        escape(name),
+                        self.link_type(rigz_type)
+                    ));
+                    if !doc.is_empty() {
+                        sections.push_str(&format!("<pre>{}</pre>", escape(&doc)));
+                    }
+                    sections.push_str("</section>\n");
+                }
+                Statement::Macro(m) => {
+                    let id = slug(&format!("macro-{}", m.name));
+                    self.entries.push(SearchEntry {
+                        name: m.name.clone(),
+                        kind: "macro",
+                        href: format!("{href}#{id}"),
+                    });
+                    sections.push_str(&format!(
+                        r#"<section id="{id}"><code>macro {}({})</code>"#,
+                        escape(&m.name),
+                        escape(&m.arguments.join(", "))
+                    ));
+                    if !doc.is_empty() {
+                        sections.push_str(&format!("<pre>{}</pre>", escape(&doc)));
+                    }
+                    sections.push_str("</section>\n");
+                }
+                _ => {}
+            }
+        }
+        if !imports.is_empty() {
+            body.push_str(&format!("<p>Imports {}</p>\n", imports.join(", ")));
+        }
+        if sections.is_empty() {
+            body.push_str("<p><small>No definitions</small></p>\n");
+        }
+        body.push_str(&sections);
+        self.site.pages.insert(href, page(&file.name, &body));
+    }
+
+    /// Names that can be linked to, collected before any page is rendered
+    fn collect_links(
+        &mut self,
+        modules: &[&TraitDefinition],
+        files: &[SourceFile],
+        programs: &[Result<Program, String>],
+    ) {
+        for module in modules {
+            let href = format!("module-{}.html", slug(&module.name));
+            self.entries.push(SearchEntry {
+                name: module.name.clone(),
+                kind: "module",
+                href: href.clone(),
+            });
+            self.links.insert(module.name.clone(), href);
+        }
+        for (file, program) in files.iter().zip(programs) {
+            self.entries.push(SearchEntry {
+                name: file.name.clone(),
+                kind: "file",
+                href: file.page.clone(),
+            });
+            let Ok(program) = program else {
+                continue;
+            };
+            for element in &program.elements {
+                let Element::Statement(statement) = element else {
+                    continue;
+                };
+                match statement {
+                    Statement::Import(ImportValue::TypeValue(module)) => {
+                        self.imported_by
+                            .entry(module.clone())
+                            .or_default()
+                            .push((file.name.clone(), file.page.clone()));
+                    }
+                    Statement::ObjectDefinition(o) => {
+                        let name = o.rigz_type.to_string();
+                        let href = format!("{}#{}", file.page, slug(&format!("object-{name}")));
+                        self.entries.push(SearchEntry {
+                            name: name.clone(),
+                            kind: "object",
+                            href: href.clone(),
+                        });
+                        self.links.insert(name, href);
+                    }
+                    Statement::TypeDefinition(name, _) => {
+                        let href = format!("{}#{}", file.page, slug(&format!("type-{name}")));
+                        self.entries.push(SearchEntry {
+                            name: name.clone(),
+                            kind: "type",
+                            href: href.clone(),
+                        });
+                        self.links.insert(name.clone(), href);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn index(&mut self, modules: &[&TraitDefinition], files: &[SourceFile]) {
+        let mut body = "<h1>Documentation</h1>\n<h2>Files</h2>\n<ul>\n".to_string();
+        for file in files {
+            body.push_str(&format!(
+                r#"<li><a href="{}">{}</a></li>"#,
+                file.page,
+                escape(&file.name)
+            ));
+        }
+        body.push_str("</ul>\n<h2>Modules</h2>\n<ul>\n");
+        for module in modules {
+            body.push_str(&format!(
+                r#"<li><a href="module-{}.html">{}</a></li>"#,
+                slug(&module.name),
+                escape(&module.name)
+            ));
+        }
+        body.push_str("</ul>\n");
+        self.site
+            .pages
+            .insert("index.html".to_string(), page("Documentation", &body));
+    }
+
+    fn search(&mut self) {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "name": e.name,
+                    "kind": e.kind,
+                    "href": e.href,
+                })
+            })
+            .collect();
+        let entries = serde_json::to_string(&entries).expect("failed to serialize search entries");
+        self.site.pages.insert(
+            "search.js".to_string(),
+            format!("const ENTRIES = {entries};\n{SEARCH}"),
+        );
+    }
+}
+
+fn source_files(input: &PathBuf) -> Vec<SourceFile> {
+    let mut paths = read_rigz_files(input).unwrap_or_else(|e| {
+        eprintln!("Failed to read {} - {e}", input.display());
+        exit(1)
+    });
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .strip_prefix(input)
+                .ok()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(|p| p.to_path_buf())
+                .unwrap_or_else(|| path.file_name().map(PathBuf::from).unwrap_or_default());
+            let name = path_to_string(&name);
+            let contents =
+                read_to_string(&path).map_err(|e| format!("Failed to read {name} - {e}"));
+            SourceFile {
+                page: format!("file-{}.html", slug(&name)),
+                name,
+                path,
+                contents,
+            }
+        })
+        .collect()
+}
+
+fn build(input: &PathBuf) -> Site {
+    let runtime = Runtime::new();
+    let mut modules: Vec<_> = runtime
+        .module_definitions()
+        .into_iter()
+        .map(|m| &m.definition)
+        .collect();
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let files = source_files(input);
+    let programs: Vec<Result<Program, String>> = files
+        .iter()
+        .map(|f| {
+            let contents = f.contents.as_ref()?;
+            let parser_options = ParserOptions {
+                current_directory: f.path.parent().map(Path::to_path_buf),
+                ..Default::default()
+            };
+            rigz_ast::parse(contents, parser_options)
+                .map_err(|e| e.to_diagnostic().render(&f.name, Some(contents)))
+        })
+        .collect();
+
+    let mut builder = SiteBuilder::new();
+    builder.collect_links(&modules, &files, &programs);
+    for module in &modules {
+        builder.module(module);
+    }
+    for (file, program) in files.iter().zip(&programs) {
+        builder.file(file, program.as_ref(), &files);
+    }
+    builder.index(&modules, &files);
+    builder.search();
+    builder.site
+}
+
+/// Modification times of every documented file, any change triggers a rebuild
+fn fingerprint(input: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files: Vec<_> = read_rigz_files(input)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|f| {
+            let modified = f.metadata().and_then(|m| m.modified()).ok();
+            (f, modified)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Pages poll the version and reload once it changes
+fn reload_script(version: u64) -> String {
+    format!(
+        r#"<script>
+setInterval(async () => {{
+  try {{
+    const response = await fetch('/__version');
+    if ((await response.text()) !== '{version}') location.reload();
+  }} catch (e) {{}}
+}}, 1000);
+</script>
+</body>"#
+    )
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn handle(mut stream: TcpStream, site: &RwLock<(u64, Site)>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // headers aren't used, they're read so the client doesn't see a reset connection
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"Bad Request");
+    };
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Method Not Allowed",
+        );
+    }
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = match path.trim_start_matches('/') {
+        "" => "index.html",
+        p => p,
+    };
+
+    let site = site.read().unwrap_or_else(|e| e.into_inner());
+    let (version, site) = (site.0, &site.1);
+    if path == "__version" {
+        return respond(
+            &mut stream,
+            "200 OK",
+            "text/plain",
+            version.to_string().as_bytes(),
+        );
+    }
+    match site.pages.get(path) {
+        None => respond(&mut stream, "404 Not Found", "text/plain", b"Not Found"),
+        Some(contents) if path.ends_with(".js") => respond(
+            &mut stream,
+            "200 OK",
+            "text/javascript",
+            contents.as_bytes(),
+        ),
+        Some(contents) => {
+            let contents = contents.replacen("</body>", &reload_script(version), 1);
+            respond(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                contents.as_bytes(),
+            )
+        }
+    }
+}
+
+fn serve(input: PathBuf, port: u16) {
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on port {port} - {e}");
+        exit(1)
+    });
+    let site = Arc::new(RwLock::new((0, build(&input))));
+
+    let watched = site.clone();
+    thread::spawn(move || {
+        let mut last = fingerprint(&input);
+        loop {
+            thread::sleep(Duration::from_millis(500));
+            let next = fingerprint(&input);
+            if next == last {
+                continue;
+            }
+            last = next;
+            let rebuilt = build(&input);
+            let mut site = watched.write().unwrap_or_else(|e| e.into_inner());
+            *site = (site.0 + 1, rebuilt);
+            println!("Documentation rebuilt");
+        }
+    });
+
+    println!("Serving documentation at http://127.0.0.1:{port}");
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to accept connection - {e}");
+                continue;
+            }
+        };
+        let site = site.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &site) {
+                eprintln!("Failed to respond - {e}");
+            }
+        });
+    }
+}
+
+pub(crate) fn docs(args: DocsArgs) {
+    let input = args.input.unwrap_or_else(current_dir);
+    if args.serve {
+        serve(input, args.port);
+        return;
+    }
+
+    let site = build(&input);
+    if let Err(e) = create_dir_all(&args.out) {
+        eprintln!("Failed to create {} - {e}", args.out.display());
+        exit(1)
+    }
+    for (name, contents) in &site.pages {
+        if let Err(e) = write(args.out.join(name), contents) {
+            eprintln!("Failed to write {name} - {e}");
+            exit(1)
+        }
+    }
+    println!(
+        "Documentation written to {}",
+        args.out.join("index.html").display()
+    );
+}
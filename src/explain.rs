@@ -0,0 +1,29 @@
+use clap::Args;
+use rigz_core::ErrorCode;
+use std::process::exit;
+
+#[derive(Args)]
+pub struct ExplainArgs {
+    #[arg(help = "Error code, i.e. E0102, lists all codes when omitted")]
+    code: Option<String>,
+}
+
+pub(crate) fn explain(args: ExplainArgs) {
+    let Some(code) = args.code else {
+        for code in ErrorCode::ALL {
+            println!("{code}: {}", code.summary());
+        }
+        return;
+    };
+
+    match code.parse::<ErrorCode>() {
+        Ok(code) => {
+            println!("{code}: {}\n", code.summary());
+            println!("{}", code.explanation());
+        }
+        Err(e) => {
+            eprintln!("{e}, run `rigz explain` to list all codes");
+            exit(1)
+        }
+    }
+}
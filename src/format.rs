@@ -1,35 +1,32 @@
 use crate::utils::{current_dir, path_to_string, read_rigz_files};
 use clap::Args;
-use std::fs::read_to_string;
-use std::fs::File;
-use std::io::Write;
+use rigz_ast::{FormatOptions, LineEnding};
+use std::fs::{read_to_string, write};
 use std::path::PathBuf;
 
 #[derive(Args)]
 pub struct FormatArgs {
     #[arg(help = "Formatter Entrypoint, defaults to current directory")]
     input: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "auto",
+        help = "Line endings to write: auto (keep CRLF if the file uses it), lf, or crlf"
+    )]
+    line_ending: LineEnding,
 }
 
 pub(crate) fn format(args: FormatArgs) {
     let input = args.input.unwrap_or_else(current_dir);
     let files = read_rigz_files(&input).expect("failed to read input files");
+    let options = FormatOptions {
+        line_ending: args.line_ending,
+    };
     for file in files {
         match read_to_string(&file) {
             Ok(input) => {
-                let formatted = rigz_ast::format(input);
-                let mut output = match File::open(&file) {
-                    Ok(output) => output,
-                    Err(e) => {
-                        eprintln!(
-                            "Failed to open {} for writing - {}",
-                            path_to_string(&file),
-                            e
-                        );
-                        continue;
-                    }
-                };
-                if let Err(e) = output.write_all(formatted.as_bytes()) {
+                let formatted = rigz_ast::format_with_options(input, options);
+                if let Err(e) = write(&file, formatted.as_bytes()) {
                     eprintln!(
                         "Failed to write formatted value to {} - {}",
                         path_to_string(&file),
@@ -0,0 +1,118 @@
+use crate::utils::{current_dir, path_to_string, read_rigz_files};
+use clap::Args;
+use rigz_ast::{EventTopics, LintConfig, LintLevel, ParserOptions, ValidationError};
+use rigz_runtime::Runtime;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+#[derive(Args)]
+pub struct LintArgs {
+    #[arg(help = "Lint Entrypoint, defaults to current directory")]
+    input: Option<PathBuf>,
+    #[arg(short = 'A', long, help = "Ignore lint rule, can be repeated")]
+    allow: Vec<String>,
+    #[arg(
+        short = 'W',
+        long,
+        help = "Report lint rule as a warning, can be repeated"
+    )]
+    warn: Vec<String>,
+    #[arg(short = 'D', long, help = "Fail on lint rule, can be repeated")]
+    deny: Vec<String>,
+}
+
+impl LintArgs {
+    /// Flags take precedence over the project's .rigzlint file
+    fn config(&self, directory: &Path) -> Result<LintConfig, ValidationError> {
+        let mut config = LintConfig::find(directory)?.unwrap_or_default();
+        let mut flags = LintConfig::default();
+        for (rules, level) in [
+            (&self.allow, LintLevel::Allow),
+            (&self.warn, LintLevel::Warn),
+            (&self.deny, LintLevel::Deny),
+        ] {
+            for rule in rules {
+                flags.set(rule, level)?;
+            }
+        }
+        config.merge(flags);
+        Ok(config)
+    }
+}
+
+pub(crate) fn lint(args: LintArgs) {
+    let input = args.input.clone().unwrap_or_else(current_dir);
+    let files = read_rigz_files(&input).expect("failed to read input files");
+    let mut failed = false;
+    let mut topics = EventTopics::default();
+    // unused module imports are found using the functions each module defines
+    let runtime = Runtime::new();
+    let modules = runtime.module_definitions();
+    for file in files {
+        let name = path_to_string(&file);
+        let contents = match read_to_string(&file) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Failed to open {name} - {e}");
+                failed = true;
+                continue;
+            }
+        };
+        let directory = file.parent().map(|p| p.to_path_buf());
+        let config = match args.config(directory.as_deref().unwrap_or(Path::new("."))) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{e}");
+                exit(1)
+            }
+        };
+        let parser_options = ParserOptions {
+            current_directory: directory,
+            ..Default::default()
+        };
+        match rigz_ast::parse(&contents, parser_options) {
+            Ok(mut program) => {
+                topics.merge(program.event_topics());
+                match config.check(program.lint_with_modules(&modules)) {
+                    Ok(warnings) => {
+                        for warning in warnings {
+                            println!("{name}: {warning}");
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{name}: {e}");
+                        failed = true;
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                failed = true;
+            }
+        }
+    }
+    // handlers & sends of every file are matched against each other
+    let directory = if input.is_dir() {
+        input.as_path()
+    } else {
+        input.parent().unwrap_or(Path::new("."))
+    };
+    match args
+        .config(directory)
+        .and_then(|config| config.check(topics.check()))
+    {
+        Ok(warnings) => {
+            for warning in warnings {
+                println!("{}: {warning}", path_to_string(&input));
+            }
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", path_to_string(&input));
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1)
+    }
+}
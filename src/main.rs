@@ -1,13 +1,23 @@
 mod ast;
+mod bundle;
+mod compile;
 mod debug;
+mod docs;
+mod explain;
 mod format;
+mod lint;
 mod repl;
 mod run;
 mod test;
 mod utils;
 
 use crate::ast::{ast, AstArgs};
+use crate::bundle::{bundle, BundleArgs};
+use crate::compile::{compile, CompileArgs};
+use crate::docs::{docs, DocsArgs};
+use crate::explain::{explain, ExplainArgs};
 use crate::format::{format, FormatArgs};
+use crate::lint::{lint, LintArgs};
 use crate::repl::ReplArgs;
 use crate::run::RunArgs;
 use crate::test::TestArgs;
@@ -37,10 +47,15 @@ pub struct CLI {
 pub enum Commands {
     Ast(AstArgs),
     Run(RunArgs),
+    Compile(CompileArgs),
+    Bundle(BundleArgs),
     Repl(ReplArgs),
     Fmt(FormatArgs),
+    Lint(LintArgs),
     // Debug(DebugArgs),
     Test(TestArgs),
+    Docs(DocsArgs),
+    Explain(ExplainArgs),
     // todo add a Lock command that verifies or writes a checksum of all URLs (and eventually packages)
     // todo add an Update command that gets latest checksum of all URLs (and eventually packages)
 }
@@ -72,10 +87,15 @@ fn main() {
             match c {
                 Commands::Ast(args) => ast(args),
                 Commands::Run(args) => run(args),
+                Commands::Compile(args) => compile(args),
+                Commands::Bundle(args) => bundle(args),
                 Commands::Repl(args) => repl(args),
                 Commands::Test(args) => test(args),
                 // Commands::Debug(args) => debug(args),
                 Commands::Fmt(args) => format(args),
+                Commands::Lint(args) => lint(args),
+                Commands::Docs(args) => docs(args),
+                Commands::Explain(args) => explain(args),
             }
         }
     }
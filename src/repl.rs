@@ -1,5 +1,5 @@
 use clap::Args;
-use rigz_core::{ObjectValue, VMError};
+use rigz_core::{enter_context, pretty, FloatFormat, ObjectValue, PrettyOptions, VMError};
 use rigz_runtime::{Runtime, RuntimeError};
 use rustyline::completion::Completer;
 use rustyline::hint::Hinter;
@@ -14,6 +14,13 @@ use tree_sitter_highlight::{Highlight, HighlightConfiguration, HighlightEvent, H
 pub struct ReplArgs {
     #[arg(short, long, default_value = "false", help = "Save History on exit")]
     save_history: bool,
+    #[arg(
+        long,
+        help = "Significant digits used to print floats, defaults to the shortest exact representation"
+    )]
+    float_precision: Option<usize>,
+    #[arg(long, help = "Instructions each input may run before it is stopped")]
+    max_instructions: Option<usize>,
 }
 
 static NAMES: [&str; 10] = [
@@ -82,6 +89,15 @@ pub(crate) fn repl(args: ReplArgs) {
     };
 
     let mut runtime = Runtime::new();
+    runtime.vm_mut().options.max_instructions = args.max_instructions;
+    // results are printed outside of the VM, they use its float format
+    let _context = enter_context(runtime.vm().context.clone());
+    if let Some(precision) = args.float_precision {
+        runtime
+            .vm()
+            .context
+            .set_float_format(FloatFormat::with_precision(precision));
+    }
     let mut r = Editor::new().expect("Failed to create REPL");
     r.set_helper(Some(&rigz_helper));
 
@@ -115,13 +131,15 @@ pub(crate) fn repl(args: ReplArgs) {
             }
             "" => continue,
             next => {
+                runtime.refill_fuel();
                 // currently eval will convert VMError into a runtime error
                 match runtime.eval(next.to_string()) {
                     Ok(v) => {
                         highlight_value(&mut highlighter, &rigz_config, v);
                     }
                     Err(RuntimeError::Parse(p)) => {
-                        eprintln!("\x1b[31mInvalid Input {p:?}\x1b[0m");
+                        let diagnostic = p.to_diagnostic().render("<repl>", Some(next));
+                        eprintln!("\x1b[31m{diagnostic}\x1b[0m");
                     }
                     Err(RuntimeError::Validation(p)) => {
                         eprintln!("\x1b[31mValidation Failed {p:?}\x1b[0m");
@@ -150,7 +168,11 @@ fn highlight_value(
     value: ObjectValue,
 ) {
     print!("=> ");
-    let r = highlight(highlighter, rigz_config, value.to_string().as_bytes());
+    let r = highlight(
+        highlighter,
+        rigz_config,
+        pretty(&value, &PrettyOptions::default()).as_bytes(),
+    );
     println!("{r}")
 }
 
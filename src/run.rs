@@ -1,39 +1,290 @@
-use clap::Args;
-use rigz_runtime::eval;
-use rigz_runtime::runtime::eval_print_vm;
-use std::fs::File;
+use clap::{Args, ValueEnum};
+use rigz_core::{current_context, pretty, ObjectValue, PrettyOptions};
+use rigz_runtime::{load_env_files, Runtime, RuntimeError};
+use rigz_vm::{changed_scopes, ReloadHandle, Scope};
+use std::fs::{read, read_to_string, write, File};
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
 
 #[derive(Args)]
 pub struct RunArgs {
-    #[arg(help = "Rigz Entrypoint")]
+    #[arg(help = "Rigz Entrypoint, .rzbc files created by `rigz compile` skip parsing")]
     main: PathBuf,
     #[arg(short, long, default_value = "false", help = "Show output from eval")]
     show_output: bool,
     #[arg(short, long, default_value = "false", help = "Print VM before run")]
     print_vm: bool,
+    #[arg(
+        long,
+        help = "Load environment variables from a .env file before running, can be repeated, later files take precedence"
+    )]
+    env_file: Vec<PathBuf>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Values from --env-file replace variables that are already set"
+    )]
+    env_override: bool,
+    #[arg(long, help = "Stop the program after running this many instructions")]
+    max_instructions: Option<usize>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Profile the program, the report is printed to stderr after it runs"
+    )]
+    profile: Option<ProfileFormat>,
+    #[arg(
+        long,
+        requires = "profile",
+        help = "Write the profile to this file instead of stderr"
+    )]
+    profile_output: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Seed randomness, use a virtual clock, & reject the Http, File, and Env modules so runs can be replayed"
+    )]
+    deterministic: bool,
+    #[arg(
+        long,
+        requires = "deterministic",
+        default_value = "0",
+        help = "Seed used by --deterministic"
+    )]
+    seed: u64,
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "text",
+        help = "Print the time spent lexing, parsing, validating, preparing, & running each file to stderr after the program runs"
+    )]
+    timings: Option<TimingsFormat>,
+    #[cfg(feature = "jit")]
+    #[arg(
+        long,
+        help = "Compile functions to native code after they're called this many times"
+    )]
+    jit: Option<usize>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Reload changed functions while the program runs & run it again once main changes, imports aren't watched"
+    )]
+    watch: bool,
 }
 
-pub(crate) fn run(args: RunArgs) {
-    let mut file = File::open(args.main).expect("Failed to open main");
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ProfileFormat {
+    /// Time & call counts per scope and instruction
+    Flat,
+    /// Collapsed stacks, used by flamegraph.pl, inferno, & speedscope
+    Folded,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TimingsFormat {
+    /// Table of phases per file
+    Text,
+    /// Single line of JSON with every phase in nanoseconds
+    Json,
+}
+
+fn create_runtime(args: &RunArgs) -> Result<Runtime<'static>, RuntimeError> {
+    if args.main.extension().is_some_and(|e| e == "rzbc") {
+        let bytes = read(&args.main).expect("Failed to read main");
+        return Runtime::from_bytecode(bytes);
+    }
+    let mut file = File::open(&args.main).expect("Failed to open main");
     let mut contents = String::new();
     file.read_to_string(&mut contents)
         .expect("Failed to read main");
-    let v = if args.print_vm {
-        eval_print_vm(contents)
-    } else {
-        eval(contents)
-    };
-    match v {
+    Runtime::create(contents)
+}
+
+/// `before_run` receives the runtime once it's ready, i.e. to reload it while it runs
+fn run_main(
+    args: &RunArgs,
+    before_run: impl FnOnce(&Runtime),
+) -> Result<ObjectValue, RuntimeError> {
+    load_env_files(&args.env_file, args.env_override).map_err(RuntimeError::Run)?;
+    let mut runtime = create_runtime(args)?;
+    if args.print_vm {
+        println!("VM (before) - {:#?}", runtime.vm());
+    }
+    runtime.vm_mut().options.enable_traceback = true;
+    runtime.vm_mut().options.max_instructions = args.max_instructions;
+    runtime.vm_mut().options.profile = args.profile.is_some();
+    runtime.vm_mut().options.deterministic = args.deterministic;
+    runtime.vm_mut().options.seed = args.seed;
+    #[cfg(feature = "jit")]
+    if let Some(threshold) = args.jit {
+        runtime.vm_mut().enable_jit(threshold);
+    }
+    // the result is printed after the runtime is dropped, it uses the format set by the program
+    runtime.vm_mut().context = current_context();
+    before_run(&runtime);
+    let result = runtime.run();
+    if let Some(format) = args.profile {
+        let report = runtime.vm().profile_report();
+        let report = match format {
+            ProfileFormat::Flat => report.to_flat(),
+            ProfileFormat::Folded => report.to_folded(),
+        };
+        match &args.profile_output {
+            None => eprint!("{report}"),
+            Some(path) => {
+                if let Err(e) = write(path, report) {
+                    eprintln!("Failed to write profile to {} - {e}", path.display());
+                }
+            }
+        }
+    }
+    if let Some(format) = args.timings {
+        let main = args.main.display().to_string();
+        let timings = runtime.timings();
+        match format {
+            TimingsFormat::Text => eprint!("{}", timings.to_text(&main)),
+            TimingsFormat::Json => eprintln!("{}", timings.to_json(&main)),
+        }
+    }
+    // the program was stopped by a signal after its `@on("signal:NAME")` handlers finished
+    if let Some(signal) = runtime.vm().received_signal() {
+        exit(signal.exit_code())
+    }
+    if let Err(RuntimeError::Run(e)) = &result {
+        let line = e.traceback().iter().find_map(|f| f.line);
+        if let Some(line) = line.or_else(|| runtime.vm().line()) {
+            eprint!("{}:{line} - ", args.main.display());
+        }
+    }
+    result
+}
+
+/// Prints errors & the output when `--show-output` is set, returns false for errors
+fn report(args: &RunArgs, result: Result<ObjectValue, RuntimeError>) -> bool {
+    match result {
+        Err(RuntimeError::Run(e)) => {
+            eprintln!("VM Run Failed [{}]: {}", e.code(), e.pretty_traceback());
+            eprintln!(
+                "For more information about this error, try `rigz explain {}`",
+                e.code()
+            );
+            false
+        }
+        Err(RuntimeError::Parse(e)) => {
+            let source = read_to_string(&args.main).ok();
+            let file = args.main.display().to_string();
+            let diagnostic = e.to_diagnostic();
+            eprintln!("{}", diagnostic.render(&file, source.as_deref()));
+            eprintln!(
+                "For more information about this error, try `rigz explain {}`",
+                diagnostic.code
+            );
+            false
+        }
         Err(e) => {
             eprintln!("VM Run Failed: {:?}", e);
-            exit(1)
+            false
+        }
+        Ok(v) => {
+            if args.show_output {
+                println!("{}", pretty(&v, &PrettyOptions::default()))
+            }
+            true
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+/// Polls `main` while the program runs, functions that changed are replaced in the running VM.
+/// Returns the last modification time that was loaded once `done` is set
+fn reload_changes(
+    main: PathBuf,
+    mut scopes: Vec<Scope>,
+    handle: ReloadHandle,
+    done: Arc<AtomicBool>,
+) -> JoinHandle<Option<SystemTime>> {
+    thread::spawn(move || {
+        let mut last = modified(&main);
+        while !done.load(Ordering::Acquire) {
+            thread::sleep(Duration::from_millis(500));
+            let next = modified(&main);
+            if next == last {
+                continue;
+            }
+            let runtime = match read_to_string(&main).map(Runtime::create) {
+                Ok(Ok(runtime)) => runtime,
+                Ok(Err(e)) => {
+                    eprintln!("Failed to reload {} - {e}", main.display());
+                    last = next;
+                    continue;
+                }
+                // the file may be partially written, try again
+                Err(_) => continue,
+            };
+            last = next;
+            let next_scopes = &runtime.vm().scopes;
+            match changed_scopes(&scopes, next_scopes) {
+                Ok(changed) if changed.is_empty() => {}
+                Ok(changed) => {
+                    let names: Vec<_> = changed.iter().map(|(_, s)| s.named.as_str()).collect();
+                    eprintln!("Reloaded {}", names.join(", "));
+                    handle.reload(changed);
+                    scopes = next_scopes.clone();
+                }
+                Err(e) => {
+                    eprintln!("{e}, {} will run again once it finishes", main.display());
+                    // run again after the program finishes
+                    return None;
+                }
+            }
         }
-        Ok(v) if args.show_output => {
-            println!("{v}")
+        last
+    })
+}
+
+/// Runs the program until it's stopped, changed functions are reloaded while it runs
+fn watch(args: &RunArgs) -> ! {
+    if args.main.extension().is_some_and(|e| e == "rzbc") {
+        eprintln!("--watch requires a .rg file, bytecode can't be reloaded");
+        exit(1)
+    }
+    loop {
+        let done = Arc::new(AtomicBool::new(false));
+        let mut watcher = None;
+        let result = run_main(args, |runtime| {
+            watcher = Some(reload_changes(
+                args.main.clone(),
+                runtime.vm().scopes.clone(),
+                runtime.vm().reload_handle(),
+                done.clone(),
+            ));
+        });
+        report(args, result);
+        done.store(true, Ordering::Release);
+        let last = watcher.and_then(|w| w.join().ok()).flatten();
+        while last.is_some() && modified(&args.main) == last {
+            thread::sleep(Duration::from_millis(500));
         }
-        Ok(_) => {}
+        eprintln!("Running {}", args.main.display());
+    }
+}
+
+pub(crate) fn run(args: RunArgs) {
+    if args.watch {
+        watch(&args)
+    }
+    if !report(&args, run_main(&args, |_| {})) {
+        exit(1)
     }
 }
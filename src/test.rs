@@ -1,9 +1,9 @@
 use crate::utils::{current_dir, path_to_string, read_rigz_files};
-use clap::Args;
-use rigz_ast::ParserOptions;
+use clap::{Args, ValueEnum};
+use rigz_ast::{parse, ParserOptions};
 use rigz_core::{Lifecycle, TestResults, VMError};
 use rigz_runtime::Runtime;
-use std::fs::read_to_string;
+use std::fs::{read_to_string, write};
 use std::path::PathBuf;
 use std::process::exit;
 
@@ -11,13 +11,63 @@ use std::process::exit;
 pub struct TestArgs {
     #[arg(help = "Test Entrypoint, defaults to current directory")]
     input: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        default_value = "false",
+        help = "List tests without running them"
+    )]
+    list: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format for --list"
+    )]
+    format: ListFormat,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Record which lines the tests run and write an lcov report"
+    )]
+    coverage: bool,
+    #[arg(
+        long,
+        requires = "coverage",
+        default_value = "lcov.info",
+        help = "Where the lcov report is written"
+    )]
+    coverage_output: PathBuf,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Seed randomness, use a virtual clock, & reject the Http, File, and Env modules so runs can be replayed"
+    )]
+    deterministic: bool,
+    #[arg(
+        long,
+        requires = "deterministic",
+        default_value = "0",
+        help = "Seed used by --deterministic"
+    )]
+    seed: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ListFormat {
+    Text,
+    Json,
 }
 
 pub(crate) fn test(args: TestArgs) {
+    if args.list {
+        return list(args);
+    }
     let input = args.input.unwrap_or_else(current_dir);
     let test_files = read_rigz_files(&input).expect("Failed to open test files");
     // # of tests
     let mut total = TestResults::default();
+    let mut lcov = String::new();
     for file in test_files {
         let pb = file.parent().expect("Absolute path expected").to_path_buf();
         let parser_options = ParserOptions {
@@ -38,9 +88,17 @@ pub(crate) fn test(args: TestArgs) {
                             continue;
                         }
                         println!("Running {}", path_to_string(&file));
+                        r.vm_mut().options.coverage = args.coverage;
+                        r.vm_mut().options.deterministic = args.deterministic;
+                        r.vm_mut().options.seed = args.seed;
                         let results = r.test();
                         total += results.clone();
-                        println!("{results}")
+                        println!("{results}");
+                        if args.coverage {
+                            let report = r.vm().coverage_report();
+                            println!("coverage: {:.2}% of lines", report.line_rate());
+                            lcov.push_str(&report.to_lcov(&path_to_string(&file)));
+                        }
                     }
                     Err(e) => {
                         total.failure_messages.push((
@@ -54,7 +112,90 @@ pub(crate) fn test(args: TestArgs) {
         }
     }
     println!("{total}");
+    if args.coverage {
+        match write(&args.coverage_output, lcov) {
+            Ok(_) => println!(
+                "Wrote coverage to {}",
+                path_to_string(&args.coverage_output)
+            ),
+            Err(e) => eprintln!(
+                "Failed to write coverage to {} - {e}",
+                path_to_string(&args.coverage_output)
+            ),
+        }
+    }
     if !total.failure_messages.is_empty() {
         exit(1)
     }
 }
+
+/// Parses each file and lists its tests, files that fail to parse are reported on stderr
+fn list(args: TestArgs) {
+    let input = args.input.unwrap_or_else(current_dir);
+    let test_files = read_rigz_files(&input).expect("Failed to open test files");
+    let mut failed = false;
+    let mut inventory = vec![];
+    for file in test_files {
+        let name = path_to_string(&file);
+        let contents = match read_to_string(&file) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to open {name} - {e}");
+                failed = true;
+                continue;
+            }
+        };
+        let parser_options = ParserOptions {
+            current_directory: file.parent().map(|p| p.to_path_buf()),
+            ..Default::default()
+        };
+        let program = match parse(&contents, parser_options) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("{name} - {e}");
+                failed = true;
+                continue;
+            }
+        };
+        inventory.extend(
+            program
+                .test_inventory()
+                .into_iter()
+                .map(|t| (name.clone(), t)),
+        );
+    }
+
+    match args.format {
+        ListFormat::Text => {
+            for (file, test) in &inventory {
+                if test.tags.is_empty() {
+                    println!("{file}:{} {}", test.line, test.name);
+                } else {
+                    println!(
+                        "{file}:{} {} [{}]",
+                        test.line,
+                        test.name,
+                        test.tags.join(", ")
+                    );
+                }
+            }
+        }
+        ListFormat::Json => {
+            let tests: Vec<_> = inventory
+                .iter()
+                .map(|(file, test)| {
+                    serde_json::json!({
+                        "file": file,
+                        "line": test.line,
+                        "name": test.name,
+                        "tags": test.tags,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(tests));
+        }
+    }
+    if failed {
+        exit(1)
+    }
+}
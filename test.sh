@@ -4,6 +4,12 @@ set -e
 
 cargo test --workspace
 
+cargo check -p rigz_core --no-default-features --target thumbv7em-none-eabi
+cargo check -p rigz_vm --no-default-features --target thumbv7em-none-eabi
+
+cargo check -p rigz_runtime --no-default-features
+cargo check -p rigz_runtime --no-default-features --features js
+
 wasm-pack test --node -p rigz_ast -p rigz_vm -p rigz_ast_derive -p rigz_runtime --features js --no-default-features
 
 ./rigz_test.sh
\ No newline at end of file